@@ -0,0 +1,36 @@
+use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// Records the commit, time and target of the build for `/api/version` and
+// `js-link self-update`.
+fn main() {
+    let git_sha = Command::new("git")
+        .args(["rev-parse", "--short=12", "HEAD"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .and_then(|output| String::from_utf8(output.stdout).ok())
+        .map(|sha| sha.trim().to_string())
+        .filter(|sha| !sha.is_empty())
+        .unwrap_or_else(|| "unknown".to_string());
+    // Reproducible builds pin the time through SOURCE_DATE_EPOCH
+    let build_epoch = std::env::var("SOURCE_DATE_EPOCH")
+        .ok()
+        .and_then(|v| v.parse::<u64>().ok())
+        .unwrap_or_else(|| {
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0)
+        });
+
+    println!("cargo:rustc-env=JSLINK_GIT_SHA={}", git_sha);
+    println!("cargo:rustc-env=JSLINK_BUILD_EPOCH={}", build_epoch);
+    println!(
+        "cargo:rustc-env=JSLINK_TARGET={}",
+        std::env::var("TARGET").unwrap_or_default()
+    );
+    println!("cargo:rerun-if-changed=.git/HEAD");
+    println!("cargo:rerun-if-changed=.git/refs/heads");
+    println!("cargo:rerun-if-env-changed=SOURCE_DATE_EPOCH");
+}
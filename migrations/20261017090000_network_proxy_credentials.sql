@@ -0,0 +1,4 @@
+-- Proxy authentication for manual proxy settings
+
+ALTER TABLE network_settings ADD COLUMN proxy_username TEXT;
+ALTER TABLE network_settings ADD COLUMN proxy_password TEXT; -- Encrypted, see crypto.rs
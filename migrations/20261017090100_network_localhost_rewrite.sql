@@ -0,0 +1,4 @@
+-- Rewrite of localhost targets when running inside a container
+
+ALTER TABLE network_settings ADD COLUMN localhost_rewrite TEXT NOT NULL DEFAULT 'never'; -- 'never', 'auto' (only when containerized), 'always'
+ALTER TABLE network_settings ADD COLUMN localhost_alias TEXT NOT NULL DEFAULT 'host.docker.internal';
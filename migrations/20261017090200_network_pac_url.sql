@@ -0,0 +1,3 @@
+-- Proxy auto-config script consulted when auto_proxy is enabled
+
+ALTER TABLE network_settings ADD COLUMN pac_url TEXT;
@@ -0,0 +1,45 @@
+-- Server-side execution history and its hourly/daily latency rollups
+
+CREATE TABLE execution_history (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    request_id INTEGER, -- NULL for direct executions
+    method TEXT NOT NULL,
+    url TEXT NOT NULL,
+    status INTEGER, -- NULL when no response was received
+    duration_ms INTEGER NOT NULL,
+    error TEXT,
+    executed_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+
+    FOREIGN KEY (request_id) REFERENCES requests (id) ON DELETE SET NULL
+);
+
+CREATE INDEX idx_execution_history_request ON execution_history (request_id, executed_at);
+
+-- hist_N count executions per latency band, see stats::HISTOGRAM_BOUNDS_MS
+CREATE TABLE latency_buckets (
+    request_id INTEGER NOT NULL,
+    granularity TEXT NOT NULL, -- 'hour' or 'day'
+    bucket_start TIMESTAMP NOT NULL,
+    count INTEGER NOT NULL,
+    error_count INTEGER NOT NULL,
+    total_ms INTEGER NOT NULL,
+    min_ms INTEGER NOT NULL,
+    max_ms INTEGER NOT NULL,
+    hist_0 INTEGER NOT NULL DEFAULT 0,
+    hist_1 INTEGER NOT NULL DEFAULT 0,
+    hist_2 INTEGER NOT NULL DEFAULT 0,
+    hist_3 INTEGER NOT NULL DEFAULT 0,
+    hist_4 INTEGER NOT NULL DEFAULT 0,
+    hist_5 INTEGER NOT NULL DEFAULT 0,
+    hist_6 INTEGER NOT NULL DEFAULT 0,
+    hist_7 INTEGER NOT NULL DEFAULT 0,
+
+    PRIMARY KEY (request_id, granularity, bucket_start),
+    FOREIGN KEY (request_id) REFERENCES requests (id) ON DELETE CASCADE
+);
+
+-- Progress markers for background jobs
+CREATE TABLE job_state (
+    name TEXT PRIMARY KEY,
+    value INTEGER NOT NULL
+);
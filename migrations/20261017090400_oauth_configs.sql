@@ -0,0 +1,33 @@
+-- OAuth 2.0 client settings attached to a request or a folder, plus the cached token
+
+CREATE TABLE oauth_configs (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    request_id INTEGER UNIQUE,
+    folder_id INTEGER UNIQUE,
+    grant_type TEXT NOT NULL, -- 'client_credentials' or 'authorization_code'
+    client_id TEXT NOT NULL,
+    client_secret TEXT, -- Encrypted
+    token_url TEXT NOT NULL,
+    authorization_url TEXT,
+    redirect_uri TEXT,
+    scope TEXT,
+    use_pkce BOOLEAN NOT NULL DEFAULT TRUE,
+
+    -- Cached token, valid for the settings it was issued for (token_key)
+    access_token TEXT, -- Encrypted
+    refresh_token TEXT, -- Encrypted
+    token_expires_at TIMESTAMP,
+    token_key TEXT,
+
+    -- In-flight authorization code flow
+    pending_state TEXT,
+    pending_verifier TEXT,
+    pending_environment_id INTEGER,
+
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+
+    CHECK ((request_id IS NULL) <> (folder_id IS NULL)),
+    FOREIGN KEY (request_id) REFERENCES requests (id) ON DELETE CASCADE,
+    FOREIGN KEY (folder_id) REFERENCES folders (id) ON DELETE CASCADE
+);
@@ -0,0 +1,3 @@
+-- Rules copying values from a request's response into environment variables
+
+ALTER TABLE requests ADD COLUMN captures TEXT; -- Stored as JSON
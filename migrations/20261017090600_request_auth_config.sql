@@ -0,0 +1,3 @@
+-- Settings of auth types beyond bearer and basic (e.g. the AWS region and service)
+
+ALTER TABLE requests ADD COLUMN auth_config TEXT; -- Stored as JSON
@@ -0,0 +1,3 @@
+-- Serialized requests never run concurrently; further executions wait their turn
+
+ALTER TABLE requests ADD COLUMN serialized BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1,7 @@
+-- Server-wide preferences set from the UI, such as the message language
+
+CREATE TABLE app_settings (
+    name TEXT PRIMARY KEY,
+    value TEXT NOT NULL,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
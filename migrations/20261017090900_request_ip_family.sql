@@ -0,0 +1,3 @@
+-- Restricts executions to IPv4 or IPv6, or pins them to one address
+
+ALTER TABLE requests ADD COLUMN ip_family TEXT NOT NULL DEFAULT 'auto';
@@ -0,0 +1,3 @@
+-- JavaScript run before each execution of the request
+
+ALTER TABLE requests ADD COLUMN pre_request_script TEXT;
@@ -0,0 +1,5 @@
+-- Test scripts run against each response, with their results kept in the history
+
+ALTER TABLE requests ADD COLUMN test_script TEXT;
+
+ALTER TABLE execution_history ADD COLUMN test_results TEXT; -- JSON, NULL without a test script
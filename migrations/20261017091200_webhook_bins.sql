@@ -0,0 +1,15 @@
+-- Webhook bins collecting deliveries from async APIs, and the callback settings
+-- that make an execution wait for its delivery
+
+CREATE TABLE webhook_deliveries (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    bin TEXT NOT NULL,
+    method TEXT NOT NULL,
+    headers TEXT NOT NULL, -- JSON object
+    body TEXT NOT NULL,
+    received_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX idx_webhook_deliveries_bin ON webhook_deliveries (bin, id);
+
+ALTER TABLE requests ADD COLUMN callback TEXT; -- JSON, see webhooks::CallbackConfig
@@ -0,0 +1,19 @@
+-- Collection runs over the requests of a folder, with their full report
+
+CREATE TABLE runs (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    folder_id INTEGER,
+    environment_id INTEGER,
+    status TEXT NOT NULL, -- 'passed', 'failed' or 'stopped'
+    total INTEGER NOT NULL,
+    passed INTEGER NOT NULL,
+    failed INTEGER NOT NULL,
+    report TEXT NOT NULL, -- JSON, see runner::RunReport
+    started_at TIMESTAMP NOT NULL,
+    finished_at TIMESTAMP NOT NULL,
+
+    FOREIGN KEY (folder_id) REFERENCES folders (id) ON DELETE SET NULL,
+    FOREIGN KEY (environment_id) REFERENCES environments (id) ON DELETE SET NULL
+);
+
+CREATE INDEX idx_runs_folder ON runs (folder_id, started_at);
@@ -0,0 +1,17 @@
+-- Saved comparisons running a folder against two environments side by side
+
+CREATE TABLE comparison_suites (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    name TEXT NOT NULL,
+    folder_id INTEGER NOT NULL,
+    left_environment_id INTEGER NOT NULL,
+    right_environment_id INTEGER NOT NULL,
+    ignore_paths TEXT NOT NULL DEFAULT '[]', -- JSON array of JSONPaths left out of body comparisons
+    last_report TEXT, -- JSON, see compare::ComparisonReport
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+
+    FOREIGN KEY (folder_id) REFERENCES folders (id) ON DELETE CASCADE,
+    FOREIGN KEY (left_environment_id) REFERENCES environments (id) ON DELETE CASCADE,
+    FOREIGN KEY (right_environment_id) REFERENCES environments (id) ON DELETE CASCADE
+);
@@ -0,0 +1,3 @@
+-- Variables flagged as secret are stored encrypted, see crypto::encrypt
+
+ALTER TABLE environments ADD COLUMN secrets TEXT NOT NULL DEFAULT '[]'; -- JSON array of variable names
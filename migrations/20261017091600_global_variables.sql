@@ -0,0 +1,7 @@
+-- Variables shared by all environments, which override them
+
+CREATE TABLE global_variables (
+    name TEXT PRIMARY KEY NOT NULL,
+    value TEXT NOT NULL,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
@@ -0,0 +1,3 @@
+-- Variables of a collection, between the globals and the environment
+
+ALTER TABLE folders ADD COLUMN variables TEXT NOT NULL DEFAULT '{}';
@@ -0,0 +1,3 @@
+-- Variables kept in an environment but left out of substitution
+
+ALTER TABLE environments ADD COLUMN disabled TEXT NOT NULL DEFAULT '[]'; -- JSON array of names
@@ -0,0 +1,3 @@
+-- An environment inherits the variables of its parent and overrides them
+
+ALTER TABLE environments ADD COLUMN parent_id INTEGER REFERENCES environments (id) ON DELETE SET NULL;
@@ -0,0 +1,4 @@
+-- What to do with placeholders no variable matches: 'error', 'warn' or
+-- 'keep'. NULL uses the global setting stored in app_settings.
+
+ALTER TABLE requests ADD COLUMN unresolved_variables TEXT;
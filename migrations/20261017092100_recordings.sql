@@ -0,0 +1,29 @@
+-- Recording proxy: requests sent to /record/:id are forwarded to the
+-- recording's target and each exchange is kept for turning into requests
+
+CREATE TABLE recordings (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    name TEXT NOT NULL,
+    target_url TEXT NOT NULL, -- base URL paths under /record/:id are appended to
+    recording BOOLEAN NOT NULL DEFAULT 1, -- paused recordings still forward
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE recorded_exchanges (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    recording_id INTEGER NOT NULL,
+    method TEXT NOT NULL,
+    url TEXT NOT NULL,
+    request_headers TEXT NOT NULL, -- JSON object
+    request_body TEXT NOT NULL,
+    status INTEGER, -- NULL when the target could not be reached
+    response_headers TEXT NOT NULL DEFAULT '{}', -- JSON object
+    response_body TEXT NOT NULL DEFAULT '',
+    error TEXT,
+    duration_ms INTEGER NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+
+    FOREIGN KEY (recording_id) REFERENCES recordings (id) ON DELETE CASCADE
+);
+
+CREATE INDEX idx_recorded_exchanges_recording ON recorded_exchanges (recording_id, id);
@@ -0,0 +1,16 @@
+-- Webhooks notified when a run of their folder fails
+
+CREATE TABLE run_alerts (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    folder_id INTEGER NOT NULL,
+    url TEXT NOT NULL,
+    format TEXT NOT NULL DEFAULT 'json', -- 'json', 'slack' or 'discord'
+    enabled BOOLEAN NOT NULL DEFAULT 1,
+    last_error TEXT, -- why the latest delivery failed, NULL after a success
+    last_sent_at TIMESTAMP,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+
+    FOREIGN KEY (folder_id) REFERENCES folders (id) ON DELETE CASCADE
+);
+
+CREATE INDEX idx_run_alerts_folder ON run_alerts (folder_id);
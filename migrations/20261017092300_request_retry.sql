@@ -0,0 +1,3 @@
+-- Retry policy of a request, JSON, see retry::RetryPolicy
+
+ALTER TABLE requests ADD COLUMN retry TEXT;
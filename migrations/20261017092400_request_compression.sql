@@ -0,0 +1,3 @@
+-- Response decompression of a request, JSON, see compression::CompressionSettings
+
+ALTER TABLE requests ADD COLUMN compression TEXT;
@@ -0,0 +1,5 @@
+-- Response bodies of recent executions, for GET /api/history/:id/download
+
+ALTER TABLE execution_history ADD COLUMN response_body BLOB;
+ALTER TABLE execution_history ADD COLUMN response_content_type TEXT;
+ALTER TABLE execution_history ADD COLUMN response_content_disposition TEXT;
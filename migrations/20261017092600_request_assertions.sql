@@ -0,0 +1,3 @@
+-- Declarative checks on the response of a request, JSON, see assertions::Assertion
+
+ALTER TABLE requests ADD COLUMN assertions TEXT;
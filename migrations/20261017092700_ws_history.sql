@@ -0,0 +1,23 @@
+-- Messages sent and received over WebSocket bridge connections, kept for
+-- searching and exporting sessions afterwards
+
+CREATE TABLE ws_sessions (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    url TEXT NOT NULL,
+    started_at TIMESTAMP NOT NULL,
+    ended_at TIMESTAMP -- NULL while the connection is open
+);
+
+CREATE TABLE ws_messages (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    session_id INTEGER NOT NULL,
+    direction TEXT NOT NULL, -- sent or received
+    encoding TEXT NOT NULL, -- text, base64 or hex, as data is written
+    data TEXT NOT NULL,
+    size INTEGER NOT NULL, -- payload bytes
+    created_at TIMESTAMP NOT NULL, -- with milliseconds
+
+    FOREIGN KEY (session_id) REFERENCES ws_sessions (id) ON DELETE CASCADE
+);
+
+CREATE INDEX idx_ws_messages_session ON ws_messages (session_id, id);
@@ -0,0 +1,8 @@
+-- Files of the sync directory and the rows they mirror, see file_sync.rs
+
+CREATE TABLE sync_files (
+    path TEXT PRIMARY KEY NOT NULL, -- Relative to the sync directory, '/'-separated
+    kind TEXT NOT NULL, -- 'folder', 'request' or 'environment'
+    row_id INTEGER NOT NULL,
+    hash TEXT NOT NULL -- SHA-256 of the content last written or read
+);
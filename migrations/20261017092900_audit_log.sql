@@ -0,0 +1,15 @@
+-- Changes to folders, requests and environments with snapshots of the row
+-- before and after, so edits can be traced and reverted
+
+CREATE TABLE audit_log (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    entity TEXT NOT NULL, -- folder, request or environment
+    entity_id INTEGER NOT NULL,
+    action TEXT NOT NULL, -- create, update, archive, unarchive, delete or revert
+    actor TEXT NOT NULL, -- X-JSLink-Actor header or client IP
+    before TEXT, -- JSON row, NULL for create
+    after TEXT, -- JSON row, NULL for delete
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX idx_audit_log_entity ON audit_log (entity, entity_id, id);
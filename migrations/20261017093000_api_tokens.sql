@@ -0,0 +1,13 @@
+-- Personal access tokens for scripts driving the API with
+-- `Authorization: Bearer`; only a hash of each token is kept
+
+CREATE TABLE api_tokens (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    name TEXT NOT NULL,
+    token_hash TEXT NOT NULL UNIQUE, -- hex SHA-256 of the token
+    prefix TEXT NOT NULL, -- first characters of the token, to tell tokens apart
+    scopes TEXT NOT NULL DEFAULT '[]', -- JSON array of read, write, execute, import, admin
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    last_used_at TIMESTAMP,
+    revoked_at TIMESTAMP
+);
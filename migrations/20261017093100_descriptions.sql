@@ -0,0 +1,3 @@
+-- Free-text documentation of folders and requests, Markdown
+ALTER TABLE folders ADD COLUMN description TEXT;
+ALTER TABLE requests ADD COLUMN description TEXT;
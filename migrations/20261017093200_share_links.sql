@@ -0,0 +1,20 @@
+-- Expiring read-only links to a request or folder, for people without the
+-- collection; only a hash of each token is kept
+
+CREATE TABLE share_links (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    token_hash TEXT NOT NULL UNIQUE, -- hex SHA-256 of the token
+    prefix TEXT NOT NULL, -- first characters of the token, to tell links apart
+    request_id INTEGER,
+    folder_id INTEGER,
+    allow_execute BOOLEAN NOT NULL DEFAULT 0,
+    environment_id INTEGER, -- the environment executions use, NULL for none
+    expires_at TIMESTAMP NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    revoked_at TIMESTAMP,
+
+    CHECK ((request_id IS NULL) <> (folder_id IS NULL)),
+    FOREIGN KEY (request_id) REFERENCES requests (id) ON DELETE CASCADE,
+    FOREIGN KEY (folder_id) REFERENCES folders (id) ON DELETE CASCADE,
+    FOREIGN KEY (environment_id) REFERENCES environments (id) ON DELETE SET NULL
+);
@@ -0,0 +1,12 @@
+-- Unsaved edits of a request, autosaved by the UI apart from the request
+-- itself so that a refresh does not lose them
+
+CREATE TABLE request_drafts (
+    request_id INTEGER PRIMARY KEY,
+    changes TEXT NOT NULL, -- JSON object of the edited fields, as for PATCH /requests/:id
+    base_updated_at TIMESTAMP NOT NULL, -- updated_at of the request when editing started
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+
+    FOREIGN KEY (request_id) REFERENCES requests (id) ON DELETE CASCADE
+);
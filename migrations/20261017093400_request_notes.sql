@@ -0,0 +1,15 @@
+-- Timestamped remarks teammates leave on a request, apart from its
+-- description
+
+CREATE TABLE request_notes (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    request_id INTEGER NOT NULL,
+    author TEXT NOT NULL, -- the actor who wrote it, see audit::Actor
+    body TEXT NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+
+    FOREIGN KEY (request_id) REFERENCES requests (id) ON DELETE CASCADE
+);
+
+CREATE INDEX idx_request_notes_request ON request_notes (request_id, created_at);
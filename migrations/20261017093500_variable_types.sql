@@ -0,0 +1,4 @@
+-- Declared types of environment variables, a JSON object by variable name,
+-- see variable_types::VariableType
+
+ALTER TABLE environments ADD COLUMN types TEXT NOT NULL DEFAULT '{}';
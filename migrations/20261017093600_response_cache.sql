@@ -0,0 +1,16 @@
+-- The last response of each saved request per resolved URL, replayed when
+-- the target is unreachable or offline mode is on
+
+CREATE TABLE response_cache (
+    request_id INTEGER NOT NULL,
+    url_hash TEXT NOT NULL, -- hex SHA-256 of the resolved URL
+    url TEXT NOT NULL,
+    status INTEGER NOT NULL,
+    headers TEXT NOT NULL, -- JSON object
+    body TEXT NOT NULL,
+    duration_ms INTEGER NOT NULL,
+    cached_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+
+    PRIMARY KEY (request_id, url_hash),
+    FOREIGN KEY (request_id) REFERENCES requests (id) ON DELETE CASCADE
+);
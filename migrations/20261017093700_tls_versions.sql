@@ -0,0 +1,2 @@
+-- TLS versions a request may negotiate, as JSON; unset uses the global setting
+ALTER TABLE requests ADD COLUMN tls_versions TEXT;
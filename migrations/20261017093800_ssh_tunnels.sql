@@ -0,0 +1,17 @@
+-- SSH connections to a bastion that requests can be sent through; each runs
+-- as a SOCKS forward on a local port while in use
+
+CREATE TABLE ssh_tunnels (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    name TEXT NOT NULL,
+    host TEXT NOT NULL,
+    port INTEGER NOT NULL DEFAULT 22,
+    username TEXT NOT NULL,
+    private_key TEXT, -- Encrypted; without a key or password the SSH agent is used
+    password TEXT, -- Encrypted
+    local_port INTEGER, -- Unset picks a free port
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+ALTER TABLE requests ADD COLUMN ssh_tunnel_id INTEGER REFERENCES ssh_tunnels (id) ON DELETE SET NULL;
@@ -0,0 +1,3 @@
+-- Prefix for the relative URLs of a folder's requests
+
+ALTER TABLE folders ADD COLUMN base_url TEXT;
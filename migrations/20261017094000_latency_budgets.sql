@@ -0,0 +1,4 @@
+-- Expected latency of a request, and the budget an execution was held to
+
+ALTER TABLE requests ADD COLUMN latency_budget_ms INTEGER;
+ALTER TABLE execution_history ADD COLUMN latency_budget_ms INTEGER; -- NULL without a budget
@@ -0,0 +1,4 @@
+-- Responses are looked up by the hash of the resolved URL, which may carry
+-- secrets, so the URL itself is no longer kept
+
+ALTER TABLE response_cache DROP COLUMN url;
@@ -0,0 +1,295 @@
+use axum::{
+    extract::{Request, State},
+    http::{header, HeaderValue, StatusCode},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    Router,
+};
+use std::env;
+use std::net::IpAddr;
+use tower_http::cors::{AllowOrigin, Any, CorsLayer};
+
+use crate::i18n;
+
+/// Which browser origins and `Host` names may use the `/api` surface, read
+/// from the environment.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AccessConfig {
+    /// Origins besides the UI itself allowed to call the API, answered with
+    /// CORS headers (`JSLINK_CORS_ALLOWED_ORIGINS`, `*` for any).
+    pub allowed_origins: Vec<String>,
+    /// Names the server may be reached by; empty allows any
+    /// (`JSLINK_ALLOWED_HOSTS`, see `allowed_hosts`).
+    pub allowed_hosts: Vec<String>,
+}
+
+/// Names this machine is reached by over loopback.
+const LOOPBACK_HOSTS: [&str; 3] = ["localhost", "127.0.0.1", "::1"];
+
+fn env_list(name: &str) -> Vec<String> {
+    env::var(name)
+        .unwrap_or_default()
+        .split(',')
+        .map(|v| v.trim().trim_end_matches('/').to_lowercase())
+        .filter(|v| !v.is_empty())
+        .collect()
+}
+
+/// Host name of a `Host` header or `host:port` pair, without the port.
+fn host_name(host: &str) -> &str {
+    if let Some(rest) = host.strip_prefix('[') {
+        // IPv6 literal
+        return rest.split(']').next().unwrap_or(rest);
+    }
+    host.rsplit_once(':').map_or(host, |(name, _)| name)
+}
+
+/// The names a server bound to `bind_host` may be reached by: those listed,
+/// else the loopback names when bound to loopback. Without that a page on a
+/// DNS-rebound name would pass as same-origin.
+pub fn allowed_hosts(listed: Vec<String>, bind_host: &str) -> Vec<String> {
+    let bind_host = host_name(bind_host.trim());
+    let loopback = bind_host.eq_ignore_ascii_case("localhost")
+        || bind_host.parse::<IpAddr>().is_ok_and(|ip| ip.is_loopback());
+    if listed.is_empty() && loopback {
+        return LOOPBACK_HOSTS.iter().map(|h| h.to_string()).collect();
+    }
+    listed
+}
+
+/// Whether `host` is one of `allowed_hosts`, any host when that is empty.
+pub fn host_allowed(allowed_hosts: &[String], host: Option<&str>) -> bool {
+    if allowed_hosts.is_empty() {
+        return true;
+    }
+    let Some(host) = host else {
+        return false;
+    };
+    let name = host_name(&host.to_lowercase()).to_string();
+    allowed_hosts.contains(&name)
+}
+
+/// Whether a browser `Origin` is the page served by this instance at `host`.
+pub fn same_origin(origin: &str, host: Option<&str>) -> bool {
+    let Ok(origin_url) = reqwest::Url::parse(origin) else {
+        return false;
+    };
+    let origin_host = match (origin_url.host_str(), origin_url.port()) {
+        (Some(h), Some(p)) => format!("{}:{}", h, p),
+        (Some(h), None) => h.to_string(),
+        _ => return false,
+    };
+    host.is_some_and(|h| h.to_lowercase() == origin_host)
+}
+
+impl AccessConfig {
+    /// `bind_host` is where the server actually listens, which `--open`
+    /// moves to loopback whatever `HOST` says.
+    pub fn from_env(bind_host: &str) -> Self {
+        Self {
+            allowed_origins: env_list("JSLINK_CORS_ALLOWED_ORIGINS"),
+            allowed_hosts: allowed_hosts(env_list("JSLINK_ALLOWED_HOSTS"), bind_host),
+        }
+    }
+
+    fn host_allowed(&self, host: Option<&str>) -> bool {
+        host_allowed(&self.allowed_hosts, host)
+    }
+
+    /// Calls without `Origin` come from non-browser clients and pass.
+    fn origin_allowed(&self, origin: Option<&str>, host: Option<&str>) -> bool {
+        let Some(origin) = origin else {
+            return true;
+        };
+        let origin = origin.trim_end_matches('/').to_lowercase();
+        self.allowed_origins
+            .iter()
+            .any(|o| o == "*" || *o == origin)
+            || same_origin(&origin, host)
+    }
+
+    fn cors_layer(&self) -> Option<CorsLayer> {
+        if self.allowed_origins.is_empty() {
+            return None;
+        }
+        let origins = if self.allowed_origins.iter().any(|o| o == "*") {
+            AllowOrigin::any()
+        } else {
+            AllowOrigin::list(
+                self.allowed_origins
+                    .iter()
+                    .filter_map(|o| HeaderValue::from_str(o).ok()),
+            )
+        };
+        Some(
+            CorsLayer::new()
+                .allow_origin(origins)
+                .allow_methods(Any)
+                .allow_headers(Any)
+                .expose_headers(Any),
+        )
+    }
+}
+
+fn header_text(request: &Request, name: header::HeaderName) -> Option<String> {
+    request
+        .headers()
+        .get(name)
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_string)
+}
+
+async fn check_access(
+    State(config): State<AccessConfig>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    let host = header_text(&request, header::HOST);
+    let origin = header_text(&request, header::ORIGIN);
+    let host = host.as_deref();
+    if !config.host_allowed(host) {
+        log::warn!("Rejected API call to host {:?}", host);
+        return (
+            StatusCode::MISDIRECTED_REQUEST,
+            i18n::t("error.host_not_allowed"),
+        )
+            .into_response();
+    }
+    let origin = origin.as_deref();
+    if !config.origin_allowed(origin, host) {
+        log::warn!("Rejected API call from origin {:?}", origin);
+        return (StatusCode::FORBIDDEN, i18n::t("error.origin_not_allowed")).into_response();
+    }
+    // WebSocket upgrades repeat the Host check for their own origin rules
+    request.extensions_mut().insert(config);
+    next.run(request).await
+}
+
+/// Wraps `router` in the host and origin checks, and CORS when origins are
+/// allowed.
+pub fn apply(router: Router, config: AccessConfig) -> Router {
+    let cors = config.cors_layer();
+    let router = router.layer(middleware::from_fn_with_state(config, check_access));
+    match cors {
+        Some(cors) => router.layer(cors),
+        None => router,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{routing::post, Extension};
+    use axum_test::TestServer;
+
+    fn server(config: AccessConfig) -> TestServer {
+        let router = Router::new().route("/ping", post(|| async { "pong" }));
+        TestServer::new(apply(router, config)).unwrap()
+    }
+
+    #[test]
+    fn test_host_name() {
+        assert_eq!(host_name("localhost:3000"), "localhost");
+        assert_eq!(host_name("example.com"), "example.com");
+        assert_eq!(host_name("[::1]:3000"), "::1");
+    }
+
+    #[tokio::test]
+    async fn test_foreign_origins_are_rejected() {
+        let server = server(AccessConfig {
+            allowed_origins: vec!["https://ci.example.com".to_string()],
+            allowed_hosts: Vec::new(),
+        });
+
+        server.post("/ping").await.assert_status_ok();
+        server
+            .post("/ping")
+            .add_header("Host", "localhost:3000")
+            .add_header("Origin", "http://localhost:3000")
+            .await
+            .assert_status_ok();
+        server
+            .post("/ping")
+            .add_header("Host", "localhost:3000")
+            .add_header("Origin", "https://evil.example")
+            .await
+            .assert_status(StatusCode::FORBIDDEN);
+
+        let response = server
+            .post("/ping")
+            .add_header("Origin", "https://ci.example.com")
+            .await;
+        response.assert_status_ok();
+        assert_eq!(
+            response.header("access-control-allow-origin"),
+            "https://ci.example.com"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_unlisted_hosts_are_rejected() {
+        let server = server(AccessConfig {
+            allowed_origins: Vec::new(),
+            allowed_hosts: vec!["localhost".to_string()],
+        });
+
+        server
+            .post("/ping")
+            .add_header("Host", "localhost:3000")
+            .await
+            .assert_status_ok();
+        server
+            .post("/ping")
+            .add_header("Host", "rebound.attacker.example")
+            .await
+            .assert_status(StatusCode::MISDIRECTED_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_rebound_names_are_rejected_on_loopback() {
+        assert_eq!(allowed_hosts(Vec::new(), "0.0.0.0"), Vec::<String>::new());
+        let server = server(AccessConfig {
+            allowed_origins: Vec::new(),
+            allowed_hosts: allowed_hosts(Vec::new(), "127.0.0.1"),
+        });
+
+        server
+            .post("/ping")
+            .add_header("Host", "evil.test:3000")
+            .add_header("Origin", "http://evil.test:3000")
+            .await
+            .assert_status(StatusCode::MISDIRECTED_REQUEST);
+        server
+            .post("/ping")
+            .add_header("Host", "[::1]:3000")
+            .add_header("Origin", "http://[::1]:3000")
+            .await
+            .assert_status_ok();
+    }
+
+    #[tokio::test]
+    async fn test_allowed_hosts_follow_the_bind_host() {
+        // `--open` listens on loopback even when `HOST` is a wildcard
+        let config = AccessConfig::from_env("127.0.0.1");
+        assert_eq!(config.allowed_hosts, allowed_hosts(Vec::new(), "127.0.0.1"));
+
+        // Handlers behind the checks see the config they ran with
+        let router = Router::new().route(
+            "/hosts",
+            post(|Extension(config): Extension<AccessConfig>| async move {
+                config.allowed_hosts.join(",")
+            }),
+        );
+        let server = TestServer::new(apply(router, config)).unwrap();
+        server
+            .post("/hosts")
+            .add_header("Host", "evil.test:3000")
+            .await
+            .assert_status(StatusCode::MISDIRECTED_REQUEST);
+        server
+            .post("/hosts")
+            .add_header("Host", "localhost:3000")
+            .await
+            .assert_text("localhost,127.0.0.1,::1");
+    }
+}
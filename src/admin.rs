@@ -0,0 +1,122 @@
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    routing::get,
+    Json, Router,
+};
+use serde::Serialize;
+
+use crate::{
+    db::{self, DbPool, MigrationState, MigrationStatus},
+    doctor::{self, DoctorOptions, DoctorReport},
+    i18n,
+};
+
+#[derive(Debug, Serialize)]
+pub struct MigrationReport {
+    pub up_to_date: bool,
+    pub migrations: Vec<MigrationStatus>,
+}
+
+#[derive(Debug)]
+pub enum AdminError {
+    DatabaseError(#[allow(dead_code)] sqlx::Error),
+}
+
+impl From<sqlx::Error> for AdminError {
+    fn from(err: sqlx::Error) -> Self {
+        AdminError::DatabaseError(err)
+    }
+}
+
+impl IntoResponse for AdminError {
+    fn into_response(self) -> Response {
+        match self {
+            AdminError::DatabaseError(_) => {
+                (StatusCode::INTERNAL_SERVER_ERROR, i18n::t("error.database")).into_response()
+            }
+        }
+    }
+}
+
+pub fn routes(pool: DbPool) -> Router {
+    Router::new()
+        .route("/admin/migrations", get(get_migrations))
+        .route("/admin/doctor", get(get_doctor))
+        .with_state(pool)
+}
+
+async fn get_migrations(State(pool): State<DbPool>) -> Result<Json<MigrationReport>, AdminError> {
+    log::debug!("Fetching migration status");
+    let migrations = db::migration_status(&pool).await?;
+    let up_to_date = migrations
+        .iter()
+        .all(|m| m.state == MigrationState::Applied);
+    Ok(Json(MigrationReport {
+        up_to_date,
+        migrations,
+    }))
+}
+
+/// Same checks as `js-link doctor`, minus the port check since this server
+/// already holds the port.
+async fn get_doctor(State(pool): State<DbPool>) -> Json<DoctorReport> {
+    log::debug!("Running doctor checks");
+    let options = DoctorOptions::from_env(false);
+    Json(doctor::run(Some(&pool), None, &options).await)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum_test::TestServer;
+    use serde_json::Value;
+
+    #[tokio::test]
+    async fn test_get_migrations() {
+        let pool = db::create_test_pool().await;
+        let server = TestServer::new(routes(pool.clone())).unwrap();
+
+        let response = server.get("/admin/migrations").await;
+        response.assert_status_ok();
+        let body: Value = response.json();
+        assert_eq!(body["up_to_date"], true);
+        assert_eq!(body["migrations"][0]["state"], "applied");
+
+        let last = body["migrations"].as_array().unwrap().last().unwrap()["version"]
+            .as_i64()
+            .unwrap();
+        sqlx::query("DELETE FROM _sqlx_migrations WHERE version = ?")
+            .bind(last)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let body: Value = server.get("/admin/migrations").await.json();
+        assert_eq!(body["up_to_date"], false);
+        assert_eq!(
+            body["migrations"].as_array().unwrap().last().unwrap()["state"],
+            "pending"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_get_doctor() {
+        let pool = db::create_test_pool().await;
+        let server = TestServer::new(routes(pool)).unwrap();
+
+        let response = server.get("/admin/doctor").await;
+        response.assert_status_ok();
+        let body: Value = response.json();
+        assert_eq!(body["checks"][0]["name"], "database");
+        assert_eq!(body["checks"][0]["status"], "ok");
+        let port = body["checks"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|c| c["name"] == "port")
+            .unwrap();
+        assert_eq!(port["status"], "ok");
+    }
+}
@@ -0,0 +1,540 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    routing::{get, put},
+    Json, Router,
+};
+use chrono::{DateTime, NaiveDateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::time::Duration;
+
+use crate::{
+    db::DbPool,
+    i18n,
+    runner::{RunReport, RunStatus},
+};
+
+// Alerts for failed folder runs: each alert POSTs the failure to a webhook,
+// formatted for Slack, Discord or as plain JSON, retrying with backoff.
+
+const MAX_ATTEMPTS: u32 = 3;
+/// Pause before the first retry, doubled for each further one.
+const RETRY_DELAY: Duration = Duration::from_secs(2);
+const DELIVERY_TIMEOUT_SECS: u64 = 10;
+/// Failed requests listed in a chat message before the rest are counted.
+const MAX_LISTED_FAILURES: usize = 10;
+// Discord rejects messages longer than this
+const DISCORD_MAX_CONTENT: usize = 2000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AlertFormat {
+    Json,
+    Slack,
+    Discord,
+}
+
+impl AlertFormat {
+    fn parse(value: &str) -> Option<Self> {
+        match value {
+            "json" => Some(AlertFormat::Json),
+            "slack" => Some(AlertFormat::Slack),
+            "discord" => Some(AlertFormat::Discord),
+            _ => None,
+        }
+    }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            AlertFormat::Json => "json",
+            AlertFormat::Slack => "slack",
+            AlertFormat::Discord => "discord",
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Alert {
+    pub id: i64,
+    pub folder_id: i64,
+    pub url: String,
+    pub format: AlertFormat,
+    pub enabled: bool,
+    /// Why the latest delivery failed after all its attempts
+    pub last_error: Option<String>,
+    pub last_sent_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+struct AlertDb {
+    id: i64,
+    folder_id: i64,
+    url: String,
+    format: String,
+    enabled: bool,
+    last_error: Option<String>,
+    last_sent_at: Option<NaiveDateTime>,
+    created_at: NaiveDateTime,
+}
+
+impl From<AlertDb> for Alert {
+    fn from(a: AlertDb) -> Self {
+        Alert {
+            id: a.id,
+            folder_id: a.folder_id,
+            url: a.url,
+            format: AlertFormat::parse(&a.format).unwrap_or(AlertFormat::Json),
+            enabled: a.enabled,
+            last_error: a.last_error,
+            last_sent_at: a.last_sent_at.map(|d| d.and_utc()),
+            created_at: a.created_at.and_utc(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateAlert {
+    pub url: String,
+    #[serde(default = "default_format")]
+    pub format: AlertFormat,
+}
+
+fn default_format() -> AlertFormat {
+    AlertFormat::Json
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateAlert {
+    pub enabled: bool,
+}
+
+pub enum AlertError {
+    InvalidUrl,
+    FolderNotFound,
+    AlertNotFound,
+    DatabaseError(#[allow(dead_code)] sqlx::Error),
+}
+
+impl From<sqlx::Error> for AlertError {
+    fn from(e: sqlx::Error) -> Self {
+        match e {
+            sqlx::Error::RowNotFound => AlertError::AlertNotFound,
+            _ => AlertError::DatabaseError(e),
+        }
+    }
+}
+
+impl IntoResponse for AlertError {
+    fn into_response(self) -> Response {
+        match self {
+            AlertError::InvalidUrl => {
+                (StatusCode::BAD_REQUEST, i18n::t("error.invalid_alert_url")).into_response()
+            }
+            AlertError::FolderNotFound => {
+                (StatusCode::NOT_FOUND, i18n::t("error.folder_not_found")).into_response()
+            }
+            AlertError::AlertNotFound => {
+                (StatusCode::NOT_FOUND, i18n::t("error.alert_not_found")).into_response()
+            }
+            AlertError::DatabaseError(_) => {
+                (StatusCode::INTERNAL_SERVER_ERROR, i18n::t("error.database")).into_response()
+            }
+        }
+    }
+}
+
+/// One line per failed request, such as `POST Pay: Status 402`.
+fn failure_lines(report: &RunReport) -> Vec<String> {
+    let failures: Vec<String> = report
+        .results
+        .iter()
+        .filter(|r| !r.passed)
+        .map(|r| {
+            format!(
+                "{} {}: {}",
+                r.method,
+                r.name,
+                r.error.as_deref().unwrap_or("failed")
+            )
+        })
+        .collect();
+    let mut lines: Vec<String> = failures.iter().take(MAX_LISTED_FAILURES).cloned().collect();
+    if failures.len() > MAX_LISTED_FAILURES {
+        lines.push(format!("and {} more", failures.len() - MAX_LISTED_FAILURES));
+    }
+    lines
+}
+
+fn summary(report: &RunReport, folder_name: &str) -> String {
+    let mut text = format!(
+        "Run {} of \"{}\" failed: {} of {} request(s) failed",
+        report.id, folder_name, report.failed, report.total
+    );
+    if report.status == RunStatus::Stopped {
+        text.push_str(&format!(", {} skipped", report.skipped));
+    }
+    for line in failure_lines(report) {
+        text.push_str("\n• ");
+        text.push_str(&line);
+    }
+    text
+}
+
+/// The body posted for a failed run.
+pub fn payload(format: AlertFormat, report: &RunReport, folder_name: &str) -> Value {
+    match format {
+        AlertFormat::Slack => json!({ "text": summary(report, folder_name) }),
+        AlertFormat::Discord => {
+            let content: String = summary(report, folder_name)
+                .chars()
+                .take(DISCORD_MAX_CONTENT)
+                .collect();
+            json!({ "content": content })
+        }
+        AlertFormat::Json => json!({
+            "event": "run.failed",
+            "run_id": report.id,
+            "folder_id": report.folder_id,
+            "folder_name": folder_name,
+            "environment_id": report.environment_id,
+            "status": report.status,
+            "total": report.total,
+            "passed": report.passed,
+            "failed": report.failed,
+            "skipped": report.skipped,
+            "finished_at": report.finished_at,
+            "failures": report.results.iter().filter(|r| !r.passed).map(|r| json!({
+                "request_id": r.request_id,
+                "name": r.name,
+                "method": r.method,
+                "url": r.url,
+                "status": r.status,
+                "error": r.error,
+            })).collect::<Vec<_>>(),
+        }),
+    }
+}
+
+/// Posts the body until the webhook answers with a 2xx status, waiting
+/// `retry_delay`, then twice as long, between attempts.
+async fn deliver(
+    client: &reqwest::Client,
+    url: &str,
+    body: &Value,
+    retry_delay: Duration,
+) -> Result<(), String> {
+    let mut delay = retry_delay;
+    let mut error = String::new();
+    for attempt in 1..=MAX_ATTEMPTS {
+        if attempt > 1 {
+            tokio::time::sleep(delay).await;
+            delay *= 2;
+        }
+        let request = client
+            .post(url)
+            .header("Content-Type", "application/json")
+            .body(body.to_string());
+        match request.send().await {
+            Ok(response) if response.status().is_success() => return Ok(()),
+            Ok(response) => error = format!("Status {}", response.status().as_u16()),
+            Err(e) => error = e.to_string(),
+        }
+        log::warn!(
+            "Alert delivery to {} failed (attempt {}/{}): {}",
+            url,
+            attempt,
+            MAX_ATTEMPTS,
+            error
+        );
+    }
+    Err(error)
+}
+
+/// Notifies the folder's enabled alerts of a failed run. Passed runs send
+/// nothing. Delivery failures are logged and kept on the alert, never
+/// returned, since the run itself has finished.
+pub async fn notify(pool: &DbPool, report: &RunReport) {
+    notify_with_delay(pool, report, RETRY_DELAY).await
+}
+
+async fn notify_with_delay(pool: &DbPool, report: &RunReport, retry_delay: Duration) {
+    if report.status == RunStatus::Passed {
+        return;
+    }
+    let alerts = sqlx::query_as!(
+        AlertDb,
+        r#"SELECT id as "id!", folder_id, url, format, enabled, last_error, last_sent_at as "last_sent_at: NaiveDateTime", created_at as "created_at: NaiveDateTime" FROM run_alerts WHERE folder_id = ? AND enabled = 1"#,
+        report.folder_id
+    )
+    .fetch_all(pool)
+    .await;
+    let alerts = match alerts {
+        Ok(alerts) if !alerts.is_empty() => alerts,
+        Ok(_) => return,
+        Err(e) => {
+            log::warn!(
+                "Failed to load alerts of folder {}: {}",
+                report.folder_id,
+                e
+            );
+            return;
+        }
+    };
+    let folder_name =
+        sqlx::query_scalar!("SELECT name FROM folders WHERE id = ?", report.folder_id)
+            .fetch_optional(pool)
+            .await
+            .ok()
+            .flatten()
+            .unwrap_or_default();
+
+    let client = match reqwest::Client::builder()
+        .timeout(Duration::from_secs(DELIVERY_TIMEOUT_SECS))
+        .build()
+    {
+        Ok(client) => client,
+        Err(e) => {
+            log::error!("Failed to build alert client: {}", e);
+            return;
+        }
+    };
+    for alert in alerts.into_iter().map(Alert::from) {
+        let body = payload(alert.format, report, &folder_name);
+        let error = deliver(&client, &alert.url, &body, retry_delay).await.err();
+        match &error {
+            None => log::info!("Alert {} notified of failed run {}", alert.id, report.id),
+            Some(e) => log::error!("Alert {} could not be delivered: {}", alert.id, e),
+        }
+        let result = sqlx::query!(
+            "UPDATE run_alerts SET last_error = ?, last_sent_at = CURRENT_TIMESTAMP WHERE id = ?",
+            error,
+            alert.id
+        )
+        .execute(pool)
+        .await;
+        if let Err(e) = result {
+            log::warn!("Failed to record delivery of alert {}: {}", alert.id, e);
+        }
+    }
+}
+
+fn validate_url(url: &str) -> Result<(), AlertError> {
+    match reqwest::Url::parse(url) {
+        Ok(url) if matches!(url.scheme(), "http" | "https") => Ok(()),
+        _ => Err(AlertError::InvalidUrl),
+    }
+}
+
+async fn list_alerts(
+    State(pool): State<DbPool>,
+    Path(folder_id): Path<i64>,
+) -> Result<impl IntoResponse, AlertError> {
+    let alerts = sqlx::query_as!(
+        AlertDb,
+        r#"SELECT id as "id!", folder_id, url, format, enabled, last_error, last_sent_at as "last_sent_at: NaiveDateTime", created_at as "created_at: NaiveDateTime" FROM run_alerts WHERE folder_id = ? ORDER BY id"#,
+        folder_id
+    )
+    .fetch_all(&pool)
+    .await?;
+    Ok(Json(
+        alerts.into_iter().map(Alert::from).collect::<Vec<_>>(),
+    ))
+}
+
+async fn create_alert(
+    State(pool): State<DbPool>,
+    Path(folder_id): Path<i64>,
+    Json(payload): Json<CreateAlert>,
+) -> Result<impl IntoResponse, AlertError> {
+    validate_url(&payload.url)?;
+    sqlx::query_scalar!("SELECT id FROM folders WHERE id = ?", folder_id)
+        .fetch_optional(&pool)
+        .await?
+        .ok_or(AlertError::FolderNotFound)?;
+
+    let format = payload.format.as_str();
+    let alert = sqlx::query_as!(
+        AlertDb,
+        r#"INSERT INTO run_alerts (folder_id, url, format) VALUES (?, ?, ?) RETURNING id as "id!", folder_id, url, format, enabled, last_error, last_sent_at as "last_sent_at: NaiveDateTime", created_at as "created_at: NaiveDateTime""#,
+        folder_id,
+        payload.url,
+        format
+    )
+    .fetch_one(&pool)
+    .await?;
+
+    log::info!(
+        "Created {} alert {} for folder {}",
+        format,
+        alert.id,
+        folder_id
+    );
+    Ok((StatusCode::CREATED, Json(Alert::from(alert))))
+}
+
+async fn update_alert(
+    State(pool): State<DbPool>,
+    Path(id): Path<i64>,
+    Json(payload): Json<UpdateAlert>,
+) -> Result<impl IntoResponse, AlertError> {
+    let alert = sqlx::query_as!(
+        AlertDb,
+        r#"UPDATE run_alerts SET enabled = ? WHERE id = ? RETURNING id as "id!", folder_id, url, format, enabled, last_error, last_sent_at as "last_sent_at: NaiveDateTime", created_at as "created_at: NaiveDateTime""#,
+        payload.enabled,
+        id
+    )
+    .fetch_one(&pool)
+    .await?;
+    Ok(Json(Alert::from(alert)))
+}
+
+async fn delete_alert(
+    State(pool): State<DbPool>,
+    Path(id): Path<i64>,
+) -> Result<impl IntoResponse, AlertError> {
+    let result = sqlx::query!("DELETE FROM run_alerts WHERE id = ?", id)
+        .execute(&pool)
+        .await?;
+    if result.rows_affected() == 0 {
+        return Err(AlertError::AlertNotFound);
+    }
+    log::info!("Deleted alert {}", id);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub fn routes(pool: DbPool) -> Router {
+    Router::new()
+        .route("/folders/:id/alerts", get(list_alerts).post(create_alert))
+        .route("/alerts/:id", put(update_alert).delete(delete_alert))
+        .with_state(pool)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db;
+    use crate::runner::RunResult;
+    use axum_test::TestServer;
+    use httpmock::MockServer;
+
+    fn failed_report(folder_id: i64) -> RunReport {
+        let result = |name: &str, error: Option<&str>| RunResult {
+            request_id: 1,
+            name: name.to_string(),
+            method: "GET".to_string(),
+            url: format!("http://api/{}", name.to_lowercase()),
+            status: Some(if error.is_some() { 500 } else { 200 }),
+            duration: 5,
+            passed: error.is_none(),
+            error: error.map(str::to_string),
+            test_results: Vec::new(),
+            assertion_results: Vec::new(),
+            body: None,
+        };
+        RunReport {
+            id: 42,
+            folder_id,
+            environment_id: None,
+            status: RunStatus::Failed,
+            total: 2,
+            passed: 1,
+            failed: 1,
+            skipped: 0,
+            started_at: Utc::now(),
+            finished_at: Utc::now(),
+            results: vec![result("Health", None), result("Orders", Some("Status 500"))],
+        }
+    }
+
+    #[test]
+    fn test_payloads() {
+        let report = failed_report(1);
+        let slack = payload(AlertFormat::Slack, &report, "Checkout");
+        assert_eq!(
+            slack["text"],
+            "Run 42 of \"Checkout\" failed: 1 of 2 request(s) failed\n• GET Orders: Status 500"
+        );
+        let discord = payload(AlertFormat::Discord, &report, "Checkout");
+        assert_eq!(discord["content"], slack["text"]);
+        let generic = payload(AlertFormat::Json, &report, "Checkout");
+        assert_eq!(generic["event"], "run.failed");
+        assert_eq!(generic["status"], "failed");
+        assert_eq!(generic["failures"].as_array().unwrap().len(), 1);
+        assert_eq!(generic["failures"][0]["url"], "http://api/orders");
+    }
+
+    #[tokio::test]
+    async fn test_notify_failed_run() {
+        let pool = db::create_test_pool().await;
+        let hooks = MockServer::start_async().await;
+        let slack = hooks.mock(|when, then| {
+            when.method(httpmock::Method::POST)
+                .path("/slack")
+                .body_includes("GET Orders: Status 500");
+            then.status(200);
+        });
+        let broken = hooks.mock(|when, then| {
+            when.path("/broken");
+            then.status(503);
+        });
+        let folder_id =
+            sqlx::query_scalar!("INSERT INTO folders (name) VALUES ('Checkout') RETURNING id")
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+        let server = TestServer::new(routes(pool.clone())).unwrap();
+
+        let created: Alert = server
+            .post(&format!("/folders/{}/alerts", folder_id))
+            .json(&json!({ "url": hooks.url("/slack"), "format": "slack" }))
+            .await
+            .json();
+        assert_eq!(created.format, AlertFormat::Slack);
+        let broken_alert: Alert = server
+            .post(&format!("/folders/{}/alerts", folder_id))
+            .json(&json!({ "url": hooks.url("/broken") }))
+            .await
+            .json();
+
+        let mut report = failed_report(folder_id);
+        notify_with_delay(&pool, &report, Duration::from_millis(10)).await;
+        slack.assert_calls(1);
+        broken.assert_calls(MAX_ATTEMPTS as usize);
+        let alerts: Vec<Alert> = server
+            .get(&format!("/folders/{}/alerts", folder_id))
+            .await
+            .json();
+        assert_eq!(alerts[0].last_error, None);
+        assert!(alerts[0].last_sent_at.is_some());
+        assert_eq!(alerts[1].last_error.as_deref(), Some("Status 503"));
+
+        // Passed runs and disabled alerts send nothing
+        server
+            .put(&format!("/alerts/{}", broken_alert.id))
+            .json(&json!({ "enabled": false }))
+            .await
+            .assert_status_ok();
+        notify_with_delay(&pool, &report, Duration::from_millis(10)).await;
+        report.status = RunStatus::Passed;
+        notify_with_delay(&pool, &report, Duration::from_millis(10)).await;
+        slack.assert_calls(2);
+        broken.assert_calls(MAX_ATTEMPTS as usize);
+
+        server
+            .post(&format!("/folders/{}/alerts", folder_id))
+            .json(&json!({ "url": "not a url" }))
+            .await
+            .assert_status(StatusCode::BAD_REQUEST);
+        server
+            .post("/folders/999/alerts")
+            .json(&json!({ "url": "https://hooks.example.com" }))
+            .await
+            .assert_status(StatusCode::NOT_FOUND);
+        server
+            .delete(&format!("/alerts/{}", created.id))
+            .await
+            .assert_status(StatusCode::NO_CONTENT);
+    }
+}
@@ -0,0 +1,580 @@
+use std::{env, net::SocketAddr};
+
+use axum::{
+    extract::{ConnectInfo, Path, Request, State},
+    http::{header, Method, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    routing::{delete, get},
+    Json, Router,
+};
+use chrono::{DateTime, NaiveDateTime, Utc};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::{db::DbPool, i18n};
+
+/// Tokens start with this, so other bearer tokens passing through the API
+/// (e.g. to the recorder) are left alone.
+pub const TOKEN_PREFIX: &str = "jsl_";
+/// Characters of a token kept in the clear to tell tokens apart.
+const SHOWN_PREFIX_LEN: usize = 12;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Scope {
+    /// Reading anything through `GET`
+    Read,
+    /// Changing collections, environments and settings
+    Write,
+    /// Sending requests and running folders
+    Execute,
+    Import,
+    /// Managing tokens and the admin endpoints
+    Admin,
+}
+
+impl Scope {
+    /// The scope a call needs, by the path within `/api`.
+    fn required(method: &Method, path: &str) -> Self {
+        let under = |prefix: &str| path == prefix || path.starts_with(&format!("{}/", prefix));
+        if under("/tokens") || under("/admin") {
+            Scope::Admin
+        } else if under("/import") {
+            Scope::Import
+        } else if under("/execute")
+            || under("/execute-direct")
+            || under("/run")
+            || under("/ws")
+            || (path.starts_with("/comparisons/") && path.ends_with("/run"))
+            || (path.starts_with("/basic/requests/") && path.ends_with("/send"))
+            // These reach out to other hosts, even when read with GET
+            || under("/record")
+            || under("/tools/dns")
+            || under("/tools/tcp-check")
+            || under("/tools/tls-inspect")
+            || (path.starts_with("/ssh-tunnels/") && path.ends_with("/connect"))
+            || (path.starts_with("/oauth/") && path.ends_with("/token"))
+        {
+            Scope::Execute
+        } else if method == Method::GET || method == Method::HEAD {
+            Scope::Read
+        } else {
+            Scope::Write
+        }
+    }
+}
+
+/// The token a call was made with, for the audit log.
+#[derive(Debug, Clone)]
+pub struct TokenIdentity {
+    pub id: i64,
+    pub name: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ApiToken {
+    pub id: i64,
+    pub name: String,
+    pub prefix: String,
+    pub scopes: Vec<Scope>,
+    pub created_at: DateTime<Utc>,
+    pub last_used_at: Option<DateTime<Utc>>,
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+struct ApiTokenDb {
+    id: i64,
+    name: String,
+    prefix: String,
+    scopes: String,
+    created_at: NaiveDateTime,
+    last_used_at: Option<NaiveDateTime>,
+    revoked_at: Option<NaiveDateTime>,
+}
+
+impl From<ApiTokenDb> for ApiToken {
+    fn from(t: ApiTokenDb) -> Self {
+        Self {
+            id: t.id,
+            name: t.name,
+            prefix: t.prefix,
+            scopes: serde_json::from_str(&t.scopes).unwrap_or_default(),
+            created_at: t.created_at.and_utc(),
+            last_used_at: t.last_used_at.map(|at| at.and_utc()),
+            revoked_at: t.revoked_at.map(|at| at.and_utc()),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct CreateToken {
+    name: String,
+    scopes: Vec<Scope>,
+}
+
+/// A new token; `token` is shown this once and cannot be fetched again.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CreatedToken {
+    #[serde(flatten)]
+    pub info: ApiToken,
+    pub token: String,
+}
+
+#[derive(Debug)]
+pub enum ApiTokenError {
+    InvalidName,
+    NoScopes,
+    TokenNotFound,
+    DatabaseError(#[allow(dead_code)] sqlx::Error),
+}
+
+impl From<sqlx::Error> for ApiTokenError {
+    fn from(e: sqlx::Error) -> Self {
+        match e {
+            sqlx::Error::RowNotFound => ApiTokenError::TokenNotFound,
+            _ => ApiTokenError::DatabaseError(e),
+        }
+    }
+}
+
+impl IntoResponse for ApiTokenError {
+    fn into_response(self) -> Response {
+        match self {
+            ApiTokenError::InvalidName => {
+                (StatusCode::BAD_REQUEST, i18n::t("error.invalid_token_name")).into_response()
+            }
+            ApiTokenError::NoScopes => (
+                StatusCode::BAD_REQUEST,
+                i18n::t("error.token_without_scopes"),
+            )
+                .into_response(),
+            ApiTokenError::TokenNotFound => {
+                (StatusCode::NOT_FOUND, i18n::t("error.token_not_found")).into_response()
+            }
+            ApiTokenError::DatabaseError(_) => {
+                (StatusCode::INTERNAL_SERVER_ERROR, i18n::t("error.database")).into_response()
+            }
+        }
+    }
+}
+
+/// Hex SHA-256 of a token, the form tokens are stored in.
+pub fn hash(token: &str) -> String {
+    format!("{:x}", Sha256::digest(token.as_bytes()))
+}
+
+/// `prefix` followed by 48 random hex digits.
+pub fn random_token(prefix: &str) -> String {
+    let mut bytes = [0u8; 24];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    let hex: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+    format!("{}{}", prefix, hex)
+}
+
+/// Whether calls from other machines must carry a token
+/// (`JSLINK_REQUIRE_API_TOKEN`); calls from this machine never need one.
+fn token_required() -> bool {
+    env::var("JSLINK_REQUIRE_API_TOKEN")
+        .map(|v| matches!(v.trim().to_lowercase().as_str(), "1" | "true" | "yes"))
+        .unwrap_or(false)
+}
+
+/// Whether any token is in use, after which calls from other machines must
+/// carry one so that scopes restrict them. Fails closed.
+async fn tokens_exist(pool: &DbPool) -> bool {
+    sqlx::query_scalar!(
+        r#"SELECT EXISTS(SELECT 1 FROM api_tokens WHERE revoked_at IS NULL) as "exists!: bool""#
+    )
+    .fetch_one(pool)
+    .await
+    .unwrap_or_else(|e| {
+        log::error!("Failed to look up API tokens: {}", e);
+        true
+    })
+}
+
+/// Whether `path` delivers to a webhook bin, `/hooks/<bin>`. The services
+/// calling these can't be given a token, so deliveries never need one.
+fn is_webhook_delivery(path: &str) -> bool {
+    path.strip_prefix("/hooks/")
+        .is_some_and(|bin| !bin.is_empty() && !bin.contains('/'))
+}
+
+/// Checks the `Authorization: Bearer jsl_…` token of an API call against the
+/// scope the call needs. Calls without one pass if they come from this
+/// machine, or while tokens are neither required nor in use.
+pub async fn authenticate(
+    State(pool): State<DbPool>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    let token = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .map(str::trim)
+        .filter(|t| t.starts_with(TOKEN_PREFIX))
+        .map(str::to_string);
+
+    let Some(token) = token else {
+        // Requests without connection info (e.g. in-process tests) are local
+        let local = request
+            .extensions()
+            .get::<ConnectInfo<SocketAddr>>()
+            .is_none_or(|info| info.0.ip().is_loopback());
+        let exempt = is_webhook_delivery(request.uri().path());
+        if !local && !exempt && (token_required() || tokens_exist(&pool).await) {
+            return (StatusCode::UNAUTHORIZED, i18n::t("error.token_required")).into_response();
+        }
+        return next.run(request).await;
+    };
+
+    let token_hash = hash(&token);
+    let stored = sqlx::query!(
+        r#"SELECT id as "id!", name, scopes FROM api_tokens WHERE token_hash = ? AND revoked_at IS NULL"#,
+        token_hash
+    )
+    .fetch_optional(&pool)
+    .await;
+    let stored = match stored {
+        Ok(Some(stored)) => stored,
+        Ok(None) => {
+            log::warn!("Rejected API call with an unknown or revoked token");
+            return (StatusCode::UNAUTHORIZED, i18n::t("error.invalid_token")).into_response();
+        }
+        Err(e) => {
+            log::error!("Failed to look up API token: {}", e);
+            return (StatusCode::INTERNAL_SERVER_ERROR, i18n::t("error.database")).into_response();
+        }
+    };
+
+    let scopes: Vec<Scope> = serde_json::from_str(&stored.scopes).unwrap_or_default();
+    let required = Scope::required(request.method(), request.uri().path());
+    if !scopes.contains(&required) {
+        log::warn!(
+            "Token '{}' lacks the {:?} scope for {} {}",
+            stored.name,
+            required,
+            request.method(),
+            request.uri().path()
+        );
+        return (StatusCode::FORBIDDEN, i18n::t("error.token_scope")).into_response();
+    }
+
+    let now = Utc::now().naive_utc();
+    if let Err(e) = sqlx::query!(
+        "UPDATE api_tokens SET last_used_at = ? WHERE id = ?",
+        now,
+        stored.id
+    )
+    .execute(&pool)
+    .await
+    {
+        log::warn!("Failed to note use of API token {}: {}", stored.id, e);
+    }
+    request.extensions_mut().insert(TokenIdentity {
+        id: stored.id,
+        name: stored.name,
+    });
+    next.run(request).await
+}
+
+async fn create_token(
+    State(pool): State<DbPool>,
+    Json(payload): Json<CreateToken>,
+) -> Result<impl IntoResponse, ApiTokenError> {
+    let name = payload.name.trim();
+    if name.is_empty() {
+        return Err(ApiTokenError::InvalidName);
+    }
+    if payload.scopes.is_empty() {
+        return Err(ApiTokenError::NoScopes);
+    }
+
+    let token = random_token(TOKEN_PREFIX);
+    let token_hash = hash(&token);
+    let prefix = token[..SHOWN_PREFIX_LEN].to_string();
+    let scopes = serde_json::to_string(&payload.scopes).unwrap_or_else(|_| "[]".to_string());
+    let created = sqlx::query_as!(
+        ApiTokenDb,
+        r#"INSERT INTO api_tokens (name, token_hash, prefix, scopes) VALUES (?, ?, ?, ?)
+        RETURNING id as "id!", name, prefix, scopes, created_at as "created_at: NaiveDateTime",
+            last_used_at as "last_used_at: NaiveDateTime", revoked_at as "revoked_at: NaiveDateTime""#,
+        name,
+        token_hash,
+        prefix,
+        scopes
+    )
+    .fetch_one(&pool)
+    .await?;
+
+    log::info!(
+        "Created API token: id={}, name={}",
+        created.id,
+        created.name
+    );
+    Ok((
+        StatusCode::CREATED,
+        Json(CreatedToken {
+            info: created.into(),
+            token,
+        }),
+    ))
+}
+
+async fn list_tokens(State(pool): State<DbPool>) -> Result<impl IntoResponse, ApiTokenError> {
+    let tokens = sqlx::query_as!(
+        ApiTokenDb,
+        r#"SELECT id as "id!", name, prefix, scopes, created_at as "created_at: NaiveDateTime",
+            last_used_at as "last_used_at: NaiveDateTime", revoked_at as "revoked_at: NaiveDateTime"
+        FROM api_tokens ORDER BY id"#
+    )
+    .fetch_all(&pool)
+    .await?;
+    Ok(Json(
+        tokens.into_iter().map(ApiToken::from).collect::<Vec<_>>(),
+    ))
+}
+
+/// Revokes a token; it stays listed so its last use can still be seen.
+async fn revoke_token(
+    State(pool): State<DbPool>,
+    Path(id): Path<i64>,
+) -> Result<impl IntoResponse, ApiTokenError> {
+    let now = Utc::now().naive_utc();
+    let result = sqlx::query!(
+        "UPDATE api_tokens SET revoked_at = COALESCE(revoked_at, ?) WHERE id = ?",
+        now,
+        id
+    )
+    .execute(&pool)
+    .await?;
+    if result.rows_affected() == 0 {
+        return Err(ApiTokenError::TokenNotFound);
+    }
+    log::info!("Revoked API token: id={}", id);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub fn routes(pool: DbPool) -> Router {
+    Router::new()
+        .route("/tokens", get(list_tokens).post(create_token))
+        .route("/tokens/:id", delete(revoke_token))
+        .with_state(pool)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{db, folders};
+    use axum_test::TestServer;
+    use serde_json::json;
+
+    #[test]
+    fn test_required_scope() {
+        let cases = [
+            (Method::GET, "/requests", Scope::Read),
+            (Method::POST, "/requests", Scope::Write),
+            (Method::POST, "/execute", Scope::Execute),
+            (Method::POST, "/run/folder/3", Scope::Execute),
+            (Method::POST, "/comparisons/2/run", Scope::Execute),
+            (Method::POST, "/comparisons", Scope::Write),
+            (Method::POST, "/import", Scope::Import),
+            (Method::GET, "/tokens", Scope::Admin),
+            (Method::GET, "/runs/1", Scope::Read),
+            (Method::POST, "/basic/requests/4/send", Scope::Execute),
+            (Method::GET, "/basic/requests/4", Scope::Read),
+        ];
+        for (method, path, scope) in cases {
+            assert_eq!(Scope::required(&method, path), scope, "{} {}", method, path);
+        }
+    }
+
+    #[test]
+    fn test_recording_proxy_needs_execute() {
+        assert_eq!(Scope::required(&Method::GET, "/record/3"), Scope::Execute);
+        assert_eq!(
+            Scope::required(&Method::GET, "/record/3/users/1"),
+            Scope::Execute
+        );
+        assert_eq!(Scope::required(&Method::GET, "/recordings/3"), Scope::Read);
+    }
+
+    #[test]
+    fn test_network_tools_need_execute() {
+        for path in ["/tools/dns", "/tools/tcp-check", "/tools/tls-inspect"] {
+            assert_eq!(
+                Scope::required(&Method::POST, path),
+                Scope::Execute,
+                "{}",
+                path
+            );
+        }
+        assert_eq!(
+            Scope::required(&Method::POST, "/tools/extract"),
+            Scope::Write
+        );
+    }
+
+    #[test]
+    fn test_tunnel_connect_needs_execute() {
+        assert_eq!(
+            Scope::required(&Method::POST, "/ssh-tunnels/2/connect"),
+            Scope::Execute
+        );
+        assert_eq!(
+            Scope::required(&Method::POST, "/ssh-tunnels/2/disconnect"),
+            Scope::Write
+        );
+    }
+
+    #[test]
+    fn test_oauth_token_fetch_needs_execute() {
+        assert_eq!(
+            Scope::required(&Method::POST, "/oauth/5/token"),
+            Scope::Execute
+        );
+        assert_eq!(
+            Scope::required(&Method::GET, "/oauth/5/authorize"),
+            Scope::Read
+        );
+    }
+
+    #[tokio::test]
+    async fn test_tokens_authenticate_calls() {
+        let pool = db::create_test_pool().await;
+        let api = routes(pool.clone())
+            .merge(folders::routes(pool.clone()))
+            .layer(axum::middleware::from_fn_with_state(
+                pool.clone(),
+                authenticate,
+            ));
+        let server = TestServer::new(api).unwrap();
+
+        let created: CreatedToken = server
+            .post("/tokens")
+            .json(&json!({ "name": "CI", "scopes": ["read"] }))
+            .await
+            .json();
+        assert!(created.token.starts_with(TOKEN_PREFIX));
+        assert!(created.token.starts_with(&created.info.prefix));
+        let stored: String = sqlx::query_scalar("SELECT token_hash FROM api_tokens")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(stored, hash(&created.token));
+
+        let bearer = format!("Bearer {}", created.token);
+        server
+            .get("/folders")
+            .add_header("Authorization", bearer.as_str())
+            .await
+            .assert_status_ok();
+        server
+            .post("/folders")
+            .add_header("Authorization", bearer.as_str())
+            .json(&json!({ "name": "Nope" }))
+            .await
+            .assert_status(StatusCode::FORBIDDEN);
+        server
+            .get("/folders")
+            .add_header("Authorization", "Bearer jsl_unknown")
+            .await
+            .assert_status(StatusCode::UNAUTHORIZED);
+        // Bearer tokens meant for other services are not ours to check
+        server
+            .get("/folders")
+            .add_header("Authorization", "Bearer abc")
+            .await
+            .assert_status_ok();
+
+        let tokens: Vec<ApiToken> = server.get("/tokens").await.json();
+        assert!(tokens[0].last_used_at.is_some());
+
+        server
+            .delete(&format!("/tokens/{}", created.info.id))
+            .await
+            .assert_status(StatusCode::NO_CONTENT);
+        server
+            .get("/folders")
+            .add_header("Authorization", bearer.as_str())
+            .await
+            .assert_status(StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn test_webhook_deliveries_need_no_token() {
+        let pool = db::create_test_pool().await;
+        let api = crate::webhooks::routes(pool.clone())
+            .layer(axum::middleware::from_fn_with_state(
+                pool.clone(),
+                authenticate,
+            ))
+            .layer(axum::middleware::from_fn(
+                |mut request: Request, next: Next| async move {
+                    request
+                        .extensions_mut()
+                        .insert(ConnectInfo(SocketAddr::from(([10, 0, 0, 5], 40000))));
+                    next.run(request).await
+                },
+            ));
+        let remote = TestServer::new(api).unwrap();
+        let local = TestServer::new(routes(pool.clone())).unwrap();
+        local
+            .post("/tokens")
+            .json(&json!({ "name": "Reader", "scopes": ["read"] }))
+            .await
+            .assert_status_success();
+
+        remote
+            .post("/hooks/orders")
+            .json(&json!({ "job_id": "1" }))
+            .await
+            .assert_status(StatusCode::ACCEPTED);
+        remote
+            .get("/hooks/orders/deliveries")
+            .await
+            .assert_status(StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn test_remote_calls_need_a_token_once_tokens_exist() {
+        let pool = db::create_test_pool().await;
+        // Calls from another machine, as seen through the connection info
+        let api = routes(pool.clone())
+            .layer(axum::middleware::from_fn_with_state(
+                pool.clone(),
+                authenticate,
+            ))
+            .layer(axum::middleware::from_fn(
+                |mut request: Request, next: Next| async move {
+                    request
+                        .extensions_mut()
+                        .insert(ConnectInfo(SocketAddr::from(([10, 0, 0, 5], 40000))));
+                    next.run(request).await
+                },
+            ));
+        let remote = TestServer::new(api).unwrap();
+        let local = TestServer::new(routes(pool.clone())).unwrap();
+
+        remote.get("/tokens").await.assert_status_ok();
+        let created: CreatedToken = local
+            .post("/tokens")
+            .json(&json!({ "name": "Reader", "scopes": ["read"] }))
+            .await
+            .json();
+
+        remote
+            .get("/tokens")
+            .await
+            .assert_status(StatusCode::UNAUTHORIZED);
+        remote
+            .get("/tokens")
+            .add_header("Authorization", format!("Bearer {}", created.token))
+            .await
+            .assert_status(StatusCode::FORBIDDEN);
+    }
+}
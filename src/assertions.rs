@@ -0,0 +1,210 @@
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+
+use crate::{capture, json_schema, scripting::TestResult};
+
+/// A check on the response, stored on a request as part of a JSON array in
+/// its `assertions` column.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Assertion {
+    Status {
+        equals: u16,
+    },
+    /// The header is present and, with `matches`, matches that regular
+    /// expression
+    Header {
+        name: String,
+        #[serde(default)]
+        matches: Option<String>,
+    },
+    /// The value at a JSONPath of the body exists and, with `equals`, equals
+    /// that JSON value
+    JsonPath {
+        path: String,
+        #[serde(default)]
+        equals: Option<Value>,
+    },
+    /// Milliseconds from sending the request to receiving the full body
+    MaxTime {
+        ms: i64,
+    },
+    /// The body validates against the schema, see json_schema for the
+    /// supported keywords
+    JsonSchema {
+        schema: Value,
+    },
+}
+
+impl Assertion {
+    /// Name of the assertion as shown with its result.
+    fn name(&self) -> String {
+        match self {
+            Assertion::Status { equals } => format!("Status is {}", equals),
+            Assertion::Header {
+                name,
+                matches: Some(pattern),
+            } => format!("Header {} matches {}", name, pattern),
+            Assertion::Header {
+                name,
+                matches: None,
+            } => format!("Header {} is present", name),
+            Assertion::JsonPath {
+                path,
+                equals: Some(expected),
+            } => format!("{} equals {}", path, expected),
+            Assertion::JsonPath { path, equals: None } => format!("{} exists", path),
+            Assertion::MaxTime { ms } => format!("Response time is at most {} ms", ms),
+            Assertion::JsonSchema { .. } => "Body matches the JSON schema".to_string(),
+        }
+    }
+
+    fn check(
+        &self,
+        status: u16,
+        headers: &HashMap<String, String>,
+        body: &str,
+        duration: i64,
+    ) -> Result<(), String> {
+        match self {
+            Assertion::Status { equals } => {
+                if status != *equals {
+                    return Err(format!("Got status {}", status));
+                }
+            }
+            Assertion::Header { name, matches } => {
+                let value = headers
+                    .iter()
+                    .find(|(header, _)| header.eq_ignore_ascii_case(name.trim()))
+                    .map(|(_, value)| value)
+                    .ok_or_else(|| "Header is missing".to_string())?;
+                if let Some(pattern) = matches {
+                    let regex = Regex::new(pattern).map_err(|e| e.to_string())?;
+                    if !regex.is_match(value) {
+                        return Err(format!("Got '{}'", value));
+                    }
+                }
+            }
+            Assertion::JsonPath { path, equals } => {
+                let json = parse_body(body)?;
+                let actual = capture::select_path(&json, path)?
+                    .ok_or_else(|| "No value at this path".to_string())?;
+                if let Some(expected) = equals {
+                    if !json_schema::equal(expected, actual) {
+                        return Err(format!("Got {}", actual));
+                    }
+                }
+            }
+            Assertion::MaxTime { ms } => {
+                if duration > *ms {
+                    return Err(format!("Took {} ms", duration));
+                }
+            }
+            Assertion::JsonSchema { schema } => {
+                json_schema::validate(schema, &parse_body(body)?)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn parse_body(body: &str) -> Result<Value, String> {
+    serde_json::from_str(body).map_err(|e| format!("Body is not JSON: {}", e))
+}
+
+/// Parses the `assertions` column of a request, rejecting malformed ones.
+pub fn parse(assertions: &str) -> Result<Vec<Assertion>, String> {
+    if assertions.trim().is_empty() {
+        return Ok(Vec::new());
+    }
+    let assertions: Vec<Assertion> =
+        serde_json::from_str(assertions).map_err(|e| format!("Invalid assertions: {}", e))?;
+    for assertion in &assertions {
+        match assertion {
+            Assertion::Header { name, matches } => {
+                if name.trim().is_empty() {
+                    return Err("Header assertions need a header name".to_string());
+                }
+                if let Some(pattern) = matches {
+                    Regex::new(pattern)
+                        .map_err(|e| format!("Invalid regular expression: {}", e))?;
+                }
+            }
+            Assertion::JsonPath { path, .. } => capture::validate_json_path(path)?,
+            Assertion::MaxTime { ms } if *ms < 0 => {
+                return Err("Maximum response time must not be negative".to_string())
+            }
+            Assertion::JsonSchema { schema } => json_schema::check(schema)?,
+            _ => {}
+        }
+    }
+    Ok(assertions)
+}
+
+/// Evaluates every assertion against a response, in order.
+pub fn evaluate(
+    assertions: &[Assertion],
+    status: u16,
+    headers: &HashMap<String, String>,
+    body: &str,
+    duration: i64,
+) -> Vec<TestResult> {
+    assertions
+        .iter()
+        .map(|assertion| {
+            let outcome = assertion.check(status, headers, body, duration);
+            TestResult {
+                name: assertion.name(),
+                passed: outcome.is_ok(),
+                error: outcome.err(),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_and_evaluate() {
+        let assertions = parse(
+            r#"[
+                {"type": "status", "equals": 200},
+                {"type": "header", "name": "content-type", "matches": "json"},
+                {"type": "header", "name": "ETag"},
+                {"type": "json_path", "path": "$.items[0].id", "equals": 7},
+                {"type": "json_path", "path": "$.next"},
+                {"type": "max_time", "ms": 100},
+                {"type": "json_schema", "schema": {"type": "object", "required": ["items"]}}
+            ]"#,
+        )
+        .unwrap();
+        let headers = HashMap::from([("Content-Type".to_string(), "application/json".to_string())]);
+        let body = r#"{"items":[{"id":7.0}],"total":1}"#;
+
+        let results = evaluate(&assertions, 200, &headers, body, 250);
+        let passed: Vec<bool> = results.iter().map(|r| r.passed).collect();
+        assert_eq!(passed, [true, true, false, true, false, false, true]);
+        assert_eq!(results[0].name, "Status is 200");
+        assert_eq!(results[2].error.as_deref(), Some("Header is missing"));
+        assert_eq!(results[4].error.as_deref(), Some("No value at this path"));
+        assert_eq!(results[5].error.as_deref(), Some("Took 250 ms"));
+
+        let results = evaluate(&assertions, 500, &headers, "oops", 10);
+        assert_eq!(results[0].error.as_deref(), Some("Got status 500"));
+        assert!(results[3]
+            .error
+            .as_deref()
+            .unwrap()
+            .starts_with("Body is not JSON"));
+
+        assert!(parse("").unwrap().is_empty());
+        assert!(parse(r#"[{"type": "status"}]"#).is_err());
+        assert!(parse(r#"[{"type": "json_path", "path": "items"}]"#).is_err());
+        assert!(parse(r#"[{"type": "header", "name": "X", "matches": "("}]"#).is_err());
+        assert!(parse(r#"[{"type": "json_schema", "schema": {"format": "uri"}}]"#).is_err());
+    }
+}
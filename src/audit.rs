@@ -0,0 +1,629 @@
+use std::net::SocketAddr;
+
+use axum::{
+    async_trait,
+    extract::{ConnectInfo, FromRequestParts, Path, Query, State},
+    http::{request::Parts, StatusCode},
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Json, Router,
+};
+use chrono::{DateTime, NaiveDateTime, Utc};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{
+    api_tokens::TokenIdentity,
+    db::DbPool,
+    environments::{self, EnvironmentDb},
+    events::{self, ChangeEvent},
+    folders::{self, FolderDb},
+    i18n,
+    requests::{self, RequestDb},
+};
+
+/// Header naming who made a change; the client IP is recorded without it.
+pub const ACTOR_HEADER: &str = "x-jslink-actor";
+const MAX_ACTOR_LEN: usize = 100;
+const DEFAULT_LIMIT: i64 = 100;
+const MAX_LIMIT: i64 = 1000;
+
+/// Who made a change: the API token used (`token:<name>`), else the
+/// `X-JSLink-Actor` header (`header:<name>`, as clients may claim any name),
+/// else the client IP.
+#[derive(Debug, Clone)]
+pub struct Actor(pub String);
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for Actor {
+    type Rejection = std::convert::Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        if let Some(token) = parts.extensions.get::<TokenIdentity>() {
+            return Ok(Actor(format!("token:{}", token.name)));
+        }
+        let named = parts
+            .headers
+            .get(ACTOR_HEADER)
+            .and_then(|v| v.to_str().ok())
+            .map(str::trim)
+            .filter(|v| !v.is_empty())
+            .map(|v| v.chars().take(MAX_ACTOR_LEN).collect::<String>());
+        if let Some(name) = named {
+            return Ok(Actor(format!("header:{}", name)));
+        }
+        // Requests without connection info (e.g. in-process tests) are unknown
+        let ip = parts
+            .extensions
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|info| info.0.ip().to_string());
+        Ok(Actor(ip.unwrap_or_else(|| "unknown".to_string())))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Entity {
+    Folder,
+    Request,
+    Environment,
+}
+
+impl Entity {
+    fn as_str(self) -> &'static str {
+        match self {
+            Entity::Folder => "folder",
+            Entity::Request => "request",
+            Entity::Environment => "environment",
+        }
+    }
+
+    fn parse(s: &str) -> Option<Self> {
+        match s {
+            "folder" => Some(Entity::Folder),
+            "request" => Some(Entity::Request),
+            "environment" => Some(Entity::Environment),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Action {
+    Create,
+    Update,
+    Archive,
+    Unarchive,
+    Delete,
+    Revert,
+}
+
+impl Action {
+    fn as_str(self) -> &'static str {
+        match self {
+            Action::Create => "create",
+            Action::Update => "update",
+            Action::Archive => "archive",
+            Action::Unarchive => "unarchive",
+            Action::Delete => "delete",
+            Action::Revert => "revert",
+        }
+    }
+}
+
+/// One recorded change. Snapshots are the stored rows, so secrets in them
+/// stay encrypted.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AuditEntry {
+    pub id: i64,
+    pub entity: String,
+    pub entity_id: i64,
+    pub action: String,
+    pub actor: String,
+    pub before: Option<Value>,
+    pub after: Option<Value>,
+    pub created_at: DateTime<Utc>,
+}
+
+struct AuditEntryDb {
+    id: i64,
+    entity: String,
+    entity_id: i64,
+    action: String,
+    actor: String,
+    before: Option<String>,
+    after: Option<String>,
+    created_at: NaiveDateTime,
+}
+
+impl From<AuditEntryDb> for AuditEntry {
+    fn from(e: AuditEntryDb) -> Self {
+        let parse = |s: Option<String>| s.and_then(|s| serde_json::from_str(&s).ok());
+        Self {
+            id: e.id,
+            entity: e.entity,
+            entity_id: e.entity_id,
+            action: e.action,
+            actor: e.actor,
+            before: parse(e.before),
+            after: parse(e.after),
+            created_at: e.created_at.and_utc(),
+        }
+    }
+}
+
+/// Filters of the log listing; all are optional.
+#[derive(Debug, Default, Deserialize)]
+pub struct AuditQuery {
+    entity: Option<Entity>,
+    entity_id: Option<i64>,
+    action: Option<Action>,
+    actor: Option<String>,
+    limit: Option<i64>,
+}
+
+#[derive(Debug)]
+pub enum AuditError {
+    EntryNotFound,
+    /// The snapshot refers to a folder or parent environment that is gone
+    RevertConflict,
+    DatabaseError(#[allow(dead_code)] sqlx::Error),
+}
+
+impl From<sqlx::Error> for AuditError {
+    fn from(e: sqlx::Error) -> Self {
+        match e {
+            sqlx::Error::RowNotFound => AuditError::EntryNotFound,
+            sqlx::Error::Database(ref d) if d.is_foreign_key_violation() => {
+                AuditError::RevertConflict
+            }
+            _ => AuditError::DatabaseError(e),
+        }
+    }
+}
+
+impl IntoResponse for AuditError {
+    fn into_response(self) -> Response {
+        match self {
+            AuditError::EntryNotFound => (
+                StatusCode::NOT_FOUND,
+                i18n::t("error.audit_entry_not_found"),
+            )
+                .into_response(),
+            AuditError::RevertConflict => {
+                (StatusCode::CONFLICT, i18n::t("error.audit_revert_conflict")).into_response()
+            }
+            AuditError::DatabaseError(_) => {
+                (StatusCode::INTERNAL_SERVER_ERROR, i18n::t("error.database")).into_response()
+            }
+        }
+    }
+}
+
+/// Records a change and announces it to `/api/events` subscribers. Failures
+/// are logged rather than returned, since the change itself has already been
+/// made.
+pub async fn record<T: Serialize>(
+    pool: &DbPool,
+    actor: &Actor,
+    entity: Entity,
+    entity_id: i64,
+    action: Action,
+    before: Option<&T>,
+    after: Option<&T>,
+) {
+    let json = |row: Option<&T>| row.and_then(|r| serde_json::to_string(r).ok());
+    let (before, after) = (json(before), json(after));
+    let entity = entity.as_str();
+    let action = action.as_str();
+    let result = sqlx::query!(
+        "INSERT INTO audit_log (entity, entity_id, action, actor, before, after) VALUES (?, ?, ?, ?, ?, ?)",
+        entity,
+        entity_id,
+        action,
+        actor.0,
+        before,
+        after
+    )
+    .execute(pool)
+    .await;
+    if let Err(e) = result {
+        log::warn!(
+            "Failed to record {} of {} {}: {}",
+            action,
+            entity,
+            entity_id,
+            e
+        );
+    }
+    events::publish(ChangeEvent {
+        entity: entity.to_string(),
+        entity_id,
+        action: action.to_string(),
+        actor: actor.0.clone(),
+        at: Utc::now(),
+    });
+}
+
+/// `Ok(None)` for a row that does not exist.
+pub fn found<T>(result: Result<T, sqlx::Error>) -> Result<Option<T>, sqlx::Error> {
+    match result {
+        Ok(row) => Ok(Some(row)),
+        Err(sqlx::Error::RowNotFound) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+async fn list_entries(
+    State(pool): State<DbPool>,
+    Query(query): Query<AuditQuery>,
+) -> Result<impl IntoResponse, AuditError> {
+    let entity = query.entity.map(Entity::as_str);
+    let action = query.action.map(Action::as_str);
+    let limit = query.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+    let entries = sqlx::query_as!(
+        AuditEntryDb,
+        r#"SELECT id as "id!", entity, entity_id, action, actor, before, after, created_at as "created_at: NaiveDateTime"
+        FROM audit_log
+        WHERE (? IS NULL OR entity = ?)
+            AND (? IS NULL OR entity_id = ?)
+            AND (? IS NULL OR action = ?)
+            AND (? IS NULL OR actor = ?)
+        ORDER BY id DESC
+        LIMIT ?"#,
+        entity,
+        entity,
+        query.entity_id,
+        query.entity_id,
+        action,
+        action,
+        query.actor,
+        query.actor,
+        limit
+    )
+    .fetch_all(&pool)
+    .await?;
+    Ok(Json(
+        entries
+            .into_iter()
+            .map(AuditEntry::from)
+            .collect::<Vec<_>>(),
+    ))
+}
+
+async fn load_entry(pool: &DbPool, id: i64) -> Result<AuditEntry, AuditError> {
+    let entry = sqlx::query_as!(
+        AuditEntryDb,
+        r#"SELECT id as "id!", entity, entity_id, action, actor, before, after, created_at as "created_at: NaiveDateTime"
+        FROM audit_log WHERE id = ?"#,
+        id
+    )
+    .fetch_one(pool)
+    .await?;
+    Ok(entry.into())
+}
+
+async fn get_entry(
+    State(pool): State<DbPool>,
+    Path(id): Path<i64>,
+) -> Result<impl IntoResponse, AuditError> {
+    Ok(Json(load_entry(&pool, id).await?))
+}
+
+/// Puts the row back as it was before the change: a created row is deleted,
+/// a deleted one is inserted again under its old id. The revert is recorded
+/// itself, so it can be reverted in turn.
+async fn revert_entry(
+    State(pool): State<DbPool>,
+    Path(id): Path<i64>,
+    actor: Actor,
+) -> Result<impl IntoResponse, AuditError> {
+    let entry = load_entry(&pool, id).await?;
+    let Some(entity) = Entity::parse(&entry.entity) else {
+        return Err(AuditError::EntryNotFound);
+    };
+    let target = entry.entity_id;
+
+    match entity {
+        Entity::Folder => {
+            let current = found(folders::load_folder(&pool, target).await)?;
+            let restored = match entry.before {
+                Some(before) => Some(restore_folder(&pool, &snapshot(before)?).await?),
+                None => {
+                    sqlx::query!("DELETE FROM folders WHERE id = ?", target)
+                        .execute(&pool)
+                        .await?;
+                    None
+                }
+            };
+            let (before, after) = (current.as_ref(), restored.as_ref());
+            record(&pool, &actor, entity, target, Action::Revert, before, after).await;
+        }
+        Entity::Request => {
+            let current = found(requests::load_request(&pool, target).await)?;
+            let restored = match entry.before {
+                Some(before) => Some(restore_request(&pool, &snapshot(before)?).await?),
+                None => {
+                    sqlx::query!("DELETE FROM requests WHERE id = ?", target)
+                        .execute(&pool)
+                        .await?;
+                    None
+                }
+            };
+            let (before, after) = (current.as_ref(), restored.as_ref());
+            record(&pool, &actor, entity, target, Action::Revert, before, after).await;
+        }
+        Entity::Environment => {
+            let current = found(environments::load_environment(&pool, target).await)?;
+            let restored = match entry.before {
+                Some(before) => Some(restore_environment(&pool, &snapshot(before)?).await?),
+                None => {
+                    sqlx::query!("DELETE FROM environments WHERE id = ?", target)
+                        .execute(&pool)
+                        .await?;
+                    None
+                }
+            };
+            let (before, after) = (current.as_ref(), restored.as_ref());
+            record(&pool, &actor, entity, target, Action::Revert, before, after).await;
+        }
+    }
+
+    log::info!(
+        "Reverted audit entry {} ({} of {} {}) for {}",
+        id,
+        entry.action,
+        entry.entity,
+        target,
+        actor.0
+    );
+    Ok(StatusCode::NO_CONTENT)
+}
+
+fn snapshot<T: DeserializeOwned>(row: Value) -> Result<T, AuditError> {
+    serde_json::from_value(row).map_err(|_| AuditError::EntryNotFound)
+}
+
+async fn restore_folder(pool: &DbPool, f: &FolderDb) -> Result<FolderDb, sqlx::Error> {
+    sqlx::query_as!(
+        FolderDb,
+        "INSERT INTO folders (id, name, variables, description, base_url, created_at, updated_at, archived_at) VALUES (?, ?, ?, ?, ?, ?, ?, ?) ON CONFLICT (id) DO UPDATE SET name = excluded.name, variables = excluded.variables, description = excluded.description, base_url = excluded.base_url, updated_at = excluded.updated_at, archived_at = excluded.archived_at RETURNING id, name, variables, description, base_url, created_at, updated_at, archived_at",
+        f.id,
+        f.name,
+        f.variables,
+        f.description,
+        f.base_url,
+        f.created_at,
+        f.updated_at,
+        f.archived_at
+    )
+    .fetch_one(pool)
+    .await
+}
+
+async fn restore_request(pool: &DbPool, r: &RequestDb) -> Result<RequestDb, sqlx::Error> {
+    sqlx::query_as!(
+        RequestDb,
+        "INSERT INTO requests (id, name, method, url, body, headers, folder_id, request_type, body_type, body_content, auth_type, auth_token, auth_username, auth_password, captures, auth_config, serialized, ip_family, pre_request_script, test_script, callback, unresolved_variables, retry, compression, assertions, description, tls_versions, ssh_tunnel_id, latency_budget_ms, created_at, updated_at, archived_at) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, (SELECT id FROM ssh_tunnels WHERE id = ?), ?, ?, ?, ?) ON CONFLICT (id) DO UPDATE SET name = excluded.name, method = excluded.method, url = excluded.url, body = excluded.body, headers = excluded.headers, folder_id = excluded.folder_id, request_type = excluded.request_type, body_type = excluded.body_type, body_content = excluded.body_content, auth_type = excluded.auth_type, auth_token = excluded.auth_token, auth_username = excluded.auth_username, auth_password = excluded.auth_password, captures = excluded.captures, auth_config = excluded.auth_config, serialized = excluded.serialized, ip_family = excluded.ip_family, pre_request_script = excluded.pre_request_script, test_script = excluded.test_script, callback = excluded.callback, unresolved_variables = excluded.unresolved_variables, retry = excluded.retry, compression = excluded.compression, assertions = excluded.assertions, description = excluded.description, tls_versions = excluded.tls_versions, ssh_tunnel_id = excluded.ssh_tunnel_id, latency_budget_ms = excluded.latency_budget_ms, updated_at = excluded.updated_at, archived_at = excluded.archived_at RETURNING id, name, method, url, body, headers, folder_id, request_type, body_type, body_content, auth_type, auth_token, auth_username, auth_password, captures, auth_config, serialized, ip_family, pre_request_script, test_script, callback, unresolved_variables, retry, compression, assertions, description, tls_versions, ssh_tunnel_id, latency_budget_ms, created_at, updated_at, archived_at",
+        r.id,
+        r.name,
+        r.method,
+        r.url,
+        r.body,
+        r.headers,
+        r.folder_id,
+        r.request_type,
+        r.body_type,
+        r.body_content,
+        r.auth_type,
+        r.auth_token,
+        r.auth_username,
+        r.auth_password,
+        r.captures,
+        r.auth_config,
+        r.serialized,
+        r.ip_family,
+        r.pre_request_script,
+        r.test_script,
+        r.callback,
+        r.unresolved_variables,
+        r.retry,
+        r.compression,
+        r.assertions,
+        r.description,
+        r.tls_versions,
+        r.ssh_tunnel_id,
+        r.latency_budget_ms,
+        r.created_at,
+        r.updated_at,
+        r.archived_at
+    )
+    .fetch_one(pool)
+    .await
+}
+
+async fn restore_environment(
+    pool: &DbPool,
+    e: &EnvironmentDb,
+) -> Result<EnvironmentDb, sqlx::Error> {
+    sqlx::query_as!(
+        EnvironmentDb,
+        "INSERT INTO environments (id, name, variables, secrets, disabled, types, parent_id, created_at, updated_at, archived_at) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?) ON CONFLICT (id) DO UPDATE SET name = excluded.name, variables = excluded.variables, secrets = excluded.secrets, disabled = excluded.disabled, types = excluded.types, parent_id = excluded.parent_id, updated_at = excluded.updated_at, archived_at = excluded.archived_at RETURNING id, name, variables, secrets, disabled, types, parent_id, created_at, updated_at, archived_at",
+        e.id,
+        e.name,
+        e.variables,
+        e.secrets,
+        e.disabled,
+        e.types,
+        e.parent_id,
+        e.created_at,
+        e.updated_at,
+        e.archived_at
+    )
+    .fetch_one(pool)
+    .await
+}
+
+pub fn routes(pool: DbPool) -> Router {
+    Router::new()
+        .route("/audit", get(list_entries))
+        .route("/audit/:id", get(get_entry))
+        .route("/audit/:id/revert", post(revert_entry))
+        .with_state(pool)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db;
+    use axum_test::TestServer;
+    use serde_json::json;
+
+    fn server(pool: &DbPool) -> TestServer {
+        TestServer::new(
+            folders::routes(pool.clone())
+                .merge(requests::routes(pool.clone()))
+                .merge(routes(pool.clone())),
+        )
+        .unwrap()
+    }
+
+    /// Creates the "Orders" folder with a bearer-authenticated request in it.
+    async fn create_folder_with_request(server: &TestServer) -> (i64, i64) {
+        let folder = server
+            .post("/folders")
+            .add_header("X-JSLink-Actor", "alice")
+            .json(&json!({"name": "Orders"}))
+            .await
+            .json::<Value>();
+        let folder_id = folder["id"].as_i64().unwrap();
+        let request = server
+            .post("/requests")
+            .json(&json!({
+                "name": "List",
+                "method": "GET",
+                "url": "https://example.com/orders",
+                "folder_id": folder_id,
+                "auth_type": "bearer",
+                "auth_token": "s3cret"
+            }))
+            .await
+            .json::<Value>();
+        (folder_id, request["id"].as_i64().unwrap())
+    }
+
+    async fn rename_folder(server: &TestServer, folder_id: i64) {
+        server
+            .put(&format!("/folders/{}", folder_id))
+            .add_header("X-JSLink-Actor", "bob")
+            .json(&json!({"name": "Renamed"}))
+            .await
+            .assert_status_ok();
+    }
+
+    #[tokio::test]
+    async fn test_changes_are_recorded() {
+        let pool = db::create_test_pool().await;
+        let server = server(&pool);
+        let (folder_id, _) = create_folder_with_request(&server).await;
+        rename_folder(&server, folder_id).await;
+
+        let entries: Vec<AuditEntry> = server
+            .get(&format!("/audit?entity=folder&entity_id={}", folder_id))
+            .await
+            .json();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].action, "update");
+        assert_eq!(entries[0].actor, "header:bob");
+        assert_eq!(entries[0].before.as_ref().unwrap()["name"], "Orders");
+        assert_eq!(entries[0].after.as_ref().unwrap()["name"], "Renamed");
+        assert_eq!(entries[1].action, "create");
+        assert_eq!(entries[1].actor, "header:alice");
+        assert!(entries[1].before.is_none());
+        server.get("/audit/9999").await.assert_status_not_found();
+    }
+
+    #[tokio::test]
+    async fn test_snapshots_keep_credentials_encrypted() {
+        let pool = db::create_test_pool().await;
+        let server = server(&pool);
+        create_folder_with_request(&server).await;
+
+        let created: Vec<AuditEntry> = server.get("/audit?entity=request").await.json();
+        let token = created[0].after.as_ref().unwrap()["auth_token"]
+            .as_str()
+            .unwrap();
+        assert!(token.starts_with("enc:v1:"));
+    }
+
+    #[tokio::test]
+    async fn test_update_is_reverted() {
+        let pool = db::create_test_pool().await;
+        let server = server(&pool);
+        let (folder_id, _) = create_folder_with_request(&server).await;
+        rename_folder(&server, folder_id).await;
+
+        let entries: Vec<AuditEntry> = server.get("/audit?action=update").await.json();
+        server
+            .post(&format!("/audit/{}/revert", entries[0].id))
+            .await
+            .assert_status(StatusCode::NO_CONTENT);
+        let folder = folders::load_folder(&pool, folder_id).await.unwrap();
+        assert_eq!(folder.name, "Orders");
+    }
+
+    #[tokio::test]
+    async fn test_deleted_folder_is_restored_before_its_requests() {
+        let pool = db::create_test_pool().await;
+        let server = server(&pool);
+        let (folder_id, request_id) = create_folder_with_request(&server).await;
+
+        // Deleting the folder deletes its request; reverting brings both back
+        server
+            .delete(&format!("/folders/{}", folder_id))
+            .await
+            .assert_status(StatusCode::NO_CONTENT);
+        let deletes: Vec<AuditEntry> = server.get("/audit?action=delete").await.json();
+        assert_eq!(deletes.len(), 2);
+        let request_delete = deletes.iter().find(|e| e.entity == "request").unwrap();
+        let folder_delete = deletes.iter().find(|e| e.entity == "folder").unwrap();
+
+        server
+            .post(&format!("/audit/{}/revert", request_delete.id))
+            .await
+            .assert_status(StatusCode::CONFLICT);
+        server
+            .post(&format!("/audit/{}/revert", folder_delete.id))
+            .await
+            .assert_status(StatusCode::NO_CONTENT);
+        server
+            .post(&format!("/audit/{}/revert", request_delete.id))
+            .await
+            .assert_status(StatusCode::NO_CONTENT);
+        let restored = server
+            .get(&format!("/requests/{}", request_id))
+            .await
+            .json::<Value>();
+        assert_eq!(restored["folder_id"], folder_id);
+        assert_eq!(restored["auth_token"], "s3cret");
+
+        let reverts: Vec<AuditEntry> = server.get("/audit?action=revert").await.json();
+        assert_eq!(reverts.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_claimed_actors_are_marked() {
+        let pool = db::create_test_pool().await;
+        let server = TestServer::new(folders::routes(pool.clone()).merge(routes(pool))).unwrap();
+
+        server
+            .post("/folders")
+            .add_header("X-JSLink-Actor", "token:CI")
+            .json(&json!({"name": "Orders"}))
+            .await
+            .assert_status(StatusCode::CREATED);
+        let entries: Vec<AuditEntry> = server.get("/audit").await.json();
+        assert_eq!(entries[0].actor, "header:token:CI");
+    }
+}
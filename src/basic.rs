@@ -0,0 +1,448 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::{Html, IntoResponse, Response},
+    routing::{get, post},
+    Form, Router,
+};
+use sailfish::TemplateOnce;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::sync::OnceLock;
+
+use crate::{
+    api_tokens,
+    db::DbPool,
+    executor::{self, ExecuteRequestPayload, ExecuteResponse, ExecutorError},
+    header_rows, i18n,
+    requests::{self, Request},
+};
+
+// Plain HTML pages under /basic that work without JavaScript, for screen
+// readers and for when the main interface fails to load.
+
+/// Random per-process key the send forms' CSRF tokens are derived from.
+static CSRF_SECRET: OnceLock<String> = OnceLock::new();
+
+/// The token the send form of request `id` must post back. It changes on
+/// every restart, so forms rendered before one have to be reloaded.
+fn csrf_token(id: i64) -> String {
+    let secret = CSRF_SECRET.get_or_init(|| api_tokens::random_token(""));
+    api_tokens::hash(&format!("{}:{}", secret, id))
+}
+
+struct RequestLink {
+    id: i64,
+    name: String,
+    method: String,
+}
+
+struct FolderSection {
+    id: i64,
+    name: String,
+    requests: Vec<RequestLink>,
+}
+
+#[derive(TemplateOnce)]
+#[template(path = "basic/index.stpl")]
+struct IndexPage {
+    title: String,
+    folders: Vec<FolderSection>,
+    unfiled: Vec<RequestLink>,
+}
+
+#[derive(TemplateOnce)]
+#[template(path = "basic/request.stpl")]
+struct RequestPage {
+    title: String,
+    request: Request,
+    headers: BTreeMap<String, String>,
+    body: Option<String>,
+    environments: Vec<(i64, String)>,
+    csrf_token: String,
+}
+
+#[derive(TemplateOnce)]
+#[template(path = "basic/response.stpl")]
+struct ResponsePage {
+    title: String,
+    request_id: i64,
+    response: ExecuteResponse,
+    headers: BTreeMap<String, String>,
+    captured: BTreeMap<String, String>,
+    body: String,
+}
+
+#[derive(TemplateOnce)]
+#[template(path = "basic/error.stpl")]
+struct ErrorPage {
+    title: String,
+    message: String,
+}
+
+pub enum BasicError {
+    NotFound,
+    Forbidden,
+    Execution(ExecutorError),
+    Render(sailfish::RenderError),
+    DatabaseError(#[allow(dead_code)] sqlx::Error),
+}
+
+impl From<sqlx::Error> for BasicError {
+    fn from(e: sqlx::Error) -> Self {
+        match e {
+            sqlx::Error::RowNotFound => BasicError::NotFound,
+            _ => BasicError::DatabaseError(e),
+        }
+    }
+}
+
+impl From<sailfish::RenderError> for BasicError {
+    fn from(e: sailfish::RenderError) -> Self {
+        BasicError::Render(e)
+    }
+}
+
+impl IntoResponse for BasicError {
+    fn into_response(self) -> Response {
+        let (status, title, message) = match self {
+            BasicError::NotFound => (
+                StatusCode::NOT_FOUND,
+                i18n::t("basic.not_found_title"),
+                i18n::t("basic.not_found"),
+            ),
+            BasicError::Forbidden => (
+                StatusCode::FORBIDDEN,
+                i18n::t("basic.form_expired_title"),
+                i18n::t("basic.form_expired"),
+            ),
+            BasicError::Execution(e) => (
+                StatusCode::BAD_GATEWAY,
+                i18n::t("basic.request_failed_title"),
+                i18n::tf("basic.request_failed", &[("message", &e)]),
+            ),
+            BasicError::Render(e) => {
+                log::error!("Failed to render page: {}", e);
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    i18n::t("basic.error_title"),
+                    i18n::t("basic.render_failed"),
+                )
+            }
+            BasicError::DatabaseError(_) => (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                i18n::t("basic.error_title"),
+                i18n::t("error.database"),
+            ),
+        };
+        let page = ErrorPage {
+            title,
+            message: message.clone(),
+        };
+        match page.render_once() {
+            Ok(html) => (status, Html(html)).into_response(),
+            Err(_) => (status, message).into_response(),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct SendForm {
+    #[serde(default)]
+    environment_id: String,
+    #[serde(default)]
+    csrf_token: String,
+}
+
+pub fn routes(pool: DbPool) -> Router {
+    Router::new()
+        .route("/basic", get(index))
+        .route("/basic/requests/:id", get(show_request))
+        .route("/basic/requests/:id/send", post(send_request))
+        .with_state(pool)
+}
+
+async fn index(State(pool): State<DbPool>) -> Result<Html<String>, BasicError> {
+    let mut folders: Vec<FolderSection> = sqlx::query!(
+        "SELECT id, name FROM folders WHERE archived_at IS NULL ORDER BY name COLLATE NOCASE"
+    )
+    .fetch_all(&pool)
+    .await?
+    .into_iter()
+    .map(|f| FolderSection {
+        id: f.id,
+        name: f.name,
+        requests: Vec::new(),
+    })
+    .collect();
+    let requests = sqlx::query!(
+        "SELECT id, name, method, folder_id FROM requests WHERE archived_at IS NULL ORDER BY name COLLATE NOCASE"
+    )
+    .fetch_all(&pool)
+    .await?;
+
+    let mut unfiled = Vec::new();
+    for r in requests {
+        let link = RequestLink {
+            id: r.id,
+            name: r.name,
+            method: r.method,
+        };
+        match folders.iter_mut().find(|f| Some(f.id) == r.folder_id) {
+            Some(folder) => folder.requests.push(link),
+            None => unfiled.push(link),
+        }
+    }
+
+    let page = IndexPage {
+        title: i18n::t("basic.requests"),
+        folders,
+        unfiled,
+    };
+    Ok(Html(page.render_once()?))
+}
+
+async fn show_request(
+    State(pool): State<DbPool>,
+    Path(id): Path<i64>,
+) -> Result<Html<String>, BasicError> {
+    let request = Request::from(requests::load_request(&pool, id).await?);
+    let headers = request
+        .headers
+        .as_deref()
+        .and_then(|h| header_rows::parse(h).ok())
+        .map(|rows| header_rows::enabled_map(&rows).into_iter().collect())
+        .unwrap_or_default();
+    let body = request
+        .body_content
+        .clone()
+        .or_else(|| request.body.clone())
+        .filter(|b| !b.is_empty());
+    let environments = sqlx::query!(
+        "SELECT id, name FROM environments WHERE archived_at IS NULL ORDER BY name COLLATE NOCASE"
+    )
+    .fetch_all(&pool)
+    .await?
+    .into_iter()
+    .map(|e| (e.id, e.name))
+    .collect();
+
+    let page = RequestPage {
+        title: request.name.clone(),
+        request,
+        headers,
+        body,
+        environments,
+        csrf_token: csrf_token(id),
+    };
+    Ok(Html(page.render_once()?))
+}
+
+async fn send_request(
+    State(pool): State<DbPool>,
+    Path(id): Path<i64>,
+    Form(form): Form<SendForm>,
+) -> Result<Html<String>, BasicError> {
+    if form.csrf_token != csrf_token(id) {
+        return Err(BasicError::Forbidden);
+    }
+    let environment_id = form.environment_id.trim().parse().ok();
+    let response = executor::execute(pool, ExecuteRequestPayload::saved(id, environment_id))
+        .await
+        .map_err(|e| match e {
+            ExecutorError::RequestNotFound => BasicError::NotFound,
+            e => BasicError::Execution(e),
+        })?;
+
+    let body = serde_json::from_str::<serde_json::Value>(&response.body)
+        .ok()
+        .and_then(|json| serde_json::to_string_pretty(&json).ok())
+        .unwrap_or_else(|| response.body.clone());
+    let page = ResponsePage {
+        title: i18n::tf("basic.response_title", &[("name", &response.request_name)]),
+        request_id: id,
+        headers: response.headers.clone().into_iter().collect(),
+        captured: response.captured.clone().into_iter().collect(),
+        body,
+        response,
+    };
+    Ok(Html(page.render_once()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::create_test_pool;
+    use axum_test::TestServer;
+    use httpmock::prelude::*;
+
+    async fn create_folder(pool: &DbPool) -> i64 {
+        sqlx::query_scalar!("INSERT INTO folders (name) VALUES ('Users API') RETURNING id")
+            .fetch_one(pool)
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_index_lists_requests_by_folder() {
+        let pool = create_test_pool().await;
+        let folder_id = create_folder(&pool).await;
+        let request_id = sqlx::query_scalar!(
+            "INSERT INTO requests (name, method, url, folder_id) VALUES ('List users', 'GET', 'http://127.0.0.1:9/users', ?) RETURNING id",
+            folder_id
+        )
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+        let app = TestServer::new(routes(pool)).unwrap();
+
+        let index = app.get("/basic").await.text();
+        assert!(index.contains(&format!("<h2 id=\"folder-{}\">Users API</h2>", folder_id)));
+        assert!(index.contains(&format!(
+            "<a href=\"/basic/requests/{}\">GET List users</a>",
+            request_id
+        )));
+    }
+
+    #[tokio::test]
+    async fn test_request_page_shows_the_request() {
+        let pool = create_test_pool().await;
+        let request_id = create_request(&pool).await;
+        let app = TestServer::new(routes(pool)).unwrap();
+
+        let request = app.get(&format!("/basic/requests/{}", request_id)).await;
+        request.assert_status_ok();
+        let html = request.text();
+        assert!(html.contains("http://127.0.0.1:9/ping"));
+        assert!(html.contains(&csrf_token(request_id)));
+    }
+
+    #[tokio::test]
+    async fn test_send_renders_the_escaped_response() {
+        let pool = create_test_pool().await;
+        let server = MockServer::start();
+        server.mock(|when, then| {
+            when.method(GET).path("/users");
+            then.status(200)
+                .header("content-type", "application/json")
+                .body(r#"{"name":"<b>Ada</b>"}"#);
+        });
+        let url = server.url("/users");
+        let request_id = sqlx::query_scalar!(
+            "INSERT INTO requests (name, method, url) VALUES ('List users', 'GET', ?) RETURNING id",
+            url
+        )
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+        let app = TestServer::new(routes(pool)).unwrap();
+
+        let token = csrf_token(request_id);
+        let response = app
+            .post(&format!("/basic/requests/{}/send", request_id))
+            .form(&[("environment_id", ""), ("csrf_token", token.as_str())])
+            .await;
+        response.assert_status_ok();
+        let html = response.text();
+        assert!(html.contains("<strong>200</strong>"));
+        assert!(html.contains("&lt;b&gt;Ada&lt;/b&gt;"));
+    }
+
+    #[tokio::test]
+    async fn test_missing_request_is_not_found() {
+        let pool = create_test_pool().await;
+        let app = TestServer::new(routes(pool)).unwrap();
+
+        let response = app.get("/basic/requests/999").await;
+        response.assert_status(StatusCode::NOT_FOUND);
+        let html = response.text();
+        assert!(html.contains("<html lang=\"en\">"));
+        assert!(html.contains("This request does not exist."));
+    }
+
+    async fn create_request(pool: &DbPool) -> i64 {
+        sqlx::query_scalar!(
+            "INSERT INTO requests (name, method, url) VALUES ('Ping', 'GET', 'http://127.0.0.1:9/ping') RETURNING id"
+        )
+        .fetch_one(pool)
+        .await
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_foreign_origins_cannot_send() {
+        let pool = create_test_pool().await;
+        let request_id = create_request(&pool).await;
+        let app = TestServer::new(crate::guard(routes(pool.clone()), &pool, "127.0.0.1")).unwrap();
+        let send = format!("/basic/requests/{}/send", request_id);
+        let token = csrf_token(request_id);
+
+        app.post(&send)
+            .add_header("Host", "localhost:3000")
+            .add_header("Origin", "https://evil.example")
+            .form(&[("environment_id", ""), ("csrf_token", token.as_str())])
+            .await
+            .assert_status(StatusCode::FORBIDDEN);
+        app.post(&send)
+            .add_header("Host", "evil.test:3000")
+            .add_header("Origin", "http://evil.test:3000")
+            .form(&[("environment_id", ""), ("csrf_token", token.as_str())])
+            .await
+            .assert_status(StatusCode::MISDIRECTED_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_send_needs_the_form_token() {
+        let pool = create_test_pool().await;
+        let request_id = create_request(&pool).await;
+        let other_id = create_request(&pool).await;
+        let app = TestServer::new(routes(pool)).unwrap();
+        let send = format!("/basic/requests/{}/send", request_id);
+
+        app.post(&send)
+            .form(&[("environment_id", "")])
+            .await
+            .assert_status(StatusCode::FORBIDDEN);
+        let other_token = csrf_token(other_id);
+        app.post(&send)
+            .form(&[("environment_id", ""), ("csrf_token", other_token.as_str())])
+            .await
+            .assert_status(StatusCode::FORBIDDEN);
+        // Nothing listens on the target, so getting as far as sending means the token was accepted
+        let token = csrf_token(request_id);
+        app.post(&send)
+            .form(&[("environment_id", ""), ("csrf_token", token.as_str())])
+            .await
+            .assert_status(StatusCode::BAD_GATEWAY);
+    }
+
+    #[tokio::test]
+    async fn test_send_needs_the_execute_scope() {
+        let pool = create_test_pool().await;
+        let request_id = create_request(&pool).await;
+        let read_token = "jsl_basic-test-reader";
+        let read_hash = api_tokens::hash(read_token);
+        sqlx::query!(
+            "INSERT INTO api_tokens (name, token_hash, prefix, scopes) VALUES ('Reader', ?, 'jsl_basic', '[\"read\"]')",
+            read_hash
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        let app = TestServer::new(crate::guard(routes(pool.clone()), &pool, "127.0.0.1")).unwrap();
+        let bearer = format!("Bearer {}", read_token);
+
+        app.get(&format!("/basic/requests/{}", request_id))
+            .add_header("Host", "localhost:3000")
+            .add_header("Authorization", bearer.as_str())
+            .await
+            .assert_status_ok();
+        let token = csrf_token(request_id);
+        app.post(&format!("/basic/requests/{}/send", request_id))
+            .add_header("Host", "localhost:3000")
+            .add_header("Authorization", bearer.as_str())
+            .form(&[("environment_id", ""), ("csrf_token", token.as_str())])
+            .await
+            .assert_status(StatusCode::FORBIDDEN);
+    }
+}
@@ -0,0 +1,218 @@
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::sync::OnceLock;
+
+use crate::{form_body, i18n};
+
+/// A problem found in a body. Positions are 1-based and present when the
+/// problem is at a specific place.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BodyIssue {
+    pub message: String,
+    pub line: Option<usize>,
+    pub column: Option<usize>,
+}
+
+impl BodyIssue {
+    fn at(message: impl Into<String>, line: usize, column: usize) -> Self {
+        BodyIssue {
+            message: message.into(),
+            line: Some(line),
+            column: Some(column),
+        }
+    }
+
+    fn general(message: impl Into<String>) -> Self {
+        BodyIssue {
+            message: message.into(),
+            line: None,
+            column: None,
+        }
+    }
+}
+
+/// Checks `body` against what its `body_type` says it is. Types without a
+/// syntax (text, binary, ...) never have issues.
+pub fn lint(body_type: &str, body: &str) -> Vec<BodyIssue> {
+    match body_type {
+        "json" => lint_json(body),
+        "xml" => lint_xml(body),
+        "form" => lint_form(body),
+        _ => Vec::new(),
+    }
+}
+
+/// Replaces `{{name}}` placeholders by a number padded to the same length,
+/// so a body is judged as it will be sent and positions stay the same.
+fn mask_placeholders(body: &str) -> String {
+    static PLACEHOLDER: OnceLock<Regex> = OnceLock::new();
+    let placeholder = PLACEHOLDER.get_or_init(|| Regex::new(r"\{\{[^{}\n]*\}\}").unwrap());
+    placeholder
+        .replace_all(body, |caps: &regex::Captures| {
+            format!("0{}", " ".repeat(caps[0].len() - 1))
+        })
+        .into_owned()
+}
+
+fn lint_json(body: &str) -> Vec<BodyIssue> {
+    if body.trim().is_empty() {
+        return Vec::new();
+    }
+    match serde_json::from_str::<serde::de::IgnoredAny>(&mask_placeholders(body)) {
+        Ok(_) => Vec::new(),
+        Err(e) => {
+            // serde_json appends the position to its message
+            let message = e.to_string();
+            let message = message
+                .rsplit_once(" at line ")
+                .map_or(message.as_str(), |(m, _)| m);
+            vec![BodyIssue::at(message, e.line(), e.column().max(1))]
+        }
+    }
+}
+
+fn lint_xml(body: &str) -> Vec<BodyIssue> {
+    if body.trim().is_empty() {
+        return Vec::new();
+    }
+    match roxmltree::Document::parse(body) {
+        Ok(_) => Vec::new(),
+        Err(e) => {
+            let pos = e.pos();
+            let message = e.to_string();
+            let message = message
+                .rsplit_once(" at ")
+                .map_or(message.as_str(), |(m, _)| m);
+            vec![BodyIssue::at(message, pos.row as usize, pos.col as usize)]
+        }
+    }
+}
+
+/// Form bodies are saved as JSON rows, but a pasted `a=1&b=2` string is
+/// checked for its encoding too.
+fn lint_form(body: &str) -> Vec<BodyIssue> {
+    let trimmed = body.trim_start();
+    if trimmed.starts_with('[') || trimmed.starts_with('{') {
+        return match form_body::parse(body) {
+            Ok(rows) => rows
+                .iter()
+                .enumerate()
+                .filter(|(_, row)| row.enabled && row.key.trim().is_empty())
+                .map(|(i, _)| {
+                    BodyIssue::general(i18n::tf("lint.form_row_without_key", &[("row", &(i + 1))]))
+                })
+                .collect(),
+            Err(e) => vec![BodyIssue::general(e)],
+        };
+    }
+    lint_urlencoded(body)
+}
+
+fn lint_urlencoded(body: &str) -> Vec<BodyIssue> {
+    let mut issues = Vec::new();
+    for (line_index, line) in body.lines().enumerate() {
+        let line_no = line_index + 1;
+        if line_index > 0 && !line.is_empty() {
+            issues.push(BodyIssue::at(
+                i18n::t("lint.unencoded_line_break"),
+                line_no,
+                1,
+            ));
+        }
+        let mut offset = 0;
+        for pair in line.split('&') {
+            let column = line[..offset].chars().count() + 1;
+            if pair.is_empty() && line.len() > offset {
+                issues.push(BodyIssue::at(
+                    i18n::t("lint.empty_form_field"),
+                    line_no,
+                    column,
+                ));
+            } else if pair.starts_with('=') {
+                issues.push(BodyIssue::at(
+                    i18n::t("lint.unnamed_form_field"),
+                    line_no,
+                    column,
+                ));
+            }
+            let chars: Vec<(usize, char)> = pair.char_indices().collect();
+            for (i, &(byte, c)) in chars.iter().enumerate() {
+                let column = line[..offset + byte].chars().count() + 1;
+                match c {
+                    '%' => {
+                        let escape = chars.get(i + 1..i + 3);
+                        if !escape.is_some_and(|e| e.iter().all(|(_, c)| c.is_ascii_hexdigit())) {
+                            issues.push(BodyIssue::at(
+                                i18n::t("lint.bad_percent_escape"),
+                                line_no,
+                                column,
+                            ));
+                        }
+                    }
+                    ' ' | '\t' => issues.push(BodyIssue::at(
+                        i18n::t("lint.unencoded_whitespace"),
+                        line_no,
+                        column,
+                    )),
+                    _ => {}
+                }
+            }
+            offset += pair.len() + 1;
+        }
+    }
+    issues
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lint_json() {
+        assert!(lint("json", r#"{"id": {{id}}, "name": "{{name}}"}"#).is_empty());
+        assert!(lint("json", "").is_empty());
+
+        let issues = lint("json", "{\n  \"a\": 1,\n  \"b\" 2\n}");
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].message, "expected `:`");
+        assert_eq!((issues[0].line, issues[0].column), (Some(3), Some(7)));
+    }
+
+    #[test]
+    fn test_lint_xml() {
+        assert!(lint("xml", "<a><b>{{value}}</b></a>").is_empty());
+        let issues = lint("xml", "<a>\n<b></a>");
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].line, Some(2));
+        assert!(issues[0].message.contains("expected 'b' tag"));
+    }
+
+    #[test]
+    fn test_lint_form() {
+        assert!(lint("form", "a=1&b=hello+world&c=%20").is_empty());
+        assert!(lint("form", r#"[{"key":"a","value":"1"}]"#).is_empty());
+
+        let issues = lint("form", "a=1 2&&=3&d=%G1");
+        let found: Vec<_> = issues
+            .iter()
+            .map(|i| (i.message.as_str(), i.column))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                ("Whitespace must be encoded as '+' or %20", Some(4)),
+                ("Empty field between '&'", Some(7)),
+                ("Field has no name", Some(8)),
+                ("'%' must be followed by two hex digits", Some(13)),
+            ]
+        );
+
+        let issues = lint(
+            "form",
+            r#"[{"key":"","value":"x"},{"key":"","enabled":false}]"#,
+        );
+        assert_eq!(issues, vec![BodyIssue::general("Row 1 has no key")]);
+
+        assert!(lint("text", "{ not json").is_empty());
+    }
+}
@@ -0,0 +1,224 @@
+use axum::{routing::get, Json, Router};
+use serde::Serialize;
+
+use crate::{dynamic_vars, i18n};
+
+// What this server supports, so the UI and external tools can feature-detect
+// instead of assuming a server version. Keep in sync with the executor's
+// body and auth handling and with the importers.
+
+#[derive(Debug, Serialize)]
+pub struct AuthType {
+    pub name: &'static str,
+    /// Request fields holding the credentials
+    pub fields: &'static [&'static str],
+    /// Whether non-secret settings are read from `auth_config`
+    pub uses_auth_config: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct VariableSource {
+    pub name: &'static str,
+    pub description: &'static str,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DynamicVariables {
+    /// Placeholder syntax, `name` standing for the variable name
+    pub syntax: &'static str,
+    /// Request fields in which placeholders are substituted
+    pub substituted_in: &'static [&'static str],
+    pub sources: Vec<VariableSource>,
+    /// Built-in `{{$name}}` variables, generated anew for each placeholder
+    pub generators: &'static [&'static str],
+    /// Ways of handling placeholders nothing matches, see
+    /// unresolved::UnresolvedMode
+    pub unresolved_modes: &'static [&'static str],
+}
+
+#[derive(Debug, Serialize)]
+pub struct FileFormat {
+    pub name: &'static str,
+    pub versions: &'static [&'static str],
+    /// What the file holds: collections, environments or both
+    pub contents: &'static str,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Capabilities {
+    pub version: &'static str,
+    pub request_types: &'static [&'static str],
+    pub body_types: &'static [&'static str],
+    pub auth_types: Vec<AuthType>,
+    pub dynamic_variables: DynamicVariables,
+    /// Where capture rules can take values from, see capture::CaptureSource
+    pub capture_sources: &'static [&'static str],
+    /// Script hooks run by the executor, in JavaScript
+    pub scripts: &'static [&'static str],
+    pub import_formats: Vec<FileFormat>,
+    pub export_formats: Vec<FileFormat>,
+    pub languages: Vec<&'static str>,
+}
+
+pub fn capabilities() -> Capabilities {
+    Capabilities {
+        version: env!("CARGO_PKG_VERSION"),
+        request_types: &["api", "ws"],
+        body_types: &["none", "json", "xml", "text", "form", "multipart", "binary"],
+        auth_types: vec![
+            AuthType {
+                name: "none",
+                fields: &[],
+                uses_auth_config: false,
+            },
+            AuthType {
+                name: "bearer",
+                fields: &["auth_token"],
+                uses_auth_config: false,
+            },
+            AuthType {
+                name: "basic",
+                fields: &["auth_username", "auth_password"],
+                uses_auth_config: false,
+            },
+            AuthType {
+                name: "oauth2",
+                fields: &["auth_token"],
+                uses_auth_config: false,
+            },
+            AuthType {
+                name: "jwt",
+                fields: &["auth_password"],
+                uses_auth_config: true,
+            },
+            AuthType {
+                name: "chained",
+                fields: &[],
+                uses_auth_config: true,
+            },
+            AuthType {
+                name: "aws_sigv4",
+                fields: &["auth_username", "auth_password", "auth_token"],
+                uses_auth_config: true,
+            },
+        ],
+        dynamic_variables: DynamicVariables {
+            syntax: "{{name}}",
+            substituted_in: &[
+                "url",
+                "headers",
+                "body",
+                "auth_token",
+                "auth_username",
+                "auth_password",
+                "auth_config",
+            ],
+            sources: vec![
+                VariableSource {
+                    name: "global",
+                    description: "Variables shared by all environments, which override them",
+                },
+                VariableSource {
+                    name: "folder",
+                    description: "Variables of the request's folder, which override the globals",
+                },
+                VariableSource {
+                    name: "environment",
+                    description: "Variables of the environment selected for the execution",
+                },
+                VariableSource {
+                    name: "capture",
+                    description: "Values captured from earlier responses into the environment",
+                },
+            ],
+            generators: dynamic_vars::NAMES,
+            unresolved_modes: &["error", "warn", "keep"],
+        },
+        capture_sources: &["body", "header", "status", "xpath", "regex"],
+        scripts: &["pre_request", "test"],
+        import_formats: vec![
+            FileFormat {
+                name: "postman",
+                versions: &["1.0", "2.0", "2.1"],
+                contents: "collections",
+            },
+            FileFormat {
+                name: "postman_environment",
+                versions: &["environment", "globals"],
+                contents: "environments",
+            },
+            FileFormat {
+                name: "postman_data_dump",
+                versions: &["zip"],
+                contents: "collections,environments",
+            },
+            FileFormat {
+                name: "insomnia",
+                versions: &["4", "5"],
+                contents: "collections",
+            },
+            FileFormat {
+                name: "thunder_client",
+                versions: &["1"],
+                contents: "collections",
+            },
+            FileFormat {
+                name: "dotenv",
+                versions: &[],
+                contents: "environments",
+            },
+        ],
+        export_formats: vec![
+            FileFormat {
+                name: "js-link-bundle",
+                versions: &["1"],
+                contents: "collections,environments",
+            },
+            FileFormat {
+                name: "postman_environment",
+                versions: &["environment"],
+                contents: "environments",
+            },
+        ],
+        languages: i18n::languages().map(|l| l.code).collect(),
+    }
+}
+
+pub fn routes() -> Router {
+    Router::new().route("/capabilities", get(get_capabilities))
+}
+
+async fn get_capabilities() -> Json<Capabilities> {
+    Json(capabilities())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum_test::TestServer;
+    use serde_json::Value;
+
+    #[tokio::test]
+    async fn test_capabilities() {
+        let server = TestServer::new(routes()).unwrap();
+        let body: Value = server.get("/capabilities").await.json();
+
+        assert_eq!(body["version"], env!("CARGO_PKG_VERSION"));
+        assert!(body["body_types"]
+            .as_array()
+            .unwrap()
+            .contains(&Value::from("multipart")));
+        let jwt = body["auth_types"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|a| a["name"] == "jwt")
+            .unwrap();
+        assert_eq!(jwt["uses_auth_config"], true);
+        assert_eq!(body["dynamic_variables"]["syntax"], "{{name}}");
+        assert!(body["languages"]
+            .as_array()
+            .unwrap()
+            .contains(&Value::from("de")));
+    }
+}
@@ -0,0 +1,521 @@
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+
+use crate::{db::DbPool, environments};
+
+/// Copies a value from a response into an environment variable.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CaptureRule {
+    pub variable: String,
+    pub source: CaptureSource,
+    /// JSONPath for `body` (`$.data.items[0].id`), header name for `header`,
+    /// XPath for `xpath` (`/order/item[2]/@id`) and a regular expression for
+    /// `regex`, capturing its first group or else the whole match.
+    #[serde(default)]
+    pub expression: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CaptureSource {
+    Body,
+    Header,
+    Status,
+    /// XML response bodies
+    Xpath,
+    /// Any response body, such as HTML or plain text
+    Regex,
+}
+
+/// Parses the `captures` column of a request, rejecting malformed rules.
+pub fn parse_rules(captures: &str) -> Result<Vec<CaptureRule>, String> {
+    let rules: Vec<CaptureRule> =
+        serde_json::from_str(captures).map_err(|e| format!("Invalid capture rules: {}", e))?;
+    for rule in &rules {
+        if rule.variable.trim().is_empty() {
+            return Err("Capture rules need a variable name".to_string());
+        }
+        match rule.source {
+            CaptureSource::Body => {
+                parse_json_path(&rule.expression)?;
+            }
+            CaptureSource::Xpath => {
+                parse_xpath(&rule.expression)?;
+            }
+            CaptureSource::Regex => {
+                Regex::new(&rule.expression)
+                    .map_err(|e| format!("Invalid regular expression: {}", e))?;
+            }
+            CaptureSource::Header | CaptureSource::Status => {}
+        }
+    }
+    Ok(rules)
+}
+
+/// Checks a JSONPath expression without evaluating it.
+pub fn validate_json_path(path: &str) -> Result<(), String> {
+    parse_json_path(path).map(|_| ())
+}
+
+#[derive(Debug, PartialEq)]
+enum PathStep {
+    Key(String),
+    Index(usize),
+}
+
+/// Parses the dotted subset of JSONPath: `$.a.b`, `$.a[0].b`, `$['a b']`.
+fn parse_json_path(path: &str) -> Result<Vec<PathStep>, String> {
+    let invalid = || format!("Invalid JSONPath '{}'", path);
+    let rest = path.trim().strip_prefix('$').ok_or_else(invalid)?;
+    let mut steps = Vec::new();
+    let mut chars = rest.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '.' => {
+                let mut key = String::new();
+                while let Some(&c) = chars.peek() {
+                    if c == '.' || c == '[' {
+                        break;
+                    }
+                    key.push(c);
+                    chars.next();
+                }
+                if key.is_empty() {
+                    return Err(invalid());
+                }
+                steps.push(PathStep::Key(key));
+            }
+            '[' => {
+                let mut inner = String::new();
+                for c in chars.by_ref() {
+                    if c == ']' {
+                        break;
+                    }
+                    inner.push(c);
+                }
+                let quoted = inner
+                    .strip_prefix('\'')
+                    .and_then(|s| s.strip_suffix('\''))
+                    .or_else(|| inner.strip_prefix('"').and_then(|s| s.strip_suffix('"')));
+                match quoted {
+                    Some(key) => steps.push(PathStep::Key(key.to_string())),
+                    None => steps.push(PathStep::Index(inner.parse().map_err(|_| invalid())?)),
+                }
+            }
+            _ => return Err(invalid()),
+        }
+    }
+    Ok(steps)
+}
+
+fn select<'a>(value: &'a Value, steps: &[PathStep]) -> Option<&'a Value> {
+    steps.iter().try_fold(value, |current, step| match step {
+        PathStep::Key(key) => current.get(key),
+        PathStep::Index(index) => current.get(index),
+    })
+}
+
+/// The value at a JSONPath, `None` when the path leads nowhere.
+pub fn select_path<'a>(value: &'a Value, path: &str) -> Result<Option<&'a Value>, String> {
+    Ok(select(value, &parse_json_path(path)?))
+}
+
+#[derive(Debug, PartialEq)]
+enum XPathStep {
+    /// `name`, `*` or `name[2]`, children of the current nodes or, after
+    /// `//`, any of their descendants
+    Element {
+        name: String,
+        index: Option<usize>,
+        descendants: bool,
+    },
+    Attribute(String),
+    Text,
+}
+
+/// Parses the location-path subset of XPath: `/a/b[2]`, `//b`, `/a/*`,
+/// ending in `@attr` or `text()`. Names match local names, ignoring
+/// namespaces.
+fn parse_xpath(path: &str) -> Result<Vec<XPathStep>, String> {
+    let invalid = || format!("Invalid XPath '{}'", path);
+    let rest = path.trim().strip_prefix('/').ok_or_else(invalid)?;
+    let mut steps = Vec::new();
+    let mut descendants = false;
+
+    for part in rest.split('/') {
+        if matches!(
+            steps.last(),
+            Some(XPathStep::Attribute(_) | XPathStep::Text)
+        ) {
+            return Err(invalid());
+        }
+        if part.is_empty() {
+            if descendants {
+                return Err(invalid());
+            }
+            descendants = true;
+            continue;
+        }
+        let step = if let Some(name) = part.strip_prefix('@') {
+            XPathStep::Attribute(name.to_string())
+        } else if part == "text()" {
+            XPathStep::Text
+        } else {
+            let (name, index) = match part.strip_suffix(']').and_then(|p| p.split_once('[')) {
+                Some((name, index)) => match index.parse::<usize>() {
+                    Ok(index) if index > 0 => (name, Some(index)),
+                    _ => return Err(invalid()),
+                },
+                None => (part, None),
+            };
+            XPathStep::Element {
+                name: name.to_string(),
+                index,
+                descendants,
+            }
+        };
+        let valid_name = match &step {
+            XPathStep::Element { name, .. } | XPathStep::Attribute(name) => {
+                name == "*"
+                    || (!name.is_empty()
+                        && name
+                            .chars()
+                            .all(|c| c.is_alphanumeric() || matches!(c, '_' | '-' | '.' | ':')))
+            }
+            XPathStep::Text => true,
+        };
+        if !valid_name || (descendants && !matches!(step, XPathStep::Element { .. })) {
+            return Err(invalid());
+        }
+        descendants = false;
+        steps.push(step);
+    }
+    if descendants || !matches!(steps.first(), Some(XPathStep::Element { .. })) {
+        return Err(invalid());
+    }
+    Ok(steps)
+}
+
+fn local_name(name: &str) -> &str {
+    name.rsplit(':').next().unwrap_or(name)
+}
+
+/// The first match in document order: an attribute's value or the text of
+/// an element.
+fn select_xml(xml: &str, steps: &[XPathStep]) -> Option<String> {
+    select_xml_all(xml, steps).ok()?.into_iter().next()
+}
+
+/// Every match in document order, leaving out empty text.
+fn select_xml_all(xml: &str, steps: &[XPathStep]) -> Result<Vec<String>, String> {
+    let document =
+        roxmltree::Document::parse(xml).map_err(|e| format!("Body is not valid XML: {}", e))?;
+    let mut nodes = vec![document.root()];
+    for step in steps {
+        match step {
+            XPathStep::Element {
+                name,
+                index,
+                descendants,
+            } => {
+                let name = local_name(name);
+                let mut next = Vec::new();
+                for node in &nodes {
+                    let candidates = if *descendants {
+                        node.descendants().skip(1).collect::<Vec<_>>()
+                    } else {
+                        node.children().collect()
+                    };
+                    let mut matching = candidates
+                        .into_iter()
+                        .filter(|n| n.is_element() && (name == "*" || n.tag_name().name() == name));
+                    match index {
+                        Some(index) => next.extend(matching.nth(index - 1)),
+                        None => next.extend(matching),
+                    }
+                }
+                nodes = next;
+            }
+            XPathStep::Attribute(name) => {
+                let name = local_name(name);
+                return Ok(nodes
+                    .iter()
+                    .filter_map(|n| {
+                        n.attributes()
+                            .find(|a| a.name() == name)
+                            .map(|a| a.value().to_string())
+                    })
+                    .collect());
+            }
+            XPathStep::Text => break,
+        }
+    }
+    Ok(nodes
+        .iter()
+        .filter_map(|node| {
+            let text: String = node
+                .descendants()
+                .filter(|n| n.is_text())
+                .filter_map(|n| n.text())
+                .collect();
+            let text = text.trim();
+            (!text.is_empty()).then(|| text.to_string())
+        })
+        .collect())
+}
+
+/// Removes the value at a JSONPath, if present. Returns whether it was there.
+pub fn remove(value: &mut Value, path: &str) -> bool {
+    let Ok(steps) = parse_json_path(path) else {
+        return false;
+    };
+    let Some((last, parents)) = steps.split_last() else {
+        return false;
+    };
+    let parent = parents.iter().try_fold(value, |current, step| match step {
+        PathStep::Key(key) => current.get_mut(key),
+        PathStep::Index(index) => current.get_mut(index),
+    });
+    match (parent, last) {
+        (Some(Value::Object(map)), PathStep::Key(key)) => map.remove(key).is_some(),
+        (Some(Value::Array(items)), PathStep::Index(index)) if *index < items.len() => {
+            items.remove(*index);
+            true
+        }
+        _ => false,
+    }
+}
+
+/// Evaluates one rule against a response. Strings are captured as-is, other
+/// JSON values in their JSON form.
+pub fn extract(
+    rule: &CaptureRule,
+    status: u16,
+    headers: &HashMap<String, String>,
+    body: &str,
+) -> Option<String> {
+    match rule.source {
+        CaptureSource::Status => Some(status.to_string()),
+        CaptureSource::Header => headers
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(rule.expression.trim()))
+            .map(|(_, value)| value.clone()),
+        CaptureSource::Body => {
+            let steps = parse_json_path(&rule.expression).ok()?;
+            let json: Value = serde_json::from_str(body).ok()?;
+            match select(&json, &steps)? {
+                Value::Null => None,
+                Value::String(s) => Some(s.clone()),
+                other => Some(other.to_string()),
+            }
+        }
+        CaptureSource::Xpath => select_xml(body, &parse_xpath(&rule.expression).ok()?),
+        CaptureSource::Regex => {
+            let captures = Regex::new(&rule.expression).ok()?.captures(body)?;
+            captures
+                .get(1)
+                .or_else(|| captures.get(0))
+                .map(|m| m.as_str().to_string())
+        }
+    }
+}
+
+/// Every match of an expression in a body, for trying expressions out: the
+/// value at a JSONPath, each node an XPath selects or each match of a regular
+/// expression. Unlike [`extract`], a body that does not parse is an error.
+pub fn find_all(
+    source: CaptureSource,
+    expression: &str,
+    body: &str,
+) -> Result<Vec<String>, String> {
+    match source {
+        CaptureSource::Body => {
+            let steps = parse_json_path(expression)?;
+            let json: Value =
+                serde_json::from_str(body).map_err(|e| format!("Body is not valid JSON: {}", e))?;
+            Ok(match select(&json, &steps) {
+                None | Some(Value::Null) => Vec::new(),
+                Some(Value::String(s)) => vec![s.clone()],
+                Some(other) => vec![other.to_string()],
+            })
+        }
+        CaptureSource::Xpath => select_xml_all(body, &parse_xpath(expression)?),
+        CaptureSource::Regex => {
+            let regex =
+                Regex::new(expression).map_err(|e| format!("Invalid regular expression: {}", e))?;
+            Ok(regex
+                .captures_iter(body)
+                .filter_map(|captures| captures.get(1).or_else(|| captures.get(0)))
+                .map(|m| m.as_str().to_string())
+                .collect())
+        }
+        CaptureSource::Header | CaptureSource::Status => {
+            Err("Only body, xpath and regex expressions can be tried out".to_string())
+        }
+    }
+}
+
+/// Applies all rules, skipping those that matched nothing.
+pub fn extract_all(
+    rules: &[CaptureRule],
+    status: u16,
+    headers: &HashMap<String, String>,
+    body: &str,
+) -> HashMap<String, String> {
+    rules
+        .iter()
+        .filter_map(|rule| {
+            let value = extract(rule, status, headers, body);
+            if value.is_none() {
+                log::debug!("Capture rule for {} matched nothing", rule.variable);
+            }
+            value.map(|v| (rule.variable.clone(), v))
+        })
+        .collect()
+}
+
+/// Merges captured values into an environment's variables.
+pub async fn store(
+    pool: &DbPool,
+    environment_id: i64,
+    values: &HashMap<String, String>,
+) -> Result<(), sqlx::Error> {
+    let changes = values
+        .iter()
+        .map(|(key, value)| (key.clone(), Some(value.clone())))
+        .collect();
+    environments::store_values(pool, environment_id, &changes).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(source: CaptureSource, expression: &str) -> CaptureRule {
+        CaptureRule {
+            variable: "v".to_string(),
+            source,
+            expression: expression.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_extract_from_body() {
+        let body =
+            r#"{"data": {"items": [{"id": 7, "name": "a"}], "token": "t0k"}, "odd key": true}"#;
+        let headers = HashMap::new();
+        let cases = [
+            ("$.data.token", Some("t0k")),
+            ("$.data.items[0].id", Some("7")),
+            ("$.data.items[0]", Some(r#"{"id":7,"name":"a"}"#)),
+            ("$['odd key']", Some("true")),
+            ("$.data.items[3].id", None),
+            ("$.missing", None),
+        ];
+        for (path, expected) in cases {
+            assert_eq!(
+                extract(&rule(CaptureSource::Body, path), 200, &headers, body).as_deref(),
+                expected,
+                "{}",
+                path
+            );
+        }
+    }
+
+    #[test]
+    fn test_remove() {
+        let mut value: Value =
+            serde_json::from_str(r#"{"meta": {"at": 1, "by": "x"}, "items": [1, 2]}"#).unwrap();
+        assert!(remove(&mut value, "$.meta.at"));
+        assert!(remove(&mut value, "$.items[0]"));
+        assert!(!remove(&mut value, "$.missing.at"));
+        assert!(!remove(&mut value, "$"));
+        assert_eq!(
+            value,
+            serde_json::json!({"meta": {"by": "x"}, "items": [2]})
+        );
+    }
+
+    #[test]
+    fn test_extract_header_and_status() {
+        let headers = HashMap::from([("location".to_string(), "/users/9".to_string())]);
+        assert_eq!(
+            extract(&rule(CaptureSource::Header, "Location"), 201, &headers, "").as_deref(),
+            Some("/users/9")
+        );
+        assert_eq!(
+            extract(&rule(CaptureSource::Status, ""), 201, &headers, "").as_deref(),
+            Some("201")
+        );
+    }
+
+    #[test]
+    fn test_extract_with_xpath_and_regex() {
+        let headers = HashMap::new();
+        let xml = r#"<?xml version="1.0"?>
+            <soap:Envelope xmlns:soap="http://schemas.xmlsoap.org/soap/envelope/">
+              <soap:Body>
+                <order id="o-1"><item sku="a">First</item><item sku="b">Second</item></order>
+              </soap:Body>
+            </soap:Envelope>"#;
+        let cases = [
+            ("/Envelope/Body/order/@id", Some("o-1")),
+            ("//item[2]", Some("Second")),
+            ("//item/@sku", Some("a")),
+            ("/soap:Envelope/*/order/item[1]/text()", Some("First")),
+            ("//missing", None),
+        ];
+        for (path, expected) in cases {
+            assert_eq!(
+                extract(&rule(CaptureSource::Xpath, path), 200, &headers, xml).as_deref(),
+                expected,
+                "{}",
+                path
+            );
+        }
+        assert!(extract(&rule(CaptureSource::Xpath, "//a"), 200, &headers, "{}").is_none());
+
+        let html = r#"<input name="csrf" value="9f8e7d"> Order #4711 created"#;
+        assert_eq!(
+            extract(
+                &rule(CaptureSource::Regex, r#"value="(\w+)""#),
+                200,
+                &headers,
+                html
+            )
+            .as_deref(),
+            Some("9f8e7d")
+        );
+        assert_eq!(
+            extract(&rule(CaptureSource::Regex, r"#\d+"), 200, &headers, html).as_deref(),
+            Some("#4711")
+        );
+    }
+
+    #[test]
+    fn test_parse_rules() {
+        let rules =
+            parse_rules(r#"[{"variable": "user_id", "source": "body", "expression": "$.id"}]"#)
+                .unwrap();
+        assert_eq!(rules[0].source, CaptureSource::Body);
+        assert!(
+            parse_rules(r#"[{"variable": "x", "source": "body", "expression": "id"}]"#).is_err()
+        );
+        assert!(parse_rules(r#"[{"variable": "", "source": "status"}]"#).is_err());
+        for invalid in ["a/b", "/a/@id/b", "//", "/a[0]", "/@id", "/a//@id"] {
+            assert!(parse_xpath(invalid).is_err(), "{}", invalid);
+        }
+        assert!(
+            parse_rules(r#"[{"variable": "x", "source": "regex", "expression": "("}]"#).is_err()
+        );
+        let rules = parse_rules(
+            r#"[{"variable": "x", "source": "xpath", "expression": "/a/b[2]/text()"}]"#,
+        )
+        .unwrap();
+        assert_eq!(rules[0].source, CaptureSource::Xpath);
+        assert!(parse_rules("{").is_err());
+    }
+}
@@ -0,0 +1,196 @@
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Mutex, MutexGuard, OnceLock};
+use std::time::{Duration, Instant};
+
+use crate::capture::{self, CaptureRule, CaptureSource};
+use crate::db::DbPool;
+
+const DEFAULT_TTL_SECS: u64 = 300;
+/// Token requests may themselves use chained auth, up to this many levels.
+const MAX_CHAIN_DEPTH: usize = 5;
+
+/// Tokens by token request and environment, kept until their TTL passes.
+type TokenCache = HashMap<(i64, Option<i64>), (String, Instant)>;
+
+static TOKENS: OnceLock<Mutex<TokenCache>> = OnceLock::new();
+
+/// The `auth_config` of a `chained` request: the token comes from running
+/// another saved request.
+#[derive(Debug, Deserialize)]
+pub struct ChainedConfig {
+    /// The saved request that returns the token, typically a login
+    pub request_id: i64,
+    /// JSONPath of the token in the token request's response body
+    #[serde(default = "default_token_path")]
+    pub token_path: String,
+    /// How long to reuse the token, in seconds; 0 runs the token request
+    /// before every execution
+    #[serde(default)]
+    pub ttl: Option<u64>,
+    /// Scheme in front of the token in the Authorization header
+    #[serde(default = "default_scheme")]
+    pub scheme: String,
+}
+
+fn default_token_path() -> String {
+    "$.access_token".to_string()
+}
+
+fn default_scheme() -> String {
+    "Bearer".to_string()
+}
+
+impl ChainedConfig {
+    pub fn parse(auth_config: Option<&str>) -> Result<Self, String> {
+        let Some(auth_config) = auth_config.filter(|c| !c.trim().is_empty()) else {
+            return Err("Chained auth needs a token request".to_string());
+        };
+        let config: ChainedConfig = serde_json::from_str(auth_config)
+            .map_err(|e| format!("Invalid chained auth settings: {}", e))?;
+        capture::validate_json_path(&config.token_path)?;
+        Ok(config)
+    }
+
+    fn rule(&self) -> CaptureRule {
+        CaptureRule {
+            variable: "token".to_string(),
+            source: CaptureSource::Body,
+            expression: self.token_path.clone(),
+        }
+    }
+
+    fn ttl(&self) -> Duration {
+        Duration::from_secs(self.ttl.unwrap_or(DEFAULT_TTL_SECS))
+    }
+}
+
+fn tokens() -> MutexGuard<'static, TokenCache> {
+    TOKENS
+        .get_or_init(Default::default)
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+}
+
+/// A token from an earlier run of the token request that is still fresh.
+pub fn cached(config: &ChainedConfig, environment_id: Option<i64>) -> Option<String> {
+    let mut tokens = tokens();
+    let key = (config.request_id, environment_id);
+    match tokens.get(&key) {
+        Some((token, expires_at)) if *expires_at > Instant::now() => Some(token.clone()),
+        Some(_) => {
+            tokens.remove(&key);
+            None
+        }
+        None => None,
+    }
+}
+
+/// Extracts the token from the token request's response and caches it.
+pub fn store(
+    config: &ChainedConfig,
+    environment_id: Option<i64>,
+    status: u16,
+    headers: &HashMap<String, String>,
+    body: &str,
+) -> Result<String, String> {
+    if !(200..300).contains(&status) {
+        return Err(format!(
+            "Token request #{} returned status {}",
+            config.request_id, status
+        ));
+    }
+    let token = capture::extract(&config.rule(), status, headers, body).ok_or_else(|| {
+        format!(
+            "No token at {} in the response of request #{}",
+            config.token_path, config.request_id
+        )
+    })?;
+    let ttl = config.ttl();
+    if !ttl.is_zero() {
+        tokens().insert(
+            (config.request_id, environment_id),
+            (token.clone(), Instant::now() + ttl),
+        );
+    }
+    Ok(token)
+}
+
+/// Follows the chain of token requests starting at `config`, rejecting
+/// chains that loop back or nest too deeply before anything is sent.
+pub async fn check_chain(
+    pool: &DbPool,
+    request_id: Option<i64>,
+    config: &ChainedConfig,
+) -> Result<(), String> {
+    let mut seen: HashSet<i64> = request_id.into_iter().collect();
+    let mut next = config.request_id;
+    for _ in 0..MAX_CHAIN_DEPTH {
+        if !seen.insert(next) {
+            return Err(format!("Chained auth loops back to request #{}", next));
+        }
+        let token_request = sqlx::query!(
+            "SELECT auth_type, auth_config FROM requests WHERE id = ?",
+            next
+        )
+        .fetch_optional(pool)
+        .await
+        .map_err(|e| format!("Failed to load token request #{}: {}", next, e))?
+        .ok_or_else(|| format!("Token request #{} does not exist", next))?;
+        if token_request.auth_type != "chained" {
+            return Ok(());
+        }
+        match ChainedConfig::parse(token_request.auth_config.as_deref()) {
+            Ok(config) => next = config.request_id,
+            // Reported when that request runs
+            Err(_) => return Ok(()),
+        }
+    }
+    Err(format!(
+        "Chained auth is nested more than {} levels deep",
+        MAX_CHAIN_DEPTH
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_defaults() {
+        let config = ChainedConfig::parse(Some(r#"{"request_id": 3}"#)).unwrap();
+        assert_eq!(config.token_path, "$.access_token");
+        assert_eq!(config.scheme, "Bearer");
+        assert_eq!(config.ttl(), Duration::from_secs(DEFAULT_TTL_SECS));
+
+        assert!(ChainedConfig::parse(None).is_err());
+        assert!(ChainedConfig::parse(Some(r#"{"request_id": 3, "token_path": "token"}"#)).is_err());
+    }
+
+    #[test]
+    fn test_store_and_reuse_token() {
+        let config = ChainedConfig::parse(Some(
+            r#"{"request_id": 9101, "token_path": "$.data.token", "ttl": 60}"#,
+        ))
+        .unwrap();
+        let headers = HashMap::new();
+        assert!(cached(&config, Some(1)).is_none());
+        assert!(store(&config, Some(1), 401, &headers, "{}").is_err());
+        assert!(store(&config, Some(1), 200, &headers, r#"{"data": {}}"#).is_err());
+
+        let token = store(
+            &config,
+            Some(1),
+            200,
+            &headers,
+            r#"{"data": {"token": "t1"}}"#,
+        );
+        assert_eq!(token.unwrap(), "t1");
+        assert_eq!(cached(&config, Some(1)).unwrap(), "t1");
+        assert!(cached(&config, Some(2)).is_none());
+
+        let uncached = ChainedConfig::parse(Some(r#"{"request_id": 9102, "ttl": 0}"#)).unwrap();
+        store(&uncached, None, 200, &headers, r#"{"access_token": "t2"}"#).unwrap();
+        assert!(cached(&uncached, None).is_none());
+    }
+}
@@ -0,0 +1,89 @@
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use std::path::PathBuf;
+
+use crate::db::MigrationMode;
+
+#[derive(Debug, Parser)]
+#[command(
+    name = "js-link",
+    version,
+    about = "Self-hosted HTTP and WebSocket API client"
+)]
+pub struct Cli {
+    /// Start without applying pending database migrations
+    #[arg(long, conflicts_with = "repair_migrations")]
+    pub skip_migrations: bool,
+
+    /// Clear failed or modified migration records, then migrate
+    #[arg(long)]
+    pub repair_migrations: bool,
+
+    /// Run as a desktop app: listen on a free local port, open the browser
+    /// and stop after the last tab is closed
+    #[arg(long)]
+    pub open: bool,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Check the database, disk, logs, proxy and network setup, then exit
+    Doctor,
+    /// Run a folder or a request without starting the server; exits with 1
+    /// when a request fails
+    Run(RunArgs),
+    /// Replace this binary with the latest release from GitHub
+    SelfUpdate {
+        /// Only report whether a newer release exists
+        #[arg(long)]
+        check: bool,
+    },
+}
+
+#[derive(Debug, Args)]
+pub struct RunArgs {
+    /// Name or id of the folder or request to run
+    pub target: String,
+
+    /// Environment to run with, by name or id
+    #[arg(long)]
+    pub env: Option<String>,
+
+    /// Format of the report
+    #[arg(long, value_enum, default_value_t = Reporter::Text)]
+    pub reporter: Reporter,
+
+    /// Write the report to a file instead of stdout
+    #[arg(long)]
+    pub output: Option<PathBuf>,
+
+    /// Skip the remaining requests after the first failure
+    #[arg(long)]
+    pub stop_on_failure: bool,
+
+    /// Pause between two requests, in milliseconds
+    #[arg(long, default_value_t = 0)]
+    pub delay_ms: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum Reporter {
+    Text,
+    Json,
+    /// JUnit XML, as read by most CI servers
+    Junit,
+}
+
+impl Cli {
+    pub fn migration_mode(&self) -> MigrationMode {
+        if self.skip_migrations {
+            MigrationMode::Skip
+        } else if self.repair_migrations {
+            MigrationMode::Repair
+        } else {
+            MigrationMode::Run
+        }
+    }
+}
@@ -0,0 +1,86 @@
+use reqwest::Client;
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+
+/// Distinct configurations kept at once; more than this starts over, which
+/// only costs the warm connections.
+const MAX_CLIENTS: usize = 32;
+
+static SHARED: OnceLock<ClientPool> = OnceLock::new();
+
+/// Clients by a hash of everything they were built from. A client shares its
+/// connection pool between clones, so executions with the same settings
+/// reuse open connections.
+#[derive(Default)]
+pub struct ClientPool {
+    clients: Mutex<HashMap<u64, Client>>,
+}
+
+impl ClientPool {
+    /// The client built for `key`, building and keeping it on first use.
+    pub fn get_or_build<E>(
+        &self,
+        key: u64,
+        build: impl FnOnce() -> Result<Client, E>,
+    ) -> Result<Client, E> {
+        if let Some(client) = self.lock().get(&key) {
+            log::debug!("Reusing HTTP client {:x}", key);
+            return Ok(client.clone());
+        }
+        let client = build()?;
+        let mut clients = self.lock();
+        if clients.len() >= MAX_CLIENTS {
+            clients.clear();
+        }
+        Ok(clients.entry(key).or_insert(client).clone())
+    }
+
+    pub fn clear(&self) {
+        let mut clients = self.lock();
+        if !clients.is_empty() {
+            log::debug!("Dropping {} cached HTTP clients", clients.len());
+            clients.clear();
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<u64, Client>> {
+        self.clients.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// The pool used by executions; cleared when the network settings change.
+pub fn shared() -> &'static ClientPool {
+    SHARED.get_or_init(Default::default)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_get_or_build() {
+        let pool = ClientPool::default();
+        let mut builds = 0;
+        let mut build = || -> Result<Client, String> {
+            builds += 1;
+            Ok(Client::new())
+        };
+        pool.get_or_build(1, &mut build).unwrap();
+        pool.get_or_build(1, &mut build).unwrap();
+        pool.get_or_build(2, &mut build).unwrap();
+        pool.clear();
+        pool.get_or_build(1, &mut build).unwrap();
+        assert_eq!(builds, 3);
+
+        assert!(pool
+            .get_or_build::<String>(3, || Err("bad proxy".to_string()))
+            .is_err());
+        assert!(!pool.lock().contains_key(&3));
+
+        for key in 10..10 + MAX_CLIENTS as u64 {
+            pool.get_or_build(key, || Ok::<_, String>(Client::new()))
+                .unwrap();
+        }
+        assert!(pool.lock().len() <= MAX_CLIENTS);
+    }
+}
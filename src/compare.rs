@@ -0,0 +1,629 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Json, Router,
+};
+use chrono::{DateTime, NaiveDateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+
+use crate::{
+    capture,
+    db::DbPool,
+    i18n,
+    runner::{self, RunFolderPayload, RunResult, RunnerError},
+};
+
+/// Differences listed per request before the rest are summarized.
+const MAX_DIFFERENCES: usize = 20;
+
+#[derive(Debug, Deserialize)]
+pub struct ComparePayload {
+    pub left_environment_id: i64,
+    pub right_environment_id: i64,
+    /// JSONPaths left out of body comparisons, such as timestamps or ids
+    #[serde(default)]
+    pub ignore_paths: Vec<String>,
+}
+
+/// One request run against both environments.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ComparisonEntry {
+    pub request_id: i64,
+    pub name: String,
+    pub left_status: Option<u16>,
+    pub right_status: Option<u16>,
+    pub left_error: Option<String>,
+    pub right_error: Option<String>,
+    pub status_matches: bool,
+    pub body_matches: bool,
+    /// JSONPaths at which the normalized bodies differ, `$` for non-JSON bodies
+    pub differences: Vec<String>,
+    /// Normalized bodies, for showing side by side
+    pub left_body: Option<String>,
+    pub right_body: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ComparisonReport {
+    pub suite_id: Option<i64>,
+    pub folder_id: i64,
+    pub left_environment_id: i64,
+    pub right_environment_id: i64,
+    pub left_run_id: i64,
+    pub right_run_id: i64,
+    pub matching: usize,
+    pub differing: usize,
+    pub entries: Vec<ComparisonEntry>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ComparisonSuite {
+    pub id: i64,
+    pub name: String,
+    pub folder_id: i64,
+    pub left_environment_id: i64,
+    pub right_environment_id: i64,
+    pub ignore_paths: Vec<String>,
+    pub last_report: Option<ComparisonReport>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+struct ComparisonSuiteDb {
+    id: i64,
+    name: String,
+    folder_id: i64,
+    left_environment_id: i64,
+    right_environment_id: i64,
+    ignore_paths: String,
+    last_report: Option<String>,
+    created_at: NaiveDateTime,
+    updated_at: NaiveDateTime,
+}
+
+impl From<ComparisonSuiteDb> for ComparisonSuite {
+    fn from(s: ComparisonSuiteDb) -> Self {
+        ComparisonSuite {
+            id: s.id,
+            name: s.name,
+            folder_id: s.folder_id,
+            left_environment_id: s.left_environment_id,
+            right_environment_id: s.right_environment_id,
+            ignore_paths: serde_json::from_str(&s.ignore_paths).unwrap_or_default(),
+            last_report: s.last_report.and_then(|r| serde_json::from_str(&r).ok()),
+            created_at: s.created_at.and_utc(),
+            updated_at: s.updated_at.and_utc(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateSuite {
+    pub name: String,
+    pub folder_id: i64,
+    #[serde(flatten)]
+    pub compare: ComparePayload,
+}
+
+pub enum CompareError {
+    InvalidName,
+    InvalidIgnorePath(String),
+    FolderNotFound,
+    EnvironmentNotFound,
+    SuiteNotFound,
+    DatabaseError(#[allow(dead_code)] sqlx::Error),
+}
+
+impl From<sqlx::Error> for CompareError {
+    fn from(e: sqlx::Error) -> Self {
+        CompareError::DatabaseError(e)
+    }
+}
+
+impl From<RunnerError> for CompareError {
+    fn from(e: RunnerError) -> Self {
+        match e {
+            // run_folder reports nothing else as missing
+            RunnerError::FolderNotFound
+            | RunnerError::RequestNotFound
+            | RunnerError::RunNotFound => CompareError::FolderNotFound,
+            RunnerError::DatabaseError(e) => CompareError::DatabaseError(e),
+        }
+    }
+}
+
+impl IntoResponse for CompareError {
+    fn into_response(self) -> Response {
+        match self {
+            CompareError::InvalidName => {
+                (StatusCode::BAD_REQUEST, i18n::t("error.invalid_suite_name")).into_response()
+            }
+            CompareError::InvalidIgnorePath(msg) => (StatusCode::BAD_REQUEST, msg).into_response(),
+            CompareError::FolderNotFound => {
+                (StatusCode::NOT_FOUND, i18n::t("error.folder_not_found")).into_response()
+            }
+            CompareError::EnvironmentNotFound => (
+                StatusCode::NOT_FOUND,
+                i18n::t("error.environment_not_found"),
+            )
+                .into_response(),
+            CompareError::SuiteNotFound => {
+                (StatusCode::NOT_FOUND, i18n::t("error.suite_not_found")).into_response()
+            }
+            CompareError::DatabaseError(_) => {
+                (StatusCode::INTERNAL_SERVER_ERROR, i18n::t("error.database")).into_response()
+            }
+        }
+    }
+}
+
+/// Parses JSON bodies, drops the ignored paths and pretty-prints them so
+/// key order and formatting do not count as differences. Other bodies are
+/// compared with line endings and trailing whitespace normalized.
+fn normalize(body: &str, ignore_paths: &[String]) -> Result<Value, String> {
+    match serde_json::from_str::<Value>(body) {
+        Ok(mut value) => {
+            for path in ignore_paths {
+                capture::remove(&mut value, path);
+            }
+            Ok(value)
+        }
+        Err(_) => Err(body
+            .lines()
+            .map(str::trim_end)
+            .collect::<Vec<_>>()
+            .join("\n")
+            .trim()
+            .to_string()),
+    }
+}
+
+fn key_path(path: &str, key: &str) -> String {
+    if !key.is_empty() && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        format!("{}.{}", path, key)
+    } else {
+        format!("{}['{}']", path, key)
+    }
+}
+
+fn diff(left: &Value, right: &Value, path: String, out: &mut Vec<String>) {
+    match (left, right) {
+        (Value::Object(l), Value::Object(r)) => {
+            let mut keys: Vec<&String> = l.keys().chain(r.keys()).collect();
+            keys.sort();
+            keys.dedup();
+            for key in keys {
+                let path = key_path(&path, key);
+                match (l.get(key), r.get(key)) {
+                    (Some(l), Some(r)) => diff(l, r, path, out),
+                    _ => out.push(path),
+                }
+            }
+        }
+        (Value::Array(l), Value::Array(r)) => {
+            for index in 0..l.len().max(r.len()) {
+                let path = format!("{}[{}]", path, index);
+                match (l.get(index), r.get(index)) {
+                    (Some(l), Some(r)) => diff(l, r, path, out),
+                    _ => out.push(path),
+                }
+            }
+        }
+        _ if left != right => out.push(path),
+        _ => {}
+    }
+}
+
+/// Paths at which two bodies differ and the normalized bodies.
+fn compare_bodies(
+    left: &str,
+    right: &str,
+    ignore_paths: &[String],
+) -> (Vec<String>, String, String) {
+    let mut differences = Vec::new();
+    let (left, right) = match (
+        normalize(left, ignore_paths),
+        normalize(right, ignore_paths),
+    ) {
+        (Ok(l), Ok(r)) => {
+            diff(&l, &r, "$".to_string(), &mut differences);
+            let pretty = |v: &Value| serde_json::to_string_pretty(v).unwrap_or_default();
+            (pretty(&l), pretty(&r))
+        }
+        (l, r) => {
+            let text = |v: Result<Value, String>| v.unwrap_or_else(Value::String);
+            let (l, r) = (text(l), text(r));
+            if l != r {
+                differences.push("$".to_string());
+            }
+            let show = |v: Value| match v {
+                Value::String(s) => s,
+                other => serde_json::to_string_pretty(&other).unwrap_or_default(),
+            };
+            (show(l), show(r))
+        }
+    };
+    if differences.len() > MAX_DIFFERENCES {
+        let more = differences.len() - MAX_DIFFERENCES;
+        differences.truncate(MAX_DIFFERENCES);
+        differences.push(format!("... {} more", more));
+    }
+    (differences, left, right)
+}
+
+fn compare_results(
+    left: RunResult,
+    right: Option<RunResult>,
+    ignore_paths: &[String],
+) -> ComparisonEntry {
+    let right = right.unwrap_or(RunResult {
+        request_id: left.request_id,
+        name: left.name.clone(),
+        method: left.method.clone(),
+        url: left.url.clone(),
+        status: None,
+        duration: 0,
+        passed: false,
+        error: Some("Not run".to_string()),
+        test_results: Vec::new(),
+        assertion_results: Vec::new(),
+        body: None,
+    });
+    let (differences, left_body, right_body) = match (&left.body, &right.body) {
+        (Some(l), Some(r)) => {
+            let (differences, l, r) = compare_bodies(l, r, ignore_paths);
+            (differences, Some(l), Some(r))
+        }
+        (l, r) => (vec!["$".to_string()], l.clone(), r.clone()),
+    };
+    ComparisonEntry {
+        request_id: left.request_id,
+        name: left.name,
+        left_status: left.status,
+        right_status: right.status,
+        status_matches: left.status.is_some() && left.status == right.status,
+        body_matches: differences.is_empty(),
+        left_error: left.error,
+        right_error: right.error,
+        differences,
+        left_body,
+        right_body,
+    }
+}
+
+fn validate_ignore_paths(ignore_paths: &[String]) -> Result<(), CompareError> {
+    for path in ignore_paths {
+        capture::validate_json_path(path).map_err(CompareError::InvalidIgnorePath)?;
+    }
+    Ok(())
+}
+
+async fn check_environments(pool: &DbPool, payload: &ComparePayload) -> Result<(), CompareError> {
+    for id in [payload.left_environment_id, payload.right_environment_id] {
+        sqlx::query_scalar!("SELECT id FROM environments WHERE id = ?", id)
+            .fetch_optional(pool)
+            .await?
+            .ok_or(CompareError::EnvironmentNotFound)?;
+    }
+    Ok(())
+}
+
+/// Runs the folder against both environments at the same time and pairs
+/// up the results by request.
+pub async fn compare_folder(
+    pool: &DbPool,
+    folder_id: i64,
+    payload: &ComparePayload,
+) -> Result<ComparisonReport, CompareError> {
+    validate_ignore_paths(&payload.ignore_paths)?;
+    check_environments(pool, payload).await?;
+
+    let run = |environment_id| RunFolderPayload {
+        environment_id: Some(environment_id),
+        stop_on_failure: false,
+        delay_ms: 0,
+    };
+    let (left_run, right_run) = (
+        run(payload.left_environment_id),
+        run(payload.right_environment_id),
+    );
+    let (left, right) = tokio::join!(
+        runner::run_folder(pool, folder_id, &left_run),
+        runner::run_folder(pool, folder_id, &right_run)
+    );
+    let (left, right) = (left?, right?);
+
+    let mut right_results: HashMap<i64, RunResult> = right
+        .results
+        .into_iter()
+        .map(|r| (r.request_id, r))
+        .collect();
+    let entries: Vec<ComparisonEntry> = left
+        .results
+        .into_iter()
+        .map(|l| {
+            let r = right_results.remove(&l.request_id);
+            compare_results(l, r, &payload.ignore_paths)
+        })
+        .collect();
+    let matching = entries
+        .iter()
+        .filter(|e| e.status_matches && e.body_matches)
+        .count();
+    log::info!(
+        "Compared folder {} across environments {} and {}: {} matching, {} differing",
+        folder_id,
+        payload.left_environment_id,
+        payload.right_environment_id,
+        matching,
+        entries.len() - matching
+    );
+
+    Ok(ComparisonReport {
+        suite_id: None,
+        folder_id,
+        left_environment_id: payload.left_environment_id,
+        right_environment_id: payload.right_environment_id,
+        left_run_id: left.id,
+        right_run_id: right.id,
+        matching,
+        differing: entries.len() - matching,
+        entries,
+    })
+}
+
+async fn compare_folder_handler(
+    State(pool): State<DbPool>,
+    Path(folder_id): Path<i64>,
+    Json(payload): Json<ComparePayload>,
+) -> Result<impl IntoResponse, CompareError> {
+    Ok(Json(compare_folder(&pool, folder_id, &payload).await?))
+}
+
+async fn fetch_suite(pool: &DbPool, id: i64) -> Result<ComparisonSuite, CompareError> {
+    let suite = sqlx::query_as!(
+        ComparisonSuiteDb,
+        "SELECT id, name, folder_id, left_environment_id, right_environment_id, ignore_paths, last_report, created_at, updated_at FROM comparison_suites WHERE id = ?",
+        id
+    )
+    .fetch_optional(pool)
+    .await?
+    .ok_or(CompareError::SuiteNotFound)?;
+    Ok(suite.into())
+}
+
+async fn create_suite(
+    State(pool): State<DbPool>,
+    Json(payload): Json<CreateSuite>,
+) -> Result<impl IntoResponse, CompareError> {
+    log::debug!("Creating comparison suite: {}", payload.name);
+    if payload.name.trim().is_empty() {
+        return Err(CompareError::InvalidName);
+    }
+    validate_ignore_paths(&payload.compare.ignore_paths)?;
+    sqlx::query_scalar!("SELECT id FROM folders WHERE id = ?", payload.folder_id)
+        .fetch_optional(&pool)
+        .await?
+        .ok_or(CompareError::FolderNotFound)?;
+    check_environments(&pool, &payload.compare).await?;
+
+    let ignore_paths =
+        serde_json::to_string(&payload.compare.ignore_paths).unwrap_or_else(|_| "[]".to_string());
+    let id = sqlx::query_scalar!(
+        "INSERT INTO comparison_suites (name, folder_id, left_environment_id, right_environment_id, ignore_paths) VALUES (?, ?, ?, ?, ?) RETURNING id as \"id!\"",
+        payload.name,
+        payload.folder_id,
+        payload.compare.left_environment_id,
+        payload.compare.right_environment_id,
+        ignore_paths
+    )
+    .fetch_one(&pool)
+    .await?;
+    Ok((StatusCode::CREATED, Json(fetch_suite(&pool, id).await?)))
+}
+
+async fn list_suites(State(pool): State<DbPool>) -> Result<impl IntoResponse, CompareError> {
+    let suites = sqlx::query_as!(
+        ComparisonSuiteDb,
+        "SELECT id, name, folder_id, left_environment_id, right_environment_id, ignore_paths, last_report, created_at, updated_at FROM comparison_suites ORDER BY name COLLATE NOCASE"
+    )
+    .fetch_all(&pool)
+    .await?;
+    Ok(Json(
+        suites
+            .into_iter()
+            .map(ComparisonSuite::from)
+            .collect::<Vec<_>>(),
+    ))
+}
+
+async fn get_suite(
+    State(pool): State<DbPool>,
+    Path(id): Path<i64>,
+) -> Result<impl IntoResponse, CompareError> {
+    Ok(Json(fetch_suite(&pool, id).await?))
+}
+
+async fn delete_suite(
+    State(pool): State<DbPool>,
+    Path(id): Path<i64>,
+) -> Result<impl IntoResponse, CompareError> {
+    log::debug!("Deleting comparison suite id: {}", id);
+    let result = sqlx::query!("DELETE FROM comparison_suites WHERE id = ?", id)
+        .execute(&pool)
+        .await?;
+    if result.rows_affected() == 0 {
+        return Err(CompareError::SuiteNotFound);
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn run_suite(
+    State(pool): State<DbPool>,
+    Path(id): Path<i64>,
+) -> Result<impl IntoResponse, CompareError> {
+    let suite = fetch_suite(&pool, id).await?;
+    let payload = ComparePayload {
+        left_environment_id: suite.left_environment_id,
+        right_environment_id: suite.right_environment_id,
+        ignore_paths: suite.ignore_paths,
+    };
+    let mut report = compare_folder(&pool, suite.folder_id, &payload).await?;
+    report.suite_id = Some(id);
+
+    let json = serde_json::to_string(&report).unwrap_or_default();
+    sqlx::query!(
+        "UPDATE comparison_suites SET last_report = ?, updated_at = CURRENT_TIMESTAMP WHERE id = ?",
+        json,
+        id
+    )
+    .execute(&pool)
+    .await?;
+    Ok(Json(report))
+}
+
+pub fn routes(pool: DbPool) -> Router {
+    Router::new()
+        .route("/run/folder/:id/compare", post(compare_folder_handler))
+        .route("/comparisons", post(create_suite).get(list_suites))
+        .route("/comparisons/:id", get(get_suite).delete(delete_suite))
+        .route("/comparisons/:id/run", post(run_suite))
+        .with_state(pool)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db;
+    use axum_test::TestServer;
+    use httpmock::MockServer;
+    use serde_json::json;
+
+    #[test]
+    fn test_compare_bodies() {
+        let ignore = vec!["$.generated_at".to_string()];
+        let (differences, left, _) = compare_bodies(
+            r#"{"b": 1, "a": [1, 2], "generated_at": "mon"}"#,
+            r#"{"a": [1, 2], "b": 1, "generated_at": "tue"}"#,
+            &ignore,
+        );
+        assert!(differences.is_empty());
+        assert!(!left.contains("generated_at"));
+
+        let (differences, _, _) = compare_bodies(
+            r#"{"user": {"name": "a", "odd key": 1}, "tags": [1]}"#,
+            r#"{"user": {"name": "b"}, "tags": [1, 2]}"#,
+            &[],
+        );
+        assert_eq!(
+            differences,
+            vec!["$.tags[1]", "$.user.name", "$.user['odd key']"]
+        );
+
+        let (differences, _, _) = compare_bodies("ok \r\n", "ok", &[]);
+        assert!(differences.is_empty());
+        let (differences, _, right) = compare_bodies("ok", r#"{"ok": true}"#, &[]);
+        assert_eq!(differences, vec!["$"]);
+        assert!(right.contains("\"ok\": true"));
+    }
+
+    #[tokio::test]
+    async fn test_compare_suite() {
+        let pool = db::create_test_pool().await;
+        sqlx::query!(
+            "INSERT OR IGNORE INTO network_settings (id, auto_proxy, http_proxy, https_proxy, no_proxy) VALUES (1, TRUE, NULL, NULL, NULL)"
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        let mock_server = MockServer::start_async().await;
+        for env in ["staging", "prod"] {
+            let version = if env == "prod" { 1 } else { 2 };
+            mock_server.mock(|when, then| {
+                when.path(format!("/{}/health", env));
+                then.status(200)
+                    .json_body(json!({ "ok": true, "checked_at": env }));
+            });
+            mock_server.mock(|when, then| {
+                when.path(format!("/{}/version", env));
+                then.status(200).json_body(json!({ "version": version }));
+            });
+        }
+
+        let folder_id =
+            sqlx::query_scalar!("INSERT INTO folders (name) VALUES ('Smoke') RETURNING id")
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+        for (name, path) in [("Health", "health"), ("Version", "version")] {
+            let url = format!("{{{{base_url}}}}/{}", path);
+            sqlx::query!(
+                "INSERT INTO requests (name, method, url, folder_id) VALUES (?, 'GET', ?, ?)",
+                name,
+                url,
+                folder_id
+            )
+            .execute(&pool)
+            .await
+            .unwrap();
+        }
+        let mut environments = Vec::new();
+        for env in ["staging", "prod"] {
+            let variables =
+                json!({ "base_url": format!("{}/{}", mock_server.base_url(), env) }).to_string();
+            let id = sqlx::query_scalar!(
+                "INSERT INTO environments (name, variables) VALUES (?, ?) RETURNING id",
+                env,
+                variables
+            )
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+            environments.push(id);
+        }
+
+        let server = TestServer::new(routes(pool.clone())).unwrap();
+        let response = server
+            .post("/comparisons")
+            .json(&json!({
+                "name": "Staging vs prod",
+                "folder_id": folder_id,
+                "left_environment_id": environments[0],
+                "right_environment_id": environments[1],
+                "ignore_paths": ["$.checked_at"]
+            }))
+            .await;
+        response.assert_status(StatusCode::CREATED);
+        let suite: ComparisonSuite = response.json();
+        assert!(suite.last_report.is_none());
+
+        let report: ComparisonReport = server
+            .post(&format!("/comparisons/{}/run", suite.id))
+            .await
+            .json();
+        assert_eq!((report.matching, report.differing), (1, 1));
+        assert!(report.entries[0].body_matches);
+        assert!(report.entries[1].status_matches);
+        assert_eq!(report.entries[1].differences, vec!["$.version"]);
+
+        let suite: ComparisonSuite = server
+            .get(&format!("/comparisons/{}", suite.id))
+            .await
+            .json();
+        assert_eq!(suite.last_report.unwrap().suite_id, Some(suite.id));
+
+        server
+            .post(&format!("/run/folder/{}/compare", folder_id))
+            .json(&json!({ "left_environment_id": environments[0], "right_environment_id": 999 }))
+            .await
+            .assert_status(StatusCode::NOT_FOUND);
+        server
+            .delete(&format!("/comparisons/{}", suite.id))
+            .await
+            .assert_status(StatusCode::NO_CONTENT);
+    }
+}
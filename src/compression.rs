@@ -0,0 +1,167 @@
+use serde::Deserialize;
+use std::io::Read;
+
+/// Sent when the request does not choose its own Accept-Encoding.
+const DEFAULT_ACCEPT_ENCODING: &str = "gzip, deflate, br";
+/// Decoded bodies larger than this are refused, so a small compressed
+/// response cannot fill the memory.
+const MAX_DECODED_SIZE: u64 = 256 * 1024 * 1024;
+
+/// The `compression` column of a request: how response bodies are
+/// decompressed.
+#[derive(Debug, Clone, Deserialize)]
+pub struct CompressionSettings {
+    /// When false the body is returned as it arrived, still encoded
+    #[serde(default = "default_true")]
+    pub decompress: bool,
+    /// Accept-Encoding to send instead of the default; an empty string
+    /// sends none
+    #[serde(default)]
+    pub accept_encoding: Option<String>,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+impl Default for CompressionSettings {
+    fn default() -> Self {
+        CompressionSettings {
+            decompress: true,
+            accept_encoding: None,
+        }
+    }
+}
+
+impl CompressionSettings {
+    pub fn parse(compression: Option<&str>) -> Result<Self, String> {
+        let Some(compression) = compression.filter(|c| !c.trim().is_empty()) else {
+            return Ok(Self::default());
+        };
+        let settings: CompressionSettings = serde_json::from_str(compression)
+            .map_err(|e| format!("Invalid compression settings: {}", e))?;
+        if let Some(accept) = &settings.accept_encoding {
+            if accept.chars().any(|c| c.is_control()) {
+                return Err("Accept-Encoding must not contain control characters".to_string());
+            }
+        }
+        Ok(settings)
+    }
+
+    /// The Accept-Encoding header to send, if any. Without decompression
+    /// nothing is asked for unless the request says so.
+    pub fn accept_encoding(&self) -> Option<&str> {
+        match self.accept_encoding.as_deref() {
+            Some("") => None,
+            Some(accept) => Some(accept),
+            None if self.decompress => Some(DEFAULT_ACCEPT_ENCODING),
+            None => None,
+        }
+    }
+}
+
+/// Undoes a Content-Encoding such as `gzip` or `deflate, br`, where the
+/// codings were applied in the listed order.
+pub fn decode(content_encoding: &str, raw: &[u8]) -> Result<Vec<u8>, String> {
+    let mut body = raw.to_vec();
+    for coding in content_encoding.rsplit(',').map(str::trim) {
+        body = match coding.to_ascii_lowercase().as_str() {
+            "" | "identity" => continue,
+            "gzip" | "x-gzip" => read_all(flate2::read::MultiGzDecoder::new(&body[..])),
+            // Servers send both zlib-wrapped and raw deflate data
+            "deflate" => read_all(flate2::read::ZlibDecoder::new(&body[..]))
+                .or_else(|_| read_all(flate2::read::DeflateDecoder::new(&body[..]))),
+            "br" => read_all(brotli_decompressor::Decompressor::new(&body[..], 4096)),
+            other => return Err(format!("Unsupported content encoding '{}'", other)),
+        }
+        .map_err(|e| format!("Failed to decode {} body: {}", coding, e))?;
+    }
+    Ok(body)
+}
+
+/// The body as text, in the charset of its Content-Type, UTF-8 by default.
+pub fn text(body: &[u8], content_type: Option<&str>) -> String {
+    let encoding = content_type
+        .and_then(|ct| {
+            ct.split(';')
+                .skip(1)
+                .filter_map(|param| param.split_once('='))
+                .find(|(name, _)| name.trim().eq_ignore_ascii_case("charset"))
+                .map(|(_, value)| value.trim().trim_matches('"').to_string())
+        })
+        .and_then(|label| encoding_rs::Encoding::for_label(label.as_bytes()))
+        .unwrap_or(encoding_rs::UTF_8);
+    encoding.decode(body).0.into_owned()
+}
+
+fn read_all(reader: impl Read) -> std::io::Result<Vec<u8>> {
+    let mut body = Vec::new();
+    reader.take(MAX_DECODED_SIZE + 1).read_to_end(&mut body)?;
+    if body.len() as u64 > MAX_DECODED_SIZE {
+        return Err(std::io::Error::other("decoded body is too large"));
+    }
+    Ok(body)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    fn gzip(data: &[u8]) -> Vec<u8> {
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    #[test]
+    fn test_decode() {
+        let body = b"hello hello hello hello";
+        assert_eq!(decode("gzip", &gzip(body)).unwrap(), body);
+        assert_eq!(decode("identity", body).unwrap(), body);
+
+        let mut zlib = flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+        zlib.write_all(body).unwrap();
+        let deflated = zlib.finish().unwrap();
+        assert_eq!(decode("deflate", &deflated).unwrap(), body);
+        // deflate applied first, then gzip
+        assert_eq!(decode("deflate, gzip", &gzip(&deflated)).unwrap(), body);
+
+        // "hello" compressed with brotli
+        let br = [0x0b, 0x02, 0x80, 0x68, 0x65, 0x6c, 0x6c, 0x6f, 0x03];
+        assert_eq!(decode("br", &br).unwrap(), b"hello");
+
+        assert!(decode("gzip", body).is_err());
+        assert!(decode("zstd", body).is_err());
+    }
+
+    #[test]
+    fn test_text() {
+        assert_eq!(text("é".as_bytes(), None), "é");
+        assert_eq!(
+            text(&[0xe9], Some("text/plain; charset=\"ISO-8859-1\"")),
+            "é"
+        );
+        assert_eq!(text(&[0xff], Some("application/json")), "\u{fffd}");
+    }
+
+    #[test]
+    fn test_accept_encoding() {
+        let default = CompressionSettings::parse(None).unwrap();
+        assert_eq!(default.accept_encoding(), Some(DEFAULT_ACCEPT_ENCODING));
+
+        let raw = CompressionSettings::parse(Some(r#"{"decompress":false}"#)).unwrap();
+        assert_eq!(raw.accept_encoding(), None);
+
+        let explicit =
+            CompressionSettings::parse(Some(r#"{"decompress":false,"accept_encoding":"br"}"#))
+                .unwrap();
+        assert_eq!(explicit.accept_encoding(), Some("br"));
+
+        let none = CompressionSettings::parse(Some(r#"{"accept_encoding":""}"#)).unwrap();
+        assert_eq!(none.accept_encoding(), None);
+
+        assert!(CompressionSettings::parse(Some(r#"{"accept_encoding":"gzip\n"}"#)).is_err());
+        assert!(CompressionSettings::parse(Some("yes")).is_err());
+    }
+}
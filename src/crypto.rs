@@ -0,0 +1,235 @@
+use aes_gcm::{
+    aead::{Aead, KeyInit},
+    Aes256Gcm, Key, Nonce,
+};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use rand::RngCore;
+use sha2::{Digest, Sha256};
+use std::env;
+use std::fmt;
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::OnceLock;
+
+/// Prefix marking a value produced by [`encrypt`]. Values without it are
+/// treated as legacy plaintext and returned unchanged by [`decrypt`].
+const ENCRYPTED_PREFIX: &str = "enc:v1:";
+const NONCE_LEN: usize = 12;
+
+static KEY: OnceLock<[u8; 32]> = OnceLock::new();
+
+#[derive(Debug)]
+pub enum CryptoError {
+    KeyUnavailable(String),
+    Malformed,
+    DecryptionFailed,
+}
+
+impl fmt::Display for CryptoError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CryptoError::KeyUnavailable(msg) => write!(f, "Secret key unavailable: {}", msg),
+            CryptoError::Malformed => write!(f, "Malformed encrypted value"),
+            CryptoError::DecryptionFailed => {
+                write!(f, "Failed to decrypt value (wrong secret key?)")
+            }
+        }
+    }
+}
+
+impl std::error::Error for CryptoError {}
+
+// Entry holding the generated key in the OS keychain
+const KEYCHAIN_SERVICE: &str = "js-link";
+const KEYCHAIN_USER: &str = "secret-key";
+
+/// Loads the key from `JSLINK_SECRET_KEY` (any passphrase, hashed to 256 bits),
+/// then from `JSLINK_SECRET_KEY_FILE` (default `jslink.key`) if that file
+/// exists or the variable is set, then from the OS keychain. A new random key
+/// is stored in the keychain, or in the key file when no keychain is
+/// available.
+#[cfg_attr(test, allow(dead_code))]
+fn load_key() -> Result<[u8; 32], CryptoError> {
+    if let Ok(secret) = env::var("JSLINK_SECRET_KEY") {
+        if !secret.is_empty() {
+            log::debug!("Using secret key from JSLINK_SECRET_KEY");
+            return Ok(Sha256::digest(secret.as_bytes()).into());
+        }
+    }
+
+    let configured_file = env::var("JSLINK_SECRET_KEY_FILE").ok();
+    let key_file = configured_file
+        .clone()
+        .unwrap_or_else(|| "jslink.key".to_string());
+    let path = Path::new(&key_file);
+    if path.exists() {
+        log::debug!("Reading secret key from {}", key_file);
+        let encoded = fs::read_to_string(path)
+            .map_err(|e| CryptoError::KeyUnavailable(format!("{}: {}", key_file, e)))?;
+        return decode_key(&encoded)
+            .map_err(|e| CryptoError::KeyUnavailable(format!("{}: {}", key_file, e)));
+    }
+
+    if configured_file.is_none() {
+        match keychain_key() {
+            Ok(key) => return Ok(key),
+            Err(e) => log::info!("OS keychain unavailable ({}), using a key file", e),
+        }
+    }
+
+    log::info!("Generating new secret key at {}", key_file);
+    let key = generate_key();
+    write_key_file(path, &BASE64.encode(key))
+        .map_err(|e| CryptoError::KeyUnavailable(format!("{}: {}", key_file, e)))?;
+    Ok(key)
+}
+
+/// Creates the key file readable by its owner only from the start, never
+/// replacing an existing one.
+fn write_key_file(path: &Path, encoded: &str) -> io::Result<()> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options.open(path)?.write_all(encoded.as_bytes())
+}
+
+/// Reads the key from the OS keychain, storing a new one on first use.
+#[cfg_attr(test, allow(dead_code))]
+fn keychain_key() -> Result<[u8; 32], String> {
+    let entry = keyring::Entry::new(KEYCHAIN_SERVICE, KEYCHAIN_USER).map_err(|e| e.to_string())?;
+    match entry.get_password() {
+        Ok(encoded) => {
+            log::debug!("Using secret key from the OS keychain");
+            decode_key(&encoded)
+        }
+        Err(keyring::Error::NoEntry) => {
+            log::info!("Storing a new secret key in the OS keychain");
+            let key = generate_key();
+            entry
+                .set_password(&BASE64.encode(key))
+                .map_err(|e| e.to_string())?;
+            Ok(key)
+        }
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+#[cfg_attr(test, allow(dead_code))]
+fn decode_key(encoded: &str) -> Result<[u8; 32], String> {
+    let bytes = BASE64.decode(encoded.trim()).map_err(|e| e.to_string())?;
+    bytes
+        .try_into()
+        .map_err(|_| "expected 32 bytes".to_string())
+}
+
+#[cfg_attr(test, allow(dead_code))]
+fn generate_key() -> [u8; 32] {
+    let mut key = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut key);
+    key
+}
+
+/// Loads the key up front. The keychain may block, so this runs before
+/// serving rather than on the first encrypted value.
+#[cfg_attr(test, allow(dead_code))]
+pub fn init() -> Result<(), CryptoError> {
+    cipher().map(|_| ())
+}
+
+fn cipher() -> Result<Aes256Gcm, CryptoError> {
+    let key = match KEY.get() {
+        Some(key) => *key,
+        None => {
+            #[cfg(test)]
+            let key = [7u8; 32];
+            #[cfg(not(test))]
+            let key = load_key()?;
+            *KEY.get_or_init(|| key)
+        }
+    };
+    Ok(Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&key)))
+}
+
+pub fn is_encrypted(value: &str) -> bool {
+    value.starts_with(ENCRYPTED_PREFIX)
+}
+
+/// Encrypts `plaintext` with AES-256-GCM under a fresh random nonce.
+pub fn encrypt(plaintext: &str) -> Result<String, CryptoError> {
+    let mut nonce = [0u8; NONCE_LEN];
+    rand::thread_rng().fill_bytes(&mut nonce);
+
+    let ciphertext = cipher()?
+        .encrypt(Nonce::from_slice(&nonce), plaintext.as_bytes())
+        .map_err(|_| CryptoError::Malformed)?;
+
+    let mut payload = nonce.to_vec();
+    payload.extend(ciphertext);
+    Ok(format!("{}{}", ENCRYPTED_PREFIX, BASE64.encode(payload)))
+}
+
+/// Decrypts a value produced by [`encrypt`]; plaintext values pass through.
+pub fn decrypt(value: &str) -> Result<String, CryptoError> {
+    if !is_encrypted(value) {
+        return Ok(value.to_string());
+    }
+    let encoded = &value[ENCRYPTED_PREFIX.len()..];
+
+    let payload = BASE64.decode(encoded).map_err(|_| CryptoError::Malformed)?;
+    if payload.len() < NONCE_LEN {
+        return Err(CryptoError::Malformed);
+    }
+    let (nonce, ciphertext) = payload.split_at(NONCE_LEN);
+
+    let plaintext = cipher()?
+        .decrypt(Nonce::from_slice(nonce), ciphertext)
+        .map_err(|_| CryptoError::DecryptionFailed)?;
+    String::from_utf8(plaintext).map_err(|_| CryptoError::Malformed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encrypt_roundtrip() {
+        let encrypted = encrypt("s3cret").unwrap();
+        assert!(is_encrypted(&encrypted));
+        assert!(!encrypted.contains("s3cret"));
+        assert_eq!(decrypt(&encrypted).unwrap(), "s3cret");
+    }
+
+    #[test]
+    fn test_decrypt_plaintext_passthrough() {
+        assert_eq!(decrypt("legacy").unwrap(), "legacy");
+    }
+
+    #[test]
+    fn test_decrypt_tampered_value() {
+        let encrypted = encrypt("s3cret").unwrap();
+        let tampered = format!("{}AAAA", &encrypted[..encrypted.len() - 4]);
+        assert!(decrypt(&tampered).is_err());
+    }
+
+    #[test]
+    fn test_write_key_file() {
+        let path = env::temp_dir().join(format!("jslink-key-{}", std::process::id()));
+        write_key_file(&path, "a2V5").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "a2V5");
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+        // An existing key is never overwritten
+        assert!(write_key_file(&path, "b3RoZXI=").is_err());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "a2V5");
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -1,34 +1,322 @@
+use serde::Serialize;
 use sqlx::{
+    migrate::Migrator,
     sqlite::{SqliteConnectOptions, SqliteJournalMode, SqlitePoolOptions},
-    Pool, Sqlite,
+    Pool, Row, Sqlite,
 };
+use std::collections::HashMap;
 use std::env;
+use std::fmt;
+use std::path::PathBuf;
 use std::str::FromStr;
 
 pub type DbPool = Pool<Sqlite>;
 
-pub async fn create_pool() -> Result<DbPool, sqlx::Error> {
-    let db_url = env::var("DATABASE_URL").unwrap_or_else(|_| "sqlite:jslink.db".to_string());
+static MIGRATOR: Migrator = sqlx::migrate!("./migrations");
+
+/// How `create_pool` treats pending schema migrations.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MigrationMode {
+    #[default]
+    Run,
+    /// Leave the schema untouched (`--skip-migrations`).
+    Skip,
+    /// Clear failed records and checksum mismatches, then run (`--repair-migrations`).
+    Repair,
+}
+
+#[derive(Debug)]
+pub enum DbError {
+    Connect(sqlx::Error),
+    Backup(String),
+    Migration {
+        source: sqlx::migrate::MigrateError,
+        report: Vec<MigrationStatus>,
+        backup: Option<PathBuf>,
+    },
+}
+
+impl fmt::Display for DbError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DbError::Connect(e) => write!(f, "Failed to connect to database: {}", e),
+            DbError::Backup(msg) => write!(f, "Failed to back up database: {}", msg),
+            DbError::Migration {
+                source,
+                report,
+                backup,
+            } => {
+                writeln!(f, "Failed to run migrations: {}", source)?;
+                for status in report.iter().filter(|s| s.state != MigrationState::Applied) {
+                    writeln!(
+                        f,
+                        "  {} {} ({:?})",
+                        status.version, status.description, status.state
+                    )?;
+                }
+                if let Some(backup) = backup {
+                    writeln!(
+                        f,
+                        "A backup taken before migrating is at {}",
+                        backup.display()
+                    )?;
+                }
+                write!(
+                    f,
+                    "Run with --repair-migrations to clear failed or modified migrations, or --skip-migrations to start without migrating"
+                )
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MigrationState {
+    Applied,
+    Pending,
+    /// Recorded as started but not completed
+    Failed,
+    /// Applied, but the embedded migration file has changed since
+    ChecksumMismatch,
+    /// Applied by a newer js-link version unknown to this binary
+    Unknown,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MigrationStatus {
+    pub version: i64,
+    pub description: String,
+    pub state: MigrationState,
+    pub installed_on: Option<String>,
+}
+
+struct AppliedMigration {
+    description: String,
+    success: bool,
+    checksum: Vec<u8>,
+    installed_on: Option<String>,
+}
+
+async fn applied_migrations(pool: &DbPool) -> Result<HashMap<i64, AppliedMigration>, sqlx::Error> {
+    let table_exists = sqlx::query(
+        "SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = '_sqlx_migrations'",
+    )
+    .fetch_optional(pool)
+    .await?
+    .is_some();
+    if !table_exists {
+        return Ok(HashMap::new());
+    }
+
+    let rows = sqlx::query(
+        "SELECT version, description, success, checksum, CAST(installed_on AS TEXT) FROM _sqlx_migrations",
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|row| {
+            (
+                row.get::<i64, _>(0),
+                AppliedMigration {
+                    description: row.get(1),
+                    success: row.get(2),
+                    checksum: row.get(3),
+                    installed_on: row.get(4),
+                },
+            )
+        })
+        .collect())
+}
+
+/// Compares the migrations embedded in this binary with those recorded in the database.
+pub async fn migration_status(pool: &DbPool) -> Result<Vec<MigrationStatus>, sqlx::Error> {
+    let mut applied = applied_migrations(pool).await?;
+    let mut report = Vec::new();
+
+    for migration in MIGRATOR.iter() {
+        if migration.migration_type.is_down_migration() {
+            continue;
+        }
+        let record = applied.remove(&migration.version);
+        let state = match &record {
+            None => MigrationState::Pending,
+            Some(r) if !r.success => MigrationState::Failed,
+            Some(r) if r.checksum != *migration.checksum => MigrationState::ChecksumMismatch,
+            Some(_) => MigrationState::Applied,
+        };
+        report.push(MigrationStatus {
+            version: migration.version,
+            description: migration.description.to_string(),
+            state,
+            installed_on: record.and_then(|r| r.installed_on),
+        });
+    }
+
+    for (version, record) in applied {
+        report.push(MigrationStatus {
+            version,
+            description: record.description,
+            state: MigrationState::Unknown,
+            installed_on: record.installed_on,
+        });
+    }
+    report.sort_by_key(|s| s.version);
+
+    Ok(report)
+}
+
+/// Removes failed migration records so they are retried, and re-stamps the
+/// checksum of migrations whose files changed after being applied.
+pub async fn repair_migrations(pool: &DbPool) -> Result<usize, sqlx::Error> {
+    let mut repaired = 0;
+    for status in migration_status(pool).await? {
+        match status.state {
+            MigrationState::Failed => {
+                log::warn!(
+                    "Clearing failed migration record {} {}",
+                    status.version,
+                    status.description
+                );
+                sqlx::query("DELETE FROM _sqlx_migrations WHERE version = ?")
+                    .bind(status.version)
+                    .execute(pool)
+                    .await?;
+                repaired += 1;
+            }
+            MigrationState::ChecksumMismatch => {
+                let Some(migration) = MIGRATOR.iter().find(|m| m.version == status.version) else {
+                    continue;
+                };
+                log::warn!(
+                    "Updating checksum of modified migration {} {}",
+                    status.version,
+                    status.description
+                );
+                sqlx::query("UPDATE _sqlx_migrations SET checksum = ? WHERE version = ?")
+                    .bind(migration.checksum.as_ref())
+                    .bind(status.version)
+                    .execute(pool)
+                    .await?;
+                repaired += 1;
+            }
+            _ => {}
+        }
+    }
+    Ok(repaired)
+}
+
+/// Snapshots the database next to the original file before migrating it.
+async fn backup_database(
+    pool: &DbPool,
+    options: &SqliteConnectOptions,
+) -> Result<Option<PathBuf>, DbError> {
+    let filename = options.get_filename();
+    if filename.as_os_str().is_empty() || filename.to_string_lossy().contains(":memory:") {
+        return Ok(None);
+    }
+
+    let mut backup = filename.as_os_str().to_owned();
+    backup.push(format!(
+        ".bak-{}",
+        chrono::Utc::now().format("%Y%m%d%H%M%S")
+    ));
+    let backup = PathBuf::from(backup);
+
+    log::info!("Backing up database to {}", backup.display());
+    sqlx::query("VACUUM INTO ?")
+        .bind(backup.to_string_lossy().to_string())
+        .execute(pool)
+        .await
+        .map_err(|e| DbError::Backup(e.to_string()))?;
+
+    Ok(Some(backup))
+}
+
+pub fn database_url() -> String {
+    env::var("DATABASE_URL").unwrap_or_else(|_| "sqlite:jslink.db".to_string())
+}
+
+pub async fn create_pool(mode: MigrationMode) -> Result<DbPool, DbError> {
+    let db_url = database_url();
     log::debug!("Connecting to database at: {}", db_url);
 
-    let connection_options = SqliteConnectOptions::from_str(&db_url)?
+    let connection_options = SqliteConnectOptions::from_str(&db_url)
+        .map_err(DbError::Connect)?
         .create_if_missing(true)
         .foreign_keys(true)
         .journal_mode(SqliteJournalMode::Wal);
 
     let pool = SqlitePoolOptions::new()
         .max_connections(5)
-        .connect_with(connection_options)
-        .await?;
+        .connect_with(connection_options.clone())
+        .await
+        .map_err(DbError::Connect)?;
 
     log::info!("Database pool created successfully");
 
-    // Run migrations
-    log::info!("Running pending migrations...");
-    sqlx::migrate!("./migrations")
-        .run(&pool)
-        .await
-        .expect("Failed to run migrations");
+    if mode == MigrationMode::Repair {
+        let repaired = repair_migrations(&pool).await.map_err(DbError::Connect)?;
+        log::info!("Repaired {} migration record(s)", repaired);
+    }
+
+    let report = migration_status(&pool).await.map_err(DbError::Connect)?;
+    let pending: Vec<&MigrationStatus> = report
+        .iter()
+        .filter(|s| s.state == MigrationState::Pending)
+        .collect();
+    for status in report.iter().filter(|s| {
+        matches!(
+            s.state,
+            MigrationState::Failed | MigrationState::ChecksumMismatch | MigrationState::Unknown
+        )
+    }) {
+        log::warn!(
+            "Migration {} {} is in state {:?}",
+            status.version,
+            status.description,
+            status.state
+        );
+    }
+
+    if mode == MigrationMode::Skip {
+        if !pending.is_empty() {
+            log::warn!(
+                "Skipping {} pending migration(s): {}",
+                pending.len(),
+                pending
+                    .iter()
+                    .map(|s| s.version.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            );
+        }
+        return Ok(pool);
+    }
+
+    if pending.is_empty() {
+        log::info!("Database schema is up to date");
+        return Ok(pool);
+    }
+
+    // Only existing databases are worth backing up
+    let backup = if report.iter().any(|s| s.state != MigrationState::Pending) {
+        backup_database(&pool, &connection_options).await?
+    } else {
+        None
+    };
+
+    log::info!("Running {} pending migration(s)...", pending.len());
+    if let Err(source) = MIGRATOR.run(&pool).await {
+        let report = migration_status(&pool).await.unwrap_or(report);
+        return Err(DbError::Migration {
+            source,
+            report,
+            backup,
+        });
+    }
     log::info!("Migrations ran successfully");
 
     Ok(pool)
@@ -52,3 +340,57 @@ pub async fn create_test_pool() -> DbPool {
     log::debug!("Test database pool created successfully");
     pool
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_migration_status_fresh_database() {
+        let pool = SqlitePoolOptions::new()
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+
+        let report = migration_status(&pool).await.unwrap();
+        assert!(!report.is_empty());
+        assert!(report.iter().all(|s| s.state == MigrationState::Pending));
+    }
+
+    #[tokio::test]
+    async fn test_migration_status_applied() {
+        let pool = create_test_pool().await;
+
+        let report = migration_status(&pool).await.unwrap();
+        assert!(report.iter().all(|s| s.state == MigrationState::Applied));
+    }
+
+    #[tokio::test]
+    async fn test_repair_failed_and_modified_migrations() {
+        let pool = create_test_pool().await;
+        let report = migration_status(&pool).await.unwrap();
+        let first = report[0].version;
+        let last = report.last().unwrap().version;
+
+        sqlx::query("UPDATE _sqlx_migrations SET checksum = X'00' WHERE version = ?")
+            .bind(first)
+            .execute(&pool)
+            .await
+            .unwrap();
+        sqlx::query("UPDATE _sqlx_migrations SET success = FALSE WHERE version = ?")
+            .bind(last)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let report = migration_status(&pool).await.unwrap();
+        assert_eq!(report[0].state, MigrationState::ChecksumMismatch);
+        assert_eq!(report.last().unwrap().state, MigrationState::Failed);
+
+        assert_eq!(repair_migrations(&pool).await.unwrap(), 2);
+
+        let report = migration_status(&pool).await.unwrap();
+        assert_eq!(report[0].state, MigrationState::Applied);
+        assert_eq!(report.last().unwrap().state, MigrationState::Pending);
+    }
+}
@@ -0,0 +1,325 @@
+use serde::{Deserialize, Serialize};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::time::Duration;
+use tokio::net::UdpSocket;
+
+// A small DNS client for the lookup tool. The system resolver only returns
+// addresses, so A, AAAA and CNAME records are asked of a name server
+// directly: the one given, else the first in /etc/resolv.conf.
+
+const DNS_PORT: u16 = 53;
+const QUERY_TIMEOUT: Duration = Duration::from_secs(3);
+/// Compression pointers followed in one name, against loops.
+const MAX_POINTERS: usize = 16;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RecordType {
+    A,
+    #[serde(rename = "AAAA")]
+    Aaaa,
+    #[serde(rename = "CNAME")]
+    Cname,
+}
+
+impl RecordType {
+    pub const ALL: [RecordType; 3] = [RecordType::A, RecordType::Aaaa, RecordType::Cname];
+
+    fn code(self) -> u16 {
+        match self {
+            RecordType::A => 1,
+            RecordType::Aaaa => 28,
+            RecordType::Cname => 5,
+        }
+    }
+
+    fn from_code(code: u16) -> Option<Self> {
+        RecordType::ALL.into_iter().find(|t| t.code() == code)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DnsRecord {
+    #[serde(rename = "type")]
+    pub record_type: RecordType,
+    /// The name the record belongs to, an alias for CNAME records
+    pub name: String,
+    /// The address, or the canonical name for CNAME records
+    pub value: String,
+    /// Seconds the answer may be cached; `None` from the system resolver
+    pub ttl: Option<u32>,
+}
+
+/// The name server to ask: `ip` or `ip:port`, else the system's.
+pub fn name_server(resolver: Option<&str>) -> Result<Option<SocketAddr>, String> {
+    match resolver.map(str::trim).filter(|r| !r.is_empty()) {
+        Some(resolver) => resolver
+            .parse::<SocketAddr>()
+            .or_else(|_| {
+                resolver
+                    .trim_start_matches('[')
+                    .trim_end_matches(']')
+                    .parse::<IpAddr>()
+                    .map(|ip| SocketAddr::new(ip, DNS_PORT))
+            })
+            .map(Some)
+            .map_err(|_| format!("Invalid resolver '{}', expected an IP address", resolver)),
+        None => Ok(system_name_server()),
+    }
+}
+
+fn system_name_server() -> Option<SocketAddr> {
+    let conf = std::fs::read_to_string("/etc/resolv.conf").ok()?;
+    conf.lines()
+        .filter_map(|line| line.trim().strip_prefix("nameserver"))
+        .filter_map(|rest| rest.trim().split('%').next()?.parse::<IpAddr>().ok())
+        .map(|ip| SocketAddr::new(ip, DNS_PORT))
+        .next()
+}
+
+/// Asks `server` for one record type of `host`. The answer may hold the
+/// CNAME chain leading to the addresses as well.
+pub async fn query(
+    server: SocketAddr,
+    host: &str,
+    record_type: RecordType,
+) -> Result<Vec<DnsRecord>, String> {
+    let id: u16 = rand::random();
+    let packet = encode_query(id, host, record_type)?;
+    let bind = if server.is_ipv4() {
+        SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), 0)
+    } else {
+        SocketAddr::new(IpAddr::V6(Ipv6Addr::UNSPECIFIED), 0)
+    };
+    let socket = UdpSocket::bind(bind)
+        .await
+        .map_err(|e| format!("Failed to open a UDP socket: {}", e))?;
+    socket
+        .send_to(&packet, server)
+        .await
+        .map_err(|e| format!("Failed to reach {}: {}", server, e))?;
+    let mut buf = [0u8; 4096];
+    let deadline = tokio::time::Instant::now() + QUERY_TIMEOUT;
+    loop {
+        let (len, from) = tokio::time::timeout_at(deadline, socket.recv_from(&mut buf))
+            .await
+            .map_err(|_| {
+                format!(
+                    "{} did not answer within {} s",
+                    server,
+                    QUERY_TIMEOUT.as_secs()
+                )
+            })?
+            .map_err(|e| format!("Failed to read the answer of {}: {}", server, e))?;
+        // Stray datagrams are skipped, not taken for the answer
+        if from == server && len >= 2 && u16::from_be_bytes([buf[0], buf[1]]) == id {
+            return parse_response(&buf[..len]);
+        }
+    }
+}
+
+pub fn encode_query(id: u16, host: &str, record_type: RecordType) -> Result<Vec<u8>, String> {
+    let mut packet = Vec::with_capacity(32 + host.len());
+    packet.extend_from_slice(&id.to_be_bytes());
+    // Recursion desired, one question
+    packet.extend_from_slice(&[0x01, 0x00, 0x00, 0x01, 0, 0, 0, 0, 0, 0]);
+    for label in host.trim_end_matches('.').split('.') {
+        if label.is_empty() || label.len() > 63 {
+            return Err(format!("Invalid host name '{}'", host));
+        }
+        packet.push(label.len() as u8);
+        packet.extend_from_slice(label.as_bytes());
+    }
+    packet.push(0);
+    packet.extend_from_slice(&record_type.code().to_be_bytes());
+    packet.extend_from_slice(&1u16.to_be_bytes()); // IN
+    Ok(packet)
+}
+
+pub fn parse_response(packet: &[u8]) -> Result<Vec<DnsRecord>, String> {
+    let truncated = || "Truncated DNS answer".to_string();
+    let u16_at = |pos: usize| {
+        packet
+            .get(pos..pos + 2)
+            .map(|b| u16::from_be_bytes([b[0], b[1]]))
+            .ok_or_else(truncated)
+    };
+    match u16_at(2)? & 0x000f {
+        0 => {}
+        2 => return Err("The name server failed to answer (SERVFAIL)".to_string()),
+        3 => return Err("No such domain (NXDOMAIN)".to_string()),
+        5 => return Err("The name server refused the query (REFUSED)".to_string()),
+        code => return Err(format!("The name server answered with error {}", code)),
+    }
+    let questions = u16_at(4)?;
+    let answers = u16_at(6)?;
+    let mut pos = 12;
+    for _ in 0..questions {
+        pos = read_name(packet, pos)?.1 + 4;
+    }
+    let mut records = Vec::new();
+    for _ in 0..answers {
+        let (name, next) = read_name(packet, pos)?;
+        let code = u16_at(next)?;
+        let ttl = packet
+            .get(next + 4..next + 8)
+            .map(|b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+            .ok_or_else(truncated)?;
+        let len = u16_at(next + 8)? as usize;
+        let start = next + 10;
+        let data = packet.get(start..start + len).ok_or_else(truncated)?;
+        pos = start + len;
+        // Other records, such as the RRSIGs of a signed zone, are skipped
+        let Some(record_type) = RecordType::from_code(code) else {
+            continue;
+        };
+        let value = match record_type {
+            RecordType::A if len == 4 => {
+                Ipv4Addr::new(data[0], data[1], data[2], data[3]).to_string()
+            }
+            RecordType::Aaaa if len == 16 => {
+                let mut octets = [0u8; 16];
+                octets.copy_from_slice(data);
+                Ipv6Addr::from(octets).to_string()
+            }
+            RecordType::Cname => read_name(packet, start)?.0,
+            _ => return Err("Malformed DNS record".to_string()),
+        };
+        records.push(DnsRecord {
+            record_type,
+            name,
+            value,
+            ttl: Some(ttl),
+        });
+    }
+    Ok(records)
+}
+
+/// Reads a possibly compressed name, returning it and the position after it.
+fn read_name(packet: &[u8], mut pos: usize) -> Result<(String, usize), String> {
+    let mut labels = Vec::new();
+    let mut end = None;
+    let mut pointers = 0;
+    loop {
+        let len = *packet.get(pos).ok_or("Truncated DNS name")? as usize;
+        match len {
+            0 => {
+                let name = if labels.is_empty() {
+                    ".".to_string()
+                } else {
+                    labels.join(".")
+                };
+                return Ok((name, end.unwrap_or(pos + 1)));
+            }
+            l if l & 0xc0 == 0xc0 => {
+                let low = *packet.get(pos + 1).ok_or("Truncated DNS name")? as usize;
+                pointers += 1;
+                if pointers > MAX_POINTERS {
+                    return Err("DNS name compression loop".to_string());
+                }
+                end.get_or_insert(pos + 2);
+                pos = ((l & 0x3f) << 8) | low;
+            }
+            l => {
+                let label = packet
+                    .get(pos + 1..pos + 1 + l)
+                    .ok_or("Truncated DNS name")?;
+                labels.push(String::from_utf8_lossy(label).into_owned());
+                pos += 1 + l;
+            }
+        }
+    }
+}
+
+/// Addresses from the system resolver, for when no name server is known.
+pub async fn system_lookup(host: &str) -> Result<Vec<DnsRecord>, String> {
+    let addrs = tokio::net::lookup_host((host, 0))
+        .await
+        .map_err(|e| format!("Failed to resolve {}: {}", host, e))?;
+    let mut records: Vec<DnsRecord> = Vec::new();
+    for addr in addrs {
+        let record = DnsRecord {
+            record_type: if addr.is_ipv4() {
+                RecordType::A
+            } else {
+                RecordType::Aaaa
+            },
+            name: host.to_string(),
+            value: addr.ip().to_string(),
+            ttl: None,
+        };
+        if !records.contains(&record) {
+            records.push(record);
+        }
+    }
+    Ok(records)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Answers every query for `www.api.test` with a CNAME to `api.test` and
+    /// its A record, using compressed names.
+    fn answer(query: &[u8]) -> Vec<u8> {
+        let mut packet = query.to_vec();
+        packet[2] = 0x81;
+        packet[3] = 0x80;
+        packet[7] = 2;
+        // www.api.test CNAME api.test, pointing into the question
+        packet.extend_from_slice(&[0xc0, 12, 0, 5, 0, 1, 0, 0, 0x0e, 0x10, 0, 2, 0xc0, 16]);
+        // api.test A 10.0.0.7
+        packet.extend_from_slice(&[0xc0, 16, 0, 1, 0, 1, 0, 0, 0, 60, 0, 4, 10, 0, 0, 7]);
+        packet
+    }
+
+    #[tokio::test]
+    async fn test_query() {
+        let server = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let addr = server.local_addr().unwrap();
+        tokio::spawn(async move {
+            let mut buf = [0u8; 512];
+            while let Ok((len, from)) = server.recv_from(&mut buf).await {
+                let _ = server.send_to(&answer(&buf[..len]), from).await;
+            }
+        });
+
+        let records = query(addr, "www.api.test", RecordType::A).await.unwrap();
+        assert_eq!(
+            records,
+            vec![
+                DnsRecord {
+                    record_type: RecordType::Cname,
+                    name: "www.api.test".to_string(),
+                    value: "api.test".to_string(),
+                    ttl: Some(3600),
+                },
+                DnsRecord {
+                    record_type: RecordType::A,
+                    name: "api.test".to_string(),
+                    value: "10.0.0.7".to_string(),
+                    ttl: Some(60),
+                },
+            ]
+        );
+
+        let mut nxdomain = encode_query(7, "missing.test", RecordType::A).unwrap();
+        nxdomain[3] = 0x83;
+        assert!(parse_response(&nxdomain).unwrap_err().contains("NXDOMAIN"));
+        // A pointer to itself must not hang
+        let mut looped = encode_query(7, "a.test", RecordType::A).unwrap();
+        looped[7] = 1;
+        looped.extend_from_slice(&[0xc0, 24, 0, 1, 0, 1, 0, 0, 0, 1, 0, 4, 1, 2, 3, 4]);
+        assert!(parse_response(&looped).is_err());
+        assert!(encode_query(1, "bad..name", RecordType::A).is_err());
+
+        assert_eq!(
+            name_server(Some("1.1.1.1")).unwrap(),
+            Some("1.1.1.1:53".parse().unwrap())
+        );
+        assert_eq!(
+            name_server(Some("[::1]:5353")).unwrap(),
+            Some("[::1]:5353".parse().unwrap())
+        );
+        assert!(name_server(Some("dns.example")).is_err());
+    }
+}
@@ -0,0 +1,586 @@
+use async_zip::{tokio::write::ZipFileWriter, Compression, ZipEntryBuilder};
+use axum::{
+    extract::{Path, Query, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    routing::get,
+    Router,
+};
+use chrono::Utc;
+use sailfish::TemplateOnce;
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::HashSet;
+use std::fmt::Write;
+
+use crate::{
+    db::DbPool,
+    executor,
+    export::slug,
+    folders::{self, FolderDb},
+    form_body, header_rows,
+    history::{self, LatestResponse},
+    i18n,
+    requests::{Request, RequestDb},
+};
+
+/// Example bodies are cut off after this many characters.
+const MAX_EXAMPLE_CHARS: usize = 8 * 1024;
+
+#[derive(Debug)]
+pub enum DocsError {
+    FolderNotFound,
+    RequestNotFound,
+    Render(sailfish::RenderError),
+    DatabaseError(sqlx::Error),
+    ArchiveError(String),
+}
+
+impl From<sqlx::Error> for DocsError {
+    fn from(e: sqlx::Error) -> Self {
+        DocsError::DatabaseError(e)
+    }
+}
+
+impl From<sailfish::RenderError> for DocsError {
+    fn from(e: sailfish::RenderError) -> Self {
+        DocsError::Render(e)
+    }
+}
+
+impl From<async_zip::error::ZipError> for DocsError {
+    fn from(e: async_zip::error::ZipError) -> Self {
+        DocsError::ArchiveError(e.to_string())
+    }
+}
+
+impl IntoResponse for DocsError {
+    fn into_response(self) -> Response {
+        match self {
+            DocsError::FolderNotFound => {
+                (StatusCode::NOT_FOUND, i18n::t("error.folder_not_found")).into_response()
+            }
+            DocsError::RequestNotFound => {
+                (StatusCode::NOT_FOUND, i18n::t("error.request_not_found")).into_response()
+            }
+            DocsError::Render(e) => {
+                log::error!("Failed to render documentation: {}", e);
+                (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response()
+            }
+            DocsError::DatabaseError(e) => {
+                log::error!("Database error: {}", e);
+                (StatusCode::INTERNAL_SERVER_ERROR, i18n::t("error.database")).into_response()
+            }
+            DocsError::ArchiveError(msg) => {
+                (StatusCode::INTERNAL_SERVER_ERROR, msg).into_response()
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DocsFormat {
+    /// `index.html` and `README.md` in one archive
+    #[default]
+    Zip,
+    Html,
+    Markdown,
+}
+
+#[derive(Deserialize)]
+pub struct DocsQuery {
+    #[serde(default)]
+    format: DocsFormat,
+}
+
+/// A request as it is documented: secrets left out, bodies pretty-printed.
+struct DocEntry {
+    id: i64,
+    anchor: String,
+    name: String,
+    method: String,
+    url: String,
+    description: Vec<String>,
+    auth: Option<&'static str>,
+    headers: Vec<(String, String)>,
+    body_type: String,
+    body: Option<String>,
+    response: Option<DocResponse>,
+}
+
+struct DocResponse {
+    status: i64,
+    content_type: Option<String>,
+    body: Option<String>,
+}
+
+#[derive(TemplateOnce)]
+#[template(path = "docs/folder.stpl")]
+struct FolderPage<'a> {
+    title: &'a str,
+    description: &'a [String],
+    entries: &'a [DocEntry],
+    /// Path the send buttons of a shared page post to, `None` in exported
+    /// documentation
+    send_action: Option<&'a str>,
+    version: &'static str,
+    generated_at: String,
+}
+
+/// Documentation of one folder, or of a single request, for readers without
+/// js-link.
+pub struct FolderDocs {
+    title: String,
+    description: Vec<String>,
+    entries: Vec<DocEntry>,
+}
+
+impl FolderDocs {
+    fn html(&self) -> Result<String, sailfish::RenderError> {
+        self.page(None)
+    }
+
+    /// The HTML page with a send button per request, posting to
+    /// `{send_action}/{request id}`.
+    pub fn page(&self, send_action: Option<&str>) -> Result<String, sailfish::RenderError> {
+        FolderPage {
+            title: &self.title,
+            description: &self.description,
+            entries: &self.entries,
+            send_action,
+            version: env!("CARGO_PKG_VERSION"),
+            generated_at: Utc::now().format("%Y-%m-%d %H:%M UTC").to_string(),
+        }
+        .render_once()
+    }
+
+    fn markdown(&self) -> String {
+        let mut md = format!("# {}\n\n", self.title);
+        for paragraph in &self.description {
+            let _ = write!(md, "{}\n\n", paragraph);
+        }
+        for entry in &self.entries {
+            let _ = writeln!(md, "- [{} {}](#{})", entry.method, entry.name, entry.anchor);
+        }
+        for entry in &self.entries {
+            let _ = write!(
+                md,
+                "\n<a id=\"{}\"></a>\n\n## {}\n\n`{} {}`\n\n",
+                entry.anchor, entry.name, entry.method, entry.url
+            );
+            for paragraph in &entry.description {
+                let _ = write!(md, "{}\n\n", paragraph);
+            }
+            if let Some(auth) = entry.auth {
+                let _ = write!(md, "Authentication: {}\n\n", auth);
+            }
+            if !entry.headers.is_empty() {
+                md.push_str("### Headers\n\n| Name | Value |\n| --- | --- |\n");
+                for (name, value) in &entry.headers {
+                    let _ = writeln!(md, "| {} | {} |", table_cell(name), table_cell(value));
+                }
+                md.push('\n');
+            }
+            if let Some(body) = &entry.body {
+                let _ = write!(
+                    md,
+                    "### Body ({})\n\n{}\n",
+                    entry.body_type,
+                    fence(body, fence_language(&entry.body_type))
+                );
+            }
+            if let Some(response) = &entry.response {
+                let _ = write!(md, "### Example response\n\nStatus {}", response.status);
+                if let Some(content_type) = &response.content_type {
+                    let _ = write!(md, ", `{}`", content_type);
+                }
+                md.push_str("\n\n");
+                if let Some(body) = &response.body {
+                    let language = response.content_type.as_deref().map_or("", |c| {
+                        if c.contains("json") {
+                            "json"
+                        } else {
+                            ""
+                        }
+                    });
+                    let _ = writeln!(md, "{}", fence(body, language));
+                }
+            }
+        }
+        md.push_str(&format!(
+            "\n---\n\nGenerated by js-link {} on {}.\n",
+            env!("CARGO_PKG_VERSION"),
+            Utc::now().format("%Y-%m-%d %H:%M UTC")
+        ));
+        md
+    }
+}
+
+fn fence_language(body_type: &str) -> &'static str {
+    match body_type {
+        "json" | "multipart" => "json",
+        "xml" => "xml",
+        _ => "",
+    }
+}
+
+/// A fenced code block longer than any backtick run in `code`.
+fn fence(code: &str, language: &str) -> String {
+    let mut longest = 0;
+    let mut run = 0;
+    for c in code.chars() {
+        run = if c == '`' { run + 1 } else { 0 };
+        longest = longest.max(run);
+    }
+    let ticks = "`".repeat(longest.max(2) + 1);
+    format!("{}{}\n{}\n{}\n", ticks, language, code.trim_end(), ticks)
+}
+
+fn table_cell(text: &str) -> String {
+    text.replace('|', "\\|").replace('\n', " ")
+}
+
+/// Blank-line separated paragraphs of a description.
+fn paragraphs(text: Option<&str>) -> Vec<String> {
+    text.unwrap_or_default()
+        .split("\n\n")
+        .map(str::trim)
+        .filter(|p| !p.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+fn truncate(text: String) -> String {
+    match text.char_indices().nth(MAX_EXAMPLE_CHARS) {
+        Some((end, _)) => format!("{}\n…", &text[..end]),
+        None => text,
+    }
+}
+
+fn pretty_json(text: &str) -> Option<String> {
+    serde_json::from_str::<Value>(text)
+        .ok()
+        .and_then(|v| serde_json::to_string_pretty(&v).ok())
+}
+
+fn auth_label(auth_type: &str) -> Option<&'static str> {
+    Some(match auth_type {
+        "basic" => "Basic",
+        "bearer" => "Bearer token",
+        "oauth2" => "OAuth 2.0",
+        "aws_sigv4" => "AWS Signature Version 4",
+        "jwt" => "JWT bearer token",
+        "chained" => "Bearer token from another request",
+        _ => return None,
+    })
+}
+
+/// Credentials are left out, unless they come from a variable.
+fn header_value(name: &str, value: &str) -> String {
+    if header_rows::is_secret(name) && executor::unresolved_names(value).is_empty() {
+        "(hidden)".to_string()
+    } else {
+        value.to_string()
+    }
+}
+
+fn request_body(request: &Request) -> Option<String> {
+    let body = request
+        .body_content
+        .as_deref()
+        .or(request.body.as_deref())
+        .filter(|b| !b.trim().is_empty())?;
+    let text = match request.body_type.as_str() {
+        "none" => return None,
+        "json" | "multipart" => pretty_json(body).unwrap_or_else(|| body.to_string()),
+        "form" => match form_body::parse(body) {
+            Ok(rows) => rows
+                .into_iter()
+                .filter(|row| row.enabled)
+                .map(|row| format!("{}={}", row.key, row.value))
+                .collect::<Vec<_>>()
+                .join("\n"),
+            Err(_) => body.to_string(),
+        },
+        _ => body.to_string(),
+    };
+    Some(truncate(text))
+}
+
+fn example_response(response: LatestResponse) -> DocResponse {
+    let content_type = response
+        .content_type
+        .as_deref()
+        .and_then(|c| c.split(';').next())
+        .map(|c| c.trim().to_string())
+        .filter(|c| !c.is_empty());
+    let textual = content_type
+        .as_deref()
+        .is_none_or(|c| c.starts_with("text/") || c.contains("json") || c.contains("xml"));
+    let body = response
+        .body
+        .filter(|b| !b.is_empty())
+        .map(|bytes| match String::from_utf8(bytes) {
+            Ok(text) if textual => pretty_json(&text).unwrap_or(text),
+            Ok(text) => format!(
+                "({} bytes of {})",
+                text.len(),
+                content_type.as_deref().unwrap_or("data")
+            ),
+            Err(e) => format!("({} bytes of binary data)", e.as_bytes().len()),
+        })
+        .map(truncate);
+    DocResponse {
+        status: response.status,
+        content_type,
+        body,
+    }
+}
+
+fn doc_entry(
+    request: Request,
+    response: Option<LatestResponse>,
+    anchors: &mut HashSet<String>,
+) -> DocEntry {
+    let mut anchor = slug(&request.name);
+    let mut n = 2;
+    while !anchors.insert(anchor.clone()) {
+        anchor = format!("{}-{}", slug(&request.name), n);
+        n += 1;
+    }
+    let headers = request
+        .headers
+        .as_deref()
+        .and_then(|h| header_rows::parse(h).ok())
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|row| row.enabled && !row.key.is_empty())
+        .map(|row| {
+            let value = header_value(&row.key, &row.value);
+            (row.key, value)
+        })
+        .collect();
+    let method = if request.request_type == "ws" {
+        "WS".to_string()
+    } else {
+        request.method.clone()
+    };
+    DocEntry {
+        id: request.id,
+        anchor,
+        description: paragraphs(request.description.as_deref()),
+        auth: auth_label(&request.auth_type),
+        headers,
+        body: request_body(&request),
+        body_type: request.body_type,
+        response: response.map(example_response),
+        name: request.name,
+        method,
+        url: request.url,
+    }
+}
+
+pub async fn load_docs(pool: &DbPool, id: i64) -> Result<FolderDocs, DocsError> {
+    let folder = sqlx::query_as!(
+        FolderDb,
+        "SELECT id, name, variables, description, base_url, created_at, updated_at, archived_at FROM folders WHERE id = ?",
+        id
+    )
+    .fetch_optional(pool)
+    .await?
+    .ok_or(DocsError::FolderNotFound)?;
+
+    let rows = sqlx::query_as!(
+        RequestDb,
+        "SELECT id, name, method, url, body, headers, folder_id, request_type, body_type, body_content, auth_type, auth_token, auth_username, auth_password, captures, auth_config, serialized, ip_family, pre_request_script, test_script, callback, unresolved_variables, retry, compression, assertions, description, tls_versions, ssh_tunnel_id, latency_budget_ms, created_at, updated_at, archived_at FROM requests WHERE folder_id = ? AND archived_at IS NULL ORDER BY id",
+        id
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let mut anchors = HashSet::new();
+    let mut entries = Vec::new();
+    for row in rows {
+        let response = history::latest_response(pool, row.id).await?;
+        let mut request = Request::from(row);
+        request.url = folders::join_base_url(folder.base_url.as_deref(), &request.url);
+        entries.push(doc_entry(request, response, &mut anchors));
+    }
+    Ok(FolderDocs {
+        title: folder.name,
+        description: paragraphs(folder.description.as_deref()),
+        entries,
+    })
+}
+
+pub async fn load_request_docs(pool: &DbPool, id: i64) -> Result<FolderDocs, DocsError> {
+    let row = sqlx::query_as!(
+        RequestDb,
+        "SELECT id, name, method, url, body, headers, folder_id, request_type, body_type, body_content, auth_type, auth_token, auth_username, auth_password, captures, auth_config, serialized, ip_family, pre_request_script, test_script, callback, unresolved_variables, retry, compression, assertions, description, tls_versions, ssh_tunnel_id, latency_budget_ms, created_at, updated_at, archived_at FROM requests WHERE id = ? AND archived_at IS NULL",
+        id
+    )
+    .fetch_optional(pool)
+    .await?
+    .ok_or(DocsError::RequestNotFound)?;
+    let response = history::latest_response(pool, row.id).await?;
+    let entry = doc_entry(Request::from(row), response, &mut HashSet::new());
+    Ok(FolderDocs {
+        title: entry.name.clone(),
+        description: Vec::new(),
+        entries: vec![entry],
+    })
+}
+
+/// Standalone documentation of a folder's requests and their last responses,
+/// for sharing with people who don't run js-link.
+async fn folder_docs(
+    State(pool): State<DbPool>,
+    Path(id): Path<i64>,
+    Query(query): Query<DocsQuery>,
+) -> Result<Response, DocsError> {
+    let docs = load_docs(&pool, id).await?;
+    log::info!(
+        "Exporting documentation of folder {} with {} requests",
+        id,
+        docs.entries.len()
+    );
+
+    let name = slug(&docs.title);
+    let (content_type, filename, content) = match query.format {
+        DocsFormat::Html => (
+            "text/html; charset=utf-8",
+            format!("{}.html", name),
+            docs.html()?.into_bytes(),
+        ),
+        DocsFormat::Markdown => (
+            "text/markdown; charset=utf-8",
+            format!("{}.md", name),
+            docs.markdown().into_bytes(),
+        ),
+        DocsFormat::Zip => {
+            let mut zip = ZipFileWriter::with_tokio(Vec::new());
+            for (path, data) in [
+                ("index.html", docs.html()?.into_bytes()),
+                ("README.md", docs.markdown().into_bytes()),
+            ] {
+                let entry = ZipEntryBuilder::new(path.to_string().into(), Compression::Deflate);
+                zip.write_entry_whole(entry, &data).await?;
+            }
+            let data = zip.close().await?.into_inner();
+            ("application/zip", format!("{}-docs.zip", name), data)
+        }
+    };
+    Ok((
+        [
+            (header::CONTENT_TYPE, content_type.to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{}\"", filename),
+            ),
+        ],
+        content,
+    )
+        .into_response())
+}
+
+pub fn routes(pool: DbPool) -> Router {
+    Router::new()
+        .route("/export/folders/:id/docs", get(folder_docs))
+        .with_state(pool)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::create_test_pool;
+    use axum_test::TestServer;
+    use std::io::{Cursor, Read};
+
+    #[test]
+    fn test_fence() {
+        assert_eq!(fence("a", "json"), "```json\na\n```\n");
+        assert_eq!(fence("x ```` y", ""), "`````\nx ```` y\n`````\n");
+    }
+
+    #[tokio::test]
+    async fn test_folder_docs() {
+        let pool = create_test_pool().await;
+        let (folder_id,): (i64,) = sqlx::query_as(
+            "INSERT INTO folders (name, description) VALUES ('Orders API', 'Manage orders.\n\nNeeds a <b>token</b>.') RETURNING id",
+        )
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+        let (create_id,): (i64,) = sqlx::query_as(
+            "INSERT INTO requests (name, method, url, headers, folder_id, body_type, body_content, auth_type, auth_token, description) VALUES ('Create order', 'POST', '{{base}}/orders', '[{\"key\":\"X-Api-Key\",\"value\":\"sk_live_123\"},{\"key\":\"X-Token\",\"value\":\"{{token}}\"},{\"key\":\"X-Tenant\",\"value\":\"acme\"}]', ?, 'json', '{\"item\":\"book\",\"qty\":2}', 'bearer', 'hunter2', 'Places an order.') RETURNING id",
+        )
+        .bind(folder_id)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+        sqlx::query("INSERT INTO requests (name, method, url, folder_id, body_type, body_content) VALUES ('Create order', 'PUT', '{{base}}/orders/1', ?, 'form', '[{\"key\":\"note\",\"value\":\"x\"}]')")
+            .bind(folder_id)
+            .execute(&pool)
+            .await
+            .unwrap();
+        sqlx::query("INSERT INTO execution_history (request_id, method, url, status, duration_ms, response_body, response_content_type) VALUES (?, 'POST', 'https://api/orders', 201, 5, '{\"id\":7}', 'application/json')")
+            .bind(create_id)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let server = TestServer::new(routes(pool)).unwrap();
+        let url = format!("/export/folders/{}/docs", folder_id);
+
+        let response = server.get(&url).add_query_param("format", "markdown").await;
+        response.assert_status_ok();
+        assert!(response
+            .header(header::CONTENT_DISPOSITION)
+            .to_str()
+            .unwrap()
+            .contains("orders-api.md"));
+        let md = response.text();
+        assert!(md.starts_with("# Orders API\n\nManage orders.\n\n"));
+        assert!(md.contains(
+            "- [POST Create order](#create-order)\n- [PUT Create order](#create-order-2)"
+        ));
+        assert!(md.contains(
+            "`POST {{base}}/orders`\n\nPlaces an order.\n\nAuthentication: Bearer token"
+        ));
+        assert!(
+            md.contains("| X-Api-Key | (hidden) |\n| X-Token | {{token}} |\n| X-Tenant | acme |")
+        );
+        assert!(md.contains("```json\n{\n  \"item\": \"book\",\n  \"qty\": 2\n}\n```"));
+        assert!(md.contains("Status 201, `application/json`\n\n```json\n{\n  \"id\": 7\n}\n```"));
+        assert!(md.contains("### Body (form)\n\n```\nnote=x\n```"));
+        assert!(!md.contains("sk_live_123") && !md.contains("hunter2"));
+
+        let html = server
+            .get(&url)
+            .add_query_param("format", "html")
+            .await
+            .text();
+        assert!(html.contains("<h1>Orders API</h1>"));
+        assert!(html.contains("Needs a &lt;b&gt;token&lt;/b&gt;."));
+        assert!(html.contains("id=\"create-order-2\""));
+        assert!(!html.contains("sk_live_123"));
+
+        let response = server.get(&url).await;
+        assert_eq!(response.header(header::CONTENT_TYPE), "application/zip");
+        let mut archive = zip::ZipArchive::new(Cursor::new(response.as_bytes().to_vec())).unwrap();
+        let mut readme = String::new();
+        archive
+            .by_name("README.md")
+            .unwrap()
+            .read_to_string(&mut readme)
+            .unwrap();
+        assert!(readme.starts_with("# Orders API"));
+        assert!(archive.by_name("index.html").is_ok());
+
+        server
+            .get("/export/folders/999/docs")
+            .await
+            .assert_status(StatusCode::NOT_FOUND);
+    }
+}
@@ -0,0 +1,463 @@
+use serde::Serialize;
+use std::env;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use tokio::net::TcpListener;
+
+use crate::{
+    db::{self, DbPool, MigrationState},
+    executor, network, proxy,
+};
+
+const DEFAULT_CONNECTIVITY_URL: &str = "https://example.com/";
+const DISK_WARNING_BYTES: u64 = 500 * 1024 * 1024;
+const DISK_FAILURE_BYTES: u64 = 50 * 1024 * 1024;
+const PROXY_VARIABLES: [&str; 8] = [
+    "HTTP_PROXY",
+    "http_proxy",
+    "HTTPS_PROXY",
+    "https_proxy",
+    "ALL_PROXY",
+    "all_proxy",
+    "NO_PROXY",
+    "no_proxy",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CheckStatus {
+    Ok,
+    Warning,
+    Failed,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Check {
+    pub name: &'static str,
+    pub status: CheckStatus,
+    pub message: String,
+    /// What to do about a warning or failure
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hint: Option<String>,
+}
+
+impl Check {
+    fn ok(name: &'static str, message: impl Into<String>) -> Self {
+        Check {
+            name,
+            status: CheckStatus::Ok,
+            message: message.into(),
+            hint: None,
+        }
+    }
+
+    fn warning(name: &'static str, message: impl Into<String>, hint: impl Into<String>) -> Self {
+        Check {
+            name,
+            status: CheckStatus::Warning,
+            message: message.into(),
+            hint: Some(hint.into()),
+        }
+    }
+
+    fn failed(name: &'static str, message: impl Into<String>, hint: impl Into<String>) -> Self {
+        Check {
+            name,
+            status: CheckStatus::Failed,
+            message: message.into(),
+            hint: Some(hint.into()),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct DoctorReport {
+    /// False when any check failed; warnings do not count.
+    pub healthy: bool,
+    pub checks: Vec<Check>,
+}
+
+impl DoctorReport {
+    /// Renders the report for the terminal, one line per check.
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        for check in &self.checks {
+            let label = match check.status {
+                CheckStatus::Ok => " ok ",
+                CheckStatus::Warning => "warn",
+                CheckStatus::Failed => "FAIL",
+            };
+            out.push_str(&format!("[{}] {}: {}\n", label, check.name, check.message));
+            if let Some(hint) = &check.hint {
+                out.push_str(&format!("       -> {}\n", hint));
+            }
+        }
+        out.push_str(if self.healthy {
+            "\nAll checks passed.\n"
+        } else {
+            "\nSome checks failed, see the hints above.\n"
+        });
+        out
+    }
+}
+
+pub struct DoctorOptions {
+    pub log_dir: PathBuf,
+    /// Fetched to prove outbound connectivity, from `JSLINK_DOCTOR_URL`.
+    pub connectivity_url: String,
+    /// `HOST:PORT` to try binding, `None` when the server is already listening.
+    pub bind_addr: Option<String>,
+}
+
+impl DoctorOptions {
+    pub fn from_env(check_port: bool) -> Self {
+        let bind_addr = check_port.then(|| {
+            format!(
+                "{}:{}",
+                env::var("HOST").unwrap_or_else(|_| "127.0.0.1".to_string()),
+                env::var("PORT").unwrap_or_else(|_| "3000".to_string())
+            )
+        });
+        DoctorOptions {
+            log_dir: PathBuf::from("./logs"),
+            connectivity_url: env::var("JSLINK_DOCTOR_URL")
+                .ok()
+                .filter(|u| !u.trim().is_empty())
+                .unwrap_or_else(|| DEFAULT_CONNECTIVITY_URL.to_string()),
+            bind_addr,
+        }
+    }
+}
+
+/// Runs every check. `pool` is `None` when the database could not be opened,
+/// with the reason in `connect_error`.
+pub async fn run(
+    pool: Option<&DbPool>,
+    connect_error: Option<String>,
+    options: &DoctorOptions,
+) -> DoctorReport {
+    let mut checks = Vec::new();
+    match pool {
+        Some(pool) => {
+            checks.push(check_database(pool).await);
+            checks.push(check_migrations(pool).await);
+        }
+        None => checks.push(Check::failed(
+            "database",
+            connect_error.unwrap_or_else(|| "The database could not be opened".to_string()),
+            "Check DATABASE_URL and that the directory holding the database exists and is writable",
+        )),
+    }
+    checks.push(check_disk_space(&database_dir()));
+    checks.push(check_log_dir(&options.log_dir));
+    let proxy_env: Vec<(&str, String)> = PROXY_VARIABLES
+        .iter()
+        .filter_map(|name| env::var(name).ok().map(|value| (*name, value)))
+        .collect();
+    checks.push(check_proxy_env(&proxy_env));
+    checks.push(check_connectivity(pool, &options.connectivity_url).await);
+    checks.push(match &options.bind_addr {
+        Some(addr) => check_port(addr).await,
+        None => Check::ok("port", "The server is listening"),
+    });
+
+    DoctorReport {
+        healthy: checks.iter().all(|c| c.status != CheckStatus::Failed),
+        checks,
+    }
+}
+
+/// Takes the write lock by creating a table in a transaction that is rolled back.
+async fn check_database(pool: &DbPool) -> Check {
+    let probe = async {
+        let mut tx = pool.begin().await?;
+        sqlx::query("CREATE TABLE _doctor_probe (id INTEGER)")
+            .execute(&mut *tx)
+            .await?;
+        tx.rollback().await
+    };
+    match probe.await {
+        Ok(()) => Check::ok("database", format!("{} is writable", db::database_url())),
+        Err(e) => Check::failed(
+            "database",
+            format!("{} is not writable: {}", db::database_url(), e),
+            "Make sure the database file and its directory are writable by the user running js-link, and that no other process holds a lock on it",
+        ),
+    }
+}
+
+async fn check_migrations(pool: &DbPool) -> Check {
+    let report = match db::migration_status(pool).await {
+        Ok(report) => report,
+        Err(e) => {
+            return Check::failed(
+                "migrations",
+                format!("Could not read migration records: {}", e),
+                "Run js-link with --repair-migrations",
+            )
+        }
+    };
+    let count = |state| report.iter().filter(|s| s.state == state).count();
+    let broken = count(MigrationState::Failed) + count(MigrationState::ChecksumMismatch);
+    let pending = count(MigrationState::Pending);
+    let unknown = count(MigrationState::Unknown);
+
+    if broken > 0 {
+        Check::failed(
+            "migrations",
+            format!("{} migration(s) failed or were modified after being applied", broken),
+            "Start js-link with --repair-migrations; a backup of the database is taken before migrating",
+        )
+    } else if unknown > 0 {
+        Check::warning(
+            "migrations",
+            format!("{} migration(s) were applied by a newer js-link", unknown),
+            "Upgrade js-link, or restore a backup taken before the newer version ran",
+        )
+    } else if pending > 0 {
+        Check::warning(
+            "migrations",
+            format!("{} migration(s) are pending", pending),
+            "They are applied the next time the server starts (unless --skip-migrations is set)",
+        )
+    } else {
+        Check::ok(
+            "migrations",
+            format!("{} migration(s) applied", report.len()),
+        )
+    }
+}
+
+/// Directory of the SQLite file, where backups and the WAL are written too.
+fn database_dir() -> PathBuf {
+    let url = db::database_url();
+    let path = url
+        .strip_prefix("sqlite://")
+        .or_else(|| url.strip_prefix("sqlite:"))
+        .unwrap_or(&url);
+    let path = path.split('?').next().unwrap_or_default();
+    Path::new(path)
+        .parent()
+        .filter(|p| !p.as_os_str().is_empty())
+        .map(Path::to_path_buf)
+        .unwrap_or_else(|| PathBuf::from("."))
+}
+
+fn check_disk_space(dir: &Path) -> Check {
+    let Some(available) = available_space(dir) else {
+        return Check::ok("disk_space", "Free space is not checked on this platform");
+    };
+    let message = format!("{} MB free in {}", available / (1024 * 1024), dir.display());
+    if available < DISK_FAILURE_BYTES {
+        Check::failed(
+            "disk_space",
+            message,
+            "Free up space; SQLite cannot write, migrate or back up the database on a full disk",
+        )
+    } else if available < DISK_WARNING_BYTES {
+        Check::warning(
+            "disk_space",
+            message,
+            "Free up space or move the database (DATABASE_URL) to a larger volume",
+        )
+    } else {
+        Check::ok("disk_space", message)
+    }
+}
+
+#[cfg(unix)]
+fn available_space(dir: &Path) -> Option<u64> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    let path = CString::new(dir.as_os_str().as_bytes()).ok()?;
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    // SAFETY: `path` is NUL-terminated and `stat` is a valid out pointer.
+    if unsafe { libc::statvfs(path.as_ptr(), &mut stat) } != 0 {
+        return None;
+    }
+    #[allow(clippy::unnecessary_cast)]
+    Some(stat.f_bavail as u64 * stat.f_frsize as u64)
+}
+
+#[cfg(not(unix))]
+fn available_space(_dir: &Path) -> Option<u64> {
+    None
+}
+
+fn check_log_dir(dir: &Path) -> Check {
+    let probe = dir.join(".doctor-probe");
+    let result = std::fs::create_dir_all(dir)
+        .and_then(|_| std::fs::write(&probe, b"probe"))
+        .and_then(|_| std::fs::remove_file(&probe));
+    match result {
+        Ok(()) => Check::ok("log_dir", format!("{} is writable", dir.display())),
+        Err(e) => Check::failed(
+            "log_dir",
+            format!("Cannot write to {}: {}", dir.display(), e),
+            "Run js-link from a directory it may write to; logs are kept in ./logs next to the working directory",
+        ),
+    }
+}
+
+/// Flags proxy variables that cannot be parsed, and upper/lowercase pairs
+/// that disagree (tools differ in which one they read).
+fn check_proxy_env(vars: &[(&str, String)]) -> Check {
+    let mut problems = Vec::new();
+    for (name, value) in vars {
+        if value.trim().is_empty() || name.eq_ignore_ascii_case("NO_PROXY") {
+            continue;
+        }
+        if let Err(e) = proxy::parse_proxy_url(value) {
+            problems.push(format!("{}: {}", name, e));
+        }
+    }
+    for (upper, lower) in [
+        ("HTTP_PROXY", "http_proxy"),
+        ("HTTPS_PROXY", "https_proxy"),
+        ("ALL_PROXY", "all_proxy"),
+        ("NO_PROXY", "no_proxy"),
+    ] {
+        let get = |name| vars.iter().find(|(n, _)| *n == name).map(|(_, v)| v.trim());
+        if let (Some(a), Some(b)) = (get(upper), get(lower)) {
+            if a != b {
+                problems.push(format!("{} and {} differ", upper, lower));
+            }
+        }
+    }
+
+    if !problems.is_empty() {
+        return Check::warning(
+            "proxy_env",
+            problems.join("; "),
+            "Fix or unset these variables, or switch off auto proxy in the network settings and configure the proxy there",
+        );
+    }
+    let set: Vec<&str> = vars
+        .iter()
+        .filter(|(_, v)| !v.trim().is_empty())
+        .map(|(n, _)| *n)
+        .collect();
+    if set.is_empty() {
+        Check::ok("proxy_env", "No proxy variables set")
+    } else {
+        Check::ok("proxy_env", format!("Using {}", set.join(", ")))
+    }
+}
+
+/// Fetches a known URL through the same proxy settings as executed requests.
+async fn check_connectivity(pool: Option<&DbPool>, url: &str) -> Check {
+    let settings = match pool {
+        Some(pool) => network::load_settings(pool).await.ok(),
+        None => None,
+    }
+    .unwrap_or_else(network::NetworkSettingsDb::defaults);
+    let pac_decision = settings.pac_decision(url).await;
+    let client = match executor::build_reqwest_client(settings, pac_decision) {
+        Ok(client) => client,
+        Err(e) => {
+            return Check::failed(
+                "connectivity",
+                e.to_string(),
+                "Review the proxy settings under Network",
+            )
+        }
+    };
+
+    let started = Instant::now();
+    match client
+        .get(url)
+        .timeout(Duration::from_secs(10))
+        .send()
+        .await
+    {
+        Ok(response) => Check::ok(
+            "connectivity",
+            format!(
+                "{} answered {} in {} ms",
+                url,
+                response.status().as_u16(),
+                started.elapsed().as_millis()
+            ),
+        ),
+        Err(e) => Check::failed(
+            "connectivity",
+            format!("Could not reach {}: {}", url, e),
+            "Check the network and proxy settings; behind a corporate proxy set HTTPS_PROXY or configure it under Network. Set JSLINK_DOCTOR_URL to test another host",
+        ),
+    }
+}
+
+async fn check_port(addr: &str) -> Check {
+    match TcpListener::bind(addr).await {
+        Ok(_) => Check::ok("port", format!("{} is available", addr)),
+        Err(e) if e.kind() == std::io::ErrorKind::AddrInUse => Check::failed(
+            "port",
+            format!("{} is already in use", addr),
+            "Stop the process using it (possibly another js-link), choose another port with PORT or let JSLINK_PORT_FALLBACK try the next ones",
+        ),
+        Err(e) => Check::failed(
+            "port",
+            format!("Cannot listen on {}: {}", addr, e),
+            "Check HOST and PORT; ports below 1024 need elevated privileges",
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use httpmock::prelude::*;
+
+    #[tokio::test]
+    async fn test_run_reports_healthy_setup() {
+        let pool = db::create_test_pool().await;
+        let server = MockServer::start();
+        server.mock(|when, then| {
+            when.method(GET).path("/");
+            then.status(204);
+        });
+        let options = DoctorOptions {
+            log_dir: env::temp_dir().join("jslink-doctor-test"),
+            connectivity_url: server.url("/"),
+            bind_addr: Some("127.0.0.1:0".to_string()),
+        };
+
+        let report = run(Some(&pool), None, &options).await;
+        let status = |name| {
+            report
+                .checks
+                .iter()
+                .find(|c| c.name == name)
+                .unwrap()
+                .status
+        };
+        assert_eq!(status("database"), CheckStatus::Ok);
+        assert_eq!(status("migrations"), CheckStatus::Ok);
+        assert_eq!(status("log_dir"), CheckStatus::Ok);
+        assert_eq!(status("connectivity"), CheckStatus::Ok);
+        assert_eq!(status("port"), CheckStatus::Ok);
+
+        let report = run(None, Some("unable to open".to_string()), &options).await;
+        assert!(!report.healthy);
+        assert!(report.render().contains("[FAIL] database: unable to open"));
+    }
+
+    #[test]
+    fn test_check_proxy_env() {
+        let ok = check_proxy_env(&[("HTTPS_PROXY", "http://proxy:3128".to_string())]);
+        assert_eq!(ok.status, CheckStatus::Ok);
+
+        let invalid = check_proxy_env(&[("HTTPS_PROXY", "ftp://proxy:21".to_string())]);
+        assert_eq!(invalid.status, CheckStatus::Warning);
+
+        let mismatch = check_proxy_env(&[
+            ("HTTP_PROXY", "http://a:3128".to_string()),
+            ("http_proxy", "http://b:3128".to_string()),
+        ]);
+        assert_eq!(mismatch.status, CheckStatus::Warning);
+        assert!(mismatch
+            .message
+            .contains("HTTP_PROXY and http_proxy differ"));
+    }
+}
@@ -0,0 +1,464 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Json, Router,
+};
+use chrono::{DateTime, NaiveDateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::{
+    audit::Actor,
+    crypto,
+    db::DbPool,
+    i18n,
+    requests::{self, PatchRequest, Request, RequestError},
+};
+
+// The UI autosaves edits of a request here while they are in progress, so
+// the saved request only changes when the user saves. A draft holds just the
+// fields that differ from the saved request, in the shape PATCH takes them.
+
+/// Request fields a draft cannot change.
+const FIXED_FIELDS: &[&str] = &["id", "created_at", "updated_at", "archived_at"];
+/// Fields encrypted at rest, as in the requests table.
+const CREDENTIAL_FIELDS: &[&str] = &["auth_token", "auth_username", "auth_password"];
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Draft {
+    pub request_id: i64,
+    pub request_name: String,
+    /// The edited fields and their unsaved values
+    pub changes: Map<String, Value>,
+    /// Whether the request was saved since editing started, so that
+    /// committing would overwrite someone else's change
+    pub stale: bool,
+    pub base_updated_at: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// A draft with the request as the editor shows it: saved fields with the
+/// changes applied.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DraftDetail {
+    #[serde(flatten)]
+    pub draft: Draft,
+    pub request: Map<String, Value>,
+}
+
+struct DraftDb {
+    request_id: i64,
+    request_name: String,
+    changes: String,
+    request_updated_at: NaiveDateTime,
+    base_updated_at: NaiveDateTime,
+    created_at: NaiveDateTime,
+    updated_at: NaiveDateTime,
+}
+
+impl TryFrom<DraftDb> for Draft {
+    type Error = DraftError;
+
+    fn try_from(d: DraftDb) -> Result<Self, Self::Error> {
+        let changes = serde_json::from_str(&d.changes)
+            .map_err(|e| DraftError::InvalidChanges(e.to_string()))?;
+        Ok(Self {
+            request_id: d.request_id,
+            request_name: d.request_name,
+            changes: decrypt_credentials(d.request_id, changes),
+            stale: d.request_updated_at > d.base_updated_at,
+            base_updated_at: d.base_updated_at.and_utc(),
+            created_at: d.created_at.and_utc(),
+            updated_at: d.updated_at.and_utc(),
+        })
+    }
+}
+
+#[derive(Deserialize)]
+pub struct CommitQuery {
+    /// Commit even though the request was saved since editing started
+    #[serde(default)]
+    force: bool,
+}
+
+pub enum DraftError {
+    RequestNotFound,
+    DraftNotFound,
+    NotAnObject,
+    UnknownField(String),
+    InvalidChanges(String),
+    Stale,
+    Request(RequestError),
+    EncryptionError(crypto::CryptoError),
+    DatabaseError(sqlx::Error),
+}
+
+impl From<sqlx::Error> for DraftError {
+    fn from(e: sqlx::Error) -> Self {
+        DraftError::DatabaseError(e)
+    }
+}
+
+impl IntoResponse for DraftError {
+    fn into_response(self) -> Response {
+        match self {
+            DraftError::RequestNotFound => {
+                (StatusCode::NOT_FOUND, i18n::t("error.request_not_found")).into_response()
+            }
+            DraftError::DraftNotFound => {
+                (StatusCode::NOT_FOUND, i18n::t("error.draft_not_found")).into_response()
+            }
+            DraftError::NotAnObject => {
+                (StatusCode::BAD_REQUEST, i18n::t("error.draft_not_object")).into_response()
+            }
+            DraftError::UnknownField(name) => (
+                StatusCode::BAD_REQUEST,
+                i18n::tf("error.draft_unknown_field", &[("name", &name)]),
+            )
+                .into_response(),
+            DraftError::InvalidChanges(msg) => (
+                StatusCode::BAD_REQUEST,
+                i18n::tf("error.draft_invalid", &[("error", &msg)]),
+            )
+                .into_response(),
+            DraftError::Stale => {
+                (StatusCode::CONFLICT, i18n::t("error.draft_stale")).into_response()
+            }
+            DraftError::Request(e) => e.into_response(),
+            DraftError::EncryptionError(e) => {
+                log::error!("Failed to encrypt draft credentials: {}", e);
+                (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response()
+            }
+            DraftError::DatabaseError(e) => {
+                log::error!("Database error: {}", e);
+                (StatusCode::INTERNAL_SERVER_ERROR, i18n::t("error.database")).into_response()
+            }
+        }
+    }
+}
+
+/// The saved request as the API returns it, credentials decrypted.
+async fn saved_fields(pool: &DbPool, id: i64) -> Result<Map<String, Value>, DraftError> {
+    let stored = match requests::load_request(pool, id).await {
+        Ok(stored) => stored,
+        Err(sqlx::Error::RowNotFound) => return Err(DraftError::RequestNotFound),
+        Err(e) => return Err(e.into()),
+    };
+    match serde_json::to_value(Request::from(stored)) {
+        Ok(Value::Object(fields)) => Ok(fields),
+        _ => unreachable!("a request serializes to an object"),
+    }
+}
+
+fn encrypt_credentials(mut changes: Map<String, Value>) -> Result<Map<String, Value>, DraftError> {
+    for field in CREDENTIAL_FIELDS {
+        if let Some(Value::String(value)) = changes.get(*field) {
+            let encrypted = requests::encrypt_credential(Some(value))
+                .map_err(DraftError::EncryptionError)?
+                .unwrap_or_default();
+            changes.insert(field.to_string(), Value::String(encrypted));
+        }
+    }
+    Ok(changes)
+}
+
+/// Like saved requests, a value that cannot be decrypted is left out.
+fn decrypt_credentials(request_id: i64, mut changes: Map<String, Value>) -> Map<String, Value> {
+    for field in CREDENTIAL_FIELDS {
+        if let Some(Value::String(value)) = changes.get(*field) {
+            match crypto::decrypt(value) {
+                Ok(plaintext) => {
+                    changes.insert(field.to_string(), Value::String(plaintext));
+                }
+                Err(e) => {
+                    log::warn!(
+                        "Could not decrypt draft credentials of request {}: {}",
+                        request_id,
+                        e
+                    );
+                    changes.remove(*field);
+                }
+            }
+        }
+    }
+    changes
+}
+
+async fn load_draft(pool: &DbPool, request_id: i64) -> Result<Draft, DraftError> {
+    sqlx::query_as!(
+        DraftDb,
+        r#"SELECT d.request_id AS "request_id!", r.name AS request_name, d.changes, r.updated_at AS "request_updated_at: NaiveDateTime",
+            d.base_updated_at AS "base_updated_at: NaiveDateTime", d.created_at AS "created_at: NaiveDateTime", d.updated_at AS "updated_at: NaiveDateTime"
+        FROM request_drafts d JOIN requests r ON r.id = d.request_id WHERE d.request_id = ?"#,
+        request_id
+    )
+    .fetch_optional(pool)
+    .await?
+    .ok_or(DraftError::DraftNotFound)?
+    .try_into()
+}
+
+async fn list_drafts(State(pool): State<DbPool>) -> Result<impl IntoResponse, DraftError> {
+    let drafts = sqlx::query_as!(
+        DraftDb,
+        r#"SELECT d.request_id AS "request_id!", r.name AS request_name, d.changes, r.updated_at AS "request_updated_at: NaiveDateTime",
+            d.base_updated_at AS "base_updated_at: NaiveDateTime", d.created_at AS "created_at: NaiveDateTime", d.updated_at AS "updated_at: NaiveDateTime"
+        FROM request_drafts d JOIN requests r ON r.id = d.request_id ORDER BY d.updated_at DESC, d.request_id"#
+    )
+    .fetch_all(&pool)
+    .await?;
+    let drafts = drafts
+        .into_iter()
+        .map(Draft::try_from)
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(Json(drafts))
+}
+
+async fn get_draft(
+    State(pool): State<DbPool>,
+    Path(request_id): Path<i64>,
+) -> Result<impl IntoResponse, DraftError> {
+    let mut request = saved_fields(&pool, request_id).await?;
+    let draft = load_draft(&pool, request_id).await?;
+    request.extend(draft.changes.clone());
+    Ok(Json(DraftDetail { draft, request }))
+}
+
+/// Autosaves the edited fields. Fields edited back to their saved value are
+/// dropped, and a draft left without changes is discarded.
+async fn save_draft(
+    State(pool): State<DbPool>,
+    Path(request_id): Path<i64>,
+    Json(payload): Json<Value>,
+) -> Result<Response, DraftError> {
+    let Value::Object(edited) = payload else {
+        return Err(DraftError::NotAnObject);
+    };
+    let saved = saved_fields(&pool, request_id).await?;
+    if let Some(name) = edited
+        .keys()
+        .find(|name| FIXED_FIELDS.contains(&name.as_str()) || !saved.contains_key(*name))
+    {
+        return Err(DraftError::UnknownField(name.clone()));
+    }
+    // Checked the way PATCH will read them when the draft is committed
+    serde_json::from_value::<PatchRequest>(Value::Object(edited.clone()))
+        .map_err(|e| DraftError::InvalidChanges(e.to_string()))?;
+
+    let changes: Map<String, Value> = edited
+        .into_iter()
+        .filter(|(name, value)| saved.get(name) != Some(value))
+        .collect();
+    if changes.is_empty() {
+        sqlx::query!(
+            "DELETE FROM request_drafts WHERE request_id = ?",
+            request_id
+        )
+        .execute(&pool)
+        .await?;
+        log::debug!("Draft of request {} matches the saved request", request_id);
+        return Ok(StatusCode::NO_CONTENT.into_response());
+    }
+
+    let stored = serde_json::to_string(&encrypt_credentials(changes)?)
+        .map_err(|e| DraftError::InvalidChanges(e.to_string()))?;
+    // A draft keeps the version it started from until it is committed or
+    // discarded, so saves made meanwhile show as stale
+    sqlx::query!(
+        "INSERT INTO request_drafts (request_id, changes, base_updated_at) SELECT id, ?, updated_at FROM requests WHERE id = ?
+        ON CONFLICT (request_id) DO UPDATE SET changes = excluded.changes, updated_at = CURRENT_TIMESTAMP",
+        stored,
+        request_id
+    )
+    .execute(&pool)
+    .await?;
+    log::debug!("Saved draft of request {}", request_id);
+    Ok(Json(load_draft(&pool, request_id).await?).into_response())
+}
+
+async fn discard_draft(
+    State(pool): State<DbPool>,
+    Path(request_id): Path<i64>,
+) -> Result<impl IntoResponse, DraftError> {
+    let result = sqlx::query!(
+        "DELETE FROM request_drafts WHERE request_id = ?",
+        request_id
+    )
+    .execute(&pool)
+    .await?;
+    if result.rows_affected() == 0 {
+        return Err(DraftError::DraftNotFound);
+    }
+    log::info!("Discarded draft of request {}", request_id);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Saves the draft to the request, as a PATCH with its changes, and drops it.
+async fn commit_draft(
+    State(pool): State<DbPool>,
+    Path(request_id): Path<i64>,
+    Query(query): Query<CommitQuery>,
+    actor: Actor,
+) -> Result<impl IntoResponse, DraftError> {
+    let draft = load_draft(&pool, request_id).await?;
+    if draft.stale && !query.force {
+        log::warn!(
+            "Not committing draft of request {}: the request was saved since",
+            request_id
+        );
+        return Err(DraftError::Stale);
+    }
+    let patch = serde_json::from_value::<PatchRequest>(Value::Object(draft.changes))
+        .map_err(|e| DraftError::InvalidChanges(e.to_string()))?;
+    requests::patch_request(State(pool.clone()), Path(request_id), actor, Json(patch))
+        .await
+        .map_err(DraftError::Request)?;
+    sqlx::query!(
+        "DELETE FROM request_drafts WHERE request_id = ?",
+        request_id
+    )
+    .execute(&pool)
+    .await?;
+
+    log::info!("Committed draft of request {}", request_id);
+    Ok(Json(saved_fields(&pool, request_id).await?))
+}
+
+pub fn routes(pool: DbPool) -> Router {
+    Router::new()
+        .route("/drafts", get(list_drafts))
+        .route(
+            "/requests/:id/draft",
+            get(get_draft).put(save_draft).delete(discard_draft),
+        )
+        .route("/requests/:id/draft/commit", post(commit_draft))
+        .with_state(pool)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::create_test_pool;
+    use axum_test::TestServer;
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn test_drafts() {
+        let pool = create_test_pool().await;
+        let (request_id,): (i64,) = sqlx::query_as(
+            "INSERT INTO requests (name, method, url, body, updated_at) VALUES ('Create order', 'POST', 'https://api.test/orders', '{}', '2026-01-01 00:00:00') RETURNING id",
+        )
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+        let server =
+            TestServer::new(routes(pool.clone()).merge(requests::routes(pool.clone()))).unwrap();
+        let draft_url = format!("/requests/{}/draft", request_id);
+
+        server
+            .put(&draft_url)
+            .json(&json!(["body"]))
+            .await
+            .assert_status(StatusCode::BAD_REQUEST);
+        server
+            .put(&draft_url)
+            .json(&json!({ "id": 7 }))
+            .await
+            .assert_status(StatusCode::BAD_REQUEST);
+        server
+            .put(&draft_url)
+            .json(&json!({ "serialized": "yes" }))
+            .await
+            .assert_status(StatusCode::BAD_REQUEST);
+        server
+            .put("/requests/999/draft")
+            .json(&json!({ "body": "{}" }))
+            .await
+            .assert_status(StatusCode::NOT_FOUND);
+
+        // Unchanged fields are not part of the draft
+        let draft: Draft = server
+            .put(&draft_url)
+            .json(&json!({ "url": "https://api.test/orders", "body": "{\"sku\":" }))
+            .await
+            .json();
+        assert_eq!(
+            draft.changes,
+            json!({ "body": "{\"sku\":" }).as_object().cloned().unwrap()
+        );
+        assert!(!draft.stale);
+        let draft: Draft = server
+            .put(&draft_url)
+            .json(&json!({ "body": "{\"sku\":1}", "auth_type": "bearer", "auth_token": "t0k" }))
+            .await
+            .json();
+        assert_eq!(draft.changes.len(), 3);
+        let (stored,): (String,) = sqlx::query_as("SELECT changes FROM request_drafts")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert!(!stored.contains("t0k"));
+
+        let drafts: Vec<Draft> = server.get("/drafts").await.json();
+        assert_eq!(drafts.len(), 1);
+        assert_eq!(drafts[0].request_name, "Create order");
+        assert_eq!(drafts[0].changes["auth_token"], "t0k");
+        let detail: DraftDetail = server.get(&draft_url).await.json();
+        assert_eq!(detail.request["body"], "{\"sku\":1}");
+        assert_eq!(detail.request["method"], "POST");
+
+        // Saving the request meanwhile makes the draft stale
+        server
+            .patch(&format!("/requests/{}", request_id))
+            .json(&json!({ "name": "Place order" }))
+            .await
+            .assert_status_ok();
+        let detail: DraftDetail = server.get(&draft_url).await.json();
+        assert!(detail.draft.stale);
+        server
+            .post(&format!("{}/commit", draft_url))
+            .await
+            .assert_status(StatusCode::CONFLICT);
+        let committed: Map<String, Value> = server
+            .post(&format!("{}/commit?force=true", draft_url))
+            .await
+            .json();
+        assert_eq!(committed["name"], "Place order");
+        assert_eq!(committed["body"], "{\"sku\":1}");
+        assert_eq!(committed["auth_token"], "t0k");
+        server
+            .get(&draft_url)
+            .await
+            .assert_status(StatusCode::NOT_FOUND);
+
+        // Editing back to the saved value leaves nothing to keep
+        server
+            .put(&draft_url)
+            .json(&json!({ "url": "https://api.test/v2/orders" }))
+            .await
+            .assert_status_ok();
+        server
+            .put(&draft_url)
+            .json(&json!({ "url": "https://api.test/orders" }))
+            .await
+            .assert_status(StatusCode::NO_CONTENT);
+        server
+            .put(&draft_url)
+            .json(&json!({ "method": "PUT" }))
+            .await
+            .assert_status_ok();
+        server
+            .delete(&draft_url)
+            .await
+            .assert_status(StatusCode::NO_CONTENT);
+        server
+            .delete(&draft_url)
+            .await
+            .assert_status(StatusCode::NOT_FOUND);
+        let drafts: Vec<Draft> = server.get("/drafts").await.json();
+        assert!(drafts.is_empty());
+    }
+}
@@ -0,0 +1,162 @@
+use chrono::{SecondsFormat, Utc};
+use rand::{seq::SliceRandom, Rng};
+
+// Built-in `{{$name}}` variables, generated anew for every placeholder so
+// each execution sends fresh ids and test data.
+
+const FIRST_NAMES: &[&str] = &[
+    "Ada", "Alan", "Grace", "Linus", "Margaret", "Dennis", "Barbara", "Ken", "Frances", "Niklaus",
+];
+const LAST_NAMES: &[&str] = &[
+    "Lovelace", "Turing", "Hopper", "Torvalds", "Hamilton", "Ritchie", "Liskov", "Thompson",
+    "Allen", "Wirth",
+];
+const WORDS: &[&str] = &[
+    "alpha", "bridge", "cobalt", "delta", "ember", "falcon", "granite", "harbor", "island",
+    "juniper", "kernel", "lantern", "meadow", "nimbus", "orbit", "pixel",
+];
+const DOMAINS: &[&str] = &["example.com", "example.org", "example.net"];
+const ALPHANUMERIC: &[u8] = b"abcdefghijklmnopqrstuvwxyz0123456789";
+
+/// Names of the built-in variables, without the `$`.
+pub const NAMES: &[&str] = &[
+    "uuid",
+    "guid",
+    "timestamp",
+    "isoTimestamp",
+    "randomInt",
+    "randomBoolean",
+    "randomAlphaNumeric",
+    "randomHexColor",
+    "randomIP",
+    "randomWord",
+    "randomFirstName",
+    "randomLastName",
+    "randomFullName",
+    "randomUserName",
+    "randomEmail",
+    "randomPhoneNumber",
+];
+
+fn pick(items: &[&'static str]) -> &'static str {
+    items
+        .choose(&mut rand::thread_rng())
+        .copied()
+        .unwrap_or_default()
+}
+
+fn uuid_v4() -> String {
+    let mut bytes: [u8; 16] = rand::thread_rng().gen();
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+    let hex: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[0..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..32]
+    )
+}
+
+/// A fresh value for `{{$name}}`, or `None` for unknown names.
+pub fn generate(name: &str) -> Option<String> {
+    let mut rng = rand::thread_rng();
+    let value = match name {
+        "uuid" | "guid" => uuid_v4(),
+        "timestamp" => Utc::now().timestamp().to_string(),
+        "isoTimestamp" => Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true),
+        "randomInt" => rng.gen_range(0..=1000).to_string(),
+        "randomBoolean" => rng.gen::<bool>().to_string(),
+        "randomAlphaNumeric" => {
+            char::from(ALPHANUMERIC[rng.gen_range(0..ALPHANUMERIC.len())]).to_string()
+        }
+        "randomHexColor" => format!("#{:06x}", rng.gen_range(0..=0xffffff)),
+        "randomIP" => format!(
+            "{}.{}.{}.{}",
+            rng.gen_range(1..=254),
+            rng.gen::<u8>(),
+            rng.gen::<u8>(),
+            rng.gen_range(1..=254)
+        ),
+        "randomWord" => pick(WORDS).to_string(),
+        "randomFirstName" => pick(FIRST_NAMES).to_string(),
+        "randomLastName" => pick(LAST_NAMES).to_string(),
+        "randomFullName" => format!("{} {}", pick(FIRST_NAMES), pick(LAST_NAMES)),
+        "randomUserName" => format!(
+            "{}.{}{}",
+            pick(FIRST_NAMES).to_lowercase(),
+            pick(LAST_NAMES).to_lowercase(),
+            rng.gen_range(1..=99)
+        ),
+        "randomEmail" => format!(
+            "{}.{}{}@{}",
+            pick(FIRST_NAMES).to_lowercase(),
+            pick(LAST_NAMES).to_lowercase(),
+            rng.gen_range(1..=999),
+            pick(DOMAINS)
+        ),
+        "randomPhoneNumber" => format!(
+            "{}-{}-{}",
+            rng.gen_range(200..=999),
+            rng.gen_range(200..=999),
+            rng.gen_range(1000..=9999)
+        ),
+        _ => return None,
+    };
+    Some(value)
+}
+
+/// Replaces every known `{{$name}}` placeholder; unknown ones are left in
+/// place.
+pub fn substitute(template: &str) -> String {
+    let mut result = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find("{{$") {
+        let Some(len) = rest[start..].find("}}") else {
+            break;
+        };
+        let placeholder = &rest[start..start + len + 2];
+        result.push_str(&rest[..start]);
+        match generate(placeholder[3..len].trim()) {
+            Some(value) => result.push_str(&value),
+            None => result.push_str(placeholder),
+        }
+        rest = &rest[start + len + 2..];
+    }
+    result.push_str(rest);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_every_name_generates() {
+        for name in NAMES {
+            assert!(generate(name).is_some_and(|v| !v.is_empty()), "{}", name);
+        }
+        assert!(generate("nope").is_none());
+
+        let uuid = generate("uuid").unwrap();
+        assert_eq!(uuid.len(), 36);
+        assert_eq!(&uuid[14..15], "4");
+        assert_ne!(uuid, generate("uuid").unwrap());
+        assert!(generate("randomEmail").unwrap().contains('@'));
+        assert!(generate("isoTimestamp").unwrap().ends_with('Z'));
+    }
+
+    #[test]
+    fn test_substitute() {
+        let result =
+            substitute(r#"{"id": "{{$uuid}}", "other": "{{$uuid}}", "n": {{$randomInt}}}"#);
+        let value: serde_json::Value = serde_json::from_str(&result).unwrap();
+        assert_ne!(value["id"], value["other"]);
+        assert!(value["n"].as_u64().unwrap() <= 1000);
+
+        assert_eq!(substitute("{{$unknown}} {{name}}"), "{{$unknown}} {{name}}");
+        assert_eq!(substitute("tail {{$uuid"), "tail {{$uuid");
+    }
+}
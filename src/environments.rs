@@ -6,36 +6,213 @@ use axum::{
     Json, Router,
 };
 use chrono::{DateTime, NaiveDateTime, Utc};
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::{Map, Value};
+use std::collections::{HashMap, HashSet};
+
+use crate::{
+    audit::{self, Action, Actor, Entity},
+    crypto::{self, CryptoError},
+    db::DbPool,
+    i18n, importers,
+    variable_types::{self, VariableType, VariableTypes},
+};
+
+/// Shown instead of secret values. Sending it back in an update keeps the
+/// stored value.
+pub const SECRET_MASK: &str = "********";
 
-use crate::db::DbPool;
+/// Longest chain of parents followed when resolving variables.
+const MAX_INHERITANCE_DEPTH: usize = 16;
 
 #[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
 pub struct Environment {
     pub id: i64,
     pub name: String,
     pub variables: String, // Stored as JSON
+    /// Names of the variables stored encrypted and masked in responses
+    #[serde(default)]
+    pub secrets: Vec<String>,
+    /// Names of the variables left out of substitution
+    #[serde(default)]
+    pub disabled: Vec<String>,
+    /// Declared types of variables, checked when they are saved and sent
+    #[serde(default)]
+    pub types: VariableTypes,
+    /// Environment whose variables this one inherits and overrides
+    #[serde(default)]
+    pub parent_id: Option<i64>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
     pub archived_at: Option<DateTime<Utc>>,
 }
 
-#[derive(sqlx::FromRow, Clone)]
+#[derive(sqlx::FromRow, Serialize, Deserialize, Clone)]
 pub struct EnvironmentDb {
     pub id: i64,
     pub name: String,
     pub variables: String,
+    pub secrets: String,
+    pub disabled: String,
+    pub types: String,
+    pub parent_id: Option<i64>,
     pub created_at: NaiveDateTime,
     pub updated_at: NaiveDateTime,
     pub archived_at: Option<NaiveDateTime>,
 }
 
-impl From<EnvironmentDb> for Environment {
-    fn from(e: EnvironmentDb) -> Self {
+impl EnvironmentDb {
+    pub fn secret_names(&self) -> Vec<String> {
+        serde_json::from_str(&self.secrets).unwrap_or_default()
+    }
+
+    pub fn disabled_names(&self) -> Vec<String> {
+        serde_json::from_str(&self.disabled).unwrap_or_default()
+    }
+
+    pub fn variable_types(&self) -> VariableTypes {
+        variable_types::parse(&self.types).unwrap_or_default()
+    }
+}
+
+/// One variable of an environment, see `/environments/:id/variables`.
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct Variable {
+    pub name: String,
+    pub value: String,
+    pub enabled: bool,
+    pub secret: bool,
+    #[serde(rename = "type", default, skip_serializing_if = "Option::is_none")]
+    pub variable_type: Option<VariableType>,
+}
+
+/// Fields left out keep their stored value; a new variable needs a value.
+#[derive(Deserialize)]
+pub struct SetVariable {
+    #[serde(default)]
+    value: Option<String>,
+    #[serde(default)]
+    enabled: Option<bool>,
+    #[serde(default)]
+    secret: Option<bool>,
+    /// `null` removes the variable's type.
+    #[serde(rename = "type", default, deserialize_with = "present")]
+    variable_type: Option<Option<VariableType>>,
+}
+
+/// Variables must be a JSON object of strings, the shape substitution uses.
+fn parse_variables(variables: &str) -> Result<Map<String, Value>, EnvironmentError> {
+    let map: Map<String, Value> =
+        serde_json::from_str(variables).map_err(|_| EnvironmentError::InvalidVariables)?;
+    if !map.values().all(Value::is_string) {
+        return Err(EnvironmentError::InvalidVariables);
+    }
+    Ok(map)
+}
+
+/// Checks the values against their declared types, masked secrets aside,
+/// and returns `secrets` with the variables typed secret added.
+fn check_types(
+    variables: &Map<String, Value>,
+    types: &VariableTypes,
+    mut secrets: Vec<String>,
+) -> Result<Vec<String>, EnvironmentError> {
+    variable_types::validate(types).map_err(EnvironmentError::InvalidTypes)?;
+    let values = variables
+        .iter()
+        .filter_map(|(name, value)| Some((name.as_str(), value.as_str()?)));
+    variable_types::check_values(types, values, |value| value == SECRET_MASK).map_err(|e| {
+        log::warn!("{}", e);
+        EnvironmentError::InvalidTypes(e)
+    })?;
+    for name in variable_types::secret_names(types) {
+        if !secrets.contains(name) {
+            secrets.push(name.clone());
+        }
+    }
+    Ok(secrets)
+}
+
+/// Replaces the values of the secret variables in a variables object.
+/// Anything that is not a JSON object is returned unchanged.
+fn map_secrets(
+    variables: &str,
+    secrets: &[String],
+    mut f: impl FnMut(&str, &Value) -> Result<Value, CryptoError>,
+) -> Result<String, CryptoError> {
+    if secrets.is_empty() {
+        return Ok(variables.to_string());
+    }
+    let Ok(mut map) = serde_json::from_str::<Map<String, Value>>(variables) else {
+        return Ok(variables.to_string());
+    };
+    for name in secrets {
+        if let Some(value) = map.get_mut(name) {
+            *value = f(name, value)?;
+        }
+    }
+    Ok(Value::Object(map).to_string())
+}
+
+fn value_text(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+/// Encrypts the secret values of `variables` for storage. Masked values
+/// keep what is stored, and variables no longer secret are stored in the
+/// clear again.
+fn seal(
+    variables: &str,
+    secrets: &[String],
+    stored: Option<&EnvironmentDb>,
+) -> Result<String, CryptoError> {
+    let stored_variables: Map<String, Value> = stored
+        .and_then(|e| serde_json::from_str(&e.variables).ok())
+        .unwrap_or_default();
+    let stored_value = |name: &str| stored_variables.get(name).map(value_text);
+
+    let mut unsealed = Vec::new();
+    let sealed = map_secrets(variables, secrets, |name, value| {
+        let text = value_text(value);
+        let text = match stored_value(name) {
+            Some(stored) if text == SECRET_MASK => stored,
+            _ => text,
+        };
+        if crypto::is_encrypted(&text) {
+            Ok(Value::String(text))
+        } else {
+            crypto::encrypt(&text).map(Value::String)
+        }
+    })?;
+    if let Some(stored) = stored {
+        for name in stored.secret_names() {
+            if !secrets.contains(&name) {
+                unsealed.push(name);
+            }
+        }
+    }
+    map_secrets(&sealed, &unsealed, |name, value| match stored_value(name) {
+        Some(stored) if value.as_str() == Some(SECRET_MASK) => {
+            crypto::decrypt(&stored).map(Value::String)
+        }
+        _ => Ok(value.clone()),
+    })
+}
+
+impl Environment {
+    fn build(e: EnvironmentDb, variables: String) -> Self {
+        let types = e.variable_types();
         Self {
             id: e.id,
             name: e.name,
-            variables: e.variables,
+            variables,
+            secrets: serde_json::from_str(&e.secrets).unwrap_or_default(),
+            disabled: serde_json::from_str(&e.disabled).unwrap_or_default(),
+            types,
+            parent_id: e.parent_id,
             created_at: DateTime::from_naive_utc_and_offset(e.created_at, Utc),
             updated_at: DateTime::from_naive_utc_and_offset(e.updated_at, Utc),
             archived_at: e
@@ -43,32 +220,208 @@ impl From<EnvironmentDb> for Environment {
                 .map(|d| DateTime::from_naive_utc_and_offset(d, Utc)),
         }
     }
+
+    /// Secret values replaced by `replacement`, such as an empty string for
+    /// exports.
+    pub fn masked(e: EnvironmentDb, replacement: &str) -> Self {
+        let variables = map_secrets(&e.variables, &e.secret_names(), |_, _| {
+            Ok(Value::String(replacement.to_string()))
+        })
+        .unwrap_or_else(|_| e.variables.clone());
+        Self::build(e, variables)
+    }
+
+    /// Secret values decrypted.
+    pub fn revealed(e: EnvironmentDb) -> Result<Self, CryptoError> {
+        let variables = map_secrets(&e.variables, &e.secret_names(), |_, value| {
+            crypto::decrypt(&value_text(value)).map(Value::String)
+        })?;
+        Ok(Self::build(e, variables))
+    }
+}
+
+impl From<EnvironmentDb> for Environment {
+    fn from(e: EnvironmentDb) -> Self {
+        Environment::masked(e, SECRET_MASK)
+    }
+}
+
+/// Sets or, for `None`, removes variables of a stored environment, as
+/// captures and scripts do. Secret variables stay encrypted.
+pub async fn store_values(
+    pool: &DbPool,
+    environment_id: i64,
+    changes: &HashMap<String, Option<String>>,
+) -> Result<(), sqlx::Error> {
+    let mut tx = pool.begin().await?;
+    let stored = sqlx::query!(
+        "SELECT variables, secrets FROM environments WHERE id = ?",
+        environment_id
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+    let secrets: Vec<String> = serde_json::from_str(&stored.secrets).unwrap_or_default();
+    let mut variables: Map<String, Value> =
+        serde_json::from_str(&stored.variables).unwrap_or_default();
+    for (key, value) in changes {
+        match value {
+            Some(value) if secrets.contains(key) => {
+                let value = crypto::encrypt(value).map_err(|e| sqlx::Error::Encode(Box::new(e)))?;
+                variables.insert(key.clone(), Value::String(value))
+            }
+            Some(value) => variables.insert(key.clone(), Value::String(value.clone())),
+            None => variables.remove(key),
+        };
+    }
+    let variables = Value::Object(variables).to_string();
+    sqlx::query!(
+        "UPDATE environments SET variables = ?, updated_at = CURRENT_TIMESTAMP WHERE id = ?",
+        variables,
+        environment_id
+    )
+    .execute(&mut *tx)
+    .await?;
+    tx.commit().await
+}
+
+/// The environment followed by its parents, nearest first. A cycle or a
+/// missing parent ends the chain.
+pub async fn load_chain(pool: &DbPool, id: i64) -> Result<Vec<EnvironmentDb>, sqlx::Error> {
+    let mut chain = vec![load_environment(pool, id).await?];
+    let mut seen = HashSet::from([id]);
+    while let Some(parent_id) = chain.last().and_then(|e| e.parent_id) {
+        if !seen.insert(parent_id) || chain.len() >= MAX_INHERITANCE_DEPTH {
+            log::warn!("Stopped resolving the parents of environment {}", id);
+            break;
+        }
+        match sqlx::query_as!(
+            EnvironmentDb,
+            "SELECT id, name, variables, secrets, disabled, types, parent_id, created_at, updated_at, archived_at FROM environments WHERE id = ?",
+            parent_id
+        )
+        .fetch_optional(pool)
+        .await?
+        {
+            Some(parent) => chain.push(parent),
+            None => break,
+        }
+    }
+    Ok(chain)
+}
+
+/// A parent must exist and must not inherit from the environment itself.
+async fn validate_parent(
+    pool: &DbPool,
+    id: Option<i64>,
+    parent_id: Option<i64>,
+) -> Result<(), EnvironmentError> {
+    let Some(parent_id) = parent_id else {
+        return Ok(());
+    };
+    let chain = load_chain(pool, parent_id).await.map_err(|e| match e {
+        sqlx::Error::RowNotFound => EnvironmentError::InvalidParent,
+        e => EnvironmentError::from(e),
+    })?;
+    if id.is_some_and(|id| chain.iter().any(|e| e.id == id)) {
+        return Err(EnvironmentError::InvalidParent);
+    }
+    Ok(())
 }
 
 #[derive(Deserialize)]
 pub struct CreateEnvironment {
     name: String,
     variables: String,
+    #[serde(default)]
+    secrets: Vec<String>,
+    #[serde(default)]
+    disabled: Vec<String>,
+    /// Variables declared `secret` are added to `secrets`.
+    #[serde(default)]
+    types: VariableTypes,
+    #[serde(default)]
+    parent_id: Option<i64>,
 }
 
 #[derive(Deserialize)]
 pub struct UpdateEnvironment {
     name: String,
     variables: String,
+    /// `null` or absent keeps the stored list.
+    #[serde(default)]
+    secrets: Option<Vec<String>>,
+    /// `null` or absent keeps the stored list.
+    #[serde(default)]
+    disabled: Option<Vec<String>>,
+    /// `null` or absent keeps the stored types.
+    #[serde(default)]
+    types: Option<VariableTypes>,
+    /// Absent keeps the stored parent, `null` removes it.
+    #[serde(default, deserialize_with = "present")]
+    parent_id: Option<Option<i64>>,
+}
+
+/// Partial update; absent fields keep their stored value. Secret values are
+/// kept unless sent.
+#[derive(Deserialize)]
+pub struct PatchEnvironment {
+    #[serde(default)]
+    name: Option<String>,
+    #[serde(default)]
+    variables: Option<String>,
+    #[serde(default)]
+    secrets: Option<Vec<String>>,
+    #[serde(default)]
+    disabled: Option<Vec<String>>,
+    #[serde(default)]
+    types: Option<VariableTypes>,
+    /// Absent keeps the stored parent, `null` removes it.
+    #[serde(default, deserialize_with = "present")]
+    parent_id: Option<Option<i64>>,
+}
+
+/// Tells an explicit `null` (`Some(None)`) from an absent field (`None`).
+pub fn present<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    T::deserialize(deserializer).map(Some)
 }
 
 #[derive(Deserialize)]
 pub struct ListEnvironmentsQuery {
     #[serde(default)]
     include_archived: bool,
+    /// Returns secret values decrypted instead of masked
+    #[serde(default)]
+    reveal: bool,
+}
+
+#[derive(Deserialize)]
+pub struct GetEnvironmentQuery {
+    #[serde(default)]
+    reveal: bool,
 }
 
 pub enum EnvironmentError {
     InvalidName,
+    InvalidVariables,
+    InvalidParent,
     EnvironmentNotFound,
+    VariableNotFound,
+    InvalidDotenv(String),
+    InvalidTypes(String),
+    EncryptionError(String),
     DatabaseError(#[allow(dead_code)] sqlx::Error),
 }
 
+impl From<CryptoError> for EnvironmentError {
+    fn from(e: CryptoError) -> Self {
+        EnvironmentError::EncryptionError(e.to_string())
+    }
+}
+
 impl From<sqlx::Error> for EnvironmentError {
     fn from(e: sqlx::Error) -> Self {
         match e {
@@ -81,14 +434,38 @@ impl From<sqlx::Error> for EnvironmentError {
 impl IntoResponse for EnvironmentError {
     fn into_response(self) -> Response {
         match self {
-            EnvironmentError::InvalidName => {
-                (StatusCode::BAD_REQUEST, "Invalid environment name").into_response()
-            }
-            EnvironmentError::EnvironmentNotFound => {
-                (StatusCode::NOT_FOUND, "Environment not found").into_response()
+            EnvironmentError::InvalidName => (
+                StatusCode::BAD_REQUEST,
+                i18n::t("error.invalid_environment_name"),
+            )
+                .into_response(),
+            EnvironmentError::InvalidVariables => (
+                StatusCode::BAD_REQUEST,
+                i18n::t("error.invalid_environment_variables"),
+            )
+                .into_response(),
+            EnvironmentError::InvalidParent => (
+                StatusCode::BAD_REQUEST,
+                i18n::t("error.invalid_environment_parent"),
+            )
+                .into_response(),
+            EnvironmentError::VariableNotFound => {
+                (StatusCode::NOT_FOUND, i18n::t("error.variable_not_found")).into_response()
             }
+            EnvironmentError::InvalidDotenv(msg) => (StatusCode::BAD_REQUEST, msg).into_response(),
+            EnvironmentError::InvalidTypes(msg) => (StatusCode::BAD_REQUEST, msg).into_response(),
+            EnvironmentError::EnvironmentNotFound => (
+                StatusCode::NOT_FOUND,
+                i18n::t("error.environment_not_found"),
+            )
+                .into_response(),
+            EnvironmentError::EncryptionError(msg) => (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Encryption error: {}", msg),
+            )
+                .into_response(),
             EnvironmentError::DatabaseError(_) => {
-                (StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response()
+                (StatusCode::INTERNAL_SERVER_ERROR, i18n::t("error.database")).into_response()
             }
         }
     }
@@ -96,6 +473,7 @@ impl IntoResponse for EnvironmentError {
 
 async fn create_environment(
     State(pool): State<DbPool>,
+    actor: Actor,
     Json(payload): Json<CreateEnvironment>,
 ) -> Result<impl IntoResponse, EnvironmentError> {
     log::debug!("Creating environment: name={}", payload.name);
@@ -105,14 +483,39 @@ async fn create_environment(
         return Err(EnvironmentError::InvalidName);
     }
 
+    let secrets = check_types(
+        &parse_variables(&payload.variables)?,
+        &payload.types,
+        payload.secrets,
+    )?;
+    validate_parent(&pool, None, payload.parent_id).await?;
+
+    let variables = seal(&payload.variables, &secrets, None)?;
+    let secrets = serde_json::to_string(&secrets).unwrap_or_else(|_| "[]".to_string());
+    let disabled = serde_json::to_string(&payload.disabled).unwrap_or_else(|_| "[]".to_string());
+    let types = serde_json::to_string(&payload.types).unwrap_or_else(|_| "{}".to_string());
     let environment_db = sqlx::query_as!(
         EnvironmentDb,
-        "INSERT INTO environments (name, variables) VALUES (?, ?) RETURNING id, name, variables, created_at, updated_at, archived_at",
+        "INSERT INTO environments (name, variables, secrets, disabled, types, parent_id) VALUES (?, ?, ?, ?, ?, ?) RETURNING id, name, variables, secrets, disabled, types, parent_id, created_at, updated_at, archived_at",
         payload.name,
-        payload.variables
+        variables,
+        secrets,
+        disabled,
+        types,
+        payload.parent_id
     )
     .fetch_one(&pool)
     .await?;
+    audit::record(
+        &pool,
+        &actor,
+        Entity::Environment,
+        environment_db.id,
+        Action::Create,
+        None,
+        Some(&environment_db),
+    )
+    .await;
 
     log::info!(
         "Created environment: id={}, name={}",
@@ -134,21 +537,27 @@ async fn list_environments(
     let environments_db = if query.include_archived {
         sqlx::query_as!(
             EnvironmentDb,
-            "SELECT id, name, variables, created_at, updated_at, archived_at FROM environments"
+            "SELECT id, name, variables, secrets, disabled, types, parent_id, created_at, updated_at, archived_at FROM environments"
         )
         .fetch_all(&pool)
         .await?
     } else {
         sqlx::query_as!(
             EnvironmentDb,
-            "SELECT id, name, variables, created_at, updated_at, archived_at FROM environments WHERE archived_at IS NULL"
+            "SELECT id, name, variables, secrets, disabled, types, parent_id, created_at, updated_at, archived_at FROM environments WHERE archived_at IS NULL"
         )
         .fetch_all(&pool)
         .await?
     };
 
-    let environments: Vec<Environment> =
-        environments_db.into_iter().map(Environment::from).collect();
+    let environments: Vec<Environment> = if query.reveal {
+        environments_db
+            .into_iter()
+            .map(Environment::revealed)
+            .collect::<Result<_, _>>()?
+    } else {
+        environments_db.into_iter().map(Environment::from).collect()
+    };
     log::debug!("Found {} environments", environments.len());
 
     Ok(Json(environments))
@@ -157,12 +566,13 @@ async fn list_environments(
 async fn get_environment(
     State(pool): State<DbPool>,
     Path(id): Path<i64>,
+    Query(query): Query<GetEnvironmentQuery>,
 ) -> Result<impl IntoResponse, EnvironmentError> {
     log::debug!("Getting environment with id: {}", id);
 
     let environment_db = sqlx::query_as!(
         EnvironmentDb,
-        "SELECT id, name, variables, created_at, updated_at, archived_at FROM environments WHERE id = ?",
+        "SELECT id, name, variables, secrets, disabled, types, parent_id, created_at, updated_at, archived_at FROM environments WHERE id = ?",
         id
     )
     .fetch_one(&pool)
@@ -173,12 +583,16 @@ async fn get_environment(
         environment_db.id,
         environment_db.name
     );
+    if query.reveal {
+        return Ok(Json(Environment::revealed(environment_db)?));
+    }
     Ok(Json(Environment::from(environment_db)))
 }
 
 async fn update_environment(
     State(pool): State<DbPool>,
     Path(id): Path<i64>,
+    actor: Actor,
     Json(payload): Json<UpdateEnvironment>,
 ) -> Result<impl IntoResponse, EnvironmentError> {
     log::debug!("Updating environment id={} with name: {}", id, payload.name);
@@ -187,16 +601,54 @@ async fn update_environment(
         log::warn!("Attempted to update environment {} with empty name", id);
         return Err(EnvironmentError::InvalidName);
     }
+    let variables = parse_variables(&payload.variables)?;
+
+    let stored = sqlx::query_as!(
+        EnvironmentDb,
+        "SELECT id, name, variables, secrets, disabled, types, parent_id, created_at, updated_at, archived_at FROM environments WHERE id = ?",
+        id
+    )
+    .fetch_one(&pool)
+    .await?;
+    let types = payload.types.unwrap_or_else(|| stored.variable_types());
+    let secrets = check_types(
+        &variables,
+        &types,
+        payload.secrets.unwrap_or_else(|| stored.secret_names()),
+    )?;
+    let variables = seal(&payload.variables, &secrets, Some(&stored))?;
+    let secrets = serde_json::to_string(&secrets).unwrap_or_else(|_| "[]".to_string());
+    let disabled = payload
+        .disabled
+        .map(|d| serde_json::to_string(&d).unwrap_or_else(|_| "[]".to_string()))
+        .unwrap_or_else(|| stored.disabled.clone());
+    let types = serde_json::to_string(&types).unwrap_or_else(|_| "{}".to_string());
+    let parent_id = payload.parent_id.unwrap_or(stored.parent_id);
+    validate_parent(&pool, Some(id), parent_id).await?;
 
     let environment_db = sqlx::query_as!(
         EnvironmentDb,
-        "UPDATE environments SET name = ?, variables = ?, updated_at = CURRENT_TIMESTAMP WHERE id = ? RETURNING id, name, variables, created_at, updated_at, archived_at",
+        "UPDATE environments SET name = ?, variables = ?, secrets = ?, disabled = ?, types = ?, parent_id = ?, updated_at = CURRENT_TIMESTAMP WHERE id = ? RETURNING id, name, variables, secrets, disabled, types, parent_id, created_at, updated_at, archived_at",
         payload.name,
-        payload.variables,
+        variables,
+        secrets,
+        disabled,
+        types,
+        parent_id,
         id
     )
     .fetch_one(&pool)
     .await?;
+    audit::record(
+        &pool,
+        &actor,
+        Entity::Environment,
+        id,
+        Action::Update,
+        Some(&stored),
+        Some(&environment_db),
+    )
+    .await;
 
     log::info!(
         "Updated environment: id={}, name={}",
@@ -206,12 +658,241 @@ async fn update_environment(
     Ok(Json(Environment::from(environment_db)))
 }
 
+async fn patch_environment(
+    State(pool): State<DbPool>,
+    Path(id): Path<i64>,
+    actor: Actor,
+    Json(payload): Json<PatchEnvironment>,
+) -> Result<impl IntoResponse, EnvironmentError> {
+    let stored = Environment::from(load_environment(&pool, id).await?);
+    let update = UpdateEnvironment {
+        name: payload.name.unwrap_or(stored.name),
+        // Masked secrets keep their stored value
+        variables: payload.variables.unwrap_or(stored.variables),
+        secrets: payload.secrets,
+        disabled: payload.disabled,
+        types: payload.types,
+        parent_id: payload.parent_id,
+    };
+    update_environment(State(pool), Path(id), actor, Json(update)).await
+}
+
+pub async fn load_environment(pool: &DbPool, id: i64) -> Result<EnvironmentDb, sqlx::Error> {
+    sqlx::query_as!(
+        EnvironmentDb,
+        "SELECT id, name, variables, secrets, disabled, types, parent_id, created_at, updated_at, archived_at FROM environments WHERE id = ?",
+        id
+    )
+    .fetch_one(pool)
+    .await
+}
+
+async fn list_variables(
+    State(pool): State<DbPool>,
+    Path(id): Path<i64>,
+    Query(query): Query<GetEnvironmentQuery>,
+) -> Result<impl IntoResponse, EnvironmentError> {
+    let environment_db = load_environment(&pool, id).await?;
+    let environment = if query.reveal {
+        Environment::revealed(environment_db)?
+    } else {
+        Environment::from(environment_db)
+    };
+    let variables: Map<String, Value> =
+        serde_json::from_str(&environment.variables).unwrap_or_default();
+    let variables: Vec<Variable> = variables
+        .into_iter()
+        .map(|(name, value)| Variable {
+            enabled: !environment.disabled.contains(&name),
+            secret: environment.secrets.contains(&name),
+            variable_type: environment.types.get(&name).cloned(),
+            value: value_text(&value),
+            name,
+        })
+        .collect();
+    Ok(Json(variables))
+}
+
+/// Adds or changes one variable.
+async fn set_variable(
+    State(pool): State<DbPool>,
+    Path((id, name)): Path<(i64, String)>,
+    actor: Actor,
+    Json(payload): Json<SetVariable>,
+) -> Result<impl IntoResponse, EnvironmentError> {
+    log::debug!("Setting variable {} of environment {}", name, id);
+    if name.is_empty() {
+        return Err(EnvironmentError::InvalidVariables);
+    }
+
+    let stored = load_environment(&pool, id).await?;
+    let mut variables = parse_variables(&stored.variables)?;
+    let mut secrets = stored.secret_names();
+    let mut disabled = stored.disabled_names();
+    let mut types = stored.variable_types();
+
+    let current = variables
+        .get(&name)
+        .map(|v| crypto::decrypt(&value_text(v)))
+        .transpose()?;
+    let value = match (payload.value, current) {
+        (Some(value), Some(current)) if value == SECRET_MASK => current,
+        (Some(value), _) => value,
+        (None, Some(current)) => current,
+        (None, None) => return Err(EnvironmentError::InvalidVariables),
+    };
+    match payload.variable_type {
+        Some(Some(variable_type)) => {
+            types.insert(name.clone(), variable_type);
+        }
+        Some(None) => {
+            types.remove(&name);
+        }
+        None => {}
+    }
+    variable_types::validate(&types).map_err(EnvironmentError::InvalidTypes)?;
+    if let Some(variable_type) = types.get(&name) {
+        variable_type
+            .check(&name, &value)
+            .map_err(EnvironmentError::InvalidTypes)?;
+    }
+    let secret = payload.secret.unwrap_or(secrets.contains(&name))
+        || types.get(&name) == Some(&VariableType::Secret);
+    let enabled = payload.enabled.unwrap_or(!disabled.contains(&name));
+    secrets.retain(|n| n != &name);
+    disabled.retain(|n| n != &name);
+    if secret {
+        secrets.push(name.clone());
+    }
+    if !enabled {
+        disabled.push(name.clone());
+    }
+    let value = if secret {
+        crypto::encrypt(&value)?
+    } else {
+        value
+    };
+    variables.insert(name.clone(), Value::String(value));
+
+    let environment_db = save_variables(&pool, id, &variables, &secrets, &disabled, &types).await?;
+    audit::record(
+        &pool,
+        &actor,
+        Entity::Environment,
+        id,
+        Action::Update,
+        Some(&stored),
+        Some(&environment_db),
+    )
+    .await;
+    log::info!("Set variable {} of environment {}", name, id);
+    Ok(Json(Environment::from(environment_db)))
+}
+
+async fn delete_variable(
+    State(pool): State<DbPool>,
+    Path((id, name)): Path<(i64, String)>,
+    actor: Actor,
+) -> Result<impl IntoResponse, EnvironmentError> {
+    let stored = load_environment(&pool, id).await?;
+    let mut variables = parse_variables(&stored.variables)?;
+    if variables.remove(&name).is_none() {
+        return Err(EnvironmentError::VariableNotFound);
+    }
+    let mut secrets = stored.secret_names();
+    let mut disabled = stored.disabled_names();
+    let mut types = stored.variable_types();
+    secrets.retain(|n| n != &name);
+    disabled.retain(|n| n != &name);
+    types.remove(&name);
+
+    let environment_db = save_variables(&pool, id, &variables, &secrets, &disabled, &types).await?;
+    audit::record(
+        &pool,
+        &actor,
+        Entity::Environment,
+        id,
+        Action::Update,
+        Some(&stored),
+        Some(&environment_db),
+    )
+    .await;
+    log::info!("Deleted variable {} of environment {}", name, id);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Merges the variables of a `.env` file into the environment, replacing
+/// those of the same name.
+async fn import_dotenv(
+    State(pool): State<DbPool>,
+    Path(id): Path<i64>,
+    actor: Actor,
+    body: String,
+) -> Result<impl IntoResponse, EnvironmentError> {
+    let variables = importers::parse_dotenv(&body).map_err(|e| {
+        log::warn!("Rejected .env import into environment {}: {}", id, e);
+        EnvironmentError::InvalidDotenv(e)
+    })?;
+    let stored = load_environment(&pool, id).await?;
+    let changes = variables
+        .into_iter()
+        .map(|(name, value)| (name, Some(value)))
+        .collect();
+    store_values(&pool, id, &changes).await?;
+
+    let environment_db = load_environment(&pool, id).await?;
+    audit::record(
+        &pool,
+        &actor,
+        Entity::Environment,
+        id,
+        Action::Update,
+        Some(&stored),
+        Some(&environment_db),
+    )
+    .await;
+
+    log::info!(
+        "Imported {} variable(s) from .env into environment {}",
+        changes.len(),
+        id
+    );
+    Ok(Json(Environment::from(environment_db)))
+}
+
+async fn save_variables(
+    pool: &DbPool,
+    id: i64,
+    variables: &Map<String, Value>,
+    secrets: &[String],
+    disabled: &[String],
+    types: &VariableTypes,
+) -> Result<EnvironmentDb, EnvironmentError> {
+    let variables = Value::Object(variables.clone()).to_string();
+    let secrets = serde_json::to_string(secrets).unwrap_or_else(|_| "[]".to_string());
+    let disabled = serde_json::to_string(disabled).unwrap_or_else(|_| "[]".to_string());
+    let types = serde_json::to_string(types).unwrap_or_else(|_| "{}".to_string());
+    Ok(sqlx::query_as!(
+        EnvironmentDb,
+        "UPDATE environments SET variables = ?, secrets = ?, disabled = ?, types = ?, updated_at = CURRENT_TIMESTAMP WHERE id = ? RETURNING id, name, variables, secrets, disabled, types, parent_id, created_at, updated_at, archived_at",
+        variables,
+        secrets,
+        disabled,
+        types,
+        id
+    )
+    .fetch_one(pool)
+    .await?)
+}
+
 async fn archive_environment(
     State(pool): State<DbPool>,
     Path(id): Path<i64>,
+    actor: Actor,
 ) -> Result<impl IntoResponse, EnvironmentError> {
     log::debug!("Archiving environment id: {}", id);
 
+    let before = audit::found(load_environment(&pool, id).await)?;
     let now = Utc::now().naive_utc();
     let result = sqlx::query("UPDATE environments SET archived_at = ? WHERE id = ?")
         .bind(now)
@@ -223,6 +904,7 @@ async fn archive_environment(
         log::warn!("Environment not found for archiving: id={}", id);
         return Err(EnvironmentError::EnvironmentNotFound);
     }
+    audit_state_change(&pool, &actor, id, Action::Archive, before).await;
 
     log::info!("Archived environment: id={}", id);
     Ok(StatusCode::OK)
@@ -231,9 +913,11 @@ async fn archive_environment(
 async fn unarchive_environment(
     State(pool): State<DbPool>,
     Path(id): Path<i64>,
+    actor: Actor,
 ) -> Result<impl IntoResponse, EnvironmentError> {
     log::debug!("Unarchiving environment id: {}", id);
 
+    let before = audit::found(load_environment(&pool, id).await)?;
     let result = sqlx::query("UPDATE environments SET archived_at = NULL WHERE id = ?")
         .bind(id)
         .execute(&pool)
@@ -243,6 +927,7 @@ async fn unarchive_environment(
         log::warn!("Environment not found for unarchiving: id={}", id);
         return Err(EnvironmentError::EnvironmentNotFound);
     }
+    audit_state_change(&pool, &actor, id, Action::Unarchive, before).await;
 
     log::info!("Unarchived environment: id={}", id);
     Ok(StatusCode::OK)
@@ -251,9 +936,11 @@ async fn unarchive_environment(
 async fn delete_environment(
     State(pool): State<DbPool>,
     Path(id): Path<i64>,
+    actor: Actor,
 ) -> Result<impl IntoResponse, EnvironmentError> {
     log::debug!("Deleting environment id: {}", id);
 
+    let before = audit::found(load_environment(&pool, id).await)?;
     let result = sqlx::query("DELETE FROM environments WHERE id = ?")
         .bind(id)
         .execute(&pool)
@@ -263,11 +950,44 @@ async fn delete_environment(
         log::warn!("Environment not found for deletion: id={}", id);
         return Err(EnvironmentError::EnvironmentNotFound);
     }
+    audit::record(
+        &pool,
+        &actor,
+        Entity::Environment,
+        id,
+        Action::Delete,
+        before.as_ref(),
+        None,
+    )
+    .await;
 
     log::info!("Deleted environment: id={}", id);
     Ok(StatusCode::NO_CONTENT)
 }
 
+/// Records an archive or unarchive with the row as it was and is now.
+async fn audit_state_change(
+    pool: &DbPool,
+    actor: &Actor,
+    id: i64,
+    action: Action,
+    before: Option<EnvironmentDb>,
+) {
+    let after = audit::found(load_environment(pool, id).await)
+        .ok()
+        .flatten();
+    audit::record(
+        pool,
+        actor,
+        Entity::Environment,
+        id,
+        action,
+        before.as_ref(),
+        after.as_ref(),
+    )
+    .await;
+}
+
 pub fn routes(pool: DbPool) -> Router {
     Router::new()
         .route(
@@ -278,8 +998,15 @@ pub fn routes(pool: DbPool) -> Router {
             "/environments/:id",
             get(get_environment)
                 .put(update_environment)
+                .patch(patch_environment)
                 .delete(delete_environment),
         )
+        .route("/environments/:id/variables", get(list_variables))
+        .route("/environments/:id/dotenv", post(import_dotenv))
+        .route(
+            "/environments/:id/variables/:name",
+            put(set_variable).delete(delete_variable),
+        )
         .route("/environments/:id/archive", put(archive_environment))
         .route("/environments/:id/unarchive", put(unarchive_environment))
         .with_state(pool)
@@ -296,7 +1023,7 @@ mod tests {
     async fn create_test_environment(pool: &DbPool, name: &str, variables: &str) -> EnvironmentDb {
         sqlx::query_as!(
             EnvironmentDb,
-            "INSERT INTO environments (name, variables) VALUES (?, ?) RETURNING id, name, variables, created_at, updated_at, archived_at",
+            "INSERT INTO environments (name, variables) VALUES (?, ?) RETURNING id, name, variables, secrets, disabled, types, parent_id, created_at, updated_at, archived_at",
             name,
             variables
         )
@@ -414,13 +1141,353 @@ mod tests {
 
         let response = server
             .put(&format!("/environments/{}", environment.id))
-            .json(&json!({ "name": "updated name", "variables": "{{\"key\": \"value\"}}" }))
+            .json(&json!({ "name": "updated name", "variables": "{\"key\": \"value\"}" }))
             .await;
 
         response.assert_status(StatusCode::OK);
         let updated_environment: Environment = response.json();
         assert_eq!(updated_environment.name, "updated name");
-        assert_eq!(updated_environment.variables, "{{\"key\": \"value\"}}");
+        assert_eq!(updated_environment.variables, "{\"key\": \"value\"}");
+    }
+
+    #[tokio::test]
+    async fn test_patch_environment() {
+        let pool = db::create_test_pool().await;
+        let server = TestServer::new(routes(pool.clone())).unwrap();
+
+        let created: Environment = server
+            .post("/environments")
+            .json(&json!({
+                "name": "Staging",
+                "variables": "{\"host\": \"staging\", \"token\": \"s3cret\"}",
+                "secrets": ["token"]
+            }))
+            .await
+            .json();
+
+        let patched: Environment = server
+            .patch(&format!("/environments/{}", created.id))
+            .json(&json!({ "name": "Stage" }))
+            .await
+            .json();
+        assert_eq!(patched.name, "Stage");
+        assert_eq!(patched.secrets, vec!["token".to_string()]);
+        let stored = load_environment(&pool, created.id).await.unwrap();
+        let revealed = Environment::revealed(stored).unwrap();
+        let variables: Value = serde_json::from_str(&revealed.variables).unwrap();
+        assert_eq!(variables["token"], "s3cret");
+        assert_eq!(variables["host"], "staging");
+
+        server
+            .patch(&format!("/environments/{}", created.id))
+            .json(&json!({ "name": "" }))
+            .await
+            .assert_status(StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_malformed_variables_rejected() {
+        let pool = db::create_test_pool().await;
+        let environment = create_test_environment(&pool, "env1", "{}").await;
+        let server = TestServer::new(routes(pool.clone())).unwrap();
+
+        for variables in ["{{\"key\": \"value\"}}", "[]", "{\"port\": 8080}"] {
+            server
+                .put(&format!("/environments/{}", environment.id))
+                .json(&json!({ "name": "env1", "variables": variables }))
+                .await
+                .assert_status(StatusCode::BAD_REQUEST);
+            server
+                .post("/environments")
+                .json(&json!({ "name": "env2", "variables": variables }))
+                .await
+                .assert_status(StatusCode::BAD_REQUEST);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_environment_parent() {
+        let pool = db::create_test_pool().await;
+        let base = create_test_environment(&pool, "Base", "{}").await;
+        let server = TestServer::new(routes(pool.clone())).unwrap();
+
+        let staging: Environment = server
+            .post("/environments")
+            .json(&json!({ "name": "Staging", "variables": "{}", "parent_id": base.id }))
+            .await
+            .json();
+        assert_eq!(staging.parent_id, Some(base.id));
+
+        // Absent keeps the parent
+        let staging: Environment = server
+            .put(&format!("/environments/{}", staging.id))
+            .json(&json!({ "name": "Staging", "variables": "{}" }))
+            .await
+            .json();
+        assert_eq!(staging.parent_id, Some(base.id));
+        let chain = load_chain(&pool, staging.id).await.unwrap();
+        let names: Vec<&str> = chain.iter().map(|e| e.name.as_str()).collect();
+        assert_eq!(names, vec!["Staging", "Base"]);
+
+        // Base cannot inherit from its own child, nor from itself
+        for parent_id in [staging.id, base.id, 999] {
+            server
+                .put(&format!("/environments/{}", base.id))
+                .json(&json!({ "name": "Base", "variables": "{}", "parent_id": parent_id }))
+                .await
+                .assert_status(StatusCode::BAD_REQUEST);
+        }
+
+        let staging: Environment = server
+            .put(&format!("/environments/{}", staging.id))
+            .json(&json!({ "name": "Staging", "variables": "{}", "parent_id": null }))
+            .await
+            .json();
+        assert_eq!(staging.parent_id, None);
+    }
+
+    #[tokio::test]
+    async fn test_variable_crud() {
+        let pool = db::create_test_pool().await;
+        let environment =
+            create_test_environment(&pool, "env1", r#"{"base_url":"https://api"}"#).await;
+        let server = TestServer::new(routes(pool.clone())).unwrap();
+        let url = |name: &str| format!("/environments/{}/variables/{}", environment.id, name);
+
+        server
+            .put(&url("token"))
+            .json(&json!({ "value": "s3cret", "secret": true }))
+            .await
+            .assert_status_ok();
+        server
+            .put(&url("base_url"))
+            .json(&json!({ "enabled": false }))
+            .await
+            .assert_status_ok();
+        // A new variable needs a value
+        server
+            .put(&url("missing"))
+            .json(&json!({ "enabled": true }))
+            .await
+            .assert_status(StatusCode::BAD_REQUEST);
+
+        let variables: Vec<Variable> = server
+            .get(&format!("/environments/{}/variables", environment.id))
+            .await
+            .json();
+        assert_eq!(variables.len(), 2);
+        let base_url = variables.iter().find(|v| v.name == "base_url").unwrap();
+        assert_eq!(
+            (base_url.value.as_str(), base_url.enabled),
+            ("https://api", false)
+        );
+        let token = variables.iter().find(|v| v.name == "token").unwrap();
+        assert_eq!((token.value.as_str(), token.secret), (SECRET_MASK, true));
+
+        // Turning the secret flag off keeps the value, now in the clear
+        let updated: Environment = server
+            .put(&url("token"))
+            .json(&json!({ "secret": false }))
+            .await
+            .json();
+        let stored: Value = serde_json::from_str(&updated.variables).unwrap();
+        assert_eq!(stored["token"], "s3cret");
+        assert_eq!(updated.disabled, vec!["base_url"]);
+
+        server
+            .delete(&url("base_url"))
+            .await
+            .assert_status(StatusCode::NO_CONTENT);
+        server
+            .delete(&url("base_url"))
+            .await
+            .assert_status(StatusCode::NOT_FOUND);
+        let environment = load_environment(&pool, environment.id).await.ok().unwrap();
+        assert!(environment.disabled_names().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_typed_variables() {
+        let pool = db::create_test_pool().await;
+        let server = TestServer::new(routes(pool.clone())).unwrap();
+        let types = json!({
+            "port": { "type": "number" },
+            "region": { "type": "enum", "values": ["eu", "us"] },
+            "api_key": { "type": "secret" }
+        });
+
+        server
+            .post("/environments")
+            .json(&json!({ "name": "Bad", "variables": r#"{"port":"80a"}"#, "types": types }))
+            .await
+            .assert_status(StatusCode::BAD_REQUEST);
+        server
+            .post("/environments")
+            .json(&json!({ "name": "Bad", "variables": "{}", "types": { "region": { "type": "enum", "values": [] } } }))
+            .await
+            .assert_status(StatusCode::BAD_REQUEST);
+
+        let response = server
+            .post("/environments")
+            .json(&json!({
+                "name": "Staging",
+                "variables": r#"{"port":"8443","region":"eu","api_key":"k3y"}"#,
+                "types": types
+            }))
+            .await;
+        response.assert_status(StatusCode::CREATED);
+        let environment: Environment = response.json();
+        // Typed secret, so stored encrypted and masked
+        assert_eq!(environment.secrets, vec!["api_key"]);
+        assert_eq!(environment.types["port"], VariableType::Number);
+        let url = |name: &str| format!("/environments/{}/variables/{}", environment.id, name);
+
+        // Masked secrets are not checked, the stored value is kept
+        server
+            .patch(&format!("/environments/{}", environment.id))
+            .json(&json!({ "variables": environment.variables }))
+            .await
+            .assert_status_ok();
+        server
+            .put(&url("region"))
+            .json(&json!({ "value": "ap" }))
+            .await
+            .assert_status(StatusCode::BAD_REQUEST);
+        server
+            .put(&url("debug"))
+            .json(&json!({ "value": "yes", "type": { "type": "boolean" } }))
+            .await
+            .assert_status(StatusCode::BAD_REQUEST);
+        server
+            .put(&url("debug"))
+            .json(&json!({ "value": "true", "type": { "type": "boolean" } }))
+            .await
+            .assert_status_ok();
+        let updated: Environment = server
+            .put(&url("port"))
+            .json(&json!({ "value": "{{base_port}}", "type": null }))
+            .await
+            .json();
+        assert!(!updated.types.contains_key("port"));
+
+        let variables: Vec<Variable> = server
+            .get(&format!("/environments/{}/variables", environment.id))
+            .await
+            .json();
+        let debug = variables.iter().find(|v| v.name == "debug").unwrap();
+        assert_eq!(debug.variable_type, Some(VariableType::Boolean));
+        server
+            .delete(&url("debug"))
+            .await
+            .assert_status(StatusCode::NO_CONTENT);
+        let stored = load_environment(&pool, environment.id).await.unwrap();
+        assert!(!stored.variable_types().contains_key("debug"));
+    }
+
+    #[tokio::test]
+    async fn test_import_dotenv() {
+        let pool = db::create_test_pool().await;
+        let server = TestServer::new(routes(pool.clone())).unwrap();
+        let created: Environment = server
+            .post("/environments")
+            .json(&json!({
+                "name": "Local",
+                "variables": r#"{"host": "old", "token": "old"}"#,
+                "secrets": ["token"]
+            }))
+            .await
+            .json();
+        let url = format!("/environments/{}/dotenv", created.id);
+
+        server
+            .post(&url)
+            .text("host=localhost\ntoken=\"s3cret\"\n# comment\nPORT=8080\n")
+            .await
+            .assert_status_ok();
+        let environment = load_environment(&pool, created.id).await.ok().unwrap();
+        let stored: Value = serde_json::from_str(&environment.variables).unwrap();
+        assert_eq!(stored["host"], "localhost");
+        assert_eq!(stored["PORT"], "8080");
+        assert!(crypto::is_encrypted(stored["token"].as_str().unwrap()));
+        let revealed = Environment::revealed(environment).ok().unwrap();
+        assert!(revealed.variables.contains("s3cret"));
+
+        server
+            .post(&url)
+            .text("not a dotenv line")
+            .await
+            .assert_status(StatusCode::BAD_REQUEST);
+        server
+            .post("/environments/999/dotenv")
+            .text("A=1")
+            .await
+            .assert_status(StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_secret_variables() {
+        let pool = db::create_test_pool().await;
+        let server = TestServer::new(routes(pool.clone())).unwrap();
+
+        let created: Environment = server
+            .post("/environments")
+            .json(&json!({
+                "name": "Prod",
+                "variables": r#"{"base": "https://api", "token": "s3cret"}"#,
+                "secrets": ["token"]
+            }))
+            .await
+            .json();
+        assert_eq!(created.secrets, vec!["token"]);
+        let variables: Value = serde_json::from_str(&created.variables).unwrap();
+        assert_eq!(variables["token"], SECRET_MASK);
+        assert_eq!(variables["base"], "https://api");
+
+        let stored = sqlx::query_scalar!(
+            "SELECT variables FROM environments WHERE id = ?",
+            created.id
+        )
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+        let stored: Value = serde_json::from_str(&stored).unwrap();
+        assert!(crypto::is_encrypted(stored["token"].as_str().unwrap()));
+
+        let listed: Vec<Environment> = server.get("/environments").await.json();
+        assert!(!listed[0].variables.contains("s3cret"));
+        let revealed: Environment = server
+            .get(&format!("/environments/{}?reveal=true", created.id))
+            .await
+            .json();
+        let variables: Value = serde_json::from_str(&revealed.variables).unwrap();
+        assert_eq!(variables["token"], "s3cret");
+
+        // Sending the mask back keeps the stored secret
+        let updated: Environment = server
+            .put(&format!("/environments/{}", created.id))
+            .json(&json!({ "name": "Prod", "variables": created.variables }))
+            .await
+            .json();
+        assert_eq!(updated.secrets, vec!["token"]);
+        let revealed: Vec<Environment> = server.get("/environments?reveal=true").await.json();
+        let variables: Value = serde_json::from_str(&revealed[0].variables).unwrap();
+        assert_eq!(variables["token"], "s3cret");
+
+        // No longer secret: stored in the clear again
+        server
+            .put(&format!("/environments/{}", created.id))
+            .json(&json!({ "name": "Prod", "variables": created.variables, "secrets": [] }))
+            .await
+            .assert_status_ok();
+        let stored = sqlx::query_scalar!(
+            "SELECT variables FROM environments WHERE id = ?",
+            created.id
+        )
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+        let stored: Value = serde_json::from_str(&stored).unwrap();
+        assert_eq!(stored["token"], "s3cret");
     }
 
     #[tokio::test]
@@ -0,0 +1,159 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::OnceLock;
+
+use axum::{
+    extract::{
+        ws::{Message, WebSocket, WebSocketUpgrade},
+        Query,
+    },
+    http::HeaderMap,
+    response::{IntoResponse, Response},
+    routing::get,
+    Extension, Router,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast;
+
+use crate::{
+    access::AccessConfig,
+    websocket::{self, WsAuthQuery},
+};
+
+/// Events a slow subscriber may fall behind by before it is told to reload.
+const CHANNEL_CAPACITY: usize = 256;
+
+static CHANNEL: OnceLock<broadcast::Sender<ChangeEvent>> = OnceLock::new();
+/// Open `/api/events` sockets; every open UI tab holds one.
+static SUBSCRIBERS: AtomicUsize = AtomicUsize::new(0);
+
+/// A folder, request or environment was created, changed, archived, restored
+/// or deleted. Subscribers fetch the row again rather than getting it here,
+/// so no secrets go out over the socket.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChangeEvent {
+    pub entity: String,
+    pub entity_id: i64,
+    pub action: String,
+    pub actor: String,
+    pub at: DateTime<Utc>,
+}
+
+/// Sent instead of the events a subscriber missed; it should reload
+/// everything.
+#[derive(Serialize)]
+struct Lagged {
+    lagged: u64,
+}
+
+fn channel() -> &'static broadcast::Sender<ChangeEvent> {
+    CHANNEL.get_or_init(|| broadcast::channel(CHANNEL_CAPACITY).0)
+}
+
+pub fn subscribe() -> broadcast::Receiver<ChangeEvent> {
+    channel().subscribe()
+}
+
+/// Number of clients connected to the event feed.
+pub fn connected_clients() -> usize {
+    SUBSCRIBERS.load(Ordering::SeqCst)
+}
+
+/// Announces a change to everyone subscribed; nobody listening is fine.
+pub fn publish(event: ChangeEvent) {
+    let _ = channel().send(event);
+}
+
+async fn events_handler(
+    ws: WebSocketUpgrade,
+    access: Option<Extension<AccessConfig>>,
+    headers: HeaderMap,
+    Query(query): Query<WsAuthQuery>,
+) -> Response {
+    if let Err(rejection) = websocket::authorize_upgrade(access.as_deref(), &headers, &query) {
+        return rejection.into_response();
+    }
+    ws.on_upgrade(stream_events)
+}
+
+async fn stream_events(mut socket: WebSocket) {
+    let mut events = subscribe();
+    SUBSCRIBERS.fetch_add(1, Ordering::SeqCst);
+    log::debug!("Change event subscriber connected");
+    loop {
+        tokio::select! {
+            event = events.recv() => {
+                let text = match event {
+                    Ok(event) => serde_json::to_string(&event),
+                    Err(broadcast::error::RecvError::Lagged(missed)) => {
+                        serde_json::to_string(&Lagged { lagged: missed })
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                };
+                let Ok(text) = text else { continue };
+                if socket.send(Message::Text(text)).await.is_err() {
+                    break;
+                }
+            }
+            // Anything but a close from the client is ignored
+            message = socket.recv() => match message {
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                Some(Ok(_)) => {}
+            },
+        }
+    }
+    SUBSCRIBERS.fetch_sub(1, Ordering::SeqCst);
+    log::debug!("Change event subscriber disconnected");
+}
+
+pub fn routes() -> Router {
+    Router::new().route("/events", get(events_handler))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{db, folders};
+    use axum_test::TestServer;
+    use serde_json::{json, Value};
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_changes_are_published() {
+        let pool = db::create_test_pool().await;
+        let server = TestServer::new(folders::routes(pool)).unwrap();
+        let mut events = subscribe();
+
+        let folder: Value = server
+            .post("/folders")
+            .add_header("X-JSLink-Actor", "events-test")
+            .json(&json!({"name": "Live"}))
+            .await
+            .json();
+        server
+            .put(&format!("/folders/{}/archive", folder["id"]))
+            .add_header("X-JSLink-Actor", "events-test")
+            .await
+            .assert_status_ok();
+
+        // Other tests publish on the same channel
+        let mut seen = Vec::new();
+        while seen.len() < 2 {
+            let event = tokio::time::timeout(Duration::from_secs(5), events.recv())
+                .await
+                .expect("no event published")
+                .unwrap();
+            if event.actor == "header:events-test" {
+                seen.push((event.entity, event.entity_id, event.action));
+            }
+        }
+        let id = folder["id"].as_i64().unwrap();
+        assert_eq!(
+            seen,
+            [
+                ("folder".to_string(), id, "create".to_string()),
+                ("folder".to_string(), id, "archive".to_string())
+            ]
+        );
+    }
+}
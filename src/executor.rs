@@ -1,16 +1,44 @@
 use axum::{
-    extract::State,
+    extract::{DefaultBodyLimit, State},
     http::StatusCode,
     response::{IntoResponse, Response},
     routing::post,
     Json, Router,
 };
-use reqwest::{Client, Proxy};
+use reqwest::{Client, ClientBuilder};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::future::Future;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::pin::Pin;
+use std::time::Instant;
 
 use crate::{
-    db::DbPool, environments::EnvironmentDb, network::NetworkSettings, requests::RequestDb,
+    assertions, aws_sigv4, capture, chained_auth, client_pool,
+    compression::{self, CompressionSettings},
+    db::DbPool,
+    dynamic_vars,
+    environments::{self, Environment},
+    folders, form_body, globals, header_rows,
+    history::{self, ExecutionRecord, StoredResponse},
+    host_queue::{self, HostLimits},
+    i18n,
+    ip_family::IpFamily,
+    jwt, limits,
+    network::{self, NetworkSettingsDb},
+    oauth,
+    pac::PacDecision,
+    proxy::{self, ProxyTarget},
+    request_lock,
+    requests::RequestDb,
+    response_cache::{self, CacheMode, CacheReason, CachedFrom},
+    retry::{RetryAttempt, RetryPolicy},
+    scripting::{self, ScriptError, ScriptRequest, ScriptResponse, TestResult},
+    ssh_tunnels, telemetry,
+    tls_version::{TlsVersion, TlsVersions},
+    unresolved::UnresolvedMode,
+    variable_types::{self, VariableTypes},
+    webhooks::{self, CallbackConfig, CallbackResult},
 };
 use std::fmt;
 
@@ -19,6 +47,12 @@ pub enum ExecutorError {
     RequestNotFound,
     NetworkError(String),
     SubstitutionError(String),
+    AuthError(String),
+    InvalidSettings(String),
+    ScriptAborted(String),
+    ScriptError(String),
+    StillRunning(i64),
+    QueueTimeout(String),
     DatabaseError(#[allow(dead_code)] sqlx::Error),
 }
 
@@ -30,6 +64,16 @@ impl fmt::Display for ExecutorError {
             ExecutorError::SubstitutionError(msg) => {
                 write!(f, "Variable substitution error: {}", msg)
             }
+            ExecutorError::AuthError(msg) => write!(f, "Authentication error: {}", msg),
+            ExecutorError::InvalidSettings(msg) => write!(f, "Invalid request settings: {}", msg),
+            ExecutorError::ScriptAborted(reason) => {
+                write!(f, "Aborted by pre-request script: {}", reason)
+            }
+            ExecutorError::ScriptError(msg) => write!(f, "Pre-request script failed: {}", msg),
+            ExecutorError::StillRunning(id) => write!(f, "Request {} is still running", id),
+            ExecutorError::QueueTimeout(host) => {
+                write!(f, "No free slot for {} in the execution queue", host)
+            }
             ExecutorError::DatabaseError(_) => write!(f, "Database error"),
         }
     }
@@ -54,18 +98,50 @@ impl IntoResponse for ExecutorError {
     fn into_response(self) -> Response {
         match self {
             ExecutorError::RequestNotFound => {
-                (StatusCode::NOT_FOUND, "Request not found").into_response()
-            }
-            ExecutorError::NetworkError(msg) => {
-                (StatusCode::BAD_GATEWAY, format!("Network error: {}", msg)).into_response()
+                (StatusCode::NOT_FOUND, i18n::t("error.request_not_found")).into_response()
             }
+            ExecutorError::NetworkError(msg) => (
+                StatusCode::BAD_GATEWAY,
+                i18n::tf("error.network", &[("message", &msg)]),
+            )
+                .into_response(),
             ExecutorError::SubstitutionError(msg) => (
                 StatusCode::BAD_REQUEST,
-                format!("Variable substitution error: {}", msg),
+                i18n::tf("error.substitution", &[("message", &msg)]),
+            )
+                .into_response(),
+            ExecutorError::AuthError(msg) => (
+                StatusCode::BAD_REQUEST,
+                i18n::tf("error.auth", &[("message", &msg)]),
+            )
+                .into_response(),
+            ExecutorError::InvalidSettings(msg) => (
+                StatusCode::BAD_REQUEST,
+                i18n::tf("error.invalid_settings", &[("message", &msg)]),
+            )
+                .into_response(),
+            ExecutorError::ScriptAborted(reason) => (
+                StatusCode::BAD_REQUEST,
+                i18n::tf("error.script_aborted", &[("reason", &reason)]),
+            )
+                .into_response(),
+            ExecutorError::ScriptError(msg) => (
+                StatusCode::BAD_REQUEST,
+                i18n::tf("error.script", &[("message", &msg)]),
+            )
+                .into_response(),
+            ExecutorError::StillRunning(id) => (
+                StatusCode::CONFLICT,
+                i18n::tf("error.still_running", &[("id", &id)]),
+            )
+                .into_response(),
+            ExecutorError::QueueTimeout(host) => (
+                StatusCode::SERVICE_UNAVAILABLE,
+                i18n::tf("error.queue_timeout", &[("host", &host)]),
             )
                 .into_response(),
             ExecutorError::DatabaseError(_) => {
-                (StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response()
+                (StatusCode::INTERNAL_SERVER_ERROR, i18n::t("error.database")).into_response()
             }
         }
     }
@@ -83,69 +159,353 @@ pub struct ExecuteRequestPayload {
     body: Option<String>,
     #[serde(default)]
     headers: Option<HashMap<String, String>>,
+    /// Overrides the request's `ip_family` for this execution
+    #[serde(default)]
+    ip_family: Option<String>,
+    /// Overrides the request's `tls_versions` for this execution
+    #[serde(default)]
+    tls_versions: Option<String>,
+}
+
+impl ExecuteRequestPayload {
+    /// Runs a saved request as stored, without overrides.
+    pub fn saved(request_id: i64, environment_id: Option<i64>) -> Self {
+        ExecuteRequestPayload {
+            request_id: Some(request_id),
+            environment_id,
+            url: None,
+            method: None,
+            body: None,
+            headers: None,
+            ip_family: None,
+            tls_versions: None,
+        }
+    }
 }
 
 #[derive(Debug, Deserialize, Serialize)]
 pub struct ExecuteResponse {
-    status: u16,
-    headers: HashMap<String, String>,
-    body: String,
-    request_name: String,
-    request_url: String,
+    pub status: u16,
+    pub headers: HashMap<String, String>,
+    pub body: String,
+    pub request_name: String,
+    pub request_url: String,
+    /// Milliseconds from sending the request to receiving the full body
+    pub duration: i64,
+    /// Values copied into the environment by the request's capture rules
+    #[serde(default)]
+    pub captured: HashMap<String, String>,
+    /// Address the response came from; the proxy's when one is used
+    #[serde(default)]
+    pub remote_addr: Option<String>,
+    /// TLS version negotiated for HTTPS targets, e.g. "1.3"
+    #[serde(default)]
+    pub tls_version: Option<String>,
+    /// Console output of the pre-request and test scripts
+    #[serde(default)]
+    pub script_logs: Vec<String>,
+    /// Results of the test script, empty without one
+    #[serde(default)]
+    pub test_results: Vec<TestResult>,
+    /// Results of the request's assertions, in order
+    #[serde(default)]
+    pub assertion_results: Vec<TestResult>,
+    /// The asynchronous reply, for requests with callback settings
+    #[serde(default)]
+    pub callback: Option<CallbackResult>,
+    /// Placeholders sent as they are, under the warn mode
+    #[serde(default)]
+    pub unresolved: Vec<String>,
+    /// Every send of the request, empty without a retry policy
+    #[serde(default)]
+    pub attempts: Vec<RetryAttempt>,
+    /// Size of the body as it arrived, before any Content-Encoding is undone
+    #[serde(default)]
+    pub raw_size: usize,
+    /// Size of the body as returned; the raw size when it is not decompressed
+    #[serde(default)]
+    pub decoded_size: usize,
+    /// History entry of this execution, for downloading the body
+    #[serde(default)]
+    pub history_id: Option<i64>,
+    /// Set when the response was replayed from the response cache instead
+    /// of received
+    #[serde(default)]
+    pub cached: Option<CachedFrom>,
+    /// The request's latency budget, and whether the duration exceeded it
+    #[serde(default)]
+    pub latency_budget_ms: Option<i64>,
+    #[serde(default)]
+    pub over_budget: bool,
 }
 
-// Function to substitute variables in a string
-fn substitute_variables(
-    template: &str,
-    variables: &HashMap<String, String>,
-) -> Result<String, ExecutorError> {
-    log::debug!("Substituting variables in template: {}", template);
+/// The request `/execute-direct` would send, returned instead of sending it.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct DryRunResponse {
+    pub request_name: String,
+    pub method: String,
+    pub url: String,
+    /// Final headers, including auth, signatures and Accept-Encoding;
+    /// repeated headers are joined with ", "
+    pub headers: HashMap<String, String>,
+    /// `None` without a body, and for multipart bodies, which are streamed
+    pub body: Option<String>,
+    #[serde(default)]
+    pub script_logs: Vec<String>,
+    /// Placeholders that would be sent as they are, under the warn mode
+    #[serde(default)]
+    pub unresolved: Vec<String>,
+    /// Set when sending would first fetch an auth token; `TOKEN_PLACEHOLDER`
+    /// stands in for it in the headers
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pending_auth: Option<String>,
+}
+
+/// Stands in for tokens a dry run does not fetch.
+pub const TOKEN_PLACEHOLDER: &str = "<token would be fetched>";
+
+/// Loads the global variables overridden by those of an environment, or the
+/// globals alone when none is selected.
+pub async fn load_environment_variables(
+    pool: &DbPool,
+    environment_id: Option<i64>,
+) -> Result<HashMap<String, String>, ExecutorError> {
+    load_variables(pool, None, environment_id).await
+}
+
+/// Loads the variables used for substitution: globals, overridden by the
+/// folder's variables, overridden by the environment's and those it inherits.
+pub async fn load_variables(
+    pool: &DbPool,
+    folder_id: Option<i64>,
+    environment_id: Option<i64>,
+) -> Result<HashMap<String, String>, ExecutorError> {
+    let mut globals = globals::load(pool).await?;
+    globals.extend(folders::load_variables(pool, folder_id).await?);
+    let Some(env_id) = environment_id else {
+        log::debug!(
+            "No environment specified, using {} global and folder variables",
+            globals.len()
+        );
+        return Ok(globals);
+    };
+
     log::debug!(
-        "Available variables: {:?}",
-        variables.keys().collect::<Vec<_>>()
+        "Loading environment variables for environment_id: {}",
+        env_id
     );
+    // Parents first, so each environment overrides those it inherits from
+    let chain = environments::load_chain(pool, env_id).await?;
+    for environment_db in chain.into_iter().rev() {
+        log::debug!("Environment loaded: name={}", environment_db.name);
+        let environment = Environment::revealed(environment_db).map_err(|e| {
+            log::error!("Failed to decrypt secret environment variables: {}", e);
+            ExecutorError::SubstitutionError(format!("Failed to decrypt secret variables: {}", e))
+        })?;
+        let mut variables: HashMap<String, String> = serde_json::from_str(&environment.variables)
+            .map_err(|e| {
+            log::error!("Failed to parse environment variables: {}", e);
+            ExecutorError::SubstitutionError(format!(
+                "Failed to parse environment variables: {}",
+                e
+            ))
+        })?;
+        // Disabled variables fall back to the parent's, the folder's or the globals
+        variables.retain(|name, _| !environment.disabled.contains(name));
+        log::debug!("Loaded {} environment variables", variables.len());
+        globals.extend(variables);
+    }
+    Ok(globals)
+}
+
+/// Variable types declared along an environment's chain of parents, the
+/// nearest environment's declaration winning.
+pub async fn load_variable_types(
+    pool: &DbPool,
+    environment_id: Option<i64>,
+) -> Result<VariableTypes, ExecutorError> {
+    let mut types = VariableTypes::new();
+    if let Some(env_id) = environment_id {
+        for environment_db in environments::load_chain(pool, env_id)
+            .await?
+            .into_iter()
+            .rev()
+        {
+            types.extend(environment_db.variable_types());
+        }
+    }
+    Ok(types)
+}
+
+/// Checks the typed variables a request refers to, so that a bad value is
+/// reported here rather than by the target API.
+fn check_variable_types(
+    request: &crate::requests::Request,
+    variables: &HashMap<String, String>,
+    types: &VariableTypes,
+) -> Result<(), ExecutorError> {
+    let texts = [
+        Some(&request.url),
+        request.body.as_ref(),
+        request.body_content.as_ref(),
+        request.headers.as_ref(),
+        request.auth_token.as_ref(),
+        request.auth_username.as_ref(),
+        request.auth_password.as_ref(),
+        request.auth_config.as_ref(),
+    ];
+    let used = variables.iter().filter(|(name, _)| {
+        let placeholder = format!("{{{{{}}}}}", name);
+        texts
+            .iter()
+            .flatten()
+            .any(|text| text.contains(&placeholder))
+    });
+    variable_types::check_values(
+        types,
+        used.map(|(name, value)| (name.as_str(), value.as_str())),
+        |_| false,
+    )
+    .map_err(|e| {
+        log::warn!("{}", e);
+        ExecutorError::SubstitutionError(e)
+    })
+}
 
+/// Replaces the known `{{name}}` placeholders and built-in generators,
+/// leaving any other placeholder in place.
+pub fn replace_placeholders(template: &str, variables: &HashMap<String, String>) -> String {
     let mut result = template.to_string();
     for (key, value) in variables {
         let placeholder = format!("{{{{{}}}}}", key);
         if result.contains(&placeholder) {
-            log::debug!("Replacing {} with {}", placeholder, value);
+            log::debug!("Replacing {}", placeholder);
             result = result.replace(&placeholder, value);
         }
     }
-    // Check if any placeholders remain
-    if result.contains("{{") && result.contains("}}") {
+    // Built-in generators, after the variables so those can shadow them
+    dynamic_vars::substitute(&result)
+}
+
+/// Names of the `{{name}}` placeholders left in a text, in order and without
+/// duplicates.
+pub fn unresolved_names(text: &str) -> Vec<String> {
+    let mut names: Vec<String> = Vec::new();
+    let mut rest = text;
+    while let Some(start) = rest.find("{{") {
+        let Some(len) = rest[start + 2..].find("}}") else {
+            break;
+        };
+        let name = rest[start + 2..start + 2 + len].trim().to_string();
+        if !names.contains(&name) {
+            names.push(name);
+        }
+        rest = &rest[start + 2 + len + 2..];
+    }
+    names
+}
+
+// Function to substitute variables and built-in `{{$name}}` generators in a string
+pub fn substitute_variables(
+    template: &str,
+    variables: &HashMap<String, String>,
+) -> Result<String, ExecutorError> {
+    log::debug!("Substituting variables in template: {}", template);
+    log::debug!(
+        "Available variables: {:?}",
+        variables.keys().collect::<Vec<_>>()
+    );
+
+    let result = replace_placeholders(template, variables);
+    let unresolved = unresolved_names(&result);
+    if !unresolved.is_empty() {
         log::warn!("Unresolved variables found in result: {}", result);
-        // This is a basic check; a more robust solution might use regex to find unmatched placeholders
-        Err(ExecutorError::SubstitutionError(
-            "Unresolved variables found".to_string(),
-        ))
+        Err(ExecutorError::SubstitutionError(format!(
+            "Unresolved variables: {}",
+            unresolved.join(", ")
+        )))
     } else {
         log::debug!("Variable substitution complete: {}", result);
         Ok(result)
     }
 }
 
+/// Substitutes like [`substitute_variables`], but under the warn and keep
+/// modes sends leftover placeholders as they are, collecting their names.
+fn substitute_with_mode(
+    template: &str,
+    variables: &HashMap<String, String>,
+    mode: UnresolvedMode,
+    unresolved: &mut Vec<String>,
+) -> Result<String, ExecutorError> {
+    if mode == UnresolvedMode::Error {
+        return substitute_variables(template, variables);
+    }
+    let result = replace_placeholders(template, variables);
+    for name in unresolved_names(&result) {
+        if !unresolved.contains(&name) {
+            unresolved.push(name);
+        }
+    }
+    Ok(result)
+}
+
 // Function to build reqwest client with network settings
-async fn build_reqwest_client(pool: &DbPool) -> Result<Client, ExecutorError> {
-    log::debug!("Building reqwest client with network settings");
+pub fn build_reqwest_client(
+    network_settings: NetworkSettingsDb,
+    pac_decision: Option<PacDecision>,
+) -> Result<Client, ExecutorError> {
+    let client = client_builder(network_settings, pac_decision)?.build()?;
+    log::debug!("Reqwest client built successfully");
+    Ok(client)
+}
 
-    let network_settings = sqlx::query_as!(
-        NetworkSettings,
-        "SELECT id, auto_proxy, http_proxy, https_proxy, no_proxy FROM network_settings WHERE id = 1"
-    )
-    .fetch_one(pool)
-    .await
-    .unwrap_or_else(|_| {
-        log::debug!("Using default network settings (auto_proxy=true)");
-        NetworkSettings {
-            id: 1,
-            auto_proxy: true,
-            http_proxy: None,
-            https_proxy: None,
-            no_proxy: None,
+/// Hash of everything [`client_builder`], [`IpFamily::apply`] and
+/// [`TlsVersions::apply`] use, so executions that would build the same
+/// client share one.
+fn client_key(
+    settings: &NetworkSettingsDb,
+    pac_decision: Option<&PacDecision>,
+    ip_family: IpFamily,
+    host: Option<&str>,
+    tls_versions: TlsVersions,
+) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    settings.auto_proxy.hash(&mut hasher);
+    settings.http_proxy.hash(&mut hasher);
+    settings.https_proxy.hash(&mut hasher);
+    settings.no_proxy.hash(&mut hasher);
+    settings.proxy_username.hash(&mut hasher);
+    settings.proxy_password.hash(&mut hasher);
+    match pac_decision {
+        None => 0u8.hash(&mut hasher),
+        Some(PacDecision::Direct) => 1u8.hash(&mut hasher),
+        Some(PacDecision::Proxy(proxy_url)) => {
+            2u8.hash(&mut hasher);
+            proxy_url.url.as_str().hash(&mut hasher);
         }
-    });
+    }
+    ip_family.hash(&mut hasher);
+    host.hash(&mut hasher);
+    tls_versions.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Client builder with the proxy settings applied, for callers that
+/// restrict the client further.
+pub fn client_builder(
+    network_settings: NetworkSettingsDb,
+    pac_decision: Option<PacDecision>,
+) -> Result<ClientBuilder, ExecutorError> {
+    log::debug!("Building reqwest client with network settings");
+
+    let proxy_password = network_settings
+        .decrypted_proxy_password()
+        .map_err(|e| {
+            log::error!("Failed to decrypt proxy password: {}", e);
+            ExecutorError::NetworkError(format!("Failed to decrypt proxy password: {}", e))
+        })?
+        .unwrap_or_default();
 
     log::debug!(
         "Network settings: auto_proxy={}, http_proxy={:?}, https_proxy={:?}",
@@ -158,59 +518,206 @@ async fn build_reqwest_client(pool: &DbPool) -> Result<Client, ExecutorError> {
 
     if !network_settings.auto_proxy {
         log::debug!("Manual proxy configuration enabled");
-        if let Some(http_proxy_str) = network_settings.http_proxy {
-            log::debug!("Setting HTTP proxy: {}", http_proxy_str);
-            if let Ok(proxy) = Proxy::http(&http_proxy_str) {
-                client_builder = client_builder.proxy(proxy);
-            } else {
-                log::error!("Invalid HTTP proxy: {}", http_proxy_str);
-                return Err(ExecutorError::NetworkError(format!(
-                    "Invalid HTTP proxy: {}",
-                    http_proxy_str
-                )));
-            }
-        }
-        if let Some(https_proxy_str) = network_settings.https_proxy {
-            log::debug!("Setting HTTPS proxy: {}", https_proxy_str);
-            if let Ok(proxy) = Proxy::https(&https_proxy_str) {
-                client_builder = client_builder.proxy(proxy);
-            } else {
-                log::error!("Invalid HTTPS proxy: {}", https_proxy_str);
-                return Err(ExecutorError::NetworkError(format!(
-                    "Invalid HTTPS proxy: {}",
-                    https_proxy_str
-                )));
-            }
+        let manual_proxies = [
+            (ProxyTarget::Http, network_settings.http_proxy.as_deref()),
+            (ProxyTarget::Https, network_settings.https_proxy.as_deref()),
+        ];
+        for (target, proxy_str) in manual_proxies {
+            let Some(proxy_str) = proxy_str.filter(|p| !p.trim().is_empty()) else {
+                continue;
+            };
+            let proxy_url = proxy::parse_proxy_url(proxy_str)
+                .map_err(|e| {
+                    log::error!("Invalid {:?} proxy: {}", target, e);
+                    ExecutorError::NetworkError(e)
+                })?
+                .with_credentials(network_settings.proxy_username.as_deref(), &proxy_password);
+            log::debug!(
+                "Setting {:?} proxy: {}://{}:{}",
+                target,
+                proxy_url.scheme,
+                proxy_url.host(),
+                proxy_url.port()
+            );
+            let proxy = proxy_url.to_reqwest(target).map_err(|e| {
+                log::error!("Invalid {:?} proxy {}: {}", target, proxy_url.redacted(), e);
+                ExecutorError::NetworkError(format!(
+                    "Invalid proxy {}: {}",
+                    proxy_url.redacted(),
+                    e
+                ))
+            })?;
+            let proxy = proxy.no_proxy(
+                network_settings
+                    .no_proxy
+                    .as_deref()
+                    .and_then(reqwest::NoProxy::from_string),
+            );
+            client_builder = client_builder.proxy(proxy);
         }
     } else {
         log::debug!("Auto proxy detection enabled");
+        match pac_decision {
+            Some(PacDecision::Direct) => {
+                log::debug!("PAC script chose DIRECT");
+                client_builder = client_builder.no_proxy();
+            }
+            Some(PacDecision::Proxy(proxy_url)) => {
+                log::debug!("PAC script chose proxy {}", proxy_url.redacted());
+                for target in [ProxyTarget::Http, ProxyTarget::Https] {
+                    let proxy = proxy_url.to_reqwest(target).map_err(|e| {
+                        ExecutorError::NetworkError(format!(
+                            "Invalid proxy {}: {}",
+                            proxy_url.redacted(),
+                            e
+                        ))
+                    })?;
+                    client_builder = client_builder.proxy(proxy);
+                }
+            }
+            None => {}
+        }
     }
 
-    let client = client_builder.build()?;
-    log::debug!("Reqwest client built successfully");
-    Ok(client)
+    Ok(client_builder)
 }
 
 async fn execute_request_handler(
     State(pool): State<DbPool>,
     Json(payload): Json<ExecuteRequestPayload>,
 ) -> Result<impl IntoResponse, ExecutorError> {
+    Ok(Json(execute(pool, payload).await?))
+}
+
+/// Prepares the request like an execution but returns it instead of sending
+/// it. Cached OAuth 2.0 and chained auth tokens are used; tokens that would
+/// have to be fetched are replaced by a placeholder.
+async fn dry_run_handler(
+    State(pool): State<DbPool>,
+    Json(payload): Json<ExecuteRequestPayload>,
+) -> Result<impl IntoResponse, ExecutorError> {
+    Ok(Json(dry_run(pool, payload).await?))
+}
+
+pub async fn dry_run(
+    pool: DbPool,
+    payload: ExecuteRequestPayload,
+) -> Result<DryRunResponse, ExecutorError> {
     log::info!(
-        "Executing request: request_id={:?}, environment_id={:?}",
+        "Dry run: request_id={:?}, environment_id={:?}",
         payload.request_id,
         payload.environment_id
     );
+    let prepared = prepare(pool, payload, true).await?;
+    let outgoing = &prepared.outgoing;
+
+    let mut headers: HashMap<String, String> = HashMap::new();
+    for (name, value) in outgoing.headers() {
+        let value = String::from_utf8_lossy(value.as_bytes());
+        headers
+            .entry(name.to_string())
+            .and_modify(|existing| {
+                existing.push_str(", ");
+                existing.push_str(&value);
+            })
+            .or_insert_with(|| value.into_owned());
+    }
+    let body = outgoing
+        .body()
+        .and_then(|body| body.as_bytes())
+        .map(|bytes| String::from_utf8_lossy(bytes).into_owned());
+
+    Ok(DryRunResponse {
+        request_name: prepared.request.name,
+        method: outgoing.method().to_string(),
+        url: outgoing.url().to_string(),
+        headers,
+        body,
+        script_logs: prepared.script_logs,
+        unresolved: if prepared.mode == UnresolvedMode::Warn {
+            prepared.unresolved
+        } else {
+            Vec::new()
+        },
+        pending_auth: prepared.pending_auth,
+    })
+}
+
+/// Applies a script's variable changes to the variables of the execution and
+/// the selected environment.
+async fn apply_script_changes(
+    pool: &DbPool,
+    environment_id: Option<i64>,
+    variables: &mut HashMap<String, String>,
+    changes: &HashMap<String, Option<String>>,
+) -> Result<(), ExecutorError> {
+    if changes.is_empty() {
+        return Ok(());
+    }
+    for (key, value) in changes {
+        match value {
+            Some(value) => variables.insert(key.clone(), value.clone()),
+            None => variables.remove(key),
+        };
+    }
+    match environment_id {
+        Some(env_id) => scripting::store_environment(pool, env_id, changes).await?,
+        None => log::debug!("No environment selected, script changes are not stored"),
+    }
+    Ok(())
+}
+
+/// Boxed [`execute`] for running token requests from within an execution.
+fn execute_boxed(
+    pool: DbPool,
+    payload: ExecuteRequestPayload,
+) -> Pin<Box<dyn Future<Output = Result<ExecuteResponse, ExecutorError>> + Send>> {
+    Box::pin(execute(pool, payload))
+}
 
+/// A request ready to send: variables substituted, auth applied and signed.
+struct Prepared {
+    request: crate::requests::Request,
+    variables: HashMap<String, String>,
+    script_logs: Vec<String>,
+    unresolved: Vec<String>,
+    mode: UnresolvedMode,
+    client: Client,
+    outgoing: reqwest::Request,
+    compression: CompressionSettings,
+    /// Held until the response has been read, for serialized requests
+    serial_guard: Option<tokio::sync::OwnedMutexGuard<()>>,
+    /// The token a dry run left out, see `DryRunResponse::pending_auth`
+    pending_auth: Option<String>,
+}
+
+/// Everything up to sending: loads the request, runs the pre-request script,
+/// substitutes variables, resolves auth and assembles the outgoing request.
+/// A dry run neither waits for serialized requests, stores the changes of
+/// the pre-request script, fetches auth tokens or PAC files nor opens SSH
+/// tunnels.
+async fn prepare(
+    pool: DbPool,
+    payload: ExecuteRequestPayload,
+    dry_run: bool,
+) -> Result<Prepared, ExecutorError> {
     // 1. Fetch Request Details or use provided values
     let mut request = if let Some(request_id) = payload.request_id {
         log::debug!("Fetching request details for id: {}", request_id);
         let request_db = sqlx::query_as!(
             RequestDb,
-            "SELECT id, name, method, url, body, headers, folder_id, request_type, body_type, body_content, auth_type, auth_token, auth_username, auth_password, created_at, updated_at, archived_at FROM requests WHERE id = ?",
+            "SELECT id, name, method, url, body, headers, folder_id, request_type, body_type, body_content, auth_type, auth_token, auth_username, auth_password, captures, auth_config, serialized, ip_family, pre_request_script, test_script, callback, unresolved_variables, retry, compression, assertions, description, tls_versions, ssh_tunnel_id, latency_budget_ms, created_at, updated_at, archived_at FROM requests WHERE id = ?",
             request_id
         )
         .fetch_one(&pool)
         .await?;
+        // Never send the ciphertext of credentials under another secret key
+        if !crate::requests::credentials_readable(&request_db) {
+            return Err(ExecutorError::AuthError(format!(
+                "The credentials of request '{}' cannot be decrypted",
+                request_db.name
+            )));
+        }
         let mut req = crate::requests::Request::from(request_db);
 
         // Override with provided values if they exist
@@ -223,15 +730,21 @@ async fn execute_request_handler(
         if let Some(body) = payload.body {
             req.body = Some(body);
         }
+        if let Some(ip_family) = payload.ip_family {
+            req.ip_family = ip_family;
+        }
+        if let Some(tls_versions) = payload.tls_versions {
+            req.tls_versions = Some(tls_versions);
+        }
         // Always use provided headers (even if empty) to allow clearing headers
         if let Some(headers_map) = &payload.headers {
             if headers_map.is_empty() {
                 // Empty headers map means no headers should be sent
                 req.headers = None;
             } else {
-                req.headers = Some(serde_json::to_string(&headers_map).map_err(|e| {
-                    ExecutorError::SubstitutionError(format!("Failed to serialize headers: {}", e))
-                })?);
+                req.headers = Some(header_rows::to_json(&header_rows::from_map(
+                    headers_map.clone(),
+                )));
             }
         }
         req
@@ -250,8 +763,7 @@ async fn execute_request_handler(
             body: payload.body,
             headers: payload
                 .headers
-                .as_ref()
-                .and_then(|h| serde_json::to_string(h).ok()),
+                .map(|h| header_rows::to_json(&header_rows::from_map(h))),
             folder_id: None,
             request_type: "api".to_string(),
             body_type: "none".to_string(),
@@ -260,12 +772,31 @@ async fn execute_request_handler(
             auth_token: None,
             auth_username: None,
             auth_password: None,
+            captures: None,
+            auth_config: None,
+            serialized: false,
+            ip_family: payload.ip_family.unwrap_or_else(|| "auto".to_string()),
+            pre_request_script: None,
+            test_script: None,
+            callback: None,
+            unresolved_variables: None,
+            retry: None,
+            compression: None,
+            assertions: None,
+            description: None,
+            tls_versions: payload.tls_versions,
+            ssh_tunnel_id: None,
+            latency_budget_ms: None,
             created_at: chrono::Utc::now(),
             updated_at: chrono::Utc::now(),
             archived_at: None,
         }
     };
 
+    // Relative URLs of requests in a folder are resolved against its base URL
+    let base_url = folders::load_base_url(&pool, request.folder_id).await?;
+    request.url = folders::join_base_url(base_url.as_deref(), &request.url);
+
     log::debug!(
         "Request loaded: name={}, method={}, url={}",
         request.name,
@@ -273,79 +804,216 @@ async fn execute_request_handler(
         request.url
     );
 
-    // 2. Fetch Environment Variables
-    let mut variables: HashMap<String, String> = HashMap::new();
-    if let Some(env_id) = payload.environment_id {
-        log::debug!(
-            "Loading environment variables for environment_id: {}",
-            env_id
-        );
-        let environment_db = sqlx::query_as!(
-            EnvironmentDb,
-            "SELECT id, name, variables, created_at, updated_at, archived_at FROM environments WHERE id = ?",
-            env_id
+    // Serialized requests run one at a time; the guard is held until the
+    // response has been read.
+    let serial_guard = match payload
+        .request_id
+        .filter(|_| request.serialized && !dry_run)
+    {
+        Some(request_id) => Some(
+            request_lock::acquire(request_id, request_lock::wait_timeout())
+                .await
+                .ok_or_else(|| {
+                    log::warn!("Timed out waiting for request {} to finish", request_id);
+                    ExecutorError::StillRunning(request_id)
+                })?,
+        ),
+        None => None,
+    };
+
+    // 2. Fetch Environment Variables, beneath them the folder's and the globals
+    let mut variables = load_variables(&pool, request.folder_id, payload.environment_id).await?;
+
+    // 2b. Run the pre-request script, which sees the request before substitution
+    let mut script_logs = Vec::new();
+    if let Some(script) = request
+        .pre_request_script
+        .clone()
+        .filter(|s| !s.trim().is_empty())
+    {
+        log::debug!("Running pre-request script");
+        let headers = match &request.headers {
+            Some(h) => header_rows::enabled_map(
+                &header_rows::parse(h).map_err(ExecutorError::SubstitutionError)?,
+            ),
+            None => HashMap::new(),
+        };
+        let uses_body_content = request.body_content.is_some();
+        let script_request = ScriptRequest {
+            method: request.method.clone(),
+            url: request.url.clone(),
+            headers,
+            body: request
+                .body_content
+                .clone()
+                .or_else(|| request.body.clone()),
+        };
+        let outcome = scripting::run_pre_request(script, script_request, variables.clone())
+            .await
+            .map_err(|e| match e {
+                ScriptError::Aborted(reason) => {
+                    log::info!("Pre-request script aborted the execution: {}", reason);
+                    ExecutorError::ScriptAborted(reason)
+                }
+                ScriptError::Failed(msg) => {
+                    log::warn!("Pre-request script failed: {}", msg);
+                    ExecutorError::ScriptError(msg)
+                }
+            })?;
+        for line in &outcome.logs {
+            log::info!("Pre-request script: {}", line);
+        }
+
+        request.method = outcome.request.method;
+        request.url = outcome.request.url;
+        request.headers = if outcome.request.headers.is_empty() {
+            None
+        } else {
+            Some(header_rows::to_json(&header_rows::from_map(
+                outcome.request.headers,
+            )))
+        };
+        if uses_body_content {
+            request.body_content = outcome.request.body;
+        } else {
+            request.body = outcome.request.body;
+        }
+        apply_script_changes(
+            &pool,
+            payload.environment_id.filter(|_| !dry_run),
+            &mut variables,
+            &outcome.environment,
         )
-        .fetch_one(&pool)
         .await?;
-        log::debug!("Environment loaded: name={}", environment_db.name);
-        let env_vars: HashMap<String, String> = serde_json::from_str(&environment_db.variables)
-            .map_err(|e| {
-                log::error!("Failed to parse environment variables: {}", e);
-                ExecutorError::SubstitutionError(format!(
-                    "Failed to parse environment variables: {}",
-                    e
-                ))
-            })?;
-        log::debug!("Loaded {} environment variables", env_vars.len());
-        variables.extend(env_vars);
-    } else {
-        log::debug!("No environment specified, using empty variable set");
+        script_logs = outcome.logs;
     }
 
+    // 2c. Check the values of typed variables the request uses
+    let types = load_variable_types(&pool, payload.environment_id).await?;
+    check_variable_types(&request, &variables, &types)?;
+
     // 3. Perform Variable Substitution
     log::debug!("Performing variable substitution");
-    let resolved_url = substitute_variables(&request.url, &variables)?;
-    let resolved_body = request
-        .body
-        .as_ref()
-        .map(|b| substitute_variables(b, &variables))
-        .transpose()?;
-    let resolved_headers = request
-        .headers
-        .as_ref()
-        .map(|h| substitute_variables(h, &variables))
-        .transpose()?;
-
-    let resolved_auth_token = request
+    let mode = UnresolvedMode::for_request(&pool, request.unresolved_variables.as_deref()).await;
+    let mut unresolved = Vec::new();
+    let mut substitute = |text: &str| substitute_with_mode(text, &variables, mode, &mut unresolved);
+    request.url = substitute(&request.url)?;
+    request.body = request.body.as_deref().map(&mut substitute).transpose()?;
+    if request.body_type == "form" {
+        if let Some(content) = &request.body_content {
+            let mut rows = form_body::parse(content).map_err(ExecutorError::SubstitutionError)?;
+            for row in rows.iter_mut().filter(|row| row.enabled) {
+                row.key = substitute(&row.key)?;
+                row.value = substitute(&row.value)?;
+            }
+            request.body_content = Some(form_body::to_json(&rows));
+        }
+    }
+    if let Some(headers) = &request.headers {
+        let mut rows = header_rows::parse(headers).map_err(ExecutorError::SubstitutionError)?;
+        // Disabled headers may use variables that are not set
+        rows.retain(|row| row.enabled);
+        for row in rows.iter_mut() {
+            row.key = substitute(&row.key)?;
+            row.value = substitute(&row.value)?;
+        }
+        request.headers = Some(header_rows::to_json(&rows));
+    }
+    request.auth_token = request
         .auth_token
-        .as_ref()
-        .map(|t| substitute_variables(t, &variables))
+        .as_deref()
+        .map(&mut substitute)
         .transpose()?;
-    let resolved_auth_username = request
+    request.auth_username = request
         .auth_username
-        .as_ref()
-        .map(|u| substitute_variables(u, &variables))
+        .as_deref()
+        .map(&mut substitute)
         .transpose()?;
-    let resolved_auth_password = request
+    request.auth_password = request
         .auth_password
-        .as_ref()
-        .map(|p| substitute_variables(p, &variables))
+        .as_deref()
+        .map(&mut substitute)
+        .transpose()?;
+    request.auth_config = request
+        .auth_config
+        .as_deref()
+        .map(&mut substitute)
         .transpose()?;
+    if mode == UnresolvedMode::Warn && !unresolved.is_empty() {
+        log::warn!(
+            "Sending request with unresolved variables: {}",
+            unresolved.join(", ")
+        );
+    }
 
-    request.url = resolved_url.clone();
-    request.body = resolved_body.clone();
-    request.headers = resolved_headers.clone();
-    request.auth_token = resolved_auth_token;
-    request.auth_username = resolved_auth_username;
-    request.auth_password = resolved_auth_password;
+    // 4. Build Reqwest Client with Network Settings
+    let network_settings = match request.ssh_tunnel_id {
+        // The tunnel's port is only known once it is open
+        Some(_) if dry_run => NetworkSettingsDb::through_socks(0),
+        // Loopback targets mean the bastion's, so they are not rewritten
+        Some(tunnel_id) => {
+            let local_port = ssh_tunnels::ensure(&pool, tunnel_id)
+                .await
+                .map_err(|e| ExecutorError::NetworkError(e.to_string()))?;
+            log::debug!("Sending through SSH tunnel on local port {}", local_port);
+            NetworkSettingsDb::through_socks(local_port)
+        }
+        None => network::load_settings(&pool).await.unwrap_or_else(|_| {
+            log::debug!("Using default network settings (auto_proxy=true)");
+            NetworkSettingsDb::defaults()
+        }),
+    };
+    if let Some(alias) = network_settings.localhost_target() {
+        if let Some(rewritten) = network::rewrite_localhost(&request.url, alias) {
+            log::info!(
+                "Redirecting loopback target {} to {}",
+                request.url,
+                rewritten
+            );
+            request.url = rewritten;
+        }
+    }
 
     log::debug!("Resolved URL: {}", request.url);
-    if let Some(ref body) = resolved_body {
+    if let Some(ref body) = request.body {
         log::debug!("Resolved body length: {} bytes", body.len());
     }
 
-    // 4. Build Reqwest Client with Network Settings
-    let client = build_reqwest_client(&pool).await?;
+    let ip_family = IpFamily::parse(&request.ip_family).map_err(ExecutorError::InvalidSettings)?;
+    let host = reqwest::Url::parse(&request.url)
+        .ok()
+        .and_then(|url| url.host_str().map(str::to_string));
+    if ip_family != IpFamily::Auto {
+        log::debug!("Connecting with IP family {:?}", ip_family);
+    }
+    // The PAC file is only fetched for requests that are sent
+    let pac_decision = if dry_run {
+        None
+    } else {
+        network_settings.pac_decision(&request.url).await
+    };
+    // Only a fixed address makes the client specific to the host
+    let host = host.filter(|_| matches!(ip_family, IpFamily::Fixed(_)));
+    let tls_versions = TlsVersions::parse(request.tls_versions.as_deref())
+        .map_err(ExecutorError::InvalidSettings)?
+        .or(TlsVersions::global(&pool).await);
+    if !tls_versions.is_default() {
+        log::debug!("Connecting with TLS versions {:?}", tls_versions);
+    }
+    let key = client_key(
+        &network_settings,
+        pac_decision.as_ref(),
+        ip_family,
+        host.as_deref(),
+        tls_versions,
+    );
+    let client = client_pool::shared().get_or_build(key, || {
+        let builder = ip_family.apply(
+            client_builder(network_settings, pac_decision)?,
+            host.as_deref(),
+        );
+        Ok::<_, ExecutorError>(tls_versions.apply(builder).tls_info(true).build()?)
+    })?;
 
     // 5. Execute HTTP Request
     log::info!("Executing {} request to: {}", request.method, request.url);
@@ -357,7 +1025,10 @@ async fn execute_request_handler(
         &request.url,
     );
 
-    // Apply authentication
+    // Apply authentication. AWS signatures cover the final headers and body,
+    // so those requests are signed just before sending.
+    let mut aws_signing = None;
+    let mut pending_auth = None;
     match request.auth_type.as_str() {
         "bearer" => {
             if let Some(token) = &request.auth_token {
@@ -373,6 +1044,108 @@ async fn execute_request_handler(
                 req_builder = req_builder.basic_auth(username, Some(password));
             }
         }
+        "oauth2" => {
+            log::debug!("Applying OAuth 2.0 access token");
+            let token = if dry_run {
+                oauth::cached_token_for_request(
+                    &pool,
+                    payload.request_id,
+                    request.folder_id,
+                    &variables,
+                )
+                .await
+                .map(|token| {
+                    token.unwrap_or_else(|| {
+                        pending_auth = Some("An OAuth 2.0 token would be fetched".to_string());
+                        TOKEN_PLACEHOLDER.to_string()
+                    })
+                })
+            } else {
+                oauth::token_for_request(&pool, payload.request_id, request.folder_id, &variables)
+                    .await
+            }
+            .map_err(|e| {
+                log::warn!("Failed to obtain OAuth token: {}", e);
+                ExecutorError::AuthError(e.to_string())
+            })?;
+            req_builder = req_builder.header("Authorization", format!("Bearer {}", token));
+        }
+        "jwt" => {
+            let config = jwt::JwtConfig::parse(request.auth_config.as_deref())
+                .map_err(ExecutorError::AuthError)?;
+            let key = request.auth_password.as_deref().unwrap_or_default();
+            if key.is_empty() {
+                return Err(ExecutorError::AuthError(
+                    "JWT auth needs a signing secret or private key".to_string(),
+                ));
+            }
+            log::debug!("Minting {:?} JWT", config.algorithm);
+            let token =
+                jwt::mint(&config, key, chrono::Utc::now()).map_err(ExecutorError::AuthError)?;
+            req_builder =
+                req_builder.header("Authorization", format!("{} {}", config.scheme, token));
+        }
+        "chained" => {
+            let config = chained_auth::ChainedConfig::parse(request.auth_config.as_deref())
+                .map_err(ExecutorError::AuthError)?;
+            let token = match chained_auth::cached(&config, payload.environment_id) {
+                Some(token) => {
+                    log::debug!("Reusing token from request {}", config.request_id);
+                    token
+                }
+                None if dry_run => {
+                    pending_auth = Some(format!(
+                        "Request #{} would be run for a token",
+                        config.request_id
+                    ));
+                    TOKEN_PLACEHOLDER.to_string()
+                }
+                None => {
+                    chained_auth::check_chain(&pool, payload.request_id, &config)
+                        .await
+                        .map_err(ExecutorError::AuthError)?;
+                    log::info!("Running request {} for an auth token", config.request_id);
+                    let response = execute_boxed(
+                        pool.clone(),
+                        ExecuteRequestPayload::saved(config.request_id, payload.environment_id),
+                    )
+                    .await
+                    .map_err(|e| {
+                        ExecutorError::AuthError(format!(
+                            "Token request #{} failed: {}",
+                            config.request_id, e
+                        ))
+                    })?;
+                    chained_auth::store(
+                        &config,
+                        payload.environment_id,
+                        response.status,
+                        &response.headers,
+                        &response.body,
+                    )
+                    .map_err(ExecutorError::AuthError)?
+                }
+            };
+            req_builder =
+                req_builder.header("Authorization", format!("{} {}", config.scheme, token));
+        }
+        "aws_sigv4" => {
+            let config = aws_sigv4::SigningConfig::parse(request.auth_config.as_deref())
+                .map_err(ExecutorError::AuthError)?;
+            let (Some(access_key_id), Some(secret_access_key)) =
+                (request.auth_username.clone(), request.auth_password.clone())
+            else {
+                return Err(ExecutorError::AuthError(
+                    "AWS signing needs an access key id and a secret access key".to_string(),
+                ));
+            };
+            let credentials = aws_sigv4::Credentials {
+                access_key_id,
+                secret_access_key,
+                session_token: request.auth_token.clone().filter(|t| !t.is_empty()),
+            };
+            aws_signing = Some((credentials, config));
+        }
         _ => {
             log::debug!("No authentication applied");
         }
@@ -403,23 +1176,11 @@ async fn execute_request_handler(
                     .body(body_content.clone());
             }
             "form" => {
-                // Parse form data from JSON format {"key1": "value1", "key2": "value2"}
-                let form_data: HashMap<String, String> = serde_json::from_str(body_content)
-                    .map_err(|e| {
-                        log::error!("Failed to parse form data: {}", e);
-                        ExecutorError::SubstitutionError(format!(
-                            "Failed to parse form data: {}",
-                            e
-                        ))
-                    })?;
-                // Build URL-encoded form data manually
-                let form_string: Vec<String> = form_data
-                    .iter()
-                    .map(|(k, v)| format!("{}={}", urlencoding::encode(k), urlencoding::encode(v)))
-                    .collect();
+                let rows =
+                    form_body::parse(body_content).map_err(ExecutorError::SubstitutionError)?;
                 req_builder = req_builder
                     .header("Content-Type", "application/x-www-form-urlencoded")
-                    .body(form_string.join("&"));
+                    .body(form_body::encode(&rows));
             }
             "multipart" => {
                 // Parse multipart data from JSON format {"key1": "value1", "key2": "value2"}
@@ -452,57 +1213,488 @@ async fn execute_request_handler(
         req_builder = req_builder.body(body.clone());
     }
 
-    if let Some(headers_str) = &request.headers {
-        log::debug!("Parsing and adding request headers");
-        let headers_map: HashMap<String, String> =
-            serde_json::from_str(headers_str).map_err(|e| {
-                log::error!("Failed to parse request headers: {}", e);
-                ExecutorError::SubstitutionError(format!("Failed to parse request headers: {}", e))
-            })?;
-        log::debug!("Adding {} headers", headers_map.len());
-        for (key, value) in headers_map {
-            req_builder = req_builder.header(&key, &value);
+    if let Some(headers) = &request.headers {
+        let rows = header_rows::parse(headers).map_err(|e| {
+            log::error!("{}", e);
+            ExecutorError::SubstitutionError(e)
+        })?;
+        log::debug!("Adding {} headers", rows.len());
+        for row in rows.iter().filter(|row| row.enabled && !row.key.is_empty()) {
+            req_builder = req_builder.header(&row.key, &row.value);
         }
     }
 
-    log::debug!("Sending HTTP request...");
-    let response = req_builder.send().await.map_err(|e| {
-        log::error!("Request execution failed: {}", e);
-        ExecutorError::NetworkError(e.to_string())
-    })?;
-
-    // 6. Format Response
-    let status = response.status().as_u16();
-    log::info!("Request completed with status: {}", status);
-    let mut headers = HashMap::new();
-    for (name, value) in response.headers().iter() {
-        headers.insert(name.to_string(), value.to_str().unwrap_or("").to_string());
+    let mut outgoing = req_builder.build()?;
+    // A header of the request's own wins over the compression settings
+    let compression = CompressionSettings::parse(request.compression.as_deref())
+        .map_err(ExecutorError::InvalidSettings)?;
+    if let Some(accept) = compression.accept_encoding() {
+        if !outgoing
+            .headers()
+            .contains_key(reqwest::header::ACCEPT_ENCODING)
+        {
+            let value = reqwest::header::HeaderValue::from_str(accept)
+                .map_err(|e| ExecutorError::InvalidSettings(e.to_string()))?;
+            outgoing
+                .headers_mut()
+                .insert(reqwest::header::ACCEPT_ENCODING, value);
+        }
+    }
+    if let Some((credentials, config)) = &aws_signing {
+        log::debug!(
+            "Signing request with AWS Signature v4 ({}/{})",
+            config.region,
+            config.service
+        );
+        aws_sigv4::sign(&mut outgoing, credentials, config, chrono::Utc::now())
+            .map_err(ExecutorError::AuthError)?;
     }
-    log::debug!("Response has {} headers", headers.len());
 
-    let body = response.text().await?;
-    log::debug!("Response body length: {} bytes", body.len());
+    Ok(Prepared {
+        request,
+        variables,
+        script_logs,
+        unresolved,
+        mode,
+        client,
+        outgoing,
+        compression,
+        serial_guard,
+        pending_auth,
+    })
+}
 
+/// Runs a saved request, with any overrides from the payload, or a direct one.
+#[tracing::instrument(
+    name = "execute",
+    skip_all,
+    fields(
+        otel.kind = "client",
+        request_id = payload.request_id,
+        http.request.method = tracing::field::Empty,
+        url.full = tracing::field::Empty,
+        http.response.status_code = tracing::field::Empty,
+        otel.status_code = tracing::field::Empty,
+    )
+)]
+pub async fn execute(
+    pool: DbPool,
+    payload: ExecuteRequestPayload,
+) -> Result<ExecuteResponse, ExecutorError> {
     log::info!(
-        "Request execution successful: {} {} -> {}",
-        request.method,
-        request.url,
-        status
+        "Executing request: request_id={:?}, environment_id={:?}",
+        payload.request_id,
+        payload.environment_id
+    );
+    let (request_id, environment_id) = (payload.request_id, payload.environment_id);
+
+    // Offline mode answers saved requests from the cache without sending
+    // them. The URL is resolved like a dry run, so nothing is sent, waited
+    // for or stored on the way.
+    let cache_mode = CacheMode::global(&pool).await;
+    if let (Some(request_id), CacheMode::Offline) = (request_id, cache_mode) {
+        let prepared = prepare(pool.clone(), payload, true).await?;
+        let url = prepared.request.url.clone();
+        return replay(
+            &pool,
+            request_id,
+            prepared.request,
+            prepared.script_logs,
+            CacheReason::Offline,
+            None,
+        )
+        .await?
+        .ok_or_else(|| {
+            ExecutorError::NetworkError(format!(
+                "Offline mode is on and no response of {} is cached",
+                url
+            ))
+        });
+    }
+
+    let Prepared {
+        request,
+        mut variables,
+        mut script_logs,
+        unresolved,
+        mode,
+        client,
+        mut outgoing,
+        compression,
+        serial_guard: _serial_guard,
+        ..
+    } = prepare(pool.clone(), payload, false).await?;
+
+    let span = tracing::Span::current();
+    span.record("http.request.method", outgoing.method().as_str());
+    span.record("url.full", outgoing.url().as_str());
+    telemetry::inject_traceparent(&span, outgoing.headers_mut());
+
+    // Deliveries already on the bin cannot be replies to this request
+    let callback = CallbackConfig::parse(request.callback.as_deref())
+        .map_err(ExecutorError::InvalidSettings)?;
+    let callback_after = match &callback {
+        Some(config) => webhooks::latest_id(&pool, &config.bin).await?,
+        None => 0,
+    };
+
+    let retry =
+        RetryPolicy::parse(request.retry.as_deref()).map_err(ExecutorError::InvalidSettings)?;
+    let mut attempts = Vec::new();
+
+    // Executions beyond the host's concurrency limit wait for a slot, held
+    // until the response has been read
+    let host = outgoing.url().host_str().unwrap_or_default().to_lowercase();
+    let host_slot = match HostLimits::global(&pool).await.limit_for(&host) {
+        Some(limit) => Some(
+            host_queue::acquire(&host, limit, host_queue::wait_timeout())
+                .await
+                .ok_or_else(|| {
+                    log::warn!("Timed out waiting for a slot for {}", host);
+                    ExecutorError::QueueTimeout(host.clone())
+                })?,
+        ),
+        None => None,
+    };
+
+    log::debug!("Sending HTTP request...");
+    let mut started = Instant::now();
+    let result = loop {
+        // Streamed bodies cannot be sent twice, those requests are not retried
+        let attempt = attempts.len() as u32 + 1;
+        let next = match &retry {
+            Some(policy) if attempt < policy.max_attempts => outgoing.try_clone(),
+            _ => None,
+        };
+        let result = client.execute(outgoing).await;
+        let Some(policy) = &retry else {
+            break result;
+        };
+        attempts.push(RetryAttempt {
+            attempt,
+            status: result.as_ref().ok().map(|r| r.status().as_u16()),
+            error: result.as_ref().err().map(|e| e.to_string()),
+            duration: started.elapsed().as_millis() as i64,
+        });
+        let retryable = match &result {
+            Ok(response) => policy.retries_status(response.status().as_u16()),
+            Err(_) => policy.retry_on_network_error,
+        };
+        match next {
+            Some(next) if retryable => {
+                let delay = policy.delay(attempt);
+                log::info!(
+                    "Attempt {} failed, retrying in {} ms",
+                    attempt,
+                    delay.as_millis()
+                );
+                tokio::time::sleep(delay).await;
+                outgoing = next;
+                started = Instant::now();
+            }
+            _ => break result,
+        }
+    };
+    let response = match result {
+        Ok(response) => response,
+        Err(e) => {
+            log::error!("Request execution failed: {}", e);
+            span.record("otel.status_code", "ERROR");
+            let error = match attempts.len() {
+                0 | 1 => e.to_string(),
+                n => format!("{} (after {} attempts)", e, n),
+            };
+            history::record(
+                &pool,
+                ExecutionRecord {
+                    request_id,
+                    method: &request.method,
+                    url: &request.url,
+                    status: None,
+                    duration_ms: started.elapsed().as_millis() as i64,
+                    error: Some(&error),
+                    test_results: None,
+                    latency_budget_ms: None,
+                    response: None,
+                },
+            )
+            .await;
+            if let (Some(request_id), CacheMode::Fallback) = (request_id, cache_mode) {
+                let error = Some(error.clone());
+                let cached = replay(
+                    &pool,
+                    request_id,
+                    request,
+                    script_logs,
+                    CacheReason::Unreachable,
+                    error,
+                )
+                .await?;
+                if let Some(cached) = cached {
+                    return Ok(cached);
+                }
+            }
+            return Err(ExecutorError::NetworkError(error));
+        }
+    };
+
+    // 6. Format Response
+    let status = response.status().as_u16();
+    span.record("http.response.status_code", status);
+    if status >= 500 {
+        span.record("otel.status_code", "ERROR");
+    }
+    let remote_addr = response.remote_addr().map(|addr| addr.to_string());
+    let tls_version = TlsVersion::negotiated(&response).map(|v| v.as_str().to_string());
+    log::info!(
+        "Request completed with status: {} from {}",
+        status,
+        remote_addr.as_deref().unwrap_or("unknown address")
+    );
+    let mut headers = HashMap::new();
+    for (name, value) in response.headers().iter() {
+        headers.insert(name.to_string(), value.to_str().unwrap_or("").to_string());
+    }
+    log::debug!("Response has {} headers", headers.len());
+
+    let raw = response.bytes().await?;
+    let duration = started.elapsed().as_millis() as i64;
+    // Tests, history and callbacks do not occupy the host
+    drop(host_slot);
+    let decoded = match headers.get("content-encoding") {
+        Some(encoding) if compression.decompress => {
+            compression::decode(encoding, &raw).map_err(|e| {
+                log::warn!("{}", e);
+                ExecutorError::NetworkError(e)
+            })?
+        }
+        _ => raw.to_vec(),
+    };
+    log::debug!(
+        "Response body length: {} bytes, {} bytes decoded",
+        raw.len(),
+        decoded.len()
+    );
+    let body = compression::text(&decoded, headers.get("content-type").map(String::as_str));
+
+    // 6b. Run the test script against the response
+    let mut test_results = None;
+    if let Some(script) = request.test_script.clone().filter(|s| !s.trim().is_empty()) {
+        log::debug!("Running test script");
+        let script_response = ScriptResponse {
+            status,
+            headers: headers.clone(),
+            body: body.clone(),
+            time: duration,
+        };
+        let results = match scripting::run_test_script(script, script_response, variables.clone())
+            .await
+        {
+            Ok(outcome) => {
+                for line in &outcome.logs {
+                    log::info!("Test script: {}", line);
+                }
+                script_logs.extend(outcome.logs);
+                apply_script_changes(&pool, environment_id, &mut variables, &outcome.environment)
+                    .await?;
+                outcome.results
+            }
+            Err(ScriptError::Aborted(msg)) | Err(ScriptError::Failed(msg)) => {
+                log::warn!("Test script failed: {}", msg);
+                vec![TestResult::script_failure(msg)]
+            }
+        };
+        log::info!(
+            "Tests: {} of {} passed",
+            results.iter().filter(|r| r.passed).count(),
+            results.len()
+        );
+        test_results = Some(results);
+    }
+    // 6c. Evaluate the request's assertions
+    let assertion_results = match request.assertions.as_deref() {
+        Some(saved) => match assertions::parse(saved) {
+            Ok(saved) => assertions::evaluate(&saved, status, &headers, &body, duration),
+            Err(e) => vec![TestResult {
+                name: "Assertions".to_string(),
+                passed: false,
+                error: Some(e),
+            }],
+        },
+        None => Vec::new(),
+    };
+    if !assertion_results.is_empty() {
+        log::info!(
+            "Assertions: {} of {} passed",
+            assertion_results.iter().filter(|r| r.passed).count(),
+            assertion_results.len()
+        );
+    }
+
+    let test_results_json = test_results
+        .as_ref()
+        .and_then(|r| serde_json::to_string(r).ok());
+
+    let history_id = history::record(
+        &pool,
+        ExecutionRecord {
+            request_id,
+            method: &request.method,
+            url: &request.url,
+            status: Some(status),
+            duration_ms: duration,
+            error: None,
+            test_results: test_results_json.as_deref(),
+            latency_budget_ms: request.latency_budget_ms,
+            response: Some(StoredResponse {
+                body: &decoded,
+                content_type: headers.get("content-type").map(String::as_str),
+                content_disposition: headers.get("content-disposition").map(String::as_str),
+            }),
+        },
+    )
+    .await;
+    if let Some(request_id) = request_id.filter(|_| cache_mode != CacheMode::Off) {
+        response_cache::store(
+            &pool,
+            request_id,
+            &request.url,
+            status,
+            &headers,
+            &body,
+            duration,
+        )
+        .await;
+    }
+
+    // 7. Apply Capture Rules
+    let mut captured = HashMap::new();
+    if let Some(captures) = request.captures.as_deref().filter(|c| !c.trim().is_empty()) {
+        match capture::parse_rules(captures) {
+            Ok(rules) => captured = capture::extract_all(&rules, status, &headers, &body),
+            Err(e) => log::warn!("Skipping capture rules: {}", e),
+        }
+    }
+    if !captured.is_empty() {
+        log::info!("Captured {} variable(s)", captured.len());
+        match environment_id {
+            Some(env_id) => capture::store(&pool, env_id, &captured).await?,
+            None => log::debug!("No environment selected, captured values are not stored"),
+        }
+    }
+
+    // 8. Wait for the asynchronous reply
+    let callback = match callback {
+        Some(config) => Some(
+            webhooks::await_callback(&pool, &config, callback_after, status, &headers, &body).await,
+        ),
+        None => None,
+    };
+
+    let over_budget = request
+        .latency_budget_ms
+        .is_some_and(|budget| duration > budget);
+    if over_budget {
+        log::warn!(
+            "{} {} took {} ms, over its budget of {} ms",
+            request.method,
+            request.url,
+            duration,
+            request.latency_budget_ms.unwrap_or_default()
+        );
+    }
+
+    log::info!(
+        "Request execution successful: {} {} -> {}",
+        request.method,
+        request.url,
+        status
     );
 
-    Ok(Json(ExecuteResponse {
+    Ok(ExecuteResponse {
         status,
         headers,
         body,
         request_name: request.name,
         request_url: request.url,
+        duration,
+        captured,
+        remote_addr,
+        tls_version,
+        script_logs,
+        test_results: test_results.unwrap_or_default(),
+        assertion_results,
+        callback,
+        unresolved: if mode == UnresolvedMode::Warn {
+            unresolved
+        } else {
+            Vec::new()
+        },
+        attempts,
+        raw_size: raw.len(),
+        decoded_size: decoded.len(),
+        history_id,
+        cached: None,
+        latency_budget_ms: request.latency_budget_ms,
+        over_budget,
+    })
+}
+
+/// The cached response of a saved request for its resolved URL, marked as
+/// replayed.
+async fn replay(
+    pool: &DbPool,
+    request_id: i64,
+    request: crate::requests::Request,
+    script_logs: Vec<String>,
+    reason: CacheReason,
+    error: Option<String>,
+) -> Result<Option<ExecuteResponse>, ExecutorError> {
+    let Some(cached) = response_cache::lookup(pool, request_id, &request.url).await? else {
+        return Ok(None);
+    };
+    log::info!(
+        "Replaying the response of request {} cached at {} ({:?})",
+        request_id,
+        cached.cached_at,
+        reason
+    );
+    let size = cached.body.len();
+    Ok(Some(ExecuteResponse {
+        status: cached.status,
+        headers: cached.headers,
+        body: cached.body,
+        request_name: request.name,
+        request_url: request.url,
+        duration: cached.duration_ms,
+        captured: HashMap::new(),
+        remote_addr: None,
+        tls_version: None,
+        script_logs,
+        test_results: Vec::new(),
+        assertion_results: Vec::new(),
+        callback: None,
+        unresolved: Vec::new(),
+        attempts: Vec::new(),
+        raw_size: size,
+        decoded_size: size,
+        history_id: None,
+        cached: Some(CachedFrom {
+            reason,
+            cached_at: cached.cached_at,
+            error,
+        }),
+        latency_budget_ms: None,
+        over_budget: false,
     }))
 }
 
 pub fn routes(pool: DbPool) -> Router {
+    let limit = limits::body_limit("JSLINK_MAX_EXECUTE_BYTES", limits::DEFAULT_BODY_LIMIT);
     Router::new()
         .route("/execute", post(execute_request_handler))
-        .route("/execute-direct", post(execute_request_handler))
+        .route("/execute-direct", post(dry_run_handler))
+        .layer(DefaultBodyLimit::max(limit))
         .with_state(pool)
 }
 
@@ -510,6 +1702,7 @@ pub fn routes(pool: DbPool) -> Router {
 mod tests {
     use super::*;
     use crate::db;
+    use crate::environments::EnvironmentDb;
     use crate::requests::CreateRequest;
     use axum_test::TestServer;
     use httpmock::MockServer;
@@ -527,7 +1720,7 @@ mod tests {
     async fn create_test_request(pool: &DbPool, req: &CreateRequest) -> RequestDb {
         sqlx::query_as!(
             RequestDb,
-            "INSERT INTO requests (name, method, url, body, headers, folder_id, request_type, body_type, body_content, auth_type, auth_token, auth_username, auth_password) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?) RETURNING id, name, method, url, body, headers, folder_id, request_type, body_type, body_content, auth_type, auth_token, auth_username, auth_password, created_at, updated_at, archived_at",
+            "INSERT INTO requests (name, method, url, body, headers, folder_id, request_type, body_type, body_content, auth_type, auth_token, auth_username, auth_password, captures, auth_config, serialized, ip_family, pre_request_script, test_script, callback, unresolved_variables, retry, compression, assertions, description, tls_versions, ssh_tunnel_id, latency_budget_ms) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?) RETURNING id, name, method, url, body, headers, folder_id, request_type, body_type, body_content, auth_type, auth_token, auth_username, auth_password, captures, auth_config, serialized, ip_family, pre_request_script, test_script, callback, unresolved_variables, retry, compression, assertions, description, tls_versions, ssh_tunnel_id, latency_budget_ms, created_at, updated_at, archived_at",
             req.name,
             req.method,
             req.url,
@@ -540,18 +1733,32 @@ mod tests {
             req.auth_type,
             req.auth_token,
             req.auth_username,
-            req.auth_password
+            req.auth_password,
+            req.captures,
+            req.auth_config,
+            req.serialized,
+            req.ip_family,
+            req.pre_request_script,
+            req.test_script,
+            req.callback,
+            req.unresolved_variables,
+            req.retry,
+            req.compression,
+            req.assertions,
+            req.description,
+            req.tls_versions,
+            req.ssh_tunnel_id,
+            req.latency_budget_ms
         )
         .fetch_one(pool)
         .await
         .unwrap()
     }
 
-    #[allow(dead_code)]
     async fn create_test_environment(pool: &DbPool, name: &str, variables: &str) -> EnvironmentDb {
         sqlx::query_as!(
             EnvironmentDb,
-            "INSERT INTO environments (name, variables) VALUES (?, ?) RETURNING id, name, variables, created_at, updated_at, archived_at",
+            "INSERT INTO environments (name, variables) VALUES (?, ?) RETURNING id, name, variables, secrets, disabled, types, parent_id, created_at, updated_at, archived_at",
             name,
             variables
         )
@@ -571,6 +1778,19 @@ mod tests {
         assert_eq!(result, "http://example.com/api/data?query=1");
     }
 
+    #[tokio::test]
+    async fn test_substitute_variables_with_generators() {
+        let variables = HashMap::from([("$timestamp".to_string(), "fixed".to_string())]);
+        let result =
+            substitute_variables("{{$uuid}}|{{$timestamp}}|{{$randomInt}}", &variables).unwrap();
+        let parts: Vec<&str> = result.split('|').collect();
+        assert_eq!(parts[0].len(), 36);
+        assert_eq!(parts[1], "fixed");
+        assert!(parts[2].parse::<u32>().is_ok());
+
+        assert!(substitute_variables("{{$nope}}", &variables).is_err());
+    }
+
     #[tokio::test]
     async fn test_substitute_variables_unresolved() {
         let mut variables = HashMap::new();
@@ -581,7 +1801,7 @@ mod tests {
         assert!(result.is_err());
         assert_eq!(
             result.unwrap_err().to_string(),
-            ExecutorError::SubstitutionError("Unresolved variables found".to_string()).to_string()
+            ExecutorError::SubstitutionError("Unresolved variables: path".to_string()).to_string()
         );
     }
 
@@ -617,6 +1837,21 @@ mod tests {
             auth_token: None,
             auth_username: None,
             auth_password: None,
+            captures: None,
+            auth_config: None,
+            serialized: false,
+            ip_family: "auto".to_string(),
+            pre_request_script: None,
+            test_script: None,
+            callback: None,
+            unresolved_variables: None,
+            retry: None,
+            compression: None,
+            assertions: None,
+            description: None,
+            tls_versions: None,
+            ssh_tunnel_id: None,
+            latency_budget_ms: None,
         };
         let request_db = create_test_request(&pool, &req).await;
 
@@ -637,6 +1872,1206 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn test_execute_unresolved_modes() {
+        let pool = db::create_test_pool().await;
+        ensure_default_network_settings(&pool).await;
+
+        let mock_server = start_mock_server().await;
+        let mock = mock_server.mock(|when, then| {
+            when.method(httpmock::Method::POST)
+                .path("/render")
+                .body("Hello {{name}}");
+            then.status(200);
+        });
+
+        let mut req = CreateRequest {
+            name: "Template".to_string(),
+            method: "POST".to_string(),
+            url: format!("{}/render", mock_server.base_url()),
+            body: Some("Hello {{name}}".to_string()),
+            headers: None,
+            folder_id: None,
+            request_type: "api".to_string(),
+            body_type: "text".to_string(),
+            body_content: None,
+            auth_type: "none".to_string(),
+            auth_token: None,
+            auth_username: None,
+            auth_password: None,
+            captures: None,
+            auth_config: None,
+            serialized: false,
+            ip_family: "auto".to_string(),
+            pre_request_script: None,
+            test_script: None,
+            callback: None,
+            unresolved_variables: Some("warn".to_string()),
+            retry: None,
+            compression: None,
+            assertions: None,
+            description: None,
+            tls_versions: None,
+            ssh_tunnel_id: None,
+            latency_budget_ms: None,
+        };
+        let warn = create_test_request(&pool, &req).await;
+        req.unresolved_variables = Some("keep".to_string());
+        let keep = create_test_request(&pool, &req).await;
+        req.unresolved_variables = None;
+        let strict = create_test_request(&pool, &req).await;
+
+        let server = TestServer::new(routes(pool)).unwrap();
+        let response: ExecuteResponse = server
+            .post("/execute")
+            .json(&json!({ "request_id": warn.id }))
+            .await
+            .json();
+        assert_eq!(response.unresolved, vec!["name"]);
+
+        let response: ExecuteResponse = server
+            .post("/execute")
+            .json(&json!({ "request_id": keep.id }))
+            .await
+            .json();
+        assert!(response.unresolved.is_empty());
+        mock.assert_calls(2);
+
+        let response = server
+            .post("/execute")
+            .json(&json!({ "request_id": strict.id }))
+            .await;
+        assert!(response.text().contains("Unresolved variables: name"));
+        mock.assert_calls(2);
+    }
+
+    #[tokio::test]
+    async fn test_execute_retries() {
+        let pool = db::create_test_pool().await;
+        ensure_default_network_settings(&pool).await;
+
+        let mock_server = start_mock_server().await;
+        let mock = mock_server.mock(|when, then| {
+            when.method(httpmock::Method::GET).path("/flaky");
+            then.status(503);
+        });
+        // Nothing listens on the port of a dropped listener
+        let closed = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+
+        let mut req = CreateRequest {
+            name: "Flaky".to_string(),
+            method: "GET".to_string(),
+            url: format!("{}/flaky", mock_server.base_url()),
+            body: None,
+            headers: None,
+            folder_id: None,
+            request_type: "api".to_string(),
+            body_type: "none".to_string(),
+            body_content: None,
+            auth_type: "none".to_string(),
+            auth_token: None,
+            auth_username: None,
+            auth_password: None,
+            captures: None,
+            auth_config: None,
+            serialized: false,
+            ip_family: "auto".to_string(),
+            pre_request_script: None,
+            test_script: None,
+            callback: None,
+            unresolved_variables: None,
+            retry: Some(r#"{"max_attempts":3,"delay_ms":10}"#.to_string()),
+            compression: None,
+            assertions: None,
+            description: None,
+            tls_versions: None,
+            ssh_tunnel_id: None,
+            latency_budget_ms: None,
+        };
+        let flaky = create_test_request(&pool, &req).await;
+        req.url = format!("http://{}/down", closed);
+        let down = create_test_request(&pool, &req).await;
+        req.retry = None;
+        let once = create_test_request(&pool, &req).await;
+
+        let server = TestServer::new(routes(pool)).unwrap();
+        let response: ExecuteResponse = server
+            .post("/execute")
+            .json(&json!({ "request_id": flaky.id }))
+            .await
+            .json();
+        assert_eq!(response.status, 503);
+        mock.assert_calls(3);
+        let attempts: Vec<_> = response.attempts.iter().map(|a| a.attempt).collect();
+        assert_eq!(attempts, vec![1, 2, 3]);
+        assert!(response.attempts.iter().all(|a| a.status == Some(503)));
+
+        let response = server
+            .post("/execute")
+            .json(&json!({ "request_id": down.id }))
+            .await;
+        assert!(response.text().contains("after 3 attempts"));
+
+        let response = server
+            .post("/execute")
+            .json(&json!({ "request_id": once.id }))
+            .await;
+        assert!(!response.text().contains("attempts"));
+    }
+
+    #[tokio::test]
+    async fn test_execute_form_rows() {
+        let pool = db::create_test_pool().await;
+        ensure_default_network_settings(&pool).await;
+        let environment_db = create_test_environment(&pool, "Dev", r#"{"user":"ada"}"#).await;
+
+        let mock_server = start_mock_server().await;
+        let mock = mock_server.mock(|when, then| {
+            when.method(httpmock::Method::POST)
+                .path("/login")
+                .header("Content-Type", "application/x-www-form-urlencoded")
+                .body("user=ada&note=a%20%26%20b");
+            then.status(204);
+        });
+
+        let req = CreateRequest {
+            name: "Login".to_string(),
+            method: "POST".to_string(),
+            url: format!("{}/login", mock_server.base_url()),
+            body: None,
+            headers: None,
+            folder_id: None,
+            request_type: "api".to_string(),
+            body_type: "form".to_string(),
+            body_content: Some(
+                r#"[{"key":"user","value":"{{user}}"},{"key":"debug","value":"{{missing}}","enabled":false},{"key":"note","value":"a & b"}]"#
+                    .to_string(),
+            ),
+            auth_type: "none".to_string(),
+            auth_token: None,
+            auth_username: None,
+            auth_password: None,
+            captures: None,
+            auth_config: None,
+            serialized: false,
+            ip_family: "auto".to_string(),
+            pre_request_script: None,
+            test_script: None,
+            callback: None,
+            unresolved_variables: None,
+            retry: None,
+            compression: None,
+            assertions: None,
+            description: None,
+            tls_versions: None,
+            ssh_tunnel_id: None,
+            latency_budget_ms: None,
+        };
+        let request_db = create_test_request(&pool, &req).await;
+
+        let server = TestServer::new(routes(pool)).unwrap();
+        let response: ExecuteResponse = server
+            .post("/execute")
+            .json(&json!({ "request_id": request_db.id, "environment_id": environment_db.id }))
+            .await
+            .json();
+        assert_eq!(response.status, 204);
+        mock.assert();
+    }
+
+    #[tokio::test]
+    async fn test_execute_header_rows() {
+        let pool = db::create_test_pool().await;
+        ensure_default_network_settings(&pool).await;
+        let environment_db = create_test_environment(&pool, "Dev", r#"{"tenant":"acme"}"#).await;
+
+        let mock_server = start_mock_server().await;
+        let mock = mock_server.mock(|when, then| {
+            when.method(httpmock::Method::GET)
+                .path("/orders")
+                .header("X-Tenant", "acme")
+                .header_missing("X-Debug");
+            then.status(200);
+        });
+
+        let request_db = create_test_request(
+            &pool,
+            &CreateRequest {
+                name: "Orders".to_string(),
+                method: "GET".to_string(),
+                url: format!("{}/orders", mock_server.base_url()),
+                body: None,
+                headers: Some(
+                    r#"[{"key":"X-Tenant","value":"{{tenant}}"},{"key":"X-Debug","value":"{{missing}}","enabled":false}]"#
+                        .to_string(),
+                ),
+                folder_id: None,
+                request_type: "api".to_string(),
+                body_type: "none".to_string(),
+                body_content: None,
+                auth_type: "none".to_string(),
+                auth_token: None,
+                auth_username: None,
+                auth_password: None,
+                captures: None,
+                auth_config: None,
+                serialized: false,
+                ip_family: "auto".to_string(),
+                pre_request_script: None,
+                test_script: None,
+                callback: None,
+                unresolved_variables: None,
+                retry: None,
+                compression: None,
+                assertions: None,
+                description: None,
+                tls_versions: None,
+                ssh_tunnel_id: None,
+                latency_budget_ms: None,
+            },
+        )
+        .await;
+
+        let server = TestServer::new(routes(pool)).unwrap();
+        let response: ExecuteResponse = server
+            .post("/execute")
+            .json(&json!({ "request_id": request_db.id, "environment_id": environment_db.id }))
+            .await
+            .json();
+        assert_eq!(response.status, 200);
+        mock.assert();
+    }
+
+    #[tokio::test]
+    async fn test_execute_extension_method() {
+        let pool = db::create_test_pool().await;
+        ensure_default_network_settings(&pool).await;
+
+        let mock_server = start_mock_server().await;
+        let mock = mock_server.mock(|when, then| {
+            when.path("/files/")
+                .is_true(|req| req.method_str() == "PROPFIND");
+            then.status(207);
+        });
+
+        let server = TestServer::new(routes(pool)).unwrap();
+        let response: ExecuteResponse = server
+            .post("/execute")
+            .json(&json!({
+                "method": "PROPFIND",
+                "url": format!("{}/files/", mock_server.base_url()),
+            }))
+            .await
+            .json();
+        assert_eq!(response.status, 207);
+        mock.assert();
+    }
+
+    #[tokio::test]
+    async fn test_execute_compressed_response() {
+        use std::io::Write;
+
+        let pool = db::create_test_pool().await;
+        ensure_default_network_settings(&pool).await;
+
+        let text = "compressible ".repeat(100);
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(text.as_bytes()).unwrap();
+        let gzipped = encoder.finish().unwrap();
+
+        let mock_server = start_mock_server().await;
+        let mock = mock_server.mock(|when, then| {
+            when.path("/report")
+                .header("Accept-Encoding", "gzip, deflate, br");
+            then.status(200)
+                .header("Content-Encoding", "gzip")
+                .body(gzipped.clone());
+        });
+        let raw_mock = mock_server.mock(|when, then| {
+            when.path("/report").header_missing("Accept-Encoding");
+            then.status(200)
+                .header("Content-Encoding", "gzip")
+                .body(gzipped.clone());
+        });
+
+        let mut req = CreateRequest {
+            name: "Report".to_string(),
+            method: "GET".to_string(),
+            url: format!("{}/report", mock_server.base_url()),
+            body: None,
+            headers: None,
+            folder_id: None,
+            request_type: "api".to_string(),
+            body_type: "none".to_string(),
+            body_content: None,
+            auth_type: "none".to_string(),
+            auth_token: None,
+            auth_username: None,
+            auth_password: None,
+            captures: None,
+            auth_config: None,
+            serialized: false,
+            ip_family: "auto".to_string(),
+            pre_request_script: None,
+            test_script: None,
+            callback: None,
+            unresolved_variables: None,
+            retry: None,
+            compression: None,
+            assertions: None,
+            description: None,
+            tls_versions: None,
+            ssh_tunnel_id: None,
+            latency_budget_ms: None,
+        };
+        let decoded = create_test_request(&pool, &req).await;
+        req.compression = Some(r#"{"decompress":false}"#.to_string());
+        let raw = create_test_request(&pool, &req).await;
+
+        let server = TestServer::new(routes(pool.clone())).unwrap();
+        let response: ExecuteResponse = server
+            .post("/execute")
+            .json(&json!({ "request_id": decoded.id }))
+            .await
+            .json();
+        assert_eq!(response.body, text);
+        assert_eq!(response.raw_size, gzipped.len());
+        assert_eq!(response.decoded_size, text.len());
+        mock.assert();
+        // History keeps the decoded body for download
+        let (stored,): (Vec<u8>,) =
+            sqlx::query_as("SELECT response_body FROM execution_history WHERE id = ?")
+                .bind(response.history_id.unwrap())
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+        assert_eq!(stored, text.as_bytes());
+
+        let response: ExecuteResponse = server
+            .post("/execute")
+            .json(&json!({ "request_id": raw.id }))
+            .await
+            .json();
+        assert_eq!(response.raw_size, gzipped.len());
+        assert_eq!(response.decoded_size, gzipped.len());
+        assert_eq!(response.headers["content-encoding"], "gzip");
+        raw_mock.assert();
+    }
+
+    #[test]
+    fn test_client_key() {
+        let settings = NetworkSettingsDb::defaults();
+        let tls = TlsVersions::default();
+        let key = client_key(&settings, None, IpFamily::Auto, None, tls);
+        assert_eq!(key, client_key(&settings, None, IpFamily::Auto, None, tls));
+        assert_ne!(
+            key,
+            client_key(
+                &settings,
+                Some(&PacDecision::Direct),
+                IpFamily::Auto,
+                None,
+                tls
+            )
+        );
+        assert_ne!(key, client_key(&settings, None, IpFamily::V4, None, tls));
+        let strict = TlsVersions {
+            min: Some(TlsVersion::Tls1_3),
+            max: None,
+        };
+        assert_ne!(
+            key,
+            client_key(&settings, None, IpFamily::Auto, None, strict)
+        );
+
+        let manual = NetworkSettingsDb {
+            auto_proxy: false,
+            http_proxy: Some("proxy.corp:3128".to_string()),
+            ..NetworkSettingsDb::defaults()
+        };
+        assert_ne!(key, client_key(&manual, None, IpFamily::Auto, None, tls));
+
+        let fixed = IpFamily::Fixed("10.0.0.7".parse().unwrap());
+        assert_ne!(
+            client_key(&settings, None, fixed, Some("a.test"), tls),
+            client_key(&settings, None, fixed, Some("b.test"), tls)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_load_variables_precedence() {
+        let pool = db::create_test_pool().await;
+        sqlx::query!(
+            "INSERT INTO global_variables (name, value) VALUES ('base_url', 'https://global'), ('auth', 'Basic abc')"
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        let environment_db =
+            create_test_environment(&pool, "Dev", r#"{"base_url":"https://dev"}"#).await;
+
+        let variables = load_environment_variables(&pool, None).await.unwrap();
+        assert_eq!(variables["base_url"], "https://global");
+
+        let variables = load_environment_variables(&pool, Some(environment_db.id))
+            .await
+            .unwrap();
+        assert_eq!(variables["base_url"], "https://dev");
+        assert_eq!(variables["auth"], "Basic abc");
+
+        // Folder variables sit between the globals and the environment
+        let folder_id = sqlx::query_scalar!(
+            r#"INSERT INTO folders (name, variables) VALUES ('Billing', '{"auth":"Basic folder","base_url":"https://folder"}') RETURNING id"#
+        )
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+        let variables = load_variables(&pool, Some(folder_id), None).await.unwrap();
+        assert_eq!(variables["base_url"], "https://folder");
+        let variables = load_variables(&pool, Some(folder_id), Some(environment_db.id))
+            .await
+            .unwrap();
+        assert_eq!(variables["base_url"], "https://dev");
+        assert_eq!(variables["auth"], "Basic folder");
+
+        // A disabled environment variable falls back to the folder's
+        sqlx::query!(
+            "UPDATE environments SET disabled = '[\"base_url\"]' WHERE id = ?",
+            environment_db.id
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        let variables = load_variables(&pool, Some(folder_id), Some(environment_db.id))
+            .await
+            .unwrap();
+        assert_eq!(variables["base_url"], "https://folder");
+
+        // Variables inherited from a parent sit beneath the environment's own
+        let child_db = create_test_environment(&pool, "Dev EU", r#"{"region":"eu"}"#).await;
+        sqlx::query!(
+            "UPDATE environments SET parent_id = ? WHERE id = ?",
+            environment_db.id,
+            child_db.id
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        sqlx::query!(
+            r#"UPDATE environments SET variables = '{"base_url":"https://dev","region":"us"}', disabled = '[]' WHERE id = ?"#,
+            environment_db.id
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        let variables = load_environment_variables(&pool, Some(child_db.id))
+            .await
+            .unwrap();
+        assert_eq!(variables["base_url"], "https://dev");
+        assert_eq!(variables["region"], "eu");
+    }
+
+    #[tokio::test]
+    async fn test_execute_checks_variable_types() {
+        let pool = db::create_test_pool().await;
+        ensure_default_network_settings(&pool).await;
+        let mock_server = start_mock_server().await;
+        let mock = mock_server.mock(|when, then| {
+            when.method(httpmock::Method::GET).path("/health");
+            then.status(200);
+        });
+
+        // Captures and scripts store values without checking them
+        let environment_db = create_test_environment(
+            &pool,
+            "Dev",
+            r#"{"path":"health","port":"80a","debug":"yes"}"#,
+        )
+        .await;
+        sqlx::query(
+            r#"UPDATE environments SET types = '{"port":{"type":"number"},"debug":{"type":"boolean"}}' WHERE id = ?"#,
+        )
+        .bind(environment_db.id)
+        .execute(&pool)
+        .await
+        .unwrap();
+        let server = TestServer::new(routes(pool.clone())).unwrap();
+        let send = |url: String| {
+            server
+                .post("/execute")
+                .json(&json!({ "method": "GET", "url": url, "environment_id": environment_db.id }))
+        };
+
+        let response = send(format!(
+            "{}/{{{{path}}}}?port={{{{port}}}}",
+            mock_server.base_url()
+        ))
+        .await;
+        response.assert_status(StatusCode::BAD_REQUEST);
+        assert!(response.text().contains("Variable 'port' must be a number"));
+        mock.assert_calls(0);
+
+        // Invalid variables the request does not use are no obstacle
+        send(format!("{}/{{{{path}}}}", mock_server.base_url()))
+            .await
+            .assert_status_ok();
+        mock.assert_calls(1);
+    }
+
+    #[tokio::test]
+    async fn test_execute_request_captures_into_environment() {
+        let pool = db::create_test_pool().await;
+        ensure_default_network_settings(&pool).await;
+
+        let mock_server = start_mock_server().await;
+        let _mock = mock_server.mock(|when, then| {
+            when.method(httpmock::Method::POST).path("/users");
+            then.status(201)
+                .json_body(json!({ "id": 42, "name": "Ada" }));
+        });
+
+        let req = CreateRequest {
+            name: "Create user".to_string(),
+            method: "POST".to_string(),
+            url: format!("{}/users", mock_server.base_url()),
+            body: None,
+            headers: None,
+            folder_id: None,
+            request_type: "api".to_string(),
+            body_type: "none".to_string(),
+            body_content: None,
+            auth_type: "none".to_string(),
+            auth_token: None,
+            auth_username: None,
+            auth_password: None,
+            captures: Some(
+                json!([{ "variable": "user_id", "source": "body", "expression": "$.id" }])
+                    .to_string(),
+            ),
+            auth_config: None,
+            serialized: false,
+            ip_family: "auto".to_string(),
+            pre_request_script: None,
+            test_script: None,
+            callback: None,
+            unresolved_variables: None,
+            retry: None,
+            compression: None,
+            assertions: None,
+            description: None,
+            tls_versions: None,
+            ssh_tunnel_id: None,
+            latency_budget_ms: None,
+        };
+        let request_db = create_test_request(&pool, &req).await;
+        let environment_db = create_test_environment(&pool, "Dev", r#"{"other":"x"}"#).await;
+        sqlx::query!(
+            "UPDATE environments SET secrets = '[\"user_id\"]' WHERE id = ?",
+            environment_db.id
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+
+        let server = TestServer::new(routes(pool.clone())).unwrap();
+        let response = server
+            .post("/execute")
+            .json(&json!({ "request_id": request_db.id, "environment_id": environment_db.id }))
+            .await;
+
+        response.assert_status(StatusCode::OK);
+        let exec_response: ExecuteResponse = response.json();
+        assert_eq!(exec_response.captured["user_id"], "42");
+
+        let variables = load_environment_variables(&pool, Some(environment_db.id))
+            .await
+            .unwrap();
+        assert_eq!(variables["user_id"], "42");
+        assert_eq!(variables["other"], "x");
+
+        // Captured into a secret variable, so stored encrypted
+        let stored = sqlx::query_scalar!(
+            "SELECT variables FROM environments WHERE id = ?",
+            environment_db.id
+        )
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+        let stored: serde_json::Value = serde_json::from_str(&stored).unwrap();
+        assert!(crate::crypto::is_encrypted(
+            stored["user_id"].as_str().unwrap()
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_execute_request_with_undecryptable_credentials() {
+        let pool = db::create_test_pool().await;
+        ensure_default_network_settings(&pool).await;
+
+        let mock_server = start_mock_server().await;
+        let mock = mock_server.mock(|when, then| {
+            when.path("/orders");
+            then.status(200);
+        });
+        let request_id: i64 = sqlx::query_scalar(
+            "INSERT INTO requests (name, method, url, auth_type, auth_token) VALUES ('Orders', 'GET', ?, 'bearer', 'enc:v1:AAAA') RETURNING id",
+        )
+        .bind(format!("{}/orders", mock_server.base_url()))
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+
+        let server = TestServer::new(routes(pool)).unwrap();
+        let response = server
+            .post("/execute")
+            .json(&json!({ "request_id": request_id }))
+            .await;
+        response.assert_status(StatusCode::BAD_REQUEST);
+        assert!(response.text().contains("'Orders'"));
+        mock.assert_calls(0);
+    }
+
+    #[tokio::test]
+    async fn test_execute_request_with_chained_auth() {
+        let pool = db::create_test_pool().await;
+        ensure_default_network_settings(&pool).await;
+
+        let mock_server = start_mock_server().await;
+        let login_mock = mock_server.mock(|when, then| {
+            when.method(httpmock::Method::POST).path("/login");
+            then.status(200)
+                .json_body(json!({ "session": { "token": "abc" } }));
+        });
+        let data_mock = mock_server.mock(|when, then| {
+            when.method(httpmock::Method::GET)
+                .path("/data")
+                .header("Authorization", "Token abc");
+            then.status(200).body("ok");
+        });
+
+        let login_url = format!("{}/login", mock_server.base_url());
+        let login_id = sqlx::query_scalar!(
+            "INSERT INTO requests (name, method, url) VALUES ('Login', 'POST', ?) RETURNING id",
+            login_url
+        )
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+        let data_url = format!("{}/data", mock_server.base_url());
+        let auth_config = json!({
+            "request_id": login_id,
+            "token_path": "$.session.token",
+            "scheme": "Token"
+        })
+        .to_string();
+        let data_id = sqlx::query_scalar!(
+            "INSERT INTO requests (name, method, url, auth_type, auth_config) VALUES ('Data', 'GET', ?, 'chained', ?) RETURNING id",
+            data_url,
+            auth_config
+        )
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+
+        let server = TestServer::new(routes(pool.clone())).unwrap();
+        for _ in 0..2 {
+            let response = server
+                .post("/execute")
+                .json(&json!({ "request_id": data_id }))
+                .await;
+            response.assert_status(StatusCode::OK);
+            assert_eq!(response.json::<ExecuteResponse>().body, "ok");
+        }
+        login_mock.assert_calls(1);
+        data_mock.assert_calls(2);
+
+        // A token request that needs its own token is rejected before sending
+        let looping_config = json!({ "request_id": data_id, "ttl": 0 }).to_string();
+        sqlx::query!(
+            "UPDATE requests SET auth_type = 'chained', auth_config = ? WHERE id = ?",
+            looping_config,
+            login_id
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        let response = server
+            .post("/execute")
+            .json(&json!({ "request_id": login_id }))
+            .await;
+        response.assert_status(StatusCode::BAD_REQUEST);
+        assert!(response.text().contains("loops back"));
+        login_mock.assert_calls(1);
+    }
+
+    #[tokio::test]
+    async fn test_execute_request_with_ip_family() {
+        let pool = db::create_test_pool().await;
+        ensure_default_network_settings(&pool).await;
+
+        let mock_server = start_mock_server().await;
+        let _mock = mock_server.mock(|when, then| {
+            when.method(httpmock::Method::GET).path("/ip");
+            then.status(200).body("ok");
+        });
+        // httpmock listens on 127.0.0.1 only
+        let port = mock_server.port();
+        let server = TestServer::new(routes(pool)).unwrap();
+
+        let response = server
+            .post("/execute")
+            .json(&json!({
+                "url": format!("http://localhost:{}/ip", port),
+                "method": "GET",
+                "ip_family": "ipv4"
+            }))
+            .await;
+        response.assert_status(StatusCode::OK);
+        let exec_response: ExecuteResponse = response.json();
+        assert_eq!(
+            exec_response.remote_addr,
+            Some(format!("127.0.0.1:{}", port))
+        );
+
+        // Pinned to an address, the host name is never resolved
+        let response = server
+            .post("/execute")
+            .json(&json!({
+                "url": format!("http://api.example.invalid:{}/ip", port),
+                "method": "GET",
+                "ip_family": "127.0.0.1"
+            }))
+            .await;
+        response.assert_status(StatusCode::OK);
+
+        let response = server
+            .post("/execute")
+            .json(&json!({
+                "url": format!("http://localhost:{}/ip", port),
+                "method": "GET",
+                "ip_family": "ipv6"
+            }))
+            .await;
+        response.assert_status(StatusCode::BAD_GATEWAY);
+
+        let response = server
+            .post("/execute")
+            .json(&json!({
+                "url": format!("http://localhost:{}/ip", port),
+                "method": "GET",
+                "ip_family": "ipv5"
+            }))
+            .await;
+        response.assert_status(StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_execute_request_with_pre_request_script() {
+        let pool = db::create_test_pool().await;
+        ensure_default_network_settings(&pool).await;
+
+        let mock_server = start_mock_server().await;
+        let mock = mock_server.mock(|when, then| {
+            when.method(httpmock::Method::GET)
+                .path("/orders/7")
+                .header("X-Signature", "abc123-signed");
+            then.status(200).body("ok");
+        });
+
+        let url = format!("{}/orders/{{{{order_id}}}}", mock_server.base_url());
+        let script = r#"
+            if (!jsl.environment.get('signing_key')) jsl.abort('No signing key');
+            jsl.environment.set('order_id', 7);
+            jsl.request.setHeader('X-Signature', jsl.environment.get('signing_key') + '-signed');
+            console.log('signed order', jsl.environment.get('order_id'));
+        "#;
+        let request_id = sqlx::query_scalar!(
+            "INSERT INTO requests (name, method, url, pre_request_script) VALUES ('Order', 'GET', ?, ?) RETURNING id",
+            url,
+            script
+        )
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+        let environment_db =
+            create_test_environment(&pool, "Dev", r#"{"signing_key":"abc123"}"#).await;
+
+        let server = TestServer::new(routes(pool.clone())).unwrap();
+        let response = server
+            .post("/execute")
+            .json(&json!({ "request_id": request_id, "environment_id": environment_db.id }))
+            .await;
+        response.assert_status(StatusCode::OK);
+        let exec_response: ExecuteResponse = response.json();
+        assert_eq!(exec_response.script_logs, vec!["signed order 7"]);
+        mock.assert();
+
+        let variables = load_environment_variables(&pool, Some(environment_db.id))
+            .await
+            .unwrap();
+        assert_eq!(variables["order_id"], "7");
+
+        let response = server
+            .post("/execute")
+            .json(&json!({ "request_id": request_id }))
+            .await;
+        response.assert_status(StatusCode::BAD_REQUEST);
+        response.assert_text("Aborted by pre-request script: No signing key");
+    }
+
+    #[tokio::test]
+    async fn test_dry_run() {
+        let pool = db::create_test_pool().await;
+        ensure_default_network_settings(&pool).await;
+
+        let mock_server = start_mock_server().await;
+        let mock = mock_server.mock(|when, then| {
+            when.path("/orders/7");
+            then.status(200);
+        });
+
+        let url = format!("{}/orders/{{{{order_id}}}}", mock_server.base_url());
+        let script = r#"
+            jsl.environment.set('order_id', 7);
+            jsl.request.setHeader('X-Signature', jsl.environment.get('signing_key') + '-signed');
+        "#;
+        let request_id = sqlx::query_scalar!(
+            "INSERT INTO requests (name, method, url, body, auth_type, auth_token, pre_request_script) VALUES ('Order', 'POST', ?, '{\"key\":\"{{signing_key}}\"}', 'bearer', '{{signing_key}}', ?) RETURNING id",
+            url,
+            script
+        )
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+        let environment_db =
+            create_test_environment(&pool, "Dev", r#"{"signing_key":"abc123"}"#).await;
+
+        let server = TestServer::new(routes(pool.clone())).unwrap();
+        let response = server
+            .post("/execute-direct")
+            .json(&json!({ "request_id": request_id, "environment_id": environment_db.id }))
+            .await;
+        response.assert_status(StatusCode::OK);
+        let dry_run: DryRunResponse = response.json();
+        assert_eq!(dry_run.method, "POST");
+        assert_eq!(dry_run.url, format!("{}/orders/7", mock_server.base_url()));
+        assert_eq!(dry_run.headers["authorization"], "Bearer abc123");
+        assert_eq!(dry_run.headers["x-signature"], "abc123-signed");
+        assert_eq!(dry_run.headers["accept-encoding"], "gzip, deflate, br");
+        assert_eq!(dry_run.body.as_deref(), Some(r#"{"key":"abc123"}"#));
+        mock.assert_calls(0);
+
+        // Script changes are not stored, and nothing reaches the history
+        let variables = load_environment_variables(&pool, Some(environment_db.id))
+            .await
+            .unwrap();
+        assert!(!variables.contains_key("order_id"));
+        let (executions,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM execution_history")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(executions, 0);
+    }
+
+    #[tokio::test]
+    async fn test_dry_run_does_not_fetch_tokens() {
+        let pool = db::create_test_pool().await;
+        ensure_default_network_settings(&pool).await;
+
+        let mock_server = start_mock_server().await;
+        let login_mock = mock_server.mock(|when, then| {
+            when.path("/login");
+            then.status(200).json_body(json!({ "access_token": "abc" }));
+        });
+        let token_mock = mock_server.mock(|when, then| {
+            when.path("/token");
+            then.status(200)
+                .json_body(json!({ "access_token": "xyz", "token_type": "Bearer" }));
+        });
+
+        // A distinct id, as chained tokens are cached by request id
+        let login_url = format!("{}/login", mock_server.base_url());
+        sqlx::query(
+            "INSERT INTO requests (id, name, method, url) VALUES (9601, 'Login', 'POST', ?)",
+        )
+        .bind(&login_url)
+        .execute(&pool)
+        .await
+        .unwrap();
+        let data_url = format!("{}/data", mock_server.base_url());
+        let (chained_id,): (i64,) = sqlx::query_as(
+            "INSERT INTO requests (name, method, url, auth_type, auth_config) VALUES ('Data', 'GET', ?, 'chained', '{\"request_id\":9601}') RETURNING id",
+        )
+        .bind(&data_url)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+        let (oauth_id,): (i64,) = sqlx::query_as(
+            "INSERT INTO requests (name, method, url, auth_type) VALUES ('Report', 'GET', ?, 'oauth2') RETURNING id",
+        )
+        .bind(&data_url)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+        sqlx::query("INSERT INTO oauth_configs (request_id, grant_type, client_id, token_url) VALUES (?, 'client_credentials', 'cli', ?)")
+            .bind(oauth_id)
+            .bind(format!("{}/token", mock_server.base_url()))
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let server = TestServer::new(routes(pool)).unwrap();
+        for request_id in [chained_id, oauth_id] {
+            let dry_run: DryRunResponse = server
+                .post("/execute-direct")
+                .json(&json!({ "request_id": request_id }))
+                .await
+                .json();
+            assert_eq!(
+                dry_run.headers["authorization"],
+                format!("Bearer {}", TOKEN_PLACEHOLDER)
+            );
+            assert!(dry_run.pending_auth.is_some());
+        }
+        login_mock.assert_calls(0);
+        token_mock.assert_calls(0);
+    }
+
+    #[tokio::test]
+    async fn test_execute_assertions() {
+        let pool = db::create_test_pool().await;
+        ensure_default_network_settings(&pool).await;
+
+        let mock_server = start_mock_server().await;
+        mock_server.mock(|when, then| {
+            when.path("/users/1");
+            then.status(200)
+                .header("Content-Type", "application/json")
+                .json_body(json!({ "id": 1, "name": "Ada" }));
+        });
+
+        let url = format!("{}/users/1", mock_server.base_url());
+        let assertions = r#"[
+            {"type": "status", "equals": 200},
+            {"type": "json_path", "path": "$.name", "equals": "Grace"},
+            {"type": "json_schema", "schema": {"required": ["id", "name"]}}
+        ]"#;
+        let request_id = sqlx::query_scalar!(
+            "INSERT INTO requests (name, method, url, assertions) VALUES ('User', 'GET', ?, ?) RETURNING id",
+            url,
+            assertions
+        )
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+
+        let server = TestServer::new(routes(pool)).unwrap();
+        let response: ExecuteResponse = server
+            .post("/execute")
+            .json(&json!({ "request_id": request_id }))
+            .await
+            .json();
+        let passed: Vec<bool> = response
+            .assertion_results
+            .iter()
+            .map(|r| r.passed)
+            .collect();
+        assert_eq!(passed, [true, false, true]);
+        assert_eq!(
+            response.assertion_results[1].error.as_deref(),
+            Some("Got \"Ada\"")
+        );
+        assert!(response.test_results.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_execute_request_with_test_script() {
+        let pool = db::create_test_pool().await;
+        ensure_default_network_settings(&pool).await;
+
+        let mock_server = start_mock_server().await;
+        mock_server.mock(|when, then| {
+            when.method(httpmock::Method::GET).path("/users/1");
+            then.status(200)
+                .header("Content-Type", "application/json")
+                .json_body(json!({ "id": 1, "name": "Ada" }));
+        });
+
+        let url = format!("{}/users/1", mock_server.base_url());
+        let script = r#"
+            jsl.test('status is 200', () => jsl.expect(jsl.response.status).toBe(200));
+            jsl.test('returns the user', () => {
+                jsl.expect(jsl.response.header('content-type')).toContain('json');
+                jsl.expect(jsl.response.json()).toHaveProperty('name');
+                jsl.environment.set('user_name', jsl.response.json().name);
+            });
+            jsl.test('is an admin', () => jsl.expect(jsl.response.json().admin).toBe(true));
+        "#;
+        let request_id = sqlx::query_scalar!(
+            "INSERT INTO requests (name, method, url, test_script) VALUES ('User', 'GET', ?, ?) RETURNING id",
+            url,
+            script
+        )
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+        let environment_db = create_test_environment(&pool, "Dev", "{}").await;
+
+        let server = TestServer::new(routes(pool.clone())).unwrap();
+        let response = server
+            .post("/execute")
+            .json(&json!({ "request_id": request_id, "environment_id": environment_db.id }))
+            .await;
+        response.assert_status(StatusCode::OK);
+        let exec_response: ExecuteResponse = response.json();
+        let passed: Vec<bool> = exec_response
+            .test_results
+            .iter()
+            .map(|r| r.passed)
+            .collect();
+        assert_eq!(passed, vec![true, true, false]);
+        assert!(exec_response.test_results[2].error.is_some());
+
+        let variables = load_environment_variables(&pool, Some(environment_db.id))
+            .await
+            .unwrap();
+        assert_eq!(variables["user_name"], "Ada");
+
+        let stored = sqlx::query_scalar!(
+            "SELECT test_results FROM execution_history WHERE request_id = ?",
+            request_id
+        )
+        .fetch_one(&pool)
+        .await
+        .unwrap()
+        .unwrap();
+        let stored: Vec<TestResult> = serde_json::from_str(&stored).unwrap();
+        assert_eq!(stored.len(), 3);
+        assert_eq!(stored[0].name, "status is 200");
+    }
+
+    #[tokio::test]
+    async fn test_execute_request_with_callback() {
+        let pool = db::create_test_pool().await;
+        ensure_default_network_settings(&pool).await;
+
+        let mock_server = start_mock_server().await;
+        mock_server.mock(|when, then| {
+            when.method(httpmock::Method::POST).path("/exports");
+            then.status(202)
+                .json_body(json!({ "job": { "id": "exp-42" } }));
+        });
+
+        let url = format!("{}/exports", mock_server.base_url());
+        let callback =
+            r#"{"bin": "exports", "id_path": "$.job.id", "match_path": "$.id", "timeout": 5}"#;
+        let request_id = sqlx::query_scalar!(
+            "INSERT INTO requests (name, method, url, callback) VALUES ('Export', 'POST', ?, ?) RETURNING id",
+            url,
+            callback
+        )
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+
+        let deliver = {
+            let pool = pool.clone();
+            tokio::spawn(async move {
+                tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+                webhooks::store(
+                    &pool,
+                    "exports",
+                    "POST",
+                    &HashMap::new(),
+                    r#"{"id": "exp-42", "file": "export.csv"}"#,
+                )
+                .await
+                .unwrap();
+            })
+        };
+
+        let server = TestServer::new(routes(pool.clone())).unwrap();
+        let response = server
+            .post("/execute")
+            .json(&json!({ "request_id": request_id }))
+            .await;
+        response.assert_status(StatusCode::OK);
+        deliver.await.unwrap();
+
+        let exec_response: ExecuteResponse = response.json();
+        assert_eq!(exec_response.status, 202);
+        let callback = exec_response.callback.unwrap();
+        assert_eq!(callback.correlation_id.as_deref(), Some("exp-42"));
+        assert!(callback.delivery.unwrap().body.contains("export.csv"));
+    }
+
+    #[tokio::test]
+    async fn test_host_slot_is_freed_once_the_response_is_read() {
+        let pool = db::create_test_pool().await;
+        ensure_default_network_settings(&pool).await;
+        sqlx::query("INSERT INTO app_settings (name, value) VALUES ('host_concurrency', '{\"hosts\":{\"localhost\":1}}')")
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let mock_server = start_mock_server().await;
+        mock_server.mock(|when, then| {
+            when.path("/exports");
+            then.status(202).json_body(json!({ "id": "exp-7" }));
+        });
+        mock_server.mock(|when, then| {
+            when.path("/ping");
+            then.status(200);
+        });
+        let base = format!("http://localhost:{}", mock_server.port());
+        let callback = r#"{"bin": "slots", "id_path": "$.id", "timeout": 10}"#;
+        let (export_id,): (i64,) = sqlx::query_as(
+            "INSERT INTO requests (name, method, url, callback) VALUES ('Export', 'POST', ?, ?) RETURNING id",
+        )
+        .bind(format!("{}/exports", base))
+        .bind(callback)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+        let (ping_id,): (i64,) = sqlx::query_as(
+            "INSERT INTO requests (name, method, url) VALUES ('Ping', 'GET', ?) RETURNING id",
+        )
+        .bind(format!("{}/ping", base))
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+
+        // The export waits for its callback after its response was read
+        let export = tokio::spawn(execute(
+            pool.clone(),
+            ExecuteRequestPayload::saved(export_id, None),
+        ));
+        tokio::time::sleep(std::time::Duration::from_millis(300)).await;
+        let ping = tokio::time::timeout(
+            std::time::Duration::from_secs(3),
+            execute(pool.clone(), ExecuteRequestPayload::saved(ping_id, None)),
+        )
+        .await
+        .expect("the waiting export still holds the host's slot")
+        .unwrap();
+        assert_eq!(ping.status, 200);
+
+        webhooks::store(
+            &pool,
+            "slots",
+            "POST",
+            &HashMap::new(),
+            r#"{"id": "exp-7"}"#,
+        )
+        .await
+        .unwrap();
+        let export = export.await.unwrap().unwrap();
+        assert!(export.callback.unwrap().delivery.is_some());
+    }
+
     // #[tokio::test]
     // async fn test_execute_request_handler_with_variables() {
     //     let pool = db::create_test_pool().await;
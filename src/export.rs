@@ -0,0 +1,498 @@
+use async_zip::{tokio::write::ZipFileWriter, Compression, ZipEntryBuilder};
+use axum::{
+    body::Body,
+    extract::{Path, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    routing::get,
+    Json, Router,
+};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use serde_json::json;
+use std::collections::BTreeMap;
+use std::fmt;
+use tokio::io::{AsyncWrite, DuplexStream};
+use tokio_util::io::ReaderStream;
+
+use crate::{
+    db::{self, DbPool, MigrationState},
+    environments::{Environment, EnvironmentDb},
+    folders::{Folder, FolderDb},
+    globals, i18n,
+    network::{self, NetworkSettings},
+    requests::{Request, RequestDb},
+};
+
+/// Identifies a workspace bundle in its manifest, see restore.
+pub const BUNDLE_FORMAT: &str = "js-link-bundle";
+/// Bumped whenever the layout of the bundle changes.
+pub const BUNDLE_FORMAT_VERSION: u32 = 2;
+// Size of the in-memory pipe between the zip writer and the response body
+const PIPE_CAPACITY: usize = 64 * 1024;
+
+type BundleWriter = ZipFileWriter<DuplexStream>;
+
+#[derive(Debug)]
+pub enum ExportError {
+    EnvironmentNotFound,
+    DatabaseError(sqlx::Error),
+    ArchiveError(String),
+}
+
+impl fmt::Display for ExportError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ExportError::EnvironmentNotFound => write!(f, "Environment not found"),
+            ExportError::DatabaseError(e) => write!(f, "Database error: {}", e),
+            ExportError::ArchiveError(msg) => write!(f, "Archive error: {}", msg),
+        }
+    }
+}
+
+impl From<sqlx::Error> for ExportError {
+    fn from(e: sqlx::Error) -> Self {
+        ExportError::DatabaseError(e)
+    }
+}
+
+impl From<async_zip::error::ZipError> for ExportError {
+    fn from(e: async_zip::error::ZipError) -> Self {
+        ExportError::ArchiveError(e.to_string())
+    }
+}
+
+impl From<serde_json::Error> for ExportError {
+    fn from(e: serde_json::Error) -> Self {
+        ExportError::ArchiveError(e.to_string())
+    }
+}
+
+impl IntoResponse for ExportError {
+    fn into_response(self) -> Response {
+        match self {
+            ExportError::EnvironmentNotFound => (
+                StatusCode::NOT_FOUND,
+                i18n::t("error.environment_not_found"),
+            )
+                .into_response(),
+            ExportError::DatabaseError(_) => {
+                (StatusCode::INTERNAL_SERVER_ERROR, i18n::t("error.database")).into_response()
+            }
+            ExportError::ArchiveError(msg) => {
+                (StatusCode::INTERNAL_SERVER_ERROR, msg).into_response()
+            }
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct Collection {
+    folder: Option<Folder>,
+    requests: Vec<Request>,
+}
+
+/// Environment file in the format Postman imports, see
+/// importers::parse_postman_environment for the reverse.
+#[derive(Serialize)]
+struct PostmanEnvironment {
+    name: String,
+    values: Vec<PostmanVariable>,
+    #[serde(rename = "_postman_variable_scope")]
+    scope: &'static str,
+    #[serde(rename = "_postman_exported_at")]
+    exported_at: DateTime<Utc>,
+    #[serde(rename = "_postman_exported_using")]
+    exported_using: String,
+}
+
+#[derive(Serialize)]
+struct PostmanVariable {
+    key: String,
+    value: String,
+    #[serde(rename = "type")]
+    kind: &'static str,
+    enabled: bool,
+}
+
+pub fn routes(pool: DbPool) -> Router {
+    Router::new()
+        .route("/export/bundle.zip", get(export_bundle))
+        .route(
+            "/export/environments/:id/postman",
+            get(export_postman_environment),
+        )
+        .with_state(pool)
+}
+
+/// Streams the whole workspace as a zip. Entries are written one at a time
+/// into a bounded pipe, so only the current entry is held in memory.
+async fn export_bundle(State(pool): State<DbPool>) -> impl IntoResponse {
+    log::info!("Exporting workspace bundle");
+
+    let (writer, reader) = tokio::io::duplex(PIPE_CAPACITY);
+    tokio::spawn(async move {
+        let zip = ZipFileWriter::with_tokio(writer);
+        match write_bundle(&pool, zip).await {
+            Ok(()) => log::info!("Workspace bundle exported"),
+            // The response is already underway, so the client sees a truncated archive
+            Err(e) => log::error!("Workspace bundle export failed: {}", e),
+        }
+    });
+
+    let filename = format!("js-link-bundle-{}.zip", Utc::now().format("%Y%m%d-%H%M%S"));
+    (
+        [
+            (header::CONTENT_TYPE, "application/zip".to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{}\"", filename),
+            ),
+        ],
+        Body::from_stream(ReaderStream::new(reader)),
+    )
+}
+
+/// One environment as a Postman environment file. Only its own variables
+/// are exported, not inherited ones, and secret values are left empty.
+async fn export_postman_environment(
+    State(pool): State<DbPool>,
+    Path(id): Path<i64>,
+) -> Result<impl IntoResponse, ExportError> {
+    let environment = sqlx::query_as!(
+        EnvironmentDb,
+        "SELECT id, name, variables, secrets, disabled, types, parent_id, created_at, updated_at, archived_at FROM environments WHERE id = ?",
+        id
+    )
+    .fetch_optional(&pool)
+    .await?
+    .ok_or(ExportError::EnvironmentNotFound)?;
+    let environment = Environment::masked(environment, "");
+
+    let variables: serde_json::Map<String, serde_json::Value> =
+        serde_json::from_str(&environment.variables).unwrap_or_default();
+    let values = variables
+        .into_iter()
+        .map(|(key, value)| PostmanVariable {
+            kind: if environment.secrets.contains(&key) {
+                "secret"
+            } else {
+                "default"
+            },
+            enabled: !environment.disabled.contains(&key),
+            value: match value {
+                serde_json::Value::String(s) => s,
+                other => other.to_string(),
+            },
+            key,
+        })
+        .collect();
+
+    log::info!("Exporting environment {} for Postman", id);
+    let filename = format!("{}.postman_environment.json", slug(&environment.name));
+    Ok((
+        [(
+            header::CONTENT_DISPOSITION,
+            format!("attachment; filename=\"{}\"", filename),
+        )],
+        Json(PostmanEnvironment {
+            name: environment.name,
+            values,
+            scope: "environment",
+            exported_at: Utc::now(),
+            exported_using: format!("js-link/{}", env!("CARGO_PKG_VERSION")),
+        }),
+    ))
+}
+
+/// A request for the bundle, with its credentials left out like the secret
+/// variables of environments.
+fn bundle_request(request: RequestDb) -> Request {
+    Request {
+        auth_token: None,
+        auth_username: None,
+        auth_password: None,
+        ..Request::from(request)
+    }
+}
+
+async fn write_bundle(pool: &DbPool, mut zip: BundleWriter) -> Result<(), ExportError> {
+    write_manifest(pool, &mut zip).await?;
+
+    let folders = sqlx::query_as!(
+        FolderDb,
+        "SELECT id, name, variables, description, base_url, created_at, updated_at, archived_at FROM folders ORDER BY id"
+    )
+    .fetch_all(pool)
+    .await?;
+    for folder in folders {
+        let requests = sqlx::query_as!(
+            RequestDb,
+            "SELECT id, name, method, url, body, headers, folder_id, request_type, body_type, body_content, auth_type, auth_token, auth_username, auth_password, captures, auth_config, serialized, ip_family, pre_request_script, test_script, callback, unresolved_variables, retry, compression, assertions, description, tls_versions, ssh_tunnel_id, latency_budget_ms, created_at, updated_at, archived_at FROM requests WHERE folder_id = ? ORDER BY id",
+            folder.id
+        )
+        .fetch_all(pool)
+        .await?;
+        let path = format!("collections/{}-{}.json", folder.id, slug(&folder.name));
+        let collection = Collection {
+            folder: Some(Folder::from(folder)),
+            requests: requests.into_iter().map(bundle_request).collect(),
+        };
+        write_json(&mut zip, &path, &collection).await?;
+    }
+
+    let unfiled = sqlx::query_as!(
+        RequestDb,
+        "SELECT id, name, method, url, body, headers, folder_id, request_type, body_type, body_content, auth_type, auth_token, auth_username, auth_password, captures, auth_config, serialized, ip_family, pre_request_script, test_script, callback, unresolved_variables, retry, compression, assertions, description, tls_versions, ssh_tunnel_id, latency_budget_ms, created_at, updated_at, archived_at FROM requests WHERE folder_id IS NULL ORDER BY id"
+    )
+    .fetch_all(pool)
+    .await?;
+    if !unfiled.is_empty() {
+        let collection = Collection {
+            folder: None,
+            requests: unfiled.into_iter().map(bundle_request).collect(),
+        };
+        write_json(&mut zip, "collections/unfiled.json", &collection).await?;
+    }
+
+    let environments = sqlx::query_as!(
+        EnvironmentDb,
+        "SELECT id, name, variables, secrets, disabled, types, parent_id, created_at, updated_at, archived_at FROM environments ORDER BY id"
+    )
+    .fetch_all(pool)
+    .await?;
+    // Secret variable values are left empty
+    for environment in environments {
+        let path = format!(
+            "environments/{}-{}.json",
+            environment.id,
+            slug(&environment.name)
+        );
+        write_json(&mut zip, &path, &Environment::masked(environment, "")).await?;
+    }
+
+    let globals: BTreeMap<String, String> = globals::load(pool).await?.into_iter().collect();
+    write_json(&mut zip, "globals.json", &globals).await?;
+
+    let rows = sqlx::query!(r#"SELECT name AS "name!", value FROM app_settings ORDER BY name"#)
+        .fetch_all(pool)
+        .await?;
+    let settings: BTreeMap<String, String> = rows.into_iter().map(|r| (r.name, r.value)).collect();
+    write_json(&mut zip, "settings/app.json", &settings).await?;
+
+    // Secrets such as the proxy password are never exported
+    if let Ok(settings) = network::load_settings(pool).await {
+        write_json(
+            &mut zip,
+            "settings/network.json",
+            &NetworkSettings::from(settings),
+        )
+        .await?;
+    }
+
+    zip.close().await?;
+    Ok(())
+}
+
+async fn write_manifest(pool: &DbPool, zip: &mut BundleWriter) -> Result<(), ExportError> {
+    let count = |table: &str| format!("SELECT COUNT(*) FROM {}", table);
+    let mut counts = serde_json::Map::new();
+    for table in ["folders", "requests", "environments"] {
+        let (n,): (i64,) = sqlx::query_as(&count(table)).fetch_one(pool).await?;
+        counts.insert(table.to_string(), n.into());
+    }
+
+    let schema_version = db::migration_status(pool)
+        .await?
+        .into_iter()
+        .filter(|m| m.state == MigrationState::Applied)
+        .map(|m| m.version)
+        .max();
+
+    let manifest = json!({
+        "format": BUNDLE_FORMAT,
+        "format_version": BUNDLE_FORMAT_VERSION,
+        "app_version": env!("CARGO_PKG_VERSION"),
+        "schema_version": schema_version,
+        "exported_at": Utc::now(),
+        "counts": counts,
+    });
+    write_json(zip, "manifest.json", &manifest).await
+}
+
+async fn write_json<W, T>(
+    zip: &mut ZipFileWriter<W>,
+    path: &str,
+    value: &T,
+) -> Result<(), ExportError>
+where
+    W: AsyncWrite + Unpin,
+    T: Serialize,
+{
+    let data = serde_json::to_vec_pretty(value)?;
+    let entry = ZipEntryBuilder::new(path.to_string().into(), Compression::Deflate);
+    zip.write_entry_whole(entry, &data).await?;
+    Ok(())
+}
+
+/// File-name-safe version of a folder, request or environment name.
+pub fn slug(name: &str) -> String {
+    let slug: String = name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_lowercase()
+            } else {
+                '-'
+            }
+        })
+        .collect();
+    let slug = slug
+        .split('-')
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join("-");
+    let slug: String = slug.chars().take(50).collect();
+    if slug.is_empty() {
+        "untitled".to_string()
+    } else {
+        slug
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum_test::TestServer;
+    use serde_json::Value;
+    use std::io::{Cursor, Read};
+
+    #[test]
+    fn test_slug() {
+        assert_eq!(slug("My API / v2"), "my-api-v2");
+        assert_eq!(slug("***"), "untitled");
+    }
+
+    #[tokio::test]
+    async fn test_export_bundle() {
+        let pool = db::create_test_pool().await;
+        let (folder_id,): (i64,) =
+            sqlx::query_as("INSERT INTO folders (name) VALUES ('Billing API') RETURNING id")
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+        sqlx::query("INSERT INTO requests (name, method, url, folder_id, auth_type, auth_token) VALUES ('List invoices', 'GET', '{{base}}/invoices', ?, 'bearer', 'bearer-secret-1234'), ('Health', 'GET', 'http://localhost/health', NULL, 'none', NULL)")
+            .bind(folder_id)
+            .execute(&pool)
+            .await
+            .unwrap();
+        sqlx::query("INSERT INTO environments (name, variables, secrets) VALUES ('Prod', '{\"base\":\"https://api\",\"token\":\"enc:v1:abc\"}', '[\"token\"]')")
+            .execute(&pool)
+            .await
+            .unwrap();
+        sqlx::query("INSERT INTO global_variables (name, value) VALUES ('tenant', 'acme')")
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let server = TestServer::new(routes(pool)).unwrap();
+        let response = server.get("/export/bundle.zip").await;
+        response.assert_status_ok();
+        assert_eq!(response.header(header::CONTENT_TYPE), "application/zip");
+
+        let mut archive = zip::ZipArchive::new(Cursor::new(response.as_bytes().to_vec())).unwrap();
+        let read_json = |archive: &mut zip::ZipArchive<Cursor<Vec<u8>>>, name: &str| -> Value {
+            let mut content = String::new();
+            archive
+                .by_name(name)
+                .unwrap()
+                .read_to_string(&mut content)
+                .unwrap();
+            serde_json::from_str(&content).unwrap()
+        };
+
+        let manifest = read_json(&mut archive, "manifest.json");
+        assert_eq!(manifest["format_version"], BUNDLE_FORMAT_VERSION);
+        assert_eq!(manifest["counts"]["requests"], 2);
+        assert!(manifest["schema_version"].is_i64());
+
+        let collection = read_json(
+            &mut archive,
+            &format!("collections/{}-billing-api.json", folder_id),
+        );
+        assert_eq!(collection["folder"]["name"], "Billing API");
+        assert_eq!(collection["requests"][0]["name"], "List invoices");
+
+        let unfiled = read_json(&mut archive, "collections/unfiled.json");
+        assert_eq!(unfiled["requests"][0]["name"], "Health");
+
+        let environment = archive
+            .file_names()
+            .find(|name| name.starts_with("environments/") && name.ends_with("-prod.json"))
+            .map(str::to_string)
+            .unwrap();
+        let environment = read_json(&mut archive, &environment);
+        let variables: Value =
+            serde_json::from_str(environment["variables"].as_str().unwrap()).unwrap();
+        assert_eq!(variables["base"], "https://api");
+        assert_eq!(variables["token"], "");
+        let network = read_json(&mut archive, "settings/network.json");
+        assert!(network.get("proxy_password").is_none());
+        let globals = read_json(&mut archive, "globals.json");
+        assert_eq!(globals, json!({"tenant": "acme"}));
+
+        assert_eq!(collection["requests"][0]["auth_type"], "bearer");
+        for index in 0..archive.len() {
+            let mut content = String::new();
+            archive
+                .by_index(index)
+                .unwrap()
+                .read_to_string(&mut content)
+                .unwrap();
+            assert!(!content.contains("bearer-secret-1234"));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_export_postman_environment() {
+        let pool = db::create_test_pool().await;
+        let (id,): (i64,) = sqlx::query_as("INSERT INTO environments (name, variables, secrets, disabled) VALUES ('Staging EU', '{\"base\":\"https://api\",\"token\":\"enc:v1:abc\",\"old\":\"x\"}', '[\"token\"]', '[\"old\"]') RETURNING id")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        let server = TestServer::new(routes(pool)).unwrap();
+
+        let response = server
+            .get(&format!("/export/environments/{}/postman", id))
+            .await;
+        response.assert_status_ok();
+        assert!(response
+            .header(header::CONTENT_DISPOSITION)
+            .to_str()
+            .unwrap()
+            .contains("staging-eu.postman_environment.json"));
+        let body = response.as_bytes().to_vec();
+        let export: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(export["_postman_variable_scope"], "environment");
+        let values = export["values"].as_array().unwrap();
+        let find = |key: &str| values.iter().find(|v| v["key"] == key).unwrap().clone();
+        assert_eq!(find("base")["value"], "https://api");
+        assert_eq!(find("token")["value"], "");
+        assert_eq!(find("token")["type"], "secret");
+        assert_eq!(find("old")["enabled"], false);
+
+        // The importer reads the file back, leaving out disabled variables
+        match crate::importers::parse_file(&body, "staging.json").unwrap() {
+            crate::importers::ParsedFile::Environment(environment) => {
+                assert_eq!(environment.name, "Staging EU");
+                assert_eq!(environment.variables.len(), 2);
+            }
+            _ => panic!("expected an environment"),
+        }
+
+        server
+            .get("/export/environments/999/postman")
+            .await
+            .assert_status(StatusCode::NOT_FOUND);
+    }
+}
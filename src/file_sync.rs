@@ -0,0 +1,974 @@
+use anyhow::Context;
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+
+use crate::{crypto, db::DbPool, export::slug, requests};
+
+// Mirrors folders, requests and environments into YAML files so they can be
+// kept in git:
+//
+//   collections/<folder>/_folder.yaml
+//   collections/<folder>/<request>.yaml
+//   collections/_unfiled/<request>.yaml
+//   environments/<environment>.yaml
+//
+// Every pass first applies files changed on disk since they were last
+// written, imports new ones and archives the rows of deleted ones, then
+// writes the workspace back out. The sync_files table remembers which row
+// each file belongs to, so a restart or a `git pull` is told apart from new
+// files. Secrets (request tokens and passwords, secret environment values)
+// are never written and are kept when a file is applied.
+
+const DEFAULT_INTERVAL_SECS: u64 = 2;
+const FOLDER_FILE: &str = "_folder.yaml";
+// Slugs never contain underscores, so this cannot clash with a folder
+const UNFILED_DIR: &str = "_unfiled";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Kind {
+    Folder,
+    Environment,
+    Request,
+}
+
+impl Kind {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Kind::Folder => "folder",
+            Kind::Environment => "environment",
+            Kind::Request => "request",
+        }
+    }
+
+    fn parse(kind: &str) -> Option<Self> {
+        match kind {
+            "folder" => Some(Kind::Folder),
+            "environment" => Some(Kind::Environment),
+            "request" => Some(Kind::Request),
+            _ => None,
+        }
+    }
+
+    /// The kind of file at a path in the sync directory, and for collection
+    /// files the directory it is in.
+    fn of(path: &str) -> Option<(Self, Option<&str>)> {
+        match path.split('/').collect::<Vec<_>>()[..] {
+            ["collections", UNFILED_DIR, _] => Some((Kind::Request, None)),
+            ["collections", dir, FOLDER_FILE] => Some((Kind::Folder, Some(dir))),
+            ["collections", dir, _] => Some((Kind::Request, Some(dir))),
+            ["environments", _] => Some((Kind::Environment, None)),
+            _ => None,
+        }
+    }
+}
+
+struct SyncFile {
+    kind: Kind,
+    row_id: i64,
+    hash: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct FolderFile {
+    name: String,
+    #[serde(default, skip_serializing_if = "Map::is_empty")]
+    variables: Map<String, Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    description: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    base_url: Option<String>,
+}
+
+/// A request without its id, timestamps and secrets. Columns stored as JSON
+/// are written as YAML structures.
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+struct RequestFile {
+    name: String,
+    method: String,
+    url: String,
+    request_type: String,
+    body_type: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    body: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    body_content: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    headers: Option<Value>,
+    auth_type: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    auth_username: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    auth_config: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    captures: Option<Value>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    serialized: bool,
+    ip_family: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pre_request_script: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    test_script: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    callback: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    unresolved_variables: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    retry: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    compression: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    assertions: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    description: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tls_versions: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    latency_budget_ms: Option<i64>,
+}
+
+impl Default for RequestFile {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            method: "GET".to_string(),
+            url: String::new(),
+            request_type: "api".to_string(),
+            body_type: "none".to_string(),
+            body: None,
+            body_content: None,
+            headers: None,
+            auth_type: "none".to_string(),
+            auth_username: None,
+            auth_config: None,
+            captures: None,
+            serialized: false,
+            ip_family: "auto".to_string(),
+            pre_request_script: None,
+            test_script: None,
+            callback: None,
+            unresolved_variables: None,
+            retry: None,
+            compression: None,
+            assertions: None,
+            description: None,
+            tls_versions: None,
+            latency_budget_ms: None,
+        }
+    }
+}
+
+/// An environment with secret values left empty and its parent by name.
+#[derive(Serialize, Deserialize)]
+struct EnvironmentFile {
+    name: String,
+    #[serde(default)]
+    variables: BTreeMap<String, Value>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    secrets: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    disabled: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    parent: Option<String>,
+}
+
+/// What one pass changed.
+#[derive(Debug, Default, PartialEq)]
+pub struct SyncReport {
+    /// Files applied to or imported into the workspace
+    pub applied: usize,
+    /// Rows archived because their file was deleted
+    pub archived: usize,
+    pub written: usize,
+    pub removed: usize,
+}
+
+impl SyncReport {
+    fn is_empty(&self) -> bool {
+        *self == SyncReport::default()
+    }
+}
+
+/// Starts syncing with `JSLINK_SYNC_DIR`, if set, every
+/// `JSLINK_SYNC_INTERVAL_SECS` seconds (default 2).
+pub fn spawn(pool: DbPool) {
+    let Some(dir) = env::var("JSLINK_SYNC_DIR").ok().filter(|d| !d.is_empty()) else {
+        return;
+    };
+    let interval_secs = env::var("JSLINK_SYNC_INTERVAL_SECS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .filter(|&secs| secs > 0)
+        .unwrap_or(DEFAULT_INTERVAL_SECS);
+    log::info!("Syncing collections with {} every {}s", dir, interval_secs);
+
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(interval_secs));
+        let mut failures = HashMap::new();
+        loop {
+            interval.tick().await;
+            match sync(&pool, Path::new(&dir), &mut failures).await {
+                Ok(report) if report.is_empty() => {}
+                Ok(report) => log::info!(
+                    "Synced {}: {} applied, {} archived, {} written, {} removed",
+                    dir,
+                    report.applied,
+                    report.archived,
+                    report.written,
+                    report.removed
+                ),
+                Err(e) => log::warn!("Syncing {} failed: {:#}", dir, e),
+            }
+        }
+    });
+}
+
+/// One pass in both directions. `failures` holds the hashes of files that
+/// could not be applied, so each broken version is reported only once; such
+/// files are left untouched until they change.
+pub async fn sync(
+    pool: &DbPool,
+    dir: &Path,
+    failures: &mut HashMap<String, String>,
+) -> Result<SyncReport, anyhow::Error> {
+    let mut report = SyncReport::default();
+    let known = load_files(pool).await?;
+    let disk = scan(dir).with_context(|| format!("Failed to read {}", dir.display()))?;
+
+    let mut folder_dirs: HashMap<String, i64> = known
+        .iter()
+        .filter(|(_, file)| file.kind == Kind::Folder)
+        .filter_map(|(path, file)| Some((Kind::of(path)?.1?.to_string(), file.row_id)))
+        .collect();
+    let mut parents = Vec::new();
+    let mut held = HashSet::new();
+    let mut created = HashSet::new();
+
+    // Known files edited or deleted on disk
+    for (path, file) in &known {
+        match disk.get(path) {
+            Some(content) if hash(content) == file.hash => {}
+            Some(content) => {
+                let id = Some(file.row_id);
+                match apply(pool, path, id, content, &mut folder_dirs, &mut parents).await {
+                    Ok(_) => report.applied += 1,
+                    Err(e) => fail(failures, &mut held, path, content, e),
+                }
+            }
+            // A folder file is only gone with its folder if the directory is
+            // empty too; otherwise it is written again
+            None if file.kind == Kind::Folder && has_files(&disk, path) => {}
+            None => {
+                archive(pool, file.kind, file.row_id).await?;
+                report.archived += 1;
+            }
+        }
+    }
+
+    // New files, folders first so their requests can be filed into them
+    let mut new_files: Vec<(Kind, &String)> = disk
+        .keys()
+        .filter(|path| !known.contains_key(*path))
+        .filter_map(|path| Some((Kind::of(path)?.0, path)))
+        .collect();
+    new_files.sort();
+    for (_, path) in new_files {
+        let content = &disk[path];
+        match apply(pool, path, None, content, &mut folder_dirs, &mut parents).await {
+            Ok(_) => {
+                created.insert(path.clone());
+                report.applied += 1;
+            }
+            Err(e) => fail(failures, &mut held, path, content, e),
+        }
+    }
+
+    for (id, parent) in parents {
+        sqlx::query!(
+            "UPDATE environments SET parent_id = (SELECT id FROM environments WHERE name = ? AND archived_at IS NULL ORDER BY id LIMIT 1) WHERE id = ?",
+            parent,
+            id
+        )
+        .execute(pool)
+        .await?;
+    }
+
+    // Write the workspace back out
+    let rendered = render(pool).await?;
+    let mut files = Vec::new();
+    for (path, kind, row_id, content) in &rendered {
+        if held.contains(path) {
+            continue;
+        }
+        if disk.get(path) != Some(content) {
+            let target = dir.join(path);
+            if let Some(parent) = target.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::write(&target, content)
+                .with_context(|| format!("Failed to write {}", target.display()))?;
+            report.written += 1;
+        }
+        files.push((path.clone(), *kind, *row_id, hash(content)));
+    }
+
+    let desired: HashSet<&String> = rendered.iter().map(|(path, ..)| path).collect();
+    for path in known.keys().chain(created.iter()) {
+        if desired.contains(path) || held.contains(path) {
+            continue;
+        }
+        let target = dir.join(path);
+        if target.exists() {
+            fs::remove_file(&target)?;
+            report.removed += 1;
+            // Leaves directories that still hold other files
+            if let Some(parent) = target.parent() {
+                let _ = fs::remove_dir(parent);
+            }
+        }
+    }
+
+    // Held files keep their old entry, so they are applied once fixed
+    for path in &held {
+        if let Some(file) = known.get(path) {
+            files.retain(|(p, ..)| p != path);
+            files.push((path.clone(), file.kind, file.row_id, file.hash.clone()));
+        }
+    }
+    save_files(pool, &files).await?;
+    failures.retain(|path, _| held.contains(path));
+
+    Ok(report)
+}
+
+fn fail(
+    failures: &mut HashMap<String, String>,
+    held: &mut HashSet<String>,
+    path: &str,
+    content: &str,
+    error: anyhow::Error,
+) {
+    let content_hash = hash(content);
+    if failures.get(path) != Some(&content_hash) {
+        log::warn!("Could not apply {}: {:#}", path, error);
+        failures.insert(path.to_string(), content_hash);
+    }
+    held.insert(path.to_string());
+}
+
+fn hash(content: &str) -> String {
+    format!("{:x}", Sha256::digest(content.as_bytes()))
+}
+
+fn has_files(disk: &BTreeMap<String, String>, folder_file: &str) -> bool {
+    let dir = folder_file.trim_end_matches(FOLDER_FILE);
+    disk.keys().any(|path| path.starts_with(dir))
+}
+
+/// The YAML files of the sync directory by relative path.
+fn scan(dir: &Path) -> std::io::Result<BTreeMap<String, String>> {
+    let mut files = BTreeMap::new();
+    for top in ["collections", "environments"] {
+        scan_dir(&dir.join(top), top, &mut files)?;
+    }
+    Ok(files)
+}
+
+fn scan_dir(
+    path: &Path,
+    relative: &str,
+    files: &mut BTreeMap<String, String>,
+) -> std::io::Result<()> {
+    let entries = match fs::read_dir(path) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e),
+    };
+    for entry in entries {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().to_string();
+        if name.starts_with('.') {
+            continue;
+        }
+        let relative = format!("{}/{}", relative, name);
+        if entry.file_type()?.is_dir() {
+            scan_dir(&entry.path(), &relative, files)?;
+        } else if name.ends_with(".yaml") && Kind::of(&relative).is_some() {
+            files.insert(relative, fs::read_to_string(entry.path())?);
+        }
+    }
+    Ok(())
+}
+
+async fn load_files(pool: &DbPool) -> Result<HashMap<String, SyncFile>, sqlx::Error> {
+    let rows = sqlx::query!("SELECT path, kind, row_id, hash FROM sync_files")
+        .fetch_all(pool)
+        .await?;
+    Ok(rows
+        .into_iter()
+        .filter_map(|r| {
+            let file = SyncFile {
+                kind: Kind::parse(&r.kind)?,
+                row_id: r.row_id,
+                hash: r.hash,
+            };
+            Some((r.path, file))
+        })
+        .collect())
+}
+
+async fn save_files(
+    pool: &DbPool,
+    files: &[(String, Kind, i64, String)],
+) -> Result<(), sqlx::Error> {
+    let mut tx = pool.begin().await?;
+    sqlx::query!("DELETE FROM sync_files")
+        .execute(&mut *tx)
+        .await?;
+    for (path, kind, row_id, hash) in files {
+        let kind = kind.as_str();
+        sqlx::query!(
+            "INSERT INTO sync_files (path, kind, row_id, hash) VALUES (?, ?, ?, ?)",
+            path,
+            kind,
+            row_id,
+            hash
+        )
+        .execute(&mut *tx)
+        .await?;
+    }
+    tx.commit().await
+}
+
+async fn archive(pool: &DbPool, kind: Kind, id: i64) -> Result<(), sqlx::Error> {
+    let now = Utc::now().naive_utc();
+    let query = match kind {
+        Kind::Folder => "UPDATE folders SET archived_at = ? WHERE id = ? AND archived_at IS NULL",
+        Kind::Request => "UPDATE requests SET archived_at = ? WHERE id = ? AND archived_at IS NULL",
+        Kind::Environment => {
+            "UPDATE environments SET archived_at = ? WHERE id = ? AND archived_at IS NULL"
+        }
+    };
+    sqlx::query(query).bind(now).bind(id).execute(pool).await?;
+    log::info!("Archived {} {} as its file was deleted", kind.as_str(), id);
+    Ok(())
+}
+
+/// Applies a file to the row `id`, or creates one, returning its id.
+/// Environment parents are collected into `parents`, to be linked once every
+/// environment exists.
+async fn apply(
+    pool: &DbPool,
+    path: &str,
+    id: Option<i64>,
+    content: &str,
+    folder_dirs: &mut HashMap<String, i64>,
+    parents: &mut Vec<(i64, Option<String>)>,
+) -> Result<i64, anyhow::Error> {
+    let (kind, dir) = Kind::of(path).context("Not a synced file")?;
+    match kind {
+        Kind::Folder => {
+            let folder: FolderFile = serde_yaml::from_str(content)?;
+            anyhow::ensure!(!folder.name.trim().is_empty(), "The folder needs a name");
+            let variables = Value::Object(folder.variables).to_string();
+            let id = match id {
+                Some(id) => {
+                    sqlx::query!(
+                        "UPDATE folders SET name = ?, variables = ?, description = ?, base_url = ?, updated_at = CURRENT_TIMESTAMP WHERE id = ?",
+                        folder.name,
+                        variables,
+                        folder.description,
+                        folder.base_url,
+                        id
+                    )
+                    .execute(pool)
+                    .await?;
+                    id
+                }
+                None => {
+                    sqlx::query_scalar!(
+                        "INSERT INTO folders (name, variables, description, base_url) VALUES (?, ?, ?, ?) RETURNING id",
+                        folder.name,
+                        variables,
+                        folder.description,
+                        folder.base_url
+                    )
+                    .fetch_one(pool)
+                    .await?
+                }
+            };
+            if let Some(dir) = dir {
+                folder_dirs.insert(dir.to_string(), id);
+            }
+            Ok(id)
+        }
+        Kind::Request => {
+            let request: RequestFile = serde_yaml::from_str(content)?;
+            anyhow::ensure!(!request.name.trim().is_empty(), "The request needs a name");
+            anyhow::ensure!(
+                request.request_type == "ws" || requests::is_valid_method(&request.method),
+                "Invalid HTTP method '{}'",
+                request.method
+            );
+            let folder_id = match dir {
+                Some(dir) => Some(folder_for(pool, dir, folder_dirs).await?),
+                None => None,
+            };
+            let headers = json_column(&request.headers);
+            let auth_config = json_column(&request.auth_config);
+            let captures = json_column(&request.captures);
+            let callback = json_column(&request.callback);
+            let retry = json_column(&request.retry);
+            let compression = json_column(&request.compression);
+            let assertions = json_column(&request.assertions);
+            let tls_versions = json_column(&request.tls_versions);
+            let auth_username = requests::encrypt_credential(request.auth_username.as_deref())?;
+            match id {
+                Some(id) => {
+                    sqlx::query!(
+                        "UPDATE requests SET name = ?, method = ?, url = ?, request_type = ?, body_type = ?, body = ?, body_content = ?, headers = ?, folder_id = ?, auth_type = ?, auth_username = ?, auth_config = ?, captures = ?, serialized = ?, ip_family = ?, pre_request_script = ?, test_script = ?, callback = ?, unresolved_variables = ?, retry = ?, compression = ?, assertions = ?, description = ?, tls_versions = ?, latency_budget_ms = ?, updated_at = CURRENT_TIMESTAMP WHERE id = ?",
+                        request.name,
+                        request.method,
+                        request.url,
+                        request.request_type,
+                        request.body_type,
+                        request.body,
+                        request.body_content,
+                        headers,
+                        folder_id,
+                        request.auth_type,
+                        auth_username,
+                        auth_config,
+                        captures,
+                        request.serialized,
+                        request.ip_family,
+                        request.pre_request_script,
+                        request.test_script,
+                        callback,
+                        request.unresolved_variables,
+                        retry,
+                        compression,
+                        assertions,
+                        request.description,
+                        tls_versions,
+                        request.latency_budget_ms,
+                        id
+                    )
+                    .execute(pool)
+                    .await?;
+                    Ok(id)
+                }
+                None => Ok(sqlx::query_scalar!(
+                    "INSERT INTO requests (name, method, url, request_type, body_type, body, body_content, headers, folder_id, auth_type, auth_username, auth_config, captures, serialized, ip_family, pre_request_script, test_script, callback, unresolved_variables, retry, compression, assertions, description, tls_versions, latency_budget_ms) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?) RETURNING id",
+                    request.name,
+                    request.method,
+                    request.url,
+                    request.request_type,
+                    request.body_type,
+                    request.body,
+                    request.body_content,
+                    headers,
+                    folder_id,
+                    request.auth_type,
+                    auth_username,
+                    auth_config,
+                    captures,
+                    request.serialized,
+                    request.ip_family,
+                    request.pre_request_script,
+                    request.test_script,
+                    callback,
+                    request.unresolved_variables,
+                    retry,
+                    compression,
+                    assertions,
+                    request.description,
+                    tls_versions,
+                    request.latency_budget_ms
+                )
+                .fetch_one(pool)
+                .await?),
+            }
+        }
+        Kind::Environment => {
+            let environment: EnvironmentFile = serde_yaml::from_str(content)?;
+            anyhow::ensure!(
+                !environment.name.trim().is_empty(),
+                "The environment needs a name"
+            );
+            let stored: Map<String, Value> = match id {
+                Some(id) => {
+                    let variables =
+                        sqlx::query_scalar!("SELECT variables FROM environments WHERE id = ?", id)
+                            .fetch_one(pool)
+                            .await?;
+                    serde_json::from_str(&variables).unwrap_or_default()
+                }
+                None => Map::new(),
+            };
+            let mut variables = Map::new();
+            for (name, value) in environment.variables {
+                let value = match value {
+                    Value::String(s) => s,
+                    Value::Null => String::new(),
+                    other => other.to_string(),
+                };
+                let value = if !environment.secrets.contains(&name) {
+                    Value::String(value)
+                } else if value.is_empty() {
+                    // Secret values are not in the file, keep the stored one
+                    stored.get(&name).cloned().unwrap_or(Value::String(value))
+                } else {
+                    Value::String(crypto::encrypt(&value)?)
+                };
+                variables.insert(name, value);
+            }
+            let variables = Value::Object(variables).to_string();
+            let secrets = serde_json::to_string(&environment.secrets)?;
+            let disabled = serde_json::to_string(&environment.disabled)?;
+            let id = match id {
+                Some(id) => {
+                    sqlx::query!(
+                        "UPDATE environments SET name = ?, variables = ?, secrets = ?, disabled = ?, updated_at = CURRENT_TIMESTAMP WHERE id = ?",
+                        environment.name,
+                        variables,
+                        secrets,
+                        disabled,
+                        id
+                    )
+                    .execute(pool)
+                    .await?;
+                    id
+                }
+                None => {
+                    sqlx::query_scalar!(
+                        "INSERT INTO environments (name, variables, secrets, disabled) VALUES (?, ?, ?, ?) RETURNING id",
+                        environment.name,
+                        variables,
+                        secrets,
+                        disabled
+                    )
+                    .fetch_one(pool)
+                    .await?
+                }
+            };
+            parents.push((id, environment.parent));
+            Ok(id)
+        }
+    }
+}
+
+/// The folder a collection directory belongs to, created for a directory
+/// that has requests but no folder file yet.
+async fn folder_for(
+    pool: &DbPool,
+    dir: &str,
+    folder_dirs: &mut HashMap<String, i64>,
+) -> Result<i64, sqlx::Error> {
+    if let Some(id) = folder_dirs.get(dir) {
+        return Ok(*id);
+    }
+    let id = sqlx::query_scalar!("INSERT INTO folders (name) VALUES (?) RETURNING id", dir)
+        .fetch_one(pool)
+        .await?;
+    folder_dirs.insert(dir.to_string(), id);
+    Ok(id)
+}
+
+/// A JSON column from its YAML form; strings are stored as they are.
+fn json_column(value: &Option<Value>) -> Option<String> {
+    match value {
+        None | Some(Value::Null) => None,
+        Some(Value::String(s)) => Some(s.clone()),
+        Some(other) => Some(other.to_string()),
+    }
+}
+
+/// A JSON column in its YAML form, or as a string if it is not valid JSON.
+fn yaml_column(column: Option<String>) -> Option<Value> {
+    let column = column.filter(|c| !c.is_empty())?;
+    Some(serde_json::from_str(&column).unwrap_or(Value::String(column)))
+}
+
+/// `name`, or `name-<id>` when another row in the same directory already
+/// took it.
+fn unique_slug(used: &mut HashSet<String>, name: &str, id: i64) -> String {
+    let base = slug(name);
+    if used.insert(base.clone()) {
+        return base;
+    }
+    let slug = format!("{}-{}", base, id);
+    used.insert(slug.clone());
+    slug
+}
+
+/// The files the current workspace is written as: path, kind, row id and
+/// content. Archived rows, and requests in archived folders, are left out.
+async fn render(pool: &DbPool) -> Result<Vec<(String, Kind, i64, String)>, anyhow::Error> {
+    let mut files = Vec::new();
+
+    let folders = sqlx::query!(
+        "SELECT id, name, variables, description, base_url FROM folders WHERE archived_at IS NULL ORDER BY id"
+    )
+    .fetch_all(pool)
+    .await?;
+    let mut used = HashSet::new();
+    let mut dirs = HashMap::new();
+    for folder in folders {
+        let dir = unique_slug(&mut used, &folder.name, folder.id);
+        let file = FolderFile {
+            name: folder.name,
+            variables: serde_json::from_str(&folder.variables).unwrap_or_default(),
+            description: folder.description.filter(|d| !d.is_empty()),
+            base_url: folder.base_url.filter(|b| !b.is_empty()),
+        };
+        files.push((
+            format!("collections/{}/{}", dir, FOLDER_FILE),
+            Kind::Folder,
+            folder.id,
+            serde_yaml::to_string(&file)?,
+        ));
+        dirs.insert(folder.id, dir);
+    }
+
+    let rows = sqlx::query!(
+        "SELECT id, name, method, url, request_type, body_type, body, body_content, headers, folder_id, auth_type, auth_username, auth_config, captures, serialized, ip_family, pre_request_script, test_script, callback, unresolved_variables, retry, compression, assertions, description, tls_versions, latency_budget_ms FROM requests WHERE archived_at IS NULL ORDER BY id"
+    )
+    .fetch_all(pool)
+    .await?;
+    let mut used: HashMap<&str, HashSet<String>> = HashMap::new();
+    for r in rows {
+        let dir = match r.folder_id {
+            Some(folder_id) => match dirs.get(&folder_id) {
+                Some(dir) => dir.as_str(),
+                None => continue,
+            },
+            None => UNFILED_DIR,
+        };
+        let name = unique_slug(used.entry(dir).or_default(), &r.name, r.id);
+        let auth_username = r
+            .auth_username
+            .map(|u| crypto::decrypt(&u).unwrap_or(u))
+            .filter(|u| !u.is_empty());
+        let file = RequestFile {
+            name: r.name,
+            method: r.method,
+            url: r.url,
+            request_type: r.request_type,
+            body_type: r.body_type,
+            body: r.body.filter(|b| !b.is_empty()),
+            body_content: r.body_content.filter(|b| !b.is_empty()),
+            headers: yaml_column(r.headers),
+            auth_type: r.auth_type,
+            auth_username,
+            auth_config: yaml_column(r.auth_config),
+            captures: yaml_column(r.captures),
+            serialized: r.serialized,
+            ip_family: r.ip_family,
+            pre_request_script: r.pre_request_script.filter(|s| !s.is_empty()),
+            test_script: r.test_script.filter(|s| !s.is_empty()),
+            callback: yaml_column(r.callback),
+            unresolved_variables: r.unresolved_variables,
+            retry: yaml_column(r.retry),
+            compression: yaml_column(r.compression),
+            assertions: yaml_column(r.assertions),
+            description: r.description.filter(|d| !d.is_empty()),
+            tls_versions: yaml_column(r.tls_versions),
+            latency_budget_ms: r.latency_budget_ms,
+        };
+        files.push((
+            format!("collections/{}/{}.yaml", dir, name),
+            Kind::Request,
+            r.id,
+            serde_yaml::to_string(&file)?,
+        ));
+    }
+
+    let environments = sqlx::query!(
+        r#"SELECT e.id, e.name, e.variables, e.secrets, e.disabled, p.name AS "parent?" FROM environments e LEFT JOIN environments p ON p.id = e.parent_id WHERE e.archived_at IS NULL ORDER BY e.id"#
+    )
+    .fetch_all(pool)
+    .await?;
+    let mut used = HashSet::new();
+    for e in environments {
+        let name = unique_slug(&mut used, &e.name, e.id);
+        let secrets: Vec<String> = serde_json::from_str(&e.secrets).unwrap_or_default();
+        let mut variables: BTreeMap<String, Value> =
+            serde_json::from_str(&e.variables).unwrap_or_default();
+        for secret in &secrets {
+            if let Some(value) = variables.get_mut(secret) {
+                *value = Value::String(String::new());
+            }
+        }
+        let file = EnvironmentFile {
+            name: e.name,
+            variables,
+            secrets,
+            disabled: serde_json::from_str(&e.disabled).unwrap_or_default(),
+            parent: e.parent,
+        };
+        files.push((
+            format!("environments/{}.yaml", name),
+            Kind::Environment,
+            e.id,
+            serde_yaml::to_string(&file)?,
+        ));
+    }
+
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db;
+    use std::path::PathBuf;
+
+    /// A workspace with one folder, request and environment, and an empty
+    /// directory to mirror it into.
+    async fn setup(test: &str) -> (DbPool, PathBuf, i64) {
+        let pool = db::create_test_pool().await;
+        let dir = env::temp_dir().join(format!("jslink-sync-{}-{}", std::process::id(), test));
+        let _ = fs::remove_dir_all(&dir);
+        let (folder_id,): (i64,) =
+            sqlx::query_as("INSERT INTO folders (name) VALUES ('Billing API') RETURNING id")
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+        sqlx::query("INSERT INTO requests (name, method, url, folder_id, headers, auth_type, auth_token) VALUES ('List invoices', 'GET', '{{base}}/invoices', ?, '[{\"name\":\"Accept\",\"value\":\"application/json\"}]', 'bearer', 'tok')")
+            .bind(folder_id)
+            .execute(&pool)
+            .await
+            .unwrap();
+        sqlx::query("INSERT INTO environments (name, variables, secrets) VALUES ('Prod', '{\"base\":\"https://api\",\"key\":\"k\"}', '[\"key\"]')")
+            .execute(&pool)
+            .await
+            .unwrap();
+        (pool, dir, folder_id)
+    }
+
+    fn request_path(dir: &Path) -> PathBuf {
+        dir.join("collections/billing-api/list-invoices.yaml")
+    }
+
+    #[tokio::test]
+    async fn test_workspace_is_written_without_secrets() {
+        let (pool, dir, _) = setup("write").await;
+        let mut failures = HashMap::new();
+
+        let report = sync(&pool, &dir, &mut failures).await.unwrap();
+        assert_eq!(report.written, 3);
+        let request = fs::read_to_string(request_path(&dir)).unwrap();
+        assert!(request.contains("url: '{{base}}/invoices'"));
+        assert!(request.contains("name: Accept"));
+        assert!(!request.contains("tok"));
+        let environment = fs::read_to_string(dir.join("environments/prod.yaml")).unwrap();
+        assert!(environment.contains("key: ''"));
+
+        // Nothing changed, nothing to do
+        assert!(sync(&pool, &dir, &mut failures).await.unwrap().is_empty());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_edits_on_disk_are_applied() {
+        let (pool, dir, folder_id) = setup("apply").await;
+        let mut failures = HashMap::new();
+        sync(&pool, &dir, &mut failures).await.unwrap();
+
+        // Secrets left out of the files are kept
+        let request = fs::read_to_string(request_path(&dir)).unwrap();
+        fs::write(request_path(&dir), request.replace("/invoices", "/bills")).unwrap();
+        fs::write(
+            dir.join("collections/billing-api/create-invoice.yaml"),
+            "name: Create invoice\nmethod: POST\nurl: '{{base}}/invoices'\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.join("environments/prod.yaml"),
+            "name: Prod\nvariables:\n  base: https://prod\n  key: ''\nsecrets: [key]\n",
+        )
+        .unwrap();
+        let report = sync(&pool, &dir, &mut failures).await.unwrap();
+        assert_eq!(report.applied, 3);
+        let (url, token): (String, String) =
+            sqlx::query_as("SELECT url, auth_token FROM requests WHERE name = 'List invoices'")
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+        assert_eq!(url, "{{base}}/bills");
+        assert_eq!(token, "tok");
+        let (created_folder,): (Option<i64>,) =
+            sqlx::query_as("SELECT folder_id FROM requests WHERE name = 'Create invoice'")
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+        assert_eq!(created_folder, Some(folder_id));
+        let (variables,): (String,) =
+            sqlx::query_as("SELECT variables FROM environments WHERE name = 'Prod'")
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+        assert_eq!(variables, r#"{"base":"https://prod","key":"k"}"#);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_broken_files_are_left_and_deleted_ones_archived() {
+        let (pool, dir, _) = setup("delete").await;
+        let mut failures = HashMap::new();
+        let created = dir.join("collections/billing-api/create-invoice.yaml");
+        sync(&pool, &dir, &mut failures).await.unwrap();
+        fs::write(
+            &created,
+            "name: Create invoice\nmethod: POST\nurl: '{{base}}/invoices'\n",
+        )
+        .unwrap();
+        sync(&pool, &dir, &mut failures).await.unwrap();
+
+        fs::write(request_path(&dir), "name: [").unwrap();
+        fs::remove_file(&created).unwrap();
+        let report = sync(&pool, &dir, &mut failures).await.unwrap();
+        assert_eq!(report.archived, 1);
+        assert_eq!(fs::read_to_string(request_path(&dir)).unwrap(), "name: [");
+        assert!(failures.contains_key("collections/billing-api/list-invoices.yaml"));
+        let (archived,): (i64,) = sqlx::query_as(
+            "SELECT COUNT(*) FROM requests WHERE name = 'Create invoice' AND archived_at IS NOT NULL",
+        )
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+        assert_eq!(archived, 1);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_renaming_in_the_app_moves_files() {
+        let (pool, dir, folder_id) = setup("rename").await;
+        let mut failures = HashMap::new();
+        sync(&pool, &dir, &mut failures).await.unwrap();
+
+        sqlx::query("UPDATE folders SET name = 'Invoices' WHERE id = ?")
+            .bind(folder_id)
+            .execute(&pool)
+            .await
+            .unwrap();
+        sync(&pool, &dir, &mut failures).await.unwrap();
+        assert!(dir.join("collections/invoices/_folder.yaml").exists());
+        assert!(dir.join("collections/invoices/list-invoices.yaml").exists());
+        assert!(!dir.join("collections/billing-api").exists());
+        assert!(failures.is_empty());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
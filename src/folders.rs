@@ -7,25 +7,40 @@ use axum::{
 };
 use chrono::{DateTime, NaiveDateTime, Utc};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 
-use crate::db::DbPool;
+use crate::{
+    audit::{self, Action, Actor, Entity},
+    db::DbPool,
+    i18n, requests,
+};
 
 #[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
 pub struct Folder {
     id: i64,
     name: String,
+    /// JSON object of variables, overriding globals and overridden by the
+    /// environment
+    variables: String,
+    /// Markdown documenting the folder
+    description: Option<String>,
+    /// Prefixed to the relative URLs of the folder's requests when they run
+    base_url: Option<String>,
     created_at: DateTime<Utc>,
     updated_at: DateTime<Utc>,
     archived_at: Option<DateTime<Utc>>,
 }
 
-#[derive(sqlx::FromRow, Clone)]
-struct FolderDb {
-    id: i64,
-    name: String,
-    created_at: NaiveDateTime,
-    updated_at: NaiveDateTime,
-    archived_at: Option<NaiveDateTime>,
+#[derive(sqlx::FromRow, Serialize, Deserialize, Clone)]
+pub struct FolderDb {
+    pub id: i64,
+    pub name: String,
+    pub variables: String,
+    pub description: Option<String>,
+    pub base_url: Option<String>,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+    pub archived_at: Option<NaiveDateTime>,
 }
 
 impl From<FolderDb> for Folder {
@@ -33,6 +48,9 @@ impl From<FolderDb> for Folder {
         Self {
             id: f.id,
             name: f.name,
+            variables: f.variables,
+            description: f.description,
+            base_url: f.base_url,
             created_at: DateTime::from_naive_utc_and_offset(f.created_at, Utc),
             updated_at: DateTime::from_naive_utc_and_offset(f.updated_at, Utc),
             archived_at: f
@@ -45,11 +63,40 @@ impl From<FolderDb> for Folder {
 #[derive(Deserialize)]
 pub struct CreateFolder {
     name: String,
+    #[serde(default)]
+    variables: Option<String>,
+    #[serde(default)]
+    description: Option<String>,
+    #[serde(default)]
+    base_url: Option<String>,
 }
 
 #[derive(Deserialize)]
 pub struct UpdateFolder {
     name: String,
+    /// `null` or absent keeps the stored variables.
+    #[serde(default)]
+    variables: Option<String>,
+    /// `null` or absent keeps the stored description, an empty string clears
+    /// it.
+    #[serde(default)]
+    description: Option<String>,
+    /// `null` or absent keeps the stored base URL, an empty string clears it.
+    #[serde(default)]
+    base_url: Option<String>,
+}
+
+/// Partial update; absent fields keep their stored value.
+#[derive(Deserialize)]
+pub struct PatchFolder {
+    #[serde(default)]
+    name: Option<String>,
+    #[serde(default)]
+    variables: Option<String>,
+    #[serde(default)]
+    description: Option<String>,
+    #[serde(default)]
+    base_url: Option<String>,
 }
 
 #[derive(Deserialize)]
@@ -60,6 +107,8 @@ pub struct ListFoldersQuery {
 
 pub enum FolderError {
     InvalidName,
+    InvalidVariables,
+    InvalidBaseUrl,
     FolderNotFound,
     DatabaseError(#[allow(dead_code)] sqlx::Error),
 }
@@ -76,21 +125,129 @@ impl From<sqlx::Error> for FolderError {
 impl IntoResponse for FolderError {
     fn into_response(self) -> Response {
         match self {
-            FolderError::InvalidName => {
-                (StatusCode::BAD_REQUEST, "Invalid folder name").into_response()
-            }
+            FolderError::InvalidName => (
+                StatusCode::BAD_REQUEST,
+                i18n::t("error.invalid_folder_name"),
+            )
+                .into_response(),
+            FolderError::InvalidVariables => (
+                StatusCode::BAD_REQUEST,
+                i18n::t("error.invalid_folder_variables"),
+            )
+                .into_response(),
+            FolderError::InvalidBaseUrl => (
+                StatusCode::BAD_REQUEST,
+                i18n::t("error.invalid_folder_base_url"),
+            )
+                .into_response(),
             FolderError::FolderNotFound => {
-                (StatusCode::NOT_FOUND, "Folder not found").into_response()
+                (StatusCode::NOT_FOUND, i18n::t("error.folder_not_found")).into_response()
             }
             FolderError::DatabaseError(_) => {
-                (StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response()
+                (StatusCode::INTERNAL_SERVER_ERROR, i18n::t("error.database")).into_response()
             }
         }
     }
 }
 
+/// Variables must be a JSON object of strings, like an environment's.
+fn validate_variables(variables: Option<&str>) -> Result<(), FolderError> {
+    match variables {
+        Some(v) if serde_json::from_str::<HashMap<String, String>>(v).is_err() => {
+            Err(FolderError::InvalidVariables)
+        }
+        _ => Ok(()),
+    }
+}
+
+/// A base URL must be an absolute http(s) URL, unless it is built from
+/// variables, e.g. `{{host}}/v2`.
+fn validate_base_url(base_url: Option<&str>) -> Result<(), FolderError> {
+    let Some(base_url) = base_url.map(str::trim).filter(|b| !b.is_empty()) else {
+        return Ok(());
+    };
+    if base_url.contains("{{") {
+        return Ok(());
+    }
+    match reqwest::Url::parse(base_url) {
+        Ok(url) if matches!(url.scheme(), "http" | "https") => Ok(()),
+        _ => Err(FolderError::InvalidBaseUrl),
+    }
+}
+
+/// Whether a request URL is relative to its folder's base URL: it has no
+/// scheme and does not start with a variable, which may hold a whole URL.
+pub fn is_relative(url: &str) -> bool {
+    let url = url.trim();
+    !has_scheme(url) && !url.starts_with("{{")
+}
+
+/// Whether `url` starts with a scheme, `[A-Za-z][A-Za-z0-9+.-]*:`, before
+/// its first `/`, `?` or `#`. A URL in the query string does not count.
+fn has_scheme(url: &str) -> bool {
+    let end = url.find(['/', '?', '#']).unwrap_or(url.len());
+    let Some((scheme, _)) = url[..end].split_once(':') else {
+        return false;
+    };
+    let mut chars = scheme.chars();
+    chars.next().is_some_and(|c| c.is_ascii_alphabetic())
+        && chars.all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '.' | '-'))
+}
+
+/// `url` prefixed with `base_url` when it is relative. The two are joined
+/// as text, so a base URL's path is kept: `https://api/v2` and `/users`
+/// give `https://api/v2/users`.
+pub fn join_base_url(base_url: Option<&str>, url: &str) -> String {
+    let Some(base_url) = base_url.map(str::trim).filter(|b| !b.is_empty()) else {
+        return url.to_string();
+    };
+    if !is_relative(url) {
+        return url.to_string();
+    }
+    let url = url.trim();
+    let base_url = base_url.trim_end_matches('/');
+    if url.is_empty() {
+        base_url.to_string()
+    } else if url.starts_with('?') || url.starts_with('#') {
+        format!("{}{}", base_url, url)
+    } else {
+        format!("{}/{}", base_url, url.trim_start_matches('/'))
+    }
+}
+
+/// The base URL of a folder, `None` for requests outside any folder.
+pub async fn load_base_url(
+    pool: &DbPool,
+    folder_id: Option<i64>,
+) -> Result<Option<String>, sqlx::Error> {
+    let Some(folder_id) = folder_id else {
+        return Ok(None);
+    };
+    let base_url = sqlx::query_scalar!("SELECT base_url FROM folders WHERE id = ?", folder_id)
+        .fetch_optional(pool)
+        .await?;
+    Ok(base_url.flatten())
+}
+
+/// The variables of a folder, empty for requests outside any folder.
+pub async fn load_variables(
+    pool: &DbPool,
+    folder_id: Option<i64>,
+) -> Result<HashMap<String, String>, sqlx::Error> {
+    let Some(folder_id) = folder_id else {
+        return Ok(HashMap::new());
+    };
+    let variables = sqlx::query_scalar!("SELECT variables FROM folders WHERE id = ?", folder_id)
+        .fetch_optional(pool)
+        .await?;
+    Ok(variables
+        .and_then(|v| serde_json::from_str(&v).ok())
+        .unwrap_or_default())
+}
+
 async fn create_folder(
     State(pool): State<DbPool>,
+    actor: Actor,
     Json(payload): Json<CreateFolder>,
 ) -> Result<impl IntoResponse, FolderError> {
     log::debug!("Creating folder with name: {}", payload.name);
@@ -99,14 +256,30 @@ async fn create_folder(
         log::warn!("Attempted to create folder with empty name");
         return Err(FolderError::InvalidName);
     }
+    validate_variables(payload.variables.as_deref())?;
+    validate_base_url(payload.base_url.as_deref())?;
 
+    let variables = payload.variables.unwrap_or_else(|| "{}".to_string());
     let folder_db = sqlx::query_as!(
         FolderDb,
-        "INSERT INTO folders (name) VALUES (?) RETURNING id, name, created_at, updated_at, archived_at",
-        payload.name
+        "INSERT INTO folders (name, variables, description, base_url) VALUES (?, ?, NULLIF(?, ''), NULLIF(TRIM(?), '')) RETURNING id, name, variables, description, base_url, created_at, updated_at, archived_at",
+        payload.name,
+        variables,
+        payload.description,
+        payload.base_url
     )
     .fetch_one(&pool)
     .await?;
+    audit::record(
+        &pool,
+        &actor,
+        Entity::Folder,
+        folder_db.id,
+        Action::Create,
+        None,
+        Some(&folder_db),
+    )
+    .await;
 
     log::info!(
         "Created folder: id={}, name={}",
@@ -128,14 +301,14 @@ async fn list_folders(
     let folders_db = if query.include_archived {
         sqlx::query_as!(
             FolderDb,
-            "SELECT id, name, created_at, updated_at, archived_at FROM folders"
+            "SELECT id, name, variables, description, base_url, created_at, updated_at, archived_at FROM folders"
         )
         .fetch_all(&pool)
         .await?
     } else {
         sqlx::query_as!(
             FolderDb,
-            "SELECT id, name, created_at, updated_at, archived_at FROM folders WHERE archived_at IS NULL"
+            "SELECT id, name, variables, description, base_url, created_at, updated_at, archived_at FROM folders WHERE archived_at IS NULL"
         )
         .fetch_all(&pool)
         .await?
@@ -147,6 +320,17 @@ async fn list_folders(
     Ok(Json(folders))
 }
 
+/// Loads a single folder row, archived or not.
+pub async fn load_folder(pool: &DbPool, id: i64) -> Result<FolderDb, sqlx::Error> {
+    sqlx::query_as!(
+        FolderDb,
+        "SELECT id, name, variables, description, base_url, created_at, updated_at, archived_at FROM folders WHERE id = ?",
+        id
+    )
+    .fetch_one(pool)
+    .await
+}
+
 async fn get_folder(
     State(pool): State<DbPool>,
     Path(id): Path<i64>,
@@ -155,7 +339,7 @@ async fn get_folder(
 
     let folder_db = sqlx::query_as!(
         FolderDb,
-        "SELECT id, name, created_at, updated_at, archived_at FROM folders WHERE id = ?",
+        "SELECT id, name, variables, description, base_url, created_at, updated_at, archived_at FROM folders WHERE id = ?",
         id
     )
     .fetch_one(&pool)
@@ -168,6 +352,7 @@ async fn get_folder(
 async fn update_folder(
     State(pool): State<DbPool>,
     Path(id): Path<i64>,
+    actor: Actor,
     Json(payload): Json<UpdateFolder>,
 ) -> Result<impl IntoResponse, FolderError> {
     log::debug!("Updating folder id={} with name: {}", id, payload.name);
@@ -176,15 +361,33 @@ async fn update_folder(
         log::warn!("Attempted to update folder {} with empty name", id);
         return Err(FolderError::InvalidName);
     }
+    validate_variables(payload.variables.as_deref())?;
+    validate_base_url(payload.base_url.as_deref())?;
 
+    let before = load_folder(&pool, id).await?;
     let folder_db = sqlx::query_as!(
         FolderDb,
-        "UPDATE folders SET name = ? WHERE id = ? RETURNING id, name, created_at, updated_at, archived_at",
+        "UPDATE folders SET name = ?, variables = COALESCE(?, variables), description = CASE WHEN ? IS NULL THEN description ELSE NULLIF(?, '') END, base_url = CASE WHEN ? IS NULL THEN base_url ELSE NULLIF(TRIM(?), '') END WHERE id = ? RETURNING id, name, variables, description, base_url, created_at, updated_at, archived_at",
         payload.name,
+        payload.variables,
+        payload.description,
+        payload.description,
+        payload.base_url,
+        payload.base_url,
         id
     )
     .fetch_one(&pool)
     .await?;
+    audit::record(
+        &pool,
+        &actor,
+        Entity::Folder,
+        id,
+        Action::Update,
+        Some(&before),
+        Some(&folder_db),
+    )
+    .await;
 
     log::info!(
         "Updated folder: id={}, name={}",
@@ -194,12 +397,30 @@ async fn update_folder(
     Ok(Json(Folder::from(folder_db)))
 }
 
+async fn patch_folder(
+    State(pool): State<DbPool>,
+    Path(id): Path<i64>,
+    actor: Actor,
+    Json(payload): Json<PatchFolder>,
+) -> Result<impl IntoResponse, FolderError> {
+    let stored = load_folder(&pool, id).await?;
+    let update = UpdateFolder {
+        name: payload.name.unwrap_or(stored.name),
+        variables: payload.variables,
+        description: payload.description,
+        base_url: payload.base_url,
+    };
+    update_folder(State(pool), Path(id), actor, Json(update)).await
+}
+
 async fn archive_folder(
     State(pool): State<DbPool>,
     Path(id): Path<i64>,
+    actor: Actor,
 ) -> Result<impl IntoResponse, FolderError> {
     log::debug!("Archiving folder id: {}", id);
 
+    let before = audit::found(load_folder(&pool, id).await)?;
     let now = Utc::now().naive_utc();
     let result = sqlx::query("UPDATE folders SET archived_at = ? WHERE id = ?")
         .bind(now)
@@ -211,6 +432,7 @@ async fn archive_folder(
         log::warn!("Folder not found for archiving: id={}", id);
         return Err(FolderError::FolderNotFound);
     }
+    audit_state_change(&pool, &actor, id, Action::Archive, before).await;
 
     log::info!("Archived folder: id={}", id);
     Ok(StatusCode::OK)
@@ -219,9 +441,11 @@ async fn archive_folder(
 async fn unarchive_folder(
     State(pool): State<DbPool>,
     Path(id): Path<i64>,
+    actor: Actor,
 ) -> Result<impl IntoResponse, FolderError> {
     log::debug!("Unarchiving folder id: {}", id);
 
+    let before = audit::found(load_folder(&pool, id).await)?;
     let result = sqlx::query("UPDATE folders SET archived_at = NULL WHERE id = ?")
         .bind(id)
         .execute(&pool)
@@ -231,6 +455,7 @@ async fn unarchive_folder(
         log::warn!("Folder not found for unarchiving: id={}", id);
         return Err(FolderError::FolderNotFound);
     }
+    audit_state_change(&pool, &actor, id, Action::Unarchive, before).await;
 
     log::info!("Unarchived folder: id={}", id);
     Ok(StatusCode::OK)
@@ -239,9 +464,13 @@ async fn unarchive_folder(
 async fn delete_folder(
     State(pool): State<DbPool>,
     Path(id): Path<i64>,
+    actor: Actor,
 ) -> Result<impl IntoResponse, FolderError> {
     log::debug!("Deleting folder id: {}", id);
 
+    let before = audit::found(load_folder(&pool, id).await)?;
+    // The folder's requests are deleted along with it
+    let requests_db = requests::load_folder_requests(&pool, id).await?;
     let result = sqlx::query("DELETE FROM folders WHERE id = ?")
         .bind(id)
         .execute(&pool)
@@ -251,17 +480,63 @@ async fn delete_folder(
         log::warn!("Folder not found for deletion: id={}", id);
         return Err(FolderError::FolderNotFound);
     }
+    for request_db in &requests_db {
+        audit::record(
+            &pool,
+            &actor,
+            Entity::Request,
+            request_db.id,
+            Action::Delete,
+            Some(request_db),
+            None,
+        )
+        .await;
+    }
+    audit::record(
+        &pool,
+        &actor,
+        Entity::Folder,
+        id,
+        Action::Delete,
+        before.as_ref(),
+        None,
+    )
+    .await;
 
     log::info!("Deleted folder: id={}", id);
     Ok(StatusCode::NO_CONTENT)
 }
 
+/// Records an archive or unarchive with the row as it was and is now.
+async fn audit_state_change(
+    pool: &DbPool,
+    actor: &Actor,
+    id: i64,
+    action: Action,
+    before: Option<FolderDb>,
+) {
+    let after = audit::found(load_folder(pool, id).await).ok().flatten();
+    audit::record(
+        pool,
+        actor,
+        Entity::Folder,
+        id,
+        action,
+        before.as_ref(),
+        after.as_ref(),
+    )
+    .await;
+}
+
 pub fn routes(pool: DbPool) -> Router {
     Router::new()
         .route("/folders", post(create_folder).get(list_folders))
         .route(
             "/folders/:id",
-            get(get_folder).put(update_folder).delete(delete_folder),
+            get(get_folder)
+                .put(update_folder)
+                .patch(patch_folder)
+                .delete(delete_folder),
         )
         .route("/folders/:id/archive", put(archive_folder))
         .route("/folders/:id/unarchive", put(unarchive_folder))
@@ -279,7 +554,7 @@ mod tests {
     async fn create_test_folder(pool: &DbPool, name: &str) -> FolderDb {
         sqlx::query_as!(
             FolderDb,
-            "INSERT INTO folders (name) VALUES (?) RETURNING id, name, created_at, updated_at, archived_at",
+            "INSERT INTO folders (name) VALUES (?) RETURNING id, name, variables, description, base_url, created_at, updated_at, archived_at",
             name
         )
         .fetch_one(pool)
@@ -409,6 +684,104 @@ mod tests {
         assert_eq!(updated_folder.name, "updated name");
     }
 
+    #[tokio::test]
+    async fn test_folder_variables() {
+        let pool = db::create_test_pool().await;
+        let server = TestServer::new(routes(pool.clone())).unwrap();
+
+        let folder: Folder = server
+            .post("/folders")
+            .json(&json!({ "name": "Billing", "variables": r#"{"version":"v2"}"# }))
+            .await
+            .json();
+        assert_eq!(folder.variables, r#"{"version":"v2"}"#);
+
+        // Renaming keeps the variables
+        let folder: Folder = server
+            .put(&format!("/folders/{}", folder.id))
+            .json(&json!({ "name": "Billing API" }))
+            .await
+            .json();
+        assert_eq!(folder.variables, r#"{"version":"v2"}"#);
+        assert_eq!(
+            load_variables(&pool, Some(folder.id)).await.unwrap()["version"],
+            "v2"
+        );
+        assert!(load_variables(&pool, None).await.unwrap().is_empty());
+
+        server
+            .put(&format!("/folders/{}", folder.id))
+            .json(&json!({ "name": "Billing API", "variables": "[1, 2]" }))
+            .await
+            .assert_status(StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_folder_base_url() {
+        let pool = db::create_test_pool().await;
+        let server = TestServer::new(routes(pool.clone())).unwrap();
+
+        let folder: Folder = server
+            .post("/folders")
+            .json(&json!({ "name": "Billing", "base_url": "https://staging.example.com/v2/" }))
+            .await
+            .json();
+        let base_url = load_base_url(&pool, Some(folder.id)).await.unwrap();
+        assert_eq!(base_url.as_deref(), Some("https://staging.example.com/v2/"));
+        assert_eq!(
+            join_base_url(base_url.as_deref(), "/invoices?page=2"),
+            "https://staging.example.com/v2/invoices?page=2"
+        );
+        assert_eq!(
+            join_base_url(base_url.as_deref(), "invoices"),
+            "https://staging.example.com/v2/invoices"
+        );
+        assert_eq!(
+            join_base_url(base_url.as_deref(), "?page=2"),
+            "https://staging.example.com/v2?page=2"
+        );
+        // A URL in the query string does not make the request URL absolute
+        assert_eq!(
+            join_base_url(base_url.as_deref(), "/login?next=https://app"),
+            "https://staging.example.com/v2/login?next=https://app"
+        );
+        // Absolute URLs and those starting with a variable are left alone
+        assert_eq!(
+            join_base_url(base_url.as_deref(), "https://other.example.com/x"),
+            "https://other.example.com/x"
+        );
+        assert_eq!(
+            join_base_url(base_url.as_deref(), "{{base}}/invoices"),
+            "{{base}}/invoices"
+        );
+        assert_eq!(join_base_url(None, "/invoices"), "/invoices");
+
+        server
+            .patch(&format!("/folders/{}", folder.id))
+            .json(&json!({ "base_url": "staging.example.com" }))
+            .await
+            .assert_status(StatusCode::BAD_REQUEST);
+        let folder: Folder = server
+            .patch(&format!("/folders/{}", folder.id))
+            .json(&json!({ "base_url": "{{host}}/v3" }))
+            .await
+            .json();
+        assert_eq!(folder.base_url.as_deref(), Some("{{host}}/v3"));
+        // Renaming keeps it, an empty string clears it
+        let folder: Folder = server
+            .put(&format!("/folders/{}", folder.id))
+            .json(&json!({ "name": "Billing API" }))
+            .await
+            .json();
+        assert_eq!(folder.base_url.as_deref(), Some("{{host}}/v3"));
+        let folder: Folder = server
+            .put(&format!("/folders/{}", folder.id))
+            .json(&json!({ "name": "Billing API", "base_url": "" }))
+            .await
+            .json();
+        assert_eq!(folder.base_url, None);
+    }
+
     #[tokio::test]
     async fn test_update_folder_not_found() {
         let pool = db::create_test_pool().await;
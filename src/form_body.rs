@@ -0,0 +1,95 @@
+use serde::{Deserialize, Serialize};
+
+/// One field of a `form` body. The `body_content` of such a request is a
+/// JSON array of these, sent in order.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FormRow {
+    pub key: String,
+    #[serde(default)]
+    pub value: String,
+    /// Disabled rows stay on the request but are not sent
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+impl FormRow {
+    pub fn new(key: impl Into<String>, value: impl Into<String>) -> Self {
+        FormRow {
+            key: key.into(),
+            value: value.into(),
+            enabled: true,
+        }
+    }
+}
+
+/// Reads the rows of a `form` body. Bodies saved before rows existed are a
+/// JSON object, read in key order.
+pub fn parse(content: &str) -> Result<Vec<FormRow>, String> {
+    if content.trim().is_empty() {
+        return Ok(Vec::new());
+    }
+    if let Ok(rows) = serde_json::from_str::<Vec<FormRow>>(content) {
+        return Ok(rows);
+    }
+    serde_json::from_str::<serde_json::Map<String, serde_json::Value>>(content)
+        .map(|fields| {
+            fields
+                .into_iter()
+                .map(|(key, value)| match value {
+                    serde_json::Value::String(s) => FormRow::new(key, s),
+                    other => FormRow::new(key, other.to_string()),
+                })
+                .collect()
+        })
+        .map_err(|_| {
+            "Form body must be a list of {\"key\", \"value\", \"enabled\"} rows".to_string()
+        })
+}
+
+/// `application/x-www-form-urlencoded` encoding of the enabled rows.
+pub fn encode(rows: &[FormRow]) -> String {
+    rows.iter()
+        .filter(|row| row.enabled && !row.key.is_empty())
+        .map(|row| {
+            format!(
+                "{}={}",
+                urlencoding::encode(&row.key),
+                urlencoding::encode(&row.value)
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("&")
+}
+
+pub fn to_json(rows: &[FormRow]) -> String {
+    serde_json::to_string(rows).unwrap_or_else(|_| "[]".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_and_encode() {
+        let rows = parse(
+            r#"[{"key":"b","value":"2 & 3"},{"key":"a","value":"x","enabled":false},{"key":"a","value":"é"}]"#,
+        )
+        .unwrap();
+        assert_eq!(rows.len(), 3);
+        assert!(rows[0].enabled);
+        assert_eq!(encode(&rows), "b=2%20%26%203&a=%C3%A9");
+
+        let legacy = parse(r#"{"name":"Ada","age":36}"#).unwrap();
+        assert_eq!(
+            legacy,
+            vec![FormRow::new("age", "36"), FormRow::new("name", "Ada")]
+        );
+        assert!(parse("").unwrap().is_empty());
+        assert!(parse("a=1&b=2").is_err());
+        assert_eq!(parse(&to_json(&rows)).unwrap(), rows);
+    }
+}
@@ -0,0 +1,727 @@
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    routing::post,
+    Json, Router,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use std::collections::BTreeMap;
+
+use crate::{
+    db::DbPool,
+    i18n,
+    importers::{self, ParsedRequest},
+    requests::{self, Request},
+};
+
+/// Marks a string id value in the generated body.
+const STRING_PLACEHOLDER: &str = "\u{0}jsl-string:";
+/// Marks a numeric id value, which is substituted without quotes.
+const NUMBER_PLACEHOLDER: &str = "\u{0}jsl-number:";
+
+#[derive(Deserialize)]
+pub struct GenerateFromExample {
+    /// A JSON payload or response; for arrays the first element is used.
+    example: Value,
+    url: String,
+    #[serde(default)]
+    method: Option<String>,
+    #[serde(default)]
+    name: Option<String>,
+    /// Existing folder to add the request to.
+    #[serde(default)]
+    folder_id: Option<i64>,
+    /// Name of a new folder, used when `folder_id` is absent.
+    #[serde(default)]
+    folder_name: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct GeneratedRequest {
+    pub folder_id: i64,
+    pub request: Request,
+    /// JSON Schema inferred from the example.
+    pub schema: Value,
+    /// Placeholders introduced for ids, with the example values they replaced.
+    pub variables: BTreeMap<String, Value>,
+}
+
+#[derive(Deserialize)]
+pub struct GenerateCrud {
+    base_url: String,
+    /// Collection path below `base_url`, e.g. `users` or `v1/users`.
+    resource: String,
+    /// Example resource, used as the create and update body.
+    #[serde(default)]
+    example: Option<Value>,
+    /// Defaults to the resource name.
+    #[serde(default)]
+    folder_name: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct GeneratedCrud {
+    pub folder_id: i64,
+    /// List, get, create, update and delete, in that order.
+    pub requests: Vec<Request>,
+    pub variables: BTreeMap<String, Value>,
+}
+
+pub enum GenerateError {
+    InvalidExample(String),
+    InvalidResource,
+    InvalidUrl(String),
+    FolderNotFound,
+    DatabaseError(#[allow(dead_code)] sqlx::Error),
+}
+
+impl From<sqlx::Error> for GenerateError {
+    fn from(e: sqlx::Error) -> Self {
+        match e {
+            sqlx::Error::RowNotFound => GenerateError::FolderNotFound,
+            _ => GenerateError::DatabaseError(e),
+        }
+    }
+}
+
+impl From<anyhow::Error> for GenerateError {
+    fn from(e: anyhow::Error) -> Self {
+        match e.downcast::<sqlx::Error>() {
+            Ok(e) => GenerateError::DatabaseError(e),
+            Err(e) => GenerateError::InvalidExample(e.to_string()),
+        }
+    }
+}
+
+impl IntoResponse for GenerateError {
+    fn into_response(self) -> Response {
+        match self {
+            GenerateError::InvalidExample(msg) => (
+                StatusCode::BAD_REQUEST,
+                i18n::tf("error.invalid_example", &[("message", &msg)]),
+            )
+                .into_response(),
+            GenerateError::InvalidResource => {
+                (StatusCode::BAD_REQUEST, i18n::t("error.resource_required")).into_response()
+            }
+            GenerateError::InvalidUrl(msg) => (
+                StatusCode::BAD_REQUEST,
+                i18n::tf("error.invalid_url", &[("message", &msg)]),
+            )
+                .into_response(),
+            GenerateError::FolderNotFound => {
+                (StatusCode::NOT_FOUND, i18n::t("error.folder_not_found")).into_response()
+            }
+            GenerateError::DatabaseError(_) => {
+                (StatusCode::INTERNAL_SERVER_ERROR, i18n::t("error.database")).into_response()
+            }
+        }
+    }
+}
+
+fn is_id_key(key: &str) -> bool {
+    let lower = key.to_ascii_lowercase();
+    matches!(lower.as_str(), "id" | "uuid" | "guid")
+        || lower.ends_with("_id")
+        || lower.ends_with("_uuid")
+        || key.ends_with("Id")
+        || key.ends_with("Uuid")
+}
+
+fn is_uuid(value: &str) -> bool {
+    value.len() == 36
+        && value.char_indices().all(|(i, c)| match i {
+            8 | 13 | 18 | 23 => c == '-',
+            _ => c.is_ascii_hexdigit(),
+        })
+}
+
+/// Naive singular form of a resource name ("users" -> "user").
+fn singular(name: &str) -> String {
+    if let Some(stem) = name.strip_suffix("ies") {
+        format!("{}y", stem)
+    } else if let Some(stem) = name.strip_suffix("ses") {
+        format!("{}s", stem)
+    } else if name.len() > 1 && name.ends_with('s') && !name.ends_with("ss") {
+        name[..name.len() - 1].to_string()
+    } else {
+        name.to_string()
+    }
+}
+
+fn variable_name(name: &str) -> String {
+    name.chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect()
+}
+
+/// Replaces id-like values with placeholder markers, collecting the variables.
+/// Nested ids are named after their parent key (`customer.id` -> `customer_id`).
+fn templatize(
+    value: &Value,
+    parent: Option<&str>,
+    variables: &mut BTreeMap<String, Value>,
+) -> Value {
+    match value {
+        Value::Object(map) => Value::Object(
+            map.iter()
+                .map(|(key, v)| {
+                    let templated = match v {
+                        Value::String(_) | Value::Number(_) if is_id_key(key) => {
+                            let name = match (parent, key.as_str()) {
+                                (Some(parent), "id" | "uuid" | "guid") => {
+                                    format!("{}_{}", singular(parent), key)
+                                }
+                                _ => key.clone(),
+                            };
+                            let name = variable_name(&name);
+                            variables.insert(name.clone(), v.clone());
+                            let marker = if v.is_number() {
+                                NUMBER_PLACEHOLDER
+                            } else {
+                                STRING_PLACEHOLDER
+                            };
+                            Value::String(format!("{}{}", marker, name))
+                        }
+                        _ => templatize(v, Some(key), variables),
+                    };
+                    (key.clone(), templated)
+                })
+                .collect(),
+        ),
+        Value::Array(items) => Value::Array(
+            items
+                .iter()
+                .map(|item| templatize(item, parent, variables))
+                .collect(),
+        ),
+        other => other.clone(),
+    }
+}
+
+/// Pretty-prints a templatized body, turning markers into `{{variable}}`
+/// placeholders: quoted for string ids, bare for numeric ones.
+fn render_body(body: &Value) -> String {
+    let text = serde_json::to_string_pretty(body).unwrap_or_default();
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text.as_str();
+    while let Some(start) = rest.find("\"\\u0000jsl-") {
+        out.push_str(&rest[..start]);
+        let marker = &rest[start + 1..];
+        let end = marker.find('"').map(|i| i + 1).unwrap_or(marker.len());
+        let inner = &marker[..end - 1];
+        if let Some(name) = inner.strip_prefix("\\u0000jsl-number:") {
+            out.push_str(&format!("{{{{{}}}}}", name));
+        } else if let Some(name) = inner.strip_prefix("\\u0000jsl-string:") {
+            out.push_str(&format!("\"{{{{{}}}}}\"", name));
+        } else {
+            out.push_str(&rest[start..start + 1 + end]);
+        }
+        rest = &marker[end..];
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Replaces numeric and UUID path segments with placeholders named after the
+/// preceding segment (`/users/42` -> `/users/{{user_id}}`).
+fn templatize_url(
+    url: &str,
+    variables: &mut BTreeMap<String, Value>,
+) -> Result<String, GenerateError> {
+    if url.is_empty() {
+        return Err(GenerateError::InvalidUrl("a URL is required".to_string()));
+    }
+    let (base, suffix) = url.split_at(url.find(['?', '#']).unwrap_or(url.len()));
+    let path_start = match base.find("://") {
+        Some(i) => base[i + 3..].find('/').map_or(base.len(), |j| i + 3 + j),
+        None => base.find('/').unwrap_or(base.len()),
+    };
+    let (prefix, path) = base.split_at(path_start);
+
+    let mut previous: Option<&str> = None;
+    let segments: Vec<String> = path
+        .split('/')
+        .map(|segment| {
+            let is_id = !segment.is_empty()
+                && (segment.chars().all(|c| c.is_ascii_digit()) || is_uuid(segment));
+            let out = if is_id {
+                let name = match previous {
+                    Some(prev) => format!("{}_id", variable_name(&singular(prev))),
+                    None => "id".to_string(),
+                };
+                let example = segment
+                    .parse::<i64>()
+                    .map(Value::from)
+                    .unwrap_or_else(|_| Value::from(segment));
+                variables.insert(name.clone(), example);
+                format!("{{{{{}}}}}", name)
+            } else {
+                segment.to_string()
+            };
+            if !segment.is_empty() {
+                previous = Some(segment);
+            }
+            out
+        })
+        .collect();
+
+    Ok(format!("{}{}{}", prefix, segments.join("/"), suffix))
+}
+
+fn string_format(value: &str) -> Option<&'static str> {
+    if chrono::DateTime::parse_from_rfc3339(value).is_ok() {
+        Some("date-time")
+    } else if chrono::NaiveDate::parse_from_str(value, "%Y-%m-%d").is_ok() {
+        Some("date")
+    } else if is_uuid(value) {
+        Some("uuid")
+    } else if value.starts_with("http://") || value.starts_with("https://") {
+        Some("uri")
+    } else if value.contains('@') && !value.contains(' ') && value.contains('.') {
+        Some("email")
+    } else {
+        None
+    }
+}
+
+/// Combines the schemas of array elements: object properties are unioned and
+/// only keys present in every element stay required.
+fn merge_schemas(a: Value, b: Value) -> Value {
+    match (a, b) {
+        (Value::Object(mut a), Value::Object(b))
+            if a.get("type") == Some(&json!("object"))
+                && b.get("type") == Some(&json!("object")) =>
+        {
+            let b_required: Vec<Value> = b
+                .get("required")
+                .and_then(Value::as_array)
+                .cloned()
+                .unwrap_or_default();
+            if let Some(Value::Array(required)) = a.get_mut("required") {
+                required.retain(|key| b_required.contains(key));
+            }
+            if let (Some(Value::Object(props)), Some(Value::Object(b_props))) =
+                (a.get_mut("properties"), b.get("properties"))
+            {
+                for (key, schema) in b_props {
+                    let merged = match props.remove(key) {
+                        Some(existing) => merge_schemas(existing, schema.clone()),
+                        None => schema.clone(),
+                    };
+                    props.insert(key.clone(), merged);
+                }
+            }
+            Value::Object(a)
+        }
+        (a, _) => a,
+    }
+}
+
+/// Infers a JSON Schema (draft 2020-12) describing `value`.
+pub fn infer_schema(value: &Value) -> Value {
+    match value {
+        Value::Null => json!({ "type": "null" }),
+        Value::Bool(_) => json!({ "type": "boolean" }),
+        Value::Number(n) if n.is_i64() || n.is_u64() => json!({ "type": "integer" }),
+        Value::Number(_) => json!({ "type": "number" }),
+        Value::String(s) => match string_format(s) {
+            Some(format) => json!({ "type": "string", "format": format }),
+            None => json!({ "type": "string" }),
+        },
+        Value::Array(items) => {
+            let items_schema = items
+                .iter()
+                .map(infer_schema)
+                .reduce(merge_schemas)
+                .unwrap_or_else(|| json!({}));
+            json!({ "type": "array", "items": items_schema })
+        }
+        Value::Object(map) => {
+            let properties: Map<String, Value> = map
+                .iter()
+                .map(|(key, v)| (key.clone(), infer_schema(v)))
+                .collect();
+            let required: Vec<&String> = map
+                .iter()
+                .filter(|(_, v)| !v.is_null())
+                .map(|(key, _)| key)
+                .collect();
+            json!({ "type": "object", "properties": properties, "required": required })
+        }
+    }
+}
+
+/// Returns the folder to generate into, creating it when no id is given.
+async fn target_folder(
+    pool: &DbPool,
+    folder_id: Option<i64>,
+    folder_name: Option<&str>,
+) -> Result<i64, GenerateError> {
+    match folder_id {
+        Some(id) => {
+            sqlx::query_scalar!(
+                "SELECT id FROM folders WHERE id = ? AND archived_at IS NULL",
+                id
+            )
+            .fetch_one(pool)
+            .await?;
+            Ok(id)
+        }
+        None => {
+            let name = folder_name
+                .map(str::trim)
+                .filter(|n| !n.is_empty())
+                .unwrap_or("Generated");
+            Ok(importers::create_folder(pool, name).await?)
+        }
+    }
+}
+
+async fn generate_from_example(
+    State(pool): State<DbPool>,
+    Json(payload): Json<GenerateFromExample>,
+) -> Result<impl IntoResponse, GenerateError> {
+    log::info!(
+        "Generating request from example: url={}, folder_id={:?}",
+        payload.url,
+        payload.folder_id
+    );
+
+    let item = match &payload.example {
+        Value::Array(items) => items.first().cloned().unwrap_or(Value::Null),
+        other => other.clone(),
+    };
+    if !matches!(item, Value::Object(_)) {
+        log::warn!("Rejected example that is not a JSON object");
+        return Err(GenerateError::InvalidExample(
+            "expected a JSON object, or an array of objects".to_string(),
+        ));
+    }
+
+    let mut variables = BTreeMap::new();
+    let url = templatize_url(payload.url.trim(), &mut variables)?;
+    let body = render_body(&templatize(&item, None, &mut variables));
+    let method = payload
+        .method
+        .map(|m| m.to_uppercase())
+        .unwrap_or_else(|| "POST".to_string());
+    let name = payload
+        .name
+        .filter(|n| !n.trim().is_empty())
+        .unwrap_or_else(|| {
+            let resource = url
+                .rsplit('/')
+                .find(|s| !s.is_empty() && !s.starts_with("{{"))
+                .unwrap_or("request");
+            format!("{} {}", method, resource)
+        });
+
+    let folder_id = target_folder(&pool, payload.folder_id, payload.folder_name.as_deref()).await?;
+    let parsed = ParsedRequest {
+        name,
+        method,
+        url,
+        body: Some(body),
+        body_type: "json".to_string(),
+        headers: Vec::new(),
+        auth_type: "none".to_string(),
+        auth_token: None,
+        auth_username: None,
+        auth_password: None,
+    };
+    let id = importers::create_request(&pool, &parsed, Some(folder_id)).await?;
+    let request = Request::from(requests::load_request(&pool, id).await?);
+    log::info!(
+        "Generated request {} with {} placeholder(s)",
+        id,
+        variables.len()
+    );
+
+    Ok((
+        StatusCode::CREATED,
+        Json(GeneratedRequest {
+            folder_id,
+            request,
+            schema: infer_schema(&payload.example),
+            variables,
+        }),
+    ))
+}
+
+fn json_request(name: String, method: &str, url: String, body: Option<String>) -> ParsedRequest {
+    ParsedRequest {
+        name,
+        method: method.to_string(),
+        url,
+        body_type: if body.is_some() { "json" } else { "none" }.to_string(),
+        body,
+        headers: Vec::new(),
+        auth_type: "none".to_string(),
+        auth_token: None,
+        auth_username: None,
+        auth_password: None,
+    }
+}
+
+/// Creates list/get/create/update/delete requests for a REST resource. The
+/// create request captures the new id into `{{<resource>_id}}`, which the
+/// other item requests use as their path variable.
+async fn generate_crud(
+    State(pool): State<DbPool>,
+    Json(payload): Json<GenerateCrud>,
+) -> Result<impl IntoResponse, GenerateError> {
+    log::info!(
+        "Generating CRUD requests: base_url={}, resource={}",
+        payload.base_url,
+        payload.resource
+    );
+
+    let resource = payload.resource.trim().trim_matches('/');
+    let Some(collection) = resource.rsplit('/').next().filter(|s| !s.is_empty()) else {
+        log::warn!("Rejected CRUD generation without a resource name");
+        return Err(GenerateError::InvalidResource);
+    };
+    let item_name = singular(collection);
+    let id_variable = format!("{}_id", variable_name(&item_name));
+
+    let mut variables = BTreeMap::new();
+    let base_url = payload.base_url.trim().trim_end_matches('/');
+    let collection_url = templatize_url(&format!("{}/{}", base_url, resource), &mut variables)?;
+    let item_url = format!("{}/{{{{{}}}}}", collection_url, id_variable);
+
+    let mut id_key = "id".to_string();
+    let body = match payload.example {
+        Some(example) => {
+            let item = match example {
+                Value::Array(items) => items.into_iter().next().unwrap_or(Value::Null),
+                other => other,
+            };
+            let Value::Object(mut fields) = item else {
+                log::warn!("Rejected example that is not a JSON object");
+                return Err(GenerateError::InvalidExample(
+                    "expected a JSON object, or an array of objects".to_string(),
+                ));
+            };
+            // The id is assigned by the server, so it is captured rather than sent
+            if let Some(key) = ["id", "uuid", "guid"]
+                .into_iter()
+                .find(|k| fields.contains_key(*k))
+            {
+                id_key = key.to_string();
+                let example_id = fields.remove(key).unwrap_or(Value::Null);
+                variables.insert(id_variable.clone(), example_id);
+            }
+            Some(render_body(&templatize(
+                &Value::Object(fields),
+                None,
+                &mut variables,
+            )))
+        }
+        None => None,
+    };
+    variables.entry(id_variable.clone()).or_insert(Value::Null);
+
+    let folder_name = payload
+        .folder_name
+        .unwrap_or_else(|| collection.to_string());
+    let folder_id = target_folder(&pool, None, Some(&folder_name)).await?;
+
+    let scaffold = [
+        json_request(
+            format!("List {}", collection),
+            "GET",
+            collection_url.clone(),
+            None,
+        ),
+        json_request(format!("Get {}", item_name), "GET", item_url.clone(), None),
+        json_request(
+            format!("Create {}", item_name),
+            "POST",
+            collection_url,
+            body.clone(),
+        ),
+        json_request(
+            format!("Update {}", item_name),
+            "PUT",
+            item_url.clone(),
+            body,
+        ),
+        json_request(format!("Delete {}", item_name), "DELETE", item_url, None),
+    ];
+
+    let captures = json!([{
+        "variable": id_variable,
+        "source": "body",
+        "expression": format!("$.{}", id_key),
+    }])
+    .to_string();
+
+    let mut requests = Vec::new();
+    for parsed in &scaffold {
+        let id = importers::create_request(&pool, parsed, Some(folder_id)).await?;
+        if parsed.method == "POST" {
+            sqlx::query!(
+                "UPDATE requests SET captures = ? WHERE id = ?",
+                captures,
+                id
+            )
+            .execute(&pool)
+            .await?;
+        }
+        requests.push(Request::from(requests::load_request(&pool, id).await?));
+    }
+    log::info!(
+        "Generated {} CRUD requests in folder {}",
+        requests.len(),
+        folder_id
+    );
+
+    Ok((
+        StatusCode::CREATED,
+        Json(GeneratedCrud {
+            folder_id,
+            requests,
+            variables,
+        }),
+    ))
+}
+
+pub fn routes(pool: DbPool) -> Router {
+    Router::new()
+        .route("/generate/from-example", post(generate_from_example))
+        .route("/generate/crud", post(generate_crud))
+        .with_state(pool)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db;
+    use axum_test::TestServer;
+
+    #[test]
+    fn test_render_body_placeholders() {
+        let mut variables = BTreeMap::new();
+        let body = templatize(
+            &json!({ "id": 7, "name": "Ada", "customer": { "id": "c-1" }, "orderId": 3 }),
+            None,
+            &mut variables,
+        );
+        let rendered = render_body(&body);
+        assert!(rendered.contains("\"id\": {{id}}"));
+        assert!(rendered.contains("\"id\": \"{{customer_id}}\""));
+        assert!(rendered.contains("\"orderId\": {{orderId}}"));
+        assert!(rendered.contains("\"name\": \"Ada\""));
+        assert_eq!(variables["customer_id"], json!("c-1"));
+        assert_eq!(variables.len(), 3);
+    }
+
+    #[test]
+    fn test_infer_schema() {
+        let schema = infer_schema(&json!([
+            { "id": 1, "email": "a@example.com", "tags": ["x"], "note": null },
+            { "id": 2, "email": "b@example.com", "tags": [], "score": 1.5 }
+        ]));
+        let items = &schema["items"];
+        assert_eq!(items["type"], "object");
+        assert_eq!(items["properties"]["id"]["type"], "integer");
+        assert_eq!(items["properties"]["email"]["format"], "email");
+        assert_eq!(items["properties"]["score"]["type"], "number");
+        assert_eq!(items["required"], json!(["email", "id", "tags"]));
+    }
+
+    #[tokio::test]
+    async fn test_generate_from_example() {
+        let pool = db::create_test_pool().await;
+        let server = TestServer::new(routes(pool)).unwrap();
+
+        let response = server
+            .post("/generate/from-example")
+            .json(&json!({
+                "url": "https://api.example.com/users/42/orders",
+                "example": { "id": 1001, "user_id": 42, "total": 9.5 },
+                "folder_name": "Orders"
+            }))
+            .await;
+        response.assert_status(StatusCode::CREATED);
+        let generated: GeneratedRequest = response.json();
+        assert_eq!(
+            generated.request.url,
+            "https://api.example.com/users/{{user_id}}/orders"
+        );
+        assert_eq!(generated.request.method, "POST");
+        assert_eq!(generated.request.name, "POST orders");
+        assert_eq!(generated.request.folder_id, Some(generated.folder_id));
+        assert!(generated
+            .request
+            .body
+            .unwrap()
+            .contains("\"user_id\": {{user_id}}"));
+        assert_eq!(generated.schema["properties"]["total"]["type"], "number");
+        assert_eq!(generated.variables["user_id"], json!(42));
+
+        server
+            .post("/generate/from-example")
+            .json(&json!({ "url": "https://api.example.com/x", "example": [1, 2] }))
+            .await
+            .assert_status(StatusCode::BAD_REQUEST);
+        server
+            .post("/generate/from-example")
+            .json(&json!({ "url": "https://api.example.com/x", "example": {}, "folder_id": 999 }))
+            .await
+            .assert_status(StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_generate_crud() {
+        let pool = db::create_test_pool().await;
+        let server = TestServer::new(routes(pool)).unwrap();
+
+        let response = server
+            .post("/generate/crud")
+            .json(&json!({
+                "base_url": "{{base_url}}/",
+                "resource": "/categories",
+                "example": { "id": 3, "name": "Books", "parent_id": 1 }
+            }))
+            .await;
+        response.assert_status(StatusCode::CREATED);
+        let generated: GeneratedCrud = response.json();
+
+        let summary: Vec<(&str, &str)> = generated
+            .requests
+            .iter()
+            .map(|r| (r.method.as_str(), r.url.as_str()))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("GET", "{{base_url}}/categories"),
+                ("GET", "{{base_url}}/categories/{{category_id}}"),
+                ("POST", "{{base_url}}/categories"),
+                ("PUT", "{{base_url}}/categories/{{category_id}}"),
+                ("DELETE", "{{base_url}}/categories/{{category_id}}"),
+            ]
+        );
+        assert_eq!(generated.requests[1].name, "Get category");
+
+        let create = &generated.requests[2];
+        let body = create.body.as_deref().unwrap();
+        assert!(!body.contains("\"id\""));
+        assert!(body.contains("\"parent_id\": {{parent_id}}"));
+        let rules = crate::capture::parse_rules(create.captures.as_deref().unwrap()).unwrap();
+        assert_eq!(rules[0].variable, "category_id");
+        assert_eq!(rules[0].expression, "$.id");
+        assert!(generated.requests[1].captures.is_none());
+        assert_eq!(generated.variables["category_id"], json!(3));
+
+        server
+            .post("/generate/crud")
+            .json(&json!({ "base_url": "http://localhost", "resource": " / " }))
+            .await
+            .assert_status(StatusCode::BAD_REQUEST);
+    }
+}
@@ -0,0 +1,215 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    routing::{get, put},
+    Json, Router,
+};
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+
+use crate::{db::DbPool, i18n};
+
+// Global variables: one set shared by every environment and used beneath
+// the selected environment's variables, or alone when none is selected.
+
+#[derive(Deserialize)]
+pub struct SetGlobal {
+    value: String,
+}
+
+pub enum GlobalsError {
+    InvalidName,
+    VariableNotFound,
+    DatabaseError(#[allow(dead_code)] sqlx::Error),
+}
+
+impl From<sqlx::Error> for GlobalsError {
+    fn from(e: sqlx::Error) -> Self {
+        GlobalsError::DatabaseError(e)
+    }
+}
+
+impl IntoResponse for GlobalsError {
+    fn into_response(self) -> Response {
+        match self {
+            GlobalsError::InvalidName => (
+                StatusCode::BAD_REQUEST,
+                i18n::t("error.invalid_global_name"),
+            )
+                .into_response(),
+            GlobalsError::VariableNotFound => {
+                (StatusCode::NOT_FOUND, i18n::t("error.global_not_found")).into_response()
+            }
+            GlobalsError::DatabaseError(_) => {
+                (StatusCode::INTERNAL_SERVER_ERROR, i18n::t("error.database")).into_response()
+            }
+        }
+    }
+}
+
+/// Names must be usable as `{{name}}`; a leading `$` is reserved for the
+/// built-in generators.
+fn valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && !name.starts_with('$')
+        && !name.contains(['{', '}'])
+        && !name.chars().any(char::is_whitespace)
+}
+
+/// All global variables.
+pub async fn load(pool: &DbPool) -> Result<HashMap<String, String>, sqlx::Error> {
+    let rows = sqlx::query!("SELECT name, value FROM global_variables")
+        .fetch_all(pool)
+        .await?;
+    Ok(rows.into_iter().map(|r| (r.name, r.value)).collect())
+}
+
+/// Sets `variables`, keeping the other globals.
+pub async fn merge(pool: &DbPool, variables: &BTreeMap<String, String>) -> Result<(), sqlx::Error> {
+    let mut tx = pool.begin().await?;
+    for (name, value) in variables {
+        sqlx::query!(
+            "INSERT INTO global_variables (name, value) VALUES (?, ?) ON CONFLICT (name) DO UPDATE SET value = excluded.value, updated_at = CURRENT_TIMESTAMP",
+            name,
+            value
+        )
+        .execute(&mut *tx)
+        .await?;
+    }
+    tx.commit().await
+}
+
+async fn list_globals(State(pool): State<DbPool>) -> Result<impl IntoResponse, GlobalsError> {
+    let rows = sqlx::query!("SELECT name, value FROM global_variables ORDER BY name")
+        .fetch_all(&pool)
+        .await?;
+    let globals: BTreeMap<String, String> = rows.into_iter().map(|r| (r.name, r.value)).collect();
+    Ok(Json(globals))
+}
+
+/// Replaces the whole set.
+async fn replace_globals(
+    State(pool): State<DbPool>,
+    Json(payload): Json<BTreeMap<String, String>>,
+) -> Result<impl IntoResponse, GlobalsError> {
+    if !payload.keys().all(|name| valid_name(name)) {
+        return Err(GlobalsError::InvalidName);
+    }
+
+    let mut tx = pool.begin().await?;
+    sqlx::query!("DELETE FROM global_variables")
+        .execute(&mut *tx)
+        .await?;
+    for (name, value) in &payload {
+        sqlx::query!(
+            "INSERT INTO global_variables (name, value) VALUES (?, ?)",
+            name,
+            value
+        )
+        .execute(&mut *tx)
+        .await?;
+    }
+    tx.commit().await?;
+
+    log::info!("Replaced global variables ({} set)", payload.len());
+    Ok(Json(payload))
+}
+
+async fn set_global(
+    State(pool): State<DbPool>,
+    Path(name): Path<String>,
+    Json(payload): Json<SetGlobal>,
+) -> Result<impl IntoResponse, GlobalsError> {
+    if !valid_name(&name) {
+        return Err(GlobalsError::InvalidName);
+    }
+
+    sqlx::query!(
+        "INSERT INTO global_variables (name, value) VALUES (?, ?) ON CONFLICT (name) DO UPDATE SET value = excluded.value, updated_at = CURRENT_TIMESTAMP",
+        name,
+        payload.value
+    )
+    .execute(&pool)
+    .await?;
+
+    log::info!("Set global variable {}", name);
+    Ok(StatusCode::OK)
+}
+
+async fn delete_global(
+    State(pool): State<DbPool>,
+    Path(name): Path<String>,
+) -> Result<impl IntoResponse, GlobalsError> {
+    let result = sqlx::query!("DELETE FROM global_variables WHERE name = ?", name)
+        .execute(&pool)
+        .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(GlobalsError::VariableNotFound);
+    }
+
+    log::info!("Deleted global variable {}", name);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub fn routes(pool: DbPool) -> Router {
+    Router::new()
+        .route("/globals", get(list_globals).put(replace_globals))
+        .route("/globals/:name", put(set_global).delete(delete_global))
+        .with_state(pool)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db;
+    use axum_test::TestServer;
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn test_globals_crud() {
+        let pool = db::create_test_pool().await;
+        let server = TestServer::new(routes(pool.clone())).unwrap();
+
+        server
+            .put("/globals")
+            .json(&json!({ "base_url": "https://api", "auth": "Basic abc" }))
+            .await
+            .assert_status_ok();
+        server
+            .put("/globals/auth")
+            .json(&json!({ "value": "Basic xyz" }))
+            .await
+            .assert_status_ok();
+        server
+            .put("/globals/tenant")
+            .json(&json!({ "value": "acme" }))
+            .await
+            .assert_status_ok();
+        server
+            .delete("/globals/base_url")
+            .await
+            .assert_status(StatusCode::NO_CONTENT);
+
+        let globals: BTreeMap<String, String> = server.get("/globals").await.json();
+        assert_eq!(globals.len(), 2);
+        assert_eq!(globals["auth"], "Basic xyz");
+        assert_eq!(load(&pool).await.unwrap()["tenant"], "acme");
+
+        server
+            .delete("/globals/base_url")
+            .await
+            .assert_status(StatusCode::NOT_FOUND);
+        server
+            .put("/globals/$uuid")
+            .json(&json!({ "value": "x" }))
+            .await
+            .assert_status(StatusCode::BAD_REQUEST);
+        server
+            .put("/globals")
+            .json(&json!({ "": "x" }))
+            .await
+            .assert_status(StatusCode::BAD_REQUEST);
+    }
+}
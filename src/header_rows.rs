@@ -0,0 +1,109 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// One header of a saved request. The `headers` column is a JSON array of
+/// these, sent in order.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HeaderRow {
+    pub key: String,
+    #[serde(default)]
+    pub value: String,
+    /// Disabled headers stay on the request but are not sent
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+}
+
+/// Parts of header names that mark a header as carrying a credential.
+const SECRET_NAME_PARTS: [&str; 6] = [
+    "authorization",
+    "cookie",
+    "token",
+    "secret",
+    "key",
+    "password",
+];
+
+fn default_enabled() -> bool {
+    true
+}
+
+impl HeaderRow {
+    pub fn new(key: impl Into<String>, value: impl Into<String>) -> Self {
+        HeaderRow {
+            key: key.into(),
+            value: value.into(),
+            enabled: true,
+        }
+    }
+}
+
+/// Reads the `headers` column. Headers saved before rows existed are a JSON
+/// object of enabled headers.
+pub fn parse(headers: &str) -> Result<Vec<HeaderRow>, String> {
+    if headers.trim().is_empty() {
+        return Ok(Vec::new());
+    }
+    if let Ok(rows) = serde_json::from_str::<Vec<HeaderRow>>(headers) {
+        return Ok(rows);
+    }
+    serde_json::from_str::<HashMap<String, String>>(headers)
+        .map(from_map)
+        .map_err(|e| format!("Failed to parse request headers: {}", e))
+}
+
+/// Rows for a map of headers, sorted by name so the order is stable.
+pub fn from_map(headers: HashMap<String, String>) -> Vec<HeaderRow> {
+    let mut rows: Vec<HeaderRow> = headers
+        .into_iter()
+        .map(|(key, value)| HeaderRow::new(key, value))
+        .collect();
+    rows.sort_by(|a, b| a.key.cmp(&b.key));
+    rows
+}
+
+/// The headers that are sent, by name; a later row wins over an earlier one.
+pub fn enabled_map(rows: &[HeaderRow]) -> HashMap<String, String> {
+    rows.iter()
+        .filter(|row| row.enabled && !row.key.is_empty())
+        .map(|row| (row.key.clone(), row.value.clone()))
+        .collect()
+}
+
+/// Whether a header of this name usually carries a credential, like
+/// `Authorization` or `X-Api-Key`.
+pub fn is_secret(name: &str) -> bool {
+    let name = name.to_lowercase();
+    SECRET_NAME_PARTS.iter().any(|part| name.contains(part))
+}
+
+pub fn to_json(rows: &[HeaderRow]) -> String {
+    serde_json::to_string(rows).unwrap_or_else(|_| "[]".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let rows = parse(
+            r#"[{"key":"Accept","value":"text/csv"},{"key":"X-Debug","value":"1","enabled":false}]"#,
+        )
+        .unwrap();
+        assert_eq!(rows[0], HeaderRow::new("Accept", "text/csv"));
+        assert!(!rows[1].enabled);
+        assert_eq!(
+            enabled_map(&rows),
+            HashMap::from([("Accept".to_string(), "text/csv".to_string())])
+        );
+
+        let legacy = parse(r#"{"b":"2","a":"1"}"#).unwrap();
+        assert_eq!(
+            legacy,
+            vec![HeaderRow::new("a", "1"), HeaderRow::new("b", "2")]
+        );
+        assert_eq!(parse(&to_json(&legacy)).unwrap(), legacy);
+        assert!(parse(" ").unwrap().is_empty());
+        assert!(parse("new headers").is_err());
+    }
+}
@@ -0,0 +1,431 @@
+use serde::Serialize;
+use std::fs;
+
+use crate::{
+    cli::{Reporter, RunArgs},
+    crypto,
+    db::{self, DbPool, MigrationMode},
+    i18n,
+    runner::{self, RunFolderPayload, RunResult, RunnerError},
+};
+
+/// Exit code when a request failed.
+pub const EXIT_FAILED: i32 = 1;
+/// Exit code when nothing could be run: unknown target, no database, ...
+pub const EXIT_ERROR: i32 = 2;
+
+enum Target {
+    Folder { id: i64, name: String },
+    Request { id: i64, name: String },
+}
+
+impl Target {
+    fn name(&self) -> &str {
+        match self {
+            Target::Folder { name, .. } | Target::Request { name, .. } => name,
+        }
+    }
+}
+
+struct Named {
+    id: i64,
+    name: String,
+}
+
+/// What `js-link run` reports, the same for a folder and a single request.
+#[derive(Debug, Serialize)]
+pub struct Outcome {
+    pub target: String,
+    /// `folder` or `request`
+    pub kind: &'static str,
+    pub environment: Option<String>,
+    /// Id of the stored run, for folders
+    pub run_id: Option<i64>,
+    pub total: usize,
+    pub passed: usize,
+    pub failed: usize,
+    pub skipped: usize,
+    pub duration: i64,
+    pub results: Vec<RunResult>,
+}
+
+impl Outcome {
+    fn succeeded(&self) -> bool {
+        self.failed == 0
+    }
+}
+
+/// Finds a folder or request by name, or by id when no name matches. A
+/// `folder:` or `request:` prefix picks between the two.
+async fn resolve_target(pool: &DbPool, target: &str) -> Result<Target, String> {
+    let (kind, key) = match target.split_once(':') {
+        Some((kind @ ("folder" | "request"), key)) => (Some(kind), key),
+        _ => (None, target),
+    };
+    let id = key.parse::<i64>().ok();
+    let mut found = Vec::new();
+    if kind != Some("request") {
+        let folders = sqlx::query_as!(
+            Named,
+            "SELECT id, name FROM folders WHERE archived_at IS NULL AND (name = ? OR id = ?) ORDER BY id",
+            key,
+            id
+        )
+        .fetch_all(pool)
+        .await
+        .map_err(|e| e.to_string())?;
+        found.extend(folders.into_iter().map(|f| Target::Folder {
+            id: f.id,
+            name: f.name,
+        }));
+    }
+    if kind != Some("folder") {
+        let requests = sqlx::query_as!(
+            Named,
+            "SELECT id, name FROM requests WHERE archived_at IS NULL AND (name = ? OR id = ?) ORDER BY id",
+            key,
+            id
+        )
+        .fetch_all(pool)
+        .await
+        .map_err(|e| e.to_string())?;
+        found.extend(requests.into_iter().map(|r| Target::Request {
+            id: r.id,
+            name: r.name,
+        }));
+    }
+    // A name wins over an id
+    if found.iter().any(|t| t.name() == key) {
+        found.retain(|t| t.name() == key);
+    }
+    match found.len() {
+        0 => Err(format!("No folder or request named '{}'", target)),
+        1 => Ok(found.remove(0)),
+        n => Err(format!(
+            "'{}' matches {} folders and requests, prefix it with folder: or request:, or use the id",
+            target, n
+        )),
+    }
+}
+
+async fn resolve_environment(pool: &DbPool, environment: &str) -> Result<Named, String> {
+    let id = environment.parse::<i64>().ok();
+    let mut found = sqlx::query_as!(
+        Named,
+        "SELECT id, name FROM environments WHERE archived_at IS NULL AND (name = ? OR id = ?) ORDER BY name = ? DESC, id",
+        environment,
+        id,
+        environment
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(|e| e.to_string())?;
+    if found.is_empty() {
+        return Err(format!("No environment named '{}'", environment));
+    }
+    Ok(found.remove(0))
+}
+
+async fn run_target(
+    pool: &DbPool,
+    target: Target,
+    environment: Option<Named>,
+    args: &RunArgs,
+) -> Result<Outcome, String> {
+    let environment_id = environment.as_ref().map(|e| e.id);
+    let environment = environment.map(|e| e.name);
+    let mut outcome = match target {
+        Target::Folder { id, name } => {
+            let payload = RunFolderPayload {
+                environment_id,
+                stop_on_failure: args.stop_on_failure,
+                delay_ms: args.delay_ms,
+            };
+            let report = runner::run_folder(pool, id, &payload)
+                .await
+                .map_err(runner_error)?;
+            Outcome {
+                target: name,
+                kind: "folder",
+                environment,
+                run_id: Some(report.id),
+                total: report.total,
+                passed: report.passed,
+                failed: report.failed,
+                skipped: report.skipped,
+                duration: (report.finished_at - report.started_at).num_milliseconds(),
+                results: report.results,
+            }
+        }
+        Target::Request { id, name } => {
+            let result = runner::run_saved_request(pool, id, environment_id)
+                .await
+                .map_err(runner_error)?;
+            Outcome {
+                target: name,
+                kind: "request",
+                environment,
+                run_id: None,
+                total: 1,
+                passed: result.passed as usize,
+                failed: !result.passed as usize,
+                skipped: 0,
+                duration: result.duration,
+                results: vec![result],
+            }
+        }
+    };
+    // Bodies are kept for comparing runs, they only bloat a CI report
+    for result in &mut outcome.results {
+        result.body = None;
+    }
+    Ok(outcome)
+}
+
+fn runner_error(e: RunnerError) -> String {
+    match e {
+        RunnerError::FolderNotFound => i18n::t("error.folder_not_found"),
+        RunnerError::RequestNotFound => i18n::t("error.request_not_found"),
+        RunnerError::RunNotFound => i18n::t("error.run_not_found"),
+        RunnerError::DatabaseError(e) => e.to_string(),
+    }
+}
+
+pub fn render_text(outcome: &Outcome) -> String {
+    let mut out = format!(
+        "Ran {} '{}'{}\n\n",
+        outcome.kind,
+        outcome.target,
+        outcome
+            .environment
+            .as_ref()
+            .map(|e| format!(" with environment '{}'", e))
+            .unwrap_or_default()
+    );
+    for result in &outcome.results {
+        let label = if result.passed { "pass" } else { "FAIL" };
+        let status = result
+            .status
+            .map(|s| s.to_string())
+            .unwrap_or_else(|| "---".to_string());
+        out.push_str(&format!(
+            "[{}] {} {} ({}, {} ms)\n",
+            label, result.method, result.name, status, result.duration
+        ));
+        if let Some(error) = &result.error {
+            out.push_str(&format!("       -> {}\n", error));
+        }
+        for check in result
+            .test_results
+            .iter()
+            .chain(&result.assertion_results)
+            .filter(|c| !c.passed)
+        {
+            out.push_str(&format!(
+                "       -> {}: {}\n",
+                check.name,
+                check.error.as_deref().unwrap_or("failed")
+            ));
+        }
+    }
+    out.push_str(&format!(
+        "\n{} passed, {} failed, {} skipped in {} ms\n",
+        outcome.passed, outcome.failed, outcome.skipped, outcome.duration
+    ));
+    out
+}
+
+fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+fn seconds(ms: i64) -> String {
+    format!("{:.3}", ms as f64 / 1000.0)
+}
+
+/// One test suite for the target, one test case per request.
+pub fn render_junit(outcome: &Outcome) -> String {
+    let suite = xml_escape(&outcome.target);
+    let mut out = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    out.push_str(&format!(
+        "<testsuites name=\"js-link\" tests=\"{}\" failures=\"{}\" skipped=\"{}\" time=\"{}\">\n",
+        outcome.total,
+        outcome.failed,
+        outcome.skipped,
+        seconds(outcome.duration)
+    ));
+    out.push_str(&format!(
+        "  <testsuite name=\"{}\" tests=\"{}\" failures=\"{}\" skipped=\"{}\" time=\"{}\">\n",
+        suite,
+        outcome.total,
+        outcome.failed,
+        outcome.skipped,
+        seconds(outcome.duration)
+    ));
+    for result in &outcome.results {
+        let name = xml_escape(&format!("{} {}", result.method, result.name));
+        let time = seconds(result.duration);
+        let Some(error) = result.error.as_deref().filter(|_| !result.passed) else {
+            out.push_str(&format!(
+                "    <testcase classname=\"{}\" name=\"{}\" time=\"{}\"/>\n",
+                suite, name, time
+            ));
+            continue;
+        };
+        let mut details = format!("{} {}", result.method, result.url);
+        for check in result
+            .test_results
+            .iter()
+            .chain(&result.assertion_results)
+            .filter(|c| !c.passed)
+        {
+            let message = check.error.as_deref().unwrap_or("failed");
+            details.push_str(&format!("\n{}: {}", check.name, message));
+        }
+        out.push_str(&format!(
+            "    <testcase classname=\"{}\" name=\"{}\" time=\"{}\">\n",
+            suite, name, time
+        ));
+        out.push_str(&format!(
+            "      <failure message=\"{}\">{}</failure>\n    </testcase>\n",
+            xml_escape(error),
+            xml_escape(&details)
+        ));
+    }
+    out.push_str("  </testsuite>\n</testsuites>\n");
+    out
+}
+
+async fn run_with(pool: &DbPool, args: &RunArgs) -> Result<Outcome, String> {
+    let target = resolve_target(pool, &args.target).await?;
+    let environment = match &args.env {
+        Some(environment) => Some(resolve_environment(pool, environment).await?),
+        None => None,
+    };
+    run_target(pool, target, environment, args).await
+}
+
+/// `js-link run`: runs the target against the configured database and
+/// reports to stdout or `--output`. Returns the process exit code.
+pub async fn run(args: &RunArgs, mode: MigrationMode) -> i32 {
+    let pool = match db::create_pool(mode).await {
+        Ok(pool) => pool,
+        Err(e) => {
+            eprintln!("{}", e);
+            return EXIT_ERROR;
+        }
+    };
+    match tokio::task::spawn_blocking(crypto::init).await {
+        Ok(Ok(())) => {}
+        Ok(Err(e)) => eprintln!("{}", e),
+        Err(e) => eprintln!("Failed to load the secret key: {}", e),
+    }
+    i18n::load(&pool).await;
+
+    let outcome = match run_with(&pool, args).await {
+        Ok(outcome) => outcome,
+        Err(e) => {
+            eprintln!("{}", e);
+            return EXIT_ERROR;
+        }
+    };
+    let report = match args.reporter {
+        Reporter::Text => render_text(&outcome),
+        Reporter::Json => serde_json::to_string_pretty(&outcome).unwrap_or_default() + "\n",
+        Reporter::Junit => render_junit(&outcome),
+    };
+    match &args.output {
+        Some(path) => {
+            if let Err(e) = fs::write(path, report) {
+                eprintln!("Failed to write {}: {}", path.display(), e);
+                return EXIT_ERROR;
+            }
+            // The report went to the file, keep a summary on the console
+            eprintln!(
+                "{} passed, {} failed, {} skipped",
+                outcome.passed, outcome.failed, outcome.skipped
+            );
+        }
+        None => print!("{}", report),
+    }
+    if outcome.succeeded() {
+        0
+    } else {
+        EXIT_FAILED
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use httpmock::MockServer;
+
+    fn args(target: &str) -> RunArgs {
+        RunArgs {
+            target: target.to_string(),
+            env: None,
+            reporter: Reporter::Junit,
+            output: None,
+            stop_on_failure: false,
+            delay_ms: 0,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_run_target() {
+        let pool = db::create_test_pool().await;
+        let mock_server = MockServer::start_async().await;
+        mock_server.mock(|when, then| {
+            when.path("/health");
+            then.status(200);
+        });
+        mock_server.mock(|when, then| {
+            when.path("/orders");
+            then.status(500);
+        });
+        sqlx::query("INSERT OR IGNORE INTO network_settings (id, auto_proxy) VALUES (1, TRUE)")
+            .execute(&pool)
+            .await
+            .unwrap();
+        sqlx::query("INSERT INTO folders (id, name) VALUES (7, 'Smoke <CI>')")
+            .execute(&pool)
+            .await
+            .unwrap();
+        sqlx::query(&format!(
+            "INSERT INTO requests (id, name, method, url, folder_id) VALUES (1, 'Health', 'GET', '{0}/health', 7), (2, 'Orders', 'GET', '{0}/orders', 7), (3, '7', 'GET', '{0}/health', NULL)",
+            mock_server.base_url()
+        ))
+        .execute(&pool)
+        .await
+        .unwrap();
+
+        let outcome = run_with(&pool, &args("Smoke <CI>")).await.unwrap();
+        assert_eq!(
+            (outcome.kind, outcome.total, outcome.failed),
+            ("folder", 2, 1)
+        );
+        assert!(!outcome.succeeded());
+        let junit = render_junit(&outcome);
+        assert!(junit.contains("<testsuite name=\"Smoke &lt;CI&gt;\" tests=\"2\" failures=\"1\""));
+        assert!(junit.contains("name=\"GET Health\" time="));
+        assert!(junit.contains("<failure message=\"Status 500\">GET "));
+
+        // The request named 7 wins over the folder with id 7
+        let outcome = run_with(&pool, &args("7")).await.unwrap();
+        assert_eq!((outcome.kind, outcome.target.as_str()), ("request", "7"));
+        assert!(outcome.succeeded());
+        let outcome = run_with(&pool, &args("folder:7")).await.unwrap();
+        assert_eq!(outcome.kind, "folder");
+        let outcome = run_with(&pool, &args("Health")).await.unwrap();
+        assert!(outcome.results[0].body.is_none());
+
+        assert!(run_with(&pool, &args("Nothing")).await.is_err());
+        let mut with_env = args("Health");
+        with_env.env = Some("staging".to_string());
+        assert!(run_with(&pool, &with_env).await.is_err());
+    }
+}
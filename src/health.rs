@@ -0,0 +1,128 @@
+use axum::{extract::State, http::StatusCode, response::IntoResponse, routing::get, Json, Router};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    db::{self, DbPool, MigrationState},
+    self_update::{self, AvailableUpdate},
+};
+
+// Probes for container orchestration, outside `/api` so they need no token
+// and are not rate limited.
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Probe {
+    /// `ok`, or what is wrong
+    pub status: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BuildInfo {
+    pub version: String,
+    pub git_sha: String,
+    pub build_time: Option<DateTime<Utc>>,
+    /// A newer release, once the background check has found one
+    #[serde(default)]
+    pub update_available: Option<AvailableUpdate>,
+}
+
+impl BuildInfo {
+    pub fn current() -> Self {
+        Self {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            git_sha: env!("JSLINK_GIT_SHA").to_string(),
+            build_time: env!("JSLINK_BUILD_EPOCH")
+                .parse::<i64>()
+                .ok()
+                .filter(|epoch| *epoch > 0)
+                .and_then(|epoch| DateTime::from_timestamp(epoch, 0)),
+            update_available: self_update::available_update(),
+        }
+    }
+}
+
+fn probe(status: StatusCode, message: &str) -> impl IntoResponse {
+    (
+        status,
+        Json(Probe {
+            status: message.to_string(),
+        }),
+    )
+}
+
+/// Alive as long as the database answers.
+async fn healthz(State(pool): State<DbPool>) -> impl IntoResponse {
+    match sqlx::query("SELECT 1").execute(&pool).await {
+        Ok(_) => probe(StatusCode::OK, "ok"),
+        Err(e) => {
+            log::warn!("Health check failed: {}", e);
+            probe(StatusCode::SERVICE_UNAVAILABLE, "database unavailable")
+        }
+    }
+}
+
+/// Ready to serve once the database schema is up to date.
+async fn readyz(State(pool): State<DbPool>) -> impl IntoResponse {
+    let migrations = match db::migration_status(&pool).await {
+        Ok(migrations) => migrations,
+        Err(e) => {
+            log::warn!("Readiness check failed: {}", e);
+            return probe(StatusCode::SERVICE_UNAVAILABLE, "database unavailable");
+        }
+    };
+    // Migrations from a newer version are fine; they only add to the schema
+    let outstanding = migrations
+        .iter()
+        .filter(|m| !matches!(m.state, MigrationState::Applied | MigrationState::Unknown))
+        .count();
+    if outstanding > 0 {
+        return probe(
+            StatusCode::SERVICE_UNAVAILABLE,
+            &format!("{} migration(s) not applied", outstanding),
+        );
+    }
+    probe(StatusCode::OK, "ok")
+}
+
+async fn version() -> impl IntoResponse {
+    Json(BuildInfo::current())
+}
+
+/// `/healthz` and `/readyz`, served at the root.
+pub fn routes(pool: DbPool) -> Router {
+    Router::new()
+        .route("/healthz", get(healthz))
+        .route("/readyz", get(readyz))
+        .with_state(pool)
+}
+
+/// `/version`, served under `/api`.
+pub fn api_routes() -> Router {
+    Router::new().route("/version", get(version))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum_test::TestServer;
+
+    #[tokio::test]
+    async fn test_probes() {
+        let pool = db::create_test_pool().await;
+        let server = TestServer::new(routes(pool.clone()).merge(api_routes())).unwrap();
+
+        let health: Probe = server.get("/healthz").await.json();
+        assert_eq!(health.status, "ok");
+        server.get("/readyz").await.assert_status_ok();
+
+        let info: BuildInfo = server.get("/version").await.json();
+        assert_eq!(info.version, env!("CARGO_PKG_VERSION"));
+        assert!(!info.git_sha.is_empty());
+
+        pool.close().await;
+        server
+            .get("/healthz")
+            .await
+            .assert_status(StatusCode::SERVICE_UNAVAILABLE);
+    }
+}
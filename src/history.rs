@@ -0,0 +1,425 @@
+use axum::{
+    body::Body,
+    extract::{Path, Query, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    routing::get,
+    Json, Router,
+};
+use chrono::{DateTime, NaiveDateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{db::DbPool, i18n};
+
+/// Bodies larger than this are not kept for download.
+const MAX_STORED_BODY: usize = 16 * 1024 * 1024;
+/// Only the bodies of this many most recent executions are kept; older ones
+/// are cleared as new ones are recorded.
+const KEPT_BODIES: i64 = 100;
+
+/// One execution, as recorded by the executor.
+pub struct ExecutionRecord<'a> {
+    pub request_id: Option<i64>,
+    pub method: &'a str,
+    pub url: &'a str,
+    pub status: Option<u16>,
+    pub duration_ms: i64,
+    pub error: Option<&'a str>,
+    /// Results of the request's test script as JSON
+    pub test_results: Option<&'a str>,
+    /// The request's latency budget, for responses received
+    pub latency_budget_ms: Option<i64>,
+    /// The response body, decompressed, with the headers needed to save it
+    pub response: Option<StoredResponse<'a>>,
+}
+
+pub struct StoredResponse<'a> {
+    pub body: &'a [u8],
+    pub content_type: Option<&'a str>,
+    pub content_disposition: Option<&'a str>,
+}
+
+/// The most recent response of a saved request, see latest_response.
+pub struct LatestResponse {
+    pub status: i64,
+    pub content_type: Option<String>,
+    /// `None` when the body was too large or has been cleared since
+    pub body: Option<Vec<u8>>,
+}
+
+#[derive(Debug)]
+pub enum HistoryError {
+    NotFound,
+    BodyNotStored,
+    DatabaseError(#[allow(dead_code)] sqlx::Error),
+}
+
+impl From<sqlx::Error> for HistoryError {
+    fn from(e: sqlx::Error) -> Self {
+        HistoryError::DatabaseError(e)
+    }
+}
+
+impl IntoResponse for HistoryError {
+    fn into_response(self) -> Response {
+        match self {
+            HistoryError::NotFound => {
+                (StatusCode::NOT_FOUND, i18n::t("error.execution_not_found")).into_response()
+            }
+            HistoryError::BodyNotStored => (
+                StatusCode::NOT_FOUND,
+                i18n::t("error.response_body_not_stored"),
+            )
+                .into_response(),
+            HistoryError::DatabaseError(_) => {
+                (StatusCode::INTERNAL_SERVER_ERROR, i18n::t("error.database")).into_response()
+            }
+        }
+    }
+}
+
+/// Appends an execution to the history and returns its id. Failures are
+/// logged rather than returned, since they must not fail the execution
+/// itself.
+pub async fn record(pool: &DbPool, record: ExecutionRecord<'_>) -> Option<i64> {
+    let status = record.status.map(i64::from);
+    let response = record
+        .response
+        .filter(|response| response.body.len() <= MAX_STORED_BODY);
+    let body = response.as_ref().map(|response| response.body);
+    let content_type = response.as_ref().and_then(|response| response.content_type);
+    let content_disposition = response
+        .as_ref()
+        .and_then(|response| response.content_disposition);
+    let result = sqlx::query_scalar!(
+        "INSERT INTO execution_history (request_id, method, url, status, duration_ms, error, test_results, latency_budget_ms, response_body, response_content_type, response_content_disposition) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?) RETURNING id",
+        record.request_id,
+        record.method,
+        record.url,
+        status,
+        record.duration_ms,
+        record.error,
+        record.test_results,
+        record.latency_budget_ms,
+        body,
+        content_type,
+        content_disposition,
+    )
+    .fetch_one(pool)
+    .await;
+
+    let id = match result {
+        Ok(id) => id,
+        Err(e) => {
+            log::warn!("Failed to record execution history: {}", e);
+            return None;
+        }
+    };
+    if body.is_some() {
+        let pruned = sqlx::query!(
+            "UPDATE execution_history SET response_body = NULL WHERE response_body IS NOT NULL AND id <= ? - ?",
+            id,
+            KEPT_BODIES
+        )
+        .execute(pool)
+        .await;
+        if let Err(e) = pruned {
+            log::warn!("Failed to clear old response bodies: {}", e);
+        }
+    }
+    Some(id)
+}
+
+/// The last response received for a request, used as its example response
+/// in generated documentation.
+pub async fn latest_response(
+    pool: &DbPool,
+    request_id: i64,
+) -> Result<Option<LatestResponse>, sqlx::Error> {
+    sqlx::query_as!(
+        LatestResponse,
+        r#"SELECT status AS "status!", response_content_type AS content_type, response_body AS body FROM execution_history WHERE request_id = ? AND status IS NOT NULL ORDER BY executed_at DESC, id DESC LIMIT 1"#,
+        request_id
+    )
+    .fetch_optional(pool)
+    .await
+}
+
+/// An execution that took longer than its request's latency budget.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BudgetViolation {
+    pub id: i64,
+    pub request_id: Option<i64>,
+    pub method: String,
+    pub url: String,
+    pub status: Option<i64>,
+    pub duration_ms: i64,
+    pub latency_budget_ms: i64,
+    pub executed_at: DateTime<Utc>,
+}
+
+struct BudgetViolationDb {
+    id: i64,
+    request_id: Option<i64>,
+    method: String,
+    url: String,
+    status: Option<i64>,
+    duration_ms: i64,
+    latency_budget_ms: i64,
+    executed_at: NaiveDateTime,
+}
+
+impl From<BudgetViolationDb> for BudgetViolation {
+    fn from(v: BudgetViolationDb) -> Self {
+        Self {
+            id: v.id,
+            request_id: v.request_id,
+            method: v.method,
+            url: v.url,
+            status: v.status,
+            duration_ms: v.duration_ms,
+            latency_budget_ms: v.latency_budget_ms,
+            executed_at: DateTime::from_naive_utc_and_offset(v.executed_at, Utc),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct BudgetViolationsQuery {
+    request_id: Option<i64>,
+    #[serde(default = "default_violations_limit")]
+    limit: i64,
+}
+
+fn default_violations_limit() -> i64 {
+    100
+}
+
+pub fn routes(pool: DbPool) -> Router {
+    Router::new()
+        .route("/history/:id/download", get(download_response))
+        .route("/history/over-budget", get(list_budget_violations))
+        .with_state(pool)
+}
+
+/// Executions over their latency budget, most recent first.
+async fn list_budget_violations(
+    State(pool): State<DbPool>,
+    Query(query): Query<BudgetViolationsQuery>,
+) -> Result<Json<Vec<BudgetViolation>>, HistoryError> {
+    let limit = query.limit.clamp(1, 1000);
+    let violations = sqlx::query_as!(
+        BudgetViolationDb,
+        r#"SELECT id, request_id, method, url, status, duration_ms, latency_budget_ms as "latency_budget_ms!", executed_at
+        FROM execution_history
+        WHERE latency_budget_ms IS NOT NULL AND duration_ms > latency_budget_ms
+            AND (? IS NULL OR request_id = ?)
+        ORDER BY executed_at DESC, id DESC LIMIT ?"#,
+        query.request_id,
+        query.request_id,
+        limit
+    )
+    .fetch_all(&pool)
+    .await?;
+    Ok(Json(
+        violations.into_iter().map(BudgetViolation::from).collect(),
+    ))
+}
+
+/// The stored response body of an execution as a file, with the Content-Type
+/// it was sent with.
+async fn download_response(
+    State(pool): State<DbPool>,
+    Path(id): Path<i64>,
+) -> Result<impl IntoResponse, HistoryError> {
+    let row = sqlx::query!(
+        "SELECT response_body, response_content_type, response_content_disposition FROM execution_history WHERE id = ?",
+        id
+    )
+    .fetch_optional(&pool)
+    .await?
+    .ok_or(HistoryError::NotFound)?;
+    let body = row.response_body.ok_or(HistoryError::BodyNotStored)?;
+
+    let content_type = row
+        .response_content_type
+        .filter(|ct| !ct.trim().is_empty())
+        .unwrap_or_else(|| "application/octet-stream".to_string());
+    let filename = row
+        .response_content_disposition
+        .as_deref()
+        .and_then(disposition_filename)
+        .unwrap_or_else(|| format!("response-{}.{}", id, extension(&content_type)));
+    log::info!("Downloading response of execution {} as {}", id, filename);
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, content_type),
+            (
+                header::CONTENT_DISPOSITION,
+                format!(
+                    "attachment; filename=\"{}\"; filename*=UTF-8''{}",
+                    filename.replace(|c: char| !c.is_ascii() || c == '"', "_"),
+                    urlencoding::encode(&filename)
+                ),
+            ),
+        ],
+        Body::from(body),
+    ))
+}
+
+/// The file name of a Content-Disposition header, preferring the RFC 6266
+/// `filename*` form. Directories are stripped so the name is safe to save.
+fn disposition_filename(disposition: &str) -> Option<String> {
+    let params: Vec<(String, &str)> = disposition
+        .split(';')
+        .skip(1)
+        .filter_map(|param| param.split_once('='))
+        .map(|(name, value)| (name.trim().to_ascii_lowercase(), value.trim()))
+        .collect();
+    let extended = params
+        .iter()
+        .find(|(name, _)| name == "filename*")
+        .and_then(|(_, value)| {
+            // charset'language'percent-encoded
+            let encoded = value.splitn(3, '\'').nth(2)?;
+            urlencoding::decode(encoded)
+                .ok()
+                .map(|name| name.into_owned())
+        });
+    let plain = || {
+        params
+            .iter()
+            .find(|(name, _)| name == "filename")
+            .map(|(_, value)| value.trim_matches('"').replace("\\\"", "\""))
+    };
+    let filename = extended.or_else(plain)?;
+    let filename = filename
+        .rsplit(['/', '\\'])
+        .next()
+        .unwrap_or_default()
+        .chars()
+        .filter(|c| !c.is_control())
+        .collect::<String>();
+    let filename = filename.trim().trim_start_matches('.');
+    (!filename.is_empty()).then(|| filename.to_string())
+}
+
+fn extension(content_type: &str) -> &'static str {
+    let mime = content_type
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase();
+    match mime.as_str() {
+        "application/json" => "json",
+        "application/pdf" => "pdf",
+        "application/zip" => "zip",
+        "application/xml" | "text/xml" => "xml",
+        "text/csv" => "csv",
+        "text/html" => "html",
+        "text/plain" => "txt",
+        "image/png" => "png",
+        "image/jpeg" => "jpg",
+        "image/gif" => "gif",
+        _ if mime.ends_with("+json") => "json",
+        _ if mime.ends_with("+xml") => "xml",
+        _ => "bin",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db;
+    use axum_test::TestServer;
+
+    fn record_with<'a>(response: Option<StoredResponse<'a>>) -> ExecutionRecord<'a> {
+        ExecutionRecord {
+            request_id: None,
+            method: "GET",
+            url: "http://localhost/report",
+            status: Some(200),
+            duration_ms: 12,
+            error: None,
+            test_results: None,
+            latency_budget_ms: None,
+            response,
+        }
+    }
+
+    #[test]
+    fn test_disposition_filename() {
+        assert_eq!(
+            disposition_filename("attachment; filename=\"report.pdf\"").as_deref(),
+            Some("report.pdf")
+        );
+        assert_eq!(
+            disposition_filename(
+                "attachment; filename=\"fallback.csv\"; filename*=UTF-8''M%C3%A4rz%20data.csv"
+            )
+            .as_deref(),
+            Some("März data.csv")
+        );
+        assert_eq!(
+            disposition_filename("attachment; filename=../../etc/passwd").as_deref(),
+            Some("passwd")
+        );
+        assert_eq!(disposition_filename("inline"), None);
+        assert_eq!(disposition_filename("attachment; filename=\"..\""), None);
+    }
+
+    #[tokio::test]
+    async fn test_download_response() {
+        let pool = db::create_test_pool().await;
+        let pdf = b"%PDF-1.7 \x00\xff binary";
+        let named = record(
+            &pool,
+            record_with(Some(StoredResponse {
+                body: pdf,
+                content_type: Some("application/pdf"),
+                content_disposition: Some("attachment; filename=\"invoice 7.pdf\""),
+            })),
+        )
+        .await
+        .unwrap();
+        let unnamed = record(
+            &pool,
+            record_with(Some(StoredResponse {
+                body: b"a,b\n1,2\n",
+                content_type: Some("text/csv; charset=utf-8"),
+                content_disposition: None,
+            })),
+        )
+        .await
+        .unwrap();
+        let failed = record(&pool, record_with(None)).await.unwrap();
+        let server = TestServer::new(routes(pool)).unwrap();
+
+        let response = server.get(&format!("/history/{}/download", named)).await;
+        response.assert_status_ok();
+        assert_eq!(response.header(header::CONTENT_TYPE), "application/pdf");
+        assert_eq!(
+            response.header(header::CONTENT_DISPOSITION),
+            "attachment; filename=\"invoice 7.pdf\"; filename*=UTF-8''invoice%207.pdf"
+        );
+        assert_eq!(response.as_bytes().as_ref(), pdf);
+
+        let response = server.get(&format!("/history/{}/download", unnamed)).await;
+        assert!(response
+            .header(header::CONTENT_DISPOSITION)
+            .to_str()
+            .unwrap()
+            .contains(&format!("response-{}.csv", unnamed)));
+
+        server
+            .get(&format!("/history/{}/download", failed))
+            .await
+            .assert_status(StatusCode::NOT_FOUND);
+        server
+            .get("/history/999/download")
+            .await
+            .assert_status(StatusCode::NOT_FOUND);
+    }
+}
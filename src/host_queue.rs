@@ -0,0 +1,309 @@
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    routing::get,
+    Json, Router,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::env;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Duration;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+use crate::{db::DbPool, i18n};
+
+const SETTING: &str = "host_concurrency";
+const DEFAULT_WAIT_SECS: u64 = 300;
+
+/// How many executions may be in flight per target host, so batch runs do
+/// not trip rate limiters or overload fragile services. Executions beyond
+/// the limit wait in arrival order.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct HostLimits {
+    /// Limit of hosts not listed, unlimited when unset
+    #[serde(default)]
+    pub default: Option<u32>,
+    /// Limits by host name; `*.example.com` covers the subdomains
+    #[serde(default)]
+    pub hosts: BTreeMap<String, u32>,
+}
+
+impl HostLimits {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.default == Some(0) {
+            return Err("The default limit must be at least 1".to_string());
+        }
+        for (host, limit) in &self.hosts {
+            let name = host.strip_prefix("*.").unwrap_or(host);
+            if name.is_empty() || name.contains(|c: char| c.is_whitespace() || "/:*".contains(c)) {
+                return Err(format!("Invalid host '{}'", host));
+            }
+            if *limit == 0 {
+                return Err(format!("The limit of {} must be at least 1", host));
+            }
+        }
+        Ok(())
+    }
+
+    /// The limit for `host`: its own, else that of the closest wildcard,
+    /// else the default.
+    pub fn limit_for(&self, host: &str) -> Option<u32> {
+        let host = host.to_lowercase();
+        if let Some(limit) = self.hosts.get(&host) {
+            return Some(*limit);
+        }
+        let mut suffix = host.as_str();
+        while let Some((_, parent)) = suffix.split_once('.') {
+            if let Some(limit) = self.hosts.get(&format!("*.{}", parent)) {
+                return Some(*limit);
+            }
+            suffix = parent;
+        }
+        self.default
+    }
+
+    pub async fn global(pool: &DbPool) -> Self {
+        let stored = sqlx::query_scalar!("SELECT value FROM app_settings WHERE name = ?", SETTING)
+            .fetch_optional(pool)
+            .await
+            .unwrap_or_else(|e| {
+                log::warn!("Failed to load host concurrency setting: {}", e);
+                None
+            });
+        stored
+            .and_then(|value| serde_json::from_str(&value).ok())
+            .unwrap_or_default()
+    }
+}
+
+/// The slots of one host. A changed limit gets a new semaphore; executions
+/// holding slots of the old one finish undisturbed.
+struct HostQueue {
+    limit: u32,
+    slots: Arc<Semaphore>,
+    waiting: usize,
+}
+
+static QUEUES: OnceLock<Mutex<HashMap<String, HostQueue>>> = OnceLock::new();
+
+fn queues() -> std::sync::MutexGuard<'static, HashMap<String, HostQueue>> {
+    QUEUES
+        .get_or_init(Default::default)
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+}
+
+/// How long an execution waits for a slot of its host, from
+/// `JSLINK_QUEUE_WAIT_SECS`.
+pub fn wait_timeout() -> Duration {
+    let secs = env::var("JSLINK_QUEUE_WAIT_SECS")
+        .ok()
+        .and_then(|v| v.trim().parse().ok())
+        .unwrap_or(DEFAULT_WAIT_SECS);
+    Duration::from_secs(secs)
+}
+
+/// Waits for one of the `limit` slots of `host`; the slot is freed when the
+/// returned permit is dropped. Returns `None` when the timeout elapses first.
+pub async fn acquire(host: &str, limit: u32, timeout: Duration) -> Option<OwnedSemaphorePermit> {
+    let slots = {
+        let mut queues = queues();
+        let queue = queues.entry(host.to_string()).or_insert_with(|| HostQueue {
+            limit,
+            slots: Arc::new(Semaphore::new(limit as usize)),
+            waiting: 0,
+        });
+        if queue.limit != limit {
+            queue.limit = limit;
+            queue.slots = Arc::new(Semaphore::new(limit as usize));
+        }
+        queue.slots.clone()
+    };
+    if let Ok(permit) = slots.clone().try_acquire_owned() {
+        return Some(permit);
+    }
+
+    let waiting = {
+        let mut queues = queues();
+        let queue = queues.get_mut(host)?;
+        queue.waiting += 1;
+        queue.waiting
+    };
+    log::info!(
+        "{} executions to {} are running, {} waiting for a slot",
+        limit,
+        host,
+        waiting
+    );
+    let permit = tokio::time::timeout(timeout, slots.acquire_owned())
+        .await
+        .ok()
+        .and_then(Result::ok);
+    if let Some(queue) = queues().get_mut(host) {
+        queue.waiting = queue.waiting.saturating_sub(1);
+    }
+    permit
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct HostQueueStatus {
+    pub host: String,
+    pub limit: u32,
+    pub running: usize,
+    pub queued: usize,
+}
+
+/// The hosts executions have been limited for, busiest first.
+pub fn status() -> Vec<HostQueueStatus> {
+    let mut status: Vec<HostQueueStatus> = queues()
+        .iter()
+        .map(|(host, queue)| HostQueueStatus {
+            host: host.clone(),
+            limit: queue.limit,
+            running: (queue.limit as usize).saturating_sub(queue.slots.available_permits()),
+            queued: queue.waiting,
+        })
+        .collect();
+    status.sort_by(|a, b| {
+        (b.running + b.queued)
+            .cmp(&(a.running + a.queued))
+            .then_with(|| a.host.cmp(&b.host))
+    });
+    status
+}
+
+pub enum HostLimitsError {
+    Invalid(String),
+    DatabaseError(sqlx::Error),
+}
+
+impl From<sqlx::Error> for HostLimitsError {
+    fn from(e: sqlx::Error) -> Self {
+        HostLimitsError::DatabaseError(e)
+    }
+}
+
+impl IntoResponse for HostLimitsError {
+    fn into_response(self) -> Response {
+        match self {
+            HostLimitsError::Invalid(msg) => (StatusCode::BAD_REQUEST, msg).into_response(),
+            HostLimitsError::DatabaseError(e) => {
+                log::error!("Database error: {}", e);
+                (StatusCode::INTERNAL_SERVER_ERROR, i18n::t("error.database")).into_response()
+            }
+        }
+    }
+}
+
+async fn get_setting(State(pool): State<DbPool>) -> Json<HostLimits> {
+    Json(HostLimits::global(&pool).await)
+}
+
+async fn set_setting(
+    State(pool): State<DbPool>,
+    Json(mut payload): Json<HostLimits>,
+) -> Result<Json<HostLimits>, HostLimitsError> {
+    payload.hosts = payload
+        .hosts
+        .into_iter()
+        .map(|(host, limit)| (host.trim().to_lowercase(), limit))
+        .collect();
+    payload.validate().map_err(HostLimitsError::Invalid)?;
+    let value = serde_json::to_string(&payload).unwrap_or_else(|_| "{}".to_string());
+    sqlx::query!(
+        "INSERT INTO app_settings (name, value) VALUES (?, ?) ON CONFLICT (name) DO UPDATE SET value = excluded.value, updated_at = CURRENT_TIMESTAMP",
+        SETTING,
+        value
+    )
+    .execute(&pool)
+    .await?;
+    log::info!("Host concurrency limits set to {}", value);
+    Ok(Json(payload))
+}
+
+async fn get_status() -> Json<Vec<HostQueueStatus>> {
+    Json(status())
+}
+
+pub fn routes(pool: DbPool) -> Router {
+    Router::new()
+        .route(
+            "/settings/host-concurrency",
+            get(get_setting).put(set_setting),
+        )
+        .route("/execution-queue", get(get_status))
+        .with_state(pool)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db;
+    use axum_test::TestServer;
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn test_zero_limits_are_rejected() {
+        let pool = db::create_test_pool().await;
+        let server = TestServer::new(routes(pool)).unwrap();
+        server
+            .put("/settings/host-concurrency")
+            .json(&json!({ "hosts": { "staging.example.com": 0 } }))
+            .await
+            .assert_status(StatusCode::BAD_REQUEST);
+        server
+            .put("/settings/host-concurrency")
+            .json(&json!({ "default": 0 }))
+            .await
+            .assert_status(StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_limits_match_hosts_and_wildcards() {
+        let pool = db::create_test_pool().await;
+        let server = TestServer::new(routes(pool.clone())).unwrap();
+        server
+            .put("/settings/host-concurrency")
+            .json(&json!({ "default": 4, "hosts": { "Staging.example.com": 1, "*.internal.test": 2 } }))
+            .await
+            .assert_status_ok();
+
+        let limits = HostLimits::global(&pool).await;
+        assert_eq!(limits.limit_for("staging.example.com"), Some(1));
+        assert_eq!(limits.limit_for("billing.eu.internal.test"), Some(2));
+        assert_eq!(limits.limit_for("internal.test"), Some(4));
+        assert_eq!(HostLimits::default().limit_for("example.com"), None);
+    }
+
+    #[tokio::test]
+    async fn test_full_hosts_time_out() {
+        let host = "full.test";
+        let _first = acquire(host, 1, Duration::from_secs(1)).await.unwrap();
+        assert!(acquire(host, 1, Duration::from_millis(50)).await.is_none());
+        assert!(acquire("other.test", 1, Duration::from_millis(50))
+            .await
+            .is_some());
+    }
+
+    #[tokio::test]
+    async fn test_waiting_executions_are_listed() {
+        let pool = db::create_test_pool().await;
+        let server = TestServer::new(routes(pool)).unwrap();
+        let host = "queue.test";
+        let first = acquire(host, 1, Duration::from_secs(1)).await.unwrap();
+
+        let waiter = tokio::spawn(acquire(host, 1, Duration::from_secs(5)));
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        let queue: Vec<HostQueueStatus> = server.get("/execution-queue").await.json();
+        let queued = queue.iter().find(|q| q.host == host).unwrap();
+        assert_eq!((queued.running, queued.queued), (1, 1));
+        drop(first);
+        let second = waiter.await.unwrap().unwrap();
+        drop(second);
+        let queued = status().into_iter().find(|q| q.host == host).unwrap();
+        assert_eq!((queued.running, queued.queued), (0, 0));
+    }
+}
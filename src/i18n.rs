@@ -0,0 +1,283 @@
+use axum::{
+    extract::{Query, Request, State},
+    http::StatusCode,
+    middleware::Next,
+    response::{IntoResponse, Response},
+    routing::{get, put},
+    Json, Router,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::env;
+use std::fmt;
+use std::sync::{OnceLock, RwLock};
+
+use crate::db::DbPool;
+
+pub const DEFAULT_LANGUAGE: &str = "en";
+const SETTING: &str = "language";
+
+/// Message catalogs compiled into the binary. English is complete and serves
+/// as the fallback for keys missing from other catalogs.
+const CATALOGS: [(&str, &str, &str); 2] = [
+    ("en", "English", include_str!("../locales/en.json")),
+    ("de", "Deutsch", include_str!("../locales/de.json")),
+];
+
+static MESSAGES: OnceLock<HashMap<&'static str, HashMap<String, String>>> = OnceLock::new();
+/// The language chosen in the settings, applied to every API request.
+static CONFIGURED: OnceLock<RwLock<&'static str>> = OnceLock::new();
+
+tokio::task_local! {
+    static LANGUAGE: &'static str;
+}
+
+fn messages(language: &str) -> Option<&'static HashMap<String, String>> {
+    MESSAGES
+        .get_or_init(|| {
+            CATALOGS
+                .iter()
+                .map(|(code, _, source)| {
+                    let catalog = serde_json::from_str(source)
+                        .unwrap_or_else(|e| panic!("Invalid message catalog {}: {}", code, e));
+                    (*code, catalog)
+                })
+                .collect()
+        })
+        .get(language)
+}
+
+/// The static code of a supported language, so it can live in the task-local.
+fn supported(language: &str) -> Option<&'static str> {
+    CATALOGS
+        .iter()
+        .map(|(code, _, _)| *code)
+        .find(|code| code.eq_ignore_ascii_case(language.trim()))
+}
+
+fn configured() -> &'static RwLock<&'static str> {
+    CONFIGURED.get_or_init(|| RwLock::new(DEFAULT_LANGUAGE))
+}
+
+fn set_configured(language: &'static str) {
+    *configured().write().unwrap_or_else(|e| e.into_inner()) = language;
+}
+
+/// Language of the request being handled; English outside of one.
+pub fn current_language() -> &'static str {
+    LANGUAGE.try_with(|l| *l).unwrap_or(DEFAULT_LANGUAGE)
+}
+
+/// Looks up a message in the current language.
+pub fn t(key: &str) -> String {
+    tf(key, &[])
+}
+
+/// Looks up a message and fills in its `{name}` placeholders.
+pub fn tf(key: &str, args: &[(&str, &dyn fmt::Display)]) -> String {
+    let template = messages(current_language())
+        .and_then(|m| m.get(key))
+        .or_else(|| messages(DEFAULT_LANGUAGE).and_then(|m| m.get(key)));
+    let Some(template) = template else {
+        log::warn!("Missing message {}", key);
+        return key.to_string();
+    };
+    args.iter().fold(template.clone(), |text, (name, value)| {
+        text.replace(&format!("{{{}}}", name), &value.to_string())
+    })
+}
+
+/// Restores the language setting at startup. `JSLINK_LANG` applies until a
+/// language is chosen in the UI.
+pub async fn load(pool: &DbPool) {
+    let stored = sqlx::query_scalar!("SELECT value FROM app_settings WHERE name = ?", SETTING)
+        .fetch_optional(pool)
+        .await
+        .unwrap_or_else(|e| {
+            log::warn!("Failed to load language setting: {}", e);
+            None
+        });
+    let wanted = stored.or_else(|| env::var("JSLINK_LANG").ok());
+    if let Some(wanted) = wanted {
+        match supported(&wanted) {
+            Some(language) => set_configured(language),
+            None => log::warn!("Unsupported language {}, using English", wanted),
+        }
+    }
+    log::info!("Server messages in language: {}", configured_language());
+}
+
+fn configured_language() -> &'static str {
+    *configured().read().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Middleware running each request with the configured language.
+pub async fn localize(request: Request, next: Next) -> Response {
+    LANGUAGE
+        .scope(configured_language(), next.run(request))
+        .await
+}
+
+#[derive(Debug)]
+pub enum I18nError {
+    UnsupportedLanguage(String),
+    DatabaseError(#[allow(dead_code)] sqlx::Error),
+}
+
+impl From<sqlx::Error> for I18nError {
+    fn from(e: sqlx::Error) -> Self {
+        I18nError::DatabaseError(e)
+    }
+}
+
+impl IntoResponse for I18nError {
+    fn into_response(self) -> Response {
+        match self {
+            I18nError::UnsupportedLanguage(language) => (
+                StatusCode::BAD_REQUEST,
+                tf("error.unsupported_language", &[("language", &language)]),
+            )
+                .into_response(),
+            I18nError::DatabaseError(_) => {
+                (StatusCode::INTERNAL_SERVER_ERROR, t("error.database")).into_response()
+            }
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct Language {
+    pub code: &'static str,
+    pub name: &'static str,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Catalog {
+    pub language: &'static str,
+    pub languages: Vec<Language>,
+    /// Every message key, falling back to English where untranslated
+    pub messages: HashMap<String, String>,
+}
+
+/// The languages with a message catalog.
+pub fn languages() -> impl Iterator<Item = Language> {
+    CATALOGS
+        .iter()
+        .map(|(code, name, _)| Language { code, name })
+}
+
+fn catalog(language: &'static str) -> Catalog {
+    let mut merged = messages(DEFAULT_LANGUAGE).cloned().unwrap_or_default();
+    if let Some(translated) = messages(language) {
+        merged.extend(translated.iter().map(|(k, v)| (k.clone(), v.clone())));
+    }
+    Catalog {
+        language,
+        languages: languages().collect(),
+        messages: merged,
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CatalogQuery {
+    /// Preview another language without changing the setting
+    lang: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SetLanguage {
+    language: String,
+}
+
+pub fn routes(pool: DbPool) -> Router {
+    Router::new()
+        .route("/i18n", get(get_catalog))
+        .route("/i18n/language", put(set_language))
+        .with_state(pool)
+}
+
+async fn get_catalog(Query(query): Query<CatalogQuery>) -> Result<Json<Catalog>, I18nError> {
+    let language = match query.lang {
+        Some(lang) => supported(&lang).ok_or(I18nError::UnsupportedLanguage(lang))?,
+        None => current_language(),
+    };
+    Ok(Json(catalog(language)))
+}
+
+async fn set_language(
+    State(pool): State<DbPool>,
+    Json(payload): Json<SetLanguage>,
+) -> Result<Json<Catalog>, I18nError> {
+    let language = supported(&payload.language)
+        .ok_or_else(|| I18nError::UnsupportedLanguage(payload.language.clone()))?;
+    sqlx::query!(
+        "INSERT INTO app_settings (name, value) VALUES (?, ?) ON CONFLICT (name) DO UPDATE SET value = excluded.value, updated_at = CURRENT_TIMESTAMP",
+        SETTING,
+        language
+    )
+    .execute(&pool)
+    .await?;
+    set_configured(language);
+    log::info!("Language set to {}", language);
+    Ok(Json(catalog(language)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::create_test_pool;
+    use axum_test::TestServer;
+    use serde_json::Value;
+
+    #[test]
+    fn test_catalogs_have_the_same_keys() {
+        let english = messages(DEFAULT_LANGUAGE).unwrap();
+        for (code, _, _) in CATALOGS {
+            let catalog = messages(code).unwrap();
+            for key in english.keys() {
+                assert!(catalog.contains_key(key), "{} is missing {}", code, key);
+            }
+            for key in catalog.keys() {
+                assert!(
+                    english.contains_key(key),
+                    "{} has unknown key {}",
+                    code,
+                    key
+                );
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_messages_follow_the_scoped_language() {
+        assert_eq!(t("error.request_not_found"), "Request not found");
+        let german = LANGUAGE
+            .scope("de", async {
+                tf("import.requests_imported", &[("count", &3)])
+            })
+            .await;
+        assert_eq!(german, "3 Anfragen erfolgreich importiert");
+        assert_eq!(t("no.such.key"), "no.such.key");
+    }
+
+    #[tokio::test]
+    async fn test_catalog_routes() {
+        let pool = create_test_pool().await;
+        let server = TestServer::new(routes(pool)).unwrap();
+
+        let body: Value = server
+            .get("/i18n")
+            .add_query_param("lang", "DE")
+            .await
+            .json();
+        assert_eq!(body["language"], "de");
+        assert_eq!(body["messages"]["ui.cancel"], "Abbrechen");
+
+        let response = server
+            .put("/i18n/language")
+            .json(&serde_json::json!({"language": "xx"}))
+            .await;
+        response.assert_status(StatusCode::BAD_REQUEST);
+        response.assert_text("Unsupported language 'xx'");
+    }
+}
@@ -1,77 +1,719 @@
 use crate::db::DbPool;
-use crate::importers::{parse_import_file, save_import, CollectionSummary};
+use crate::i18n;
+use crate::importers::{
+    extract_archive, find_folder, is_zip_archive, parse_file, save_environment, save_globals,
+    save_import, CollectionSummary, ConflictStrategy, EnvironmentSummary, ParsedFile,
+};
+use crate::limits;
+use crate::restore;
 use axum::{
-    extract::{Multipart, Query, State},
-    response::{IntoResponse, Json},
+    extract::{multipart::MultipartError, DefaultBodyLimit, Multipart, Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Json, Response},
     routing::post,
     Router,
 };
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use serde_json::json;
 
+// Postman data dumps bundle every collection and environment, so allow more
+// than axum's 2 MB default
+const MAX_IMPORT_SIZE: usize = 50 * 1024 * 1024;
+
 #[derive(Debug, Deserialize)]
 struct ImportParams {
     preview: Option<bool>,
+    /// For collections named like an existing folder; duplicates by default
+    #[serde(default)]
+    conflict: ConflictStrategy,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+enum FileStatus {
+    Imported,
+    /// Parsed fine in a preview
+    Ready,
+    Failed,
+    /// An archive member that is not a collection or environment
+    Skipped,
+}
+
+/// The outcome for one uploaded file, or one member of an uploaded zip.
+#[derive(Debug, Serialize)]
+struct FileReport {
+    file: String,
+    /// The zip the file was taken from
+    #[serde(skip_serializing_if = "Option::is_none")]
+    archive: Option<String>,
+    status: FileStatus,
+    /// What was imported, or why the file failed or was skipped
+    message: Option<String>,
+    /// Things that were imported but may not be what the user expects
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    warnings: Vec<String>,
+}
+
+impl FileReport {
+    fn new(file: &str, archive: Option<&str>, status: FileStatus, message: Option<String>) -> Self {
+        FileReport {
+            file: file.to_string(),
+            archive: archive.map(str::to_string),
+            status,
+            message,
+            warnings: Vec::new(),
+        }
+    }
+}
+
+pub enum ImportError {
+    /// The upload itself could not be read, e.g. it was cut off or too large
+    Upload(MultipartError),
+    NoFile,
+}
+
+impl IntoResponse for ImportError {
+    fn into_response(self) -> Response {
+        match self {
+            ImportError::Upload(e) => (
+                e.status(),
+                i18n::tf("import.upload_error", &[("error", &e.body_text())]),
+            )
+                .into_response(),
+            ImportError::NoFile => {
+                (StatusCode::BAD_REQUEST, i18n::t("import.no_file")).into_response()
+            }
+        }
+    }
+}
+
+/// Everything an import has done so far.
+#[derive(Default)]
+struct ImportReport {
+    message: String,
+    collections: Vec<CollectionSummary>,
+    environments: Vec<EnvironmentSummary>,
+    errors: Vec<String>,
+    files: Vec<FileReport>,
+}
+
+impl ImportReport {
+    fn fail(&mut self, file: &str, archive: Option<&str>, error: String) {
+        self.files.push(FileReport::new(
+            file,
+            archive,
+            FileStatus::Failed,
+            Some(error.clone()),
+        ));
+        self.errors.push(error);
+    }
 }
 
 pub fn routes(pool: DbPool) -> Router {
     Router::new()
-        .route("/import", post(handle_import))
+        .route(
+            "/import",
+            post(handle_import).layer(DefaultBodyLimit::max(limits::body_limit(
+                "JSLINK_MAX_IMPORT_BYTES",
+                MAX_IMPORT_SIZE,
+            ))),
+        )
         .with_state(pool)
 }
 
+/// Imports, or with `preview` only parses, every uploaded file. Answers
+/// `422 Unprocessable Entity` when files were sent but none of them could be
+/// imported; `files` says what happened to each of them.
+#[tracing::instrument(name = "import", skip_all, fields(preview = params.preview.unwrap_or(false)))]
 async fn handle_import(
     State(pool): State<DbPool>,
     Query(params): Query<ImportParams>,
     mut multipart: Multipart,
-) -> impl IntoResponse {
-    let mut message = String::new();
+) -> Result<impl IntoResponse, ImportError> {
     let is_preview = params.preview.unwrap_or(false);
+    let mut report = ImportReport::default();
 
-    // For preview, we collect summaries. For execute, we collect status messages.
-    let mut preview_collections = Vec::new();
-
-    while let Some(field) = multipart.next_field().await.unwrap() {
+    while let Some(field) = multipart.next_field().await.map_err(ImportError::Upload)? {
         let file_name = field.file_name().unwrap_or("unknown").to_string();
-        let data = field.bytes().await.unwrap();
+        let data = field.bytes().await.map_err(ImportError::Upload)?;
 
-        match parse_import_file(&data, &file_name) {
-            Ok(folders) => {
+        if !is_zip_archive(&data, &file_name) {
+            import_file(&pool, &params, &mut report, &file_name, None, &data).await;
+            continue;
+        }
+        let archive = match extract_archive(&data) {
+            Ok(archive) => archive,
+            Err(e) => {
+                let error = i18n::tf("import.read_error", &[("file", &file_name), ("error", &e)]);
+                report.fail(&file_name, None, error);
+                continue;
+            }
+        };
+        match restore::read_bundle(&archive.members) {
+            // A js-link workspace bundle is restored as a whole
+            Ok(Some(bundle)) => {
                 if is_preview {
-                    for folder in folders {
-                        preview_collections.push(CollectionSummary {
-                            name: folder.name,
-                            request_count: folder.requests.len(),
-                        });
-                    }
-                } else {
-                    match save_import(&pool, folders).await {
-                        Ok(msg) => message.push_str(&format!("Success: {}\n", msg)),
-                        Err(e) => message.push_str(&format!("Error saving {}: {}\n", file_name, e)),
+                    let (collections, environments) = bundle.summary();
+                    report.collections.extend(collections);
+                    report.environments.extend(environments);
+                    report
+                        .files
+                        .push(FileReport::new(&file_name, None, FileStatus::Ready, None));
+                    continue;
+                }
+                match restore::restore(&pool, bundle).await {
+                    Ok(msg) => {
+                        report.message.push_str(&success("", &msg));
+                        report.files.push(FileReport::new(
+                            &file_name,
+                            None,
+                            FileStatus::Imported,
+                            Some(msg),
+                        ));
                     }
+                    Err(e) => report.fail(&file_name, None, save_error(&file_name, &e)),
                 }
             }
             Err(e) => {
-                if !is_preview {
-                    message.push_str(&format!("Error parsing {}: {}\n", file_name, e));
-                } else {
-                    // In preview mode, maybe just return error for that file?
-                    // For now we just ignore or could return error structure.
-                    // Let's just return what we have.
+                let error = i18n::tf("import.read_error", &[("file", &file_name), ("error", &e)]);
+                report.fail(&file_name, None, error);
+            }
+            // Zip uploads are imported member by member; the report prefixes
+            // each line with the member it came from
+            Ok(None) => {
+                log::info!(
+                    "Importing {} member(s) from archive {}",
+                    archive.members.len(),
+                    file_name
+                );
+                for skipped in &archive.skipped {
+                    report.files.push(FileReport::new(
+                        skipped,
+                        Some(&file_name),
+                        FileStatus::Skipped,
+                        Some(i18n::t("import.skipped_member")),
+                    ));
+                }
+                for (member, data) in &archive.members {
+                    import_file(&pool, &params, &mut report, member, Some(&file_name), data).await;
                 }
             }
         }
     }
 
-    if is_preview {
-        Json(json!({
-            "preview": true,
-            "collections": preview_collections
-        }))
+    if report.files.is_empty() {
+        return Err(ImportError::NoFile);
+    }
+    let imported = report
+        .files
+        .iter()
+        .any(|f| matches!(f.status, FileStatus::Imported | FileStatus::Ready));
+    let status = if imported || report.errors.is_empty() {
+        StatusCode::OK
     } else {
+        StatusCode::UNPROCESSABLE_ENTITY
+    };
+
+    let ImportReport {
+        mut message,
+        collections,
+        environments,
+        errors,
+        files,
+    } = report;
+    if is_preview {
+        return Ok((
+            status,
+            Json(json!({
+                "preview": true,
+                "collections": collections,
+                "environments": environments,
+                "files": files,
+                "errors": errors
+            })),
+        ));
+    }
+    for error in &errors {
+        message.push_str(&format!("{}\n", error));
+    }
+    Ok((
+        status,
         Json(json!({
             "preview": false,
-            "message": message
-        }))
+            "message": message,
+            "files": files,
+            "errors": errors
+        })),
+    ))
+}
+
+/// Parses one uploaded file or archive member and, unless previewing, saves
+/// what it contains.
+async fn import_file(
+    pool: &DbPool,
+    params: &ImportParams,
+    report: &mut ImportReport,
+    name: &str,
+    archive: Option<&str>,
+    data: &[u8],
+) {
+    let is_preview = params.preview.unwrap_or(false);
+    let prefix = archive.map(|_| format!("{}: ", name)).unwrap_or_default();
+    let mut warnings = Vec::new();
+
+    let outcome = match parse_file(data, name) {
+        Ok(ParsedFile::Collections(folders)) => {
+            for folder in folders.iter().filter(|f| f.requests.is_empty()) {
+                warnings.push(i18n::tf(
+                    "import.empty_collection",
+                    &[("name", &folder.name)],
+                ));
+            }
+            if is_preview {
+                for folder in folders {
+                    let exists = find_folder(pool, &folder.name)
+                        .await
+                        .is_ok_and(|id| id.is_some());
+                    if exists {
+                        warnings.push(i18n::tf("import.folder_exists", &[("name", &folder.name)]));
+                    }
+                    report.collections.push(CollectionSummary {
+                        name: folder.name,
+                        request_count: folder.requests.len(),
+                        exists,
+                    });
+                }
+                Ok(None)
+            } else {
+                save_import(pool, folders, params.conflict)
+                    .await
+                    .map(Some)
+                    .map_err(|e| save_error(name, &e))
+            }
+        }
+        Ok(ParsedFile::Environment(environment)) => {
+            if environment.variables.is_empty() {
+                warnings.push(i18n::tf(
+                    "import.empty_environment",
+                    &[("name", &environment.name)],
+                ));
+            }
+            if is_preview {
+                report.environments.push(EnvironmentSummary {
+                    name: environment.name,
+                    variable_count: environment.variables.len(),
+                });
+                Ok(None)
+            } else {
+                save_environment(pool, environment)
+                    .await
+                    .map(Some)
+                    .map_err(|e| save_error(name, &e))
+            }
+        }
+        Ok(ParsedFile::Globals(variables)) => {
+            if is_preview {
+                report.environments.push(EnvironmentSummary {
+                    name: i18n::t("import.globals"),
+                    variable_count: variables.len(),
+                });
+                Ok(None)
+            } else {
+                save_globals(pool, &variables)
+                    .await
+                    .map(Some)
+                    .map_err(|e| save_error(name, &e))
+            }
+        }
+        Err(e) => Err(i18n::tf(
+            "import.parse_error",
+            &[("file", &name), ("error", &e)],
+        )),
+    };
+
+    match outcome {
+        Ok(msg) => {
+            if let Some(msg) = &msg {
+                report.message.push_str(&success(&prefix, msg));
+            }
+            let status = if is_preview {
+                FileStatus::Ready
+            } else {
+                FileStatus::Imported
+            };
+            let mut file = FileReport::new(name, archive, status, msg);
+            file.warnings = warnings;
+            report.files.push(file);
+        }
+        Err(error) => report.fail(name, archive, error),
+    }
+}
+
+fn success(prefix: &str, details: &str) -> String {
+    let details = format!("{}{}", prefix, details);
+    format!("{}\n", i18n::tf("import.success", &[("details", &details)]))
+}
+
+fn save_error(file: &str, error: &anyhow::Error) -> String {
+    i18n::tf("import.save_error", &[("file", &file), ("error", error)])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::create_test_pool;
+    use axum_test::multipart::{MultipartForm, Part};
+    use axum_test::TestServer;
+    use serde_json::Value;
+    use std::io::Write;
+    use zip::write::SimpleFileOptions;
+
+    fn postman_dump() -> Vec<u8> {
+        let mut zip = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+        let options = SimpleFileOptions::default();
+        let members = [
+            ("archive.json", r#"{"collection":{"c1":true}}"#),
+            (
+                "collection/c1.json",
+                r#"{"info":{"_postman_id":"c1","name":"Dump API"},"item":[{"name":"Ping","request":{"method":"GET","url":"{{base}}/ping"}}]}"#,
+            ),
+            (
+                "environment/e1.json",
+                r#"{"id":"e1","name":"Staging","values":[{"key":"base","value":"https://staging.example","enabled":true},{"key":"off","value":"x","enabled":false}]}"#,
+            ),
+            (
+                "globals/g1.json",
+                r#"{"id":"g1","_postman_variable_scope":"globals","values":[{"key":"retries","value":3}]}"#,
+            ),
+            ("collection/broken.json", "not json"),
+        ];
+        for (name, content) in members {
+            zip.start_file(name, options).unwrap();
+            zip.write_all(content.as_bytes()).unwrap();
+        }
+        zip.finish().unwrap().into_inner()
+    }
+
+    fn upload(data: Vec<u8>) -> MultipartForm {
+        MultipartForm::new().add_part("file", Part::bytes(data).file_name("backup.zip"))
+    }
+
+    #[tokio::test]
+    async fn test_preview_postman_dump() {
+        let pool = create_test_pool().await;
+        let server = TestServer::new(routes(pool)).unwrap();
+
+        let response = server
+            .post("/import")
+            .add_query_param("preview", true)
+            .multipart(upload(postman_dump()))
+            .await;
+        response.assert_status_ok();
+        let body: Value = response.json();
+        assert_eq!(body["collections"][0]["name"], "Dump API");
+        assert_eq!(body["collections"][0]["request_count"], 1);
+        assert_eq!(body["environments"].as_array().unwrap().len(), 2);
+        assert_eq!(body["errors"].as_array().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_import_postman_dump() {
+        let pool = create_test_pool().await;
+        let server = TestServer::new(routes(pool.clone())).unwrap();
+
+        let response = server
+            .post("/import")
+            .multipart(upload(postman_dump()))
+            .await;
+        response.assert_status_ok();
+        let body: Value = response.json();
+        let message = body["message"].as_str().unwrap();
+        assert!(message.contains("environment/e1.json: Imported environment 'Staging'"));
+        assert!(message.contains("Error parsing collection/broken.json"));
+
+        let (variables,): (String,) =
+            sqlx::query_as("SELECT variables FROM environments WHERE name = 'Staging'")
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+        let variables: Value = serde_json::from_str(&variables).unwrap();
+        assert_eq!(variables, json!({"base": "https://staging.example"}));
+
+        assert!(message.contains("globals/g1.json: Imported 1 global variables"));
+        let globals = crate::globals::load(&pool).await.unwrap();
+        assert_eq!(globals["retries"], "3");
+        let (environments,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM environments")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(environments, 1);
+
+        let (count,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM requests")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(count, 1);
+    }
+
+    #[tokio::test]
+    async fn test_restore_workspace_bundle() {
+        let source = create_test_pool().await;
+        let (folder_id,): (i64,) = sqlx::query_as(
+            "INSERT INTO folders (name, variables) VALUES ('Billing', '{\"v\":\"1\"}') RETURNING id",
+        )
+        .fetch_one(&source)
+        .await
+        .unwrap();
+        sqlx::query("INSERT INTO requests (name, method, url, folder_id, test_script) VALUES ('Invoices', 'GET', '{{base}}/invoices', ?, 'jsl.test(1)'), ('Health', 'GET', '/health', NULL, NULL)")
+            .bind(folder_id)
+            .execute(&source)
+            .await
+            .unwrap();
+        let (base_id,): (i64,) = sqlx::query_as(
+            "INSERT INTO environments (name, variables) VALUES ('Base', '{\"base\":\"https://api\"}') RETURNING id",
+        )
+        .fetch_one(&source)
+        .await
+        .unwrap();
+        sqlx::query("INSERT INTO environments (name, variables, secrets, parent_id) VALUES ('Prod', '{\"token\":\"enc:v1:abc\"}', '[\"token\"]', ?)")
+            .bind(base_id)
+            .execute(&source)
+            .await
+            .unwrap();
+        sqlx::query("INSERT INTO global_variables (name, value) VALUES ('tenant', 'acme')")
+            .execute(&source)
+            .await
+            .unwrap();
+        let bundle = TestServer::new(crate::export::routes(source))
+            .unwrap()
+            .get("/export/bundle.zip")
+            .await
+            .as_bytes()
+            .to_vec();
+
+        let pool = create_test_pool().await;
+        let server = TestServer::new(routes(pool.clone())).unwrap();
+        let preview: Value = server
+            .post("/import")
+            .add_query_param("preview", true)
+            .multipart(upload(bundle.clone()))
+            .await
+            .json();
+        assert_eq!(preview["collections"].as_array().unwrap().len(), 2);
+        assert_eq!(preview["environments"].as_array().unwrap().len(), 2);
+
+        let body: Value = server
+            .post("/import")
+            .multipart(upload(bundle))
+            .await
+            .json();
+        assert!(body["message"]
+            .as_str()
+            .unwrap()
+            .contains("1 folders, 2 requests and 2 environments"));
+
+        let (request_folder, variables, test_script): (Option<i64>, String, Option<String>) =
+            sqlx::query_as("SELECT r.folder_id, f.variables, r.test_script FROM requests r JOIN folders f ON f.id = r.folder_id WHERE r.name = 'Invoices'")
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+        assert!(request_folder.is_some());
+        assert_eq!(variables, r#"{"v":"1"}"#);
+        assert_eq!(test_script.as_deref(), Some("jsl.test(1)"));
+
+        // Secret values are not part of the bundle
+        let (parent, variables): (String, String) = sqlx::query_as("SELECT p.name, e.variables FROM environments e JOIN environments p ON p.id = e.parent_id WHERE e.name = 'Prod'")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(parent, "Base");
+        assert_eq!(variables, r#"{"token":""}"#);
+
+        let (tenant,): (String,) =
+            sqlx::query_as("SELECT value FROM global_variables WHERE name = 'tenant'")
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+        assert_eq!(tenant, "acme");
+    }
+
+    #[tokio::test]
+    async fn test_archive_file_reports() {
+        let mut zip = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+        let options = SimpleFileOptions::default();
+        let members = [
+            (
+                "workspace/orders.json",
+                r#"{"info":{"_postman_id":"o1","name":"Orders"},"item":[{"name":"List","request":{"method":"GET","url":"/orders"}}]}"#,
+            ),
+            ("workspace/.env.staging", "BASE=https://staging.example\n"),
+            ("workspace/README.md", "# Orders API"),
+            ("workspace/broken.yaml", "- [unclosed"),
+        ];
+        for (name, content) in members {
+            zip.start_file(name, options).unwrap();
+            zip.write_all(content.as_bytes()).unwrap();
+        }
+        let data = zip.finish().unwrap().into_inner();
+
+        let pool = create_test_pool().await;
+        let server = TestServer::new(routes(pool.clone())).unwrap();
+        let body: Value = server.post("/import").multipart(upload(data)).await.json();
+        let files: Vec<(&str, &str)> = body["files"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|f| (f["file"].as_str().unwrap(), f["status"].as_str().unwrap()))
+            .collect();
+        assert_eq!(
+            files,
+            vec![
+                ("workspace/README.md", "skipped"),
+                ("workspace/orders.json", "imported"),
+                ("workspace/.env.staging", "imported"),
+                ("workspace/broken.yaml", "failed"),
+            ]
+        );
+        assert_eq!(body["files"][0]["archive"], "backup.zip");
+        assert!(body["files"][3]["message"]
+            .as_str()
+            .unwrap()
+            .contains("Error parsing workspace/broken.yaml"));
+
+        let (variables,): (String,) =
+            sqlx::query_as("SELECT variables FROM environments WHERE name = 'staging'")
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+        assert_eq!(variables, r#"{"BASE":"https://staging.example"}"#);
+    }
+
+    #[tokio::test]
+    async fn test_import_conflict_strategies() {
+        let pool = create_test_pool().await;
+        let server = TestServer::new(routes(pool.clone())).unwrap();
+        let collection = |url: &str| {
+            let content = format!(
+                r#"{{"info":{{"_postman_id":"c1","name":"Shop"}},"item":[{{"name":"List","request":{{"method":"GET","url":"{}"}}}},{{"name":"Create","request":{{"method":"POST","url":"/items"}}}}]}}"#,
+                url
+            );
+            MultipartForm::new().add_part(
+                "file",
+                Part::bytes(content.into_bytes()).file_name("shop.json"),
+            )
+        };
+        let import = |conflict: &'static str, url: &'static str| {
+            server
+                .post("/import")
+                .add_query_param("conflict", conflict)
+                .multipart(collection(url))
+        };
+        let counts = || async {
+            let folders: (i64,) =
+                sqlx::query_as("SELECT COUNT(*) FROM folders WHERE name = 'Shop'")
+                    .fetch_one(&pool)
+                    .await
+                    .unwrap();
+            let requests: (i64,) = sqlx::query_as("SELECT COUNT(*) FROM requests")
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+            (folders.0, requests.0)
+        };
+
+        import("duplicate", "/items").await.assert_status_ok();
+        let preview: Value = server
+            .post("/import")
+            .add_query_param("preview", true)
+            .multipart(collection("/items"))
+            .await
+            .json();
+        assert_eq!(preview["collections"][0]["exists"], true);
+
+        import("duplicate", "/items").await.assert_status_ok();
+        assert_eq!(counts().await, (2, 4));
+        sqlx::query("DELETE FROM folders WHERE id = (SELECT MAX(id) FROM folders)")
+            .execute(&pool)
+            .await
+            .unwrap();
+        assert_eq!(counts().await, (1, 2));
+
+        // "List" changed its URL, so only it is new to skip
+        let body: Value = import("skip", "/items?page=1").await.json();
+        assert!(body["message"].as_str().unwrap().contains("skipped 1"));
+        assert_eq!(counts().await, (1, 3));
+
+        let (id,): (i64,) =
+            sqlx::query_as("SELECT id FROM requests WHERE name = 'List' ORDER BY id LIMIT 1")
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+        import("overwrite", "/items?page=2")
+            .await
+            .assert_status_ok();
+        assert_eq!(counts().await, (1, 3));
+        let (url,): (String,) = sqlx::query_as("SELECT url FROM requests WHERE id = ?")
+            .bind(id)
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(url, "/items?page=2");
+
+        import("merge", "/items").await.assert_status_ok();
+        assert_eq!(counts().await, (1, 5));
+    }
+
+    #[tokio::test]
+    async fn test_import_status_codes() {
+        let pool = create_test_pool().await;
+        let server = TestServer::new(routes(pool)).unwrap();
+        let file = |name: &str, content: &str| {
+            Part::bytes(content.as_bytes().to_vec()).file_name(name.to_string())
+        };
+
+        let response = server
+            .post("/import")
+            .multipart(MultipartForm::new().add_part("file", file("notes.txt", "hello")))
+            .await;
+        response.assert_status(StatusCode::UNPROCESSABLE_ENTITY);
+        let body: Value = response.json();
+        assert_eq!(body["files"][0]["status"], "failed");
+        assert_eq!(body["errors"].as_array().unwrap().len(), 1);
+
+        // One good file is enough for a 200; the warnings say what is odd
+        let response = server
+            .post("/import")
+            .add_query_param("preview", true)
+            .multipart(
+                MultipartForm::new()
+                    .add_part("a", file("notes.txt", "hello"))
+                    .add_part(
+                        "b",
+                        file(
+                            "empty.json",
+                            r#"{"info":{"_postman_id":"e","name":"Empty"},"item":[]}"#,
+                        ),
+                    ),
+            )
+            .await;
+        response.assert_status_ok();
+        let body: Value = response.json();
+        assert_eq!(body["files"][1]["status"], "ready");
+        assert_eq!(
+            body["files"][1]["warnings"][0],
+            "'Empty' contains no requests"
+        );
+
+        server
+            .post("/import")
+            .multipart(MultipartForm::new())
+            .await
+            .assert_status(StatusCode::BAD_REQUEST);
+        server
+            .post("/import")
+            .content_type("multipart/form-data; boundary=x")
+            .text("--x\r\nContent-Disposition: form-data; name=\"file\"; filename=\"a.json\"\r\n\r\n{")
+            .await
+            .assert_status(StatusCode::BAD_REQUEST);
     }
 }
@@ -2,13 +2,22 @@ use anyhow::Context;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use sqlx::{Row, SqlitePool};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::io::{Cursor, Read};
+
+use crate::form_body::{self, FormRow};
+use crate::globals;
+use crate::header_rows::{self, HeaderRow};
+use crate::i18n;
+use crate::requests;
 
 // Intermediate structures for parsing and preview
 #[derive(Debug, Serialize, Clone)]
 pub struct ParsedFolder {
     pub name: String,
     pub requests: Vec<ParsedRequest>,
+    /// Collection variables, saved as the folder's variables
+    pub variables: BTreeMap<String, String>,
 }
 
 #[derive(Debug, Serialize, Clone)]
@@ -16,9 +25,11 @@ pub struct ParsedRequest {
     pub name: String,
     pub method: String,
     pub url: String,
+    /// Raw body; for `form` bodies the rows as JSON, see form_body
     pub body: Option<String>,
     pub body_type: String,
-    pub headers: HashMap<String, String>,
+    /// In order, disabled ones included
+    pub headers: Vec<HeaderRow>,
     pub auth_type: String,
     pub auth_token: Option<String>,
     pub auth_username: Option<String>,
@@ -29,65 +40,286 @@ pub struct ParsedRequest {
 pub struct CollectionSummary {
     pub name: String,
     pub request_count: usize,
+    /// A folder of this name exists already, so the import's conflict
+    /// strategy applies to it
+    pub exists: bool,
+}
+
+/// What an import does with a collection named like an existing folder.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConflictStrategy {
+    /// Creates a second folder of the same name
+    #[default]
+    Duplicate,
+    /// Adds every imported request to the existing folder
+    Merge,
+    /// Adds only the requests the folder does not have, matched by name,
+    /// method and URL
+    Skip,
+    /// Updates the folder's requests matched by name and method in place,
+    /// keeping their history, and adds the others
+    Overwrite,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct ParsedEnvironment {
+    pub name: String,
+    pub variables: BTreeMap<String, String>,
 }
 
+#[derive(Debug, Serialize)]
+pub struct EnvironmentSummary {
+    pub name: String,
+    pub variable_count: usize,
+}
+
+/// What a single uploaded file, or archive member, contains.
+#[derive(Debug)]
+pub enum ParsedFile {
+    Collections(Vec<ParsedFolder>),
+    Environment(ParsedEnvironment),
+    /// Postman globals, merged into the global variables
+    Globals(BTreeMap<String, String>),
+}
+
+// Limits for archive members, so a small upload cannot inflate into gigabytes
+const MAX_ARCHIVE_MEMBER_SIZE: u64 = 50 * 1024 * 1024;
+const MAX_ARCHIVE_TOTAL_SIZE: u64 = 200 * 1024 * 1024;
+
 // --- Import Logic ---
 
+/// The collection exports [`parse_import_file`] understands.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ImportFormat {
+    ThunderClient,
+    PostmanV2,
+    PostmanV1,
+    /// Insomnia's v4 export, a flat list of resources
+    InsomniaExport,
+    /// Insomnia's v5 collection file, usually YAML
+    InsomniaV5,
+}
+
+fn is_array(object: &serde_json::Map<String, Value>, key: &str) -> bool {
+    object.get(key).is_some_and(Value::is_array)
+}
+
+/// Recognises an export by its structure, however it is formatted.
+fn detect_format(value: &Value) -> Option<ImportFormat> {
+    let object = value.as_object()?;
+    let text = |key: &str| object.get(key).and_then(Value::as_str);
+
+    if text("clientName") == Some("Thunder Client")
+        || (object.contains_key("collectionName") && is_array(object, "requests"))
+    {
+        return Some(ImportFormat::ThunderClient);
+    }
+    if let Some(info) = object.get("info").and_then(Value::as_object) {
+        let schema = info
+            .get("schema")
+            .and_then(Value::as_str)
+            .unwrap_or_default();
+        if info.contains_key("_postman_id")
+            || schema.contains("schema.getpostman.com")
+            || is_array(object, "item")
+        {
+            return Some(ImportFormat::PostmanV2);
+        }
+    }
+    if is_array(object, "requests") && (is_array(object, "folders") || is_array(object, "order")) {
+        return Some(ImportFormat::PostmanV1);
+    }
+    if let Some(resources) = object.get("resources").and_then(Value::as_array) {
+        if text("_type") == Some("export") || resources.iter().any(|r| r.get("_type").is_some()) {
+            return Some(ImportFormat::InsomniaExport);
+        }
+    }
+    if text("type").is_some_and(|t| t.starts_with("collection.insomnia.rest"))
+        || is_array(object, "collection")
+    {
+        return Some(ImportFormat::InsomniaV5);
+    }
+    None
+}
+
+/// Reads a JSON or YAML document; YAML is only tried for files that are not
+/// JSON, so JSON errors keep their own message.
+fn parse_document(content: &[u8], file_name: &str) -> Result<Value, anyhow::Error> {
+    let json_error = match serde_json::from_slice::<Value>(content) {
+        Ok(value) => return Ok(value),
+        Err(e) => e,
+    };
+    let lower = file_name.to_lowercase();
+    let looks_like_json = content
+        .iter()
+        .find(|b| !b.is_ascii_whitespace())
+        .is_some_and(|b| matches!(b, b'{' | b'['));
+    if lower.ends_with(".json") || (looks_like_json && !lower.ends_with(".yaml")) {
+        return Err(anyhow::Error::new(json_error).context("The file is not valid JSON"));
+    }
+    serde_yaml::from_slice::<Value>(content).context("The file is neither JSON nor YAML")
+}
+
 pub fn parse_import_file(
     content: &[u8],
     file_name: &str,
 ) -> Result<Vec<ParsedFolder>, anyhow::Error> {
-    let content_str = String::from_utf8_lossy(content);
+    let document = parse_document(content, file_name)?;
+    match detect_format(&document) {
+        Some(ImportFormat::ThunderClient) => {
+            parse_thunder_client(document).context("Failed to parse Thunder Client export")
+        }
+        Some(ImportFormat::PostmanV2) => {
+            parse_postman_v2(document).context("Failed to parse Postman v2 export")
+        }
+        Some(ImportFormat::PostmanV1) => {
+            parse_postman_v1(document).context("Failed to parse Postman v1 export")
+        }
+        Some(ImportFormat::InsomniaExport) => serde_json::from_value(document)
+            .map_err(anyhow::Error::from)
+            .and_then(parse_insomnia)
+            .context("Failed to parse Insomnia export"),
+        Some(ImportFormat::InsomniaV5) => serde_json::from_value(document)
+            .map_err(anyhow::Error::from)
+            .and_then(parse_insomnia_v5)
+            .context("Failed to parse Insomnia collection"),
+        None => Err(anyhow::anyhow!(
+            "Unknown file format. Please use Postman (v1/v2), Insomnia, or Thunder Client exports."
+        )),
+    }
+}
 
-    // Order matters here. Specific formats first.
+/// Parses a Postman environment or globals export, a `.env` file, or falls
+/// back to [`parse_import_file`] for collections.
+#[tracing::instrument(name = "import.parse", skip(content), fields(bytes = content.len()))]
+pub fn parse_file(content: &[u8], file_name: &str) -> Result<ParsedFile, anyhow::Error> {
+    if let Some(name) = dotenv_name(file_name) {
+        let variables = parse_dotenv(&String::from_utf8_lossy(content))
+            .map_err(anyhow::Error::msg)
+            .context("Failed to parse .env file")?;
+        return Ok(ParsedFile::Environment(ParsedEnvironment {
+            name,
+            variables,
+        }));
+    }
+    if let Some(parsed) = parse_postman_environment(content, file_name)? {
+        return Ok(parsed);
+    }
+    parse_import_file(content, file_name).map(ParsedFile::Collections)
+}
 
-    if content_str.contains("\"clientName\": \"Thunder Client\"") {
-        parse_thunder_client(&content_str).context("Failed to parse Thunder Client export")
-    } else if content_str.contains("\"_postman_id\"")
-        || content_str.contains("\"schema\": \"https://schema.getpostman.com/json/collection/v2")
-    {
-        parse_postman_v2(&content_str).context("Failed to parse Postman v2 export")
-    } else if content_str.contains("\"requests\": [") && content_str.contains("\"folders\": [") {
-        // Likely Postman v1
-        parse_postman_v1(&content_str).context("Failed to parse Postman v1 export")
-    } else if content_str.contains("collection.insomnia.rest")
-        || content_str.contains("_type\": \"request_group\"")
-        || file_name.ends_with(".yaml")
-        || file_name.ends_with(".yml")
-    {
-        // Insomnia export (JSON Export or YAML Collection)
-        // Try parsing as Export first (JSON)
-        if let Ok(export) = serde_json::from_str::<InsomniaExport>(&content_str) {
-            return parse_insomnia(export).context("Failed to parse Insomnia JSON export");
-        }
+pub fn is_zip_archive(content: &[u8], file_name: &str) -> bool {
+    content.starts_with(b"PK\x03\x04") || file_name.to_lowercase().ends_with(".zip")
+}
 
-        // Try parsing as V5 Collection (YAML or JSON)
-        // Since content_str is a lossy string, we can try parsing.
-        if let Ok(export) = serde_yaml::from_str::<InsomniaV5>(&content_str) {
-            return parse_insomnia_v5(export).context("Failed to parse Insomnia V5/YAML export");
+/// The importable members of a zip, and the names of those left out.
+#[derive(Debug, Default)]
+pub struct Archive {
+    pub members: Vec<(String, Vec<u8>)>,
+    /// Files that are not collections or environments, such as images or
+    /// a README; directories and system files are not listed
+    pub skipped: Vec<String>,
+}
+
+/// Extracts the importable JSON/YAML and `.env` members of a zip, such as a
+/// Postman data dump containing collections, environments and globals.
+pub fn extract_archive(content: &[u8]) -> Result<Archive, anyhow::Error> {
+    let mut archive =
+        zip::ZipArchive::new(Cursor::new(content)).context("Failed to read zip archive")?;
+    let mut extracted = Archive::default();
+    let mut total_size = 0;
+
+    for index in 0..archive.len() {
+        let file = archive.by_index(index)?;
+        let name = file.name().to_string();
+        let lower = name.to_lowercase();
+        let base_name = lower.rsplit('/').next().unwrap_or_default();
+        let is_dotenv = dotenv_name(base_name).is_some();
+
+        if file.is_dir()
+            || lower.starts_with("__macosx/")
+            || (base_name.starts_with('.') && !is_dotenv)
+            // Postman's dump manifest only lists the IDs of the other members
+            || base_name == "archive.json"
+        {
+            log::debug!("Skipping archive member {}", name);
+            continue;
+        }
+        if !is_dotenv
+            && ![".json", ".yaml", ".yml"]
+                .iter()
+                .any(|ext| base_name.ends_with(ext))
+        {
+            log::debug!("Skipping archive member {}", name);
+            extracted.skipped.push(name);
+            continue;
         }
 
-        // Try generic YAML Export
-        if let Ok(export) = serde_yaml::from_str::<InsomniaExport>(&content_str) {
-            return parse_insomnia(export).context("Failed to parse Insomnia YAML export");
+        let mut data = Vec::new();
+        file.take(MAX_ARCHIVE_MEMBER_SIZE + 1)
+            .read_to_end(&mut data)?;
+        if data.len() as u64 > MAX_ARCHIVE_MEMBER_SIZE {
+            anyhow::bail!("Archive member '{}' is too large", name);
+        }
+        total_size += data.len() as u64;
+        if total_size > MAX_ARCHIVE_TOTAL_SIZE {
+            anyhow::bail!("Archive contents are too large");
         }
 
-        Err(anyhow::anyhow!("Detected Insomnia format but failed to parse as JSON export, YAML collection, or YAML export"))
-    } else {
-        Err(anyhow::anyhow!(
-            "Unknown file format. Please use Postman (v1/v2), Insomnia, or Thunder Client exports."
-        ))
+        extracted.members.push((name, data));
     }
+
+    Ok(extracted)
+}
+
+#[tracing::instrument(name = "import.save_environment", skip_all, fields(variables = environment.variables.len()))]
+pub async fn save_environment(
+    pool: &SqlitePool,
+    environment: ParsedEnvironment,
+) -> Result<String, anyhow::Error> {
+    let name = if environment.name.trim().is_empty() {
+        "import"
+    } else {
+        &environment.name
+    };
+    create_environment(pool, name, &environment.variables)
+        .await
+        .context(format!("Failed to create environment '{}'", name))?;
+
+    Ok(i18n::tf(
+        "import.environment_imported",
+        &[("name", &name), ("count", &environment.variables.len())],
+    ))
+}
+
+#[tracing::instrument(name = "import.save_globals", skip_all, fields(variables = variables.len()))]
+pub async fn save_globals(
+    pool: &SqlitePool,
+    variables: &BTreeMap<String, String>,
+) -> Result<String, anyhow::Error> {
+    globals::merge(pool, variables)
+        .await
+        .context("Failed to save global variables")?;
+
+    Ok(i18n::tf(
+        "import.globals_imported",
+        &[("count", &variables.len())],
+    ))
 }
 
+#[tracing::instrument(name = "import.save", skip_all, fields(folders = folders.len()))]
 pub async fn save_import(
     pool: &SqlitePool,
     folders: Vec<ParsedFolder>,
+    conflict: ConflictStrategy,
 ) -> Result<String, anyhow::Error> {
     let mut total_requests = 0;
+    let mut updated = 0;
+    let mut skipped = 0;
 
     if folders.is_empty() {
-        return Ok("No collections found to import".to_string());
+        return Ok(i18n::t("import.no_collections"));
     }
 
     for folder in folders {
@@ -97,48 +329,158 @@ pub async fn save_import(
         } else {
             &folder.name
         };
-        let folder_id = create_folder(pool, folder_name)
-            .await
-            .context(format!("Failed to create folder '{}'", folder_name))?;
+        let existing = match conflict {
+            ConflictStrategy::Duplicate => None,
+            _ => find_folder(pool, folder_name).await?,
+        };
+        let Some(folder_id) = existing else {
+            let folder_id = create_folder(pool, folder_name)
+                .await
+                .context(format!("Failed to create folder '{}'", folder_name))?;
+            merge_folder_variables(pool, folder_id, &folder.variables, true).await?;
+            for req in folder.requests {
+                create_request(pool, &req, Some(folder_id))
+                    .await
+                    .context(format!("Failed to create request '{}'", req.name))?;
+                total_requests += 1;
+            }
+            continue;
+        };
 
+        log::info!(
+            "Importing into existing folder '{}' ({:?})",
+            folder_name,
+            conflict
+        );
+        merge_folder_variables(
+            pool,
+            folder_id,
+            &folder.variables,
+            conflict == ConflictStrategy::Overwrite,
+        )
+        .await?;
+        let current = folder_requests(pool, folder_id).await?;
         for req in folder.requests {
-            create_request(
-                pool,
-                &req.name,
-                &req.method,
-                &req.url,
-                req.body.as_deref(),
-                &req.headers,
-                Some(folder_id),
-                &req.body_type,
-                &req.auth_type,
-                req.auth_token.as_deref(),
-                req.auth_username.as_deref(),
-                req.auth_password.as_deref(),
-            )
-            .await
-            .context(format!("Failed to create request '{}'", req.name))?;
-            total_requests += 1;
+            let matched = current.iter().find(|(_, name, method, url)| {
+                name == &req.name
+                    && method.eq_ignore_ascii_case(&req.method)
+                    && (conflict == ConflictStrategy::Overwrite || url == &req.url)
+            });
+            match (conflict, matched) {
+                (ConflictStrategy::Skip, Some(_)) => skipped += 1,
+                (ConflictStrategy::Overwrite, Some((id, ..))) => {
+                    update_request(pool, *id, &req)
+                        .await
+                        .context(format!("Failed to update request '{}'", req.name))?;
+                    updated += 1;
+                }
+                _ => {
+                    create_request(pool, &req, Some(folder_id))
+                        .await
+                        .context(format!("Failed to create request '{}'", req.name))?;
+                    total_requests += 1;
+                }
+            }
         }
     }
 
-    Ok(format!("Successfully imported {} requests", total_requests))
+    if updated == 0 && skipped == 0 {
+        return Ok(i18n::tf(
+            "import.requests_imported",
+            &[("count", &total_requests)],
+        ));
+    }
+    Ok(i18n::tf(
+        "import.requests_merged",
+        &[
+            ("count", &total_requests),
+            ("updated", &updated),
+            ("skipped", &skipped),
+        ],
+    ))
+}
+
+/// The oldest active folder called `name`.
+pub async fn find_folder(pool: &SqlitePool, name: &str) -> Result<Option<i64>, anyhow::Error> {
+    let row = sqlx::query(
+        "SELECT id FROM folders WHERE name = ? AND archived_at IS NULL ORDER BY id LIMIT 1",
+    )
+    .bind(name)
+    .fetch_optional(pool)
+    .await?;
+    Ok(row.map(|r| r.get(0)))
+}
+
+/// Adds imported collection variables to a folder's. `replace` lets them
+/// win over variables the folder has already.
+async fn merge_folder_variables(
+    pool: &SqlitePool,
+    folder_id: i64,
+    imported: &BTreeMap<String, String>,
+    replace: bool,
+) -> Result<(), anyhow::Error> {
+    if imported.is_empty() {
+        return Ok(());
+    }
+    let row = sqlx::query("SELECT variables FROM folders WHERE id = ?")
+        .bind(folder_id)
+        .fetch_one(pool)
+        .await?;
+    let mut variables: serde_json::Map<String, Value> =
+        serde_json::from_str(row.get::<&str, _>(0)).unwrap_or_default();
+    for (key, value) in imported {
+        if replace || !variables.contains_key(key) {
+            variables.insert(key.clone(), Value::String(value.clone()));
+        }
+    }
+    sqlx::query("UPDATE folders SET variables = ?, updated_at = CURRENT_TIMESTAMP WHERE id = ?")
+        .bind(Value::Object(variables).to_string())
+        .bind(folder_id)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+/// Id, name, method and URL of the active requests in a folder.
+async fn folder_requests(
+    pool: &SqlitePool,
+    folder_id: i64,
+) -> Result<Vec<(i64, String, String, String)>, anyhow::Error> {
+    let rows = sqlx::query(
+        "SELECT id, name, method, url FROM requests WHERE folder_id = ? AND archived_at IS NULL ORDER BY id",
+    )
+    .bind(folder_id)
+    .fetch_all(pool)
+    .await?;
+    Ok(rows
+        .iter()
+        .map(|r| (r.get(0), r.get(1), r.get(2), r.get(3)))
+        .collect())
 }
 
 // --- Parsers ---
 
-fn parse_postman_v2(content: &str) -> Result<Vec<ParsedFolder>, anyhow::Error> {
-    let collection: PostmanCollectionV2 = serde_json::from_str(content)?;
+fn parse_postman_v2(document: Value) -> Result<Vec<ParsedFolder>, anyhow::Error> {
+    let collection: PostmanCollectionV2 = serde_json::from_value(document)?;
     let mut all_requests = Vec::new();
-    flatten_postman_v2_items(&collection.item, &mut all_requests);
+    flatten_postman_v2_items(
+        &collection.item,
+        collection.auth.as_ref(),
+        &mut all_requests,
+    );
 
     Ok(vec![ParsedFolder {
         name: collection.info.name,
         requests: all_requests,
+        variables: postman_variables(collection.variable),
     }])
 }
 
-fn flatten_postman_v2_items(items: &[PostmanItemV2], results: &mut Vec<ParsedRequest>) {
+fn flatten_postman_v2_items(
+    items: &[PostmanItemV2],
+    inherited: Option<&PostmanAuthV2>,
+    results: &mut Vec<ParsedRequest>,
+) {
     for item in items {
         if let Some(req) = &item.request {
             let url = match &req.url {
@@ -147,63 +489,35 @@ fn flatten_postman_v2_items(items: &[PostmanItemV2], results: &mut Vec<ParsedReq
                 None => String::new(),
             };
 
-            let headers: HashMap<String, String> = req
+            let headers: Vec<HeaderRow> = req
                 .header
                 .as_ref()
                 .map(|h| {
                     h.iter()
-                        .map(|header| (header.key.clone(), header.value.clone()))
+                        .map(|header| HeaderRow {
+                            key: header.key.clone(),
+                            value: header.value.clone(),
+                            enabled: !header.disabled,
+                        })
                         .collect()
                 })
                 .unwrap_or_default();
 
             let (body_type, body_content) = match &req.body {
-                Some(b) => {
-                    if let Some(raw) = &b.raw {
-                        ("json", Some(raw.clone()))
-                    } else {
-                        ("none", None)
-                    }
-                }
+                Some(b) => postman_body(b),
                 None => ("none", None),
             };
 
-            let (auth_type, auth_token, auth_user, auth_pass) = if let Some(auth) = &req.auth {
-                match auth.r#type.as_str() {
-                    "bearer" => {
-                        let token = auth
-                            .bearer
-                            .as_ref()
-                            .and_then(|params| params.iter().find(|p| p.key == "token"))
-                            .map(|p| match &p.value {
-                                Value::String(s) => s.clone(),
-                                v => v.to_string(),
-                            });
-                        ("bearer".to_string(), token, None, None)
-                    }
-                    "basic" => {
-                        let user = auth
-                            .basic
-                            .as_ref()
-                            .and_then(|params| params.iter().find(|p| p.key == "username"))
-                            .map(|p| match &p.value {
-                                Value::String(s) => s.clone(),
-                                v => v.to_string(),
-                            });
-                        let pass = auth
-                            .basic
-                            .as_ref()
-                            .and_then(|params| params.iter().find(|p| p.key == "password"))
-                            .map(|p| match &p.value {
-                                Value::String(s) => s.clone(),
-                                v => v.to_string(),
-                            });
-                        ("basic".to_string(), None, user, pass)
-                    }
-                    _ => ("none".to_string(), None, None, None),
-                }
-            } else {
-                ("none".to_string(), None, None, None)
+            // Requests without auth of their own use the nearest folder's, or
+            // the collection's
+            let (auth_type, auth_token, auth_user, auth_pass) = match req
+                .auth
+                .as_ref()
+                .filter(|a| a.r#type != "inherit")
+                .or(inherited)
+            {
+                Some(auth) => postman_auth(auth),
+                None => ("none".to_string(), None, None, None),
             };
 
             results.push(ParsedRequest {
@@ -219,30 +533,105 @@ fn flatten_postman_v2_items(items: &[PostmanItemV2], results: &mut Vec<ParsedReq
                 auth_password: auth_pass,
             });
         } else if let Some(sub_items) = &item.item {
-            flatten_postman_v2_items(sub_items, results);
+            let auth = item
+                .auth
+                .as_ref()
+                .filter(|a| a.r#type != "inherit")
+                .or(inherited);
+            flatten_postman_v2_items(sub_items, auth, results);
         }
     }
 }
 
-fn parse_postman_v1(content: &str) -> Result<Vec<ParsedFolder>, anyhow::Error> {
-    let collection: PostmanCollectionV1 = serde_json::from_str(content)?;
+/// Maps Postman auth to js-link's auth type, token, username and password.
+fn postman_auth(auth: &PostmanAuthV2) -> (String, Option<String>, Option<String>, Option<String>) {
+    let param = |params: &Option<Vec<PostmanAuthParamV2>>, key: &str| {
+        params
+            .as_ref()
+            .and_then(|params| params.iter().find(|p| p.key == key))
+            .map(|p| match &p.value {
+                Value::String(s) => s.clone(),
+                v => v.to_string(),
+            })
+    };
+    match auth.r#type.as_str() {
+        "bearer" => (
+            "bearer".to_string(),
+            param(&auth.bearer, "token"),
+            None,
+            None,
+        ),
+        "basic" => (
+            "basic".to_string(),
+            None,
+            param(&auth.basic, "username"),
+            param(&auth.basic, "password"),
+        ),
+        _ => ("none".to_string(), None, None, None),
+    }
+}
+
+/// The enabled variables of a Postman environment or collection.
+fn postman_variables(variables: Vec<PostmanVariable>) -> BTreeMap<String, String> {
+    variables
+        .into_iter()
+        .filter(|v| v.enabled && !v.disabled)
+        .map(|v| {
+            let value = match v.value {
+                Value::String(s) => s,
+                Value::Null => String::new(),
+                v => v.to_string(),
+            };
+            (v.key, value)
+        })
+        .collect()
+}
+
+fn parse_postman_v1(document: Value) -> Result<Vec<ParsedFolder>, anyhow::Error> {
+    let collection: PostmanCollectionV1 = serde_json::from_value(document)?;
     let mut requests = Vec::new();
 
     for req in collection.requests {
-        let mut headers = HashMap::new();
-        // Postman v1 headers are often a string
+        let mut headers = Vec::new();
+        // Postman v1 headers are often a string; disabled ones start with //
         for line in req.headers.lines() {
+            let (line, enabled) = match line.trim_start().strip_prefix("//") {
+                Some(rest) => (rest, false),
+                None => (line, true),
+            };
             if let Some((key, value)) = line.split_once(':') {
-                headers.insert(key.trim().to_string(), value.trim().to_string());
+                headers.push(HeaderRow {
+                    key: key.trim().to_string(),
+                    value: value.trim().to_string(),
+                    enabled,
+                });
             }
         }
 
+        let rows = |data: &[PostmanFormParamV2]| {
+            data.iter()
+                .filter(|param| param.kind.as_deref() != Some("file"))
+                .map(|param| FormRow {
+                    key: param.key.clone(),
+                    value: param.value.clone(),
+                    enabled: !param.disabled,
+                })
+                .collect::<Vec<_>>()
+        };
+        let (body_type, body) = match (req.dataMode.as_deref(), req.rawModeData) {
+            (Some("urlencoded"), _) => ("form", Some(form_body::to_json(&rows(&req.data)))),
+            (Some("params"), _) => ("multipart", Some(multipart_json(&rows(&req.data)))),
+            (Some("binary"), _) => ("binary", None),
+            (_, Some(raw)) if !raw.is_empty() => (text_body_type(None, &raw), Some(raw)),
+            _ => ("none", None),
+        };
+
         requests.push(ParsedRequest {
             name: req.name,
             method: req.method,
             url: req.url,
-            body: req.rawModeData,
-            body_type: "json".to_string(),
+            body,
+            body_type: body_type.to_string(),
             headers,
             auth_type: "none".to_string(),
             auth_token: None,
@@ -254,11 +643,113 @@ fn parse_postman_v1(content: &str) -> Result<Vec<ParsedFolder>, anyhow::Error> {
     Ok(vec![ParsedFolder {
         name: collection.name,
         requests,
+        variables: BTreeMap::new(),
     }])
 }
 
-fn parse_thunder_client(content: &str) -> Result<Vec<ParsedFolder>, anyhow::Error> {
-    let collection: ThunderCollection = serde_json::from_str(content)?;
+fn parse_postman_environment(
+    content: &[u8],
+    file_name: &str,
+) -> Result<Option<ParsedFile>, anyhow::Error> {
+    let Ok(value) = serde_json::from_slice::<Value>(content) else {
+        return Ok(None);
+    };
+    let scope = value.get("_postman_variable_scope").and_then(Value::as_str);
+    let is_globals = scope == Some("globals") || file_name.ends_with("globals.json");
+    // Environments from data dumps may lack the scope marker
+    if scope.is_none() && !(value.get("values").is_some() && value.get("item").is_none()) {
+        return Ok(None);
+    }
+
+    let export: PostmanEnvironment =
+        serde_json::from_value(value).context("Failed to parse Postman environment export")?;
+    let variables = postman_variables(export.values);
+    if is_globals {
+        return Ok(Some(ParsedFile::Globals(variables)));
+    }
+
+    let name = export.name.unwrap_or_default();
+    Ok(Some(ParsedFile::Environment(ParsedEnvironment {
+        name,
+        variables,
+    })))
+}
+
+/// Environment name for a dotenv file: `.env.staging` and `staging.env`
+/// become `staging`, a plain `.env` becomes `dotenv`.
+fn dotenv_name(file_name: &str) -> Option<String> {
+    let base = file_name.rsplit(['/', '\\']).next().unwrap_or(file_name);
+    let name = if base == ".env" {
+        "dotenv"
+    } else if let Some(suffix) = base.strip_prefix(".env.") {
+        suffix
+    } else {
+        base.strip_suffix(".env")?
+    };
+    Some(name.to_string())
+}
+
+/// Parses `KEY=value` lines as dotenv files write them: blank lines and
+/// `#` comments are skipped, an `export ` prefix is allowed, single quotes
+/// keep their content as is and double quotes understand `\n`, `\t`, `\"`
+/// and `\\`. Unquoted values end at a ` #` comment.
+pub fn parse_dotenv(content: &str) -> Result<BTreeMap<String, String>, String> {
+    let mut variables = BTreeMap::new();
+    for (index, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let line = line.strip_prefix("export ").unwrap_or(line);
+        let Some((key, value)) = line.split_once('=') else {
+            return Err(format!("Line {}: expected KEY=value", index + 1));
+        };
+        let key = key.trim();
+        if key.is_empty()
+            || !key
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || "_.-".contains(c))
+        {
+            return Err(format!("Line {}: invalid name '{}'", index + 1, key));
+        }
+        let value = value.trim();
+        let value = if let Some(quoted) = value.strip_prefix('"') {
+            let Some(end) = quoted.rfind('"') else {
+                return Err(format!("Line {}: unterminated quote", index + 1));
+            };
+            let mut unescaped = String::with_capacity(end);
+            let mut chars = quoted[..end].chars();
+            while let Some(c) = chars.next() {
+                if c != '\\' {
+                    unescaped.push(c);
+                    continue;
+                }
+                match chars.next() {
+                    Some('n') => unescaped.push('\n'),
+                    Some('t') => unescaped.push('\t'),
+                    Some(other) => unescaped.push(other),
+                    None => unescaped.push('\\'),
+                }
+            }
+            unescaped
+        } else if let Some(quoted) = value.strip_prefix('\'') {
+            let Some(end) = quoted.rfind('\'') else {
+                return Err(format!("Line {}: unterminated quote", index + 1));
+            };
+            quoted[..end].to_string()
+        } else {
+            match value.find(" #") {
+                Some(comment) => value[..comment].trim_end().to_string(),
+                None => value.to_string(),
+            }
+        };
+        variables.insert(key.to_string(), value);
+    }
+    Ok(variables)
+}
+
+fn parse_thunder_client(document: Value) -> Result<Vec<ParsedFolder>, anyhow::Error> {
+    let collection: ThunderCollection = serde_json::from_value(document)?;
     let mut folders_map: HashMap<String, ParsedFolder> = HashMap::new();
 
     for folder in &collection.folders {
@@ -267,6 +758,7 @@ fn parse_thunder_client(content: &str) -> Result<Vec<ParsedFolder>, anyhow::Erro
             ParsedFolder {
                 name: folder.name.clone(),
                 requests: Vec::new(),
+                variables: BTreeMap::new(),
             },
         );
     }
@@ -274,18 +766,16 @@ fn parse_thunder_client(content: &str) -> Result<Vec<ParsedFolder>, anyhow::Erro
     let mut root_requests = Vec::new();
 
     for req in &collection.requests {
-        let headers: HashMap<String, String> = req
+        let headers: Vec<HeaderRow> = req
             .headers
             .iter()
-            .map(|h| (h.name.clone(), h.value.clone()))
+            .map(|h| HeaderRow::new(h.name.clone(), h.value.clone()))
             .collect();
 
-        let body_content = req.body.as_ref().and_then(|b| b.raw.clone());
-        let body_type = req
-            .body
-            .as_ref()
-            .map(|b| b.body_type.clone())
-            .unwrap_or_else(|| "none".to_string());
+        let (body_type, body_content) = match &req.body {
+            Some(body) => thunder_body(body),
+            None => ("none", None),
+        };
 
         let (auth_type, auth_token, auth_user, auth_pass) = if let Some(auth) = &req.auth {
             match auth.r#type.as_str() {
@@ -307,7 +797,7 @@ fn parse_thunder_client(content: &str) -> Result<Vec<ParsedFolder>, anyhow::Erro
             method: req.method.clone(),
             url: req.url.clone(),
             body: body_content,
-            body_type,
+            body_type: body_type.to_string(),
             headers,
             auth_type,
             auth_token,
@@ -335,6 +825,7 @@ fn parse_thunder_client(content: &str) -> Result<Vec<ParsedFolder>, anyhow::Erro
                 collection.collectionName.clone()
             },
             requests: root_requests,
+            variables: BTreeMap::new(),
         });
     }
 
@@ -352,6 +843,7 @@ fn parse_insomnia(export: InsomniaExport) -> Result<Vec<ParsedFolder>, anyhow::E
                 ParsedFolder {
                     name: res.name.clone().unwrap_or_else(|| "import".to_string()),
                     requests: Vec::new(),
+                    variables: BTreeMap::new(),
                 },
             );
         }
@@ -366,24 +858,17 @@ fn parse_insomnia(export: InsomniaExport) -> Result<Vec<ParsedFolder>, anyhow::E
                 .clone()
                 .unwrap_or_else(|| "Unnamed Request".to_string());
 
-            let headers: HashMap<String, String> = res
+            let headers = res
                 .headers
-                .as_ref()
-                .map(|h| {
-                    h.iter()
-                        .map(|header| (header.name.clone(), header.value.clone()))
-                        .collect()
-                })
+                .as_deref()
+                .map(insomnia_header_rows)
                 .unwrap_or_default();
 
-            let (body_type, real_body) = if let Some(body_obj) = &res.body {
-                if let Some(text) = body_obj.get("text").and_then(|t| t.as_str()) {
-                    ("json", Some(text.to_string()))
-                } else {
-                    ("none", None)
-                }
-            } else {
-                ("none", None)
+            let (body_type, real_body) = match &res.body {
+                Some(body) => serde_json::from_value::<InsomniaV5Body>(body.clone())
+                    .map(|body| insomnia_body(&body))
+                    .unwrap_or(("none", None)),
+                None => ("none", None),
             };
 
             let (auth_type, auth_token, auth_user, auth_pass) =
@@ -455,6 +940,7 @@ fn parse_insomnia(export: InsomniaExport) -> Result<Vec<ParsedFolder>, anyhow::E
         final_folders.push(ParsedFolder {
             name: "import".to_string(),
             requests: root_requests,
+            variables: BTreeMap::new(),
         });
     }
 
@@ -500,6 +986,7 @@ fn collect_insomnia_v5_items(item: &InsomniaV5Item, path: String, folders: &mut
             folders.push(ParsedFolder {
                 name: folder_name,
                 requests,
+                variables: BTreeMap::new(),
             });
         }
     } else if item.url.is_some() {
@@ -521,24 +1008,15 @@ fn parse_insomnia_v5_request(item: &InsomniaV5Item) -> ParsedRequest {
         .clone()
         .unwrap_or_else(|| "Unnamed Request".to_string());
 
-    let headers: HashMap<String, String> = item
+    let headers = item
         .headers
-        .as_ref()
-        .map(|h| {
-            h.iter()
-                .map(|header| (header.name.clone(), header.value.clone()))
-                .collect()
-        })
+        .as_deref()
+        .map(insomnia_header_rows)
         .unwrap_or_default();
 
-    let (body_type, body_content) = if let Some(body) = &item.body {
-        if let Some(text) = &body.text {
-            ("json".to_string(), Some(text.clone()))
-        } else {
-            ("none".to_string(), None)
-        }
-    } else {
-        ("none".to_string(), None)
+    let (body_type, body_content) = match &item.body {
+        Some(body) => insomnia_body(body),
+        None => ("none", None),
     };
 
     let (auth_type, auth_token, auth_user, auth_pass) = if let Some(auth) = &item.authentication {
@@ -561,7 +1039,7 @@ fn parse_insomnia_v5_request(item: &InsomniaV5Item) -> ParsedRequest {
         method,
         url,
         body: body_content,
-        body_type,
+        body_type: body_type.to_string(),
         headers,
         auth_type,
         auth_token,
@@ -570,12 +1048,158 @@ fn parse_insomnia_v5_request(item: &InsomniaV5Item) -> ParsedRequest {
     }
 }
 
+fn insomnia_header_rows(headers: &[InsomniaHeader]) -> Vec<HeaderRow> {
+    headers
+        .iter()
+        .map(|header| HeaderRow {
+            key: header.name.clone(),
+            value: header.value.clone(),
+            enabled: !header.disabled,
+        })
+        .collect()
+}
+
+/// The js-link body type of a textual body declared as `kind`, a media type
+/// or Postman's raw language. Undeclared bodies are recognised by their
+/// first character.
+fn text_body_type(kind: Option<&str>, text: &str) -> &'static str {
+    let kind = kind.unwrap_or_default().to_lowercase();
+    if kind.contains("json") || kind.contains("graphql") {
+        "json"
+    } else if kind.contains("xml") {
+        "xml"
+    } else if !kind.is_empty() {
+        "text"
+    } else {
+        match text.trim_start().chars().next() {
+            Some('{') | Some('[') => "json",
+            Some('<') => "xml",
+            _ => "text",
+        }
+    }
+}
+
+/// A `multipart` body: the enabled text fields as a JSON object, see
+/// executor::execute. Files cannot be imported.
+fn multipart_json(rows: &[FormRow]) -> String {
+    let fields: serde_json::Map<String, Value> = rows
+        .iter()
+        .filter(|row| row.enabled)
+        .map(|row| (row.key.clone(), Value::String(row.value.clone())))
+        .collect();
+    Value::Object(fields).to_string()
+}
+
+/// A GraphQL query sent as JSON; `variables` is the JSON text the tools
+/// store them as.
+fn graphql_json(query: &str, variables: Option<&str>) -> String {
+    let variables = variables
+        .filter(|v| !v.trim().is_empty())
+        .and_then(|v| serde_json::from_str::<Value>(v).ok())
+        .unwrap_or_else(|| Value::Object(Default::default()));
+    serde_json::to_string_pretty(&serde_json::json!({ "query": query, "variables": variables }))
+        .unwrap_or_default()
+}
+
+fn postman_body(body: &PostmanBodyV2) -> (&'static str, Option<String>) {
+    let rows = |params: &[PostmanFormParamV2]| -> Vec<FormRow> {
+        params
+            .iter()
+            .filter(|param| param.kind.as_deref() != Some("file"))
+            .map(|param| FormRow {
+                key: param.key.clone(),
+                value: param.value.clone(),
+                enabled: !param.disabled,
+            })
+            .collect()
+    };
+    match body.mode.as_deref() {
+        Some("urlencoded") => ("form", Some(form_body::to_json(&rows(&body.urlencoded)))),
+        Some("formdata") => ("multipart", Some(multipart_json(&rows(&body.formdata)))),
+        Some("graphql") => match &body.graphql {
+            Some(graphql) => (
+                "json",
+                Some(graphql_json(&graphql.query, graphql.variables.as_deref())),
+            ),
+            None => ("none", None),
+        },
+        Some("file") => ("binary", None),
+        _ => match &body.raw {
+            Some(raw) => {
+                let language = body
+                    .options
+                    .as_ref()
+                    .and_then(|o| o.pointer("/raw/language"))
+                    .and_then(Value::as_str);
+                (text_body_type(language, raw), Some(raw.clone()))
+            }
+            None => ("none", None),
+        },
+    }
+}
+
+fn thunder_body(body: &ThunderBody) -> (&'static str, Option<String>) {
+    let rows = || -> Vec<FormRow> {
+        body.form
+            .iter()
+            .map(|field| FormRow {
+                key: field.name.clone(),
+                value: field.value.clone(),
+                enabled: !field.is_disabled,
+            })
+            .collect()
+    };
+    match body.body_type.as_str() {
+        "formencode" => ("form", Some(form_body::to_json(&rows()))),
+        "formdata" => ("multipart", Some(multipart_json(&rows()))),
+        "graphql" => match &body.graphql {
+            Some(graphql) => (
+                "json",
+                Some(graphql_json(&graphql.query, graphql.variables.as_deref())),
+            ),
+            None => ("none", None),
+        },
+        "binary" => ("binary", None),
+        "none" => ("none", None),
+        kind => match &body.raw {
+            Some(raw) => (text_body_type(Some(kind), raw), Some(raw.clone())),
+            None => ("none", None),
+        },
+    }
+}
+
+fn insomnia_body(body: &InsomniaV5Body) -> (&'static str, Option<String>) {
+    let rows = || -> Vec<FormRow> {
+        body.params
+            .iter()
+            .filter(|param| param.kind.as_deref() != Some("file"))
+            .map(|param| FormRow {
+                key: param.name.clone(),
+                value: param.value.clone(),
+                enabled: !param.disabled,
+            })
+            .collect()
+    };
+    match body.mime_type.as_deref() {
+        Some("application/x-www-form-urlencoded") => ("form", Some(form_body::to_json(&rows()))),
+        Some("multipart/form-data") => ("multipart", Some(multipart_json(&rows()))),
+        Some("application/octet-stream") => ("binary", None),
+        mime_type => match &body.text {
+            Some(text) => (text_body_type(mime_type, text), Some(text.clone())),
+            None => ("none", None),
+        },
+    }
+}
+
 // --- Structs for Deserialization ---
 
 #[derive(Debug, Deserialize)]
 struct PostmanCollectionV2 {
     info: PostmanInfoV2,
     item: Vec<PostmanItemV2>,
+    auth: Option<PostmanAuthV2>,
+    #[serde(default)]
+    variable: Vec<PostmanVariable>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -588,6 +1212,8 @@ struct PostmanItemV2 {
     name: String,
     request: Option<PostmanRequestV2>,
     item: Option<Vec<PostmanItemV2>>,
+    /// Of a folder, for the requests in it
+    auth: Option<PostmanAuthV2>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -623,11 +1249,66 @@ enum PostmanUrlV2 {
 struct PostmanHeaderV2 {
     key: String,
     value: String,
+    #[serde(default)]
+    disabled: bool,
 }
 
 #[derive(Debug, Deserialize)]
 struct PostmanBodyV2 {
+    mode: Option<String>,
     raw: Option<String>,
+    /// `raw.language` says what a raw body is
+    options: Option<Value>,
+    #[serde(default)]
+    urlencoded: Vec<PostmanFormParamV2>,
+    #[serde(default)]
+    formdata: Vec<PostmanFormParamV2>,
+    graphql: Option<GraphqlBody>,
+}
+
+/// A row of a Postman urlencoded or form-data body, or of a v1 request's
+/// `data`.
+#[derive(Debug, Deserialize)]
+struct PostmanFormParamV2 {
+    key: String,
+    #[serde(default)]
+    value: String,
+    #[serde(default)]
+    disabled: bool,
+    /// `file` for form-data uploads
+    #[serde(rename = "type")]
+    kind: Option<String>,
+}
+
+/// A GraphQL body of Postman or Thunder Client.
+#[derive(Debug, Deserialize)]
+struct GraphqlBody {
+    #[serde(default)]
+    query: String,
+    variables: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct PostmanEnvironment {
+    name: Option<String>,
+    #[serde(default)]
+    values: Vec<PostmanVariable>,
+}
+
+#[derive(Debug, Deserialize)]
+struct PostmanVariable {
+    key: String,
+    #[serde(default)]
+    value: Value,
+    /// Environments mark variables enabled, collections mark them disabled
+    #[serde(default = "default_enabled")]
+    enabled: bool,
+    #[serde(default)]
+    disabled: bool,
+}
+
+fn default_enabled() -> bool {
+    true
 }
 
 #[derive(Debug, Deserialize)]
@@ -644,6 +1325,9 @@ struct PostmanRequestV1 {
     method: String,
     headers: String,
     rawModeData: Option<String>,
+    dataMode: Option<String>,
+    #[serde(default)]
+    data: Vec<PostmanFormParamV2>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -691,6 +1375,18 @@ struct ThunderBody {
     #[serde(rename = "type")]
     body_type: String,
     raw: Option<String>,
+    #[serde(default)]
+    form: Vec<ThunderField>,
+    graphql: Option<GraphqlBody>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ThunderField {
+    name: String,
+    #[serde(default)]
+    value: String,
+    #[serde(default, rename = "isDisabled")]
+    is_disabled: bool,
 }
 
 #[derive(Debug, Deserialize)]
@@ -717,6 +1413,8 @@ struct InsomniaResource {
 struct InsomniaHeader {
     name: String,
     value: String,
+    #[serde(default)]
+    disabled: bool,
 }
 
 // Insomnia V5
@@ -738,10 +1436,24 @@ struct InsomniaV5Item {
 
 #[derive(Debug, Deserialize)]
 struct InsomniaV5Body {
-    #[allow(dead_code)]
     #[serde(rename = "mimeType")]
     mime_type: Option<String>,
     text: Option<String>,
+    #[serde(default)]
+    params: Vec<InsomniaParam>,
+}
+
+/// A row of an Insomnia form body.
+#[derive(Debug, Deserialize)]
+struct InsomniaParam {
+    name: String,
+    #[serde(default)]
+    value: String,
+    #[serde(default)]
+    disabled: bool,
+    /// `file` for multipart uploads
+    #[serde(rename = "type")]
+    kind: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -754,7 +1466,7 @@ struct InsomniaV5Auth {
 
 // --- DB Helpers ---
 
-async fn create_folder(pool: &SqlitePool, name: &str) -> Result<i64, anyhow::Error> {
+pub async fn create_folder(pool: &SqlitePool, name: &str) -> Result<i64, anyhow::Error> {
     let row = sqlx::query("INSERT INTO folders (name) VALUES (?) RETURNING id")
         .bind(name)
         .fetch_one(pool)
@@ -762,32 +1474,46 @@ async fn create_folder(pool: &SqlitePool, name: &str) -> Result<i64, anyhow::Err
     Ok(row.get(0))
 }
 
-async fn create_request(
+async fn create_environment(
     pool: &SqlitePool,
     name: &str,
-    method: &str,
-    url: &str,
-    body: Option<&str>,
-    headers: &HashMap<String, String>,
+    variables: &BTreeMap<String, String>,
+) -> Result<i64, anyhow::Error> {
+    let row = sqlx::query("INSERT INTO environments (name, variables) VALUES (?, ?) RETURNING id")
+        .bind(name)
+        .bind(serde_json::to_string(variables)?)
+        .fetch_one(pool)
+        .await?;
+    Ok(row.get(0))
+}
+
+pub async fn create_request(
+    pool: &SqlitePool,
+    req: &ParsedRequest,
     folder_id: Option<i64>,
-    body_type: &str,
-    auth_type: &str,
-    auth_token: Option<&str>,
-    auth_username: Option<&str>,
-    auth_password: Option<&str>,
 ) -> Result<i64, anyhow::Error> {
-    let headers_json = serde_json::to_string(headers)?;
+    let headers_json = header_rows::to_json(&req.headers);
+    // Form rows and multipart fields are only read from body_content
+    let (body, body_content) = if matches!(req.body_type.as_str(), "form" | "multipart") {
+        (None, req.body.as_ref())
+    } else {
+        (req.body.as_ref(), None)
+    };
+    let auth_token = requests::encrypt_credential(req.auth_token.as_deref())?;
+    let auth_username = requests::encrypt_credential(req.auth_username.as_deref())?;
+    let auth_password = requests::encrypt_credential(req.auth_password.as_deref())?;
     let row = sqlx::query(
-        "INSERT INTO requests (name, method, url, body, headers, folder_id, body_type, request_type, auth_type, auth_token, auth_username, auth_password) VALUES (?, ?, ?, ?, ?, ?, ?, 'api', ?, ?, ?, ?) RETURNING id"
+        "INSERT INTO requests (name, method, url, body, headers, folder_id, body_type, body_content, request_type, auth_type, auth_token, auth_username, auth_password) VALUES (?, ?, ?, ?, ?, ?, ?, ?, 'api', ?, ?, ?, ?) RETURNING id"
     )
-        .bind(name)
-        .bind(method)
-        .bind(url)
+        .bind(&req.name)
+        .bind(&req.method)
+        .bind(&req.url)
         .bind(body)
         .bind(headers_json)
         .bind(folder_id)
-        .bind(body_type)
-        .bind(auth_type)
+        .bind(&req.body_type)
+        .bind(body_content)
+        .bind(&req.auth_type)
         .bind(auth_token)
         .bind(auth_username)
         .bind(auth_password)
@@ -796,12 +1522,131 @@ async fn create_request(
     Ok(row.get(0))
 }
 
+/// Replaces what an import carries of request `id`, leaving its scripts,
+/// assertions and history alone.
+async fn update_request(
+    pool: &SqlitePool,
+    id: i64,
+    req: &ParsedRequest,
+) -> Result<(), anyhow::Error> {
+    let headers_json = header_rows::to_json(&req.headers);
+    let (body, body_content) = if matches!(req.body_type.as_str(), "form" | "multipart") {
+        (None, req.body.as_ref())
+    } else {
+        (req.body.as_ref(), None)
+    };
+    let auth_token = requests::encrypt_credential(req.auth_token.as_deref())?;
+    let auth_username = requests::encrypt_credential(req.auth_username.as_deref())?;
+    let auth_password = requests::encrypt_credential(req.auth_password.as_deref())?;
+    sqlx::query(
+        "UPDATE requests SET method = ?, url = ?, body = ?, headers = ?, body_type = ?, body_content = ?, auth_type = ?, auth_token = ?, auth_username = ?, auth_password = ?, updated_at = CURRENT_TIMESTAMP WHERE id = ?"
+    )
+        .bind(&req.method)
+        .bind(&req.url)
+        .bind(body)
+        .bind(headers_json)
+        .bind(&req.body_type)
+        .bind(body_content)
+        .bind(&req.auth_type)
+        .bind(auth_token)
+        .bind(auth_username)
+        .bind(auth_password)
+        .bind(id)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use std::fs;
     use std::path::PathBuf;
 
+    #[test]
+    fn test_parse_dotenv() {
+        let content = "# database\nexport DB_HOST=localhost\nDB_PORT = 5432 # default\n\nGREETING=\"Hello\\n\\\"you\\\"\"\nRAW='a \\n # b'\nEMPTY=\n";
+        let variables = parse_dotenv(content).unwrap();
+        assert_eq!(variables.len(), 5);
+        assert_eq!(variables["DB_HOST"], "localhost");
+        assert_eq!(variables["DB_PORT"], "5432");
+        assert_eq!(variables["GREETING"], "Hello\n\"you\"");
+        assert_eq!(variables["RAW"], "a \\n # b");
+        assert_eq!(variables["EMPTY"], "");
+
+        assert_eq!(
+            parse_dotenv("OK=1\nbroken line").unwrap_err(),
+            "Line 2: expected KEY=value"
+        );
+        assert!(parse_dotenv("A=\"open").is_err());
+
+        match parse_file(b"TOKEN=abc", "config/.env.staging").unwrap() {
+            ParsedFile::Environment(environment) => {
+                assert_eq!(environment.name, "staging");
+                assert_eq!(environment.variables["TOKEN"], "abc");
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+        assert_eq!(dotenv_name(".env").as_deref(), Some("dotenv"));
+        assert_eq!(dotenv_name("local.env").as_deref(), Some("local"));
+        assert_eq!(dotenv_name("collection.json"), None);
+    }
+
+    #[test]
+    fn test_detection_ignores_formatting() {
+        let fixture = |name: &str| -> Value {
+            let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+            path.push(".import");
+            path.push(name);
+            let content = fs::read(&path).unwrap();
+            serde_yaml::from_slice(&content).unwrap()
+        };
+        let v1 = serde_json::json!({
+            "id": "c1",
+            "name": "Legacy",
+            "order": ["r1"],
+            "folders": [],
+            "requests": [{"id": "r1", "name": "Ping", "url": "/ping", "method": "GET", "headers": ""}]
+        });
+        let cases = [
+            (fixture("postman_collection.json"), ImportFormat::PostmanV2),
+            (
+                fixture("thunder-collection.json"),
+                ImportFormat::ThunderClient,
+            ),
+            (fixture("Insomnia.yaml"), ImportFormat::InsomniaV5),
+            (v1, ImportFormat::PostmanV1),
+        ];
+        for (document, format) in cases {
+            assert_eq!(detect_format(&document), Some(format));
+            let minified = serde_json::to_string(&document).unwrap();
+            let pretty = serde_json::to_string_pretty(&document).unwrap();
+            let tabbed = pretty.replace("  ", "\t");
+            let expected = parse_import_file(minified.as_bytes(), "export.json").unwrap();
+            for text in [&pretty, &tabbed] {
+                let parsed = parse_import_file(text.as_bytes(), "export.json").unwrap();
+                assert_eq!(
+                    serde_json::to_value(&parsed).unwrap(),
+                    serde_json::to_value(&expected).unwrap(),
+                    "{:?}",
+                    format
+                );
+            }
+            let yaml = serde_yaml::to_string(&document).unwrap();
+            let parsed = parse_import_file(yaml.as_bytes(), "export.yaml").unwrap();
+            assert_eq!(parsed.len(), expected.len(), "{:?} as YAML", format);
+        }
+
+        let insomnia = r#"{"_type":"export","__export_format":4,"resources":[{"_id":"req_1","_type":"request","name":"Ping","method":"GET","url":"/ping"}]}"#;
+        let folders = parse_import_file(insomnia.as_bytes(), "insomnia.json").unwrap();
+        assert_eq!(folders[0].requests[0].name, "Ping");
+
+        let error = parse_import_file(br#"{"hello": "world"}"#, "other.json").unwrap_err();
+        assert!(error.to_string().starts_with("Unknown file format"));
+        let error = parse_import_file(b"{\"info\": ", "cut.json").unwrap_err();
+        assert_eq!(error.to_string(), "The file is not valid JSON");
+    }
+
     #[test]
     fn test_parse_insomnia_file() {
         let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
@@ -891,7 +1736,7 @@ mod tests {
         let content = fs::read(&path).expect("Failed to read Insomnia.yaml");
         let folders = parse_import_file(&content, "Insomnia.yaml").expect("Failed to parse");
 
-        let result = save_import(&pool, folders).await;
+        let result = save_import(&pool, folders, ConflictStrategy::Duplicate).await;
         match result {
             Ok(msg) => println!("Save successful: {}", msg),
             Err(e) => panic!("Failed to save import: {:?}", e),
@@ -910,7 +1755,7 @@ mod tests {
         let folders =
             parse_import_file(&content, "thunder-collection.json").expect("Failed to parse");
 
-        let result = save_import(&pool, folders).await;
+        let result = save_import(&pool, folders, ConflictStrategy::Duplicate).await;
         match result {
             Ok(msg) => println!("Save successful: {}", msg),
             Err(e) => panic!("Failed to save import: {:?}", e),
@@ -929,7 +1774,7 @@ mod tests {
         let folders =
             parse_import_file(&content, "postman_collection.json").expect("Failed to parse");
 
-        let result = save_import(&pool, folders).await;
+        let result = save_import(&pool, folders, ConflictStrategy::Duplicate).await;
         match result {
             Ok(msg) => println!("Save successful: {}", msg),
             Err(e) => panic!("Failed to save import: {:?}", e),
@@ -966,7 +1811,9 @@ mod tests {
         );
 
         // Save and verify
-        save_import(&pool, folders).await.expect("Failed to save");
+        save_import(&pool, folders, ConflictStrategy::Duplicate)
+            .await
+            .expect("Failed to save");
 
         // Verify saved data
         let row = sqlx::query("SELECT COUNT(*) as count FROM requests WHERE auth_type != 'none'")
@@ -977,4 +1824,214 @@ mod tests {
         println!("Requests with authentication: {}", count);
         assert!(count > 0, "Should have saved requests with authentication");
     }
+
+    #[tokio::test]
+    async fn test_postman_collection_auth_and_variables() {
+        use crate::db::create_test_pool;
+
+        let postman = r#"{
+            "info": {"_postman_id": "1", "name": "Inherited"},
+            "auth": {"type": "bearer", "bearer": [{"key": "token", "value": "{{token}}"}]},
+            "variable": [
+                {"key": "base", "value": "https://api.test"},
+                {"key": "retries", "value": 3},
+                {"key": "old", "value": "x", "disabled": true}
+            ],
+            "item": [
+                {"name": "Me", "request": {"method": "GET", "url": "{{base}}/me"}},
+                {"name": "Public", "request": {"method": "GET", "url": "{{base}}/status", "auth": {"type": "noauth"}}},
+                {"name": "Admin", "auth": {"type": "basic", "basic": [{"key": "username", "value": "root"}, {"key": "password", "value": "pw"}]}, "item": [
+                    {"name": "Users", "request": {"method": "GET", "url": "{{base}}/users"}},
+                    {"name": "Stats", "request": {"method": "GET", "url": "{{base}}/stats", "auth": {"type": "inherit"}}}
+                ]}
+            ]
+        }"#;
+        let folders = parse_import_file(postman.as_bytes(), "inherited.json").unwrap();
+        let auth: Vec<(&str, &str, Option<&str>, Option<&str>)> = folders[0]
+            .requests
+            .iter()
+            .map(|r| {
+                (
+                    r.name.as_str(),
+                    r.auth_type.as_str(),
+                    r.auth_token.as_deref(),
+                    r.auth_username.as_deref(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            auth,
+            [
+                ("Me", "bearer", Some("{{token}}"), None),
+                ("Public", "none", None, None),
+                ("Users", "basic", None, Some("root")),
+                ("Stats", "basic", None, Some("root")),
+            ]
+        );
+        assert_eq!(folders[0].variables.len(), 2);
+        assert_eq!(folders[0].variables["retries"], "3");
+
+        let pool = create_test_pool().await;
+        sqlx::query("INSERT INTO folders (name, variables) VALUES ('Inherited', '{\"base\":\"http://localhost\",\"mine\":\"1\"}')")
+            .execute(&pool)
+            .await
+            .unwrap();
+        save_import(&pool, folders, ConflictStrategy::Merge)
+            .await
+            .unwrap();
+        let row = sqlx::query("SELECT variables FROM folders WHERE name = 'Inherited'")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        let variables: Value = serde_json::from_str(row.get::<&str, _>(0)).unwrap();
+        assert_eq!(
+            variables,
+            serde_json::json!({"base": "http://localhost", "mine": "1", "retries": "3"})
+        );
+    }
+
+    #[test]
+    fn test_body_type_mapping() {
+        let postman = r#"{
+            "info": {"_postman_id": "1", "name": "Bodies"},
+            "item": [
+                {"name": "Xml", "request": {"method": "POST", "url": "/x", "body": {"mode": "raw", "raw": "<a/>", "options": {"raw": {"language": "xml"}}}}},
+                {"name": "Text", "request": {"method": "POST", "url": "/t", "body": {"mode": "raw", "raw": "{not json}", "options": {"raw": {"language": "text"}}}}},
+                {"name": "Sniffed", "request": {"method": "POST", "url": "/s", "body": {"mode": "raw", "raw": " {\"a\": 1}"}}},
+                {"name": "Upload", "request": {"method": "POST", "url": "/u", "body": {"mode": "formdata", "formdata": [
+                    {"key": "title", "value": "Report", "type": "text"},
+                    {"key": "file", "src": "/tmp/a.pdf", "type": "file"},
+                    {"key": "draft", "value": "1", "disabled": true}
+                ]}}},
+                {"name": "Query", "request": {"method": "POST", "url": "/graphql", "body": {"mode": "graphql", "graphql": {"query": "{ me { id } }", "variables": "{\"x\": 1}"}}}},
+                {"name": "File", "request": {"method": "PUT", "url": "/f", "body": {"mode": "file", "file": {"src": "a.bin"}}}}
+            ]
+        }"#;
+        let folders = parse_import_file(postman.as_bytes(), "bodies.json").unwrap();
+        let types: Vec<&str> = folders[0]
+            .requests
+            .iter()
+            .map(|r| r.body_type.as_str())
+            .collect();
+        assert_eq!(
+            types,
+            ["xml", "text", "json", "multipart", "json", "binary"]
+        );
+        let requests = &folders[0].requests;
+        assert_eq!(requests[3].body.as_deref(), Some(r#"{"title":"Report"}"#));
+        let graphql: Value = serde_json::from_str(requests[4].body.as_deref().unwrap()).unwrap();
+        assert_eq!(graphql["query"], "{ me { id } }");
+        assert_eq!(graphql["variables"]["x"], 1);
+        assert_eq!(requests[5].body, None);
+
+        let insomnia = r#"{"resources": [
+            {"_id": "fld_1", "_type": "request_group", "name": "Empty"},
+            {"_id": "r1", "_type": "request", "name": "Xml", "method": "POST", "url": "/x", "body": {"mimeType": "application/xml", "text": "<a/>"}},
+            {"_id": "r2", "_type": "request", "name": "Plain", "method": "POST", "url": "/p", "body": {"mimeType": "text/plain", "text": "hi"}},
+            {"_id": "r3", "_type": "request", "name": "Multi", "method": "POST", "url": "/m", "body": {"mimeType": "multipart/form-data", "params": [{"name": "a", "value": "1"}, {"name": "f", "type": "file", "fileName": "x"}]}},
+            {"_id": "r4", "_type": "request", "name": "Gql", "method": "POST", "url": "/g", "body": {"mimeType": "application/graphql", "text": "{\"query\": \"{ me }\"}"}}
+        ]}"#;
+        let folders = parse_import_file(insomnia.as_bytes(), "insomnia.json").unwrap();
+        let mut types: Vec<(&str, &str)> = folders[0]
+            .requests
+            .iter()
+            .map(|r| (r.name.as_str(), r.body_type.as_str()))
+            .collect();
+        types.sort();
+        assert_eq!(
+            types,
+            [
+                ("Gql", "json"),
+                ("Multi", "multipart"),
+                ("Plain", "text"),
+                ("Xml", "xml")
+            ]
+        );
+
+        let thunder = r#"{"clientName": "Thunder Client", "collectionName": "T", "folders": [], "requests": [
+            {"containerId": "", "name": "Form", "url": "/f", "method": "POST", "headers": [], "body": {"type": "formencode", "raw": "", "form": [{"name": "a", "value": "1"}, {"name": "b", "value": "2", "isDisabled": true}]}},
+            {"containerId": "", "name": "Xml", "url": "/x", "method": "POST", "headers": [], "body": {"type": "xml", "raw": "<a/>"}}
+        ]}"#;
+        let folders = parse_import_file(thunder.as_bytes(), "thunder.json").unwrap();
+        let form = &folders[0].requests[0];
+        assert_eq!(form.body_type, "form");
+        let rows = form_body::parse(form.body.as_deref().unwrap()).unwrap();
+        assert_eq!(rows[0], FormRow::new("a", "1"));
+        assert!(!rows[1].enabled);
+        assert_eq!(folders[0].requests[1].body_type, "xml");
+
+        let v1 = r#"{"name": "Old", "folders": [], "requests": [
+            {"name": "Login", "url": "/login", "method": "POST", "headers": "", "dataMode": "urlencoded", "data": [{"key": "user", "value": "ada"}]},
+            {"name": "Raw", "url": "/raw", "method": "POST", "headers": "", "dataMode": "raw", "rawModeData": "hello"}
+        ]}"#;
+        let folders = parse_import_file(v1.as_bytes(), "v1.json").unwrap();
+        assert_eq!(folders[0].requests[0].body_type, "form");
+        assert_eq!(folders[0].requests[1].body_type, "text");
+    }
+
+    #[tokio::test]
+    async fn test_import_form_bodies_and_headers() {
+        use crate::db::create_test_pool;
+
+        let postman = r#"{
+            "info": {"_postman_id": "1", "name": "Forms"},
+            "item": [{
+                "name": "Login",
+                "request": {
+                    "method": "POST",
+                    "url": "https://api.test/login",
+                    "header": [
+                        {"key": "Accept", "value": "application/json"},
+                        {"key": "X-Debug", "value": "1", "disabled": true}
+                    ],
+                    "body": {"mode": "urlencoded", "urlencoded": [
+                        {"key": "user", "value": "ada"},
+                        {"key": "debug", "value": "1", "disabled": true}
+                    ]}
+                }
+            }]
+        }"#;
+        let folders = parse_import_file(postman.as_bytes(), "forms.json").unwrap();
+        let req = &folders[0].requests[0];
+        assert_eq!(req.body_type, "form");
+        let rows = form_body::parse(req.body.as_deref().unwrap()).unwrap();
+        assert_eq!(rows[0], FormRow::new("user", "ada"));
+        assert!(!rows[1].enabled);
+        assert_eq!(req.headers[0], HeaderRow::new("Accept", "application/json"));
+        assert!(!req.headers[1].enabled);
+
+        let insomnia = r#"{"resources": [{"_id": "fld_1", "_type": "request_group", "name": "Auth"}, {
+            "_id": "req_1",
+            "_type": "request",
+            "parentId": "fld_1",
+            "name": "Token",
+            "method": "POST",
+            "url": "https://api.test/token",
+            "body": {"mimeType": "application/x-www-form-urlencoded", "params": [
+                {"name": "grant_type", "value": "client_credentials"}
+            ]}
+        }]}"#;
+        let insomnia_folders = parse_import_file(insomnia.as_bytes(), "insomnia.json").unwrap();
+        let req = &insomnia_folders[0].requests[0];
+        assert_eq!(req.body_type, "form");
+        assert_eq!(
+            form_body::parse(req.body.as_deref().unwrap()).unwrap(),
+            vec![FormRow::new("grant_type", "client_credentials")]
+        );
+
+        let pool = create_test_pool().await;
+        save_import(&pool, folders, ConflictStrategy::Duplicate)
+            .await
+            .unwrap();
+        let row =
+            sqlx::query("SELECT body, body_content, headers FROM requests WHERE name = 'Login'")
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+        assert!(row.get::<Option<String>, _>(0).is_none());
+        let body_content: String = row.get(1);
+        assert_eq!(form_body::parse(&body_content).unwrap().len(), 2);
+        let headers: String = row.get(2);
+        assert_eq!(header_rows::parse(&headers).unwrap().len(), 2);
+    }
 }
@@ -0,0 +1,72 @@
+use reqwest::ClientBuilder;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+
+/// Which addresses an execution may connect to, from the request's
+/// `ip_family` column or the execution payload.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum IpFamily {
+    /// Whatever the resolver returns, IPv6 first with IPv4 fallback
+    Auto,
+    V4,
+    V6,
+    /// Skips DNS and connects to this address, keeping the URL's host name
+    /// for the Host header and TLS
+    Fixed(IpAddr),
+}
+
+impl IpFamily {
+    pub fn parse(value: &str) -> Result<Self, String> {
+        match value.trim().to_lowercase().as_str() {
+            "" | "auto" => Ok(IpFamily::Auto),
+            "ipv4" => Ok(IpFamily::V4),
+            "ipv6" => Ok(IpFamily::V6),
+            other => other
+                .trim_start_matches('[')
+                .trim_end_matches(']')
+                .parse()
+                .map(IpFamily::Fixed)
+                .map_err(|_| {
+                    format!(
+                        "Invalid IP family '{}', expected auto, ipv4, ipv6 or an IP address",
+                        value
+                    )
+                }),
+        }
+    }
+
+    /// Restricts the client's connections. Binding to the unspecified
+    /// address of a family makes the connector skip addresses of the other.
+    pub fn apply(self, builder: ClientBuilder, host: Option<&str>) -> ClientBuilder {
+        match self {
+            IpFamily::Auto => builder,
+            IpFamily::V4 => builder.local_address(IpAddr::V4(Ipv4Addr::UNSPECIFIED)),
+            IpFamily::V6 => builder.local_address(IpAddr::V6(Ipv6Addr::UNSPECIFIED)),
+            // The port is ignored, the URL's port is used
+            IpFamily::Fixed(ip) => match host {
+                Some(host) => builder.resolve(host, SocketAddr::new(ip, 0)),
+                None => builder,
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(IpFamily::parse("").unwrap(), IpFamily::Auto);
+        assert_eq!(IpFamily::parse("IPv4").unwrap(), IpFamily::V4);
+        assert_eq!(IpFamily::parse("ipv6").unwrap(), IpFamily::V6);
+        assert_eq!(
+            IpFamily::parse("10.0.0.7").unwrap(),
+            IpFamily::Fixed("10.0.0.7".parse().unwrap())
+        );
+        assert_eq!(
+            IpFamily::parse("[::1]").unwrap(),
+            IpFamily::Fixed(IpAddr::V6(Ipv6Addr::LOCALHOST))
+        );
+        assert!(IpFamily::parse("ipv5").is_err());
+    }
+}
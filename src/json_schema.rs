@@ -0,0 +1,268 @@
+use regex::Regex;
+use serde_json::Value;
+
+/// Keywords understood by [`validate`]; schemas using others are rejected
+/// rather than silently passing.
+const KEYWORDS: &[&str] = &[
+    "$schema",
+    "$id",
+    "title",
+    "description",
+    "type",
+    "enum",
+    "const",
+    "properties",
+    "required",
+    "additionalProperties",
+    "items",
+    "minItems",
+    "maxItems",
+    "minLength",
+    "maxLength",
+    "pattern",
+    "minimum",
+    "maximum",
+    "anyOf",
+];
+
+/// Checks that a schema only uses the supported subset of JSON Schema.
+pub fn check(schema: &Value) -> Result<(), String> {
+    match schema {
+        Value::Bool(_) => Ok(()),
+        Value::Object(map) => {
+            for (keyword, value) in map {
+                if !KEYWORDS.contains(&keyword.as_str()) {
+                    return Err(format!("Unsupported JSON Schema keyword '{}'", keyword));
+                }
+                match keyword.as_str() {
+                    "properties" => {
+                        let properties =
+                            value.as_object().ok_or("'properties' must be an object")?;
+                        properties.values().try_for_each(check)?;
+                    }
+                    "items" | "additionalProperties" => check(value)?,
+                    "anyOf" => {
+                        let schemas = value.as_array().ok_or("'anyOf' must be an array")?;
+                        schemas.iter().try_for_each(check)?;
+                    }
+                    "pattern" => {
+                        let pattern = value.as_str().ok_or("'pattern' must be a string")?;
+                        Regex::new(pattern).map_err(|e| format!("Invalid pattern: {}", e))?;
+                    }
+                    _ => {}
+                }
+            }
+            Ok(())
+        }
+        _ => Err("A JSON Schema must be an object or a boolean".to_string()),
+    }
+}
+
+/// Validates a value against a schema, returning the first violation with
+/// the path to the offending value.
+pub fn validate(schema: &Value, value: &Value) -> Result<(), String> {
+    validate_at(schema, value, "$")
+}
+
+fn validate_at(schema: &Value, value: &Value, path: &str) -> Result<(), String> {
+    let map = match schema {
+        Value::Bool(true) => return Ok(()),
+        Value::Bool(false) => return Err(format!("{}: no value is allowed", path)),
+        Value::Object(map) => map,
+        _ => return Ok(()),
+    };
+
+    if let Some(expected) = map.get("type") {
+        let types: Vec<&str> = match expected {
+            Value::String(t) => vec![t.as_str()],
+            Value::Array(ts) => ts.iter().filter_map(Value::as_str).collect(),
+            _ => Vec::new(),
+        };
+        if !types.iter().any(|t| has_type(value, t)) {
+            return Err(format!(
+                "{}: expected {}, got {}",
+                path,
+                types.join(" or "),
+                type_name(value)
+            ));
+        }
+    }
+    if let Some(allowed) = map.get("enum").and_then(Value::as_array) {
+        if !allowed.iter().any(|v| equal(v, value)) {
+            return Err(format!(
+                "{}: {} is not one of the allowed values",
+                path, value
+            ));
+        }
+    }
+    if let Some(expected) = map.get("const") {
+        if !equal(expected, value) {
+            return Err(format!("{}: expected {}, got {}", path, expected, value));
+        }
+    }
+    if let Some(schemas) = map.get("anyOf").and_then(Value::as_array) {
+        if !schemas.iter().any(|s| validate_at(s, value, path).is_ok()) {
+            return Err(format!("{}: matches none of the anyOf schemas", path));
+        }
+    }
+
+    match value {
+        Value::Object(object) => {
+            if let Some(required) = map.get("required").and_then(Value::as_array) {
+                for name in required.iter().filter_map(Value::as_str) {
+                    if !object.contains_key(name) {
+                        return Err(format!("{}: missing required property '{}'", path, name));
+                    }
+                }
+            }
+            let properties = map.get("properties").and_then(Value::as_object);
+            for (name, item) in object {
+                let item_path = format!("{}.{}", path, name);
+                match properties.and_then(|p| p.get(name)) {
+                    Some(property) => validate_at(property, item, &item_path)?,
+                    None => match map.get("additionalProperties") {
+                        Some(Value::Bool(false)) => {
+                            return Err(format!("{}: unexpected property '{}'", path, name))
+                        }
+                        Some(additional) => validate_at(additional, item, &item_path)?,
+                        None => {}
+                    },
+                }
+            }
+        }
+        Value::Array(items) => {
+            if let Some(min) = map.get("minItems").and_then(Value::as_u64) {
+                if (items.len() as u64) < min {
+                    return Err(format!("{}: expected at least {} items", path, min));
+                }
+            }
+            if let Some(max) = map.get("maxItems").and_then(Value::as_u64) {
+                if items.len() as u64 > max {
+                    return Err(format!("{}: expected at most {} items", path, max));
+                }
+            }
+            if let Some(item_schema) = map.get("items") {
+                for (index, item) in items.iter().enumerate() {
+                    validate_at(item_schema, item, &format!("{}[{}]", path, index))?;
+                }
+            }
+        }
+        Value::String(s) => {
+            let length = s.chars().count() as u64;
+            if let Some(min) = map.get("minLength").and_then(Value::as_u64) {
+                if length < min {
+                    return Err(format!("{}: shorter than {} characters", path, min));
+                }
+            }
+            if let Some(max) = map.get("maxLength").and_then(Value::as_u64) {
+                if length > max {
+                    return Err(format!("{}: longer than {} characters", path, max));
+                }
+            }
+            if let Some(pattern) = map.get("pattern").and_then(Value::as_str) {
+                let regex = Regex::new(pattern).map_err(|e| e.to_string())?;
+                if !regex.is_match(s) {
+                    return Err(format!("{}: does not match '{}'", path, pattern));
+                }
+            }
+        }
+        Value::Number(n) => {
+            let n = n.as_f64().unwrap_or_default();
+            if let Some(min) = map.get("minimum").and_then(Value::as_f64) {
+                if n < min {
+                    return Err(format!("{}: {} is less than {}", path, n, min));
+                }
+            }
+            if let Some(max) = map.get("maximum").and_then(Value::as_f64) {
+                if n > max {
+                    return Err(format!("{}: {} is greater than {}", path, n, max));
+                }
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+fn has_type(value: &Value, expected: &str) -> bool {
+    match expected {
+        "integer" => value.as_f64().is_some_and(|n| n.fract() == 0.0),
+        "number" => value.is_number(),
+        other => type_name(value) == other,
+    }
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+/// JSON equality where `1` and `1.0` are the same number.
+pub fn equal(a: &Value, b: &Value) -> bool {
+    match (a, b) {
+        (Value::Number(x), Value::Number(y)) => x.as_f64() == y.as_f64(),
+        (Value::Array(xs), Value::Array(ys)) => {
+            xs.len() == ys.len() && xs.iter().zip(ys).all(|(x, y)| equal(x, y))
+        }
+        (Value::Object(xs), Value::Object(ys)) => {
+            xs.len() == ys.len()
+                && xs
+                    .iter()
+                    .all(|(key, x)| ys.get(key).is_some_and(|y| equal(x, y)))
+        }
+        _ => a == b,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_validate() {
+        let schema = json!({
+            "type": "object",
+            "required": ["id", "tags"],
+            "properties": {
+                "id": { "type": "integer", "minimum": 1 },
+                "name": { "type": ["string", "null"], "maxLength": 5 },
+                "tags": { "type": "array", "items": { "enum": ["a", "b"] }, "minItems": 1 }
+            },
+            "additionalProperties": false
+        });
+        check(&schema).unwrap();
+        assert!(validate(&schema, &json!({ "id": 3, "tags": ["a"] })).is_ok());
+        assert!(validate(&schema, &json!({ "id": 3.0, "name": null, "tags": ["b"] })).is_ok());
+
+        let error = |value| validate(&schema, &value).unwrap_err();
+        assert_eq!(
+            error(json!({ "tags": ["a"] })),
+            "$: missing required property 'id'"
+        );
+        assert_eq!(
+            error(json!({ "id": "3", "tags": ["a"] })),
+            "$.id: expected integer, got string"
+        );
+        assert_eq!(
+            error(json!({ "id": 3, "tags": ["c"] })),
+            "$.tags[0]: \"c\" is not one of the allowed values"
+        );
+        assert_eq!(
+            error(json!({ "id": 3, "tags": ["a"], "extra": 1 })),
+            "$: unexpected property 'extra'"
+        );
+        assert!(validate(&schema, &json!({ "id": 0, "tags": ["a"] })).is_err());
+        assert!(validate(&schema, &json!({ "id": 1, "tags": [] })).is_err());
+
+        assert!(check(&json!({ "type": "string", "format": "email" })).is_err());
+        assert!(check(&json!({ "pattern": "(" })).is_err());
+        assert!(check(&json!("string")).is_err());
+    }
+}
@@ -0,0 +1,48 @@
+use std::env;
+
+// Request body limits that can be raised or lowered per deployment. Bodies
+// over the limit are refused with `413 Payload Too Large` before they are
+// read into memory.
+
+/// axum's own default, kept for routes without a setting of their own.
+pub const DEFAULT_BODY_LIMIT: usize = 2 * 1024 * 1024;
+
+/// The body limit in bytes set by `var`, or `default` when it is unset or
+/// not a positive number.
+pub fn body_limit(var: &str, default: usize) -> usize {
+    parse_limit(env::var(var).ok().as_deref()).unwrap_or(default)
+}
+
+/// Bytes, optionally with a `k`, `m` or `g` suffix (powers of 1024).
+fn parse_limit(value: Option<&str>) -> Option<usize> {
+    let value = value?.trim().to_lowercase();
+    let value = value.strip_suffix('b').unwrap_or(&value);
+    let (digits, unit) = match value.char_indices().last()? {
+        (i, 'k') => (&value[..i], 1024),
+        (i, 'm') => (&value[..i], 1024 * 1024),
+        (i, 'g') => (&value[..i], 1024 * 1024 * 1024),
+        _ => (value, 1),
+    };
+    digits
+        .trim()
+        .parse::<usize>()
+        .ok()
+        .and_then(|n| n.checked_mul(unit))
+        .filter(|n| *n > 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_limit() {
+        assert_eq!(parse_limit(Some("1024")), Some(1024));
+        assert_eq!(parse_limit(Some("10m")), Some(10 * 1024 * 1024));
+        assert_eq!(parse_limit(Some(" 512KB ")), Some(512 * 1024));
+        assert_eq!(parse_limit(Some("1g")), Some(1024 * 1024 * 1024));
+        assert_eq!(parse_limit(Some("0")), None);
+        assert_eq!(parse_limit(Some("lots")), None);
+        assert_eq!(parse_limit(None), None);
+    }
+}
@@ -0,0 +1,516 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    routing::post,
+    Json, Router,
+};
+use regex::Regex;
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::sync::OnceLock;
+
+use crate::{
+    capture,
+    db::DbPool,
+    environments,
+    executor::{self, ExecutorError},
+    folders::{self, FolderDb},
+    header_rows, i18n,
+    requests::{Request, RequestDb},
+};
+
+// Checks across a whole collection for things that work but should not be
+// shared that way: credentials in plain text, unencrypted URLs, variables
+// nothing defines, and gaps in the documentation.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LintRule {
+    PlainHttp,
+    HardcodedSecret,
+    UnresolvedVariable,
+    MissingDescription,
+    DuplicateName,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Error,
+    Warning,
+    Info,
+}
+
+impl LintRule {
+    fn severity(self) -> Severity {
+        match self {
+            LintRule::HardcodedSecret | LintRule::UnresolvedVariable => Severity::Error,
+            LintRule::PlainHttp | LintRule::DuplicateName => Severity::Warning,
+            LintRule::MissingDescription => Severity::Info,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LintIssue {
+    pub rule: LintRule,
+    pub severity: Severity,
+    /// `None` for issues of the folder itself
+    pub request_id: Option<i64>,
+    pub request_name: Option<String>,
+    /// Where in the request the problem is, e.g. `url` or `headers.X-Api-Key`
+    pub field: Option<String>,
+    pub message: String,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct LintSummary {
+    pub errors: usize,
+    pub warnings: usize,
+    pub info: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LintReport {
+    pub folder_id: i64,
+    pub environment_id: Option<i64>,
+    pub summary: LintSummary,
+    pub issues: Vec<LintIssue>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct LintOptions {
+    /// Placeholders are checked against this environment, its parents, the
+    /// folder's variables and the globals
+    #[serde(default)]
+    environment_id: Option<i64>,
+}
+
+pub enum LintError {
+    FolderNotFound,
+    EnvironmentNotFound,
+    Variables(ExecutorError),
+    DatabaseError(sqlx::Error),
+}
+
+impl From<sqlx::Error> for LintError {
+    fn from(e: sqlx::Error) -> Self {
+        LintError::DatabaseError(e)
+    }
+}
+
+impl IntoResponse for LintError {
+    fn into_response(self) -> Response {
+        match self {
+            LintError::FolderNotFound => {
+                (StatusCode::NOT_FOUND, i18n::t("error.folder_not_found")).into_response()
+            }
+            LintError::EnvironmentNotFound => (
+                StatusCode::NOT_FOUND,
+                i18n::t("error.environment_not_found"),
+            )
+                .into_response(),
+            LintError::Variables(e) => e.into_response(),
+            LintError::DatabaseError(e) => {
+                log::error!("Database error: {}", e);
+                (StatusCode::INTERNAL_SERVER_ERROR, i18n::t("error.database")).into_response()
+            }
+        }
+    }
+}
+
+/// Variables a script sets with `jsl.environment.set('name', ...)`.
+fn script_variables(script: &str) -> impl Iterator<Item = String> + '_ {
+    static SET: OnceLock<Regex> = OnceLock::new();
+    SET.get_or_init(|| Regex::new(r#"jsl\.environment\.set\(\s*['"`]([^'"`]+)['"`]"#).unwrap())
+        .captures_iter(script)
+        .map(|caps| caps[1].to_string())
+}
+
+/// Hosts where plain HTTP is expected.
+fn is_local(host: &str) -> bool {
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    host == "localhost"
+        || host.ends_with(".localhost")
+        || host.ends_with(".local")
+        || host
+            .parse::<std::net::IpAddr>()
+            .is_ok_and(|ip| ip.is_loopback() || ip.is_unspecified())
+}
+
+/// Whether a literal value is written where a variable belongs.
+fn is_literal(value: &str) -> bool {
+    !value.trim().is_empty() && executor::unresolved_names(value).is_empty()
+}
+
+struct Linter {
+    variables: HashMap<String, String>,
+    /// Set while the folder runs, by captures and scripts
+    runtime: HashSet<String>,
+    issues: Vec<LintIssue>,
+}
+
+impl Linter {
+    fn push(
+        &mut self,
+        rule: LintRule,
+        request: Option<&Request>,
+        field: Option<String>,
+        message: String,
+    ) {
+        self.issues.push(LintIssue {
+            rule,
+            severity: rule.severity(),
+            request_id: request.map(|r| r.id),
+            request_name: request.map(|r| r.name.clone()),
+            field,
+            message,
+        });
+    }
+
+    fn check_unresolved(&mut self, request: &Request, field: &str, text: &str) {
+        let resolved = executor::replace_placeholders(text, &self.variables);
+        for name in executor::unresolved_names(&resolved) {
+            if self.runtime.contains(&name) {
+                continue;
+            }
+            let message = i18n::tf(
+                "lint.unresolved_variable",
+                &[("field", &field), ("name", &name)],
+            );
+            self.push(
+                LintRule::UnresolvedVariable,
+                Some(request),
+                Some(field.to_string()),
+                message,
+            );
+        }
+    }
+
+    fn check_secret(&mut self, request: &Request, field: &str, value: &str) {
+        if is_literal(value) {
+            let message = i18n::tf("lint.hardcoded_secret", &[("field", &field)]);
+            self.push(
+                LintRule::HardcodedSecret,
+                Some(request),
+                Some(field.to_string()),
+                message,
+            );
+        }
+    }
+
+    fn check_url(&mut self, request: &Request) {
+        self.check_unresolved(request, "url", &request.url);
+        let resolved = executor::replace_placeholders(&request.url, &self.variables);
+        let Ok(url) = Url::parse(resolved.trim()) else {
+            return;
+        };
+        if matches!(url.scheme(), "http" | "ws") && !url.host_str().is_some_and(is_local) {
+            let message = i18n::tf("lint.plain_http", &[("url", &url.as_str())]);
+            self.push(
+                LintRule::PlainHttp,
+                Some(request),
+                Some("url".to_string()),
+                message,
+            );
+        }
+        for (name, value) in url.query_pairs() {
+            // Judged on the saved URL, a value from a variable is fine
+            if header_rows::is_secret(&name) && request.url.contains(&format!("{}={}", name, value))
+            {
+                self.check_secret(request, &format!("query.{}", name), &value);
+            }
+        }
+    }
+
+    fn check_request(&mut self, request: &Request) {
+        self.check_url(request);
+
+        let rows = request
+            .headers
+            .as_deref()
+            .and_then(|h| header_rows::parse(h).ok())
+            .unwrap_or_default();
+        for row in rows.iter().filter(|row| row.enabled && !row.key.is_empty()) {
+            let field = format!("headers.{}", row.key);
+            if header_rows::is_secret(&row.key) {
+                self.check_secret(request, &field, &row.value);
+            }
+            self.check_unresolved(request, &field, &format!("{}{}", row.key, row.value));
+        }
+
+        if request.auth_type != "none" {
+            if let Some(token) = request.auth_token.as_deref() {
+                self.check_secret(request, "auth_token", token);
+            }
+            if let Some(password) = request.auth_password.as_deref() {
+                self.check_secret(request, "auth_password", password);
+            }
+            for (field, value) in [
+                ("auth_token", &request.auth_token),
+                ("auth_username", &request.auth_username),
+                ("auth_password", &request.auth_password),
+                ("auth_config", &request.auth_config),
+            ] {
+                if let Some(value) = value {
+                    self.check_unresolved(request, field, value);
+                }
+            }
+        }
+
+        if let Some(body) = request.body.as_deref().or(request.body_content.as_deref()) {
+            self.check_unresolved(request, "body", body);
+        }
+
+        if request
+            .description
+            .as_deref()
+            .is_none_or(|d| d.trim().is_empty())
+        {
+            self.push(
+                LintRule::MissingDescription,
+                Some(request),
+                Some("description".to_string()),
+                i18n::t("lint.missing_description"),
+            );
+        }
+    }
+}
+
+async fn lint_folder(
+    State(pool): State<DbPool>,
+    Path(id): Path<i64>,
+    Json(options): Json<LintOptions>,
+) -> Result<Json<LintReport>, LintError> {
+    let folder = sqlx::query_as!(
+        FolderDb,
+        "SELECT id, name, variables, description, base_url, created_at, updated_at, archived_at FROM folders WHERE id = ?",
+        id
+    )
+    .fetch_optional(&pool)
+    .await?
+    .ok_or(LintError::FolderNotFound)?;
+    if let Some(environment_id) = options.environment_id {
+        match environments::load_environment(&pool, environment_id).await {
+            Ok(_) => {}
+            Err(sqlx::Error::RowNotFound) => return Err(LintError::EnvironmentNotFound),
+            Err(e) => return Err(e.into()),
+        }
+    }
+
+    let requests: Vec<Request> = sqlx::query_as!(
+        RequestDb,
+        "SELECT id, name, method, url, body, headers, folder_id, request_type, body_type, body_content, auth_type, auth_token, auth_username, auth_password, captures, auth_config, serialized, ip_family, pre_request_script, test_script, callback, unresolved_variables, retry, compression, assertions, description, tls_versions, ssh_tunnel_id, latency_budget_ms, created_at, updated_at, archived_at FROM requests WHERE folder_id = ? AND archived_at IS NULL ORDER BY id",
+        id
+    )
+    .fetch_all(&pool)
+    .await?
+    .into_iter()
+    .map(Request::from)
+    .map(|mut request| {
+        request.url = folders::join_base_url(folder.base_url.as_deref(), &request.url);
+        request
+    })
+    .collect();
+
+    let variables = executor::load_variables(&pool, Some(id), options.environment_id)
+        .await
+        .map_err(LintError::Variables)?;
+    let mut runtime = HashSet::new();
+    for request in &requests {
+        if let Some(rules) = request.captures.as_deref() {
+            let rules = capture::parse_rules(rules).unwrap_or_default();
+            runtime.extend(rules.into_iter().map(|rule| rule.variable));
+        }
+        for script in [&request.pre_request_script, &request.test_script]
+            .into_iter()
+            .flatten()
+        {
+            runtime.extend(script_variables(script));
+        }
+    }
+
+    let mut linter = Linter {
+        variables,
+        runtime,
+        issues: Vec::new(),
+    };
+    if folder
+        .description
+        .as_deref()
+        .is_none_or(|d| d.trim().is_empty())
+    {
+        linter.push(
+            LintRule::MissingDescription,
+            None,
+            Some("description".to_string()),
+            i18n::t("lint.missing_folder_description"),
+        );
+    }
+    let mut names = HashSet::new();
+    for request in &requests {
+        if !names.insert(request.name.trim().to_lowercase()) {
+            let message = i18n::tf("lint.duplicate_name", &[("name", &request.name)]);
+            linter.push(
+                LintRule::DuplicateName,
+                Some(request),
+                Some("name".to_string()),
+                message,
+            );
+        }
+        linter.check_request(request);
+    }
+
+    let mut summary = LintSummary::default();
+    for issue in &linter.issues {
+        match issue.severity {
+            Severity::Error => summary.errors += 1,
+            Severity::Warning => summary.warnings += 1,
+            Severity::Info => summary.info += 1,
+        }
+    }
+    log::info!(
+        "Linted folder {}: {} errors, {} warnings",
+        id,
+        summary.errors,
+        summary.warnings
+    );
+    Ok(Json(LintReport {
+        folder_id: id,
+        environment_id: options.environment_id,
+        summary,
+        issues: linter.issues,
+    }))
+}
+
+pub fn routes(pool: DbPool) -> Router {
+    Router::new()
+        .route("/folders/:id/lint", post(lint_folder))
+        .with_state(pool)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::create_test_pool;
+    use axum_test::TestServer;
+    use serde_json::json;
+
+    #[test]
+    fn test_is_local() {
+        assert!(is_local("localhost"));
+        assert!(is_local("api.localhost"));
+        assert!(is_local("127.0.0.1"));
+        assert!(is_local("[::1]"));
+        assert!(!is_local("api.example.com"));
+        assert!(!is_local("10.0.0.1"));
+    }
+
+    #[tokio::test]
+    async fn test_lint_folder() {
+        let pool = create_test_pool().await;
+        let (folder_id,): (i64,) = sqlx::query_as(
+            "INSERT INTO folders (name, variables) VALUES ('Shop', '{\"base\":\"http://shop.example.com\"}') RETURNING id",
+        )
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+        let (env_id,): (i64,) = sqlx::query_as(
+            "INSERT INTO environments (name, variables) VALUES ('Prod', '{\"tenant\":\"acme\"}') RETURNING id",
+        )
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+        sqlx::query(
+            "INSERT INTO requests (name, method, url, headers, folder_id, auth_type, auth_token, captures, description) VALUES
+             ('Login', 'POST', 'https://auth.example.com/login?api_key=abc123', '[{\"key\":\"X-Api-Key\",\"value\":\"sk_live\"},{\"key\":\"X-Tenant\",\"value\":\"{{tenant}}\"}]', ?, 'bearer', '{{token}}', '[{\"variable\":\"session\",\"source\":\"body\",\"expression\":\"$.id\"}]', 'Signs in.'),
+             ('Cart', 'GET', '{{base}}/cart/{{session}}?page={{page}}', NULL, ?, 'basic', NULL, NULL, NULL),
+             ('login', 'GET', 'http://localhost:8080/health', NULL, ?, 'none', NULL, NULL, 'Local check.')",
+        )
+        .bind(folder_id)
+        .bind(folder_id)
+        .bind(folder_id)
+        .execute(&pool)
+        .await
+        .unwrap();
+        sqlx::query("UPDATE requests SET auth_password = 'hunter2', auth_username = 'me' WHERE name = 'Cart'")
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let server = TestServer::new(routes(pool)).unwrap();
+        let report: LintReport = server
+            .post(&format!("/folders/{}/lint", folder_id))
+            .json(&json!({ "environment_id": env_id }))
+            .await
+            .json();
+        let found: Vec<(LintRule, Option<&str>, Option<&str>)> = report
+            .issues
+            .iter()
+            .map(|i| (i.rule, i.request_name.as_deref(), i.field.as_deref()))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                (LintRule::MissingDescription, None, Some("description")),
+                (
+                    LintRule::HardcodedSecret,
+                    Some("Login"),
+                    Some("query.api_key")
+                ),
+                (
+                    LintRule::HardcodedSecret,
+                    Some("Login"),
+                    Some("headers.X-Api-Key")
+                ),
+                // `token` is neither defined nor captured
+                (
+                    LintRule::UnresolvedVariable,
+                    Some("Login"),
+                    Some("auth_token")
+                ),
+                (LintRule::UnresolvedVariable, Some("Cart"), Some("url")),
+                (LintRule::PlainHttp, Some("Cart"), Some("url")),
+                (
+                    LintRule::HardcodedSecret,
+                    Some("Cart"),
+                    Some("auth_password")
+                ),
+                (
+                    LintRule::MissingDescription,
+                    Some("Cart"),
+                    Some("description")
+                ),
+                (LintRule::DuplicateName, Some("login"), Some("name")),
+            ]
+        );
+        assert!(report.issues[4].message.contains("page"));
+        assert_eq!(report.summary.errors, 5);
+        assert_eq!(report.summary.warnings, 2);
+        assert_eq!(report.summary.info, 2);
+
+        // Without the environment `tenant` is unknown as well
+        let report: LintReport = server
+            .post(&format!("/folders/{}/lint", folder_id))
+            .json(&json!({}))
+            .await
+            .json();
+        assert!(report
+            .issues
+            .iter()
+            .any(|i| i.field.as_deref() == Some("headers.X-Tenant")));
+
+        server
+            .post(&format!("/folders/{}/lint", folder_id))
+            .json(&json!({ "environment_id": 999 }))
+            .await
+            .assert_status(StatusCode::NOT_FOUND);
+        server
+            .post("/folders/999/lint")
+            .json(&json!({}))
+            .await
+            .assert_status(StatusCode::NOT_FOUND);
+    }
+}
@@ -0,0 +1,192 @@
+use std::env;
+use std::io::ErrorKind;
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+use tokio::net::TcpListener;
+
+/// Seconds without a connected UI before `--open` stops the server.
+const DEFAULT_IDLE_SHUTDOWN_SECS: u64 = 30;
+
+/// Where the server listens, read from the environment.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ListenConfig {
+    /// Only this machine can connect unless `HOST` says otherwise
+    pub host: String,
+    pub port: u16,
+    /// How many of the following ports to try when `port` is taken
+    /// (`JSLINK_PORT_FALLBACK`)
+    pub fallback_ports: u16,
+    /// Open the UI in the default browser once listening
+    /// (`JSLINK_OPEN_BROWSER`)
+    pub open_browser: bool,
+    /// Stop once no UI has been connected for this long, see
+    /// `ListenConfig::desktop`
+    pub idle_shutdown: Option<Duration>,
+}
+
+impl ListenConfig {
+    pub fn from_env() -> Result<Self, String> {
+        let port = match env::var("PORT") {
+            Ok(port) => port
+                .trim()
+                .parse::<u16>()
+                .map_err(|_| format!("PORT must be a port number, not '{}'", port))?,
+            Err(_) => 3000,
+        };
+        Ok(Self {
+            host: env::var("HOST").unwrap_or_else(|_| "127.0.0.1".to_string()),
+            port,
+            fallback_ports: env::var("JSLINK_PORT_FALLBACK")
+                .ok()
+                .and_then(|v| v.trim().parse().ok())
+                .unwrap_or(0),
+            open_browser: env::var("JSLINK_OPEN_BROWSER")
+                .map(|v| v == "true" || v == "1")
+                .unwrap_or(false),
+            idle_shutdown: None,
+        })
+    }
+
+    /// `--open`: a single user on this machine, on any free port, with the UI
+    /// opened in the browser. Closing the last tab stops the server after
+    /// `JSLINK_IDLE_SHUTDOWN_SECS`.
+    pub fn desktop(self) -> Self {
+        let grace = env::var("JSLINK_IDLE_SHUTDOWN_SECS")
+            .ok()
+            .and_then(|v| v.trim().parse().ok())
+            .unwrap_or(DEFAULT_IDLE_SHUTDOWN_SECS);
+        Self {
+            host: "127.0.0.1".to_string(),
+            port: 0,
+            fallback_ports: 0,
+            open_browser: true,
+            idle_shutdown: Some(Duration::from_secs(grace)),
+        }
+    }
+}
+
+/// Binds the configured port, or the first free one of the fallback ports
+/// after it.
+pub async fn bind(config: &ListenConfig) -> Result<TcpListener, String> {
+    let last = config.port.saturating_add(config.fallback_ports);
+    for port in config.port..=last {
+        let addr = format!("{}:{}", config.host, port);
+        match TcpListener::bind(&addr).await {
+            Ok(listener) => return Ok(listener),
+            Err(e) if e.kind() == ErrorKind::AddrInUse && port < last => {
+                log::warn!("{} is already in use, trying port {}", addr, port + 1);
+            }
+            Err(e) if e.kind() == ErrorKind::AddrInUse => {
+                let hint = if config.fallback_ports == 0 {
+                    "choose another port with PORT or set JSLINK_PORT_FALLBACK to try the next ones"
+                } else {
+                    "choose another port with PORT"
+                };
+                return Err(format!("{} is already in use; {}", addr, hint));
+            }
+            Err(e) => return Err(format!("Cannot listen on {}: {}", addr, e)),
+        }
+    }
+    unreachable!("the port range is never empty")
+}
+
+/// The address to open the UI at.
+pub fn ui_url(addr: SocketAddr) -> String {
+    let ip = addr.ip();
+    if ip.is_loopback() || ip.is_unspecified() {
+        format!("http://localhost:{}", addr.port())
+    } else {
+        format!("http://{}", addr)
+    }
+}
+
+/// Resolves once `clients` has been zero for `grace`, counting from the
+/// start so a browser that never connects also ends the server.
+pub async fn wait_until_idle(clients: impl Fn() -> usize, grace: Duration) {
+    let poll = (grace / 10).clamp(Duration::from_millis(10), Duration::from_secs(1));
+    let mut idle_since = Some(Instant::now());
+    loop {
+        tokio::time::sleep(poll).await;
+        match (clients(), idle_since) {
+            (0, Some(since)) if since.elapsed() >= grace => return,
+            (0, None) => idle_since = Some(Instant::now()),
+            (0, Some(_)) => {}
+            _ => idle_since = None,
+        }
+    }
+}
+
+/// Opens the UI at `addr`, passing `JSLINK_WS_TOKEN` along so its sockets
+/// are accepted.
+pub fn open_browser(addr: SocketAddr) {
+    let mut url = ui_url(addr);
+    if let Ok(token) = env::var("JSLINK_WS_TOKEN") {
+        url = format!("{}/?token={}", url, urlencoding::encode(&token));
+    }
+    match webbrowser::open(&url) {
+        Ok(()) => log::info!("Opened {} in the browser", url),
+        Err(e) => log::warn!("Could not open a browser: {}", e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_bind_falls_back_to_free_port() {
+        let taken = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let mut config = ListenConfig {
+            host: "127.0.0.1".to_string(),
+            port: taken.local_addr().unwrap().port(),
+            fallback_ports: 0,
+            open_browser: false,
+            idle_shutdown: None,
+        };
+
+        let error = bind(&config).await.unwrap_err();
+        assert!(error.contains("already in use"));
+
+        config.fallback_ports = 20;
+        let listener = bind(&config).await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        assert!(port > config.port && port <= config.port + 20);
+    }
+
+    #[tokio::test]
+    async fn test_wait_until_idle() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Arc;
+
+        let clients = Arc::new(AtomicUsize::new(1));
+        let counter = Arc::clone(&clients);
+        let grace = Duration::from_millis(200);
+        let idle = tokio::spawn(wait_until_idle(
+            move || counter.load(Ordering::SeqCst),
+            grace,
+        ));
+
+        tokio::time::sleep(Duration::from_millis(400)).await;
+        assert!(!idle.is_finished());
+        let closed = Instant::now();
+        clients.store(0, Ordering::SeqCst);
+        idle.await.unwrap();
+        assert!(closed.elapsed() >= grace);
+    }
+
+    #[test]
+    fn test_ui_url() {
+        assert_eq!(
+            ui_url("0.0.0.0:3001".parse().unwrap()),
+            "http://localhost:3001"
+        );
+        assert_eq!(
+            ui_url("192.168.1.5:3000".parse().unwrap()),
+            "http://192.168.1.5:3000"
+        );
+        assert_eq!(
+            ui_url("[::1]:3000".parse().unwrap()),
+            "http://localhost:3000"
+        );
+    }
+}
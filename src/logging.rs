@@ -0,0 +1,122 @@
+use axum::{body::Body, http::Request, Router};
+use std::env;
+use tower_http::{
+    request_id::{MakeRequestUuid, PropagateRequestIdLayer, SetRequestIdLayer},
+    trace::TraceLayer,
+};
+use tracing::Subscriber;
+use tracing_subscriber::{fmt, fmt::MakeWriter, registry::LookupSpan, Layer};
+
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum LogFormat {
+    #[default]
+    Text,
+    /// One JSON object per line, for Loki, ELK and the like
+    Json,
+}
+
+impl LogFormat {
+    fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "text" => Some(LogFormat::Text),
+            "json" => Some(LogFormat::Json),
+            _ => None,
+        }
+    }
+}
+
+/// How the console and the log files are written, read from the environment.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct LogConfig {
+    pub console: LogFormat,
+    pub file: LogFormat,
+}
+
+impl LogConfig {
+    /// `JSLINK_LOG_FORMAT` sets both outputs; `JSLINK_LOG_FORMAT_CONSOLE` and
+    /// `JSLINK_LOG_FORMAT_FILE` override one of them.
+    pub fn from_env() -> Self {
+        let read = |name: &str| env::var(name).ok().and_then(|v| LogFormat::parse(&v));
+        let format = read("JSLINK_LOG_FORMAT").unwrap_or_default();
+        Self {
+            console: read("JSLINK_LOG_FORMAT_CONSOLE").unwrap_or(format),
+            file: read("JSLINK_LOG_FORMAT_FILE").unwrap_or(format),
+        }
+    }
+}
+
+/// A log output writing `format` to `writer`. JSON lines carry the fields of
+/// the spans they were logged in, the request id among them.
+pub fn fmt_layer<S, W>(format: LogFormat, writer: W, ansi: bool) -> Box<dyn Layer<S> + Send + Sync>
+where
+    S: Subscriber + for<'span> LookupSpan<'span>,
+    W: for<'writer> MakeWriter<'writer> + Send + Sync + 'static,
+{
+    match format {
+        LogFormat::Text => fmt::layer().with_writer(writer).with_ansi(ansi).boxed(),
+        LogFormat::Json => fmt::layer()
+            .json()
+            .flatten_event(true)
+            .with_writer(writer)
+            .boxed(),
+    }
+}
+
+fn request_span(request: &Request<Body>) -> tracing::Span {
+    let request_id = request
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default();
+    tracing::info_span!(
+        "request",
+        otel.kind = "server",
+        request_id = %request_id,
+        method = %request.method(),
+        path = %request.uri().path(),
+    )
+}
+
+/// Gives every call an `x-request-id`, keeping one sent by the client, logs
+/// everything done for it inside a span carrying the id and returns the id
+/// with the response.
+pub fn apply(router: Router) -> Router {
+    router
+        .layer(PropagateRequestIdLayer::x_request_id())
+        .layer(TraceLayer::new_for_http().make_span_with(request_span))
+        .layer(SetRequestIdLayer::x_request_id(MakeRequestUuid))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::routing::get;
+    use axum_test::TestServer;
+
+    #[test]
+    fn test_parse_format() {
+        assert_eq!(LogFormat::parse(" JSON "), Some(LogFormat::Json));
+        assert_eq!(LogFormat::parse("text"), Some(LogFormat::Text));
+        assert_eq!(LogFormat::parse("xml"), None);
+    }
+
+    #[tokio::test]
+    async fn test_request_ids() {
+        let router = Router::new().route("/ping", get(|| async { "pong" }));
+        let server = TestServer::new(apply(router)).unwrap();
+
+        let response = server.get("/ping").await;
+        let generated = response.header(REQUEST_ID_HEADER);
+        assert_eq!(generated.to_str().unwrap().len(), 36);
+        let other = server.get("/ping").await.header(REQUEST_ID_HEADER);
+        assert_ne!(generated, other);
+
+        let response = server
+            .get("/ping")
+            .add_header(REQUEST_ID_HEADER, "deploy-check-1")
+            .await;
+        assert_eq!(response.header(REQUEST_ID_HEADER), "deploy-check-1");
+    }
+}
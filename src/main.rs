@@ -1,12 +1,82 @@
+mod access;
+mod admin;
+mod alerts;
+mod api_tokens;
+mod assertions;
+mod audit;
+mod aws_sigv4;
+mod basic;
+mod body_lint;
+mod capabilities;
+mod capture;
+mod chained_auth;
+mod cli;
+mod client_pool;
+mod compare;
+mod compression;
+mod crypto;
 mod db;
+mod dns;
+mod docs;
+mod doctor;
+mod drafts;
+mod dynamic_vars;
 mod environments;
+mod events;
 mod executor;
+mod export;
+mod file_sync;
 mod folders;
+mod form_body;
+mod generate;
+mod globals;
+mod header_rows;
+mod headless;
+mod health;
+mod history;
+mod host_queue;
+mod i18n;
 mod import_api;
 mod importers;
+mod ip_family;
+mod json_schema;
+mod jwt;
+mod limits;
+mod lint;
+mod listen;
+mod logging;
+mod net_tools;
 mod network;
+mod notes;
+mod oauth;
+mod openapi;
+mod pac;
+mod proxy;
+mod rate_limit;
+mod recorder;
+mod request_lock;
 mod requests;
+mod resolve;
+mod response_cache;
+mod restore;
+mod retry;
+mod runner;
+mod sanitize;
+mod schema_example;
+mod scripting;
+mod self_update;
+mod shares;
+mod ssh_tunnels;
+mod stats;
+mod telemetry;
+mod tls_version;
+mod tools;
+mod tree;
+mod unresolved;
+mod variable_types;
+mod webhooks;
 mod websocket;
+mod ws_history;
 
 use axum::{
     body::Body,
@@ -16,13 +86,13 @@ use axum::{
     routing::get,
     Router,
 };
+use clap::Parser;
 use dotenvy::dotenv;
 use rust_embed::RustEmbed;
 use sailfish::TemplateOnce;
-use std::env;
-use tokio::net::TcpListener;
+use std::net::SocketAddr;
 use tracing_appender::rolling;
-use tracing_subscriber::{fmt, layer::SubscriberExt, util::SubscriberInitExt, EnvFilter, Layer};
+use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, EnvFilter, Layer};
 
 #[derive(TemplateOnce)]
 #[template(path = "index.stpl")]
@@ -56,8 +126,52 @@ async fn static_handler(Path(path): Path<String>) -> impl IntoResponse {
     }
 }
 
+/// Rate limiting, token authentication, the Host and Origin checks and
+/// localization, for every route that reads or changes the workspace.
+/// `bind_host` is the host the server listens on, which decides the names
+/// it may be reached by.
+fn guard(mut router: Router, pool: &db::DbPool, bind_host: &str) -> Router {
+    // Inside authentication, so clients are counted by their verified token
+    if let Some(rate_limit) = rate_limit::RateLimitConfig::from_env() {
+        router = rate_limit::apply(router, rate_limit);
+    }
+    router = router.layer(axum::middleware::from_fn_with_state(
+        pool.clone(),
+        api_tokens::authenticate,
+    ));
+    router = access::apply(router, access::AccessConfig::from_env(bind_host));
+    router.layer(axum::middleware::from_fn(i18n::localize))
+}
+
 #[tokio::main]
 async fn main() {
+    let cli = cli::Cli::parse();
+
+    if let Some(cli::Command::Doctor) = cli.command {
+        dotenv().ok();
+        let (pool, connect_error) = match db::create_pool(db::MigrationMode::Skip).await {
+            Ok(pool) => (Some(pool), None),
+            Err(e) => (None, Some(e.to_string())),
+        };
+        let options = doctor::DoctorOptions::from_env(true);
+        let report = doctor::run(pool.as_ref(), connect_error, &options).await;
+        print!("{}", report.render());
+        std::process::exit(if report.healthy { 0 } else { 1 });
+    }
+
+    if let Some(cli::Command::SelfUpdate { check }) = cli.command {
+        dotenv().ok();
+        std::process::exit(self_update::run(check).await);
+    }
+
+    if let Some(cli::Command::Run(args)) = &cli.command {
+        dotenv().ok();
+        std::process::exit(headless::run(args, cli.migration_mode()).await);
+    }
+
+    // Read .env before anything is configured from the environment
+    let env_loaded = dotenv().ok().is_some();
+
     // File appender: rotate daily, store in ./logs, prefix with js-link, suffix with log
     let file_appender = rolling::Builder::new()
         .rotation(rolling::Rotation::DAILY)
@@ -67,66 +181,143 @@ async fn main() {
         .expect("failed to initialize rolling file appender");
     let (non_blocking, _guard) = tracing_appender::non_blocking(file_appender);
 
+    let log_config = logging::LogConfig::from_env();
+
     // Console layer
-    let console_layer = fmt::layer()
-        .with_writer(std::io::stdout)
+    let console_layer = logging::fmt_layer(log_config.console, std::io::stdout, true)
         .with_filter(EnvFilter::from_default_env().add_directive(tracing::Level::INFO.into()));
 
     // File layer
-    let file_layer = fmt::layer()
-        .with_writer(non_blocking)
-        .with_ansi(false)
+    let file_layer = logging::fmt_layer(log_config.file, non_blocking, false)
         .with_filter(EnvFilter::from_default_env().add_directive(tracing::Level::INFO.into()));
 
+    // Span export, when an OTLP endpoint is configured
+    let telemetry_layer = telemetry::layer(telemetry::TelemetryConfig::from_env()).map(|layer| {
+        layer.with_filter(EnvFilter::from_default_env().add_directive(tracing::Level::INFO.into()))
+    });
+
     // Register subscriber
     tracing_subscriber::registry()
         .with(console_layer)
         .with(file_layer)
+        .with(telemetry_layer)
         .init();
 
-    println!(
-        r#"
+    // The banner would break up JSON lines
+    if log_config.console == logging::LogFormat::Text {
+        println!(
+            r#"
      ██╗███████╗      ██╗      ██╗███╗   ██╗██╗  ██╗
      ██║██╔════╝      ██║      ██║████╗  ██║██║ ██╔╝
      ██║███████╗█████╗██║      ██║██╔██╗ ██║█████╔╝ 
 ██   ██║╚════██║╚════╝██║      ██║██║╚██╗██║██╔═██╗ 
 ╚█████╔╝███████║      ███████╗ ██║██║ ╚████║██║  ██╗
  ╚════╝ ╚══════╝      ╚══════╝ ╚═╝╚═╝  ╚═══╝╚═╝  ╚═╝
-    "#
-    );
+        "#
+        );
+    }
 
     let version = env!("CARGO_PKG_VERSION");
     log::info!("JS-Link v{} starting up...", version);
 
-    // Load .env if it exists
-    if dotenv().ok().is_some() {
+    if env_loaded {
         log::info!("Loaded environment variables from .env");
     }
 
     log::info!("Initializing database connection...");
-    let pool = db::create_pool()
-        .await
-        .expect("Failed to create database pool");
+    let pool = match db::create_pool(cli.migration_mode()).await {
+        Ok(pool) => pool,
+        Err(e) => {
+            log::error!("{}", e);
+            std::process::exit(1);
+        }
+    };
+
+    match tokio::task::spawn_blocking(crypto::init).await {
+        Ok(Ok(())) => {}
+        Ok(Err(e)) => log::error!("{}", e),
+        Err(e) => log::error!("Failed to load the secret key: {}", e),
+    }
+    if let Err(e) = requests::encrypt_stored_credentials(&pool).await {
+        log::error!("Failed to encrypt stored request credentials: {}", e);
+    }
+
+    stats::spawn_aggregator(pool.clone());
+    self_update::spawn_checker();
+    file_sync::spawn(pool.clone());
+    i18n::load(&pool).await;
+
+    let listen_config = match listen::ListenConfig::from_env() {
+        Ok(config) if cli.open => config.desktop(),
+        Ok(config) => config,
+        Err(e) => {
+            log::error!("{}", e);
+            std::process::exit(1);
+        }
+    };
+
+    // The pages send saved requests, so they are guarded like the API
+    let basic = guard(basic::routes(pool.clone()), &pool, &listen_config.host);
+    let api = admin::routes(pool.clone())
+        .merge(folders::routes(pool.clone()))
+        .merge(requests::routes(pool.clone()))
+        .merge(tree::routes(pool.clone()))
+        .merge(resolve::routes(pool.clone()))
+        .merge(unresolved::routes(pool.clone()))
+        .merge(response_cache::routes(pool.clone()))
+        .merge(tls_version::routes(pool.clone()))
+        .merge(ssh_tunnels::routes(pool.clone()))
+        .merge(host_queue::routes(pool.clone()))
+        .merge(recorder::routes(pool.clone()))
+        .merge(environments::routes(pool.clone()))
+        .merge(audit::routes(pool.clone()))
+        .merge(api_tokens::routes(pool.clone()))
+        .merge(globals::routes(pool.clone()))
+        .merge(network::routes(pool.clone()))
+        .merge(oauth::routes(pool.clone()))
+        .merge(executor::routes(pool.clone()))
+        .merge(runner::routes(pool.clone()))
+        .merge(alerts::routes(pool.clone()))
+        .merge(compare::routes(pool.clone()))
+        .merge(websocket::routes(pool.clone()))
+        .merge(events::routes())
+        .merge(ws_history::routes(pool.clone()))
+        .merge(export::routes(pool.clone()))
+        .merge(openapi::routes(pool.clone()))
+        .merge(docs::routes(pool.clone()))
+        .merge(lint::routes(pool.clone()))
+        .merge(shares::routes(pool.clone()))
+        .merge(drafts::routes(pool.clone()))
+        .merge(notes::routes(pool.clone()))
+        .merge(history::routes(pool.clone()))
+        .merge(generate::routes(pool.clone()))
+        .merge(i18n::routes(pool.clone()))
+        .merge(sanitize::routes())
+        .merge(tools::routes())
+        .merge(net_tools::routes())
+        .merge(capabilities::routes())
+        .merge(health::api_routes())
+        .merge(stats::routes(pool.clone()))
+        .merge(webhooks::routes(pool.clone()))
+        .merge(import_api::routes(pool.clone()));
+    let api = guard(api, &pool, &listen_config.host);
 
     let app = Router::new()
         .route("/", get(index))
-        .nest(
-            "/api",
-            folders::routes(pool.clone())
-                .merge(requests::routes(pool.clone()))
-                .merge(environments::routes(pool.clone()))
-                .merge(network::routes(pool.clone()))
-                .merge(executor::routes(pool.clone()))
-                .merge(websocket::routes(pool.clone()))
-                .merge(import_api::routes(pool)),
-        )
+        .nest("/api", api)
+        .merge(shares::public_routes(pool.clone()))
+        .merge(health::routes(pool))
+        .merge(basic)
         .route("/static/*path", get(static_handler));
+    let app = logging::apply(app);
 
-    let host = env::var("HOST").unwrap_or_else(|_| "0.0.0.0".to_string());
-    let port = env::var("PORT").unwrap_or_else(|_| "3000".to_string());
-    let addr = format!("{}:{}", host, port);
-
-    let listener = TcpListener::bind(&addr).await.unwrap();
+    let listener = match listen::bind(&listen_config).await {
+        Ok(listener) => listener,
+        Err(e) => {
+            log::error!("{}", e);
+            std::process::exit(1);
+        }
+    };
     let local_addr = listener.local_addr().unwrap();
 
     log::info!("──────────────────────────────────────────────────");
@@ -143,5 +334,32 @@ async fn main() {
     }
     log::info!("──────────────────────────────────────────────────");
 
-    axum::serve(listener, app).await.unwrap();
+    if listen_config.port != 0 && local_addr.port() != listen_config.port {
+        log::warn!(
+            "Port {} was in use, js-link is at {} instead",
+            listen_config.port,
+            listen::ui_url(local_addr)
+        );
+    }
+    if listen_config.open_browser {
+        listen::open_browser(local_addr);
+    }
+
+    let idle_shutdown = listen_config.idle_shutdown;
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .with_graceful_shutdown(async move {
+        match idle_shutdown {
+            Some(grace) => {
+                listen::wait_until_idle(events::connected_clients, grace).await;
+                log::info!("No UI connected for {} s, shutting down", grace.as_secs());
+            }
+            None => std::future::pending().await,
+        }
+    })
+    .await
+    .unwrap();
+    ssh_tunnels::stop_all().await;
 }
@@ -7,7 +7,17 @@ use axum::{
 };
 use serde::{Deserialize, Serialize};
 
-use crate::db::DbPool;
+use std::fs;
+use std::net::IpAddr;
+use std::path::Path;
+
+use crate::{
+    client_pool, crypto,
+    db::DbPool,
+    i18n,
+    pac::{self, PacDecision},
+    proxy,
+};
 
 #[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
 pub struct NetworkSettings {
@@ -16,15 +26,186 @@ pub struct NetworkSettings {
     pub http_proxy: Option<String>,
     pub https_proxy: Option<String>,
     pub no_proxy: Option<String>,
+    pub proxy_username: Option<String>,
+    pub proxy_password_set: bool, // The password itself is never returned
+    pub localhost_rewrite: String, // 'never', 'auto', 'always'
+    pub localhost_alias: String,
+    pub pac_url: Option<String>,
+    pub in_container: bool, // Detected at runtime, read-only
 }
 
 #[derive(sqlx::FromRow, Clone)]
-struct NetworkSettingsDb {
-    id: i64,
-    auto_proxy: bool,
-    http_proxy: Option<String>,
-    https_proxy: Option<String>,
-    no_proxy: Option<String>,
+pub struct NetworkSettingsDb {
+    pub id: i64,
+    pub auto_proxy: bool,
+    pub http_proxy: Option<String>,
+    pub https_proxy: Option<String>,
+    pub no_proxy: Option<String>,
+    pub proxy_username: Option<String>,
+    pub proxy_password: Option<String>, // Encrypted
+    pub localhost_rewrite: String,
+    pub localhost_alias: String,
+    pub pac_url: Option<String>,
+}
+
+impl NetworkSettingsDb {
+    /// Settings used when the row is missing: system proxy detection, no credentials.
+    pub fn defaults() -> Self {
+        Self {
+            id: 1,
+            auto_proxy: true,
+            http_proxy: None,
+            https_proxy: None,
+            no_proxy: None,
+            proxy_username: None,
+            proxy_password: None,
+            localhost_rewrite: "never".to_string(),
+            localhost_alias: "host.docker.internal".to_string(),
+            pac_url: None,
+        }
+    }
+
+    /// Settings sending everything through a SOCKS forward on this machine,
+    /// with host names resolved on the far side.
+    pub fn through_socks(local_port: u16) -> Self {
+        let proxy = format!("socks5h://127.0.0.1:{}", local_port);
+        Self {
+            auto_proxy: false,
+            http_proxy: Some(proxy.clone()),
+            https_proxy: Some(proxy),
+            ..Self::defaults()
+        }
+    }
+
+    /// Returns the decrypted proxy password, if one is stored.
+    pub fn decrypted_proxy_password(&self) -> Result<Option<String>, crypto::CryptoError> {
+        self.proxy_password
+            .as_deref()
+            .map(crypto::decrypt)
+            .transpose()
+    }
+
+    /// Proxy to use for a connection to `host`. Manual settings apply the stored
+    /// credentials; auto mode falls back to the `*_PROXY` environment variables.
+    pub fn proxy_for(&self, secure: bool, host: &str) -> Result<Option<proxy::ProxyUrl>, String> {
+        let env_var = |names: &[&str]| {
+            names
+                .iter()
+                .find_map(|name| std::env::var(name).ok().filter(|v| !v.trim().is_empty()))
+        };
+
+        let (configured, no_proxy) = if self.auto_proxy {
+            let configured = if secure {
+                env_var(&["HTTPS_PROXY", "https_proxy", "ALL_PROXY", "all_proxy"])
+            } else {
+                env_var(&["HTTP_PROXY", "http_proxy", "ALL_PROXY", "all_proxy"])
+            };
+            (configured, env_var(&["NO_PROXY", "no_proxy"]))
+        } else {
+            let configured = if secure {
+                self.https_proxy.clone()
+            } else {
+                self.http_proxy.clone()
+            };
+            (configured, self.no_proxy.clone())
+        };
+
+        let Some(configured) = configured.filter(|p| !p.trim().is_empty()) else {
+            return Ok(None);
+        };
+        if no_proxy.is_some_and(|no_proxy| proxy::bypasses_proxy(host, &no_proxy)) {
+            log::debug!("Bypassing proxy for {}", host);
+            return Ok(None);
+        }
+
+        let mut proxy_url = proxy::parse_proxy_url(&configured)?;
+        if !self.auto_proxy {
+            let password = self
+                .decrypted_proxy_password()
+                .map_err(|e| format!("Failed to decrypt proxy password: {}", e))?
+                .unwrap_or_default();
+            proxy_url = proxy_url.with_credentials(self.proxy_username.as_deref(), &password);
+        }
+        Ok(Some(proxy_url))
+    }
+
+    /// What the PAC script says about `url`, when auto proxy is on and a PAC URL
+    /// is configured. Failures are logged and yield `None`, as browsers fall
+    /// back to the regular proxy settings.
+    pub async fn pac_decision(&self, url: &str) -> Option<PacDecision> {
+        let pac_url = self.pac_url.as_deref().filter(|u| !u.trim().is_empty())?;
+        if !self.auto_proxy {
+            return None;
+        }
+        match pac::find_proxy(pac_url, url).await {
+            Ok(decision) => Some(decision),
+            Err(e) => {
+                log::warn!("PAC evaluation failed for {}: {}", url, e);
+                None
+            }
+        }
+    }
+
+    /// Proxy for a connection to `url`, consulting the PAC script first.
+    pub async fn select_proxy(&self, url: &str) -> Result<Option<proxy::ProxyUrl>, String> {
+        match self.pac_decision(url).await {
+            Some(PacDecision::Direct) => Ok(None),
+            Some(PacDecision::Proxy(proxy_url)) => Ok(Some(proxy_url)),
+            None => {
+                let parsed = reqwest::Url::parse(url).map_err(|e| e.to_string())?;
+                let secure = matches!(parsed.scheme(), "https" | "wss");
+                self.proxy_for(secure, parsed.host_str().unwrap_or_default())
+            }
+        }
+    }
+
+    /// Host that loopback targets should be redirected to, if rewriting applies.
+    pub fn localhost_target(&self) -> Option<&str> {
+        let enabled = match self.localhost_rewrite.as_str() {
+            "always" => true,
+            "auto" => running_in_container(),
+            _ => false,
+        };
+        (enabled && !self.localhost_alias.is_empty()).then_some(self.localhost_alias.as_str())
+    }
+}
+
+/// Detects Docker, Podman and Kubernetes containers.
+pub fn running_in_container() -> bool {
+    if Path::new("/.dockerenv").exists() || Path::new("/run/.containerenv").exists() {
+        return true;
+    }
+    if std::env::var_os("container").is_some()
+        || std::env::var_os("KUBERNETES_SERVICE_HOST").is_some()
+    {
+        return true;
+    }
+    fs::read_to_string("/proc/1/cgroup")
+        .map(|cgroup| {
+            ["docker", "kubepods", "containerd", "libpod"]
+                .iter()
+                .any(|marker| cgroup.contains(marker))
+        })
+        .unwrap_or(false)
+}
+
+/// Replaces a loopback host (`localhost`, `127.0.0.1`, `::1`) in `url` with `alias`.
+/// Returns `None` when the URL does not target the loopback interface.
+pub fn rewrite_localhost(url: &str, alias: &str) -> Option<String> {
+    let mut parsed = reqwest::Url::parse(url).ok()?;
+    let host = parsed.host_str()?;
+    let is_loopback = host.eq_ignore_ascii_case("localhost")
+        || host
+            .trim_start_matches('[')
+            .trim_end_matches(']')
+            .parse::<IpAddr>()
+            .map(|ip| ip.is_loopback())
+            .unwrap_or(false);
+    if !is_loopback {
+        return None;
+    }
+    parsed.set_host(Some(alias)).ok()?;
+    Some(parsed.to_string())
 }
 
 impl From<NetworkSettingsDb> for NetworkSettings {
@@ -35,6 +216,12 @@ impl From<NetworkSettingsDb> for NetworkSettings {
             http_proxy: s.http_proxy,
             https_proxy: s.https_proxy,
             no_proxy: s.no_proxy,
+            proxy_username: s.proxy_username,
+            proxy_password_set: s.proxy_password.is_some(),
+            localhost_rewrite: s.localhost_rewrite,
+            localhost_alias: s.localhost_alias,
+            pac_url: s.pac_url,
+            in_container: running_in_container(),
         }
     }
 }
@@ -45,10 +232,25 @@ pub struct UpdateNetworkSettings {
     http_proxy: Option<String>,
     https_proxy: Option<String>,
     no_proxy: Option<String>,
+    #[serde(default)]
+    proxy_username: Option<String>,
+    /// `null` or absent keeps the stored password, an empty string clears it.
+    #[serde(default)]
+    proxy_password: Option<String>,
+    /// `null` or absent keeps the stored values.
+    #[serde(default)]
+    localhost_rewrite: Option<String>,
+    #[serde(default)]
+    localhost_alias: Option<String>,
+    /// `null` or absent keeps the stored URL, an empty string clears it.
+    #[serde(default)]
+    pac_url: Option<String>,
 }
 
 pub enum NetworkSettingsError {
     SettingsNotFound,
+    InvalidSetting(String),
+    EncryptionError(String),
     DatabaseError(#[allow(dead_code)] sqlx::Error),
 }
 
@@ -64,27 +266,42 @@ impl From<sqlx::Error> for NetworkSettingsError {
 impl IntoResponse for NetworkSettingsError {
     fn into_response(self) -> Response {
         match self {
-            NetworkSettingsError::SettingsNotFound => {
-                (StatusCode::NOT_FOUND, "Network settings not found").into_response()
+            NetworkSettingsError::SettingsNotFound => (
+                StatusCode::NOT_FOUND,
+                i18n::t("error.network_settings_not_found"),
+            )
+                .into_response(),
+            NetworkSettingsError::InvalidSetting(msg) => {
+                (StatusCode::BAD_REQUEST, msg).into_response()
             }
+            NetworkSettingsError::EncryptionError(msg) => (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                i18n::tf("error.encryption", &[("message", &msg)]),
+            )
+                .into_response(),
             NetworkSettingsError::DatabaseError(_) => {
-                (StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response()
+                (StatusCode::INTERNAL_SERVER_ERROR, i18n::t("error.database")).into_response()
             }
         }
     }
 }
 
+/// Loads the stored network settings row.
+pub async fn load_settings(pool: &DbPool) -> Result<NetworkSettingsDb, sqlx::Error> {
+    sqlx::query_as!(
+        NetworkSettingsDb,
+        "SELECT id, auto_proxy, http_proxy, https_proxy, no_proxy, proxy_username, proxy_password, localhost_rewrite, localhost_alias, pac_url FROM network_settings WHERE id = 1"
+    )
+    .fetch_one(pool)
+    .await
+}
+
 async fn get_network_settings(
     State(pool): State<DbPool>,
 ) -> Result<impl IntoResponse, NetworkSettingsError> {
     log::debug!("Getting network settings");
 
-    let settings_db = sqlx::query_as!(
-        NetworkSettingsDb,
-        "SELECT id, auto_proxy, http_proxy, https_proxy, no_proxy FROM network_settings WHERE id = 1"
-    )
-    .fetch_one(&pool)
-    .await?;
+    let settings_db = load_settings(&pool).await?;
 
     let settings = NetworkSettings::from(settings_db);
     log::debug!(
@@ -101,20 +318,73 @@ async fn update_network_settings(
     State(pool): State<DbPool>,
     Json(payload): Json<UpdateNetworkSettings>,
 ) -> Result<impl IntoResponse, NetworkSettingsError> {
-    log::info!("Updating network settings: auto_proxy={}, http_proxy={:?}, https_proxy={:?}, no_proxy={:?}", 
-        payload.auto_proxy, payload.http_proxy, payload.https_proxy, payload.no_proxy);
+    log::info!(
+        "Updating network settings: auto_proxy={}, http_proxy={:?}, https_proxy={:?}, no_proxy={:?}, proxy_username={:?}",
+        payload.auto_proxy,
+        payload.http_proxy,
+        payload.https_proxy,
+        payload.no_proxy,
+        payload.proxy_username
+    );
+
+    if let Some(mode) = payload.localhost_rewrite.as_deref() {
+        if !matches!(mode, "never" | "auto" | "always") {
+            log::warn!("Invalid localhost_rewrite mode: {}", mode);
+            return Err(NetworkSettingsError::InvalidSetting(format!(
+                "Invalid localhost_rewrite '{}', expected never, auto or always",
+                mode
+            )));
+        }
+    }
+
+    for proxy_str in [&payload.http_proxy, &payload.https_proxy]
+        .into_iter()
+        .flatten()
+        .filter(|p| !p.trim().is_empty())
+    {
+        if let Err(e) = proxy::parse_proxy_url(proxy_str) {
+            log::warn!("Rejected proxy setting: {}", e);
+            return Err(NetworkSettingsError::InvalidSetting(e));
+        }
+    }
+
+    if let Some(pac_url) = payload.pac_url.as_deref().filter(|u| !u.trim().is_empty()) {
+        if let Err(e) = pac::validate_pac_url(pac_url) {
+            log::warn!("Rejected PAC URL: {}", e);
+            return Err(NetworkSettingsError::InvalidSetting(e));
+        }
+    }
+
+    let keep_password = payload.proxy_password.is_none();
+    let proxy_password = match payload.proxy_password.as_deref() {
+        None | Some("") => None,
+        Some(password) => Some(crypto::encrypt(password).map_err(|e| {
+            log::error!("Failed to encrypt proxy password: {}", e);
+            NetworkSettingsError::EncryptionError(e.to_string())
+        })?),
+    };
 
     let settings_db = sqlx::query_as!(
         NetworkSettingsDb,
-        "UPDATE network_settings SET auto_proxy = ?, http_proxy = ?, https_proxy = ?, no_proxy = ? WHERE id = 1 RETURNING id, auto_proxy, http_proxy, https_proxy, no_proxy",
+        "UPDATE network_settings SET auto_proxy = ?, http_proxy = ?, https_proxy = ?, no_proxy = ?, proxy_username = ?, proxy_password = CASE WHEN ? THEN proxy_password ELSE ? END, localhost_rewrite = COALESCE(?, localhost_rewrite), localhost_alias = COALESCE(?, localhost_alias), pac_url = CASE WHEN ? IS NULL THEN pac_url ELSE NULLIF(?, '') END WHERE id = 1 RETURNING id, auto_proxy, http_proxy, https_proxy, no_proxy, proxy_username, proxy_password, localhost_rewrite, localhost_alias, pac_url",
         payload.auto_proxy,
         payload.http_proxy,
         payload.https_proxy,
         payload.no_proxy,
+        payload.proxy_username,
+        keep_password,
+        proxy_password,
+        payload.localhost_rewrite,
+        payload.localhost_alias,
+        payload.pac_url,
+        payload.pac_url,
     )
     .fetch_one(&pool)
     .await?;
 
+    // Clients built from the old settings would keep using them
+    client_pool::shared().clear();
+
     let settings = NetworkSettings::from(settings_db);
     log::info!("Network settings updated successfully");
 
@@ -186,4 +456,240 @@ mod tests {
         assert_eq!(settings.https_proxy, None);
         assert_eq!(settings.no_proxy, Some("localhost".to_string()));
     }
+
+    #[tokio::test]
+    async fn test_update_network_settings_proxy_credentials() {
+        let pool = db::create_test_pool().await;
+        ensure_default_network_settings(&pool).await;
+        let server = TestServer::new(routes(pool.clone())).unwrap();
+
+        let response = server
+            .put("/settings/network")
+            .json(&json!({
+                "auto_proxy": false,
+                "http_proxy": "http://proxy.corp:3128",
+                "https_proxy": "http://proxy.corp:3128",
+                "no_proxy": null,
+                "proxy_username": "alice",
+                "proxy_password": "hunter2"
+            }))
+            .await;
+
+        response.assert_status(StatusCode::OK);
+        assert!(!response.text().contains("hunter2"));
+        let settings: NetworkSettings = response.json();
+        assert_eq!(settings.proxy_username, Some("alice".to_string()));
+        assert!(settings.proxy_password_set);
+
+        let stored = load_settings(&pool).await.unwrap();
+        assert!(crypto::is_encrypted(
+            stored.proxy_password.as_deref().unwrap()
+        ));
+        assert_eq!(
+            stored.decrypted_proxy_password().unwrap(),
+            Some("hunter2".to_string())
+        );
+
+        // Omitting the password keeps the stored one
+        let response = server
+            .put("/settings/network")
+            .json(&json!({
+                "auto_proxy": false,
+                "http_proxy": "http://proxy.corp:3128",
+                "https_proxy": null,
+                "no_proxy": null,
+                "proxy_username": "alice"
+            }))
+            .await;
+        let settings: NetworkSettings = response.json();
+        assert!(settings.proxy_password_set);
+
+        // An empty password clears it
+        let response = server
+            .put("/settings/network")
+            .json(&json!({
+                "auto_proxy": true,
+                "http_proxy": null,
+                "https_proxy": null,
+                "no_proxy": null,
+                "proxy_username": null,
+                "proxy_password": ""
+            }))
+            .await;
+        let settings: NetworkSettings = response.json();
+        assert!(!settings.proxy_password_set);
+    }
+
+    #[tokio::test]
+    async fn test_update_network_settings_localhost_rewrite() {
+        let pool = db::create_test_pool().await;
+        ensure_default_network_settings(&pool).await;
+        let server = TestServer::new(routes(pool)).unwrap();
+
+        let settings: NetworkSettings = server.get("/settings/network").await.json();
+        assert_eq!(settings.localhost_rewrite, "never");
+        assert_eq!(settings.localhost_alias, "host.docker.internal");
+
+        let response = server
+            .put("/settings/network")
+            .json(&json!({
+                "auto_proxy": true,
+                "http_proxy": null,
+                "https_proxy": null,
+                "no_proxy": null,
+                "localhost_rewrite": "always",
+                "localhost_alias": "gateway.local"
+            }))
+            .await;
+        response.assert_status(StatusCode::OK);
+        let settings: NetworkSettings = response.json();
+        assert_eq!(settings.localhost_rewrite, "always");
+        assert_eq!(settings.localhost_alias, "gateway.local");
+
+        let response = server
+            .put("/settings/network")
+            .json(&json!({
+                "auto_proxy": true,
+                "http_proxy": null,
+                "https_proxy": null,
+                "no_proxy": null,
+                "localhost_rewrite": "sometimes"
+            }))
+            .await;
+        response.assert_status(StatusCode::BAD_REQUEST);
+    }
+
+    #[test]
+    fn test_rewrite_localhost() {
+        assert_eq!(
+            rewrite_localhost("http://localhost:8080/api?x=1", "host.docker.internal"),
+            Some("http://host.docker.internal:8080/api?x=1".to_string())
+        );
+        assert_eq!(
+            rewrite_localhost("https://127.0.0.1/", "host.docker.internal"),
+            Some("https://host.docker.internal/".to_string())
+        );
+        assert_eq!(
+            rewrite_localhost("http://[::1]:3000", "host.docker.internal"),
+            Some("http://host.docker.internal:3000/".to_string())
+        );
+        assert_eq!(
+            rewrite_localhost("http://example.com", "host.docker.internal"),
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn test_update_network_settings_socks_proxy() {
+        let pool = db::create_test_pool().await;
+        ensure_default_network_settings(&pool).await;
+        let server = TestServer::new(routes(pool)).unwrap();
+
+        let response = server
+            .put("/settings/network")
+            .json(&json!({
+                "auto_proxy": false,
+                "http_proxy": "socks5://127.0.0.1:1080",
+                "https_proxy": "socks5h://127.0.0.1:1080",
+                "no_proxy": null
+            }))
+            .await;
+        response.assert_status(StatusCode::OK);
+
+        let response = server
+            .put("/settings/network")
+            .json(&json!({
+                "auto_proxy": false,
+                "http_proxy": "gopher://127.0.0.1:70",
+                "https_proxy": null,
+                "no_proxy": null
+            }))
+            .await;
+        response.assert_status(StatusCode::BAD_REQUEST);
+    }
+
+    #[test]
+    fn test_proxy_for_manual_settings() {
+        let settings = NetworkSettingsDb {
+            auto_proxy: false,
+            http_proxy: Some("http://proxy.corp:3128".to_string()),
+            https_proxy: Some("socks5h://proxy.corp:1080".to_string()),
+            no_proxy: Some(".internal".to_string()),
+            proxy_username: Some("alice".to_string()),
+            proxy_password: Some(crypto::encrypt("s3cret").unwrap()),
+            ..NetworkSettingsDb::defaults()
+        };
+
+        let proxy = settings.proxy_for(false, "example.com").unwrap().unwrap();
+        assert_eq!(proxy.scheme, proxy::ProxyScheme::Http);
+        assert_eq!(
+            proxy.credentials(),
+            Some(("alice".to_string(), "s3cret".to_string()))
+        );
+
+        let proxy = settings.proxy_for(true, "example.com").unwrap().unwrap();
+        assert_eq!(proxy.scheme, proxy::ProxyScheme::Socks5h);
+
+        assert!(settings.proxy_for(true, "ws.internal").unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_update_network_settings_pac_url() {
+        let pool = db::create_test_pool().await;
+        ensure_default_network_settings(&pool).await;
+        let server = TestServer::new(routes(pool)).unwrap();
+
+        let response = server
+            .put("/settings/network")
+            .json(&json!({
+                "auto_proxy": true,
+                "http_proxy": null,
+                "https_proxy": null,
+                "no_proxy": null,
+                "pac_url": "http://wpad.corp/proxy.pac"
+            }))
+            .await;
+        response.assert_status(StatusCode::OK);
+
+        // Omitting the URL keeps it, an empty string clears it
+        let settings: NetworkSettings = server
+            .put("/settings/network")
+            .json(&json!({
+                "auto_proxy": true,
+                "http_proxy": null,
+                "https_proxy": null,
+                "no_proxy": null
+            }))
+            .await
+            .json();
+        assert_eq!(
+            settings.pac_url.as_deref(),
+            Some("http://wpad.corp/proxy.pac")
+        );
+
+        let settings: NetworkSettings = server
+            .put("/settings/network")
+            .json(&json!({
+                "auto_proxy": true,
+                "http_proxy": null,
+                "https_proxy": null,
+                "no_proxy": null,
+                "pac_url": ""
+            }))
+            .await
+            .json();
+        assert_eq!(settings.pac_url, None);
+
+        let response = server
+            .put("/settings/network")
+            .json(&json!({
+                "auto_proxy": true,
+                "http_proxy": null,
+                "https_proxy": null,
+                "no_proxy": null,
+                "pac_url": "ftp://wpad.corp/proxy.pac"
+            }))
+            .await;
+        response.assert_status(StatusCode::BAD_REQUEST);
+    }
 }
@@ -0,0 +1,297 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    routing::{get, put},
+    Json, Router,
+};
+use chrono::{DateTime, NaiveDateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{audit::Actor, db::DbPool, i18n};
+
+/// Longest note accepted, in characters.
+const MAX_NOTE_LEN: usize = 10_000;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Note {
+    pub id: i64,
+    pub request_id: i64,
+    /// Who wrote the note, as recorded in the audit log
+    pub author: String,
+    pub body: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+struct NoteDb {
+    id: i64,
+    request_id: i64,
+    author: String,
+    body: String,
+    created_at: NaiveDateTime,
+    updated_at: NaiveDateTime,
+}
+
+impl From<NoteDb> for Note {
+    fn from(n: NoteDb) -> Self {
+        Self {
+            id: n.id,
+            request_id: n.request_id,
+            author: n.author,
+            body: n.body,
+            created_at: n.created_at.and_utc(),
+            updated_at: n.updated_at.and_utc(),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct WriteNote {
+    body: String,
+}
+
+pub enum NoteError {
+    RequestNotFound,
+    NoteNotFound,
+    EmptyNote,
+    TooLong,
+    DatabaseError(sqlx::Error),
+}
+
+impl From<sqlx::Error> for NoteError {
+    fn from(e: sqlx::Error) -> Self {
+        NoteError::DatabaseError(e)
+    }
+}
+
+impl IntoResponse for NoteError {
+    fn into_response(self) -> Response {
+        match self {
+            NoteError::RequestNotFound => {
+                (StatusCode::NOT_FOUND, i18n::t("error.request_not_found")).into_response()
+            }
+            NoteError::NoteNotFound => {
+                (StatusCode::NOT_FOUND, i18n::t("error.note_not_found")).into_response()
+            }
+            NoteError::EmptyNote => {
+                (StatusCode::BAD_REQUEST, i18n::t("error.note_empty")).into_response()
+            }
+            NoteError::TooLong => (
+                StatusCode::BAD_REQUEST,
+                i18n::tf("error.note_too_long", &[("max", &MAX_NOTE_LEN)]),
+            )
+                .into_response(),
+            NoteError::DatabaseError(e) => {
+                log::error!("Database error: {}", e);
+                (StatusCode::INTERNAL_SERVER_ERROR, i18n::t("error.database")).into_response()
+            }
+        }
+    }
+}
+
+fn validate(body: &str) -> Result<&str, NoteError> {
+    let body = body.trim();
+    if body.is_empty() {
+        Err(NoteError::EmptyNote)
+    } else if body.chars().count() > MAX_NOTE_LEN {
+        Err(NoteError::TooLong)
+    } else {
+        Ok(body)
+    }
+}
+
+async fn ensure_request(pool: &DbPool, request_id: i64) -> Result<(), NoteError> {
+    sqlx::query_scalar!(
+        r#"SELECT id AS "id!" FROM requests WHERE id = ?"#,
+        request_id
+    )
+    .fetch_optional(pool)
+    .await?
+    .map(|_| ())
+    .ok_or(NoteError::RequestNotFound)
+}
+
+/// Oldest first, like a conversation.
+async fn list_notes(
+    State(pool): State<DbPool>,
+    Path(request_id): Path<i64>,
+) -> Result<impl IntoResponse, NoteError> {
+    ensure_request(&pool, request_id).await?;
+    let notes = sqlx::query_as!(
+        NoteDb,
+        r#"SELECT id AS "id!", request_id, author, body, created_at AS "created_at: NaiveDateTime", updated_at AS "updated_at: NaiveDateTime"
+        FROM request_notes WHERE request_id = ? ORDER BY created_at, id"#,
+        request_id
+    )
+    .fetch_all(&pool)
+    .await?;
+    Ok(Json(notes.into_iter().map(Note::from).collect::<Vec<_>>()))
+}
+
+async fn create_note(
+    State(pool): State<DbPool>,
+    Path(request_id): Path<i64>,
+    actor: Actor,
+    Json(payload): Json<WriteNote>,
+) -> Result<impl IntoResponse, NoteError> {
+    let body = validate(&payload.body)?;
+    ensure_request(&pool, request_id).await?;
+    let note = sqlx::query_as!(
+        NoteDb,
+        r#"INSERT INTO request_notes (request_id, author, body) VALUES (?, ?, ?)
+        RETURNING id AS "id!", request_id, author, body, created_at AS "created_at: NaiveDateTime", updated_at AS "updated_at: NaiveDateTime""#,
+        request_id,
+        actor.0,
+        body
+    )
+    .fetch_one(&pool)
+    .await?;
+    log::info!(
+        "Added note {} to request {} by {}",
+        note.id,
+        request_id,
+        note.author
+    );
+    Ok((StatusCode::CREATED, Json(Note::from(note))))
+}
+
+/// Edits keep the original author.
+async fn update_note(
+    State(pool): State<DbPool>,
+    Path((request_id, id)): Path<(i64, i64)>,
+    Json(payload): Json<WriteNote>,
+) -> Result<impl IntoResponse, NoteError> {
+    let body = validate(&payload.body)?;
+    let note = sqlx::query_as!(
+        NoteDb,
+        r#"UPDATE request_notes SET body = ?, updated_at = CURRENT_TIMESTAMP WHERE id = ? AND request_id = ?
+        RETURNING id AS "id!", request_id, author, body, created_at AS "created_at: NaiveDateTime", updated_at AS "updated_at: NaiveDateTime""#,
+        body,
+        id,
+        request_id
+    )
+    .fetch_optional(&pool)
+    .await?
+    .ok_or(NoteError::NoteNotFound)?;
+    log::info!("Updated note {} of request {}", id, request_id);
+    Ok(Json(Note::from(note)))
+}
+
+async fn delete_note(
+    State(pool): State<DbPool>,
+    Path((request_id, id)): Path<(i64, i64)>,
+) -> Result<impl IntoResponse, NoteError> {
+    let result = sqlx::query!(
+        "DELETE FROM request_notes WHERE id = ? AND request_id = ?",
+        id,
+        request_id
+    )
+    .execute(&pool)
+    .await?;
+    if result.rows_affected() == 0 {
+        return Err(NoteError::NoteNotFound);
+    }
+    log::info!("Deleted note {} of request {}", id, request_id);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub fn routes(pool: DbPool) -> Router {
+    Router::new()
+        .route("/requests/:id/notes", get(list_notes).post(create_note))
+        .route(
+            "/requests/:id/notes/:note_id",
+            put(update_note).delete(delete_note),
+        )
+        .with_state(pool)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{audit::ACTOR_HEADER, db::create_test_pool};
+    use axum_test::TestServer;
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn test_request_notes() {
+        let pool = create_test_pool().await;
+        let (request_id,): (i64,) = sqlx::query_as(
+            "INSERT INTO requests (name, method, url) VALUES ('Legacy orders', 'GET', 'https://api.test/orders') RETURNING id",
+        )
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+        let server = TestServer::new(routes(pool.clone())).unwrap();
+        let notes_url = format!("/requests/{}/notes", request_id);
+
+        server
+            .post(&notes_url)
+            .json(&json!({ "body": "  " }))
+            .await
+            .assert_status(StatusCode::BAD_REQUEST);
+        server
+            .post(&notes_url)
+            .json(&json!({ "body": "x".repeat(MAX_NOTE_LEN + 1) }))
+            .await
+            .assert_status(StatusCode::BAD_REQUEST);
+        server
+            .post("/requests/999/notes")
+            .json(&json!({ "body": "Hello" }))
+            .await
+            .assert_status(StatusCode::NOT_FOUND);
+
+        let response = server
+            .post(&notes_url)
+            .add_header(ACTOR_HEADER, "ana")
+            .json(&json!({ "body": "Needs the legacy header until Q3. " }))
+            .await;
+        response.assert_status(StatusCode::CREATED);
+        let first: Note = response.json();
+        assert_eq!(first.author, "header:ana");
+        assert_eq!(first.body, "Needs the legacy header until Q3.");
+        let second: Note = server
+            .post(&notes_url)
+            .json(&json!({ "body": "Moved to v2 in staging." }))
+            .await
+            .json();
+
+        let updated: Note = server
+            .put(&format!("{}/{}", notes_url, first.id))
+            .add_header(ACTOR_HEADER, "ben")
+            .json(&json!({ "body": "Needs the legacy header until Q4." }))
+            .await
+            .json();
+        assert_eq!(updated.author, "header:ana");
+        let notes: Vec<Note> = server.get(&notes_url).await.json();
+        assert_eq!(
+            notes.iter().map(|n| n.id).collect::<Vec<_>>(),
+            vec![first.id, second.id]
+        );
+        assert_eq!(notes[0].body, "Needs the legacy header until Q4.");
+
+        // A note is only reachable through its own request
+        server
+            .delete(&format!("/requests/999/notes/{}", second.id))
+            .await
+            .assert_status(StatusCode::NOT_FOUND);
+        server
+            .delete(&format!("{}/{}", notes_url, second.id))
+            .await
+            .assert_status(StatusCode::NO_CONTENT);
+        let notes: Vec<Note> = server.get(&notes_url).await.json();
+        assert_eq!(notes.len(), 1);
+
+        // Notes go with their request
+        sqlx::query("DELETE FROM requests WHERE id = ?")
+            .bind(request_id)
+            .execute(&pool)
+            .await
+            .unwrap();
+        let (left,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM request_notes")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(left, 0);
+    }
+}
@@ -0,0 +1,1141 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{Html, IntoResponse, Redirect, Response},
+    routing::{get, post},
+    Json, Router,
+};
+use base64::Engine;
+use chrono::{DateTime, Duration, NaiveDateTime, Utc};
+use rand::RngCore;
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+
+use crate::{
+    crypto,
+    db::DbPool,
+    executor::{self, ExecutorError},
+    i18n,
+    network::{self, NetworkSettingsDb},
+};
+
+/// Path of the redirect endpoint, relative to the server root.
+pub const CALLBACK_PATH: &str = "/api/oauth/callback";
+
+/// Tokens this close to expiry are refreshed rather than used.
+const EXPIRY_MARGIN_SECS: i64 = 30;
+
+const GRANT_CLIENT_CREDENTIALS: &str = "client_credentials";
+const GRANT_AUTHORIZATION_CODE: &str = "authorization_code";
+
+/// Whose settings a config is: a single request, or every request in a folder.
+#[derive(Debug, Clone, Copy)]
+enum Owner {
+    Request(i64),
+    Folder(i64),
+}
+
+impl Owner {
+    fn ids(self) -> (Option<i64>, Option<i64>) {
+        match self {
+            Owner::Request(id) => (Some(id), None),
+            Owner::Folder(id) => (None, Some(id)),
+        }
+    }
+}
+
+#[derive(sqlx::FromRow, Clone)]
+pub struct OAuthConfigDb {
+    pub id: i64,
+    pub request_id: Option<i64>,
+    pub folder_id: Option<i64>,
+    pub grant_type: String,
+    pub client_id: String,
+    pub client_secret: Option<String>, // Encrypted
+    pub token_url: String,
+    pub authorization_url: Option<String>,
+    pub redirect_uri: Option<String>,
+    pub scope: Option<String>,
+    pub use_pkce: bool,
+    pub access_token: Option<String>,  // Encrypted
+    pub refresh_token: Option<String>, // Encrypted
+    pub token_expires_at: Option<NaiveDateTime>,
+    pub token_key: Option<String>,
+    pub pending_verifier: Option<String>,
+    pub pending_environment_id: Option<i64>,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct OAuthConfig {
+    pub id: i64,
+    pub request_id: Option<i64>,
+    pub folder_id: Option<i64>,
+    pub grant_type: String, // 'client_credentials' or 'authorization_code'
+    pub client_id: String,
+    pub client_secret_set: bool, // Secrets and tokens are never returned
+    pub token_url: String,
+    pub authorization_url: Option<String>,
+    pub redirect_uri: Option<String>,
+    pub scope: Option<String>,
+    pub use_pkce: bool,
+    pub token: Option<TokenStatus>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct TokenStatus {
+    pub expires_at: Option<DateTime<Utc>>,
+    pub expired: bool,
+    pub refresh_token_set: bool,
+}
+
+impl From<OAuthConfigDb> for OAuthConfig {
+    fn from(c: OAuthConfigDb) -> Self {
+        let token = c.access_token.as_ref().map(|_| {
+            let expires_at = c
+                .token_expires_at
+                .map(|d| DateTime::from_naive_utc_and_offset(d, Utc));
+            TokenStatus {
+                expires_at,
+                expired: expires_at.is_some_and(|e| e <= Utc::now()),
+                refresh_token_set: c.refresh_token.is_some(),
+            }
+        });
+        Self {
+            id: c.id,
+            request_id: c.request_id,
+            folder_id: c.folder_id,
+            grant_type: c.grant_type,
+            client_id: c.client_id,
+            client_secret_set: c.client_secret.is_some(),
+            token_url: c.token_url,
+            authorization_url: c.authorization_url,
+            redirect_uri: c.redirect_uri,
+            scope: c.scope,
+            use_pkce: c.use_pkce,
+            token,
+            created_at: DateTime::from_naive_utc_and_offset(c.created_at, Utc),
+            updated_at: DateTime::from_naive_utc_and_offset(c.updated_at, Utc),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct UpdateOAuthConfig {
+    grant_type: String,
+    client_id: String,
+    /// `null` or absent keeps the stored secret, an empty string clears it.
+    #[serde(default)]
+    client_secret: Option<String>,
+    token_url: String,
+    #[serde(default)]
+    authorization_url: Option<String>,
+    /// Defaults to this server's callback route.
+    #[serde(default)]
+    redirect_uri: Option<String>,
+    #[serde(default)]
+    scope: Option<String>,
+    #[serde(default = "default_use_pkce")]
+    use_pkce: bool,
+}
+
+fn default_use_pkce() -> bool {
+    true
+}
+
+#[derive(Deserialize)]
+pub struct EnvironmentQuery {
+    #[serde(default)]
+    environment_id: Option<i64>,
+}
+
+#[derive(Deserialize)]
+pub struct CallbackQuery {
+    #[serde(default)]
+    code: Option<String>,
+    #[serde(default)]
+    state: Option<String>,
+    #[serde(default)]
+    error: Option<String>,
+    #[serde(default)]
+    error_description: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    #[serde(default)]
+    expires_in: Option<i64>,
+    #[serde(default)]
+    refresh_token: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct TokenErrorResponse {
+    error: String,
+    #[serde(default)]
+    error_description: Option<String>,
+}
+
+#[derive(Debug)]
+pub enum OAuthError {
+    NotFound,
+    NotConfigured,
+    InvalidConfig(String),
+    /// The authorization code flow has to be completed in a browser first.
+    AuthorizationRequired(i64),
+    TokenRequest(String),
+    Substitution(String),
+    EncryptionError(String),
+    DatabaseError(sqlx::Error),
+}
+
+impl std::fmt::Display for OAuthError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            OAuthError::NotFound => write!(f, "OAuth settings not found"),
+            OAuthError::NotConfigured => {
+                write!(f, "No OAuth 2.0 settings for this request or its folder")
+            }
+            OAuthError::InvalidConfig(msg) => write!(f, "Invalid OAuth 2.0 settings: {}", msg),
+            OAuthError::AuthorizationRequired(id) => write!(
+                f,
+                "Authorization required, open /api/oauth/{}/authorize in a browser",
+                id
+            ),
+            OAuthError::TokenRequest(msg) => write!(f, "Token request failed: {}", msg),
+            OAuthError::Substitution(msg) => write!(f, "Variable substitution error: {}", msg),
+            OAuthError::EncryptionError(msg) => write!(f, "Encryption error: {}", msg),
+            OAuthError::DatabaseError(e) => write!(f, "Database error: {}", e),
+        }
+    }
+}
+
+impl From<sqlx::Error> for OAuthError {
+    fn from(e: sqlx::Error) -> Self {
+        match e {
+            sqlx::Error::RowNotFound => OAuthError::NotFound,
+            _ => OAuthError::DatabaseError(e),
+        }
+    }
+}
+
+impl From<crypto::CryptoError> for OAuthError {
+    fn from(e: crypto::CryptoError) -> Self {
+        OAuthError::EncryptionError(e.to_string())
+    }
+}
+
+impl From<ExecutorError> for OAuthError {
+    fn from(e: ExecutorError) -> Self {
+        match e {
+            ExecutorError::SubstitutionError(msg) => OAuthError::Substitution(msg),
+            ExecutorError::RequestNotFound => OAuthError::NotFound,
+            other => OAuthError::TokenRequest(other.to_string()),
+        }
+    }
+}
+
+impl IntoResponse for OAuthError {
+    fn into_response(self) -> Response {
+        let status = match self {
+            OAuthError::NotFound => StatusCode::NOT_FOUND,
+            OAuthError::NotConfigured
+            | OAuthError::InvalidConfig(_)
+            | OAuthError::Substitution(_) => StatusCode::BAD_REQUEST,
+            OAuthError::AuthorizationRequired(_) => StatusCode::UNAUTHORIZED,
+            OAuthError::TokenRequest(_) => StatusCode::BAD_GATEWAY,
+            OAuthError::EncryptionError(_) | OAuthError::DatabaseError(_) => {
+                StatusCode::INTERNAL_SERVER_ERROR
+            }
+        };
+        match self {
+            OAuthError::DatabaseError(_) => (status, i18n::t("error.database")).into_response(),
+            other => (status, other.to_string()).into_response(),
+        }
+    }
+}
+
+/// Config fields with environment variables substituted in.
+struct ResolvedSettings {
+    grant_type: String,
+    client_id: String,
+    client_secret: Option<String>,
+    token_url: String,
+    authorization_url: Option<String>,
+    redirect_uri: Option<String>,
+    scope: Option<String>,
+}
+
+impl ResolvedSettings {
+    fn resolve(
+        config: &OAuthConfigDb,
+        variables: &HashMap<String, String>,
+    ) -> Result<Self, OAuthError> {
+        let sub = |s: &str| executor::substitute_variables(s, variables);
+        let opt = |s: &Option<String>| s.as_deref().map(sub).transpose();
+        let client_secret = config
+            .client_secret
+            .as_deref()
+            .map(crypto::decrypt)
+            .transpose()?;
+
+        Ok(Self {
+            grant_type: config.grant_type.clone(),
+            client_id: sub(&config.client_id)?,
+            client_secret: opt(&client_secret)?,
+            token_url: sub(&config.token_url)?,
+            authorization_url: opt(&config.authorization_url)?,
+            redirect_uri: opt(&config.redirect_uri)?,
+            scope: opt(&config.scope)?.filter(|s| !s.is_empty()),
+        })
+    }
+
+    /// Identifies what a cached token was issued for, so a token obtained with
+    /// one environment's client is never sent with another's.
+    fn token_key(&self) -> String {
+        let mut hasher = Sha256::new();
+        for part in [
+            self.grant_type.as_str(),
+            &self.token_url,
+            &self.client_id,
+            self.scope.as_deref().unwrap_or(""),
+        ] {
+            hasher.update(part.as_bytes());
+            hasher.update([0]);
+        }
+        format!("{:x}", hasher.finalize())
+    }
+}
+
+fn random_token(len: usize) -> String {
+    let mut bytes = vec![0u8; len];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(bytes)
+}
+
+/// S256 code challenge for a PKCE verifier (RFC 7636).
+fn pkce_challenge(verifier: &str) -> String {
+    base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(Sha256::digest(verifier.as_bytes()))
+}
+
+fn validate_url(field: &str, value: &str) -> Result<(), OAuthError> {
+    // Placeholders are only resolved at execution time
+    if value.contains("{{") {
+        return Ok(());
+    }
+    match Url::parse(value) {
+        Ok(url) if matches!(url.scheme(), "http" | "https") => Ok(()),
+        _ => Err(OAuthError::InvalidConfig(format!(
+            "{} must be an http(s) URL",
+            field
+        ))),
+    }
+}
+
+fn callback_uri(settings: &ResolvedSettings, headers: &HeaderMap) -> String {
+    if let Some(uri) = &settings.redirect_uri {
+        return uri.clone();
+    }
+    let host = headers
+        .get(header::HOST)
+        .and_then(|h| h.to_str().ok())
+        .unwrap_or("localhost:3000");
+    format!("http://{}{}", host, CALLBACK_PATH)
+}
+
+async fn load_config(pool: &DbPool, id: i64) -> Result<OAuthConfigDb, sqlx::Error> {
+    sqlx::query_as!(
+        OAuthConfigDb,
+        "SELECT id, request_id, folder_id, grant_type, client_id, client_secret, token_url, authorization_url, redirect_uri, scope, use_pkce, access_token, refresh_token, token_expires_at, token_key, pending_verifier, pending_environment_id, created_at, updated_at FROM oauth_configs WHERE id = ?",
+        id
+    )
+    .fetch_one(pool)
+    .await
+}
+
+async fn find_config(pool: &DbPool, owner: Owner) -> Result<Option<OAuthConfigDb>, sqlx::Error> {
+    let (request_id, folder_id) = owner.ids();
+    sqlx::query_as!(
+        OAuthConfigDb,
+        r#"SELECT id as "id!", request_id, folder_id, grant_type, client_id, client_secret, token_url, authorization_url, redirect_uri, scope, use_pkce, access_token, refresh_token, token_expires_at, token_key, pending_verifier, pending_environment_id, created_at, updated_at FROM oauth_configs WHERE request_id IS ? AND folder_id IS ?"#,
+        request_id,
+        folder_id
+    )
+    .fetch_optional(pool)
+    .await
+}
+
+/// Posts a grant to the token endpoint, through the configured proxy.
+async fn request_token(
+    pool: &DbPool,
+    settings: &ResolvedSettings,
+    mut params: Vec<(&str, String)>,
+) -> Result<TokenResponse, OAuthError> {
+    let network_settings = network::load_settings(pool)
+        .await
+        .unwrap_or_else(|_| NetworkSettingsDb::defaults());
+    let pac_decision = network_settings.pac_decision(&settings.token_url).await;
+    let client = executor::build_reqwest_client(network_settings, pac_decision)?;
+
+    let mut builder = client
+        .post(&settings.token_url)
+        .header(header::ACCEPT, "application/json");
+    match &settings.client_secret {
+        Some(secret) => builder = builder.basic_auth(&settings.client_id, Some(secret)),
+        None => params.push(("client_id", settings.client_id.clone())),
+    }
+    if let Some(scope) = &settings.scope {
+        params.push(("scope", scope.clone()));
+    }
+    let form = params
+        .iter()
+        .map(|(k, v)| format!("{}={}", urlencoding::encode(k), urlencoding::encode(v)))
+        .collect::<Vec<_>>()
+        .join("&");
+
+    log::debug!("Requesting OAuth token from {}", settings.token_url);
+    let response = builder
+        .header(header::CONTENT_TYPE, "application/x-www-form-urlencoded")
+        .body(form)
+        .send()
+        .await
+        .map_err(|e| OAuthError::TokenRequest(e.to_string()))?;
+    let status = response.status();
+    let body = response
+        .text()
+        .await
+        .map_err(|e| OAuthError::TokenRequest(e.to_string()))?;
+
+    if !status.is_success() {
+        let message = match serde_json::from_str::<TokenErrorResponse>(&body) {
+            Ok(e) => match e.error_description {
+                Some(description) => format!("{}: {}", e.error, description),
+                None => e.error,
+            },
+            Err(_) => body,
+        };
+        log::warn!("Token endpoint returned {}: {}", status, message);
+        return Err(OAuthError::TokenRequest(format!(
+            "{} ({})",
+            message, status
+        )));
+    }
+
+    serde_json::from_str(&body)
+        .map_err(|e| OAuthError::TokenRequest(format!("Unexpected token endpoint response: {}", e)))
+}
+
+/// Caches a freshly issued token, keeping the previous refresh token when the
+/// server did not rotate it.
+async fn store_token(
+    pool: &DbPool,
+    config_id: i64,
+    token_key: &str,
+    token: &TokenResponse,
+) -> Result<(), OAuthError> {
+    let access_token = crypto::encrypt(&token.access_token)?;
+    let refresh_token = token
+        .refresh_token
+        .as_deref()
+        .map(crypto::encrypt)
+        .transpose()?;
+    let expires_at = token
+        .expires_in
+        .map(|secs| (Utc::now() + Duration::seconds(secs)).naive_utc());
+
+    sqlx::query!(
+        "UPDATE oauth_configs SET access_token = ?, refresh_token = COALESCE(?, CASE WHEN token_key = ? THEN refresh_token END), token_expires_at = ?, token_key = ? WHERE id = ?",
+        access_token,
+        refresh_token,
+        token_key,
+        expires_at,
+        token_key,
+        config_id
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// The cached access token of `config`, if it was issued for `key` and is
+/// still valid.
+fn cached_token(config: &OAuthConfigDb, key: &str) -> Result<Option<String>, OAuthError> {
+    if config.token_key.as_deref() != Some(key) {
+        return Ok(None);
+    }
+    let Some(token) = &config.access_token else {
+        return Ok(None);
+    };
+    let fresh = config.token_expires_at.is_none_or(|expires_at| {
+        expires_at > (Utc::now() + Duration::seconds(EXPIRY_MARGIN_SECS)).naive_utc()
+    });
+    if !fresh {
+        return Ok(None);
+    }
+    log::debug!("Using cached OAuth token for config {}", config.id);
+    Ok(Some(crypto::decrypt(token)?))
+}
+
+/// Returns a usable access token for `config`, reusing the cached one while it
+/// is valid and otherwise refreshing it or running the client credentials grant.
+async fn access_token(
+    pool: &DbPool,
+    config: &OAuthConfigDb,
+    variables: &HashMap<String, String>,
+    force: bool,
+) -> Result<String, OAuthError> {
+    let settings = ResolvedSettings::resolve(config, variables)?;
+    let key = settings.token_key();
+    let same_key = config.token_key.as_deref() == Some(key.as_str());
+
+    if !force {
+        if let Some(token) = cached_token(config, &key)? {
+            return Ok(token);
+        }
+    }
+
+    if let Some(refresh_token) = config.refresh_token.as_deref().filter(|_| same_key) {
+        log::info!("Refreshing OAuth token for config {}", config.id);
+        let params = vec![
+            ("grant_type", "refresh_token".to_string()),
+            ("refresh_token", crypto::decrypt(refresh_token)?),
+        ];
+        match request_token(pool, &settings, params).await {
+            Ok(token) => {
+                store_token(pool, config.id, &key, &token).await?;
+                return Ok(token.access_token);
+            }
+            Err(e) => log::warn!("Token refresh failed, starting over: {}", e),
+        }
+    }
+
+    if settings.grant_type != GRANT_CLIENT_CREDENTIALS {
+        return Err(OAuthError::AuthorizationRequired(config.id));
+    }
+
+    log::info!(
+        "Requesting client credentials token for config {}",
+        config.id
+    );
+    let params = vec![("grant_type", GRANT_CLIENT_CREDENTIALS.to_string())];
+    let token = request_token(pool, &settings, params).await?;
+    store_token(pool, config.id, &key, &token).await?;
+    Ok(token.access_token)
+}
+
+/// The settings an `oauth2` request uses: its own win over its folder's.
+async fn config_for_request(
+    pool: &DbPool,
+    request_id: Option<i64>,
+    folder_id: Option<i64>,
+) -> Result<OAuthConfigDb, OAuthError> {
+    let mut config = None;
+    if let Some(id) = request_id {
+        config = find_config(pool, Owner::Request(id)).await?;
+    }
+    if config.is_none() {
+        if let Some(id) = folder_id {
+            config = find_config(pool, Owner::Folder(id)).await?;
+        }
+    }
+    config.ok_or(OAuthError::NotConfigured)
+}
+
+/// Token for an `oauth2` request.
+pub async fn token_for_request(
+    pool: &DbPool,
+    request_id: Option<i64>,
+    folder_id: Option<i64>,
+    variables: &HashMap<String, String>,
+) -> Result<String, OAuthError> {
+    let config = config_for_request(pool, request_id, folder_id).await?;
+    access_token(pool, &config, variables, false).await
+}
+
+/// The valid cached token for an `oauth2` request, without contacting the
+/// token endpoint.
+pub async fn cached_token_for_request(
+    pool: &DbPool,
+    request_id: Option<i64>,
+    folder_id: Option<i64>,
+    variables: &HashMap<String, String>,
+) -> Result<Option<String>, OAuthError> {
+    let config = config_for_request(pool, request_id, folder_id).await?;
+    let settings = ResolvedSettings::resolve(&config, variables)?;
+    cached_token(&config, &settings.token_key())
+}
+
+async fn ensure_owner_exists(pool: &DbPool, owner: Owner) -> Result<(), sqlx::Error> {
+    match owner {
+        Owner::Request(id) => {
+            sqlx::query_scalar!("SELECT id FROM requests WHERE id = ?", id)
+                .fetch_one(pool)
+                .await?;
+        }
+        Owner::Folder(id) => {
+            sqlx::query_scalar!("SELECT id FROM folders WHERE id = ?", id)
+                .fetch_one(pool)
+                .await?;
+        }
+    }
+    Ok(())
+}
+
+async fn get_config(pool: &DbPool, owner: Owner) -> Result<Json<OAuthConfig>, OAuthError> {
+    log::debug!("Getting OAuth settings for {:?}", owner);
+    let config = find_config(pool, owner)
+        .await?
+        .ok_or(OAuthError::NotFound)?;
+    Ok(Json(OAuthConfig::from(config)))
+}
+
+async fn save_config(
+    pool: &DbPool,
+    owner: Owner,
+    payload: UpdateOAuthConfig,
+) -> Result<Json<OAuthConfig>, OAuthError> {
+    log::info!(
+        "Saving OAuth settings for {:?}: grant_type={}, client_id={}, token_url={}",
+        owner,
+        payload.grant_type,
+        payload.client_id,
+        payload.token_url
+    );
+
+    if !matches!(
+        payload.grant_type.as_str(),
+        GRANT_CLIENT_CREDENTIALS | GRANT_AUTHORIZATION_CODE
+    ) {
+        return Err(OAuthError::InvalidConfig(format!(
+            "unsupported grant_type '{}', expected client_credentials or authorization_code",
+            payload.grant_type
+        )));
+    }
+    if payload.client_id.trim().is_empty() {
+        return Err(OAuthError::InvalidConfig(
+            "client_id is required".to_string(),
+        ));
+    }
+    validate_url("token_url", &payload.token_url)?;
+    let authorization_url = payload.authorization_url.filter(|u| !u.trim().is_empty());
+    let redirect_uri = payload.redirect_uri.filter(|u| !u.trim().is_empty());
+    match &authorization_url {
+        Some(url) => validate_url("authorization_url", url)?,
+        None if payload.grant_type == GRANT_AUTHORIZATION_CODE => {
+            return Err(OAuthError::InvalidConfig(
+                "authorization_url is required for the authorization_code grant".to_string(),
+            ))
+        }
+        None => {}
+    }
+    if let Some(uri) = &redirect_uri {
+        validate_url("redirect_uri", uri)?;
+    }
+
+    ensure_owner_exists(pool, owner).await?;
+
+    let keep_secret = payload.client_secret.is_none();
+    let client_secret = match payload.client_secret.as_deref() {
+        None | Some("") => None,
+        Some(secret) => Some(crypto::encrypt(secret)?),
+    };
+
+    let id = match find_config(pool, owner).await? {
+        Some(existing) => {
+            sqlx::query!(
+                "UPDATE oauth_configs SET grant_type = ?, client_id = ?, client_secret = CASE WHEN ? THEN client_secret ELSE ? END, token_url = ?, authorization_url = ?, redirect_uri = ?, scope = ?, use_pkce = ?, updated_at = CURRENT_TIMESTAMP WHERE id = ?",
+                payload.grant_type,
+                payload.client_id,
+                keep_secret,
+                client_secret,
+                payload.token_url,
+                authorization_url,
+                redirect_uri,
+                payload.scope,
+                payload.use_pkce,
+                existing.id
+            )
+            .execute(pool)
+            .await?;
+            existing.id
+        }
+        None => {
+            let (request_id, folder_id) = owner.ids();
+            sqlx::query!(
+                "INSERT INTO oauth_configs (request_id, folder_id, grant_type, client_id, client_secret, token_url, authorization_url, redirect_uri, scope, use_pkce) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+                request_id,
+                folder_id,
+                payload.grant_type,
+                payload.client_id,
+                client_secret,
+                payload.token_url,
+                authorization_url,
+                redirect_uri,
+                payload.scope,
+                payload.use_pkce
+            )
+            .execute(pool)
+            .await?
+            .last_insert_rowid()
+        }
+    };
+
+    Ok(Json(OAuthConfig::from(load_config(pool, id).await?)))
+}
+
+async fn delete_config(pool: &DbPool, owner: Owner) -> Result<StatusCode, OAuthError> {
+    log::info!("Deleting OAuth settings for {:?}", owner);
+    let (request_id, folder_id) = owner.ids();
+    let result = sqlx::query!(
+        "DELETE FROM oauth_configs WHERE request_id IS ? AND folder_id IS ?",
+        request_id,
+        folder_id
+    )
+    .execute(pool)
+    .await?;
+    if result.rows_affected() == 0 {
+        return Err(OAuthError::NotFound);
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn get_request_config(
+    State(pool): State<DbPool>,
+    Path(id): Path<i64>,
+) -> Result<impl IntoResponse, OAuthError> {
+    get_config(&pool, Owner::Request(id)).await
+}
+
+async fn put_request_config(
+    State(pool): State<DbPool>,
+    Path(id): Path<i64>,
+    Json(payload): Json<UpdateOAuthConfig>,
+) -> Result<impl IntoResponse, OAuthError> {
+    save_config(&pool, Owner::Request(id), payload).await
+}
+
+async fn delete_request_config(
+    State(pool): State<DbPool>,
+    Path(id): Path<i64>,
+) -> Result<impl IntoResponse, OAuthError> {
+    delete_config(&pool, Owner::Request(id)).await
+}
+
+async fn get_folder_config(
+    State(pool): State<DbPool>,
+    Path(id): Path<i64>,
+) -> Result<impl IntoResponse, OAuthError> {
+    get_config(&pool, Owner::Folder(id)).await
+}
+
+async fn put_folder_config(
+    State(pool): State<DbPool>,
+    Path(id): Path<i64>,
+    Json(payload): Json<UpdateOAuthConfig>,
+) -> Result<impl IntoResponse, OAuthError> {
+    save_config(&pool, Owner::Folder(id), payload).await
+}
+
+async fn delete_folder_config(
+    State(pool): State<DbPool>,
+    Path(id): Path<i64>,
+) -> Result<impl IntoResponse, OAuthError> {
+    delete_config(&pool, Owner::Folder(id)).await
+}
+
+/// Fetches a new token right away (refreshing it for the authorization code grant).
+async fn fetch_token(
+    State(pool): State<DbPool>,
+    Path(id): Path<i64>,
+    Query(query): Query<EnvironmentQuery>,
+) -> Result<impl IntoResponse, OAuthError> {
+    log::info!("Fetching OAuth token for config {}", id);
+    let config = load_config(&pool, id).await?;
+    let variables = executor::load_environment_variables(&pool, query.environment_id).await?;
+    access_token(&pool, &config, &variables, true).await?;
+    Ok(Json(OAuthConfig::from(load_config(&pool, id).await?)))
+}
+
+async fn clear_token(
+    State(pool): State<DbPool>,
+    Path(id): Path<i64>,
+) -> Result<impl IntoResponse, OAuthError> {
+    log::info!("Clearing cached OAuth token for config {}", id);
+    let config = load_config(&pool, id).await?;
+    sqlx::query!(
+        "UPDATE oauth_configs SET access_token = NULL, refresh_token = NULL, token_expires_at = NULL, token_key = NULL WHERE id = ?",
+        config.id
+    )
+    .execute(&pool)
+    .await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Starts the authorization code flow by redirecting the browser to the
+/// authorization server.
+async fn authorize(
+    State(pool): State<DbPool>,
+    Path(id): Path<i64>,
+    Query(query): Query<EnvironmentQuery>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, OAuthError> {
+    log::info!("Starting OAuth authorization for config {}", id);
+    let config = load_config(&pool, id).await?;
+    if config.grant_type != GRANT_AUTHORIZATION_CODE {
+        return Err(OAuthError::InvalidConfig(
+            "only the authorization_code grant uses a browser authorization".to_string(),
+        ));
+    }
+    let variables = executor::load_environment_variables(&pool, query.environment_id).await?;
+    let settings = ResolvedSettings::resolve(&config, &variables)?;
+    let authorization_url = settings
+        .authorization_url
+        .as_deref()
+        .ok_or_else(|| OAuthError::InvalidConfig("authorization_url is required".to_string()))?;
+    let mut url = Url::parse(authorization_url)
+        .map_err(|e| OAuthError::InvalidConfig(format!("authorization_url: {}", e)))?;
+
+    let state = random_token(16);
+    let verifier = config.use_pkce.then(|| random_token(32));
+    {
+        let mut pairs = url.query_pairs_mut();
+        pairs
+            .append_pair("response_type", "code")
+            .append_pair("client_id", &settings.client_id)
+            .append_pair("redirect_uri", &callback_uri(&settings, &headers))
+            .append_pair("state", &state);
+        if let Some(scope) = &settings.scope {
+            pairs.append_pair("scope", scope);
+        }
+        if let Some(verifier) = &verifier {
+            pairs
+                .append_pair("code_challenge", &pkce_challenge(verifier))
+                .append_pair("code_challenge_method", "S256");
+        }
+    }
+
+    sqlx::query!(
+        "UPDATE oauth_configs SET pending_state = ?, pending_verifier = ?, pending_environment_id = ? WHERE id = ?",
+        state,
+        verifier,
+        query.environment_id,
+        id
+    )
+    .execute(&pool)
+    .await?;
+
+    Ok(Redirect::to(url.as_str()))
+}
+
+fn callback_page(title: &str, message: &str) -> Html<String> {
+    Html(format!(
+        "<!DOCTYPE html><html><head><meta charset=\"utf-8\"><title>{title}</title></head><body><h1>{title}</h1><p>{}</p></body></html>",
+        ammonia::clean_text(message)
+    ))
+}
+
+/// Redirect target of the authorization server: exchanges the code for tokens.
+async fn callback(
+    State(pool): State<DbPool>,
+    Query(query): Query<CallbackQuery>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, OAuthError> {
+    let Some(state) = query.state else {
+        return Err(OAuthError::InvalidConfig("missing state".to_string()));
+    };
+    let config = sqlx::query_as!(
+        OAuthConfigDb,
+        "SELECT id, request_id, folder_id, grant_type, client_id, client_secret, token_url, authorization_url, redirect_uri, scope, use_pkce, access_token, refresh_token, token_expires_at, token_key, pending_verifier, pending_environment_id, created_at, updated_at FROM oauth_configs WHERE pending_state = ?",
+        state
+    )
+    .fetch_one(&pool)
+    .await?;
+
+    sqlx::query!(
+        "UPDATE oauth_configs SET pending_state = NULL, pending_verifier = NULL, pending_environment_id = NULL WHERE id = ?",
+        config.id
+    )
+    .execute(&pool)
+    .await?;
+
+    if let Some(error) = query.error {
+        log::warn!(
+            "Authorization for config {} was denied: {}",
+            config.id,
+            error
+        );
+        let message = match query.error_description {
+            Some(description) => format!("{}: {}", error, description),
+            None => error,
+        };
+        return Ok(callback_page("Authorization failed", &message));
+    }
+    let Some(code) = query.code else {
+        return Err(OAuthError::InvalidConfig("missing code".to_string()));
+    };
+
+    let variables =
+        executor::load_environment_variables(&pool, config.pending_environment_id).await?;
+    let settings = ResolvedSettings::resolve(&config, &variables)?;
+    let mut params = vec![
+        ("grant_type", GRANT_AUTHORIZATION_CODE.to_string()),
+        ("code", code),
+        ("redirect_uri", callback_uri(&settings, &headers)),
+    ];
+    if let Some(verifier) = config.pending_verifier.clone() {
+        params.push(("code_verifier", verifier));
+    }
+
+    let token = match request_token(&pool, &settings, params).await {
+        Ok(token) => token,
+        Err(e) => return Ok(callback_page("Authorization failed", &e.to_string())),
+    };
+    store_token(&pool, config.id, &settings.token_key(), &token).await?;
+    log::info!("Authorization for config {} completed", config.id);
+
+    Ok(callback_page(
+        "Authorization complete",
+        "js-link received an access token. You can close this window.",
+    ))
+}
+
+pub fn routes(pool: DbPool) -> Router {
+    Router::new()
+        .route(
+            "/requests/:id/oauth",
+            get(get_request_config)
+                .put(put_request_config)
+                .delete(delete_request_config),
+        )
+        .route(
+            "/folders/:id/oauth",
+            get(get_folder_config)
+                .put(put_folder_config)
+                .delete(delete_folder_config),
+        )
+        .route("/oauth/callback", get(callback))
+        .route("/oauth/:id/authorize", get(authorize))
+        .route("/oauth/:id/token", post(fetch_token).delete(clear_token))
+        .with_state(pool)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db;
+    use axum::Form;
+    use axum_test::TestServer;
+    use serde_json::json;
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+    use tokio::net::TcpListener;
+
+    /// Minimal token endpoint issuing `token-N` for every grant it accepts.
+    async fn spawn_token_server(expires_in: i64) -> (String, Arc<AtomicUsize>) {
+        let issued = Arc::new(AtomicUsize::new(0));
+        let counter = issued.clone();
+        let app = Router::new().route(
+            "/token",
+            post(move |Form(form): Form<HashMap<String, String>>| {
+                let counter = counter.clone();
+                async move {
+                    let n = counter.fetch_add(1, Ordering::SeqCst) + 1;
+                    match form.get("grant_type").map(String::as_str) {
+                        Some("authorization_code") if !form.contains_key("code_verifier") => (
+                            StatusCode::BAD_REQUEST,
+                            Json(json!({ "error": "invalid_request", "error_description": "missing code_verifier" })),
+                        ),
+                        _ => (
+                            StatusCode::OK,
+                            Json(json!({
+                                "access_token": format!("token-{}", n),
+                                "token_type": "Bearer",
+                                "expires_in": expires_in,
+                                "refresh_token": format!("refresh-{}", n),
+                            })),
+                        ),
+                    }
+                }
+            }),
+        );
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        (format!("http://{}/token", addr), issued)
+    }
+
+    async fn create_request(pool: &DbPool) -> i64 {
+        sqlx::query_scalar!(
+            "INSERT INTO requests (name, method, url, auth_type) VALUES ('Me', 'GET', 'http://localhost/me', 'oauth2') RETURNING id"
+        )
+        .fetch_one(pool)
+        .await
+        .unwrap()
+    }
+
+    async fn disable_proxy(pool: &DbPool) {
+        sqlx::query!("UPDATE network_settings SET auto_proxy = FALSE WHERE id = 1")
+            .execute(pool)
+            .await
+            .unwrap();
+    }
+
+    #[test]
+    fn test_pkce_challenge() {
+        // Example from RFC 7636 appendix B
+        assert_eq!(
+            pkce_challenge("dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk"),
+            "E9Melhoa2OwvFrEMTJguCHaoeK1t8URWbuGJSstw-cM"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_client_credentials_token_is_cached_and_refreshed() {
+        let pool = db::create_test_pool().await;
+        disable_proxy(&pool).await;
+        let request_id = create_request(&pool).await;
+        let (token_url, issued) = spawn_token_server(3600).await;
+        let server = TestServer::new(routes(pool.clone())).unwrap();
+
+        let response = server
+            .put(&format!("/requests/{}/oauth", request_id))
+            .json(&json!({
+                "grant_type": "client_credentials",
+                "client_id": "{{client_id}}",
+                "client_secret": "s3cret",
+                "token_url": token_url,
+            }))
+            .await;
+        response.assert_status_ok();
+        let config: OAuthConfig = response.json();
+        assert!(config.client_secret_set);
+        assert!(config.token.is_none());
+
+        let variables = HashMap::from([("client_id".to_string(), "app".to_string())]);
+        let token = token_for_request(&pool, Some(request_id), None, &variables)
+            .await
+            .unwrap();
+        assert_eq!(token, "token-1");
+        let token = token_for_request(&pool, Some(request_id), None, &variables)
+            .await
+            .unwrap();
+        assert_eq!(token, "token-1");
+        assert_eq!(issued.load(Ordering::SeqCst), 1);
+
+        // An expired token is refreshed
+        sqlx::query!("UPDATE oauth_configs SET token_expires_at = '2000-01-01 00:00:00'")
+            .execute(&pool)
+            .await
+            .unwrap();
+        let token = token_for_request(&pool, Some(request_id), None, &variables)
+            .await
+            .unwrap();
+        assert_eq!(token, "token-2");
+
+        // A different client never reuses the cached token
+        let other = HashMap::from([("client_id".to_string(), "other".to_string())]);
+        let token = token_for_request(&pool, Some(request_id), None, &other)
+            .await
+            .unwrap();
+        assert_eq!(token, "token-3");
+    }
+
+    #[tokio::test]
+    async fn test_authorization_code_flow_with_pkce() {
+        let pool = db::create_test_pool().await;
+        disable_proxy(&pool).await;
+        let folder_id =
+            sqlx::query_scalar!("INSERT INTO folders (name) VALUES ('API') RETURNING id")
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+        let (token_url, _) = spawn_token_server(3600).await;
+        let server = TestServer::new(routes(pool.clone())).unwrap();
+
+        let config: OAuthConfig = server
+            .put(&format!("/folders/{}/oauth", folder_id))
+            .json(&json!({
+                "grant_type": "authorization_code",
+                "client_id": "app",
+                "token_url": token_url,
+                "authorization_url": "https://auth.example.com/authorize",
+                "scope": "read",
+            }))
+            .await
+            .json();
+
+        let err = token_for_request(&pool, None, Some(folder_id), &HashMap::new())
+            .await
+            .unwrap_err();
+        assert!(matches!(err, OAuthError::AuthorizationRequired(_)));
+
+        let response = server
+            .get(&format!("/oauth/{}/authorize", config.id))
+            .add_header(header::HOST, "localhost:3000")
+            .await;
+        response.assert_status(StatusCode::SEE_OTHER);
+        let location = Url::parse(response.header(header::LOCATION).to_str().unwrap()).unwrap();
+        let query: HashMap<_, _> = location.query_pairs().into_owned().collect();
+        assert_eq!(query["client_id"], "app");
+        assert_eq!(query["code_challenge_method"], "S256");
+        assert_eq!(
+            query["redirect_uri"],
+            "http://localhost:3000/api/oauth/callback"
+        );
+
+        let response = server
+            .get("/oauth/callback")
+            .add_query_param("code", "abc")
+            .add_query_param("state", &query["state"])
+            .await;
+        response.assert_status_ok();
+        assert!(response.text().contains("Authorization complete"));
+
+        let token = token_for_request(&pool, None, Some(folder_id), &HashMap::new())
+            .await
+            .unwrap();
+        assert_eq!(token, "token-1");
+
+        // The state is single use
+        server
+            .get("/oauth/callback")
+            .add_query_param("code", "abc")
+            .add_query_param("state", &query["state"])
+            .await
+            .assert_status(StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_invalid_config_rejected() {
+        let pool = db::create_test_pool().await;
+        let request_id = create_request(&pool).await;
+        let server = TestServer::new(routes(pool)).unwrap();
+
+        server
+            .put(&format!("/requests/{}/oauth", request_id))
+            .json(&json!({
+                "grant_type": "authorization_code",
+                "client_id": "app",
+                "token_url": "https://auth.example.com/token",
+            }))
+            .await
+            .assert_status(StatusCode::BAD_REQUEST);
+        server
+            .put("/requests/999/oauth")
+            .json(&json!({
+                "grant_type": "client_credentials",
+                "client_id": "app",
+                "token_url": "https://auth.example.com/token",
+            }))
+            .await
+            .assert_status(StatusCode::NOT_FOUND);
+    }
+}
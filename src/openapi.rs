@@ -0,0 +1,560 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    routing::get,
+    Json, Router,
+};
+use regex::Regex;
+use serde::Deserialize;
+use serde_json::{json, Map, Value};
+use std::sync::OnceLock;
+
+use crate::{
+    db::DbPool,
+    folders::{self, FolderDb},
+    form_body, generate, header_rows,
+    history::{self, LatestResponse},
+    i18n,
+    requests::{Request, RequestDb},
+};
+
+/// Headers described by the document in other ways than as parameters.
+const IMPLIED_HEADERS: [&str; 3] = ["authorization", "content-type", "accept"];
+
+pub enum OpenApiError {
+    FolderNotFound,
+    DatabaseError(sqlx::Error),
+}
+
+impl From<sqlx::Error> for OpenApiError {
+    fn from(e: sqlx::Error) -> Self {
+        OpenApiError::DatabaseError(e)
+    }
+}
+
+impl IntoResponse for OpenApiError {
+    fn into_response(self) -> Response {
+        match self {
+            OpenApiError::FolderNotFound => {
+                (StatusCode::NOT_FOUND, i18n::t("error.folder_not_found")).into_response()
+            }
+            OpenApiError::DatabaseError(e) => {
+                log::error!("Database error: {}", e);
+                (StatusCode::INTERNAL_SERVER_ERROR, i18n::t("error.database")).into_response()
+            }
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct OpenApiQuery {
+    /// `json` (default) or `yaml`
+    format: Option<String>,
+}
+
+fn placeholder() -> &'static Regex {
+    static PLACEHOLDER: OnceLock<Regex> = OnceLock::new();
+    PLACEHOLDER.get_or_init(|| Regex::new(r"\{\{\s*([^{}]+?)\s*\}\}").unwrap())
+}
+
+/// A request URL taken apart: the server it goes to, the path template with
+/// its parameters, and the query string.
+#[derive(Debug, PartialEq)]
+struct UrlParts {
+    server: Option<String>,
+    path: String,
+    path_params: Vec<String>,
+    query: Vec<(String, String)>,
+}
+
+fn split_url(url: &str) -> UrlParts {
+    let url = url.trim().split('#').next().unwrap_or_default();
+    let (url, query) = url.split_once('?').unwrap_or((url, ""));
+
+    // `{{base_url}}/users` or `https://api.example.com/users`
+    let (server, path) = if url.starts_with("{{") && url.contains("}}") {
+        let end = url.find("}}").unwrap() + 2;
+        (Some(url[..end].to_string()), &url[end..])
+    } else if let Some(scheme_end) = url.find("://") {
+        let host_end = url[scheme_end + 3..]
+            .find('/')
+            .map_or(url.len(), |i| scheme_end + 3 + i);
+        (Some(url[..host_end].to_string()), &url[host_end..])
+    } else {
+        (None, url)
+    };
+
+    let mut path_params = Vec::new();
+    let segments: Vec<String> = path
+        .split('/')
+        .filter(|s| !s.is_empty())
+        .map(|segment| {
+            // Express-style `:id` segments are parameters as well
+            if let Some(name) = segment.strip_prefix(':') {
+                path_params.push(name.to_string());
+                return format!("{{{}}}", name);
+            }
+            placeholder()
+                .replace_all(segment, |caps: &regex::Captures| {
+                    path_params.push(caps[1].to_string());
+                    format!("{{{}}}", &caps[1])
+                })
+                .into_owned()
+        })
+        .collect();
+
+    let query = query
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            let decode = |s: &str| {
+                urlencoding::decode(&s.replace('+', " "))
+                    .map(|d| d.into_owned())
+                    .unwrap_or_else(|_| s.to_string())
+            };
+            (decode(key), decode(value))
+        })
+        .collect();
+
+    UrlParts {
+        server,
+        path: format!("/{}", segments.join("/")),
+        path_params,
+        query,
+    }
+}
+
+/// An OpenAPI server for `server`, its `{{placeholders}}` turned into server
+/// variables defaulting to the folder's values.
+fn server_object(server: &str, folder_variables: &Map<String, Value>) -> Value {
+    let mut variables = Map::new();
+    let url = placeholder().replace_all(server, |caps: &regex::Captures| {
+        let name = caps[1].to_string();
+        let default = match folder_variables.get(&name) {
+            Some(Value::String(s)) => s.clone(),
+            Some(other) => other.to_string(),
+            None => name.clone(),
+        };
+        variables.insert(name.clone(), json!({ "default": default }));
+        format!("{{{}}}", name)
+    });
+    if variables.is_empty() {
+        json!({ "url": url })
+    } else {
+        json!({ "url": url, "variables": variables })
+    }
+}
+
+/// `operationId` from a request name, e.g. `List users` -> `listUsers`.
+fn operation_id(name: &str, used: &mut Vec<String>) -> String {
+    let mut id = String::new();
+    for (i, word) in name
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .enumerate()
+    {
+        let mut chars = word.chars();
+        let first = chars.next().unwrap();
+        if i == 0 {
+            id.push(first.to_ascii_lowercase());
+        } else {
+            id.push(first.to_ascii_uppercase());
+        }
+        id.push_str(chars.as_str());
+    }
+    if id.is_empty() {
+        id = "operation".to_string();
+    }
+    let base = id.clone();
+    let mut n = 2;
+    while used.contains(&id) {
+        id = format!("{}{}", base, n);
+        n += 1;
+    }
+    used.push(id.clone());
+    id
+}
+
+/// The security scheme a request's auth maps to, by name and definition.
+fn security_scheme(auth_type: &str) -> Option<(&'static str, Value)> {
+    match auth_type {
+        "basic" => Some(("basicAuth", json!({ "type": "http", "scheme": "basic" }))),
+        // Each of these ends in an `Authorization: Bearer` header
+        "bearer" | "oauth2" | "jwt" | "chained" => {
+            Some(("bearerAuth", json!({ "type": "http", "scheme": "bearer" })))
+        }
+        "aws_sigv4" => Some((
+            "awsSigV4",
+            json!({ "type": "apiKey", "in": "header", "name": "Authorization" }),
+        )),
+        _ => None,
+    }
+}
+
+fn request_body(request: &Request) -> Option<Value> {
+    let body = request
+        .body_content
+        .as_deref()
+        .or(request.body.as_deref())
+        .filter(|b| !b.trim().is_empty())?;
+    let (content_type, example, schema) = match request.body_type.as_str() {
+        "json" => {
+            let example: Value =
+                serde_json::from_str(body).unwrap_or_else(|_| Value::String(body.to_string()));
+            let schema = generate::infer_schema(&example);
+            ("application/json", example, schema)
+        }
+        "form" | "multipart" => {
+            let fields: Map<String, Value> = if request.body_type == "form" {
+                form_body::parse(body)
+                    .unwrap_or_default()
+                    .into_iter()
+                    .filter(|row| row.enabled)
+                    .map(|row| (row.key, Value::String(row.value)))
+                    .collect()
+            } else {
+                serde_json::from_str(body).unwrap_or_default()
+            };
+            let example = Value::Object(fields);
+            let schema = generate::infer_schema(&example);
+            let content_type = if request.body_type == "form" {
+                "application/x-www-form-urlencoded"
+            } else {
+                "multipart/form-data"
+            };
+            (content_type, example, schema)
+        }
+        "xml" => (
+            "application/xml",
+            Value::String(body.to_string()),
+            json!({ "type": "string" }),
+        ),
+        "binary" => (
+            "application/octet-stream",
+            Value::String(body.to_string()),
+            json!({ "type": "string", "format": "binary" }),
+        ),
+        _ => (
+            "text/plain",
+            Value::String(body.to_string()),
+            json!({ "type": "string" }),
+        ),
+    };
+    Some(json!({
+        "content": { content_type: { "schema": schema, "example": example } }
+    }))
+}
+
+fn responses(example: Option<LatestResponse>) -> Value {
+    let Some(example) = example else {
+        return json!({ "default": { "description": "Response" } });
+    };
+    let mut response = json!({ "description": format!("Last response ({})", example.status) });
+    if let Some(body) = example.body.filter(|b| !b.is_empty()) {
+        let content_type = example
+            .content_type
+            .as_deref()
+            .and_then(|c| c.split(';').next())
+            .unwrap_or("application/octet-stream")
+            .trim()
+            .to_string();
+        let text = String::from_utf8_lossy(&body);
+        let example = if content_type.contains("json") {
+            serde_json::from_str(&text).unwrap_or_else(|_| Value::String(text.to_string()))
+        } else {
+            Value::String(text.to_string())
+        };
+        response["content"] = json!({ content_type: { "example": example } });
+    }
+    json!({ example.status.to_string(): response })
+}
+
+fn operation(
+    request: &Request,
+    url: &UrlParts,
+    example: Option<LatestResponse>,
+    used_ids: &mut Vec<String>,
+) -> Value {
+    let mut parameters: Vec<Value> = url
+        .path_params
+        .iter()
+        .map(|name| {
+            json!({ "name": name, "in": "path", "required": true, "schema": { "type": "string" } })
+        })
+        .collect();
+    for (name, value) in &url.query {
+        parameters.push(json!({
+            "name": name,
+            "in": "query",
+            "schema": { "type": "string" },
+            "example": value,
+        }));
+    }
+    let headers = request
+        .headers
+        .as_deref()
+        .and_then(|h| header_rows::parse(h).ok())
+        .unwrap_or_default();
+    for row in headers.iter().filter(|row| {
+        row.enabled
+            && !row.key.is_empty()
+            && !IMPLIED_HEADERS.contains(&row.key.to_lowercase().as_str())
+    }) {
+        parameters.push(json!({
+            "name": row.key,
+            "in": "header",
+            "schema": { "type": "string" },
+            "example": row.value,
+        }));
+    }
+
+    let mut operation = json!({
+        "summary": request.name,
+        "operationId": operation_id(&request.name, used_ids),
+        "responses": responses(example),
+    });
+    if let Some(description) = request.description.as_deref().filter(|d| !d.is_empty()) {
+        operation["description"] = Value::String(description.to_string());
+    }
+    if !parameters.is_empty() {
+        operation["parameters"] = Value::Array(parameters);
+    }
+    if let Some(body) = request_body(request) {
+        operation["requestBody"] = body;
+    }
+    match security_scheme(&request.auth_type) {
+        Some((name, _)) => operation["security"] = json!([{ name: [] }]),
+        None => operation["security"] = json!([]),
+    }
+    operation
+}
+
+/// Builds the OpenAPI 3 document describing `requests`.
+fn document(folder: &FolderDb, requests: Vec<(Request, Option<LatestResponse>)>) -> Value {
+    let folder_variables: Map<String, Value> =
+        serde_json::from_str(&folder.variables).unwrap_or_default();
+    let mut servers: Vec<String> = Vec::new();
+    let mut paths = Map::new();
+    let mut schemes = Map::new();
+    let mut used_ids = Vec::new();
+
+    for (request, example) in requests {
+        let url = split_url(&folders::join_base_url(
+            folder.base_url.as_deref(),
+            &request.url,
+        ));
+        if let Some(server) = &url.server {
+            if !servers.contains(server) {
+                servers.push(server.clone());
+            }
+        }
+        if let Some((name, scheme)) = security_scheme(&request.auth_type) {
+            schemes.insert(name.to_string(), scheme);
+        }
+        let method = request.method.to_lowercase();
+        let path = paths
+            .entry(url.path.clone())
+            .or_insert_with(|| json!({}))
+            .as_object_mut()
+            .unwrap();
+        if path.contains_key(&method) {
+            log::debug!(
+                "Leaving out '{}', {} {} is described already",
+                request.name,
+                request.method,
+                url.path
+            );
+            continue;
+        }
+        let operation = operation(&request, &url, example, &mut used_ids);
+        path.insert(method, operation);
+    }
+
+    let description = match folder.description.as_deref().filter(|d| !d.is_empty()) {
+        Some(description) => description.to_string(),
+        None => format!(
+            "Generated by js-link {} from the folder '{}'.",
+            env!("CARGO_PKG_VERSION"),
+            folder.name
+        ),
+    };
+    let mut document = json!({
+        "openapi": "3.0.3",
+        "info": {
+            "title": folder.name,
+            "version": "1.0.0",
+            "description": description,
+        },
+        "paths": paths,
+    });
+    if !servers.is_empty() {
+        document["servers"] = servers
+            .iter()
+            .map(|s| server_object(s, &folder_variables))
+            .collect();
+    }
+    if !schemes.is_empty() {
+        document["components"] = json!({ "securitySchemes": schemes });
+    }
+    document
+}
+
+/// An OpenAPI 3 document describing the API calls of a folder, as a starting
+/// point for real documentation.
+async fn folder_openapi(
+    State(pool): State<DbPool>,
+    Path(id): Path<i64>,
+    Query(query): Query<OpenApiQuery>,
+) -> Result<Response, OpenApiError> {
+    let folder = sqlx::query_as!(
+        FolderDb,
+        "SELECT id, name, variables, description, base_url, created_at, updated_at, archived_at FROM folders WHERE id = ?",
+        id
+    )
+    .fetch_optional(&pool)
+    .await?
+    .ok_or(OpenApiError::FolderNotFound)?;
+
+    let rows = sqlx::query_as!(
+        RequestDb,
+        "SELECT id, name, method, url, body, headers, folder_id, request_type, body_type, body_content, auth_type, auth_token, auth_username, auth_password, captures, auth_config, serialized, ip_family, pre_request_script, test_script, callback, unresolved_variables, retry, compression, assertions, description, tls_versions, ssh_tunnel_id, latency_budget_ms, created_at, updated_at, archived_at FROM requests WHERE folder_id = ? AND archived_at IS NULL AND request_type = 'api' ORDER BY id",
+        id
+    )
+    .fetch_all(&pool)
+    .await?;
+
+    let mut requests = Vec::new();
+    for row in rows {
+        let example = history::latest_response(&pool, row.id).await?;
+        requests.push((Request::from(row), example));
+    }
+
+    log::info!(
+        "Generating OpenAPI for folder {} from {} requests",
+        id,
+        requests.len()
+    );
+    let document = document(&folder, requests);
+    if query.format.as_deref() == Some("yaml") {
+        let yaml = serde_yaml::to_string(&document).unwrap_or_default();
+        return Ok(([(header::CONTENT_TYPE, "application/yaml")], yaml).into_response());
+    }
+    Ok(Json(document).into_response())
+}
+
+pub fn routes(pool: DbPool) -> Router {
+    Router::new()
+        .route("/folders/:id/openapi", get(folder_openapi))
+        .with_state(pool)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::create_test_pool;
+    use axum_test::TestServer;
+
+    #[test]
+    fn test_split_url() {
+        let parts = split_url("{{base}}/users/{{user_id}}/posts/:post?page=2&q=a+b#top");
+        assert_eq!(parts.server.as_deref(), Some("{{base}}"));
+        assert_eq!(parts.path, "/users/{user_id}/posts/{post}");
+        assert_eq!(parts.path_params, ["user_id", "post"]);
+        assert_eq!(
+            parts.query,
+            [
+                ("page".to_string(), "2".to_string()),
+                ("q".to_string(), "a b".to_string())
+            ]
+        );
+
+        let parts = split_url("https://api.example.com");
+        assert_eq!(parts.server.as_deref(), Some("https://api.example.com"));
+        assert_eq!(parts.path, "/");
+    }
+
+    #[tokio::test]
+    async fn test_folder_openapi() {
+        let pool = create_test_pool().await;
+        let (folder_id,): (i64,) = sqlx::query_as(
+            "INSERT INTO folders (name, variables) VALUES ('Users API', '{\"base\":\"https://api.example.com\"}') RETURNING id",
+        )
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+        let (list_id,): (i64,) = sqlx::query_as(
+            "INSERT INTO requests (name, method, url, headers, folder_id, auth_type) VALUES ('List users', 'GET', '{{base}}/users?limit=10', '[{\"key\":\"X-Tenant\",\"value\":\"acme\"},{\"key\":\"Accept\",\"value\":\"application/json\"}]', ?, 'bearer') RETURNING id",
+        )
+        .bind(folder_id)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+        sqlx::query("INSERT INTO requests (name, method, url, folder_id, body_type, body_content, auth_type) VALUES ('Create user', 'POST', '{{base}}/users', ?, 'json', '{\"name\":\"Ada\",\"age\":36}', 'basic'), ('Old', 'GET', '{{base}}/old', ?, 'none', NULL, 'none')")
+            .bind(folder_id)
+            .bind(folder_id)
+            .execute(&pool)
+            .await
+            .unwrap();
+        sqlx::query("UPDATE requests SET archived_at = CURRENT_TIMESTAMP WHERE name = 'Old'")
+            .execute(&pool)
+            .await
+            .unwrap();
+        sqlx::query("INSERT INTO execution_history (request_id, method, url, status, duration_ms, response_body, response_content_type) VALUES (?, 'GET', 'https://api.example.com/users', 200, 12, '[{\"id\":1}]', 'application/json; charset=utf-8')")
+            .bind(list_id)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let server = TestServer::new(routes(pool)).unwrap();
+        let doc: Value = server
+            .get(&format!("/folders/{}/openapi", folder_id))
+            .await
+            .json();
+        assert_eq!(doc["openapi"], "3.0.3");
+        assert_eq!(doc["info"]["title"], "Users API");
+        assert_eq!(doc["servers"][0]["url"], "{base}");
+        assert_eq!(
+            doc["servers"][0]["variables"]["base"]["default"],
+            "https://api.example.com"
+        );
+        assert!(doc["paths"].get("/old").is_none());
+
+        let list = &doc["paths"]["/users"]["get"];
+        assert_eq!(list["operationId"], "listUsers");
+        assert_eq!(list["security"], json!([{ "bearerAuth": [] }]));
+        let parameters: Vec<(&str, &str)> = list["parameters"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|p| (p["name"].as_str().unwrap(), p["in"].as_str().unwrap()))
+            .collect();
+        assert_eq!(parameters, [("limit", "query"), ("X-Tenant", "header")]);
+        assert_eq!(
+            list["responses"]["200"]["content"]["application/json"]["example"],
+            json!([{ "id": 1 }])
+        );
+
+        let create = &doc["paths"]["/users"]["post"];
+        let body = &create["requestBody"]["content"]["application/json"];
+        assert_eq!(body["example"]["name"], "Ada");
+        assert_eq!(body["schema"]["properties"]["age"]["type"], "integer");
+        assert!(create["responses"]["default"].is_object());
+        assert_eq!(
+            doc["components"]["securitySchemes"]["basicAuth"]["scheme"],
+            "basic"
+        );
+
+        let yaml = server
+            .get(&format!("/folders/{}/openapi", folder_id))
+            .add_query_param("format", "yaml")
+            .await
+            .text();
+        assert!(yaml.contains("operationId: createUser"));
+        server
+            .get("/folders/999/openapi")
+            .await
+            .assert_status(StatusCode::NOT_FOUND);
+    }
+}
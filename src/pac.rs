@@ -0,0 +1,410 @@
+use boa_engine::{js_string, Context, JsResult, JsValue, NativeFunction, Source};
+use reqwest::{Client, Url};
+use std::collections::HashMap;
+use std::net::{IpAddr, ToSocketAddrs, UdpSocket};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+use crate::proxy::{self, ProxyUrl};
+
+/// How long a downloaded PAC script is reused before fetching it again.
+const SCRIPT_TTL: Duration = Duration::from_secs(300);
+const FETCH_TIMEOUT: Duration = Duration::from_secs(10);
+const MAX_SCRIPT_SIZE: usize = 1024 * 1024;
+// Keeps a runaway script from hanging the request
+const LOOP_ITERATION_LIMIT: u64 = 1_000_000;
+
+/// Downloaded scripts by PAC URL, with the time they were fetched.
+type ScriptCache = HashMap<String, (Instant, Arc<str>)>;
+
+static SCRIPTS: OnceLock<Mutex<ScriptCache>> = OnceLock::new();
+
+/// The predefined PAC functions that need no host access, as specified by
+/// Netscape and implemented by browsers. `dnsResolve` and `myIpAddress` are
+/// provided natively.
+const PAC_UTILS: &str = r#"
+function isPlainHostName(host) { return host.indexOf('.') < 0; }
+function dnsDomainIs(host, domain) {
+    return host.length >= domain.length && host.substring(host.length - domain.length) == domain;
+}
+function localHostOrDomainIs(host, hostdom) {
+    return host == hostdom || hostdom.lastIndexOf(host + '.', 0) == 0;
+}
+function isResolvable(host) { return dnsResolve(host) !== null; }
+function dnsDomainLevels(host) { return host.split('.').length - 1; }
+function convert_addr(ipchars) {
+    var bytes = ipchars.split('.');
+    return ((bytes[0] & 0xff) << 24 | (bytes[1] & 0xff) << 16 | (bytes[2] & 0xff) << 8 | (bytes[3] & 0xff)) >>> 0;
+}
+function isInNet(ipaddr, pattern, maskstr) {
+    var ip = /^\d+\.\d+\.\d+\.\d+$/.test(ipaddr) ? ipaddr : dnsResolve(ipaddr);
+    if (ip === null) return false;
+    var mask = convert_addr(maskstr);
+    return ((convert_addr(ip) & mask) >>> 0) == ((convert_addr(pattern) & mask) >>> 0);
+}
+function shExpMatch(url, pattern) {
+    pattern = pattern.replace(/[.+^${}()|[\]\\]/g, '\\$&').replace(/\*/g, '.*').replace(/\?/g, '.');
+    return new RegExp('^' + pattern + '$').test(url);
+}
+function pacNow(args) {
+    var gmt = args.length > 0 && args[args.length - 1] === 'GMT';
+    return { date: new Date(), gmt: gmt, args: gmt ? Array.prototype.slice.call(args, 0, -1) : Array.prototype.slice.call(args) };
+}
+function weekdayRange() {
+    var now = pacNow(arguments);
+    var days = ['SUN', 'MON', 'TUE', 'WED', 'THU', 'FRI', 'SAT'];
+    var today = now.gmt ? now.date.getUTCDay() : now.date.getDay();
+    var start = days.indexOf(now.args[0]);
+    var end = now.args.length > 1 ? days.indexOf(now.args[1]) : start;
+    return start <= end ? (today >= start && today <= end) : (today >= start || today <= end);
+}
+function timeRange() {
+    var now = pacNow(arguments);
+    var a = now.args.map(Number);
+    var h = now.gmt ? now.date.getUTCHours() : now.date.getHours();
+    var m = now.gmt ? now.date.getUTCMinutes() : now.date.getMinutes();
+    var s = now.gmt ? now.date.getUTCSeconds() : now.date.getSeconds();
+    var current, start, end;
+    if (a.length == 1) return h == a[0];
+    if (a.length == 2) { current = h; start = a[0]; end = a[1]; }
+    else if (a.length == 4) { current = h * 60 + m; start = a[0] * 60 + a[1]; end = a[2] * 60 + a[3]; }
+    else if (a.length == 6) { current = h * 3600 + m * 60 + s; start = a[0] * 3600 + a[1] * 60 + a[2]; end = a[3] * 3600 + a[4] * 60 + a[5]; }
+    else return false;
+    return start <= end ? (current >= start && current < end) : (current >= start || current < end);
+}
+function dateRange() {
+    var now = pacNow(arguments);
+    var months = ['JAN', 'FEB', 'MAR', 'APR', 'MAY', 'JUN', 'JUL', 'AUG', 'SEP', 'OCT', 'NOV', 'DEC'];
+    var d = now.date;
+    var value = function (arg, kind) {
+        if (kind == 'month') return months.indexOf(arg);
+        return Number(arg);
+    };
+    var kindOf = function (arg) {
+        if (months.indexOf(arg) >= 0) return 'month';
+        return Number(arg) > 31 ? 'year' : 'day';
+    };
+    var current = function (kind) {
+        if (kind == 'month') return now.gmt ? d.getUTCMonth() : d.getMonth();
+        if (kind == 'year') return now.gmt ? d.getUTCFullYear() : d.getFullYear();
+        return now.gmt ? d.getUTCDate() : d.getDate();
+    };
+    var args = now.args;
+    if (args.length == 1) return current(kindOf(args[0])) == value(args[0], kindOf(args[0]));
+    if (args.length == 2) {
+        var kind = kindOf(args[0]);
+        var start = value(args[0], kind), end = value(args[1], kind), cur = current(kind);
+        return start <= end ? (cur >= start && cur <= end) : (cur >= start || cur <= end);
+    }
+    return false;
+}
+"#;
+
+/// The outcome of `FindProxyForURL` for one target.
+#[derive(Debug, Clone)]
+pub enum PacDecision {
+    Direct,
+    Proxy(ProxyUrl),
+}
+
+/// Checks that a PAC URL can be fetched: `http`, `https` or `file`.
+pub fn validate_pac_url(raw: &str) -> Result<(), String> {
+    let url = Url::parse(raw.trim()).map_err(|e| format!("Invalid PAC URL '{}': {}", raw, e))?;
+    match url.scheme() {
+        "http" | "https" | "file" => Ok(()),
+        scheme => Err(format!(
+            "Unsupported PAC URL scheme '{}' (expected http, https or file)",
+            scheme
+        )),
+    }
+}
+
+/// Evaluates the PAC script at `pac_url` for `target_url`. Only the first
+/// usable entry of the returned list is honoured.
+pub async fn find_proxy(pac_url: &str, target_url: &str) -> Result<PacDecision, String> {
+    let script = fetch_script(pac_url).await?;
+
+    let target = Url::parse(target_url).map_err(|e| format!("Invalid URL: {}", e))?;
+    let host = target.host_str().unwrap_or_default().to_string();
+    let pac_target = pac_target_url(&target);
+
+    let result = tokio::task::spawn_blocking(move || evaluate(&script, &pac_target, &host))
+        .await
+        .map_err(|e| format!("PAC evaluation aborted: {}", e))??;
+    log::debug!("PAC result for {}: {}", target_url, result);
+
+    parse_result(&result)
+}
+
+/// The URL handed to `FindProxyForURL`. WebSocket schemes are reported as
+/// their HTTP equivalents and secure URLs are reduced to their origin, as
+/// browsers do.
+fn pac_target_url(target: &Url) -> String {
+    let scheme = match target.scheme() {
+        "ws" => "http",
+        "wss" => "https",
+        other => other,
+    };
+    let mut url = format!("{}://{}", scheme, target.host_str().unwrap_or_default());
+    if let Some(port) = target.port() {
+        url.push_str(&format!(":{}", port));
+    }
+    if scheme == "https" {
+        url.push('/');
+    } else {
+        url.push_str(target.path());
+        if let Some(query) = target.query() {
+            url.push('?');
+            url.push_str(query);
+        }
+    }
+    url
+}
+
+async fn fetch_script(pac_url: &str) -> Result<Arc<str>, String> {
+    let cache = SCRIPTS.get_or_init(Default::default);
+    if let Some((fetched_at, script)) = cache.lock().unwrap().get(pac_url) {
+        if fetched_at.elapsed() < SCRIPT_TTL {
+            return Ok(Arc::clone(script));
+        }
+    }
+
+    log::info!("Fetching PAC script from {}", pac_url);
+    let url = Url::parse(pac_url).map_err(|e| format!("Invalid PAC URL: {}", e))?;
+    let script = if url.scheme() == "file" {
+        let path = url
+            .to_file_path()
+            .map_err(|_| format!("Invalid PAC file path: {}", pac_url))?;
+        tokio::fs::read_to_string(&path)
+            .await
+            .map_err(|e| format!("Failed to read PAC file {}: {}", path.display(), e))?
+    } else {
+        // The PAC script itself is always fetched directly
+        let client = Client::builder()
+            .no_proxy()
+            .timeout(FETCH_TIMEOUT)
+            .build()
+            .map_err(|e| e.to_string())?;
+        let response = client
+            .get(url)
+            .send()
+            .await
+            .and_then(|r| r.error_for_status())
+            .map_err(|e| format!("Failed to fetch PAC script: {}", e))?;
+        response
+            .text()
+            .await
+            .map_err(|e| format!("Failed to read PAC script: {}", e))?
+    };
+    if script.len() > MAX_SCRIPT_SIZE {
+        return Err("PAC script is too large".to_string());
+    }
+
+    let script: Arc<str> = script.into();
+    cache
+        .lock()
+        .unwrap()
+        .insert(pac_url.to_string(), (Instant::now(), Arc::clone(&script)));
+    Ok(script)
+}
+
+fn evaluate(script: &str, url: &str, host: &str) -> Result<String, String> {
+    let mut context = Context::default();
+    context
+        .runtime_limits_mut()
+        .set_loop_iteration_limit(LOOP_ITERATION_LIMIT);
+    let js_error = |e: boa_engine::JsError| format!("PAC script error: {}", e);
+
+    context
+        .register_global_builtin_callable(
+            js_string!("dnsResolve"),
+            1,
+            NativeFunction::from_fn_ptr(dns_resolve),
+        )
+        .map_err(js_error)?;
+    context
+        .register_global_builtin_callable(
+            js_string!("myIpAddress"),
+            0,
+            NativeFunction::from_fn_ptr(my_ip_address),
+        )
+        .map_err(js_error)?;
+    context
+        .register_global_builtin_callable(
+            js_string!("alert"),
+            1,
+            NativeFunction::from_fn_ptr(alert),
+        )
+        .map_err(js_error)?;
+
+    context
+        .eval(Source::from_bytes(PAC_UTILS))
+        .map_err(js_error)?;
+    context.eval(Source::from_bytes(script)).map_err(js_error)?;
+
+    let call = format!(
+        "FindProxyForURL({}, {})",
+        serde_json::Value::from(url),
+        serde_json::Value::from(host)
+    );
+    let result = context.eval(Source::from_bytes(&call)).map_err(js_error)?;
+    Ok(result
+        .to_string(&mut context)
+        .map_err(js_error)?
+        .to_std_string_escaped())
+}
+
+/// Picks the first supported entry of a PAC result such as
+/// `PROXY proxy.corp:3128; SOCKS5 socks.corp:1080; DIRECT`.
+fn parse_result(result: &str) -> Result<PacDecision, String> {
+    for entry in result.split(';') {
+        let mut parts = entry.split_whitespace();
+        let Some(kind) = parts.next() else {
+            continue;
+        };
+        let scheme = match kind.to_ascii_uppercase().as_str() {
+            "DIRECT" => return Ok(PacDecision::Direct),
+            "PROXY" | "HTTP" => "http",
+            "HTTPS" => "https",
+            "SOCKS" | "SOCKS4" => "socks4",
+            "SOCKS5" => "socks5",
+            other => {
+                log::debug!("Skipping unsupported PAC entry type {}", other);
+                continue;
+            }
+        };
+        let Some(address) = parts.next() else {
+            continue;
+        };
+        return proxy::parse_proxy_url(&format!("{}://{}", scheme, address))
+            .map(PacDecision::Proxy);
+    }
+    // An empty result means DIRECT
+    Ok(PacDecision::Direct)
+}
+
+fn dns_resolve(_this: &JsValue, args: &[JsValue], context: &mut Context) -> JsResult<JsValue> {
+    let host = args
+        .first()
+        .cloned()
+        .unwrap_or_default()
+        .to_string(context)?
+        .to_std_string_escaped();
+    let resolved = (host.as_str(), 0)
+        .to_socket_addrs()
+        .ok()
+        .and_then(|mut addrs| addrs.find(|a| a.is_ipv4()))
+        .map(|addr| addr.ip().to_string());
+    Ok(match resolved {
+        Some(ip) => JsValue::from(js_string!(ip)),
+        None => JsValue::null(),
+    })
+}
+
+fn my_ip_address(_this: &JsValue, _args: &[JsValue], _context: &mut Context) -> JsResult<JsValue> {
+    // Connecting a UDP socket sends nothing but selects the outbound interface
+    let ip = UdpSocket::bind("0.0.0.0:0")
+        .and_then(|socket| {
+            socket.connect("192.0.2.1:80")?;
+            socket.local_addr()
+        })
+        .map(|addr| addr.ip())
+        .unwrap_or(IpAddr::from([127, 0, 0, 1]));
+    Ok(JsValue::from(js_string!(ip.to_string())))
+}
+
+fn alert(_this: &JsValue, args: &[JsValue], context: &mut Context) -> JsResult<JsValue> {
+    if let Some(message) = args.first() {
+        log::info!(
+            "PAC alert: {}",
+            message.to_string(context)?.to_std_string_escaped()
+        );
+    }
+    Ok(JsValue::undefined())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::proxy::ProxyScheme;
+
+    const SCRIPT: &str = r#"
+        function FindProxyForURL(url, host) {
+            if (isPlainHostName(host) || dnsDomainIs(host, ".intranet")) return "DIRECT";
+            if (shExpMatch(host, "*.example.com")) return "SOCKS5 socks.corp:1080; DIRECT";
+            if (isInNet(host, "10.0.0.0", "255.0.0.0")) return "PROXY lab.corp:8080";
+            if (url.indexOf("https:") == 0) return "PROXY secure.corp:3128";
+            return "PROXY proxy.corp:3128; DIRECT";
+        }
+    "#;
+
+    fn eval(url: &str) -> PacDecision {
+        let target = Url::parse(url).unwrap();
+        let result =
+            evaluate(SCRIPT, &pac_target_url(&target), target.host_str().unwrap()).unwrap();
+        parse_result(&result).unwrap()
+    }
+
+    fn proxy_of(decision: PacDecision) -> (ProxyScheme, String, u16) {
+        match decision {
+            PacDecision::Proxy(p) => (p.scheme, p.host().to_string(), p.port()),
+            PacDecision::Direct => panic!("expected a proxy"),
+        }
+    }
+
+    #[test]
+    fn test_evaluate_pac_script() {
+        assert!(matches!(
+            eval("http://wiki.intranet/page"),
+            PacDecision::Direct
+        ));
+        assert!(matches!(eval("http://printer/"), PacDecision::Direct));
+        assert_eq!(
+            proxy_of(eval("wss://api.example.com/socket")),
+            (ProxyScheme::Socks5, "socks.corp".to_string(), 1080)
+        );
+        assert_eq!(
+            proxy_of(eval("http://10.1.2.3/status")),
+            (ProxyScheme::Http, "lab.corp".to_string(), 8080)
+        );
+        assert_eq!(
+            proxy_of(eval("https://other.org/path?q=1")),
+            (ProxyScheme::Http, "secure.corp".to_string(), 3128)
+        );
+    }
+
+    #[test]
+    fn test_pac_target_url() {
+        let url = Url::parse("https://api.example.com:8443/secret?token=1").unwrap();
+        assert_eq!(pac_target_url(&url), "https://api.example.com:8443/");
+        let url = Url::parse("ws://example.com/socket?x=1").unwrap();
+        assert_eq!(pac_target_url(&url), "http://example.com/socket?x=1");
+    }
+
+    #[test]
+    fn test_evaluate_runaway_script_is_stopped() {
+        let result = evaluate(
+            "function FindProxyForURL(url, host) { while (true) {} }",
+            "http://example.com/",
+            "example.com",
+        );
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_find_proxy_from_file() {
+        let path = std::env::temp_dir().join(format!("jslink-{}.pac", std::process::id()));
+        std::fs::write(&path, SCRIPT).unwrap();
+        let pac_url = Url::from_file_path(&path).unwrap().to_string();
+
+        let decision = find_proxy(&pac_url, "http://example.org/").await.unwrap();
+        assert_eq!(
+            proxy_of(decision),
+            (ProxyScheme::Http, "proxy.corp".to_string(), 3128)
+        );
+        assert!(validate_pac_url(&pac_url).is_ok());
+        assert!(validate_pac_url("ftp://host/proxy.pac").is_err());
+
+        std::fs::remove_file(path).unwrap();
+    }
+}
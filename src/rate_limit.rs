@@ -0,0 +1,238 @@
+use axum::{
+    body::Body,
+    extract::ConnectInfo,
+    http::{header, HeaderValue, Request, Response, StatusCode},
+    Router,
+};
+use std::env;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::sync::Arc;
+use std::time::Duration;
+use tower_governor::{
+    governor::GovernorConfigBuilder, key_extractor::KeyExtractor, GovernorError, GovernorLayer,
+};
+
+use crate::api_tokens::TokenIdentity;
+
+/// The highest per-second limit, one request per nanosecond.
+const MAX_PER_SECOND: u64 = 1_000_000_000;
+
+/// Rate limiting settings for the `/api` surface, read from the environment.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RateLimitConfig {
+    /// Sustained number of requests allowed per second for one client.
+    pub per_second: u64,
+    /// Number of requests a client may issue back to back before being throttled.
+    pub burst: u32,
+}
+
+impl RateLimitConfig {
+    /// Reads `JSLINK_RATE_LIMIT_PER_SECOND` and `JSLINK_RATE_LIMIT_BURST`.
+    /// Rate limiting stays disabled unless a positive per-second limit is set;
+    /// limits above one request per nanosecond are capped to that.
+    pub fn from_env() -> Option<Self> {
+        Self::parse(
+            env::var("JSLINK_RATE_LIMIT_PER_SECOND").ok().as_deref(),
+            env::var("JSLINK_RATE_LIMIT_BURST").ok().as_deref(),
+        )
+    }
+
+    fn parse(per_second: Option<&str>, burst: Option<&str>) -> Option<Self> {
+        let per_second = per_second
+            .and_then(|v| v.trim().parse::<u64>().ok())
+            .filter(|v| *v > 0)?
+            .min(MAX_PER_SECOND);
+        let burst = burst
+            .and_then(|v| v.trim().parse::<u32>().ok())
+            .filter(|v| *v > 0)
+            .unwrap_or_else(|| per_second.min(u32::MAX as u64) as u32);
+
+        Some(Self { per_second, burst })
+    }
+}
+
+/// Identifies the client a request is counted against.
+#[derive(Debug, Clone, Hash, PartialEq, Eq)]
+pub enum ClientKey {
+    Token(i64),
+    Ip(IpAddr),
+}
+
+/// Keys requests by the API token they were authenticated with, otherwise by
+/// peer IP. Unverified bearer tokens are ignored so that clients cannot open
+/// fresh buckets by sending made-up ones.
+#[derive(Debug, Clone, Copy)]
+pub struct ClientKeyExtractor;
+
+impl KeyExtractor for ClientKeyExtractor {
+    type Key = ClientKey;
+
+    fn extract<T>(&self, req: &Request<T>) -> Result<Self::Key, GovernorError> {
+        if let Some(token) = req.extensions().get::<TokenIdentity>() {
+            return Ok(ClientKey::Token(token.id));
+        }
+
+        // Requests without connection info (e.g. in-process tests) share one bucket
+        let ip = req
+            .extensions()
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|info| info.0.ip())
+            .unwrap_or(IpAddr::V4(Ipv4Addr::UNSPECIFIED));
+        Ok(ClientKey::Ip(ip))
+    }
+}
+
+fn too_many_requests(error: GovernorError, burst: u32) -> Response<Body> {
+    match error {
+        GovernorError::TooManyRequests { wait_time, headers } => {
+            log::warn!("Rate limit exceeded, retry in {}s", wait_time);
+            let mut response = Response::new(Body::from(format!(
+                "Too many requests, retry in {}s",
+                wait_time
+            )));
+            *response.status_mut() = StatusCode::TOO_MANY_REQUESTS;
+            if let Some(headers) = headers {
+                response.headers_mut().extend(headers);
+            }
+            let headers = response.headers_mut();
+            headers.insert("ratelimit-limit", HeaderValue::from(burst));
+            headers.insert("ratelimit-remaining", HeaderValue::from(0));
+            headers.insert("ratelimit-reset", HeaderValue::from(wait_time));
+            headers.insert(header::RETRY_AFTER, HeaderValue::from(wait_time));
+            response
+        }
+        mut other => other.as_response(),
+    }
+}
+
+/// Wraps `router` in the rate limiting layer described by `config`. Must be
+/// applied inside `api_tokens::authenticate` to tell token holders apart.
+pub fn apply(router: Router, config: RateLimitConfig) -> Router {
+    let burst = config.burst;
+    let governor_config = GovernorConfigBuilder::default()
+        .period(Duration::from_nanos(
+            MAX_PER_SECOND / config.per_second.clamp(1, MAX_PER_SECOND),
+        ))
+        .burst_size(config.burst)
+        .key_extractor(ClientKeyExtractor)
+        .error_handler(move |error| too_many_requests(error, burst))
+        .use_headers()
+        .finish()
+        .expect("rate limit period and burst are non-zero");
+    let governor_config = Arc::new(governor_config);
+
+    // Forget idle clients periodically so the key map doesn't grow without bound
+    let limiter = governor_config.limiter().clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(60));
+        loop {
+            interval.tick().await;
+            limiter.retain_recent();
+        }
+    });
+
+    log::info!(
+        "API rate limiting enabled: {} req/s per client, burst {}",
+        config.per_second,
+        config.burst
+    );
+
+    router.layer(GovernorLayer {
+        config: governor_config,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{middleware::Next, routing::get};
+    use axum_test::TestServer;
+
+    /// Stands in for `api_tokens::authenticate`, accepting `valid-<id>`.
+    async fn identify(mut request: Request<Body>, next: Next) -> Response<Body> {
+        let id = request
+            .headers()
+            .get(header::AUTHORIZATION)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.strip_prefix("Bearer valid-"))
+            .and_then(|id| id.parse().ok());
+        if let Some(id) = id {
+            request.extensions_mut().insert(TokenIdentity {
+                id,
+                name: format!("token {}", id),
+            });
+        }
+        next.run(request).await
+    }
+
+    fn limited_server(burst: u32) -> TestServer {
+        let router = Router::new().route("/ping", get(|| async { "pong" }));
+        let router = apply(
+            router,
+            RateLimitConfig {
+                per_second: 1,
+                burst,
+            },
+        )
+        .layer(axum::middleware::from_fn(identify));
+        TestServer::new(router).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_requests_over_burst_are_rejected() {
+        let server = limited_server(2);
+
+        server.get("/ping").await.assert_status(StatusCode::OK);
+        server.get("/ping").await.assert_status(StatusCode::OK);
+
+        let response = server.get("/ping").await;
+        response.assert_status(StatusCode::TOO_MANY_REQUESTS);
+        assert!(response.headers().contains_key("retry-after"));
+        assert_eq!(response.headers()["ratelimit-remaining"], "0");
+    }
+
+    #[tokio::test]
+    async fn test_tokens_have_separate_buckets() {
+        let server = limited_server(1);
+
+        server
+            .get("/ping")
+            .authorization_bearer("valid-1")
+            .await
+            .assert_status(StatusCode::OK);
+        server
+            .get("/ping")
+            .authorization_bearer("valid-1")
+            .await
+            .assert_status(StatusCode::TOO_MANY_REQUESTS);
+        server
+            .get("/ping")
+            .authorization_bearer("valid-2")
+            .await
+            .assert_status(StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_unverified_tokens_share_the_ip_bucket() {
+        let server = limited_server(1);
+
+        server
+            .get("/ping")
+            .authorization_bearer("made-up-1")
+            .await
+            .assert_status(StatusCode::OK);
+        server
+            .get("/ping")
+            .authorization_bearer("made-up-2")
+            .await
+            .assert_status(StatusCode::TOO_MANY_REQUESTS);
+    }
+
+    #[test]
+    fn test_per_second_is_capped() {
+        let config = RateLimitConfig::parse(Some("5000000000"), Some("10")).unwrap();
+        assert_eq!(config.per_second, MAX_PER_SECOND);
+        assert_eq!(config.burst, 10);
+        assert_eq!(RateLimitConfig::parse(Some("0"), None), None);
+    }
+}
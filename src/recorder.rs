@@ -0,0 +1,719 @@
+use axum::{
+    body::{Body, Bytes},
+    extract::{Path, RawQuery, State},
+    http::{HeaderMap, HeaderName, Method, StatusCode},
+    response::{IntoResponse, Response},
+    routing::{any, get, post, put},
+    Json, Router,
+};
+use chrono::{DateTime, NaiveDateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+
+use crate::{db::DbPool, header_rows, i18n};
+
+// Recording proxy: js-link stands in front of an existing app as a reverse
+// proxy, forwarding everything sent to /record/:id to the recording's
+// target and keeping each exchange so it can be saved as requests.
+
+/// Bodies longer than this are stored truncated; forwarding is unaffected.
+const MAX_RECORDED_BODY: usize = 1024 * 1024;
+const FORWARD_TIMEOUT_SECS: u64 = 60;
+
+/// Headers that describe a single connection rather than the message.
+const HOP_BY_HOP: &[&str] = &[
+    "connection",
+    "keep-alive",
+    "proxy-authenticate",
+    "proxy-authorization",
+    "proxy-connection",
+    "te",
+    "trailer",
+    "transfer-encoding",
+    "upgrade",
+];
+
+static CLIENT: OnceLock<reqwest::Client> = OnceLock::new();
+
+/// Redirects are passed through to the caller rather than followed.
+fn client() -> &'static reqwest::Client {
+    CLIENT.get_or_init(|| {
+        reqwest::Client::builder()
+            .redirect(reqwest::redirect::Policy::none())
+            .timeout(Duration::from_secs(FORWARD_TIMEOUT_SECS))
+            .build()
+            .expect("Failed to build recording proxy client")
+    })
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Recording {
+    pub id: i64,
+    pub name: String,
+    pub target_url: String,
+    /// Whether exchanges are kept; a paused recording only forwards
+    pub recording: bool,
+    pub created_at: DateTime<Utc>,
+}
+
+struct RecordingDb {
+    id: i64,
+    name: String,
+    target_url: String,
+    recording: bool,
+    created_at: NaiveDateTime,
+}
+
+impl From<RecordingDb> for Recording {
+    fn from(r: RecordingDb) -> Self {
+        Recording {
+            id: r.id,
+            name: r.name,
+            target_url: r.target_url,
+            recording: r.recording,
+            created_at: r.created_at.and_utc(),
+        }
+    }
+}
+
+/// One request forwarded through a recording and the target's reply.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Exchange {
+    pub id: i64,
+    pub recording_id: i64,
+    pub method: String,
+    pub url: String,
+    pub request_headers: HashMap<String, String>,
+    pub request_body: String,
+    pub status: Option<u16>,
+    pub response_headers: HashMap<String, String>,
+    pub response_body: String,
+    pub error: Option<String>,
+    pub duration_ms: i64,
+    pub created_at: DateTime<Utc>,
+}
+
+struct ExchangeDb {
+    id: i64,
+    recording_id: i64,
+    method: String,
+    url: String,
+    request_headers: String,
+    request_body: String,
+    status: Option<i64>,
+    response_headers: String,
+    response_body: String,
+    error: Option<String>,
+    duration_ms: i64,
+    created_at: NaiveDateTime,
+}
+
+impl From<ExchangeDb> for Exchange {
+    fn from(e: ExchangeDb) -> Self {
+        Exchange {
+            id: e.id,
+            recording_id: e.recording_id,
+            method: e.method,
+            url: e.url,
+            request_headers: serde_json::from_str(&e.request_headers).unwrap_or_default(),
+            request_body: e.request_body,
+            status: e.status.and_then(|s| u16::try_from(s).ok()),
+            response_headers: serde_json::from_str(&e.response_headers).unwrap_or_default(),
+            response_body: e.response_body,
+            error: e.error,
+            duration_ms: e.duration_ms,
+            created_at: e.created_at.and_utc(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateRecording {
+    pub name: String,
+    pub target_url: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateRecording {
+    pub recording: bool,
+}
+
+/// Which exchanges to save, all of them when absent, and the folder to save
+/// them in; a new folder named after the recording when absent.
+#[derive(Debug, Default, Deserialize)]
+pub struct SaveExchanges {
+    #[serde(default)]
+    pub exchange_ids: Option<Vec<i64>>,
+    #[serde(default)]
+    pub folder_id: Option<i64>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SavedExchanges {
+    pub folder_id: i64,
+    pub request_ids: Vec<i64>,
+}
+
+pub enum RecorderError {
+    InvalidName,
+    InvalidTargetUrl,
+    RecordingNotFound,
+    FolderNotFound,
+    DatabaseError(#[allow(dead_code)] sqlx::Error),
+}
+
+impl From<sqlx::Error> for RecorderError {
+    fn from(e: sqlx::Error) -> Self {
+        match e {
+            sqlx::Error::RowNotFound => RecorderError::RecordingNotFound,
+            _ => RecorderError::DatabaseError(e),
+        }
+    }
+}
+
+impl IntoResponse for RecorderError {
+    fn into_response(self) -> Response {
+        match self {
+            RecorderError::InvalidName => (
+                StatusCode::BAD_REQUEST,
+                i18n::t("error.invalid_recording_name"),
+            )
+                .into_response(),
+            RecorderError::InvalidTargetUrl => {
+                (StatusCode::BAD_REQUEST, i18n::t("error.invalid_target_url")).into_response()
+            }
+            RecorderError::RecordingNotFound => {
+                (StatusCode::NOT_FOUND, i18n::t("error.recording_not_found")).into_response()
+            }
+            RecorderError::FolderNotFound => {
+                (StatusCode::NOT_FOUND, i18n::t("error.folder_not_found")).into_response()
+            }
+            RecorderError::DatabaseError(_) => {
+                (StatusCode::INTERNAL_SERVER_ERROR, i18n::t("error.database")).into_response()
+            }
+        }
+    }
+}
+
+fn validate_target_url(url: &str) -> Result<(), RecorderError> {
+    match reqwest::Url::parse(url) {
+        Ok(url) if matches!(url.scheme(), "http" | "https") && url.has_host() => Ok(()),
+        _ => Err(RecorderError::InvalidTargetUrl),
+    }
+}
+
+/// The target URL for a path below /record/:id.
+fn target_for(base: &str, path: &str, query: Option<&str>) -> String {
+    let mut url = format!("{}/{}", base.trim_end_matches('/'), path);
+    if let Some(query) = query.filter(|q| !q.is_empty()) {
+        url.push('?');
+        url.push_str(query);
+    }
+    url
+}
+
+fn is_hop_by_hop(name: &HeaderName) -> bool {
+    HOP_BY_HOP.contains(&name.as_str())
+}
+
+/// Headers as stored, repeated ones joined with a comma.
+fn header_map(headers: &HeaderMap) -> HashMap<String, String> {
+    let mut map: HashMap<String, String> = HashMap::new();
+    for (name, value) in headers {
+        if is_hop_by_hop(name) {
+            continue;
+        }
+        let Ok(value) = value.to_str() else {
+            continue;
+        };
+        map.entry(name.to_string())
+            .and_modify(|v| {
+                v.push_str(", ");
+                v.push_str(value);
+            })
+            .or_insert_with(|| value.to_string());
+    }
+    map
+}
+
+fn body_text(body: &[u8]) -> String {
+    let text = String::from_utf8_lossy(&body[..body.len().min(MAX_RECORDED_BODY)]);
+    text.into_owned()
+}
+
+async fn fetch_recording(pool: &DbPool, id: i64) -> Result<RecordingDb, sqlx::Error> {
+    sqlx::query_as!(
+        RecordingDb,
+        r#"SELECT id as "id!", name, target_url, recording, created_at as "created_at: NaiveDateTime" FROM recordings WHERE id = ?"#,
+        id
+    )
+    .fetch_one(pool)
+    .await
+}
+
+async fn create_recording(
+    State(pool): State<DbPool>,
+    Json(payload): Json<CreateRecording>,
+) -> Result<impl IntoResponse, RecorderError> {
+    if payload.name.trim().is_empty() {
+        return Err(RecorderError::InvalidName);
+    }
+    validate_target_url(&payload.target_url)?;
+
+    let recording = sqlx::query_as!(
+        RecordingDb,
+        r#"INSERT INTO recordings (name, target_url) VALUES (?, ?) RETURNING id as "id!", name, target_url, recording, created_at as "created_at: NaiveDateTime""#,
+        payload.name,
+        payload.target_url
+    )
+    .fetch_one(&pool)
+    .await?;
+
+    log::info!(
+        "Created recording {} forwarding to {}",
+        recording.id,
+        recording.target_url
+    );
+    Ok((StatusCode::CREATED, Json(Recording::from(recording))))
+}
+
+async fn list_recordings(State(pool): State<DbPool>) -> Result<impl IntoResponse, RecorderError> {
+    let recordings = sqlx::query_as!(
+        RecordingDb,
+        r#"SELECT id as "id!", name, target_url, recording, created_at as "created_at: NaiveDateTime" FROM recordings ORDER BY id DESC"#
+    )
+    .fetch_all(&pool)
+    .await?;
+    Ok(Json(
+        recordings
+            .into_iter()
+            .map(Recording::from)
+            .collect::<Vec<_>>(),
+    ))
+}
+
+/// Pauses or resumes recording.
+async fn update_recording(
+    State(pool): State<DbPool>,
+    Path(id): Path<i64>,
+    Json(payload): Json<UpdateRecording>,
+) -> Result<impl IntoResponse, RecorderError> {
+    let recording = sqlx::query_as!(
+        RecordingDb,
+        r#"UPDATE recordings SET recording = ? WHERE id = ? RETURNING id as "id!", name, target_url, recording, created_at as "created_at: NaiveDateTime""#,
+        payload.recording,
+        id
+    )
+    .fetch_one(&pool)
+    .await?;
+    log::info!(
+        "Recording {} {}",
+        id,
+        if recording.recording {
+            "resumed"
+        } else {
+            "paused"
+        }
+    );
+    Ok(Json(Recording::from(recording)))
+}
+
+async fn delete_recording(
+    State(pool): State<DbPool>,
+    Path(id): Path<i64>,
+) -> Result<impl IntoResponse, RecorderError> {
+    let result = sqlx::query!("DELETE FROM recordings WHERE id = ?", id)
+        .execute(&pool)
+        .await?;
+    if result.rows_affected() == 0 {
+        return Err(RecorderError::RecordingNotFound);
+    }
+    log::info!("Deleted recording {}", id);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn list_exchanges(
+    State(pool): State<DbPool>,
+    Path(id): Path<i64>,
+) -> Result<impl IntoResponse, RecorderError> {
+    fetch_recording(&pool, id).await?;
+    let exchanges = sqlx::query_as!(
+        ExchangeDb,
+        r#"SELECT id as "id!", recording_id, method, url, request_headers, request_body, status, response_headers, response_body, error, duration_ms, created_at as "created_at: NaiveDateTime" FROM recorded_exchanges WHERE recording_id = ? ORDER BY id"#,
+        id
+    )
+    .fetch_all(&pool)
+    .await?;
+    Ok(Json(
+        exchanges
+            .into_iter()
+            .map(Exchange::from)
+            .collect::<Vec<_>>(),
+    ))
+}
+
+/// Name of a request saved from an exchange, such as `GET /users/{id}`
+/// without the query.
+fn request_name(method: &str, url: &str) -> String {
+    let path = reqwest::Url::parse(url)
+        .map(|u| u.path().to_string())
+        .unwrap_or_else(|_| url.to_string());
+    format!("{} {}", method, path)
+}
+
+fn body_type(headers: &HashMap<String, String>, body: &str) -> &'static str {
+    if body.is_empty() {
+        return "none";
+    }
+    let content_type = headers
+        .get("content-type")
+        .map(|c| c.to_lowercase())
+        .unwrap_or_default();
+    if content_type.contains("json") {
+        "json"
+    } else if content_type.contains("xml") {
+        "xml"
+    } else {
+        "text"
+    }
+}
+
+/// Saves exchanges as requests. Repeated calls to the same method and URL
+/// are saved once, and the headers the proxy or client set per connection
+/// are left out.
+async fn save_exchanges(
+    State(pool): State<DbPool>,
+    Path(id): Path<i64>,
+    Json(payload): Json<SaveExchanges>,
+) -> Result<impl IntoResponse, RecorderError> {
+    let recording = fetch_recording(&pool, id).await?;
+    let exchanges = sqlx::query_as!(
+        ExchangeDb,
+        r#"SELECT id as "id!", recording_id, method, url, request_headers, request_body, status, response_headers, response_body, error, duration_ms, created_at as "created_at: NaiveDateTime" FROM recorded_exchanges WHERE recording_id = ? ORDER BY id"#,
+        id
+    )
+    .fetch_all(&pool)
+    .await?;
+
+    let mut tx = pool.begin().await?;
+    let folder_id = match payload.folder_id {
+        Some(folder_id) => {
+            sqlx::query_scalar!("SELECT id FROM folders WHERE id = ?", folder_id)
+                .fetch_optional(&mut *tx)
+                .await?
+                .ok_or(RecorderError::FolderNotFound)?;
+            folder_id
+        }
+        None => {
+            sqlx::query_scalar!(
+                r#"INSERT INTO folders (name) VALUES (?) RETURNING id as "id!""#,
+                recording.name
+            )
+            .fetch_one(&mut *tx)
+            .await?
+        }
+    };
+
+    let mut seen = HashSet::new();
+    let mut request_ids = Vec::new();
+    for exchange in exchanges.into_iter().map(Exchange::from) {
+        if payload
+            .exchange_ids
+            .as_ref()
+            .is_some_and(|ids| !ids.contains(&exchange.id))
+        {
+            continue;
+        }
+        if !seen.insert((exchange.method.clone(), exchange.url.clone())) {
+            continue;
+        }
+        let mut headers = exchange.request_headers;
+        headers.retain(|name, _| !matches!(name.as_str(), "host" | "content-length"));
+        let body_type = body_type(&headers, &exchange.request_body);
+        let headers = header_rows::to_json(&header_rows::from_map(headers));
+        let body = Some(exchange.request_body).filter(|b| !b.is_empty());
+        let name = request_name(&exchange.method, &exchange.url);
+        let request_id = sqlx::query_scalar!(
+            r#"INSERT INTO requests (name, method, url, body, headers, folder_id, body_type) VALUES (?, ?, ?, ?, ?, ?, ?) RETURNING id as "id!""#,
+            name,
+            exchange.method,
+            exchange.url,
+            body,
+            headers,
+            folder_id,
+            body_type
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+        request_ids.push(request_id);
+    }
+    tx.commit().await?;
+
+    log::info!(
+        "Saved {} request(s) from recording {} into folder {}",
+        request_ids.len(),
+        id,
+        folder_id
+    );
+    Ok(Json(SavedExchanges {
+        folder_id,
+        request_ids,
+    }))
+}
+
+async fn forward_root(
+    State(pool): State<DbPool>,
+    Path(id): Path<i64>,
+    RawQuery(query): RawQuery,
+    method: Method,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<Response, RecorderError> {
+    forward(pool, id, String::new(), query, method, headers, body).await
+}
+
+async fn forward_path(
+    State(pool): State<DbPool>,
+    Path((id, path)): Path<(i64, String)>,
+    RawQuery(query): RawQuery,
+    method: Method,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<Response, RecorderError> {
+    forward(pool, id, path, query, method, headers, body).await
+}
+
+/// Sends the request on to the target and replies with whatever comes
+/// back, recording both unless the recording is paused.
+async fn forward(
+    pool: DbPool,
+    id: i64,
+    path: String,
+    query: Option<String>,
+    method: Method,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<Response, RecorderError> {
+    let recording = fetch_recording(&pool, id).await?;
+    let url = target_for(&recording.target_url, &path, query.as_deref());
+    log::debug!("Recording {} forwarding {} {}", id, method, url);
+
+    let mut outgoing = client().request(method.clone(), &url);
+    for (name, value) in &headers {
+        if !is_hop_by_hop(name) && name != "host" && name != "content-length" {
+            outgoing = outgoing.header(name, value);
+        }
+    }
+
+    let started = Instant::now();
+    let result = async {
+        let response = outgoing.body(body.clone()).send().await?;
+        let status = response.status();
+        let response_headers = response.headers().clone();
+        let response_body = response.bytes().await?;
+        Ok::<_, reqwest::Error>((status, response_headers, response_body))
+    }
+    .await;
+    let duration_ms = started.elapsed().as_millis() as i64;
+
+    let (reply, status, response_headers, response_body, error) = match result {
+        Ok((status, response_headers, response_body)) => {
+            let mut reply = Response::builder().status(status.as_u16());
+            for (name, value) in &response_headers {
+                if !is_hop_by_hop(name) && name != "content-length" {
+                    reply = reply.header(name, value);
+                }
+            }
+            let recorded_headers = header_map(&response_headers);
+            let recorded_body = body_text(&response_body);
+            let reply = reply
+                .body(Body::from(response_body))
+                .unwrap_or_else(|_| StatusCode::BAD_GATEWAY.into_response());
+            (
+                reply,
+                Some(i64::from(status.as_u16())),
+                recorded_headers,
+                recorded_body,
+                None,
+            )
+        }
+        Err(e) => {
+            log::warn!("Recording {} could not reach {}: {}", id, url, e);
+            let message = format!("Failed to reach {}: {}", recording.target_url, e);
+            (
+                (StatusCode::BAD_GATEWAY, message.clone()).into_response(),
+                None,
+                HashMap::new(),
+                String::new(),
+                Some(message),
+            )
+        }
+    };
+
+    if recording.recording {
+        let request_headers =
+            serde_json::to_string(&header_map(&headers)).unwrap_or_else(|_| "{}".to_string());
+        let request_body = body_text(&body);
+        let response_headers =
+            serde_json::to_string(&response_headers).unwrap_or_else(|_| "{}".to_string());
+        let method = method.as_str();
+        let stored = sqlx::query!(
+            "INSERT INTO recorded_exchanges (recording_id, method, url, request_headers, request_body, status, response_headers, response_body, error, duration_ms) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+            id,
+            method,
+            url,
+            request_headers,
+            request_body,
+            status,
+            response_headers,
+            response_body,
+            error,
+            duration_ms
+        )
+        .execute(&pool)
+        .await;
+        // The caller already has its reply, so a failed insert only loses the record
+        if let Err(e) = stored {
+            log::warn!("Failed to record exchange for recording {}: {}", id, e);
+        }
+    }
+
+    Ok(reply)
+}
+
+pub fn routes(pool: DbPool) -> Router {
+    Router::new()
+        .route("/recordings", post(create_recording).get(list_recordings))
+        .route(
+            "/recordings/:id",
+            put(update_recording).delete(delete_recording),
+        )
+        .route("/recordings/:id/exchanges", get(list_exchanges))
+        .route("/recordings/:id/save", post(save_exchanges))
+        .route("/record/:id", any(forward_root))
+        .route("/record/:id/*path", any(forward_path))
+        .with_state(pool)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db;
+    use axum_test::TestServer;
+    use httpmock::MockServer;
+    use serde_json::json;
+
+    #[test]
+    fn test_target_for() {
+        assert_eq!(
+            target_for("http://app:8080/api/", "users/1", Some("page=2")),
+            "http://app:8080/api/users/1?page=2"
+        );
+        assert_eq!(target_for("http://app", "", None), "http://app/");
+        assert_eq!(
+            request_name("GET", "http://app/users/1?page=2"),
+            "GET /users/1"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_record_and_save() {
+        let pool = db::create_test_pool().await;
+        let target = MockServer::start_async().await;
+        let mock = target.mock(|when, then| {
+            when.method(httpmock::Method::POST)
+                .path("/api/orders")
+                .query_param("dry", "1")
+                .header("x-tenant", "acme")
+                .body(r#"{"item":"book"}"#);
+            then.status(201)
+                .header("content-type", "application/json")
+                .body(r#"{"id":7}"#);
+        });
+        let server = TestServer::new(routes(pool.clone())).unwrap();
+
+        let recording: Recording = server
+            .post("/recordings")
+            .json(&json!({ "name": "Shop", "target_url": target.url("/api") }))
+            .await
+            .json();
+        assert!(recording.recording);
+
+        for _ in 0..2 {
+            let response = server
+                .post(&format!("/record/{}/orders?dry=1", recording.id))
+                .add_header("x-tenant", "acme")
+                .add_header("content-type", "application/json")
+                .text(r#"{"item":"book"}"#)
+                .await;
+            response.assert_status(StatusCode::CREATED);
+            assert_eq!(response.text(), r#"{"id":7}"#);
+        }
+        mock.assert_calls(2);
+
+        let exchanges: Vec<Exchange> = server
+            .get(&format!("/recordings/{}/exchanges", recording.id))
+            .await
+            .json();
+        assert_eq!(exchanges.len(), 2);
+        assert_eq!(exchanges[0].url, target.url("/api/orders?dry=1"));
+        assert_eq!(exchanges[0].status, Some(201));
+        assert_eq!(exchanges[0].request_headers["x-tenant"], "acme");
+        assert_eq!(exchanges[0].response_body, r#"{"id":7}"#);
+
+        // A paused recording forwards without recording
+        server
+            .put(&format!("/recordings/{}", recording.id))
+            .json(&json!({ "recording": false }))
+            .await
+            .assert_status_ok();
+        server
+            .post(&format!("/record/{}/orders?dry=1", recording.id))
+            .add_header("x-tenant", "acme")
+            .text(r#"{"item":"book"}"#)
+            .await
+            .assert_status(StatusCode::CREATED);
+        mock.assert_calls(3);
+
+        // The repeated call is saved once
+        let saved: SavedExchanges = server
+            .post(&format!("/recordings/{}/save", recording.id))
+            .json(&json!({}))
+            .await
+            .json();
+        assert_eq!(saved.request_ids.len(), 1);
+        let request = sqlx::query!(
+            "SELECT name, method, url, body, body_type, headers, folder_id FROM requests WHERE id = ?",
+            saved.request_ids[0]
+        )
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+        assert_eq!(request.name, "POST /api/orders");
+        assert_eq!(request.url, target.url("/api/orders?dry=1"));
+        assert_eq!(request.body.as_deref(), Some(r#"{"item":"book"}"#));
+        assert_eq!(request.body_type, "json");
+        assert_eq!(request.folder_id, Some(saved.folder_id));
+        let headers = header_rows::enabled_map(
+            &header_rows::parse(request.headers.as_deref().unwrap()).unwrap(),
+        );
+        assert_eq!(headers["x-tenant"], "acme");
+        assert!(!headers.contains_key("host"));
+
+        server
+            .post("/recordings")
+            .json(&json!({ "name": "Bad", "target_url": "ftp://files" }))
+            .await
+            .assert_status(StatusCode::BAD_REQUEST);
+        server
+            .get("/record/999/anything")
+            .await
+            .assert_status(StatusCode::NOT_FOUND);
+        server
+            .delete(&format!("/recordings/{}", recording.id))
+            .await
+            .assert_status(StatusCode::NO_CONTENT);
+    }
+}
@@ -0,0 +1,59 @@
+use std::collections::HashMap;
+use std::env;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Duration;
+use tokio::sync::{Mutex as AsyncMutex, OwnedMutexGuard};
+
+const DEFAULT_WAIT_SECS: u64 = 30;
+
+/// One lock per serialized request, created on first use.
+static LOCKS: OnceLock<Mutex<HashMap<i64, Arc<AsyncMutex<()>>>>> = OnceLock::new();
+
+/// How long an execution waits for a running instance of the same request,
+/// from `JSLINK_SERIALIZED_WAIT_SECS`.
+pub fn wait_timeout() -> Duration {
+    let secs = env::var("JSLINK_SERIALIZED_WAIT_SECS")
+        .ok()
+        .and_then(|v| v.trim().parse().ok())
+        .unwrap_or(DEFAULT_WAIT_SECS);
+    Duration::from_secs(secs)
+}
+
+/// Waits for the request's previous executions to finish. Waiters are served
+/// in arrival order; the returned guard releases the request when dropped.
+/// Returns `None` when the timeout elapses first.
+pub async fn acquire(request_id: i64, timeout: Duration) -> Option<OwnedMutexGuard<()>> {
+    let lock = {
+        let mut locks = LOCKS
+            .get_or_init(Default::default)
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        locks.entry(request_id).or_default().clone()
+    };
+    if let Ok(guard) = lock.clone().try_lock_owned() {
+        return Some(guard);
+    }
+    log::info!(
+        "Request {} is already running, waiting up to {}s",
+        request_id,
+        timeout.as_secs()
+    );
+    tokio::time::timeout(timeout, lock.lock_owned()).await.ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_acquire_waits_for_running_instance() {
+        let first = acquire(9001, Duration::from_secs(1)).await.unwrap();
+        assert!(acquire(9001, Duration::from_millis(50)).await.is_none());
+        assert!(acquire(9002, Duration::from_millis(50)).await.is_some());
+
+        let waiter = tokio::spawn(acquire(9001, Duration::from_secs(5)));
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        drop(first);
+        assert!(waiter.await.unwrap().is_some());
+    }
+}
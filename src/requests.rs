@@ -8,7 +8,22 @@ use axum::{
 use chrono::{DateTime, NaiveDateTime, Utc};
 use serde::{Deserialize, Serialize};
 
-use crate::db::DbPool;
+use crate::{
+    assertions,
+    audit::{self, Action, Actor, Entity},
+    capture,
+    compression::CompressionSettings,
+    crypto,
+    db::DbPool,
+    environments::present,
+    form_body, i18n,
+    ip_family::IpFamily,
+    retry::RetryPolicy,
+    ssh_tunnels,
+    tls_version::TlsVersions,
+    unresolved::UnresolvedMode,
+    webhooks::CallbackConfig,
+};
 
 #[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
 pub struct Request {
@@ -22,16 +37,46 @@ pub struct Request {
     pub request_type: String, // 'api' or 'ws'
     pub body_type: String,    // 'none', 'json', 'xml', 'text', 'form', 'multipart', 'binary'
     pub body_content: Option<String>,
-    pub auth_type: String, // 'none', 'bearer', 'basic'
+    pub auth_type: String, // 'none', 'bearer', 'basic', 'oauth2', 'aws_sigv4', 'jwt', 'chained'
     pub auth_token: Option<String>,
     pub auth_username: Option<String>,
     pub auth_password: Option<String>,
+    pub captures: Option<String>, // Stored as JSON, see capture::CaptureRule
+    pub auth_config: Option<String>, // Stored as JSON, settings of auth_type
+    /// Executions of a serialized request wait for the previous one to finish.
+    pub serialized: bool,
+    /// 'auto', 'ipv4', 'ipv6' or an IP address to connect to, see ip_family
+    pub ip_family: String,
+    /// JavaScript run before variable substitution, see scripting
+    pub pre_request_script: Option<String>,
+    /// JavaScript with `jsl.test` assertions run against the response
+    pub test_script: Option<String>,
+    /// Stored as JSON, how to wait for an async reply, see webhooks
+    pub callback: Option<String>,
+    /// 'error', 'warn' or 'keep' for placeholders no variable matches, see
+    /// unresolved::UnresolvedMode; unset uses the global setting
+    pub unresolved_variables: Option<String>,
+    /// Stored as JSON, when to send the request again, see retry
+    pub retry: Option<String>,
+    /// Stored as JSON, how response bodies are decompressed, see compression
+    pub compression: Option<String>,
+    /// Stored as JSON, checks on the response, see assertions
+    pub assertions: Option<String>,
+    /// Markdown documenting the request
+    pub description: Option<String>,
+    /// Stored as JSON, the TLS versions to offer, see tls_version; unset
+    /// uses the global setting
+    pub tls_versions: Option<String>,
+    /// SSH tunnel the request is sent through, see ssh_tunnels
+    pub ssh_tunnel_id: Option<i64>,
+    /// Expected latency; slower responses are marked over budget
+    pub latency_budget_ms: Option<i64>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
     pub archived_at: Option<DateTime<Utc>>,
 }
 
-#[derive(sqlx::FromRow, Clone)]
+#[derive(sqlx::FromRow, Serialize, Deserialize, Clone)]
 pub struct RequestDb {
     pub id: i64,
     pub name: String,
@@ -47,6 +92,21 @@ pub struct RequestDb {
     pub auth_token: Option<String>,
     pub auth_username: Option<String>,
     pub auth_password: Option<String>,
+    pub captures: Option<String>,
+    pub auth_config: Option<String>,
+    pub serialized: bool,
+    pub ip_family: String,
+    pub pre_request_script: Option<String>,
+    pub test_script: Option<String>,
+    pub callback: Option<String>,
+    pub unresolved_variables: Option<String>,
+    pub retry: Option<String>,
+    pub compression: Option<String>,
+    pub assertions: Option<String>,
+    pub description: Option<String>,
+    pub tls_versions: Option<String>,
+    pub ssh_tunnel_id: Option<i64>,
+    pub latency_budget_ms: Option<i64>,
     pub created_at: NaiveDateTime,
     pub updated_at: NaiveDateTime,
     pub archived_at: Option<NaiveDateTime>,
@@ -66,9 +126,24 @@ impl From<RequestDb> for Request {
             body_type: r.body_type,
             body_content: r.body_content,
             auth_type: r.auth_type,
-            auth_token: r.auth_token,
-            auth_username: r.auth_username,
-            auth_password: r.auth_password,
+            auth_token: decrypt_credential(r.id, r.auth_token),
+            auth_username: decrypt_credential(r.id, r.auth_username),
+            auth_password: decrypt_credential(r.id, r.auth_password),
+            captures: r.captures,
+            auth_config: r.auth_config,
+            serialized: r.serialized,
+            ip_family: r.ip_family,
+            pre_request_script: r.pre_request_script,
+            test_script: r.test_script,
+            callback: r.callback,
+            unresolved_variables: r.unresolved_variables,
+            retry: r.retry,
+            compression: r.compression,
+            assertions: r.assertions,
+            description: r.description,
+            tls_versions: r.tls_versions,
+            ssh_tunnel_id: r.ssh_tunnel_id,
+            latency_budget_ms: r.latency_budget_ms,
             created_at: DateTime::from_naive_utc_and_offset(r.created_at, Utc),
             updated_at: DateTime::from_naive_utc_and_offset(r.updated_at, Utc),
             archived_at: r
@@ -78,6 +153,79 @@ impl From<RequestDb> for Request {
     }
 }
 
+/// Encrypts a credential column (`auth_token`, `auth_username` or
+/// `auth_password`) for storage. Empty and already encrypted values are
+/// stored as they are.
+pub fn encrypt_credential(value: Option<&str>) -> Result<Option<String>, crypto::CryptoError> {
+    match value {
+        Some(value) if !value.is_empty() && !crypto::is_encrypted(value) => {
+            crypto::encrypt(value).map(Some)
+        }
+        other => Ok(other.map(str::to_string)),
+    }
+}
+
+/// A value that cannot be decrypted, such as one written under another
+/// secret key, is left out so that its ciphertext is never shown or sent.
+fn decrypt_credential(id: i64, value: Option<String>) -> Option<String> {
+    let value = value?;
+    match crypto::decrypt(&value) {
+        Ok(plaintext) => Some(plaintext),
+        Err(e) => {
+            log::warn!("Could not decrypt credentials of request {}: {}", id, e);
+            None
+        }
+    }
+}
+
+/// Whether every stored credential of the request can be decrypted.
+pub fn credentials_readable(request: &RequestDb) -> bool {
+    [
+        &request.auth_token,
+        &request.auth_username,
+        &request.auth_password,
+    ]
+    .into_iter()
+    .flatten()
+    .all(|value| crypto::decrypt(value).is_ok())
+}
+
+/// Encrypts credentials stored in plaintext by earlier versions. Run once
+/// the database is migrated; rows already encrypted are left alone.
+pub async fn encrypt_stored_credentials(pool: &DbPool) -> Result<u64, anyhow::Error> {
+    let rows = sqlx::query!(
+        "SELECT id, auth_token, auth_username, auth_password FROM requests WHERE auth_token NOT LIKE 'enc:%' OR auth_username NOT LIKE 'enc:%' OR auth_password NOT LIKE 'enc:%'"
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let mut updated = 0;
+    for row in rows {
+        let auth_token = encrypt_credential(row.auth_token.as_deref())?;
+        let auth_username = encrypt_credential(row.auth_username.as_deref())?;
+        let auth_password = encrypt_credential(row.auth_password.as_deref())?;
+        if (&auth_token, &auth_username, &auth_password)
+            == (&row.auth_token, &row.auth_username, &row.auth_password)
+        {
+            continue;
+        }
+        sqlx::query!(
+            "UPDATE requests SET auth_token = ?, auth_username = ?, auth_password = ? WHERE id = ?",
+            auth_token,
+            auth_username,
+            auth_password,
+            row.id
+        )
+        .execute(pool)
+        .await?;
+        updated += 1;
+    }
+    if updated > 0 {
+        log::info!("Encrypted the stored credentials of {} request(s)", updated);
+    }
+    Ok(updated)
+}
+
 #[derive(Deserialize, Clone)]
 pub struct CreateRequest {
     pub name: String,
@@ -96,6 +244,36 @@ pub struct CreateRequest {
     pub auth_token: Option<String>,
     pub auth_username: Option<String>,
     pub auth_password: Option<String>,
+    #[serde(default)]
+    pub captures: Option<String>,
+    #[serde(default)]
+    pub auth_config: Option<String>,
+    #[serde(default)]
+    pub serialized: bool,
+    #[serde(default = "default_ip_family")]
+    pub ip_family: String,
+    #[serde(default)]
+    pub pre_request_script: Option<String>,
+    #[serde(default)]
+    pub test_script: Option<String>,
+    #[serde(default)]
+    pub callback: Option<String>,
+    #[serde(default)]
+    pub unresolved_variables: Option<String>,
+    #[serde(default)]
+    pub retry: Option<String>,
+    #[serde(default)]
+    pub compression: Option<String>,
+    #[serde(default)]
+    pub assertions: Option<String>,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub tls_versions: Option<String>,
+    #[serde(default)]
+    pub ssh_tunnel_id: Option<i64>,
+    #[serde(default)]
+    pub latency_budget_ms: Option<i64>,
 }
 
 fn default_request_type() -> String {
@@ -110,6 +288,10 @@ fn default_auth_type() -> String {
     "none".to_string()
 }
 
+fn default_ip_family() -> String {
+    "auto".to_string()
+}
+
 #[derive(Deserialize, Clone)]
 pub struct UpdateRequest {
     name: String,
@@ -128,6 +310,149 @@ pub struct UpdateRequest {
     auth_token: Option<String>,
     auth_username: Option<String>,
     auth_password: Option<String>,
+    /// `null` or absent keeps the stored rules, an empty string clears them.
+    #[serde(default)]
+    captures: Option<String>,
+    /// `null` or absent keeps the stored settings, an empty string clears them.
+    #[serde(default)]
+    auth_config: Option<String>,
+    /// `null` or absent keeps the stored flag.
+    #[serde(default)]
+    serialized: Option<bool>,
+    /// `null` or absent keeps the stored choice.
+    #[serde(default)]
+    ip_family: Option<String>,
+    /// `null` or absent keeps the stored script, an empty string clears it.
+    #[serde(default)]
+    pre_request_script: Option<String>,
+    /// `null` or absent keeps the stored script, an empty string clears it.
+    #[serde(default)]
+    test_script: Option<String>,
+    /// `null` or absent keeps the stored settings, an empty string clears them.
+    #[serde(default)]
+    callback: Option<String>,
+    /// `null` or absent keeps the stored mode, an empty string returns to the
+    /// global setting.
+    #[serde(default)]
+    unresolved_variables: Option<String>,
+    /// `null` or absent keeps the stored policy, an empty string clears it.
+    #[serde(default)]
+    retry: Option<String>,
+    /// `null` or absent keeps the stored settings, an empty string clears them.
+    #[serde(default)]
+    compression: Option<String>,
+    /// `null` or absent keeps the stored assertions, an empty string clears
+    /// them.
+    #[serde(default)]
+    assertions: Option<String>,
+    /// `null` or absent keeps the stored description, an empty string clears
+    /// it.
+    #[serde(default)]
+    description: Option<String>,
+    /// `null` or absent keeps the stored versions, an empty string returns
+    /// to the global setting.
+    #[serde(default)]
+    tls_versions: Option<String>,
+    /// `null` or absent keeps the stored tunnel, 0 sends the request
+    /// directly again.
+    #[serde(default)]
+    ssh_tunnel_id: Option<i64>,
+    /// `null` or absent keeps the stored budget, 0 removes it.
+    #[serde(default)]
+    latency_budget_ms: Option<i64>,
+}
+
+/// Partial update; absent fields keep their stored value and `null` clears
+/// an optional one. Credentials are kept unless sent.
+#[derive(Deserialize)]
+pub struct PatchRequest {
+    #[serde(default)]
+    name: Option<String>,
+    #[serde(default)]
+    method: Option<String>,
+    #[serde(default)]
+    url: Option<String>,
+    #[serde(default)]
+    request_type: Option<String>,
+    #[serde(default)]
+    body_type: Option<String>,
+    #[serde(default)]
+    auth_type: Option<String>,
+    #[serde(default, deserialize_with = "present")]
+    body: Option<Option<String>>,
+    #[serde(default, deserialize_with = "present")]
+    headers: Option<Option<String>>,
+    #[serde(default, deserialize_with = "present")]
+    folder_id: Option<Option<i64>>,
+    #[serde(default, deserialize_with = "present")]
+    body_content: Option<Option<String>>,
+    #[serde(default, deserialize_with = "present")]
+    auth_token: Option<Option<String>>,
+    #[serde(default, deserialize_with = "present")]
+    auth_username: Option<Option<String>>,
+    #[serde(default, deserialize_with = "present")]
+    auth_password: Option<Option<String>>,
+    #[serde(default, deserialize_with = "present")]
+    captures: Option<Option<String>>,
+    #[serde(default, deserialize_with = "present")]
+    auth_config: Option<Option<String>>,
+    #[serde(default, deserialize_with = "present")]
+    pre_request_script: Option<Option<String>>,
+    #[serde(default, deserialize_with = "present")]
+    test_script: Option<Option<String>>,
+    #[serde(default, deserialize_with = "present")]
+    callback: Option<Option<String>>,
+    #[serde(default, deserialize_with = "present")]
+    unresolved_variables: Option<Option<String>>,
+    #[serde(default, deserialize_with = "present")]
+    retry: Option<Option<String>>,
+    #[serde(default, deserialize_with = "present")]
+    compression: Option<Option<String>>,
+    #[serde(default, deserialize_with = "present")]
+    assertions: Option<Option<String>>,
+    #[serde(default, deserialize_with = "present")]
+    description: Option<Option<String>>,
+    #[serde(default, deserialize_with = "present")]
+    tls_versions: Option<Option<String>>,
+    #[serde(default, deserialize_with = "present")]
+    ssh_tunnel_id: Option<Option<i64>>,
+    #[serde(default, deserialize_with = "present")]
+    latency_budget_ms: Option<Option<i64>>,
+    #[serde(default)]
+    serialized: Option<bool>,
+    #[serde(default)]
+    ip_family: Option<String>,
+}
+
+/// Header carrying the number of requests matching a list query before
+/// `limit` and `offset` are applied.
+pub const TOTAL_COUNT_HEADER: &str = "x-total-count";
+
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SortField {
+    #[default]
+    Id,
+    Name,
+    UpdatedAt,
+}
+
+impl SortField {
+    fn as_str(self) -> &'static str {
+        match self {
+            SortField::Id => "id",
+            SortField::Name => "name",
+            SortField::UpdatedAt => "updated_at",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    #[default]
+    Asc,
+    Desc,
 }
 
 #[derive(Deserialize)]
@@ -136,11 +461,40 @@ pub struct ListRequestsQuery {
     include_archived: bool,
     #[serde(default)]
     folder_id: Option<i64>,
+    /// HTTP method, case-insensitive
+    #[serde(default)]
+    method: Option<String>,
+    #[serde(default)]
+    request_type: Option<String>,
+    /// Case-insensitive text the name or URL contains
+    #[serde(default)]
+    q: Option<String>,
+    #[serde(default)]
+    sort: SortField,
+    #[serde(default)]
+    order: SortOrder,
+    /// Absent returns all matching requests
+    #[serde(default)]
+    limit: Option<i64>,
+    #[serde(default)]
+    offset: Option<i64>,
 }
 
 pub enum RequestError {
     InvalidName,
     InvalidMethod,
+    InvalidCaptures(String),
+    InvalidIpFamily(String),
+    InvalidCallback(String),
+    InvalidUnresolvedMode(String),
+    InvalidRetry(String),
+    InvalidCompression(String),
+    InvalidAssertions(String),
+    InvalidTlsVersions(String),
+    SshTunnelNotFound,
+    InvalidLatencyBudget,
+    InvalidFormBody(String),
+    EncryptionError(String),
     RequestNotFound,
     DatabaseError(#[allow(dead_code)] sqlx::Error),
 }
@@ -154,27 +508,178 @@ impl From<sqlx::Error> for RequestError {
     }
 }
 
+impl From<crypto::CryptoError> for RequestError {
+    fn from(e: crypto::CryptoError) -> Self {
+        log::error!("Failed to encrypt request credentials: {}", e);
+        RequestError::EncryptionError(e.to_string())
+    }
+}
+
 impl IntoResponse for RequestError {
     fn into_response(self) -> Response {
         match self {
-            RequestError::InvalidName => {
-                (StatusCode::BAD_REQUEST, "Invalid request name").into_response()
-            }
+            RequestError::InvalidName => (
+                StatusCode::BAD_REQUEST,
+                i18n::t("error.invalid_request_name"),
+            )
+                .into_response(),
             RequestError::InvalidMethod => {
-                (StatusCode::BAD_REQUEST, "Invalid HTTP method").into_response()
+                (StatusCode::BAD_REQUEST, i18n::t("error.invalid_method")).into_response()
+            }
+            RequestError::InvalidCaptures(msg) => (StatusCode::BAD_REQUEST, msg).into_response(),
+            RequestError::InvalidIpFamily(msg) => (StatusCode::BAD_REQUEST, msg).into_response(),
+            RequestError::InvalidCallback(msg) => (StatusCode::BAD_REQUEST, msg).into_response(),
+            RequestError::InvalidUnresolvedMode(msg) => {
+                (StatusCode::BAD_REQUEST, msg).into_response()
+            }
+            RequestError::InvalidRetry(msg) => (StatusCode::BAD_REQUEST, msg).into_response(),
+            RequestError::InvalidCompression(msg) => (StatusCode::BAD_REQUEST, msg).into_response(),
+            RequestError::InvalidTlsVersions(msg) => (StatusCode::BAD_REQUEST, msg).into_response(),
+            RequestError::SshTunnelNotFound => (
+                StatusCode::BAD_REQUEST,
+                i18n::t("error.ssh_tunnel_not_found"),
+            )
+                .into_response(),
+            RequestError::InvalidLatencyBudget => (
+                StatusCode::BAD_REQUEST,
+                i18n::t("error.invalid_latency_budget"),
+            )
+                .into_response(),
+            RequestError::InvalidAssertions(msg) => (StatusCode::BAD_REQUEST, msg).into_response(),
+            RequestError::InvalidFormBody(msg) => (StatusCode::BAD_REQUEST, msg).into_response(),
+            RequestError::EncryptionError(msg) => {
+                (StatusCode::INTERNAL_SERVER_ERROR, msg).into_response()
             }
             RequestError::RequestNotFound => {
-                (StatusCode::NOT_FOUND, "Request not found").into_response()
+                (StatusCode::NOT_FOUND, i18n::t("error.request_not_found")).into_response()
             }
             RequestError::DatabaseError(_) => {
-                (StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response()
+                (StatusCode::INTERNAL_SERVER_ERROR, i18n::t("error.database")).into_response()
             }
         }
     }
 }
 
+fn validate_captures(captures: Option<&str>) -> Result<(), RequestError> {
+    match captures.filter(|c| !c.trim().is_empty()) {
+        Some(captures) => capture::parse_rules(captures).map(|_| ()).map_err(|e| {
+            log::warn!("{}", e);
+            RequestError::InvalidCaptures(e)
+        }),
+        None => Ok(()),
+    }
+}
+
+fn validate_ip_family(ip_family: Option<&str>) -> Result<(), RequestError> {
+    match ip_family {
+        Some(ip_family) => IpFamily::parse(ip_family).map(|_| ()).map_err(|e| {
+            log::warn!("{}", e);
+            RequestError::InvalidIpFamily(e)
+        }),
+        None => Ok(()),
+    }
+}
+
+/// Any RFC 7230 token is a method, so WebDAV's PROPFIND or a cache's PURGE
+/// are accepted as well as the standard ones.
+pub fn is_valid_method(method: &str) -> bool {
+    !method.is_empty()
+        && method
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "!#$%&'*+-.^_`|~".contains(c))
+}
+
+fn validate_callback(callback: Option<&str>) -> Result<(), RequestError> {
+    CallbackConfig::parse(callback).map(|_| ()).map_err(|e| {
+        log::warn!("{}", e);
+        RequestError::InvalidCallback(e)
+    })
+}
+
+/// An empty mode clears the request's own setting.
+fn validate_unresolved_mode(mode: Option<&str>) -> Result<(), RequestError> {
+    match mode.filter(|m| !m.is_empty()) {
+        Some(mode) => UnresolvedMode::parse(mode).map(|_| ()).map_err(|e| {
+            log::warn!("{}", e);
+            RequestError::InvalidUnresolvedMode(e)
+        }),
+        None => Ok(()),
+    }
+}
+
+fn validate_retry(retry: Option<&str>) -> Result<(), RequestError> {
+    RetryPolicy::parse(retry).map(|_| ()).map_err(|e| {
+        log::warn!("{}", e);
+        RequestError::InvalidRetry(e)
+    })
+}
+
+fn validate_compression(compression: Option<&str>) -> Result<(), RequestError> {
+    CompressionSettings::parse(compression)
+        .map(|_| ())
+        .map_err(|e| {
+            log::warn!("{}", e);
+            RequestError::InvalidCompression(e)
+        })
+}
+
+fn validate_assertions(assertions: Option<&str>) -> Result<(), RequestError> {
+    match assertions {
+        Some(assertions) => assertions::parse(assertions).map(|_| ()).map_err(|e| {
+            log::warn!("{}", e);
+            RequestError::InvalidAssertions(e)
+        }),
+        None => Ok(()),
+    }
+}
+
+fn validate_tls_versions(tls_versions: Option<&str>) -> Result<(), RequestError> {
+    TlsVersions::parse(tls_versions).map(|_| ()).map_err(|e| {
+        log::warn!("{}", e);
+        RequestError::InvalidTlsVersions(e)
+    })
+}
+
+async fn validate_ssh_tunnel(
+    pool: &DbPool,
+    ssh_tunnel_id: Option<i64>,
+) -> Result<(), RequestError> {
+    match ssh_tunnel_id.filter(|id| *id != 0) {
+        Some(id) if !ssh_tunnels::exists(pool, id).await? => {
+            log::warn!("SSH tunnel {} does not exist", id);
+            Err(RequestError::SshTunnelNotFound)
+        }
+        _ => Ok(()),
+    }
+}
+
+/// 0 stands for no budget, as in an update.
+fn validate_latency_budget(latency_budget_ms: Option<i64>) -> Result<(), RequestError> {
+    match latency_budget_ms {
+        Some(budget) if budget < 0 => {
+            log::warn!("Invalid latency budget {}", budget);
+            Err(RequestError::InvalidLatencyBudget)
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Form bodies are sent row by row, so they must parse as rows.
+fn validate_form_body(body_type: &str, body_content: Option<&str>) -> Result<(), RequestError> {
+    match body_content {
+        Some(content) if body_type == "form" => {
+            form_body::parse(content).map(|_| ()).map_err(|e| {
+                log::warn!("{}", e);
+                RequestError::InvalidFormBody(e)
+            })
+        }
+        _ => Ok(()),
+    }
+}
+
 async fn create_request(
     State(pool): State<DbPool>,
+    actor: Actor,
     Json(payload): Json<CreateRequest>,
 ) -> Result<impl IntoResponse, RequestError> {
     log::debug!(
@@ -192,19 +697,28 @@ async fn create_request(
     }
 
     // Only validate HTTP method for API requests, not for WebSocket
-    if payload.request_type != "ws" {
-        match payload.method.to_uppercase().as_str() {
-            "GET" | "POST" | "PUT" | "DELETE" | "PATCH" | "HEAD" | "OPTIONS" => (),
-            _ => {
-                log::warn!("Invalid HTTP method: {}", payload.method);
-                return Err(RequestError::InvalidMethod);
-            }
-        }
+    if payload.request_type != "ws" && !is_valid_method(&payload.method) {
+        log::warn!("Invalid HTTP method: {}", payload.method);
+        return Err(RequestError::InvalidMethod);
     }
+    validate_captures(payload.captures.as_deref())?;
+    validate_ip_family(Some(&payload.ip_family))?;
+    validate_callback(payload.callback.as_deref())?;
+    validate_unresolved_mode(payload.unresolved_variables.as_deref())?;
+    validate_retry(payload.retry.as_deref())?;
+    validate_compression(payload.compression.as_deref())?;
+    validate_assertions(payload.assertions.as_deref())?;
+    validate_tls_versions(payload.tls_versions.as_deref())?;
+    validate_ssh_tunnel(&pool, payload.ssh_tunnel_id).await?;
+    validate_latency_budget(payload.latency_budget_ms)?;
+    validate_form_body(&payload.body_type, payload.body_content.as_deref())?;
+    let auth_token = encrypt_credential(payload.auth_token.as_deref())?;
+    let auth_username = encrypt_credential(payload.auth_username.as_deref())?;
+    let auth_password = encrypt_credential(payload.auth_password.as_deref())?;
 
     let request_db = sqlx::query_as!(
         RequestDb,
-        "INSERT INTO requests (name, method, url, body, headers, folder_id, request_type, body_type, body_content, auth_type, auth_token, auth_username, auth_password) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?) RETURNING id, name, method, url, body, headers, folder_id, request_type, body_type, body_content, auth_type, auth_token, auth_username, auth_password, created_at, updated_at, archived_at",
+        "INSERT INTO requests (name, method, url, body, headers, folder_id, request_type, body_type, body_content, auth_type, auth_token, auth_username, auth_password, captures, auth_config, serialized, ip_family, pre_request_script, test_script, callback, unresolved_variables, retry, compression, assertions, description, tls_versions, ssh_tunnel_id, latency_budget_ms) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, NULLIF(?, 0)) RETURNING id, name, method, url, body, headers, folder_id, request_type, body_type, body_content, auth_type, auth_token, auth_username, auth_password, captures, auth_config, serialized, ip_family, pre_request_script, test_script, callback, unresolved_variables, retry, compression, assertions, description, tls_versions, ssh_tunnel_id, latency_budget_ms, created_at, updated_at, archived_at",
         payload.name,
         payload.method,
         payload.url,
@@ -215,12 +729,37 @@ async fn create_request(
         payload.body_type,
         payload.body_content,
         payload.auth_type,
-        payload.auth_token,
-        payload.auth_username,
-        payload.auth_password
+        auth_token,
+        auth_username,
+        auth_password,
+        payload.captures,
+        payload.auth_config,
+        payload.serialized,
+        payload.ip_family,
+        payload.pre_request_script,
+        payload.test_script,
+        payload.callback,
+        payload.unresolved_variables,
+        payload.retry,
+        payload.compression,
+        payload.assertions,
+        payload.description,
+        payload.tls_versions,
+        payload.ssh_tunnel_id,
+        payload.latency_budget_ms
     )
     .fetch_one(&pool)
     .await?;
+    audit::record(
+        &pool,
+        &actor,
+        Entity::Request,
+        request_db.id,
+        Action::Create,
+        None,
+        Some(&request_db),
+    )
+    .await;
 
     log::info!(
         "Created request: id={}, name={}, method={}",
@@ -236,52 +775,112 @@ async fn list_requests(
     Query(query): Query<ListRequestsQuery>,
 ) -> Result<impl IntoResponse, RequestError> {
     log::debug!(
-        "Listing requests: include_archived={}, folder_id={:?}",
+        "Listing requests: include_archived={}, folder_id={:?}, q={:?}",
         query.include_archived,
-        query.folder_id
+        query.folder_id,
+        query.q
     );
 
-    let requests_db = match (query.include_archived, query.folder_id) {
-        (false, None) => {
-            sqlx::query_as!(
-                RequestDb,
-                "SELECT id, name, method, url, body, headers, folder_id, request_type, body_type, body_content, auth_type, auth_token, auth_username, auth_password, created_at, updated_at, archived_at FROM requests WHERE archived_at IS NULL"
-            )
-            .fetch_all(&pool)
-            .await?
-        }
-        (true, None) => {
-            sqlx::query_as!(
-                RequestDb,
-                "SELECT id, name, method, url, body, headers, folder_id, request_type, body_type, body_content, auth_type, auth_token, auth_username, auth_password, created_at, updated_at, archived_at FROM requests"
-            )
-            .fetch_all(&pool)
-            .await?
-        }
-        (false, Some(folder_id)) => {
-            sqlx::query_as!(
-                RequestDb,
-                "SELECT id, name, method, url, body, headers, folder_id, request_type, body_type, body_content, auth_type, auth_token, auth_username, auth_password, created_at, updated_at, archived_at FROM requests WHERE archived_at IS NULL AND folder_id = ?",
-                folder_id
-            )
-            .fetch_all(&pool)
-            .await?
-        }
-        (true, Some(folder_id)) => {
-            sqlx::query_as!(
-                RequestDb,
-                "SELECT id, name, method, url, body, headers, folder_id, request_type, body_type, body_content, auth_type, auth_token, auth_username, auth_password, created_at, updated_at, archived_at FROM requests WHERE folder_id = ?",
-                folder_id
-            )
-            .fetch_all(&pool)
-            .await?
-        }
-    };
+    let method = query.method.as_deref().filter(|m| !m.is_empty());
+    let request_type = query.request_type.as_deref().filter(|t| !t.is_empty());
+    let q = query.q.as_deref().filter(|q| !q.is_empty());
+    let sort = query.sort.as_str();
+    let descending = matches!(query.order, SortOrder::Desc);
+    // SQLite treats a negative limit as no limit
+    let limit = query.limit.map_or(-1, |l| l.max(0));
+    let offset = query.offset.unwrap_or(0).max(0);
+
+    let total = sqlx::query_scalar!(
+        r#"SELECT COUNT(*) as "total!: i64" FROM requests
+        WHERE (? OR archived_at IS NULL)
+            AND (? IS NULL OR folder_id = ?)
+            AND (? IS NULL OR upper(method) = upper(?))
+            AND (? IS NULL OR request_type = ?)
+            AND (? IS NULL OR instr(lower(name), lower(?)) > 0 OR instr(lower(url), lower(?)) > 0)"#,
+        query.include_archived,
+        query.folder_id,
+        query.folder_id,
+        method,
+        method,
+        request_type,
+        request_type,
+        q,
+        q,
+        q
+    )
+    .fetch_one(&pool)
+    .await?;
+
+    let requests_db = sqlx::query_as!(
+        RequestDb,
+        r#"SELECT id, name, method, url, body, headers, folder_id, request_type, body_type, body_content, auth_type, auth_token, auth_username, auth_password, captures, auth_config, serialized, ip_family, pre_request_script, test_script, callback, unresolved_variables, retry, compression, assertions, description, tls_versions, ssh_tunnel_id, latency_budget_ms, created_at, updated_at, archived_at FROM requests
+        WHERE (? OR archived_at IS NULL)
+            AND (? IS NULL OR folder_id = ?)
+            AND (? IS NULL OR upper(method) = upper(?))
+            AND (? IS NULL OR request_type = ?)
+            AND (? IS NULL OR instr(lower(name), lower(?)) > 0 OR instr(lower(url), lower(?)) > 0)
+        ORDER BY
+            CASE WHEN ? = 'name' AND NOT ? THEN lower(name) END ASC,
+            CASE WHEN ? = 'name' AND ? THEN lower(name) END DESC,
+            CASE WHEN ? = 'updated_at' AND NOT ? THEN updated_at END ASC,
+            CASE WHEN ? = 'updated_at' AND ? THEN updated_at END DESC,
+            CASE WHEN ? THEN id END DESC,
+            id ASC
+        LIMIT ? OFFSET ?"#,
+        query.include_archived,
+        query.folder_id,
+        query.folder_id,
+        method,
+        method,
+        request_type,
+        request_type,
+        q,
+        q,
+        q,
+        sort,
+        descending,
+        sort,
+        descending,
+        sort,
+        descending,
+        sort,
+        descending,
+        descending,
+        limit,
+        offset
+    )
+    .fetch_all(&pool)
+    .await?;
 
     let requests: Vec<Request> = requests_db.into_iter().map(Request::from).collect();
-    log::debug!("Found {} requests", requests.len());
+    log::debug!("Found {} of {} requests", requests.len(), total);
+
+    Ok(([(TOTAL_COUNT_HEADER, total.to_string())], Json(requests)))
+}
+
+/// Loads a single request row, archived or not.
+pub async fn load_request(pool: &DbPool, id: i64) -> Result<RequestDb, sqlx::Error> {
+    sqlx::query_as!(
+        RequestDb,
+        "SELECT id, name, method, url, body, headers, folder_id, request_type, body_type, body_content, auth_type, auth_token, auth_username, auth_password, captures, auth_config, serialized, ip_family, pre_request_script, test_script, callback, unresolved_variables, retry, compression, assertions, description, tls_versions, ssh_tunnel_id, latency_budget_ms, created_at, updated_at, archived_at FROM requests WHERE id = ?",
+        id
+    )
+    .fetch_one(pool)
+    .await
+}
 
-    Ok(Json(requests))
+/// Loads the requests of a folder, archived or not.
+pub async fn load_folder_requests(
+    pool: &DbPool,
+    folder_id: i64,
+) -> Result<Vec<RequestDb>, sqlx::Error> {
+    sqlx::query_as!(
+        RequestDb,
+        "SELECT id, name, method, url, body, headers, folder_id, request_type, body_type, body_content, auth_type, auth_token, auth_username, auth_password, captures, auth_config, serialized, ip_family, pre_request_script, test_script, callback, unresolved_variables, retry, compression, assertions, description, tls_versions, ssh_tunnel_id, latency_budget_ms, created_at, updated_at, archived_at FROM requests WHERE folder_id = ?",
+        folder_id
+    )
+    .fetch_all(pool)
+    .await
 }
 
 async fn get_request(
@@ -292,7 +891,7 @@ async fn get_request(
 
     let request_db = sqlx::query_as!(
         RequestDb,
-        "SELECT id, name, method, url, body, headers, folder_id, request_type, body_type, body_content, auth_type, auth_token, auth_username, auth_password, created_at, updated_at, archived_at FROM requests WHERE id = ?",
+        "SELECT id, name, method, url, body, headers, folder_id, request_type, body_type, body_content, auth_type, auth_token, auth_username, auth_password, captures, auth_config, serialized, ip_family, pre_request_script, test_script, callback, unresolved_variables, retry, compression, assertions, description, tls_versions, ssh_tunnel_id, latency_budget_ms, created_at, updated_at, archived_at FROM requests WHERE id = ?",
         id
     )
     .fetch_one(&pool)
@@ -310,6 +909,7 @@ async fn get_request(
 async fn update_request(
     State(pool): State<DbPool>,
     Path(id): Path<i64>,
+    actor: Actor,
     Json(payload): Json<UpdateRequest>,
 ) -> Result<impl IntoResponse, RequestError> {
     log::debug!(
@@ -327,19 +927,29 @@ async fn update_request(
     }
 
     // Only validate HTTP method for API requests, not for WebSocket
-    if payload.request_type != "ws" {
-        match payload.method.to_uppercase().as_str() {
-            "GET" | "POST" | "PUT" | "DELETE" | "PATCH" | "HEAD" | "OPTIONS" => (),
-            _ => {
-                log::warn!("Invalid HTTP method for request {}: {}", id, payload.method);
-                return Err(RequestError::InvalidMethod);
-            }
-        }
+    if payload.request_type != "ws" && !is_valid_method(&payload.method) {
+        log::warn!("Invalid HTTP method for request {}: {}", id, payload.method);
+        return Err(RequestError::InvalidMethod);
     }
-
+    validate_captures(payload.captures.as_deref())?;
+    validate_ip_family(payload.ip_family.as_deref())?;
+    validate_callback(payload.callback.as_deref())?;
+    validate_unresolved_mode(payload.unresolved_variables.as_deref())?;
+    validate_retry(payload.retry.as_deref())?;
+    validate_compression(payload.compression.as_deref())?;
+    validate_assertions(payload.assertions.as_deref())?;
+    validate_tls_versions(payload.tls_versions.as_deref())?;
+    validate_ssh_tunnel(&pool, payload.ssh_tunnel_id).await?;
+    validate_latency_budget(payload.latency_budget_ms)?;
+    validate_form_body(&payload.body_type, payload.body_content.as_deref())?;
+    let auth_token = encrypt_credential(payload.auth_token.as_deref())?;
+    let auth_username = encrypt_credential(payload.auth_username.as_deref())?;
+    let auth_password = encrypt_credential(payload.auth_password.as_deref())?;
+
+    let before = load_request(&pool, id).await?;
     let request_db = sqlx::query_as!(
         RequestDb,
-        "UPDATE requests SET name = ?, method = ?, url = ?, body = ?, headers = ?, folder_id = ?, request_type = ?, body_type = ?, body_content = ?, auth_type = ?, auth_token = ?, auth_username = ?, auth_password = ?, updated_at = CURRENT_TIMESTAMP WHERE id = ? RETURNING id, name, method, url, body, headers, folder_id, request_type, body_type, body_content, auth_type, auth_token, auth_username, auth_password, created_at, updated_at, archived_at",
+        "UPDATE requests SET name = ?, method = ?, url = ?, body = ?, headers = ?, folder_id = ?, request_type = ?, body_type = ?, body_content = ?, auth_type = ?, auth_token = ?, auth_username = ?, auth_password = ?, captures = CASE WHEN ? IS NULL THEN captures ELSE NULLIF(?, '') END, auth_config = CASE WHEN ? IS NULL THEN auth_config ELSE NULLIF(?, '') END, serialized = COALESCE(?, serialized), ip_family = COALESCE(?, ip_family), pre_request_script = CASE WHEN ? IS NULL THEN pre_request_script ELSE NULLIF(?, '') END, test_script = CASE WHEN ? IS NULL THEN test_script ELSE NULLIF(?, '') END, callback = CASE WHEN ? IS NULL THEN callback ELSE NULLIF(?, '') END, unresolved_variables = CASE WHEN ? IS NULL THEN unresolved_variables ELSE NULLIF(?, '') END, retry = CASE WHEN ? IS NULL THEN retry ELSE NULLIF(?, '') END, compression = CASE WHEN ? IS NULL THEN compression ELSE NULLIF(?, '') END, assertions = CASE WHEN ? IS NULL THEN assertions ELSE NULLIF(?, '') END, description = CASE WHEN ? IS NULL THEN description ELSE NULLIF(?, '') END, tls_versions = CASE WHEN ? IS NULL THEN tls_versions ELSE NULLIF(?, '') END, ssh_tunnel_id = CASE WHEN ? IS NULL THEN ssh_tunnel_id ELSE NULLIF(?, 0) END, latency_budget_ms = CASE WHEN ? IS NULL THEN latency_budget_ms ELSE NULLIF(?, 0) END, updated_at = CURRENT_TIMESTAMP WHERE id = ? RETURNING id, name, method, url, body, headers, folder_id, request_type, body_type, body_content, auth_type, auth_token, auth_username, auth_password, captures, auth_config, serialized, ip_family, pre_request_script, test_script, callback, unresolved_variables, retry, compression, assertions, description, tls_versions, ssh_tunnel_id, latency_budget_ms, created_at, updated_at, archived_at",
         payload.name,
         payload.method,
         payload.url,
@@ -350,13 +960,51 @@ async fn update_request(
         payload.body_type,
         payload.body_content,
         payload.auth_type,
-        payload.auth_token,
-        payload.auth_username,
-        payload.auth_password,
+        auth_token,
+        auth_username,
+        auth_password,
+        payload.captures,
+        payload.captures,
+        payload.auth_config,
+        payload.auth_config,
+        payload.serialized,
+        payload.ip_family,
+        payload.pre_request_script,
+        payload.pre_request_script,
+        payload.test_script,
+        payload.test_script,
+        payload.callback,
+        payload.callback,
+        payload.unresolved_variables,
+        payload.unresolved_variables,
+        payload.retry,
+        payload.retry,
+        payload.compression,
+        payload.compression,
+        payload.assertions,
+        payload.assertions,
+        payload.description,
+        payload.description,
+        payload.tls_versions,
+        payload.tls_versions,
+        payload.ssh_tunnel_id,
+        payload.ssh_tunnel_id,
+        payload.latency_budget_ms,
+        payload.latency_budget_ms,
         id
     )
     .fetch_one(&pool)
     .await?;
+    audit::record(
+        &pool,
+        &actor,
+        Entity::Request,
+        id,
+        Action::Update,
+        Some(&before),
+        Some(&request_db),
+    )
+    .await;
 
     log::info!(
         "Updated request: id={}, name={}, method={}",
@@ -367,12 +1015,57 @@ async fn update_request(
     Ok(Json(Request::from(request_db)))
 }
 
+pub async fn patch_request(
+    State(pool): State<DbPool>,
+    Path(id): Path<i64>,
+    actor: Actor,
+    Json(payload): Json<PatchRequest>,
+) -> Result<impl IntoResponse, RequestError> {
+    // Stored credentials are already encrypted and pass through as they are
+    let stored = load_request(&pool, id).await?;
+    // The full update keeps a field on null and clears it on an empty string
+    let clear = |field: Option<Option<String>>| field.map(Option::unwrap_or_default);
+    let update = UpdateRequest {
+        name: payload.name.unwrap_or(stored.name),
+        method: payload.method.unwrap_or(stored.method),
+        url: payload.url.unwrap_or(stored.url),
+        body: payload.body.unwrap_or(stored.body),
+        headers: payload.headers.unwrap_or(stored.headers),
+        folder_id: payload.folder_id.unwrap_or(stored.folder_id),
+        request_type: payload.request_type.unwrap_or(stored.request_type),
+        body_type: payload.body_type.unwrap_or(stored.body_type),
+        body_content: payload.body_content.unwrap_or(stored.body_content),
+        auth_type: payload.auth_type.unwrap_or(stored.auth_type),
+        auth_token: payload.auth_token.unwrap_or(stored.auth_token),
+        auth_username: payload.auth_username.unwrap_or(stored.auth_username),
+        auth_password: payload.auth_password.unwrap_or(stored.auth_password),
+        captures: clear(payload.captures),
+        auth_config: clear(payload.auth_config),
+        serialized: payload.serialized,
+        ip_family: payload.ip_family,
+        pre_request_script: clear(payload.pre_request_script),
+        test_script: clear(payload.test_script),
+        callback: clear(payload.callback),
+        unresolved_variables: clear(payload.unresolved_variables),
+        retry: clear(payload.retry),
+        compression: clear(payload.compression),
+        assertions: clear(payload.assertions),
+        description: clear(payload.description),
+        tls_versions: clear(payload.tls_versions),
+        ssh_tunnel_id: payload.ssh_tunnel_id.map(|id| id.unwrap_or(0)),
+        latency_budget_ms: payload.latency_budget_ms.map(|ms| ms.unwrap_or(0)),
+    };
+    update_request(State(pool), Path(id), actor, Json(update)).await
+}
+
 async fn archive_request(
     State(pool): State<DbPool>,
     Path(id): Path<i64>,
+    actor: Actor,
 ) -> Result<impl IntoResponse, RequestError> {
     log::debug!("Archiving request id: {}", id);
 
+    let before = audit::found(load_request(&pool, id).await)?;
     let now = Utc::now().naive_utc();
     let result = sqlx::query("UPDATE requests SET archived_at = ? WHERE id = ?")
         .bind(now)
@@ -384,6 +1077,7 @@ async fn archive_request(
         log::warn!("Request not found for archiving: id={}", id);
         return Err(RequestError::RequestNotFound);
     }
+    audit_state_change(&pool, &actor, id, Action::Archive, before).await;
 
     log::info!("Archived request: id={}", id);
     Ok(StatusCode::OK)
@@ -392,9 +1086,11 @@ async fn archive_request(
 async fn unarchive_request(
     State(pool): State<DbPool>,
     Path(id): Path<i64>,
+    actor: Actor,
 ) -> Result<impl IntoResponse, RequestError> {
     log::debug!("Unarchiving request id: {}", id);
 
+    let before = audit::found(load_request(&pool, id).await)?;
     let result = sqlx::query("UPDATE requests SET archived_at = NULL WHERE id = ?")
         .bind(id)
         .execute(&pool)
@@ -404,6 +1100,7 @@ async fn unarchive_request(
         log::warn!("Request not found for unarchiving: id={}", id);
         return Err(RequestError::RequestNotFound);
     }
+    audit_state_change(&pool, &actor, id, Action::Unarchive, before).await;
 
     log::info!("Unarchived request: id={}", id);
     Ok(StatusCode::OK)
@@ -412,9 +1109,11 @@ async fn unarchive_request(
 async fn delete_request(
     State(pool): State<DbPool>,
     Path(id): Path<i64>,
+    actor: Actor,
 ) -> Result<impl IntoResponse, RequestError> {
     log::debug!("Deleting request id: {}", id);
 
+    let before = audit::found(load_request(&pool, id).await)?;
     let result = sqlx::query("DELETE FROM requests WHERE id = ?")
         .bind(id)
         .execute(&pool)
@@ -424,17 +1123,51 @@ async fn delete_request(
         log::warn!("Request not found for deletion: id={}", id);
         return Err(RequestError::RequestNotFound);
     }
+    audit::record(
+        &pool,
+        &actor,
+        Entity::Request,
+        id,
+        Action::Delete,
+        before.as_ref(),
+        None,
+    )
+    .await;
 
     log::info!("Deleted request: id={}", id);
     Ok(StatusCode::NO_CONTENT)
 }
 
+/// Records an archive or unarchive with the row as it was and is now.
+async fn audit_state_change(
+    pool: &DbPool,
+    actor: &Actor,
+    id: i64,
+    action: Action,
+    before: Option<RequestDb>,
+) {
+    let after = audit::found(load_request(pool, id).await).ok().flatten();
+    audit::record(
+        pool,
+        actor,
+        Entity::Request,
+        id,
+        action,
+        before.as_ref(),
+        after.as_ref(),
+    )
+    .await;
+}
+
 pub fn routes(pool: DbPool) -> Router {
     Router::new()
         .route("/requests", post(create_request).get(list_requests))
         .route(
             "/requests/:id",
-            get(get_request).put(update_request).delete(delete_request),
+            get(get_request)
+                .put(update_request)
+                .patch(patch_request)
+                .delete(delete_request),
         )
         .route("/requests/:id/archive", put(archive_request))
         .route("/requests/:id/unarchive", put(unarchive_request))
@@ -452,7 +1185,7 @@ mod tests {
     async fn create_test_request(pool: &DbPool, req: &CreateRequest) -> RequestDb {
         sqlx::query_as!(
             RequestDb,
-            "INSERT INTO requests (name, method, url, body, headers, folder_id, request_type, body_type, body_content, auth_type, auth_token, auth_username, auth_password) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?) RETURNING id, name, method, url, body, headers, folder_id, request_type, body_type, body_content, auth_type, auth_token, auth_username, auth_password, created_at, updated_at, archived_at",
+            "INSERT INTO requests (name, method, url, body, headers, folder_id, request_type, body_type, body_content, auth_type, auth_token, auth_username, auth_password, captures, auth_config, serialized, ip_family, pre_request_script, test_script, callback, unresolved_variables, retry, compression, assertions, description, tls_versions, ssh_tunnel_id, latency_budget_ms) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?) RETURNING id, name, method, url, body, headers, folder_id, request_type, body_type, body_content, auth_type, auth_token, auth_username, auth_password, captures, auth_config, serialized, ip_family, pre_request_script, test_script, callback, unresolved_variables, retry, compression, assertions, description, tls_versions, ssh_tunnel_id, latency_budget_ms, created_at, updated_at, archived_at",
             req.name,
             req.method,
             req.url,
@@ -465,7 +1198,22 @@ mod tests {
             req.auth_type,
             req.auth_token,
             req.auth_username,
-            req.auth_password
+            req.auth_password,
+            req.captures,
+            req.auth_config,
+            req.serialized,
+            req.ip_family,
+            req.pre_request_script,
+            req.test_script,
+            req.callback,
+            req.unresolved_variables,
+            req.retry,
+            req.compression,
+            req.assertions,
+            req.description,
+            req.tls_versions,
+            req.ssh_tunnel_id,
+            req.latency_budget_ms
         )
         .fetch_one(pool)
         .await
@@ -492,6 +1240,137 @@ mod tests {
         response.assert_status(StatusCode::CREATED);
     }
 
+    #[tokio::test]
+    async fn test_credentials_stored_encrypted() {
+        let pool = db::create_test_pool().await;
+        let server = TestServer::new(routes(pool.clone())).unwrap();
+
+        let created: Request = server
+            .post("/requests")
+            .json(&json!({
+                "name": "Account",
+                "method": "GET",
+                "url": "http://example.com",
+                "auth_type": "basic",
+                "auth_username": "alice",
+                "auth_password": "s3cret",
+                "auth_token": ""
+            }))
+            .await
+            .json();
+        assert_eq!(created.auth_password.as_deref(), Some("s3cret"));
+
+        let stored = sqlx::query!(
+            "SELECT auth_token, auth_username, auth_password FROM requests WHERE id = ?",
+            created.id
+        )
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+        assert!(crypto::is_encrypted(
+            stored.auth_password.as_deref().unwrap()
+        ));
+        assert!(crypto::is_encrypted(
+            stored.auth_username.as_deref().unwrap()
+        ));
+        assert_eq!(stored.auth_token.as_deref(), Some(""));
+
+        let fetched: Request = server
+            .get(&format!("/requests/{}", created.id))
+            .await
+            .json();
+        assert_eq!(fetched.auth_username.as_deref(), Some("alice"));
+
+        // Rows written before encryption are encrypted once on startup
+        sqlx::query("INSERT INTO requests (name, method, url, auth_type, auth_token) VALUES ('Legacy', 'GET', 'http://example.com', 'bearer', 'tok')")
+            .execute(&pool)
+            .await
+            .unwrap();
+        assert_eq!(encrypt_stored_credentials(&pool).await.unwrap(), 1);
+        assert_eq!(encrypt_stored_credentials(&pool).await.unwrap(), 0);
+        let (token,): (String,) =
+            sqlx::query_as("SELECT auth_token FROM requests WHERE name = 'Legacy'")
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+        assert_eq!(crypto::decrypt(&token).unwrap(), "tok");
+
+        // Credentials that cannot be decrypted are left out, not echoed
+        sqlx::query("UPDATE requests SET auth_password = 'enc:v1:AAAA' WHERE id = ?")
+            .bind(created.id)
+            .execute(&pool)
+            .await
+            .unwrap();
+        let fetched: Request = server
+            .get(&format!("/requests/{}", created.id))
+            .await
+            .json();
+        assert_eq!(fetched.auth_username.as_deref(), Some("alice"));
+        assert_eq!(fetched.auth_password, None);
+    }
+
+    #[test]
+    fn test_is_valid_method() {
+        for method in ["GET", "PROPFIND", "PURGE", "LINK", "M-SEARCH", "report"] {
+            assert!(is_valid_method(method), "{}", method);
+        }
+        for method in ["", "GET /v1", "PUT\r\n", "(GET)", "MÉTHODE"] {
+            assert!(!is_valid_method(method), "{}", method);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_patch_request() {
+        let pool = db::create_test_pool().await;
+        let server = TestServer::new(routes(pool.clone())).unwrap();
+
+        let created: Request = server
+            .post("/requests")
+            .json(&json!({
+                "name": "Account",
+                "method": "GET",
+                "url": "http://example.com",
+                "body": "{}",
+                "auth_type": "bearer",
+                "auth_token": "s3cret",
+                "test_script": "jsl.test('ok', () => true)"
+            }))
+            .await
+            .json();
+
+        let patched: Request = server
+            .patch(&format!("/requests/{}", created.id))
+            .json(&json!({ "name": "Renamed" }))
+            .await
+            .json();
+        assert_eq!(patched.name, "Renamed");
+        assert_eq!(patched.url, "http://example.com");
+        assert_eq!(patched.body.as_deref(), Some("{}"));
+        assert_eq!(patched.auth_token.as_deref(), Some("s3cret"));
+        assert!(patched.test_script.is_some());
+
+        let patched: Request = server
+            .patch(&format!("/requests/{}", created.id))
+            .json(&json!({ "body": null, "test_script": null, "method": "POST" }))
+            .await
+            .json();
+        assert_eq!(patched.method, "POST");
+        assert_eq!(patched.body, None);
+        assert_eq!(patched.test_script, None);
+        assert_eq!(patched.auth_token.as_deref(), Some("s3cret"));
+
+        server
+            .patch(&format!("/requests/{}", created.id))
+            .json(&json!({ "method": "NOT VALID" }))
+            .await
+            .assert_status(StatusCode::BAD_REQUEST);
+        server
+            .patch("/requests/999")
+            .json(&json!({ "name": "Missing" }))
+            .await
+            .assert_status(StatusCode::NOT_FOUND);
+    }
+
     #[tokio::test]
     async fn test_create_request_invalid_method() {
         let pool = db::create_test_pool().await;
@@ -501,7 +1380,7 @@ mod tests {
             .post("/requests")
             .json(&json!({
                 "name": "New Request",
-                "method": "INVALID",
+                "method": "GET /v1",
                 "url": "http://example.com",
                 "body": null,
                 "headers": null,
@@ -549,6 +1428,21 @@ mod tests {
             auth_token: None,
             auth_username: None,
             auth_password: None,
+            captures: None,
+            auth_config: None,
+            serialized: false,
+            ip_family: "auto".to_string(),
+            pre_request_script: None,
+            test_script: None,
+            callback: None,
+            unresolved_variables: None,
+            retry: None,
+            compression: None,
+            assertions: None,
+            description: None,
+            tls_versions: None,
+            ssh_tunnel_id: None,
+            latency_budget_ms: None,
         };
         create_test_request(&pool, &req1).await;
         let server = TestServer::new(routes(pool.clone())).unwrap();
@@ -560,6 +1454,41 @@ mod tests {
         assert_eq!(requests.len(), 1);
     }
 
+    #[tokio::test]
+    async fn test_list_requests_paginated() {
+        let pool = db::create_test_pool().await;
+        let server = TestServer::new(routes(pool.clone())).unwrap();
+        for (name, method, url) in [
+            ("Orders", "GET", "http://shop.test/orders"),
+            ("create order", "POST", "http://shop.test/orders"),
+            ("Users", "GET", "http://auth.test/users"),
+            ("Health", "GET", "http://shop.test/health"),
+        ] {
+            server
+                .post("/requests")
+                .json(&json!({ "name": name, "method": method, "url": url }))
+                .await
+                .assert_status(StatusCode::CREATED);
+        }
+        let names = |requests: Vec<Request>| -> Vec<String> {
+            requests.into_iter().map(|r| r.name).collect()
+        };
+
+        let response = server.get("/requests?sort=name&limit=2").await;
+        assert_eq!(response.header(TOTAL_COUNT_HEADER), "4");
+        assert_eq!(names(response.json()), ["create order", "Health"]);
+        let response = server.get("/requests?sort=name&limit=2&offset=2").await;
+        assert_eq!(names(response.json()), ["Orders", "Users"]);
+        let response = server.get("/requests?sort=name&order=desc&limit=1").await;
+        assert_eq!(names(response.json()), ["Users"]);
+
+        let response = server.get("/requests?method=get&q=SHOP.test").await;
+        assert_eq!(response.header(TOTAL_COUNT_HEADER), "2");
+        assert_eq!(names(response.json()), ["Orders", "Health"]);
+        let response = server.get("/requests?q=order&request_type=ws").await;
+        assert_eq!(response.header(TOTAL_COUNT_HEADER), "0");
+    }
+
     #[tokio::test]
     async fn test_list_requests_include_archived() {
         let pool = db::create_test_pool().await;
@@ -577,6 +1506,21 @@ mod tests {
             auth_token: None,
             auth_username: None,
             auth_password: None,
+            captures: None,
+            auth_config: None,
+            serialized: false,
+            ip_family: "auto".to_string(),
+            pre_request_script: None,
+            test_script: None,
+            callback: None,
+            unresolved_variables: None,
+            retry: None,
+            compression: None,
+            assertions: None,
+            description: None,
+            tls_versions: None,
+            ssh_tunnel_id: None,
+            latency_budget_ms: None,
         };
         let req2 = create_test_request(&pool, &req1).await;
         sqlx::query("UPDATE requests SET archived_at = ? WHERE id = ?")
@@ -623,6 +1567,21 @@ mod tests {
             auth_token: None,
             auth_username: None,
             auth_password: None,
+            captures: None,
+            auth_config: None,
+            serialized: false,
+            ip_family: "auto".to_string(),
+            pre_request_script: None,
+            test_script: None,
+            callback: None,
+            unresolved_variables: None,
+            retry: None,
+            compression: None,
+            assertions: None,
+            description: None,
+            tls_versions: None,
+            ssh_tunnel_id: None,
+            latency_budget_ms: None,
         };
         let request = create_test_request(&pool, &req1).await;
         let server = TestServer::new(routes(pool.clone())).unwrap();
@@ -671,6 +1630,21 @@ mod tests {
             auth_token: None,
             auth_username: None,
             auth_password: None,
+            captures: None,
+            auth_config: None,
+            serialized: false,
+            ip_family: "auto".to_string(),
+            pre_request_script: None,
+            test_script: None,
+            callback: None,
+            unresolved_variables: None,
+            retry: None,
+            compression: None,
+            assertions: None,
+            description: None,
+            tls_versions: None,
+            ssh_tunnel_id: None,
+            latency_budget_ms: None,
         };
         let request_db = create_test_request(&pool, &req).await;
         let server = TestServer::new(routes(pool.clone())).unwrap();
@@ -734,6 +1708,21 @@ mod tests {
             auth_token: None,
             auth_username: None,
             auth_password: None,
+            captures: None,
+            auth_config: None,
+            serialized: false,
+            ip_family: "auto".to_string(),
+            pre_request_script: None,
+            test_script: None,
+            callback: None,
+            unresolved_variables: None,
+            retry: None,
+            compression: None,
+            assertions: None,
+            description: None,
+            tls_versions: None,
+            ssh_tunnel_id: None,
+            latency_budget_ms: None,
         };
         let request_db = create_test_request(&pool, &req).await;
         let server = TestServer::new(routes(pool.clone())).unwrap();
@@ -770,6 +1759,21 @@ mod tests {
             auth_token: None,
             auth_username: None,
             auth_password: None,
+            captures: None,
+            auth_config: None,
+            serialized: false,
+            ip_family: "auto".to_string(),
+            pre_request_script: None,
+            test_script: None,
+            callback: None,
+            unresolved_variables: None,
+            retry: None,
+            compression: None,
+            assertions: None,
+            description: None,
+            tls_versions: None,
+            ssh_tunnel_id: None,
+            latency_budget_ms: None,
         };
         let request_db = create_test_request(&pool, &req).await;
         let server = TestServer::new(routes(pool.clone())).unwrap();
@@ -778,7 +1782,7 @@ mod tests {
             .put(&format!("/requests/{}", request_db.id))
             .json(&json!({
                 "name": "new name",
-                "method": "INVALID",
+                "method": "GET /v1",
                 "url": "http://new.com",
                 "body": "new body",
                 "headers": "new headers",
@@ -806,6 +1810,21 @@ mod tests {
             auth_token: None,
             auth_username: None,
             auth_password: None,
+            captures: None,
+            auth_config: None,
+            serialized: false,
+            ip_family: "auto".to_string(),
+            pre_request_script: None,
+            test_script: None,
+            callback: None,
+            unresolved_variables: None,
+            retry: None,
+            compression: None,
+            assertions: None,
+            description: None,
+            tls_versions: None,
+            ssh_tunnel_id: None,
+            latency_budget_ms: None,
         };
         let request_db = create_test_request(&pool, &req).await;
         let server = TestServer::new(routes(pool.clone())).unwrap();
@@ -847,6 +1866,21 @@ mod tests {
             auth_token: None,
             auth_username: None,
             auth_password: None,
+            captures: None,
+            auth_config: None,
+            serialized: false,
+            ip_family: "auto".to_string(),
+            pre_request_script: None,
+            test_script: None,
+            callback: None,
+            unresolved_variables: None,
+            retry: None,
+            compression: None,
+            assertions: None,
+            description: None,
+            tls_versions: None,
+            ssh_tunnel_id: None,
+            latency_budget_ms: None,
         };
         let request_db = create_test_request(&pool, &req).await;
         sqlx::query("UPDATE requests SET archived_at = ? WHERE id = ?")
@@ -894,6 +1928,21 @@ mod tests {
             auth_token: None,
             auth_username: None,
             auth_password: None,
+            captures: None,
+            auth_config: None,
+            serialized: false,
+            ip_family: "auto".to_string(),
+            pre_request_script: None,
+            test_script: None,
+            callback: None,
+            unresolved_variables: None,
+            retry: None,
+            compression: None,
+            assertions: None,
+            description: None,
+            tls_versions: None,
+            ssh_tunnel_id: None,
+            latency_budget_ms: None,
         };
         let request_db = create_test_request(&pool, &req).await;
         let server = TestServer::new(routes(pool.clone())).unwrap();
@@ -0,0 +1,264 @@
+use axum::{
+    extract::{Path, Query, State},
+    routing::post,
+    Json, Router,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+use crate::{
+    db::DbPool,
+    environments::{self, SECRET_MASK},
+    executor::{self, ExecutorError},
+    folders, form_body, header_rows,
+    requests::{Request, RequestDb},
+};
+
+// Preview of what executing a saved request would send: every field with
+// its variables substituted, without running scripts or sending anything.
+
+#[derive(Deserialize)]
+pub struct ResolveQuery {
+    environment_id: Option<i64>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ResolvedAuth {
+    pub auth_type: String,
+    pub token: Option<String>,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    pub config: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ResolvedRequest {
+    pub method: String,
+    pub url: String,
+    pub headers: HashMap<String, String>,
+    pub body: Option<String>,
+    pub auth: ResolvedAuth,
+    /// Placeholders no variable or generator matched
+    pub unresolved: Vec<String>,
+}
+
+/// Names of the secret variables the environment, or those it inherits
+/// from, provide.
+async fn secret_names(
+    pool: &DbPool,
+    environment_id: Option<i64>,
+) -> Result<HashSet<String>, sqlx::Error> {
+    let Some(environment_id) = environment_id else {
+        return Ok(HashSet::new());
+    };
+    let mut secrets = HashSet::new();
+    for environment in environments::load_chain(pool, environment_id)
+        .await?
+        .into_iter()
+        .rev()
+    {
+        let flagged = environment.secret_names();
+        let disabled = environment.disabled_names();
+        let variables: HashMap<String, serde_json::Value> =
+            serde_json::from_str(&environment.variables).unwrap_or_default();
+        for name in variables.into_keys().filter(|n| !disabled.contains(n)) {
+            if flagged.contains(&name) {
+                secrets.insert(name);
+            } else {
+                secrets.remove(&name);
+            }
+        }
+    }
+    Ok(secrets)
+}
+
+async fn resolve_request(
+    State(pool): State<DbPool>,
+    Path(id): Path<i64>,
+    Query(query): Query<ResolveQuery>,
+) -> Result<Json<ResolvedRequest>, ExecutorError> {
+    let request_db = sqlx::query_as!(
+        RequestDb,
+        "SELECT id, name, method, url, body, headers, folder_id, request_type, body_type, body_content, auth_type, auth_token, auth_username, auth_password, captures, auth_config, serialized, ip_family, pre_request_script, test_script, callback, unresolved_variables, retry, compression, assertions, description, tls_versions, ssh_tunnel_id, latency_budget_ms, created_at, updated_at, archived_at FROM requests WHERE id = ?",
+        id
+    )
+    .fetch_one(&pool)
+    .await?;
+    let mut request = Request::from(request_db);
+    // Relative URLs are resolved against the folder's base URL, as on execute
+    let base_url = folders::load_base_url(&pool, request.folder_id).await?;
+    request.url = folders::join_base_url(base_url.as_deref(), &request.url);
+
+    let mut variables =
+        executor::load_variables(&pool, request.folder_id, query.environment_id).await?;
+    for name in secret_names(&pool, query.environment_id).await? {
+        variables.insert(name, SECRET_MASK.to_string());
+    }
+
+    let mut unresolved = Vec::new();
+    let mut resolve = |text: Option<&str>| {
+        text.map(|text| {
+            let result = executor::replace_placeholders(text, &variables);
+            for name in executor::unresolved_names(&result) {
+                if !unresolved.contains(&name) {
+                    unresolved.push(name);
+                }
+            }
+            result
+        })
+    };
+
+    let url = resolve(Some(&request.url)).unwrap_or_default();
+    let rows = request
+        .headers
+        .as_deref()
+        .and_then(|h| header_rows::parse(h).ok())
+        .unwrap_or_default();
+    let headers = header_rows::enabled_map(&rows)
+        .into_iter()
+        .map(|(key, value)| {
+            (
+                resolve(Some(&key)).unwrap_or_default(),
+                resolve(Some(&value)).unwrap_or_default(),
+            )
+        })
+        .collect();
+    // Enabled form rows are substituted and url-encoded in order, other body
+    // content is sent as stored, like the executor does
+    let body = match request.body_content.as_deref() {
+        Some(content) if request.body_type == "form" => {
+            let mut rows = form_body::parse(content).map_err(ExecutorError::SubstitutionError)?;
+            for row in rows.iter_mut().filter(|row| row.enabled) {
+                row.key = resolve(Some(&row.key)).unwrap_or_default();
+                row.value = resolve(Some(&row.value)).unwrap_or_default();
+            }
+            Some(form_body::encode(&rows))
+        }
+        Some(content) => Some(content.to_string()),
+        None => resolve(request.body.as_deref()),
+    };
+    let auth = ResolvedAuth {
+        token: resolve(request.auth_token.as_deref()),
+        username: resolve(request.auth_username.as_deref()),
+        password: resolve(request.auth_password.as_deref()),
+        config: resolve(request.auth_config.as_deref()),
+        auth_type: request.auth_type,
+    };
+
+    Ok(Json(ResolvedRequest {
+        method: request.method,
+        url,
+        headers,
+        body,
+        auth,
+        unresolved,
+    }))
+}
+
+pub fn routes(pool: DbPool) -> Router {
+    Router::new()
+        .route("/requests/:id/resolve", post(resolve_request))
+        .with_state(pool)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db;
+    use axum::http::StatusCode;
+    use axum_test::TestServer;
+
+    #[tokio::test]
+    async fn test_resolve_request() {
+        let pool = db::create_test_pool().await;
+        let variables = serde_json::json!({
+            "base": "https://dev",
+            "token": crate::crypto::encrypt("s3cret").unwrap(),
+        })
+        .to_string();
+        let environment_id = sqlx::query_scalar!(
+            r#"INSERT INTO environments (name, variables, secrets) VALUES ('Dev', ?, '["token"]') RETURNING id"#,
+            variables
+        )
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+        let request_id = sqlx::query_scalar!(
+            r#"INSERT INTO requests (name, method, url, headers, body, auth_type, auth_token) VALUES ('Orders', 'POST', '{{base}}/orders/{{order_id}}', '{"X-Trace":"{{$uuid}}"}', '{"tenant":"{{tenant}}"}', 'bearer', '{{token}}') RETURNING id"#
+        )
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+        let server = TestServer::new(routes(pool)).unwrap();
+
+        let resolved: ResolvedRequest = server
+            .post(&format!(
+                "/requests/{}/resolve?environment_id={}",
+                request_id, environment_id
+            ))
+            .await
+            .json();
+        assert_eq!(resolved.url, "https://dev/orders/{{order_id}}");
+        assert_eq!(resolved.headers["X-Trace"].len(), 36);
+        assert_eq!(resolved.auth.token.as_deref(), Some(SECRET_MASK));
+        assert_eq!(resolved.unresolved, vec!["order_id", "tenant"]);
+
+        server
+            .post("/requests/999/resolve")
+            .await
+            .assert_status(StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_resolve_request_in_folder_with_base_url() {
+        let pool = db::create_test_pool().await;
+        let folder_id: i64 = sqlx::query_scalar(
+            r#"INSERT INTO folders (name, variables, base_url) VALUES ('Users', '{"version":"v2"}', 'https://api/{{version}}') RETURNING id"#,
+        )
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+        let request_id: i64 = sqlx::query_scalar(
+            "INSERT INTO requests (name, method, url, folder_id) VALUES ('List users', 'GET', '/users', ?) RETURNING id",
+        )
+        .bind(folder_id)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+        let server = TestServer::new(routes(pool)).unwrap();
+
+        let resolved: ResolvedRequest = server
+            .post(&format!("/requests/{}/resolve", request_id))
+            .await
+            .json();
+        assert_eq!(resolved.url, "https://api/v2/users");
+        assert!(resolved.unresolved.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_resolve_form_body() {
+        let pool = db::create_test_pool().await;
+        let request_id: i64 = sqlx::query_scalar(
+            r#"INSERT INTO requests (name, method, url, body_type, body_content) VALUES ('Login', 'POST', 'https://auth/login', 'form', '[{"key":"user","value":"{{user}}"},{"key":"code","value":"{{code}}"},{"key":"debug","value":"{{debug}}","enabled":false}]') RETURNING id"#,
+        )
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+        sqlx::query("INSERT INTO global_variables (name, value) VALUES ('user', 'a&b')")
+            .execute(&pool)
+            .await
+            .unwrap();
+        let server = TestServer::new(routes(pool)).unwrap();
+
+        let resolved: ResolvedRequest = server
+            .post(&format!("/requests/{}/resolve", request_id))
+            .await
+            .json();
+        assert_eq!(
+            resolved.body.as_deref(),
+            Some("user=a%26b&code=%7B%7Bcode%7D%7D")
+        );
+        // Disabled rows are not sent, so their placeholders are not reported
+        assert_eq!(resolved.unresolved, vec!["code"]);
+    }
+}
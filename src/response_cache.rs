@@ -0,0 +1,496 @@
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    routing::{delete, get},
+    Json, Router,
+};
+use chrono::{DateTime, NaiveDateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+
+use crate::{db::DbPool, i18n};
+
+const SETTING: &str = "response_cache";
+/// Larger bodies are not cached.
+const MAX_CACHED_BODY: usize = 1024 * 1024;
+/// Headers describing the body as it came over the wire. The cache keeps
+/// the decoded body, which they no longer match.
+const WIRE_HEADERS: [&str; 3] = ["content-encoding", "content-length", "transfer-encoding"];
+
+/// When executions of saved requests answer from the cache, from the
+/// global `response_cache` setting. Responses are only cached while the
+/// mode is not `Off`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CacheMode {
+    /// Always sends the request
+    #[default]
+    Off,
+    /// Replays the cached response when the target cannot be reached
+    Fallback,
+    /// Replays the cached response without sending the request, for demos
+    /// without the backend
+    Offline,
+}
+
+impl CacheMode {
+    pub fn parse(value: &str) -> Result<Self, String> {
+        match value.trim().to_lowercase().as_str() {
+            "off" => Ok(CacheMode::Off),
+            "fallback" => Ok(CacheMode::Fallback),
+            "offline" => Ok(CacheMode::Offline),
+            _ => Err(i18n::tf("error.invalid_cache_mode", &[("mode", &value)])),
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            CacheMode::Off => "off",
+            CacheMode::Fallback => "fallback",
+            CacheMode::Offline => "offline",
+        }
+    }
+
+    pub async fn global(pool: &DbPool) -> Self {
+        let stored = sqlx::query_scalar!("SELECT value FROM app_settings WHERE name = ?", SETTING)
+            .fetch_optional(pool)
+            .await
+            .unwrap_or_else(|e| {
+                log::warn!("Failed to load response cache setting: {}", e);
+                None
+            });
+        stored
+            .and_then(|value| Self::parse(&value).ok())
+            .unwrap_or_default()
+    }
+}
+
+/// Why a response came from the cache.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CacheReason {
+    Offline,
+    Unreachable,
+}
+
+/// Marks a replayed response in the execution result.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CachedFrom {
+    pub reason: CacheReason,
+    /// When the replayed response was received
+    pub cached_at: DateTime<Utc>,
+    /// The error sending the request, for `unreachable`
+    #[serde(default)]
+    pub error: Option<String>,
+}
+
+pub struct CachedResponse {
+    pub status: u16,
+    pub headers: HashMap<String, String>,
+    pub body: String,
+    pub duration_ms: i64,
+    pub cached_at: DateTime<Utc>,
+}
+
+fn url_hash(url: &str) -> String {
+    format!("{:x}", Sha256::digest(url.as_bytes()))
+}
+
+/// Keeps a response as the last one of the request for its URL. Only the
+/// hash of the URL is stored. Failures are logged, they do not fail the
+/// execution.
+pub async fn store(
+    pool: &DbPool,
+    request_id: i64,
+    url: &str,
+    status: u16,
+    headers: &HashMap<String, String>,
+    body: &str,
+    duration_ms: i64,
+) {
+    if body.len() > MAX_CACHED_BODY {
+        log::debug!(
+            "Not caching the {} byte response of request {}",
+            body.len(),
+            request_id
+        );
+        return;
+    }
+    let hash = url_hash(url);
+    let headers: HashMap<&String, &String> = headers
+        .iter()
+        .filter(|(name, _)| {
+            !WIRE_HEADERS
+                .iter()
+                .any(|wire| name.eq_ignore_ascii_case(wire))
+        })
+        .collect();
+    let headers = serde_json::to_string(&headers).unwrap_or_else(|_| "{}".to_string());
+    let now = Utc::now().naive_utc();
+    let result = sqlx::query!(
+        "INSERT INTO response_cache (request_id, url_hash, status, headers, body, duration_ms, cached_at) VALUES (?, ?, ?, ?, ?, ?, ?)
+        ON CONFLICT (request_id, url_hash) DO UPDATE SET status = excluded.status, headers = excluded.headers, body = excluded.body, duration_ms = excluded.duration_ms, cached_at = excluded.cached_at",
+        request_id,
+        hash,
+        status,
+        headers,
+        body,
+        duration_ms,
+        now
+    )
+    .execute(pool)
+    .await;
+    if let Err(e) = result {
+        log::warn!("Failed to cache response of request {}: {}", request_id, e);
+    }
+}
+
+pub async fn lookup(
+    pool: &DbPool,
+    request_id: i64,
+    url: &str,
+) -> Result<Option<CachedResponse>, sqlx::Error> {
+    let hash = url_hash(url);
+    let row = sqlx::query!(
+        r#"SELECT status, headers, body, duration_ms, cached_at AS "cached_at: NaiveDateTime" FROM response_cache WHERE request_id = ? AND url_hash = ?"#,
+        request_id,
+        hash
+    )
+    .fetch_optional(pool)
+    .await?;
+    Ok(row.map(|row| CachedResponse {
+        status: row.status as u16,
+        headers: serde_json::from_str(&row.headers).unwrap_or_default(),
+        body: row.body,
+        duration_ms: row.duration_ms,
+        cached_at: row.cached_at.and_utc(),
+    }))
+}
+
+pub enum CacheError {
+    InvalidMode(String),
+    DatabaseError(sqlx::Error),
+}
+
+impl From<sqlx::Error> for CacheError {
+    fn from(e: sqlx::Error) -> Self {
+        CacheError::DatabaseError(e)
+    }
+}
+
+impl IntoResponse for CacheError {
+    fn into_response(self) -> Response {
+        match self {
+            CacheError::InvalidMode(msg) => (StatusCode::BAD_REQUEST, msg).into_response(),
+            CacheError::DatabaseError(e) => {
+                log::error!("Database error: {}", e);
+                (StatusCode::INTERNAL_SERVER_ERROR, i18n::t("error.database")).into_response()
+            }
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CacheSetting {
+    pub mode: String,
+}
+
+async fn get_setting(State(pool): State<DbPool>) -> Json<CacheSetting> {
+    Json(CacheSetting {
+        mode: CacheMode::global(&pool).await.as_str().to_string(),
+    })
+}
+
+async fn set_setting(
+    State(pool): State<DbPool>,
+    Json(payload): Json<CacheSetting>,
+) -> Result<Json<CacheSetting>, CacheError> {
+    let mode = CacheMode::parse(&payload.mode).map_err(CacheError::InvalidMode)?;
+    let value = mode.as_str();
+    sqlx::query!(
+        "INSERT INTO app_settings (name, value) VALUES (?, ?) ON CONFLICT (name) DO UPDATE SET value = excluded.value, updated_at = CURRENT_TIMESTAMP",
+        SETTING,
+        value
+    )
+    .execute(&pool)
+    .await?;
+    log::info!("Response cache mode set to {}", value);
+    Ok(Json(CacheSetting {
+        mode: value.to_string(),
+    }))
+}
+
+async fn clear_cache(State(pool): State<DbPool>) -> Result<impl IntoResponse, CacheError> {
+    let result = sqlx::query!("DELETE FROM response_cache")
+        .execute(&pool)
+        .await?;
+    log::info!("Cleared {} cached responses", result.rows_affected());
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub fn routes(pool: DbPool) -> Router {
+    Router::new()
+        .route(
+            "/settings/response-cache",
+            get(get_setting).put(set_setting),
+        )
+        .route("/response-cache", delete(clear_cache))
+        .with_state(pool)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        db::create_test_pool,
+        executor::{self, ExecuteRequestPayload, ExecuteResponse, ExecutorError},
+    };
+    use axum_test::TestServer;
+    use httpmock::prelude::*;
+    use serde_json::json;
+
+    /// A saved request to `{{base}}/orders`, with `base` pointing at `base`.
+    async fn create_request(pool: &DbPool, base: &str) -> i64 {
+        let (request_id,): (i64,) = sqlx::query_as(
+            "INSERT INTO requests (name, method, url) VALUES ('Orders', 'GET', '{{base}}/orders') RETURNING id",
+        )
+        .fetch_one(pool)
+        .await
+        .unwrap();
+        sqlx::query("INSERT INTO global_variables (name, value) VALUES ('base', ?)")
+            .bind(base)
+            .execute(pool)
+            .await
+            .unwrap();
+        request_id
+    }
+
+    async fn set_mode(server: &TestServer, mode: &str) {
+        server
+            .put("/settings/response-cache")
+            .json(&json!({ "mode": mode }))
+            .await
+            .assert_status_ok();
+    }
+
+    async fn send(pool: &DbPool, request_id: i64) -> Result<ExecuteResponse, ExecutorError> {
+        executor::execute(pool.clone(), ExecuteRequestPayload::saved(request_id, None)).await
+    }
+
+    #[tokio::test]
+    async fn test_received_responses_are_stored() {
+        let pool = create_test_pool().await;
+        let server = TestServer::new(routes(pool.clone())).unwrap();
+        let mock = MockServer::start();
+        mock.mock(|when, then| {
+            when.method(GET).path("/orders");
+            then.status(200).body(r#"{"count":2}"#);
+        });
+        let request_id = create_request(&pool, &mock.base_url()).await;
+        set_mode(&server, "fallback").await;
+
+        let received = send(&pool, request_id).await.unwrap();
+        assert!(received.cached.is_none());
+        let url = mock.url("/orders");
+        assert!(lookup(&pool, request_id, &url).await.unwrap().is_some());
+
+        server
+            .delete("/response-cache")
+            .await
+            .assert_status(StatusCode::NO_CONTENT);
+        assert!(lookup(&pool, request_id, &url).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_responses_are_not_stored_when_off() {
+        let pool = create_test_pool().await;
+        let mock = MockServer::start();
+        mock.mock(|when, then| {
+            when.method(GET).path("/orders");
+            then.status(200).body(r#"{"count":2}"#);
+        });
+        let request_id = create_request(&pool, &mock.base_url()).await;
+
+        send(&pool, request_id).await.unwrap();
+        let url = mock.url("/orders");
+        assert!(lookup(&pool, request_id, &url).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_wire_headers_are_not_replayed() {
+        let pool = create_test_pool().await;
+        let request_id = create_request(&pool, "http://127.0.0.1:9").await;
+        let url = "http://127.0.0.1:9/orders";
+        let headers = HashMap::from([
+            ("content-type".to_string(), "application/json".to_string()),
+            ("content-encoding".to_string(), "gzip".to_string()),
+            ("Content-Length".to_string(), "31".to_string()),
+        ]);
+        store(&pool, request_id, url, 200, &headers, "{}", 5).await;
+
+        let cached = lookup(&pool, request_id, url).await.unwrap().unwrap();
+        assert_eq!(
+            cached.headers,
+            HashMap::from([("content-type".to_string(), "application/json".to_string())])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_urls_are_not_stored() {
+        let pool = create_test_pool().await;
+        // Lookups go by the hash, the URL may carry secrets
+        let (url_columns,): (i64,) = sqlx::query_as(
+            "SELECT COUNT(*) FROM pragma_table_info('response_cache') WHERE name = 'url'",
+        )
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+        assert_eq!(url_columns, 0);
+    }
+
+    #[tokio::test]
+    async fn test_offline_replays_without_sending() {
+        let pool = create_test_pool().await;
+        let server = TestServer::new(routes(pool.clone())).unwrap();
+        let mock = MockServer::start();
+        let orders = mock.mock(|when, then| {
+            when.method(GET).path("/orders");
+            then.status(200).body(r#"{"count":2}"#);
+        });
+        let request_id = create_request(&pool, &mock.base_url()).await;
+
+        // Without a cached response offline mode fails
+        set_mode(&server, "offline").await;
+        assert!(matches!(
+            send(&pool, request_id).await,
+            Err(ExecutorError::NetworkError(_))
+        ));
+
+        set_mode(&server, "fallback").await;
+        send(&pool, request_id).await.unwrap();
+        set_mode(&server, "offline").await;
+        let replayed = send(&pool, request_id).await.unwrap();
+        orders.assert_calls(1);
+        assert_eq!(replayed.cached.unwrap().reason, CacheReason::Offline);
+        assert_eq!(
+            (replayed.status, replayed.body.as_str()),
+            (200, r#"{"count":2}"#)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_fallback_replays_unreachable_hosts() {
+        let pool = create_test_pool().await;
+        let server = TestServer::new(routes(pool.clone())).unwrap();
+        let closed = "http://127.0.0.1:9";
+        let request_id = create_request(&pool, closed).await;
+        set_mode(&server, "fallback").await;
+
+        // Responses are kept by resolved URL, this one has none yet
+        assert!(matches!(
+            send(&pool, request_id).await,
+            Err(ExecutorError::NetworkError(_))
+        ));
+        let headers = HashMap::from([("content-type".to_string(), "text/plain".to_string())]);
+        store(
+            &pool,
+            request_id,
+            &format!("{}/orders", closed),
+            200,
+            &headers,
+            "stale",
+            5,
+        )
+        .await;
+        let replayed = send(&pool, request_id).await.unwrap();
+        let cached = replayed.cached.unwrap();
+        assert_eq!(cached.reason, CacheReason::Unreachable);
+        assert!(cached.error.is_some());
+        assert_eq!(replayed.body, "stale");
+        assert_eq!(replayed.headers, headers);
+
+        // Without fallback the error is reported
+        set_mode(&server, "off").await;
+        assert!(send(&pool, request_id).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_unknown_mode_is_rejected() {
+        let pool = create_test_pool().await;
+        let server = TestServer::new(routes(pool)).unwrap();
+        let response = server
+            .put("/settings/response-cache")
+            .json(&json!({ "mode": "sometimes" }))
+            .await;
+        response.assert_status(StatusCode::BAD_REQUEST);
+        response.assert_text(
+            "Invalid response cache mode 'sometimes', expected off, fallback or offline",
+        );
+    }
+
+    #[tokio::test]
+    async fn test_offline_replay_has_no_side_effects() {
+        let pool = create_test_pool().await;
+        let server = TestServer::new(routes(pool.clone())).unwrap();
+        let mock = MockServer::start();
+        let orders = mock.mock(|when, then| {
+            when.path("/orders");
+            then.status(200);
+        });
+        let token = mock.mock(|when, then| {
+            when.path("/token");
+            then.status(200).json_body(json!({ "access_token": "t" }));
+        });
+        let script = "jsl.environment.set('visits', 1);";
+        let (request_id,): (i64,) = sqlx::query_as(
+            "INSERT INTO requests (name, method, url, auth_type, serialized, pre_request_script) VALUES ('Orders', 'GET', ?, 'oauth2', 1, ?) RETURNING id",
+        )
+        .bind(mock.url("/orders"))
+        .bind(script)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+        sqlx::query("INSERT INTO oauth_configs (request_id, grant_type, client_id, token_url) VALUES (?, 'client_credentials', 'cli', ?)")
+            .bind(request_id)
+            .bind(mock.url("/token"))
+            .execute(&pool)
+            .await
+            .unwrap();
+        let (environment_id,): (i64,) = sqlx::query_as(
+            "INSERT INTO environments (name, variables) VALUES ('Dev', '{}') RETURNING id",
+        )
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+        store(
+            &pool,
+            request_id,
+            &mock.url("/orders"),
+            200,
+            &HashMap::new(),
+            "cached",
+            5,
+        )
+        .await;
+        server
+            .put("/settings/response-cache")
+            .json(&json!({ "mode": "offline" }))
+            .await
+            .assert_status_ok();
+
+        let replayed = executor::execute(
+            pool.clone(),
+            ExecuteRequestPayload::saved(request_id, Some(environment_id)),
+        )
+        .await
+        .unwrap();
+        assert_eq!(replayed.body, "cached");
+        orders.assert_calls(0);
+        token.assert_calls(0);
+        let variables = executor::load_environment_variables(&pool, Some(environment_id))
+            .await
+            .unwrap();
+        assert!(!variables.contains_key("visits"));
+    }
+}
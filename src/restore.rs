@@ -0,0 +1,430 @@
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use std::collections::{BTreeMap, HashMap};
+
+use crate::{
+    crypto,
+    db::DbPool,
+    export::{BUNDLE_FORMAT, BUNDLE_FORMAT_VERSION},
+    i18n,
+    importers::{CollectionSummary, EnvironmentSummary},
+    requests,
+};
+
+// Restores a workspace bundle written by export::write_bundle. Everything in
+// it is added next to the current workspace, keeping names, timestamps and
+// the links between folders, requests and parent environments.
+
+#[derive(Deserialize)]
+struct Manifest {
+    format: String,
+    format_version: u32,
+}
+
+#[derive(Deserialize)]
+struct BundleCollection {
+    folder: Option<BundleFolder>,
+    #[serde(default)]
+    requests: Vec<BundleRequest>,
+}
+
+#[derive(Deserialize)]
+struct BundleFolder {
+    id: i64,
+    name: String,
+    #[serde(default = "empty_object")]
+    variables: String,
+    #[serde(default)]
+    description: Option<String>,
+    #[serde(default)]
+    base_url: Option<String>,
+    created_at: Option<DateTime<Utc>>,
+    updated_at: Option<DateTime<Utc>>,
+    archived_at: Option<DateTime<Utc>>,
+}
+
+/// A request as exported; columns added after the bundle was written keep
+/// their defaults.
+#[derive(Deserialize)]
+#[serde(default)]
+struct BundleRequest {
+    name: String,
+    method: String,
+    url: String,
+    body: Option<String>,
+    headers: Option<String>,
+    folder_id: Option<i64>,
+    request_type: String,
+    body_type: String,
+    body_content: Option<String>,
+    auth_type: String,
+    auth_token: Option<String>,
+    auth_username: Option<String>,
+    auth_password: Option<String>,
+    captures: Option<String>,
+    auth_config: Option<String>,
+    serialized: bool,
+    ip_family: String,
+    pre_request_script: Option<String>,
+    test_script: Option<String>,
+    callback: Option<String>,
+    unresolved_variables: Option<String>,
+    retry: Option<String>,
+    compression: Option<String>,
+    assertions: Option<String>,
+    description: Option<String>,
+    tls_versions: Option<String>,
+    latency_budget_ms: Option<i64>,
+    created_at: Option<DateTime<Utc>>,
+    updated_at: Option<DateTime<Utc>>,
+    archived_at: Option<DateTime<Utc>>,
+}
+
+impl Default for BundleRequest {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            method: "GET".to_string(),
+            url: String::new(),
+            body: None,
+            headers: None,
+            folder_id: None,
+            request_type: "api".to_string(),
+            body_type: "none".to_string(),
+            body_content: None,
+            auth_type: "none".to_string(),
+            auth_token: None,
+            auth_username: None,
+            auth_password: None,
+            captures: None,
+            auth_config: None,
+            serialized: false,
+            ip_family: "auto".to_string(),
+            pre_request_script: None,
+            test_script: None,
+            callback: None,
+            unresolved_variables: None,
+            retry: None,
+            compression: None,
+            assertions: None,
+            description: None,
+            tls_versions: None,
+            latency_budget_ms: None,
+            created_at: None,
+            updated_at: None,
+            archived_at: None,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct BundleEnvironment {
+    id: i64,
+    name: String,
+    variables: String,
+    #[serde(default)]
+    secrets: Vec<String>,
+    #[serde(default)]
+    disabled: Vec<String>,
+    #[serde(default)]
+    parent_id: Option<i64>,
+    created_at: Option<DateTime<Utc>>,
+    updated_at: Option<DateTime<Utc>>,
+    archived_at: Option<DateTime<Utc>>,
+}
+
+/// Network settings as exported, without the proxy password.
+#[derive(Deserialize)]
+struct BundleNetworkSettings {
+    auto_proxy: bool,
+    http_proxy: Option<String>,
+    https_proxy: Option<String>,
+    no_proxy: Option<String>,
+    proxy_username: Option<String>,
+    localhost_rewrite: String,
+    localhost_alias: String,
+    pac_url: Option<String>,
+}
+
+/// The parsed contents of a workspace bundle.
+pub struct Bundle {
+    collections: Vec<BundleCollection>,
+    environments: Vec<BundleEnvironment>,
+    globals: BTreeMap<String, String>,
+    app_settings: BTreeMap<String, String>,
+    network: Option<BundleNetworkSettings>,
+}
+
+fn empty_object() -> String {
+    "{}".to_string()
+}
+
+/// Reads a bundle from the members of an uploaded zip, see
+/// importers::extract_archive. Archives without a bundle manifest are left to
+/// the other importers.
+pub fn read_bundle(members: &[(String, Vec<u8>)]) -> Result<Option<Bundle>, anyhow::Error> {
+    let Some((_, manifest)) = members.iter().find(|(name, _)| name == "manifest.json") else {
+        return Ok(None);
+    };
+    let manifest: Manifest = match serde_json::from_slice(manifest) {
+        Ok(manifest) => manifest,
+        Err(_) => return Ok(None),
+    };
+    if manifest.format != BUNDLE_FORMAT {
+        return Ok(None);
+    }
+    if manifest.format_version > BUNDLE_FORMAT_VERSION {
+        anyhow::bail!(
+            "Bundle format {} is newer than this version supports ({})",
+            manifest.format_version,
+            BUNDLE_FORMAT_VERSION
+        );
+    }
+
+    let mut bundle = Bundle {
+        collections: Vec::new(),
+        environments: Vec::new(),
+        globals: BTreeMap::new(),
+        app_settings: BTreeMap::new(),
+        network: None,
+    };
+    for (name, data) in members {
+        let context = || format!("Invalid bundle member '{}'", name);
+        if name.starts_with("collections/") {
+            bundle
+                .collections
+                .push(serde_json::from_slice(data).with_context(context)?);
+        } else if name.starts_with("environments/") {
+            bundle
+                .environments
+                .push(serde_json::from_slice(data).with_context(context)?);
+        } else if name == "globals.json" {
+            bundle.globals = serde_json::from_slice(data).with_context(context)?;
+        } else if name == "settings/app.json" {
+            bundle.app_settings = serde_json::from_slice(data).with_context(context)?;
+        } else if name == "settings/network.json" {
+            bundle.network = Some(serde_json::from_slice(data).with_context(context)?);
+        }
+    }
+    Ok(Some(bundle))
+}
+
+impl Bundle {
+    /// What restoring would create, for the import preview.
+    pub fn summary(&self) -> (Vec<CollectionSummary>, Vec<EnvironmentSummary>) {
+        let collections = self
+            .collections
+            .iter()
+            .map(|c| CollectionSummary {
+                name: c
+                    .folder
+                    .as_ref()
+                    .map(|f| f.name.clone())
+                    .unwrap_or_else(|| i18n::t("import.unfiled")),
+                request_count: c.requests.len(),
+                exists: false,
+            })
+            .collect();
+        let environments = self
+            .environments
+            .iter()
+            .map(|e| EnvironmentSummary {
+                name: e.name.clone(),
+                variable_count: serde_json::from_str::<Map<String, Value>>(&e.variables)
+                    .map(|v| v.len())
+                    .unwrap_or_default(),
+            })
+            .collect();
+        (collections, environments)
+    }
+}
+
+/// Restores a bundle in one transaction, so a failure leaves the workspace
+/// as it was.
+pub async fn restore(pool: &DbPool, bundle: Bundle) -> Result<String, anyhow::Error> {
+    let mut tx = pool.begin().await?;
+    let mut folder_ids = HashMap::new();
+    let mut request_count = 0;
+
+    for collection in &bundle.collections {
+        if let Some(folder) = &collection.folder {
+            let created_at = folder.created_at.unwrap_or_else(Utc::now).naive_utc();
+            let updated_at = folder.updated_at.unwrap_or_else(Utc::now).naive_utc();
+            let archived_at = folder.archived_at.map(|at| at.naive_utc());
+            let id = sqlx::query_scalar!(
+                "INSERT INTO folders (name, variables, description, base_url, created_at, updated_at, archived_at) VALUES (?, ?, ?, ?, ?, ?, ?) RETURNING id",
+                folder.name,
+                folder.variables,
+                folder.description,
+                folder.base_url,
+                created_at,
+                updated_at,
+                archived_at
+            )
+            .fetch_one(&mut *tx)
+            .await
+            .with_context(|| format!("Failed to create folder '{}'", folder.name))?;
+            folder_ids.insert(folder.id, id);
+        }
+    }
+
+    for request in bundle.collections.iter().flat_map(|c| &c.requests) {
+        let folder_id = request
+            .folder_id
+            .and_then(|id| folder_ids.get(&id).copied());
+        let created_at = request.created_at.unwrap_or_else(Utc::now).naive_utc();
+        let updated_at = request.updated_at.unwrap_or_else(Utc::now).naive_utc();
+        let archived_at = request.archived_at.map(|at| at.naive_utc());
+        let auth_token = requests::encrypt_credential(request.auth_token.as_deref())?;
+        let auth_username = requests::encrypt_credential(request.auth_username.as_deref())?;
+        let auth_password = requests::encrypt_credential(request.auth_password.as_deref())?;
+        sqlx::query!(
+            "INSERT INTO requests (name, method, url, body, headers, folder_id, request_type, body_type, body_content, auth_type, auth_token, auth_username, auth_password, captures, auth_config, serialized, ip_family, pre_request_script, test_script, callback, unresolved_variables, retry, compression, assertions, description, tls_versions, latency_budget_ms, created_at, updated_at, archived_at) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+            request.name,
+            request.method,
+            request.url,
+            request.body,
+            request.headers,
+            folder_id,
+            request.request_type,
+            request.body_type,
+            request.body_content,
+            request.auth_type,
+            auth_token,
+            auth_username,
+            auth_password,
+            request.captures,
+            request.auth_config,
+            request.serialized,
+            request.ip_family,
+            request.pre_request_script,
+            request.test_script,
+            request.callback,
+            request.unresolved_variables,
+            request.retry,
+            request.compression,
+            request.assertions,
+            request.description,
+            request.tls_versions,
+            request.latency_budget_ms,
+            created_at,
+            updated_at,
+            archived_at
+        )
+        .execute(&mut *tx)
+        .await
+        .with_context(|| format!("Failed to create request '{}'", request.name))?;
+        request_count += 1;
+    }
+
+    let mut environment_ids = HashMap::new();
+    for environment in &bundle.environments {
+        let variables = encrypt_secrets(&environment.variables, &environment.secrets)?;
+        let secrets = serde_json::to_string(&environment.secrets)?;
+        let disabled = serde_json::to_string(&environment.disabled)?;
+        let created_at = environment.created_at.unwrap_or_else(Utc::now).naive_utc();
+        let updated_at = environment.updated_at.unwrap_or_else(Utc::now).naive_utc();
+        let archived_at = environment.archived_at.map(|at| at.naive_utc());
+        let id = sqlx::query_scalar!(
+            "INSERT INTO environments (name, variables, secrets, disabled, created_at, updated_at, archived_at) VALUES (?, ?, ?, ?, ?, ?, ?) RETURNING id",
+            environment.name,
+            variables,
+            secrets,
+            disabled,
+            created_at,
+            updated_at,
+            archived_at
+        )
+        .fetch_one(&mut *tx)
+        .await
+        .with_context(|| format!("Failed to create environment '{}'", environment.name))?;
+        environment_ids.insert(environment.id, id);
+    }
+    // Linked once all exist; a parent missing from the bundle is dropped
+    for environment in &bundle.environments {
+        let parent_id = environment
+            .parent_id
+            .and_then(|id| environment_ids.get(&id).copied());
+        if let Some(parent_id) = parent_id {
+            let id = environment_ids[&environment.id];
+            sqlx::query!(
+                "UPDATE environments SET parent_id = ? WHERE id = ?",
+                parent_id,
+                id
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+    }
+
+    for (name, value) in &bundle.globals {
+        sqlx::query!(
+            "INSERT INTO global_variables (name, value) VALUES (?, ?) ON CONFLICT (name) DO UPDATE SET value = excluded.value, updated_at = CURRENT_TIMESTAMP",
+            name,
+            value
+        )
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    for (name, value) in &bundle.app_settings {
+        sqlx::query!(
+            "INSERT INTO app_settings (name, value) VALUES (?, ?) ON CONFLICT (name) DO UPDATE SET value = excluded.value, updated_at = CURRENT_TIMESTAMP",
+            name,
+            value
+        )
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    // The stored proxy password is kept, as bundles never contain it
+    if let Some(network) = &bundle.network {
+        sqlx::query!(
+            "UPDATE network_settings SET auto_proxy = ?, http_proxy = ?, https_proxy = ?, no_proxy = ?, proxy_username = ?, localhost_rewrite = ?, localhost_alias = ?, pac_url = ? WHERE id = 1",
+            network.auto_proxy,
+            network.http_proxy,
+            network.https_proxy,
+            network.no_proxy,
+            network.proxy_username,
+            network.localhost_rewrite,
+            network.localhost_alias,
+            network.pac_url
+        )
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    tx.commit().await?;
+    log::info!(
+        "Restored workspace bundle: {} folder(s), {} request(s), {} environment(s)",
+        folder_ids.len(),
+        request_count,
+        environment_ids.len()
+    );
+
+    Ok(i18n::tf(
+        "import.bundle_restored",
+        &[
+            ("folders", &folder_ids.len()),
+            ("requests", &request_count),
+            ("environments", &environment_ids.len()),
+        ],
+    ))
+}
+
+/// Secret values are exported empty; any filled in by hand are stored
+/// encrypted like the rest.
+fn encrypt_secrets(variables: &str, secrets: &[String]) -> Result<String, anyhow::Error> {
+    let mut variables: Map<String, Value> =
+        serde_json::from_str(variables).context("Environment variables must be a JSON object")?;
+    for name in secrets {
+        if let Some(Value::String(value)) = variables.get_mut(name) {
+            if !value.is_empty() && !crypto::is_encrypted(value) {
+                *value = crypto::encrypt(value)?;
+            }
+        }
+    }
+    Ok(Value::Object(variables).to_string())
+}
@@ -0,0 +1,144 @@
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+const MAX_ATTEMPTS: u32 = 10;
+const MAX_DELAY_MS: u64 = 60_000;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Backoff {
+    /// The same delay before every retry
+    Fixed,
+    /// The delay doubles after every retry
+    #[default]
+    Exponential,
+}
+
+/// The `retry` column of a request: when a send fails it is repeated, up to
+/// `max_attempts` sends in total.
+#[derive(Debug, Clone, Deserialize)]
+pub struct RetryPolicy {
+    pub max_attempts: u32,
+    /// Milliseconds before the first retry
+    #[serde(default = "default_delay_ms")]
+    pub delay_ms: u64,
+    #[serde(default)]
+    pub backoff: Backoff,
+    /// Response statuses that are retried
+    #[serde(default = "default_retry_on_status")]
+    pub retry_on_status: Vec<u16>,
+    /// Whether connection failures and timeouts are retried
+    #[serde(default = "default_true")]
+    pub retry_on_network_error: bool,
+}
+
+fn default_delay_ms() -> u64 {
+    500
+}
+
+fn default_retry_on_status() -> Vec<u16> {
+    vec![502, 503, 504]
+}
+
+fn default_true() -> bool {
+    true
+}
+
+impl RetryPolicy {
+    pub fn parse(retry: Option<&str>) -> Result<Option<Self>, String> {
+        let Some(retry) = retry.filter(|r| !r.trim().is_empty()) else {
+            return Ok(None);
+        };
+        let policy: RetryPolicy =
+            serde_json::from_str(retry).map_err(|e| format!("Invalid retry settings: {}", e))?;
+        if !(1..=MAX_ATTEMPTS).contains(&policy.max_attempts) {
+            return Err(format!(
+                "Retry attempts must be between 1 and {}",
+                MAX_ATTEMPTS
+            ));
+        }
+        if policy.delay_ms > MAX_DELAY_MS {
+            return Err(format!(
+                "Retry delay must be at most {} milliseconds",
+                MAX_DELAY_MS
+            ));
+        }
+        if let Some(status) = policy
+            .retry_on_status
+            .iter()
+            .find(|s| !(100..=599).contains(*s))
+        {
+            return Err(format!("Invalid retry status: {}", status));
+        }
+        Ok(Some(policy))
+    }
+
+    pub fn retries_status(&self, status: u16) -> bool {
+        self.retry_on_status.contains(&status)
+    }
+
+    /// Wait before the given retry, counting from 1; never longer than the
+    /// largest configurable delay.
+    pub fn delay(&self, retry: u32) -> Duration {
+        let ms = match self.backoff {
+            Backoff::Fixed => self.delay_ms,
+            Backoff::Exponential => self
+                .delay_ms
+                .saturating_mul(1u64 << retry.saturating_sub(1).min(16)),
+        };
+        Duration::from_millis(ms.min(MAX_DELAY_MS))
+    }
+}
+
+/// One send of a request with a retry policy, as reported by the executor.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RetryAttempt {
+    /// Counting from 1
+    pub attempt: u32,
+    /// `None` when no response arrived
+    pub status: Option<u16>,
+    pub error: Option<String>,
+    /// Milliseconds until the response headers or the failure
+    pub duration: i64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_and_delay() {
+        assert!(RetryPolicy::parse(None).unwrap().is_none());
+        assert!(RetryPolicy::parse(Some(" ")).unwrap().is_none());
+
+        let policy = RetryPolicy::parse(Some(r#"{"max_attempts":3}"#))
+            .unwrap()
+            .unwrap();
+        assert_eq!(policy.backoff, Backoff::Exponential);
+        assert!(policy.retries_status(503));
+        assert!(!policy.retries_status(500));
+        assert!(policy.retry_on_network_error);
+        assert_eq!(policy.delay(1), Duration::from_millis(500));
+        assert_eq!(policy.delay(3), Duration::from_millis(2000));
+
+        let fixed = RetryPolicy::parse(Some(
+            r#"{"max_attempts":2,"delay_ms":100,"backoff":"fixed","retry_on_status":[429]}"#,
+        ))
+        .unwrap()
+        .unwrap();
+        assert_eq!(fixed.delay(5), Duration::from_millis(100));
+        assert!(fixed.retries_status(429));
+
+        let capped = RetryPolicy::parse(Some(r#"{"max_attempts":10,"delay_ms":60000}"#))
+            .unwrap()
+            .unwrap();
+        assert_eq!(capped.delay(9), Duration::from_millis(MAX_DELAY_MS));
+
+        assert!(RetryPolicy::parse(Some(r#"{"max_attempts":0}"#)).is_err());
+        assert!(RetryPolicy::parse(Some(r#"{"max_attempts":11}"#)).is_err());
+        assert!(RetryPolicy::parse(Some(r#"{"max_attempts":2,"delay_ms":60001}"#)).is_err());
+        assert!(RetryPolicy::parse(Some(r#"{"max_attempts":2,"retry_on_status":[700]}"#)).is_err());
+        assert!(RetryPolicy::parse(Some(r#"{"max_attempts":2,"backoff":"linear"}"#)).is_err());
+        assert!(RetryPolicy::parse(Some("3")).is_err());
+    }
+}
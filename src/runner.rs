@@ -0,0 +1,438 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Json, Router,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+use crate::{
+    alerts,
+    db::DbPool,
+    executor::{self, ExecuteRequestPayload},
+    i18n,
+    scripting::TestResult,
+};
+
+/// Longest pause allowed between two requests of a run.
+const MAX_DELAY_MS: u64 = 60_000;
+
+#[derive(Debug, Default, Deserialize)]
+pub struct RunFolderPayload {
+    pub environment_id: Option<i64>,
+    /// Skips the remaining requests after the first failure
+    #[serde(default)]
+    pub stop_on_failure: bool,
+    /// Pause between two requests, in milliseconds
+    #[serde(default)]
+    pub delay_ms: u64,
+}
+
+/// The outcome of one request in a run. A request fails when it cannot be
+/// sent, answers with a 4xx/5xx status, fails a test or an assertion, or gets
+/// no callback.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RunResult {
+    pub request_id: i64,
+    pub name: String,
+    pub method: String,
+    pub url: String,
+    pub status: Option<u16>,
+    pub duration: i64,
+    pub passed: bool,
+    pub error: Option<String>,
+    #[serde(default)]
+    pub test_results: Vec<TestResult>,
+    #[serde(default)]
+    pub assertion_results: Vec<TestResult>,
+    /// Response body, kept for comparing runs
+    #[serde(default)]
+    pub body: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RunStatus {
+    Passed,
+    Failed,
+    /// Failed and stopped early, see `RunFolderPayload::stop_on_failure`
+    Stopped,
+}
+
+impl RunStatus {
+    fn as_str(self) -> &'static str {
+        match self {
+            RunStatus::Passed => "passed",
+            RunStatus::Failed => "failed",
+            RunStatus::Stopped => "stopped",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RunReport {
+    pub id: i64,
+    pub folder_id: i64,
+    pub environment_id: Option<i64>,
+    pub status: RunStatus,
+    pub total: usize,
+    pub passed: usize,
+    pub failed: usize,
+    /// Requests not run because the run stopped
+    pub skipped: usize,
+    pub started_at: DateTime<Utc>,
+    pub finished_at: DateTime<Utc>,
+    pub results: Vec<RunResult>,
+}
+
+pub enum RunnerError {
+    FolderNotFound,
+    RequestNotFound,
+    RunNotFound,
+    DatabaseError(#[allow(dead_code)] sqlx::Error),
+}
+
+impl From<sqlx::Error> for RunnerError {
+    fn from(e: sqlx::Error) -> Self {
+        RunnerError::DatabaseError(e)
+    }
+}
+
+impl IntoResponse for RunnerError {
+    fn into_response(self) -> Response {
+        match self {
+            RunnerError::FolderNotFound => {
+                (StatusCode::NOT_FOUND, i18n::t("error.folder_not_found")).into_response()
+            }
+            RunnerError::RequestNotFound => {
+                (StatusCode::NOT_FOUND, i18n::t("error.request_not_found")).into_response()
+            }
+            RunnerError::RunNotFound => {
+                (StatusCode::NOT_FOUND, i18n::t("error.run_not_found")).into_response()
+            }
+            RunnerError::DatabaseError(_) => {
+                (StatusCode::INTERNAL_SERVER_ERROR, i18n::t("error.database")).into_response()
+            }
+        }
+    }
+}
+
+struct RunRequest {
+    id: i64,
+    name: String,
+    method: String,
+    url: String,
+}
+
+async fn run_request(pool: &DbPool, request: RunRequest, environment_id: Option<i64>) -> RunResult {
+    let payload = ExecuteRequestPayload::saved(request.id, environment_id);
+    let mut result = RunResult {
+        request_id: request.id,
+        name: request.name,
+        method: request.method,
+        url: request.url,
+        status: None,
+        duration: 0,
+        passed: false,
+        error: None,
+        test_results: Vec::new(),
+        assertion_results: Vec::new(),
+        body: None,
+    };
+    match executor::execute(pool.clone(), payload).await {
+        Ok(response) => {
+            let failed_tests = response.test_results.iter().filter(|t| !t.passed).count();
+            let failed_assertions = response
+                .assertion_results
+                .iter()
+                .filter(|a| !a.passed)
+                .count();
+            let callback_error = response.callback.and_then(|c| c.error);
+            result.error = if response.status >= 400 {
+                Some(format!("Status {}", response.status))
+            } else if failed_tests > 0 {
+                Some(format!("{} test(s) failed", failed_tests))
+            } else if failed_assertions > 0 {
+                Some(format!("{} assertion(s) failed", failed_assertions))
+            } else {
+                callback_error
+            };
+            result.passed = result.error.is_none();
+            result.status = Some(response.status);
+            result.duration = response.duration;
+            result.test_results = response.test_results;
+            result.assertion_results = response.assertion_results;
+            result.body = Some(response.body);
+        }
+        Err(e) => result.error = Some(e.to_string()),
+    }
+    result
+}
+
+/// Runs one saved request the way it would run as part of a folder.
+pub async fn run_saved_request(
+    pool: &DbPool,
+    request_id: i64,
+    environment_id: Option<i64>,
+) -> Result<RunResult, RunnerError> {
+    let request = sqlx::query_as!(
+        RunRequest,
+        "SELECT id, name, method, url FROM requests WHERE id = ?",
+        request_id
+    )
+    .fetch_optional(pool)
+    .await?
+    .ok_or(RunnerError::RequestNotFound)?;
+    Ok(run_request(pool, request, environment_id).await)
+}
+
+/// Runs the folder's requests one after another and stores the report.
+pub async fn run_folder(
+    pool: &DbPool,
+    folder_id: i64,
+    payload: &RunFolderPayload,
+) -> Result<RunReport, RunnerError> {
+    sqlx::query_scalar!("SELECT id FROM folders WHERE id = ?", folder_id)
+        .fetch_optional(pool)
+        .await?
+        .ok_or(RunnerError::FolderNotFound)?;
+
+    // The order the requests are listed in, oldest first
+    let requests = sqlx::query_as!(
+        RunRequest,
+        "SELECT id, name, method, url FROM requests WHERE folder_id = ? AND archived_at IS NULL AND request_type = 'api' ORDER BY id",
+        folder_id
+    )
+    .fetch_all(pool)
+    .await?;
+    let total = requests.len();
+    let delay = Duration::from_millis(payload.delay_ms.min(MAX_DELAY_MS));
+    log::info!(
+        "Running {} request(s) of folder {} (environment {:?})",
+        total,
+        folder_id,
+        payload.environment_id
+    );
+
+    let started_at = Utc::now();
+    let mut results = Vec::with_capacity(total);
+    for (index, request) in requests.into_iter().enumerate() {
+        if index > 0 && !delay.is_zero() {
+            tokio::time::sleep(delay).await;
+        }
+        let result = run_request(pool, request, payload.environment_id).await;
+        log::info!(
+            "Run step {}/{}: {} -> {}",
+            index + 1,
+            total,
+            result.name,
+            result.error.as_deref().unwrap_or("passed")
+        );
+        let stop = !result.passed && payload.stop_on_failure;
+        results.push(result);
+        if stop {
+            break;
+        }
+    }
+    let finished_at = Utc::now();
+
+    let passed = results.iter().filter(|r| r.passed).count();
+    let failed = results.len() - passed;
+    let skipped = total - results.len();
+    let status = match (failed, skipped) {
+        (0, _) => RunStatus::Passed,
+        (_, 0) => RunStatus::Failed,
+        _ => RunStatus::Stopped,
+    };
+    let mut report = RunReport {
+        id: 0,
+        folder_id,
+        environment_id: payload.environment_id,
+        status,
+        total,
+        passed,
+        failed,
+        skipped,
+        started_at,
+        finished_at,
+        results,
+    };
+    report.id = store(pool, &report).await?;
+    log::info!(
+        "Run {} {}: {} passed, {} failed, {} skipped",
+        report.id,
+        status.as_str(),
+        passed,
+        failed,
+        skipped
+    );
+    if status != RunStatus::Passed {
+        let (pool, report) = (pool.clone(), report.clone());
+        tokio::spawn(async move { alerts::notify(&pool, &report).await });
+    }
+    Ok(report)
+}
+
+async fn store(pool: &DbPool, report: &RunReport) -> Result<i64, sqlx::Error> {
+    let status = report.status.as_str();
+    let total = report.total as i64;
+    let passed = report.passed as i64;
+    let failed = report.failed as i64;
+    let json = serde_json::to_string(report).unwrap_or_else(|_| "{}".to_string());
+    let started_at = report.started_at.naive_utc();
+    let finished_at = report.finished_at.naive_utc();
+    sqlx::query_scalar!(
+        "INSERT INTO runs (folder_id, environment_id, status, total, passed, failed, report, started_at, finished_at) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?) RETURNING id",
+        report.folder_id,
+        report.environment_id,
+        status,
+        total,
+        passed,
+        failed,
+        json,
+        started_at,
+        finished_at
+    )
+    .fetch_one(pool)
+    .await
+}
+
+async fn run_folder_handler(
+    State(pool): State<DbPool>,
+    Path(folder_id): Path<i64>,
+    payload: Option<Json<RunFolderPayload>>,
+) -> Result<impl IntoResponse, RunnerError> {
+    let payload = payload.map(|Json(p)| p).unwrap_or_default();
+    Ok(Json(run_folder(&pool, folder_id, &payload).await?))
+}
+
+async fn get_run(
+    State(pool): State<DbPool>,
+    Path(id): Path<i64>,
+) -> Result<impl IntoResponse, RunnerError> {
+    let report = sqlx::query_scalar!("SELECT report FROM runs WHERE id = ?", id)
+        .fetch_optional(&pool)
+        .await?
+        .ok_or(RunnerError::RunNotFound)?;
+    // Stored before its id was known
+    let mut report: serde_json::Value = serde_json::from_str(&report).unwrap_or_default();
+    report["id"] = id.into();
+    Ok(Json(report))
+}
+
+pub fn routes(pool: DbPool) -> Router {
+    Router::new()
+        .route("/run/folder/:id", post(run_folder_handler))
+        .route("/runs/:id", get(get_run))
+        .with_state(pool)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db;
+    use axum_test::TestServer;
+    use httpmock::MockServer;
+    use serde_json::json;
+
+    async fn setup(pool: &DbPool, base_url: &str) -> i64 {
+        sqlx::query!(
+            "INSERT OR IGNORE INTO network_settings (id, auto_proxy, http_proxy, https_proxy, no_proxy) VALUES (1, TRUE, NULL, NULL, NULL)"
+        )
+        .execute(pool)
+        .await
+        .unwrap();
+        let folder_id =
+            sqlx::query_scalar!("INSERT INTO folders (name) VALUES ('Checkout') RETURNING id")
+                .fetch_one(pool)
+                .await
+                .unwrap();
+        for (name, path) in [("Cart", "/cart"), ("Pay", "/pay"), ("Receipt", "/receipt")] {
+            let url = format!("{}{}", base_url, path);
+            sqlx::query!(
+                "INSERT INTO requests (name, method, url, folder_id) VALUES (?, 'GET', ?, ?)",
+                name,
+                url,
+                folder_id
+            )
+            .execute(pool)
+            .await
+            .unwrap();
+        }
+        folder_id
+    }
+
+    #[tokio::test]
+    async fn test_run_folder() {
+        let pool = db::create_test_pool().await;
+        let mock_server = MockServer::start_async().await;
+        let cart = mock_server.mock(|when, then| {
+            when.path("/cart");
+            then.status(200).body("cart");
+        });
+        mock_server.mock(|when, then| {
+            when.path("/pay");
+            then.status(402);
+        });
+        let receipt = mock_server.mock(|when, then| {
+            when.path("/receipt");
+            then.status(200);
+        });
+        let folder_id = setup(&pool, &mock_server.base_url()).await;
+        let server = TestServer::new(routes(pool.clone())).unwrap();
+
+        let report: RunReport = server
+            .post(&format!("/run/folder/{}", folder_id))
+            .json(&json!({ "delay_ms": 10 }))
+            .await
+            .json();
+        assert_eq!(report.status, RunStatus::Failed);
+        assert_eq!((report.total, report.passed, report.failed), (3, 2, 1));
+        let names: Vec<&str> = report.results.iter().map(|r| r.name.as_str()).collect();
+        assert_eq!(names, vec!["Cart", "Pay", "Receipt"]);
+        assert_eq!(report.results[1].status, Some(402));
+        assert_eq!(report.results[1].error.as_deref(), Some("Status 402"));
+        assert_eq!(report.results[0].body.as_deref(), Some("cart"));
+
+        let stored: RunReport = server.get(&format!("/runs/{}", report.id)).await.json();
+        assert_eq!(stored.id, report.id);
+        assert_eq!(stored.results.len(), 3);
+
+        let report: RunReport = server
+            .post(&format!("/run/folder/{}", folder_id))
+            .json(&json!({ "stop_on_failure": true }))
+            .await
+            .json();
+        assert_eq!(report.status, RunStatus::Stopped);
+        assert_eq!((report.results.len(), report.skipped), (2, 1));
+        cart.assert_calls(2);
+        receipt.assert_calls(1);
+
+        sqlx::query!(
+            "UPDATE requests SET assertions = '[{\"type\":\"status\",\"equals\":201}]' WHERE name = 'Cart'"
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        let report: RunReport = server
+            .post(&format!("/run/folder/{}", folder_id))
+            .await
+            .json();
+        let cart_result = &report.results[0];
+        assert!(!cart_result.passed);
+        assert_eq!(cart_result.error.as_deref(), Some("1 assertion(s) failed"));
+        assert_eq!(cart_result.assertion_results[0].name, "Status is 201");
+
+        server
+            .post("/run/folder/999")
+            .await
+            .assert_status(StatusCode::NOT_FOUND);
+        server
+            .get("/runs/999")
+            .await
+            .assert_status(StatusCode::NOT_FOUND);
+    }
+}
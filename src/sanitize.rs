@@ -0,0 +1,121 @@
+use ammonia::{Builder, UrlRelative};
+use axum::{
+    extract::DefaultBodyLimit,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    routing::post,
+    Json, Router,
+};
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+
+// Response bodies can be much larger than axum's 2 MB default
+const MAX_HTML_SIZE: usize = 10 * 1024 * 1024;
+
+#[derive(Deserialize)]
+pub struct SanitizeHtml {
+    html: String,
+    /// URL the response came from; relative links and images are resolved against it.
+    #[serde(default)]
+    base_url: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SanitizedHtml {
+    pub html: String,
+}
+
+pub enum SanitizeError {
+    InvalidBaseUrl(String),
+}
+
+impl IntoResponse for SanitizeError {
+    fn into_response(self) -> Response {
+        match self {
+            SanitizeError::InvalidBaseUrl(msg) => (
+                StatusCode::BAD_REQUEST,
+                format!("Invalid base URL: {}", msg),
+            )
+                .into_response(),
+        }
+    }
+}
+
+/// Strips scripts, event handlers, `javascript:` URLs and other active content,
+/// keeping the markup needed for a readable preview.
+pub fn sanitize_html(html: &str, base_url: Option<Url>) -> String {
+    let mut builder = Builder::default();
+    builder.url_relative(match base_url {
+        Some(base) => UrlRelative::RewriteWithBase(base),
+        None => UrlRelative::PassThrough,
+    });
+    builder.clean(html).to_string()
+}
+
+async fn sanitize_html_handler(
+    Json(payload): Json<SanitizeHtml>,
+) -> Result<impl IntoResponse, SanitizeError> {
+    log::debug!("Sanitizing HTML preview: {} bytes", payload.html.len());
+
+    let base_url = payload
+        .base_url
+        .as_deref()
+        .filter(|u| !u.trim().is_empty())
+        .map(|u| {
+            Url::parse(u).map_err(|e| {
+                log::warn!("Invalid base URL for HTML preview: {}", e);
+                SanitizeError::InvalidBaseUrl(e.to_string())
+            })
+        })
+        .transpose()?;
+
+    let html = sanitize_html(&payload.html, base_url);
+    Ok(Json(SanitizedHtml { html }))
+}
+
+pub fn routes() -> Router {
+    Router::new().route(
+        "/sanitize/html",
+        post(sanitize_html_handler).layer(DefaultBodyLimit::max(MAX_HTML_SIZE)),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum_test::TestServer;
+    use serde_json::json;
+
+    #[test]
+    fn test_sanitize_html_strips_active_content() {
+        let html = r#"<h1 onclick="steal()">Hi</h1><script>alert(1)</script><a href="javascript:alert(2)">x</a><iframe src="https://evil"></iframe>"#;
+        let clean = sanitize_html(html, None);
+        assert!(clean.contains("<h1>Hi</h1>"));
+        assert!(!clean.contains("script"));
+        assert!(!clean.contains("onclick"));
+        assert!(!clean.contains("javascript:"));
+        assert!(!clean.contains("iframe"));
+    }
+
+    #[tokio::test]
+    async fn test_sanitize_endpoint_rewrites_relative_urls() {
+        let server = TestServer::new(routes()).unwrap();
+
+        let response = server
+            .post("/sanitize/html")
+            .json(&json!({
+                "html": "<img src=\"/logo.png\"><p>ok</p>",
+                "base_url": "https://api.example.com/v1/page"
+            }))
+            .await;
+        response.assert_status_ok();
+        let body: SanitizedHtml = response.json();
+        assert!(body.html.contains("https://api.example.com/logo.png"));
+
+        let response = server
+            .post("/sanitize/html")
+            .json(&json!({ "html": "<p>ok</p>", "base_url": "not a url" }))
+            .await;
+        response.assert_status(StatusCode::BAD_REQUEST);
+    }
+}
@@ -0,0 +1,265 @@
+use serde_json::{json, Map, Value};
+
+/// Schemas referring to themselves are cut off this deep.
+const MAX_DEPTH: usize = 10;
+
+/// Builds an example value for `schema`. `$ref`s are looked up in `root`,
+/// the document the schema came from, so components of an OpenAPI document
+/// work as well as `$defs` of a plain JSON Schema.
+pub fn example(schema: &Value, root: &Value) -> Result<Value, String> {
+    Generator { root }.value(schema, None, 0)
+}
+
+/// The schema of the named component of an OpenAPI 3 document, or a Swagger
+/// 2 definition.
+pub fn component<'a>(document: &'a Value, name: &str) -> Option<&'a Value> {
+    document
+        .pointer("/components/schemas")
+        .or_else(|| document.get("definitions"))
+        .and_then(|schemas| schemas.get(name))
+}
+
+struct Generator<'a> {
+    root: &'a Value,
+}
+
+impl Generator<'_> {
+    fn resolve<'s>(&'s self, reference: &str) -> Result<&'s Value, String> {
+        let pointer = reference
+            .strip_prefix('#')
+            .ok_or_else(|| format!("Only local references are supported, not '{}'", reference))?;
+        self.root
+            .pointer(pointer)
+            .ok_or_else(|| format!("Reference '{}' points to nothing", reference))
+    }
+
+    /// `name` is the property the value is for, used to pick a plausible
+    /// string or number.
+    fn value(&self, schema: &Value, name: Option<&str>, depth: usize) -> Result<Value, String> {
+        let Value::Object(schema) = schema else {
+            // `true`, or an empty schema, allows anything
+            return Ok(Value::Null);
+        };
+        if depth > MAX_DEPTH {
+            return Ok(Value::Null);
+        }
+        if let Some(reference) = schema.get("$ref").and_then(Value::as_str) {
+            return self.value(self.resolve(reference)?, name, depth + 1);
+        }
+        for keyword in ["const", "example", "default"] {
+            if let Some(value) = schema.get(keyword) {
+                return Ok(value.clone());
+            }
+        }
+        if let Some(first) = schema
+            .get("examples")
+            .and_then(Value::as_array)
+            .and_then(|e| e.first())
+        {
+            return Ok(first.clone());
+        }
+        if let Some(first) = schema
+            .get("enum")
+            .and_then(Value::as_array)
+            .and_then(|e| e.first())
+        {
+            return Ok(first.clone());
+        }
+        if let Some(all) = schema.get("allOf").and_then(Value::as_array) {
+            let mut merged = Map::new();
+            for part in all {
+                match self.value(part, name, depth + 1)? {
+                    Value::Object(fields) => merged.extend(fields),
+                    other if all.len() == 1 => return Ok(other),
+                    _ => {}
+                }
+            }
+            return Ok(Value::Object(merged));
+        }
+        for keyword in ["oneOf", "anyOf"] {
+            if let Some(first) = schema
+                .get(keyword)
+                .and_then(Value::as_array)
+                .and_then(|s| s.first())
+            {
+                return self.value(first, name, depth + 1);
+            }
+        }
+
+        let kind = match schema.get("type") {
+            Some(Value::String(kind)) => kind.as_str(),
+            // `["string", "null"]` and the like
+            Some(Value::Array(kinds)) => kinds
+                .iter()
+                .filter_map(Value::as_str)
+                .find(|k| *k != "null")
+                .unwrap_or("null"),
+            _ if schema.contains_key("properties") => "object",
+            _ if schema.contains_key("items") => "array",
+            _ => return Ok(Value::Null),
+        };
+        Ok(match kind {
+            "object" => {
+                let mut object = Map::new();
+                if let Some(properties) = schema.get("properties").and_then(Value::as_object) {
+                    for (key, property) in properties {
+                        object.insert(key.clone(), self.value(property, Some(key), depth + 1)?);
+                    }
+                }
+                Value::Object(object)
+            }
+            "array" => {
+                let count = schema
+                    .get("minItems")
+                    .and_then(Value::as_u64)
+                    .unwrap_or(1)
+                    .clamp(1, 10);
+                let item = match schema.get("items") {
+                    Some(items) => self.value(items, singular(name).as_deref(), depth + 1)?,
+                    None => Value::Null,
+                };
+                Value::Array(vec![item; count as usize])
+            }
+            "string" => Value::String(string_example(schema, name)),
+            "integer" => json!(number_example(schema, name, true) as i64),
+            "number" => json!(number_example(schema, name, false)),
+            "boolean" => Value::Bool(true),
+            _ => Value::Null,
+        })
+    }
+}
+
+/// `tags` -> `tag`, so items of a list are named like one of them.
+fn singular(name: Option<&str>) -> Option<String> {
+    name.map(|n| n.strip_suffix('s').unwrap_or(n).to_string())
+}
+
+fn string_example(schema: &Map<String, Value>, name: Option<&str>) -> String {
+    let by_format = match schema.get("format").and_then(Value::as_str) {
+        Some("date-time") => Some("2024-01-15T09:30:00Z"),
+        Some("date") => Some("2024-01-15"),
+        Some("time") => Some("09:30:00"),
+        Some("email") => Some("jane.doe@example.com"),
+        Some("uuid") => Some("3fa85f64-5717-4562-b3fc-2c963f66afa6"),
+        Some("uri") | Some("url") => Some("https://example.com"),
+        Some("hostname") => Some("example.com"),
+        Some("ipv4") => Some("192.0.2.1"),
+        Some("ipv6") => Some("2001:db8::1"),
+        Some("password") => Some("s3cret-Passw0rd"),
+        Some("byte") => Some("aGVsbG8="),
+        _ => None,
+    };
+    let name = name.unwrap_or_default().to_lowercase();
+    let by_name = || {
+        Some(match name.as_str() {
+            n if n.contains("email") => "jane.doe@example.com",
+            n if n.ends_with("url") || n.ends_with("uri") || n == "website" => {
+                "https://example.com"
+            }
+            n if n.contains("phone") => "+1-202-555-0123",
+            "first_name" | "firstname" | "given_name" => "Jane",
+            "last_name" | "lastname" | "family_name" | "surname" => "Doe",
+            "name" | "full_name" | "fullname" | "username" | "user" => "Jane Doe",
+            "city" => "Berlin",
+            "country" => "DE",
+            "street" | "address" => "Main Street 1",
+            "zip" | "zipcode" | "postal_code" | "postcode" => "10115",
+            "currency" => "EUR",
+            "title" => "Example title",
+            "description" | "comment" | "note" => "Lorem ipsum dolor sit amet",
+            "status" | "state" => "active",
+            n if n == "id" || n.ends_with("_id") || n.ends_with("id") => "abc123",
+            _ => return None,
+        })
+    };
+    let mut text = by_format.or_else(by_name).unwrap_or("string").to_string();
+    let min = schema.get("minLength").and_then(Value::as_u64).unwrap_or(0) as usize;
+    let max = schema
+        .get("maxLength")
+        .and_then(Value::as_u64)
+        .map(|m| m as usize);
+    while text.chars().count() < min {
+        text.push('x');
+    }
+    if let Some(max) = max {
+        text = text.chars().take(max).collect();
+    }
+    text
+}
+
+fn number_example(schema: &Map<String, Value>, name: Option<&str>, integer: bool) -> f64 {
+    let name = name.unwrap_or_default().to_lowercase();
+    let guess = match name.as_str() {
+        n if n == "id" || n.ends_with("_id") || n.ends_with("id") => 1.0,
+        "age" => 30.0,
+        "year" => 2024.0,
+        n if n.contains("price") || n.contains("amount") || n.contains("total") => 19.99,
+        n if n.contains("count") || n.contains("quantity") || n == "qty" => 2.0,
+        "page" => 1.0,
+        "limit" | "per_page" | "size" => 20.0,
+        "latitude" | "lat" => 52.52,
+        "longitude" | "lng" | "lon" => 13.405,
+        _ => 1.0,
+    };
+    let min = schema.get("minimum").and_then(Value::as_f64);
+    let max = schema.get("maximum").and_then(Value::as_f64);
+    let value = match (min, max) {
+        (Some(min), _) if guess < min => min,
+        (_, Some(max)) if guess > max => max,
+        _ => guess,
+    };
+    if integer {
+        value.round()
+    } else {
+        value
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_example_from_openapi_component() {
+        let document = json!({
+            "openapi": "3.0.0",
+            "components": { "schemas": {
+                "Tag": { "type": "string", "enum": ["new", "sale"] },
+                "Product": {
+                    "type": "object",
+                    "required": ["name"],
+                    "properties": {
+                        "id": { "type": "integer", "format": "int64" },
+                        "name": { "type": "string" },
+                        "price": { "type": "number", "minimum": 50 },
+                        "contact": { "type": "string", "format": "email" },
+                        "tags": { "type": "array", "items": { "$ref": "#/components/schemas/Tag" } },
+                        "created": { "type": ["string", "null"], "format": "date-time" },
+                        "code": { "type": "string", "maxLength": 3 },
+                        "stock": { "allOf": [{ "properties": { "warehouse": { "type": "string", "example": "north" } } }] },
+                        "parent": { "$ref": "#/components/schemas/Product" }
+                    }
+                }
+            }}
+        });
+        let schema = component(&document, "Product").unwrap();
+        let body = example(schema, &document).unwrap();
+        assert_eq!(body["id"], json!(1));
+        assert_eq!(body["name"], json!("Jane Doe"));
+        assert_eq!(body["price"], json!(50.0));
+        assert_eq!(body["contact"], json!("jane.doe@example.com"));
+        assert_eq!(body["tags"], json!(["new"]));
+        assert_eq!(body["created"], json!("2024-01-15T09:30:00Z"));
+        assert_eq!(body["code"], json!("str"));
+        assert_eq!(body["stock"], json!({ "warehouse": "north" }));
+        // The recursion stops instead of overflowing
+        assert!(body["parent"]["parent"].is_object());
+
+        let error = example(
+            &json!({ "$ref": "#/components/schemas/Missing" }),
+            &document,
+        );
+        assert!(error.unwrap_err().contains("points to nothing"));
+        assert!(component(&document, "Missing").is_none());
+    }
+}
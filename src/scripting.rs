@@ -0,0 +1,559 @@
+use base64::Engine;
+use boa_engine::{js_string, Context, JsResult, JsValue, NativeFunction, Source};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+
+use crate::{db::DbPool, environments};
+
+type NativeFn = fn(&JsValue, &[JsValue], &mut Context) -> JsResult<JsValue>;
+
+// Keeps a runaway script from hanging the execution
+const LOOP_ITERATION_LIMIT: u64 = 1_000_000;
+const RECURSION_LIMIT: usize = 512;
+
+/// The `jsl` object shared by all scripts. Inputs are passed in as JSON in
+/// `__jsl_state` and results read back the same way, so the Rust side never
+/// touches JS objects.
+const PRELUDE: &str = r#"
+var __jsl = (function (state) {
+    var env = state.environment;
+    var changes = {};
+    var logs = [];
+    function text(value) {
+        if (value === undefined || value === null) return '';
+        return typeof value === 'object' ? JSON.stringify(value) : String(value);
+    }
+    function format(args) {
+        return Array.prototype.map.call(args, text).join(' ');
+    }
+    var jsl = {
+        environment: {
+            get: function (name) {
+                return Object.prototype.hasOwnProperty.call(env, name) ? env[name] : undefined;
+            },
+            has: function (name) { return Object.prototype.hasOwnProperty.call(env, name); },
+            set: function (name, value) {
+                env[name] = text(value);
+                changes[name] = env[name];
+            },
+            unset: function (name) {
+                delete env[name];
+                changes[name] = null;
+            },
+            toObject: function () { return Object.assign({}, env); }
+        },
+        crypto: {
+            sha256: __jsl_sha256,
+            hmacSha256: __jsl_hmac_sha256,
+            base64: __jsl_base64
+        }
+    };
+    var console = {
+        log: function () { logs.push(format(arguments)); },
+        info: function () { logs.push(format(arguments)); },
+        warn: function () { logs.push('warn: ' + format(arguments)); },
+        error: function () { logs.push('error: ' + format(arguments)); }
+    };
+    return {
+        jsl: jsl,
+        console: console,
+        state: state,
+        text: text,
+        changes: changes,
+        logs: logs
+    };
+})(__jsl_state);
+var jsl = __jsl.jsl;
+var console = __jsl.console;
+"#;
+
+/// Adds `jsl.request` and `jsl.abort` for pre-request scripts.
+const PRE_REQUEST_PRELUDE: &str = r#"
+(function (jsl, text) {
+    var request = __jsl.state.request;
+    var abortSignal = {};
+    request.setHeader = function (name, value) {
+        request.removeHeader(name);
+        request.headers[String(name)] = text(value);
+    };
+    request.removeHeader = function (name) {
+        var lower = String(name).toLowerCase();
+        Object.keys(request.headers).forEach(function (key) {
+            if (key.toLowerCase() === lower) delete request.headers[key];
+        });
+    };
+    jsl.request = request;
+    jsl.abort = function (reason) {
+        __jsl.state.aborted = text(reason) || 'Aborted by pre-request script';
+        throw abortSignal;
+    };
+    __jsl.aborted = function () {
+        return __jsl.state.aborted === undefined ? null : __jsl.state.aborted;
+    };
+    __jsl.result = function () {
+        var headers = {};
+        Object.keys(request.headers).forEach(function (key) {
+            headers[key] = text(request.headers[key]);
+        });
+        return JSON.stringify({
+            request: {
+                method: text(request.method),
+                url: text(request.url),
+                headers: headers,
+                body: request.body === undefined || request.body === null ? null : text(request.body)
+            },
+            environment: __jsl.changes,
+            logs: __jsl.logs
+        });
+    };
+})(jsl, __jsl.text);
+"#;
+
+/// Adds `jsl.response`, `jsl.test` and `jsl.expect` for test scripts.
+const TEST_PRELUDE: &str = r#"
+(function (jsl, text) {
+    var response = __jsl.state.response;
+    var results = [];
+    var parsed;
+    response.json = function () {
+        if (parsed === undefined) parsed = JSON.parse(response.body);
+        return parsed;
+    };
+    response.header = function (name) {
+        var lower = String(name).toLowerCase();
+        var key = Object.keys(response.headers).filter(function (k) {
+            return k.toLowerCase() === lower;
+        })[0];
+        return key === undefined ? undefined : response.headers[key];
+    };
+    function show(value) {
+        if (value === undefined) return 'undefined';
+        if (typeof value === 'function') return String(value);
+        try { return JSON.stringify(value); } catch (e) { return String(value); }
+    }
+    function expect(actual, negate) {
+        function check(pass, verb, expected, hasExpected) {
+            if (Boolean(pass) === negate) {
+                throw new Error('expected ' + show(actual) + (negate ? ' not ' : ' ') + verb +
+                    (hasExpected ? ' ' + show(expected) : ''));
+            }
+        }
+        var matchers = {
+            toBe: function (e) { check(actual === e, 'to be', e, true); },
+            toEqual: function (e) { check(JSON.stringify(actual) === JSON.stringify(e), 'to equal', e, true); },
+            toBeTruthy: function () { check(actual, 'to be truthy'); },
+            toBeFalsy: function () { check(!actual, 'to be falsy'); },
+            toBeDefined: function () { check(actual !== undefined, 'to be defined'); },
+            toBeNull: function () { check(actual === null, 'to be null'); },
+            toBeGreaterThan: function (e) { check(actual > e, 'to be greater than', e, true); },
+            toBeLessThan: function (e) { check(actual < e, 'to be less than', e, true); },
+            toContain: function (e) {
+                check(actual !== null && actual !== undefined && actual.indexOf(e) >= 0, 'to contain', e, true);
+            },
+            toMatch: function (e) { check(new RegExp(e).test(String(actual)), 'to match', String(e), true); },
+            toHaveProperty: function (e) {
+                check(actual !== null && actual !== undefined && Object.prototype.hasOwnProperty.call(Object(actual), e),
+                    'to have property', e, true);
+            }
+        };
+        if (!negate) matchers.not = expect(actual, true);
+        return matchers;
+    }
+    jsl.response = response;
+    jsl.expect = function (actual) { return expect(actual, false); };
+    jsl.test = function (name, fn) {
+        try {
+            fn();
+            results.push({ name: text(name), passed: true, error: null });
+        } catch (e) {
+            var message = e instanceof Error ? e.message : text(e);
+            results.push({ name: text(name), passed: false, error: message });
+        }
+    };
+    __jsl.result = function () {
+        return JSON.stringify({ results: results, environment: __jsl.changes, logs: __jsl.logs });
+    };
+})(jsl, __jsl.text);
+"#;
+
+/// The parts of a request a script may read and change.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ScriptRequest {
+    pub method: String,
+    pub url: String,
+    pub headers: HashMap<String, String>,
+    pub body: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ScriptOutcome {
+    pub request: ScriptRequest,
+    /// Variables set by the script, `None` for those it removed
+    pub environment: HashMap<String, Option<String>>,
+    /// Output of `console.log` and friends
+    pub logs: Vec<String>,
+}
+
+/// The response a test script runs against.
+#[derive(Debug, Serialize)]
+pub struct ScriptResponse {
+    pub status: u16,
+    pub headers: HashMap<String, String>,
+    pub body: String,
+    /// Milliseconds from sending the request to receiving the full body
+    pub time: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TestResult {
+    pub name: String,
+    pub passed: bool,
+    #[serde(default)]
+    pub error: Option<String>,
+}
+
+impl TestResult {
+    /// Stands in for the tests when the script itself fails.
+    pub fn script_failure(error: String) -> Self {
+        TestResult {
+            name: "Test script".to_string(),
+            passed: false,
+            error: Some(error),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TestOutcome {
+    pub results: Vec<TestResult>,
+    pub environment: HashMap<String, Option<String>>,
+    pub logs: Vec<String>,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ScriptError {
+    /// The script called `jsl.abort(reason)`
+    Aborted(String),
+    Failed(String),
+}
+
+fn js_error(e: boa_engine::JsError) -> ScriptError {
+    ScriptError::Failed(format!("Script error: {}", e))
+}
+
+/// Runs a pre-request script on a blocking thread, as boa contexts are
+/// neither `Send` nor async.
+pub async fn run_pre_request(
+    script: String,
+    request: ScriptRequest,
+    environment: HashMap<String, String>,
+) -> Result<ScriptOutcome, ScriptError> {
+    tokio::task::spawn_blocking(move || run(&script, &request, &environment))
+        .await
+        .map_err(|e| ScriptError::Failed(format!("Script did not finish: {}", e)))?
+}
+
+/// Runs a test script on a blocking thread.
+pub async fn run_test_script(
+    script: String,
+    response: ScriptResponse,
+    environment: HashMap<String, String>,
+) -> Result<TestOutcome, ScriptError> {
+    tokio::task::spawn_blocking(move || run_tests(&script, &response, &environment))
+        .await
+        .map_err(|e| ScriptError::Failed(format!("Script did not finish: {}", e)))?
+}
+
+/// A context with the limits, native helpers and preludes set up.
+fn context(state: Value, prelude: &str) -> Result<Context, ScriptError> {
+    let mut context = Context::default();
+    context
+        .runtime_limits_mut()
+        .set_loop_iteration_limit(LOOP_ITERATION_LIMIT);
+    context
+        .runtime_limits_mut()
+        .set_recursion_limit(RECURSION_LIMIT);
+
+    let natives: [(&str, usize, NativeFn); 3] = [
+        ("__jsl_sha256", 1, sha256),
+        ("__jsl_hmac_sha256", 2, hmac_sha256),
+        ("__jsl_base64", 1, base64_encode),
+    ];
+    for (name, length, function) in natives {
+        context
+            .register_global_builtin_callable(
+                js_string!(name),
+                length,
+                NativeFunction::from_fn_ptr(function),
+            )
+            .map_err(js_error)?;
+    }
+
+    context
+        .eval(Source::from_bytes(&format!("var __jsl_state = {};", state)))
+        .map_err(js_error)?;
+    for source in [PRELUDE, prelude] {
+        context.eval(Source::from_bytes(source)).map_err(js_error)?;
+    }
+    Ok(context)
+}
+
+fn eval_string(context: &mut Context, source: &str) -> Result<String, ScriptError> {
+    Ok(context
+        .eval(Source::from_bytes(source))
+        .map_err(js_error)?
+        .to_string(context)
+        .map_err(js_error)?
+        .to_std_string_escaped())
+}
+
+fn read_result<T: serde::de::DeserializeOwned>(context: &mut Context) -> Result<T, ScriptError> {
+    let result = eval_string(context, "__jsl.result()")?;
+    serde_json::from_str(&result)
+        .map_err(|e| ScriptError::Failed(format!("Invalid script result: {}", e)))
+}
+
+pub fn run(
+    script: &str,
+    request: &ScriptRequest,
+    environment: &HashMap<String, String>,
+) -> Result<ScriptOutcome, ScriptError> {
+    let state = serde_json::json!({ "request": request, "environment": environment });
+    let mut context = context(state, PRE_REQUEST_PRELUDE)?;
+
+    if let Err(e) = context.eval(Source::from_bytes(script)) {
+        let aborted = context
+            .eval(Source::from_bytes("__jsl.aborted()"))
+            .map_err(js_error)?;
+        if !aborted.is_null() {
+            let reason = aborted
+                .to_string(&mut context)
+                .map_err(js_error)?
+                .to_std_string_escaped();
+            return Err(ScriptError::Aborted(reason));
+        }
+        return Err(js_error(e));
+    }
+    read_result(&mut context)
+}
+
+/// Runs a test script. An error outside of `jsl.test` ends the script and
+/// is reported as a failed result after the tests that already ran.
+pub fn run_tests(
+    script: &str,
+    response: &ScriptResponse,
+    environment: &HashMap<String, String>,
+) -> Result<TestOutcome, ScriptError> {
+    let state = serde_json::json!({ "response": response, "environment": environment });
+    let mut context = context(state, TEST_PRELUDE)?;
+
+    let failure = context.eval(Source::from_bytes(script)).err();
+    let mut outcome: TestOutcome = read_result(&mut context)?;
+    if let Some(e) = failure {
+        outcome
+            .results
+            .push(TestResult::script_failure(format!("Script error: {}", e)));
+    }
+    Ok(outcome)
+}
+
+/// Applies the script's variable changes to the stored environment.
+pub async fn store_environment(
+    pool: &DbPool,
+    environment_id: i64,
+    changes: &HashMap<String, Option<String>>,
+) -> Result<(), sqlx::Error> {
+    environments::store_values(pool, environment_id, changes).await
+}
+
+fn string_arg(args: &[JsValue], index: usize, context: &mut Context) -> JsResult<String> {
+    Ok(args
+        .get(index)
+        .cloned()
+        .unwrap_or_default()
+        .to_string(context)?
+        .to_std_string_escaped())
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn sha256(_this: &JsValue, args: &[JsValue], context: &mut Context) -> JsResult<JsValue> {
+    let text = string_arg(args, 0, context)?;
+    Ok(JsValue::from(js_string!(hex(&Sha256::digest(
+        text.as_bytes()
+    )))))
+}
+
+fn hmac_sha256(_this: &JsValue, args: &[JsValue], context: &mut Context) -> JsResult<JsValue> {
+    let key = string_arg(args, 0, context)?;
+    let text = string_arg(args, 1, context)?;
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(key.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(text.as_bytes());
+    Ok(JsValue::from(js_string!(hex(&mac.finalize().into_bytes()))))
+}
+
+fn base64_encode(_this: &JsValue, args: &[JsValue], context: &mut Context) -> JsResult<JsValue> {
+    let text = string_arg(args, 0, context)?;
+    Ok(JsValue::from(js_string!(
+        base64::engine::general_purpose::STANDARD.encode(text.as_bytes())
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request() -> ScriptRequest {
+        ScriptRequest {
+            method: "GET".to_string(),
+            url: "https://api.example.com/{{path}}".to_string(),
+            headers: HashMap::from([("Accept".to_string(), "text/plain".to_string())]),
+            body: None,
+        }
+    }
+
+    #[test]
+    fn test_script_changes_request_and_environment() {
+        let environment = HashMap::from([
+            ("secret".to_string(), "key".to_string()),
+            ("stale".to_string(), "x".to_string()),
+        ]);
+        let script = r#"
+            var signature = jsl.crypto.hmacSha256(jsl.environment.get('secret'),
+                'The quick brown fox jumps over the lazy dog');
+            jsl.request.setHeader('accept', 'application/json');
+            jsl.request.headers['X-Signature'] = signature;
+            jsl.request.method = 'POST';
+            jsl.request.body = { signed: true };
+            jsl.environment.set('count', 3);
+            jsl.environment.unset('stale');
+            console.log('signed', jsl.environment.has('stale'));
+        "#;
+        let outcome = run(script, &request(), &environment).unwrap();
+
+        assert_eq!(outcome.request.method, "POST");
+        assert_eq!(outcome.request.url, "https://api.example.com/{{path}}");
+        assert_eq!(
+            outcome.request.headers,
+            HashMap::from([
+                ("accept".to_string(), "application/json".to_string()),
+                (
+                    "X-Signature".to_string(),
+                    "f7bc83f430538424b13298e6aa6fb143ef4d59a14946175997479dbc2d1a3cd8".to_string()
+                ),
+            ])
+        );
+        assert_eq!(outcome.request.body.as_deref(), Some(r#"{"signed":true}"#));
+        assert_eq!(
+            outcome.environment,
+            HashMap::from([
+                ("count".to_string(), Some("3".to_string())),
+                ("stale".to_string(), None),
+            ])
+        );
+        assert_eq!(outcome.logs, vec!["signed false"]);
+    }
+
+    #[test]
+    fn test_script_abort_and_errors() {
+        let environment = HashMap::new();
+        assert_eq!(
+            run(
+                "if (!jsl.environment.get('token')) jsl.abort('Log in first');",
+                &request(),
+                &environment
+            )
+            .unwrap_err(),
+            ScriptError::Aborted("Log in first".to_string())
+        );
+        assert!(matches!(
+            run("undefinedFunction();", &request(), &environment),
+            Err(ScriptError::Failed(_))
+        ));
+        assert!(matches!(
+            run("while (true) {}", &request(), &environment),
+            Err(ScriptError::Failed(_))
+        ));
+        assert_eq!(
+            run(
+                "jsl.environment.set('b64', jsl.crypto.base64('hi')); jsl.environment.set('sha', jsl.crypto.sha256(''));",
+                &request(),
+                &environment
+            )
+            .unwrap()
+            .environment,
+            HashMap::from([
+                ("b64".to_string(), Some("aGk=".to_string())),
+                (
+                    "sha".to_string(),
+                    Some(
+                        "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+                            .to_string()
+                    )
+                ),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_tests_report_each_assertion() {
+        let response = ScriptResponse {
+            status: 201,
+            headers: HashMap::from([("Content-Type".to_string(), "application/json".to_string())]),
+            body: r#"{"id": 5, "tags": ["a", "b"]}"#.to_string(),
+            time: 42,
+        };
+        let script = r#"
+            jsl.test('created', () => jsl.expect(jsl.response.status).toBe(201));
+            jsl.test('json', () => {
+                var body = jsl.response.json();
+                jsl.expect(body).toHaveProperty('id');
+                jsl.expect(body.tags).toContain('b');
+                jsl.expect(body.tags).toEqual(['a', 'b']);
+                jsl.expect(jsl.response.header('content-type')).toMatch(/json/);
+            });
+            jsl.test('fast', () => jsl.expect(jsl.response.time).toBeLessThan(1000));
+            jsl.test('not found', () => jsl.expect(jsl.response.status).not.toBe(201));
+            jsl.environment.set('id', jsl.response.json().id);
+        "#;
+        let outcome = run_tests(script, &response, &HashMap::new()).unwrap();
+
+        let passed: Vec<(&str, bool)> = outcome
+            .results
+            .iter()
+            .map(|r| (r.name.as_str(), r.passed))
+            .collect();
+        assert_eq!(
+            passed,
+            vec![
+                ("created", true),
+                ("json", true),
+                ("fast", true),
+                ("not found", false)
+            ]
+        );
+        assert!(outcome.results[3].error.is_some());
+        assert_eq!(
+            outcome.environment,
+            HashMap::from([("id".to_string(), Some("5".to_string()))])
+        );
+
+        // Results before a script error are kept
+        let outcome = run_tests(
+            "jsl.test('first', () => {}); undefinedFunction();",
+            &response,
+            &HashMap::new(),
+        )
+        .unwrap();
+        assert_eq!(outcome.results.len(), 2);
+        assert!(outcome.results[0].passed);
+        assert_eq!(outcome.results[1].name, "Test script");
+        assert!(!outcome.results[1].passed);
+    }
+}
@@ -0,0 +1,356 @@
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{OnceLock, RwLock};
+use std::time::Duration;
+
+/// The newest release on GitHub.
+const RELEASES_URL: &str = "https://api.github.com/repos/MKJSM/js-link/releases/latest";
+/// How often the server looks for a newer release.
+const CHECK_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+/// Checksums of every asset of a release, as written by `sha256sum`.
+const CHECKSUMS_ASSET: &str = "SHA256SUMS";
+
+static AVAILABLE: OnceLock<RwLock<Option<AvailableUpdate>>> = OnceLock::new();
+
+#[derive(Debug, Deserialize)]
+struct Release {
+    tag_name: String,
+    html_url: String,
+    #[serde(default)]
+    assets: Vec<Asset>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Asset {
+    name: String,
+    browser_download_url: String,
+}
+
+/// A release newer than the running binary.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AvailableUpdate {
+    pub version: String,
+    pub url: String,
+}
+
+/// `JSLINK_UPDATE_URL` points at a mirror of the latest-release API.
+fn releases_url() -> String {
+    env::var("JSLINK_UPDATE_URL").unwrap_or_else(|_| RELEASES_URL.to_string())
+}
+
+/// Name of the release binary built for this platform.
+fn asset_name() -> String {
+    format!(
+        "js-link-{}{}",
+        env!("JSLINK_TARGET"),
+        env::consts::EXE_SUFFIX
+    )
+}
+
+fn parse_version(version: &str) -> Option<(u64, u64, u64)> {
+    let version = version.trim().trim_start_matches('v');
+    // Pre-releases and build metadata are not offered as updates
+    if version.contains(['-', '+']) {
+        return None;
+    }
+    let mut parts = version.split('.').map(|p| p.parse::<u64>().ok());
+    let version = (parts.next()??, parts.next()??, parts.next()??);
+    parts.next().is_none().then_some(version)
+}
+
+fn is_newer(candidate: &str, current: &str) -> bool {
+    match (parse_version(candidate), parse_version(current)) {
+        (Some(candidate), Some(current)) => candidate > current,
+        _ => false,
+    }
+}
+
+fn client() -> Result<reqwest::Client, String> {
+    // GitHub refuses API calls without a user agent
+    reqwest::Client::builder()
+        .user_agent(concat!("js-link/", env!("CARGO_PKG_VERSION")))
+        .timeout(Duration::from_secs(300))
+        .build()
+        .map_err(|e| e.to_string())
+}
+
+async fn fetch_release(client: &reqwest::Client, url: &str) -> Result<Release, String> {
+    let body = client
+        .get(url)
+        .header("accept", "application/vnd.github+json")
+        .send()
+        .await
+        .and_then(|r| r.error_for_status())
+        .map_err(|e| format!("Could not look up the latest release: {}", e))?
+        .bytes()
+        .await
+        .map_err(|e| format!("Could not look up the latest release: {}", e))?;
+    serde_json::from_slice(&body).map_err(|e| format!("Unexpected answer from {}: {}", url, e))
+}
+
+async fn download(client: &reqwest::Client, url: &str) -> Result<Vec<u8>, String> {
+    let bytes = client
+        .get(url)
+        .send()
+        .await
+        .and_then(|r| r.error_for_status())
+        .map_err(|e| format!("Could not download {}: {}", url, e))?
+        .bytes()
+        .await
+        .map_err(|e| format!("Could not download {}: {}", url, e))?;
+    Ok(bytes.to_vec())
+}
+
+/// The expected SHA-256 of `asset`, from `SHA256SUMS` or `<asset>.sha256`.
+async fn expected_checksum(
+    client: &reqwest::Client,
+    release: &Release,
+    asset: &str,
+) -> Result<String, String> {
+    let single = format!("{}.sha256", asset);
+    for candidate in release
+        .assets
+        .iter()
+        .filter(|a| a.name == CHECKSUMS_ASSET || a.name == single)
+    {
+        let text = download(client, &candidate.browser_download_url).await?;
+        let text = String::from_utf8_lossy(&text);
+        for line in text.lines() {
+            let mut fields = line.split_whitespace();
+            let (Some(hash), name) = (fields.next(), fields.next()) else {
+                continue;
+            };
+            // `sha256sum` marks binary mode with a leading `*`
+            let name = name.map(|n| n.trim_start_matches('*'));
+            if name.is_none_or(|n| n == asset) && hash.len() == 64 {
+                return Ok(hash.to_lowercase());
+            }
+        }
+    }
+    Err(format!(
+        "Release {} publishes no checksum for {}, not installing it",
+        release.tag_name, asset
+    ))
+}
+
+/// Writes `binary` next to `exe` and moves it into place, so the executable
+/// is never half written.
+fn replace_executable(exe: &Path, binary: &[u8]) -> Result<(), String> {
+    let staged = exe.with_extension("new");
+    fs::write(&staged, binary).map_err(|e| format!("Cannot write {}: {}", staged.display(), e))?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = fs::metadata(exe)
+            .map(|m| m.permissions().mode())
+            .unwrap_or(0o755);
+        fs::set_permissions(&staged, fs::Permissions::from_mode(mode))
+            .map_err(|e| format!("Cannot make {} executable: {}", staged.display(), e))?;
+    }
+    // A running executable cannot be overwritten on Windows, but renamed
+    #[cfg(windows)]
+    {
+        let old = exe.with_extension("old");
+        let _ = fs::remove_file(&old);
+        fs::rename(exe, &old).map_err(|e| format!("Cannot move {}: {}", exe.display(), e))?;
+    }
+    fs::rename(&staged, exe).map_err(|e| {
+        let _ = fs::remove_file(&staged);
+        format!("Cannot replace {}: {}", exe.display(), e)
+    })
+}
+
+/// Looks for a newer release; `None` when the running one is the latest.
+async fn check(client: &reqwest::Client, url: &str) -> Result<Option<Release>, String> {
+    let release = fetch_release(client, url).await?;
+    Ok(is_newer(&release.tag_name, env!("CARGO_PKG_VERSION")).then_some(release))
+}
+
+async fn install(client: &reqwest::Client, release: &Release, exe: &Path) -> Result<(), String> {
+    let name = asset_name();
+    let asset = release
+        .assets
+        .iter()
+        .find(|a| a.name == name)
+        .ok_or_else(|| {
+            format!(
+                "Release {} has no build for this platform ({})",
+                release.tag_name, name
+            )
+        })?;
+    let expected = expected_checksum(client, release, &name).await?;
+    let binary = download(client, &asset.browser_download_url).await?;
+    let actual = format!("{:x}", Sha256::digest(&binary));
+    if actual != expected {
+        return Err(format!(
+            "Checksum mismatch for {}: expected {}, got {}",
+            name, expected, actual
+        ));
+    }
+    replace_executable(exe, &binary)
+}
+
+fn current_exe() -> Result<PathBuf, String> {
+    env::current_exe()
+        .and_then(|p| p.canonicalize())
+        .map_err(|e| format!("Cannot locate the running executable: {}", e))
+}
+
+async fn update(check_only: bool) -> Result<(), String> {
+    let client = client()?;
+    let Some(release) = check(&client, &releases_url()).await? else {
+        println!(
+            "js-link {} is the latest version.",
+            env!("CARGO_PKG_VERSION")
+        );
+        return Ok(());
+    };
+    println!(
+        "js-link {} is available (running {}): {}",
+        release.tag_name.trim_start_matches('v'),
+        env!("CARGO_PKG_VERSION"),
+        release.html_url
+    );
+    if check_only {
+        return Ok(());
+    }
+    let exe = current_exe()?;
+    install(&client, &release, &exe).await?;
+    println!("Updated {}, restart js-link to use it.", exe.display());
+    Ok(())
+}
+
+/// `js-link self-update`. Returns the process exit code.
+pub async fn run(check_only: bool) -> i32 {
+    match update(check_only).await {
+        Ok(()) => 0,
+        Err(e) => {
+            eprintln!("{}", e);
+            1
+        }
+    }
+}
+
+fn available() -> &'static RwLock<Option<AvailableUpdate>> {
+    AVAILABLE.get_or_init(|| RwLock::new(None))
+}
+
+/// The newer release found by the last background check, if any.
+pub fn available_update() -> Option<AvailableUpdate> {
+    available().read().ok().and_then(|a| a.clone())
+}
+
+/// Checks for a newer release now and once a day, unless
+/// `JSLINK_UPDATE_CHECK=false`.
+pub fn spawn_checker() {
+    if env::var("JSLINK_UPDATE_CHECK").is_ok_and(|v| v == "false" || v == "0") {
+        log::info!("Update check disabled");
+        return;
+    }
+    tokio::spawn(async move {
+        let client = match client() {
+            Ok(client) => client,
+            Err(e) => {
+                log::warn!("Update check disabled: {}", e);
+                return;
+            }
+        };
+        let url = releases_url();
+        let mut ticker = tokio::time::interval(CHECK_INTERVAL);
+        loop {
+            ticker.tick().await;
+            match check(&client, &url).await {
+                Ok(release) => {
+                    let update = release.map(|r| AvailableUpdate {
+                        version: r.tag_name.trim_start_matches('v').to_string(),
+                        url: r.html_url,
+                    });
+                    if let Some(update) = &update {
+                        log::info!("js-link {} is available: {}", update.version, update.url);
+                    }
+                    if let Ok(mut available) = available().write() {
+                        *available = update;
+                    }
+                }
+                Err(e) => log::debug!("Update check failed: {}", e),
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use httpmock::prelude::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_versions() {
+        assert!(is_newer("v99.0.0", env!("CARGO_PKG_VERSION")));
+        assert!(is_newer("1.10.0", "1.9.3"));
+        assert!(!is_newer("1.9.3", "1.9.3"));
+        assert!(!is_newer("v2.0.0-rc.1", "1.0.0"));
+        assert!(!is_newer("latest", "1.0.0"));
+    }
+
+    #[tokio::test]
+    async fn test_install_verifies_checksum() {
+        let server = MockServer::start_async().await;
+        let binary = b"new js-link build".to_vec();
+        let hash = format!("{:x}", Sha256::digest(&binary));
+        let name = asset_name();
+        server.mock(|when, then| {
+            when.path("/latest");
+            then.json_body(json!({
+                "tag_name": "v99.0.0",
+                "html_url": "https://github.com/MKJSM/js-link/releases/tag/v99.0.0",
+                "assets": [
+                    {"name": name, "browser_download_url": server.url("/binary")},
+                    {"name": "SHA256SUMS", "browser_download_url": server.url("/sums")},
+                ]
+            }));
+        });
+        server.mock(|when, then| {
+            when.path("/binary");
+            then.body(binary.clone());
+        });
+        let mut sums = server.mock(|when, then| {
+            when.path("/sums");
+            then.body(format!(
+                "{}  other-asset\n{} *{}\n",
+                "0".repeat(64),
+                hash,
+                name
+            ));
+        });
+
+        let dir = env::temp_dir().join(format!("jslink-update-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let exe = dir.join("js-link");
+        fs::write(&exe, b"old build").unwrap();
+
+        let client = client().unwrap();
+        let release = check(&client, &server.url("/latest"))
+            .await
+            .unwrap()
+            .unwrap();
+        install(&client, &release, &exe).await.unwrap();
+        assert_eq!(fs::read(&exe).unwrap(), binary);
+        sums.assert();
+
+        // A tampered download leaves the executable alone
+        sums.delete();
+        server.mock(|when, then| {
+            when.path("/sums");
+            then.body(format!("{} *{}\n", "1".repeat(64), name));
+        });
+        fs::write(&exe, b"old build").unwrap();
+        let error = install(&client, &release, &exe).await.unwrap_err();
+        assert!(error.contains("Checksum mismatch"));
+        assert_eq!(fs::read(&exe).unwrap(), b"old build");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
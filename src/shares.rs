@@ -0,0 +1,534 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::{Html, IntoResponse, Response},
+    routing::{delete, get, post},
+    Json, Router,
+};
+use chrono::{DateTime, Duration, NaiveDateTime, Utc};
+use sailfish::TemplateOnce;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use crate::{
+    api_tokens,
+    db::DbPool,
+    docs::{self, DocsError},
+    environments,
+    executor::{self, ExecuteRequestPayload, ExecuteResponse, ExecutorError},
+    i18n,
+};
+
+// Expiring links showing a request or folder read-only to someone without
+// the collection, optionally with a button to send it against a fixed
+// environment. The pages are served outside /api, the token in the path is
+// the only credential.
+
+/// Share tokens start with this, to tell them from API tokens.
+const TOKEN_PREFIX: &str = "jss_";
+/// Characters of a token kept in the clear to tell links apart.
+const SHOWN_PREFIX_LEN: usize = 12;
+const DEFAULT_EXPIRY_HOURS: i64 = 7 * 24;
+const MAX_EXPIRY_HOURS: i64 = 30 * 24;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ShareLink {
+    pub id: i64,
+    pub prefix: String,
+    pub request_id: Option<i64>,
+    pub folder_id: Option<i64>,
+    pub allow_execute: bool,
+    pub environment_id: Option<i64>,
+    pub expires_at: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+struct ShareLinkDb {
+    id: i64,
+    prefix: String,
+    request_id: Option<i64>,
+    folder_id: Option<i64>,
+    allow_execute: bool,
+    environment_id: Option<i64>,
+    expires_at: NaiveDateTime,
+    created_at: NaiveDateTime,
+    revoked_at: Option<NaiveDateTime>,
+}
+
+impl From<ShareLinkDb> for ShareLink {
+    fn from(s: ShareLinkDb) -> Self {
+        Self {
+            id: s.id,
+            prefix: s.prefix,
+            request_id: s.request_id,
+            folder_id: s.folder_id,
+            allow_execute: s.allow_execute,
+            environment_id: s.environment_id,
+            expires_at: s.expires_at.and_utc(),
+            created_at: s.created_at.and_utc(),
+            revoked_at: s.revoked_at.map(|at| at.and_utc()),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct CreateShare {
+    /// Either a request or a folder is shared
+    #[serde(default)]
+    request_id: Option<i64>,
+    #[serde(default)]
+    folder_id: Option<i64>,
+    /// Hours until the link stops working, at most 30 days
+    #[serde(default)]
+    expires_in_hours: Option<i64>,
+    /// Whether visitors may send the requests, against `environment_id`
+    #[serde(default)]
+    allow_execute: bool,
+    #[serde(default)]
+    environment_id: Option<i64>,
+}
+
+/// A new link; `token` and `url` are shown this once and cannot be fetched
+/// again.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CreatedShare {
+    #[serde(flatten)]
+    pub info: ShareLink,
+    pub token: String,
+    /// Path of the shared page on this server
+    pub url: String,
+}
+
+#[derive(Debug)]
+pub enum ShareError {
+    InvalidTarget,
+    InvalidExpiry,
+    TargetNotFound,
+    EnvironmentNotFound,
+    ShareNotFound,
+    /// Unknown, expired or revoked link, on the shared pages
+    LinkNotFound,
+    ExecutionNotAllowed,
+    Docs(DocsError),
+    Execution(ExecutorError),
+    Render(sailfish::RenderError),
+    DatabaseError(sqlx::Error),
+}
+
+impl From<sqlx::Error> for ShareError {
+    fn from(e: sqlx::Error) -> Self {
+        ShareError::DatabaseError(e)
+    }
+}
+
+impl From<sailfish::RenderError> for ShareError {
+    fn from(e: sailfish::RenderError) -> Self {
+        ShareError::Render(e)
+    }
+}
+
+impl From<DocsError> for ShareError {
+    fn from(e: DocsError) -> Self {
+        match e {
+            // The shared request or folder was archived or deleted since
+            DocsError::FolderNotFound | DocsError::RequestNotFound => ShareError::LinkNotFound,
+            e => ShareError::Docs(e),
+        }
+    }
+}
+
+impl IntoResponse for ShareError {
+    fn into_response(self) -> Response {
+        match self {
+            ShareError::InvalidTarget => {
+                (StatusCode::BAD_REQUEST, i18n::t("error.share_target")).into_response()
+            }
+            ShareError::InvalidExpiry => (
+                StatusCode::BAD_REQUEST,
+                i18n::tf("error.share_expiry", &[("max", &MAX_EXPIRY_HOURS)]),
+            )
+                .into_response(),
+            ShareError::TargetNotFound => (
+                StatusCode::NOT_FOUND,
+                i18n::t("error.share_target_not_found"),
+            )
+                .into_response(),
+            ShareError::EnvironmentNotFound => (
+                StatusCode::NOT_FOUND,
+                i18n::t("error.environment_not_found"),
+            )
+                .into_response(),
+            ShareError::ShareNotFound => {
+                (StatusCode::NOT_FOUND, i18n::t("error.share_not_found")).into_response()
+            }
+            ShareError::LinkNotFound => {
+                (StatusCode::NOT_FOUND, i18n::t("error.share_link_invalid")).into_response()
+            }
+            ShareError::ExecutionNotAllowed => {
+                (StatusCode::FORBIDDEN, i18n::t("error.share_read_only")).into_response()
+            }
+            ShareError::Docs(e) => e.into_response(),
+            ShareError::Execution(e) => e.into_response(),
+            ShareError::Render(e) => {
+                log::error!("Failed to render shared page: {}", e);
+                (StatusCode::INTERNAL_SERVER_ERROR, i18n::t("error.internal")).into_response()
+            }
+            ShareError::DatabaseError(e) => {
+                log::error!("Database error: {}", e);
+                (StatusCode::INTERNAL_SERVER_ERROR, i18n::t("error.database")).into_response()
+            }
+        }
+    }
+}
+
+#[derive(TemplateOnce)]
+#[template(path = "share/response.stpl")]
+struct ResponsePage {
+    back: String,
+    response: ExecuteResponse,
+    headers: BTreeMap<String, String>,
+    body: String,
+}
+
+async fn create_share(
+    State(pool): State<DbPool>,
+    Json(payload): Json<CreateShare>,
+) -> Result<impl IntoResponse, ShareError> {
+    let target_exists = match (payload.request_id, payload.folder_id) {
+        (Some(id), None) => sqlx::query_scalar!(
+            r#"SELECT id AS "id!" FROM requests WHERE id = ? AND archived_at IS NULL"#,
+            id
+        )
+        .fetch_optional(&pool)
+        .await?
+        .is_some(),
+        (None, Some(id)) => sqlx::query_scalar!(
+            r#"SELECT id AS "id!" FROM folders WHERE id = ? AND archived_at IS NULL"#,
+            id
+        )
+        .fetch_optional(&pool)
+        .await?
+        .is_some(),
+        _ => return Err(ShareError::InvalidTarget),
+    };
+    if !target_exists {
+        return Err(ShareError::TargetNotFound);
+    }
+    let hours = payload.expires_in_hours.unwrap_or(DEFAULT_EXPIRY_HOURS);
+    if !(1..=MAX_EXPIRY_HOURS).contains(&hours) {
+        return Err(ShareError::InvalidExpiry);
+    }
+    if let Some(environment_id) = payload.environment_id {
+        match environments::load_environment(&pool, environment_id).await {
+            Ok(_) => {}
+            Err(sqlx::Error::RowNotFound) => return Err(ShareError::EnvironmentNotFound),
+            Err(e) => return Err(e.into()),
+        }
+    }
+
+    let token = api_tokens::random_token(TOKEN_PREFIX);
+    let token_hash = api_tokens::hash(&token);
+    let prefix = token[..SHOWN_PREFIX_LEN].to_string();
+    let expires_at = (Utc::now() + Duration::hours(hours)).naive_utc();
+    let created = sqlx::query_as!(
+        ShareLinkDb,
+        r#"INSERT INTO share_links (token_hash, prefix, request_id, folder_id, allow_execute, environment_id, expires_at) VALUES (?, ?, ?, ?, ?, ?, ?)
+        RETURNING id AS "id!", prefix, request_id, folder_id, allow_execute AS "allow_execute: bool", environment_id,
+            expires_at AS "expires_at: NaiveDateTime", created_at AS "created_at: NaiveDateTime", revoked_at AS "revoked_at: NaiveDateTime""#,
+        token_hash,
+        prefix,
+        payload.request_id,
+        payload.folder_id,
+        payload.allow_execute,
+        payload.environment_id,
+        expires_at
+    )
+    .fetch_one(&pool)
+    .await?;
+
+    log::info!(
+        "Created share link {} for {}, expiring in {} hours",
+        created.id,
+        match (created.request_id, created.folder_id) {
+            (Some(id), _) => format!("request {}", id),
+            (_, Some(id)) => format!("folder {}", id),
+            _ => "nothing".to_string(),
+        },
+        hours
+    );
+    let url = format!("/share/{}", token);
+    Ok((
+        StatusCode::CREATED,
+        Json(CreatedShare {
+            info: created.into(),
+            token,
+            url,
+        }),
+    ))
+}
+
+async fn list_shares(State(pool): State<DbPool>) -> Result<impl IntoResponse, ShareError> {
+    let shares = sqlx::query_as!(
+        ShareLinkDb,
+        r#"SELECT id AS "id!", prefix, request_id, folder_id, allow_execute AS "allow_execute: bool", environment_id,
+            expires_at AS "expires_at: NaiveDateTime", created_at AS "created_at: NaiveDateTime", revoked_at AS "revoked_at: NaiveDateTime"
+        FROM share_links ORDER BY id"#
+    )
+    .fetch_all(&pool)
+    .await?;
+    Ok(Json(
+        shares.into_iter().map(ShareLink::from).collect::<Vec<_>>(),
+    ))
+}
+
+/// Revokes a link; it stays listed until it would have expired anyway.
+async fn revoke_share(
+    State(pool): State<DbPool>,
+    Path(id): Path<i64>,
+) -> Result<impl IntoResponse, ShareError> {
+    let now = Utc::now().naive_utc();
+    let result = sqlx::query!(
+        "UPDATE share_links SET revoked_at = COALESCE(revoked_at, ?) WHERE id = ?",
+        now,
+        id
+    )
+    .execute(&pool)
+    .await?;
+    if result.rows_affected() == 0 {
+        return Err(ShareError::ShareNotFound);
+    }
+    log::info!("Revoked share link: id={}", id);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// The link a token belongs to, if it is still valid.
+async fn find_link(pool: &DbPool, token: &str) -> Result<ShareLinkDb, ShareError> {
+    if !token.starts_with(TOKEN_PREFIX) {
+        return Err(ShareError::LinkNotFound);
+    }
+    let token_hash = api_tokens::hash(token);
+    let now = Utc::now().naive_utc();
+    sqlx::query_as!(
+        ShareLinkDb,
+        r#"SELECT id AS "id!", prefix, request_id, folder_id, allow_execute AS "allow_execute: bool", environment_id,
+            expires_at AS "expires_at: NaiveDateTime", created_at AS "created_at: NaiveDateTime", revoked_at AS "revoked_at: NaiveDateTime"
+        FROM share_links WHERE token_hash = ? AND revoked_at IS NULL AND expires_at > ?"#,
+        token_hash,
+        now
+    )
+    .fetch_optional(pool)
+    .await?
+    .ok_or(ShareError::LinkNotFound)
+}
+
+async fn show_share(
+    State(pool): State<DbPool>,
+    Path(token): Path<String>,
+) -> Result<Html<String>, ShareError> {
+    let link = find_link(&pool, &token).await?;
+    let docs = match (link.request_id, link.folder_id) {
+        (Some(id), _) => docs::load_request_docs(&pool, id).await?,
+        (_, Some(id)) => docs::load_docs(&pool, id).await?,
+        _ => return Err(ShareError::LinkNotFound),
+    };
+    let send_action = format!("/share/{}/send", token);
+    let page = docs.page(link.allow_execute.then_some(send_action.as_str()))?;
+    Ok(Html(page))
+}
+
+async fn send_shared(
+    State(pool): State<DbPool>,
+    Path((token, request_id)): Path<(String, i64)>,
+) -> Result<Html<String>, ShareError> {
+    let link = find_link(&pool, &token).await?;
+    if !link.allow_execute {
+        return Err(ShareError::ExecutionNotAllowed);
+    }
+    let shared = match (link.request_id, link.folder_id) {
+        (Some(id), _) => id == request_id,
+        (_, Some(folder_id)) => sqlx::query_scalar!(
+            r#"SELECT id AS "id!" FROM requests WHERE id = ? AND folder_id = ? AND archived_at IS NULL"#,
+            request_id,
+            folder_id
+        )
+        .fetch_optional(&pool)
+        .await?
+        .is_some(),
+        _ => false,
+    };
+    if !shared {
+        return Err(ShareError::LinkNotFound);
+    }
+
+    log::info!(
+        "Sending request {} through share link {}",
+        request_id,
+        link.id
+    );
+    let response = executor::execute(
+        pool,
+        ExecuteRequestPayload::saved(request_id, link.environment_id),
+    )
+    .await
+    .map_err(ShareError::Execution)?;
+    let body = serde_json::from_str::<serde_json::Value>(&response.body)
+        .ok()
+        .and_then(|json| serde_json::to_string_pretty(&json).ok())
+        .unwrap_or_else(|| response.body.clone());
+    let page = ResponsePage {
+        back: format!("/share/{}", token),
+        headers: response.headers.clone().into_iter().collect(),
+        body,
+        response,
+    };
+    Ok(Html(page.render_once()?))
+}
+
+/// Managing links, under /api.
+pub fn routes(pool: DbPool) -> Router {
+    Router::new()
+        .route("/shares", get(list_shares).post(create_share))
+        .route("/shares/:id", delete(revoke_share))
+        .with_state(pool)
+}
+
+/// The shared pages themselves, outside /api.
+pub fn public_routes(pool: DbPool) -> Router {
+    Router::new()
+        .route("/share/:token", get(show_share))
+        .route("/share/:token/send/:request_id", post(send_shared))
+        .with_state(pool)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::create_test_pool;
+    use axum_test::TestServer;
+    use httpmock::prelude::*;
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn test_share_links() {
+        let pool = create_test_pool().await;
+        let mock = MockServer::start();
+        mock.mock(|when, then| {
+            when.method(GET).path("/orders").header("x-tenant", "acme");
+            then.status(200).body(r#"{"count":2}"#);
+        });
+        let (folder_id,): (i64,) = sqlx::query_as(
+            "INSERT INTO folders (name, description) VALUES ('Orders', 'Order endpoints.') RETURNING id",
+        )
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+        let (env_id,): (i64,) = sqlx::query_as(
+            "INSERT INTO environments (name, variables) VALUES ('Staging', '{\"tenant\":\"acme\"}') RETURNING id",
+        )
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+        let (request_id,): (i64,) = sqlx::query_as(
+            "INSERT INTO requests (name, method, url, headers, folder_id, auth_type, auth_token) VALUES ('List orders', 'GET', ?, '[{\"key\":\"X-Tenant\",\"value\":\"{{tenant}}\"}]', ?, 'bearer', 'sk_secret') RETURNING id",
+        )
+        .bind(mock.url("/orders"))
+        .bind(folder_id)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+        let (other_id,): (i64,) = sqlx::query_as(
+            "INSERT INTO requests (name, method, url) VALUES ('Elsewhere', 'GET', 'http://localhost:9/') RETURNING id",
+        )
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+
+        let app = routes(pool.clone()).merge(public_routes(pool.clone()));
+        let server = TestServer::new(app).unwrap();
+
+        server
+            .post("/shares")
+            .json(&json!({ "request_id": request_id, "folder_id": folder_id }))
+            .await
+            .assert_status(StatusCode::BAD_REQUEST);
+        server
+            .post("/shares")
+            .json(&json!({ "folder_id": folder_id, "expires_in_hours": 10000 }))
+            .await
+            .assert_status(StatusCode::BAD_REQUEST);
+        server
+            .post("/shares")
+            .json(&json!({ "folder_id": 999 }))
+            .await
+            .assert_status(StatusCode::NOT_FOUND);
+
+        // Read-only
+        let read_only: CreatedShare = server
+            .post("/shares")
+            .json(&json!({ "request_id": request_id }))
+            .await
+            .json();
+        assert!(read_only.token.starts_with(TOKEN_PREFIX));
+        let page = server.get(&read_only.url).await.text();
+        assert!(page.contains("<h1>List orders</h1>"));
+        assert!(!page.contains("sk_secret"));
+        assert!(!page.contains("<form"));
+        server
+            .post(&format!("{}/send/{}", read_only.url, request_id))
+            .await
+            .assert_status(StatusCode::FORBIDDEN);
+
+        // A folder, sent against the fixed environment
+        let runnable: CreatedShare = server
+            .post("/shares")
+            .json(
+                &json!({ "folder_id": folder_id, "allow_execute": true, "environment_id": env_id }),
+            )
+            .await
+            .json();
+        let page = server.get(&runnable.url).await.text();
+        assert!(page.contains("Order endpoints."));
+        assert!(page.contains(&format!("action=\"{}/send/{}\"", runnable.url, request_id)));
+        let response = server
+            .post(&format!("{}/send/{}", runnable.url, request_id))
+            .await;
+        response.assert_status_ok();
+        assert!(response.text().contains("&quot;count&quot;: 2"));
+        server
+            .post(&format!("{}/send/{}", runnable.url, other_id))
+            .await
+            .assert_status(StatusCode::NOT_FOUND);
+
+        // Revoked and expired links stop working
+        let links: Vec<ShareLink> = server.get("/shares").await.json();
+        assert_eq!(links.len(), 2);
+        assert!(links
+            .iter()
+            .all(|l| !read_only.token.starts_with(&l.prefix) || l.id == read_only.info.id));
+        server
+            .delete(&format!("/shares/{}", read_only.info.id))
+            .await
+            .assert_status(StatusCode::NO_CONTENT);
+        server
+            .get(&read_only.url)
+            .await
+            .assert_status(StatusCode::NOT_FOUND);
+        sqlx::query("UPDATE share_links SET expires_at = '2020-01-01 00:00:00'")
+            .execute(&pool)
+            .await
+            .unwrap();
+        server
+            .get(&runnable.url)
+            .await
+            .assert_status(StatusCode::NOT_FOUND);
+        server
+            .get("/share/jss_unknown")
+            .await
+            .assert_status(StatusCode::NOT_FOUND);
+        server
+            .delete("/shares/999")
+            .await
+            .assert_status(StatusCode::NOT_FOUND);
+    }
+}
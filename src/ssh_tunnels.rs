@@ -0,0 +1,698 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Json, Router,
+};
+use chrono::{DateTime, NaiveDateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::path::PathBuf;
+use std::process::Stdio;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::process::{Child, Command};
+use tokio::sync::Mutex as AsyncMutex;
+
+use crate::{api_tokens, crypto, db::DbPool, i18n};
+
+// Requests marked with a tunnel are sent through `ssh -D`, a SOCKS forward
+// on a local port, so any host the bastion reaches can be called and host
+// names are resolved on its side. The OpenSSH client is started on the first
+// execution that needs it and kept running until the tunnel changes, is
+// disconnected or js-link stops.
+
+/// How long the bastion has to accept the connection and the forward to
+/// start listening.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(20);
+/// Lines of ssh's output kept to explain a failed connection.
+const KEPT_OUTPUT_LINES: usize = 20;
+
+static RUNNING: OnceLock<AsyncMutex<HashMap<i64, Running>>> = OnceLock::new();
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SshTunnel {
+    pub id: i64,
+    pub name: String,
+    pub host: String,
+    pub port: i64,
+    pub username: String,
+    pub private_key_set: bool, // The key itself is never returned
+    pub password_set: bool,
+    /// Port of the local forward; unset picks a free one on each start
+    pub local_port: Option<i64>,
+    /// Port the running forward listens on, `None` while disconnected
+    #[serde(default)]
+    pub active_port: Option<u16>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+pub struct SshTunnelDb {
+    pub id: i64,
+    pub name: String,
+    pub host: String,
+    pub port: i64,
+    pub username: String,
+    pub private_key: Option<String>, // Encrypted
+    pub password: Option<String>,    // Encrypted
+    pub local_port: Option<i64>,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+impl SshTunnelDb {
+    fn view(self, active_port: Option<u16>) -> SshTunnel {
+        SshTunnel {
+            id: self.id,
+            name: self.name,
+            host: self.host,
+            port: self.port,
+            username: self.username,
+            private_key_set: self.private_key.is_some(),
+            password_set: self.password.is_some(),
+            local_port: self.local_port,
+            active_port,
+            created_at: self.created_at.and_utc(),
+            updated_at: self.updated_at.and_utc(),
+        }
+    }
+
+    /// Changes to anything ssh is started with restart a running tunnel.
+    fn fingerprint(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        self.host.hash(&mut hasher);
+        self.port.hash(&mut hasher);
+        self.username.hash(&mut hasher);
+        self.private_key.hash(&mut hasher);
+        self.password.hash(&mut hasher);
+        self.local_port.hash(&mut hasher);
+        hasher.finish()
+    }
+}
+
+/// Body of create and update. On update, a `null` or absent key or password
+/// keeps the stored one and an empty string clears it.
+#[derive(Deserialize)]
+pub struct WriteTunnel {
+    name: String,
+    host: String,
+    #[serde(default = "default_port")]
+    port: i64,
+    username: String,
+    #[serde(default)]
+    private_key: Option<String>,
+    #[serde(default)]
+    password: Option<String>,
+    #[serde(default)]
+    local_port: Option<i64>,
+}
+
+fn default_port() -> i64 {
+    22
+}
+
+pub enum TunnelError {
+    NotFound,
+    Invalid(String),
+    ConnectFailed(String),
+    EncryptionError(String),
+    DatabaseError(sqlx::Error),
+}
+
+impl From<sqlx::Error> for TunnelError {
+    fn from(e: sqlx::Error) -> Self {
+        match e {
+            sqlx::Error::RowNotFound => TunnelError::NotFound,
+            _ => TunnelError::DatabaseError(e),
+        }
+    }
+}
+
+impl From<crypto::CryptoError> for TunnelError {
+    fn from(e: crypto::CryptoError) -> Self {
+        log::error!("Failed to encrypt or decrypt SSH credentials: {}", e);
+        TunnelError::EncryptionError(e.to_string())
+    }
+}
+
+impl fmt::Display for TunnelError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TunnelError::NotFound => write!(f, "{}", i18n::t("error.ssh_tunnel_not_found")),
+            TunnelError::Invalid(msg)
+            | TunnelError::ConnectFailed(msg)
+            | TunnelError::EncryptionError(msg) => write!(f, "{}", msg),
+            TunnelError::DatabaseError(_) => write!(f, "{}", i18n::t("error.database")),
+        }
+    }
+}
+
+impl IntoResponse for TunnelError {
+    fn into_response(self) -> Response {
+        let status = match &self {
+            TunnelError::NotFound => StatusCode::NOT_FOUND,
+            TunnelError::Invalid(_) => StatusCode::BAD_REQUEST,
+            TunnelError::ConnectFailed(_) => StatusCode::BAD_GATEWAY,
+            TunnelError::EncryptionError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            TunnelError::DatabaseError(e) => {
+                log::error!("Database error: {}", e);
+                StatusCode::INTERNAL_SERVER_ERROR
+            }
+        };
+        (status, self.to_string()).into_response()
+    }
+}
+
+/// Host and user names go on ssh's command line, so they must not look
+/// like options.
+fn validate(payload: &WriteTunnel) -> Result<(), TunnelError> {
+    let plain = |value: &str| {
+        !value.is_empty()
+            && !value.starts_with('-')
+            && !value.chars().any(|c| c.is_whitespace() || c.is_control())
+    };
+    if payload.name.trim().is_empty() {
+        return Err(TunnelError::Invalid("A tunnel needs a name".to_string()));
+    }
+    if !plain(&payload.host) || payload.host.contains('@') {
+        return Err(TunnelError::Invalid(format!(
+            "Invalid SSH host '{}'",
+            payload.host
+        )));
+    }
+    if !plain(&payload.username) || payload.username.contains('@') {
+        return Err(TunnelError::Invalid(format!(
+            "Invalid SSH user '{}'",
+            payload.username
+        )));
+    }
+    if !(1..=65535).contains(&payload.port) {
+        return Err(TunnelError::Invalid(format!(
+            "Invalid SSH port {}",
+            payload.port
+        )));
+    }
+    if let Some(local_port) = payload.local_port {
+        if !(1024..=65535).contains(&local_port) {
+            return Err(TunnelError::Invalid(format!(
+                "Local port {} must be between 1024 and 65535",
+                local_port
+            )));
+        }
+    }
+    Ok(())
+}
+
+fn encrypt_secret(value: Option<&str>) -> Result<Option<String>, TunnelError> {
+    match value {
+        None | Some("") => Ok(None),
+        Some(value) => Ok(Some(crypto::encrypt(value)?)),
+    }
+}
+
+pub async fn exists(pool: &DbPool, id: i64) -> Result<bool, sqlx::Error> {
+    Ok(
+        sqlx::query_scalar!(r#"SELECT id AS "id!" FROM ssh_tunnels WHERE id = ?"#, id)
+            .fetch_optional(pool)
+            .await?
+            .is_some(),
+    )
+}
+
+async fn load(pool: &DbPool, id: i64) -> Result<SshTunnelDb, sqlx::Error> {
+    sqlx::query_as!(
+        SshTunnelDb,
+        r#"SELECT id AS "id!", name, host, port, username, private_key, password, local_port, created_at AS "created_at: NaiveDateTime", updated_at AS "updated_at: NaiveDateTime" FROM ssh_tunnels WHERE id = ?"#,
+        id
+    )
+    .fetch_one(pool)
+    .await
+}
+
+/// The output ssh wrote so far, shared with the task reading it.
+type Output = Arc<Mutex<Vec<String>>>;
+
+struct Running {
+    child: Child,
+    local_port: u16,
+    fingerprint: u64,
+}
+
+impl Running {
+    fn is_alive(&mut self) -> bool {
+        matches!(self.child.try_wait(), Ok(None))
+    }
+}
+
+fn running() -> &'static AsyncMutex<HashMap<i64, Running>> {
+    RUNNING.get_or_init(Default::default)
+}
+
+/// The OpenSSH client, `JSLINK_SSH` or `ssh` from the path.
+fn ssh_program() -> String {
+    std::env::var("JSLINK_SSH")
+        .ok()
+        .filter(|p| !p.trim().is_empty())
+        .unwrap_or_else(|| "ssh".to_string())
+}
+
+/// A key or askpass script written for ssh to read, removed once the
+/// connection is up or has failed.
+struct SecretFile(PathBuf);
+
+impl SecretFile {
+    fn write(contents: &str, mode: u32) -> Result<Self, TunnelError> {
+        let file = SecretFile(std::env::temp_dir().join(api_tokens::random_token("js-link-ssh-")));
+        let mut options = std::fs::OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(mode);
+        }
+        #[cfg(not(unix))]
+        let _ = mode;
+        options
+            .open(&file.0)
+            .and_then(|mut f| std::io::Write::write_all(&mut f, contents.as_bytes()))
+            .map_err(|e| {
+                TunnelError::ConnectFailed(format!("Failed to write SSH credentials: {}", e))
+            })?;
+        Ok(file)
+    }
+}
+
+impl Drop for SecretFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
+
+async fn free_port() -> Result<u16, TunnelError> {
+    let listener = TcpListener::bind("127.0.0.1:0")
+        .await
+        .map_err(|e| TunnelError::ConnectFailed(format!("No free local port: {}", e)))?;
+    Ok(listener.local_addr().map(|a| a.port()).unwrap_or_default())
+}
+
+/// Starts ssh and waits until the forward accepts connections.
+async fn start(tunnel: &SshTunnelDb) -> Result<Running, TunnelError> {
+    let local_port = match tunnel.local_port {
+        Some(port) => {
+            let port = port as u16;
+            // Something else listening there would pass for the tunnel
+            TcpListener::bind(("127.0.0.1", port)).await.map_err(|e| {
+                TunnelError::ConnectFailed(format!("Local port {} is not free: {}", port, e))
+            })?;
+            port
+        }
+        None => free_port().await?,
+    };
+
+    let mut command = Command::new(ssh_program());
+    command
+        .arg("-N")
+        .args(["-D", &format!("127.0.0.1:{}", local_port)])
+        .args(["-p", &tunnel.port.to_string()])
+        .args(["-l", &tunnel.username])
+        .args(["-o", "ExitOnForwardFailure=yes"])
+        .args(["-o", "StrictHostKeyChecking=accept-new"])
+        .args(["-o", "ServerAliveInterval=30"])
+        .args([
+            "-o",
+            &format!("ConnectTimeout={}", CONNECT_TIMEOUT.as_secs()),
+        ]);
+
+    let mut secrets = Vec::new();
+    if let Some(key) = tunnel.private_key.as_deref() {
+        let key = SecretFile::write(&crypto::decrypt(key)?, 0o600)?;
+        command
+            .arg("-i")
+            .arg(&key.0)
+            .args(["-o", "IdentitiesOnly=yes"]);
+        secrets.push(key);
+    }
+    match tunnel.password.as_deref() {
+        // ssh only reads passwords from a terminal or an askpass program
+        Some(password) => {
+            let askpass = SecretFile::write(
+                "#!/bin/sh\nprintf '%s\\n' \"$JSLINK_SSH_PASSWORD\"\n",
+                0o700,
+            )?;
+            command
+                .env("SSH_ASKPASS", &askpass.0)
+                .env("SSH_ASKPASS_REQUIRE", "force")
+                .env("JSLINK_SSH_PASSWORD", crypto::decrypt(password)?);
+            secrets.push(askpass);
+        }
+        None => {
+            command.args(["-o", "BatchMode=yes"]);
+        }
+    }
+    command
+        .arg("--")
+        .arg(&tunnel.host)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true);
+
+    log::info!(
+        "Opening SSH tunnel '{}' to {}@{}:{} on local port {}",
+        tunnel.name,
+        tunnel.username,
+        tunnel.host,
+        tunnel.port,
+        local_port
+    );
+    let mut child = command.spawn().map_err(|e| {
+        TunnelError::ConnectFailed(format!("Failed to start {}: {}", ssh_program(), e))
+    })?;
+    let output = Output::default();
+    if let Some(stderr) = child.stderr.take() {
+        let (output, name) = (output.clone(), tunnel.name.clone());
+        tokio::spawn(async move {
+            let mut lines = BufReader::new(stderr).lines();
+            while let Ok(Some(line)) = lines.next_line().await {
+                log::debug!("ssh ({}): {}", name, line);
+                let mut output = output.lock().unwrap_or_else(|e| e.into_inner());
+                if output.len() == KEPT_OUTPUT_LINES {
+                    output.remove(0);
+                }
+                output.push(line);
+            }
+        });
+    }
+
+    let deadline = tokio::time::Instant::now() + CONNECT_TIMEOUT;
+    loop {
+        if let Ok(Some(status)) = child.try_wait() {
+            // Give the reader a moment to pick up the last lines
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            let output = output.lock().unwrap_or_else(|e| e.into_inner()).join("\n");
+            log::warn!("SSH tunnel '{}' failed: {}", tunnel.name, output);
+            return Err(TunnelError::ConnectFailed(format!(
+                "SSH tunnel '{}' could not be opened ({}): {}",
+                tunnel.name,
+                status,
+                output.trim()
+            )));
+        }
+        if TcpStream::connect(("127.0.0.1", local_port)).await.is_ok() {
+            break;
+        }
+        if tokio::time::Instant::now() >= deadline {
+            return Err(TunnelError::ConnectFailed(format!(
+                "SSH tunnel '{}' did not open within {} s",
+                tunnel.name,
+                CONNECT_TIMEOUT.as_secs()
+            )));
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    log::info!("SSH tunnel '{}' is open", tunnel.name);
+    Ok(Running {
+        child,
+        local_port,
+        fingerprint: tunnel.fingerprint(),
+    })
+}
+
+/// The local port of the tunnel's SOCKS forward, opening the tunnel when it
+/// is not running or has changed since it was opened.
+pub async fn ensure(pool: &DbPool, id: i64) -> Result<u16, TunnelError> {
+    let tunnel = load(pool, id).await?;
+    let mut running = running().lock().await;
+    if let Some(current) = running.get_mut(&id) {
+        if current.fingerprint == tunnel.fingerprint() && current.is_alive() {
+            return Ok(current.local_port);
+        }
+        log::info!("Reopening SSH tunnel '{}'", tunnel.name);
+    }
+    // Dropping the old process stops it
+    running.remove(&id);
+    let started = start(&tunnel).await?;
+    let local_port = started.local_port;
+    running.insert(id, started);
+    Ok(local_port)
+}
+
+async fn active_port(id: i64) -> Option<u16> {
+    let mut running = running().lock().await;
+    let current = running.get_mut(&id)?;
+    if current.is_alive() {
+        Some(current.local_port)
+    } else {
+        running.remove(&id);
+        None
+    }
+}
+
+async fn stop(id: i64) -> bool {
+    match running().lock().await.remove(&id) {
+        Some(mut current) => {
+            let _ = current.child.kill().await;
+            true
+        }
+        None => false,
+    }
+}
+
+/// Closes every open tunnel, on shutdown.
+pub async fn stop_all() {
+    let mut running = running().lock().await;
+    for (_, mut current) in running.drain() {
+        let _ = current.child.kill().await;
+    }
+}
+
+async fn list_tunnels(State(pool): State<DbPool>) -> Result<impl IntoResponse, TunnelError> {
+    let tunnels = sqlx::query_as!(
+        SshTunnelDb,
+        r#"SELECT id AS "id!", name, host, port, username, private_key, password, local_port, created_at AS "created_at: NaiveDateTime", updated_at AS "updated_at: NaiveDateTime" FROM ssh_tunnels ORDER BY name, id"#
+    )
+    .fetch_all(&pool)
+    .await?;
+    let mut views = Vec::with_capacity(tunnels.len());
+    for tunnel in tunnels {
+        let active = active_port(tunnel.id).await;
+        views.push(tunnel.view(active));
+    }
+    Ok(Json(views))
+}
+
+async fn get_tunnel(
+    State(pool): State<DbPool>,
+    Path(id): Path<i64>,
+) -> Result<impl IntoResponse, TunnelError> {
+    let tunnel = load(&pool, id).await?;
+    Ok(Json(tunnel.view(active_port(id).await)))
+}
+
+async fn create_tunnel(
+    State(pool): State<DbPool>,
+    Json(payload): Json<WriteTunnel>,
+) -> Result<impl IntoResponse, TunnelError> {
+    validate(&payload)?;
+    let private_key = encrypt_secret(payload.private_key.as_deref())?;
+    let password = encrypt_secret(payload.password.as_deref())?;
+    let name = payload.name.trim();
+    let tunnel = sqlx::query_as!(
+        SshTunnelDb,
+        r#"INSERT INTO ssh_tunnels (name, host, port, username, private_key, password, local_port) VALUES (?, ?, ?, ?, ?, ?, ?)
+        RETURNING id AS "id!", name, host, port, username, private_key, password, local_port, created_at AS "created_at: NaiveDateTime", updated_at AS "updated_at: NaiveDateTime""#,
+        name,
+        payload.host,
+        payload.port,
+        payload.username,
+        private_key,
+        password,
+        payload.local_port
+    )
+    .fetch_one(&pool)
+    .await?;
+    log::info!("Created SSH tunnel {} '{}'", tunnel.id, tunnel.name);
+    Ok((StatusCode::CREATED, Json(tunnel.view(None))))
+}
+
+/// A running tunnel keeps its connection until the next execution through
+/// it, which reopens it with the new settings.
+async fn update_tunnel(
+    State(pool): State<DbPool>,
+    Path(id): Path<i64>,
+    Json(payload): Json<WriteTunnel>,
+) -> Result<impl IntoResponse, TunnelError> {
+    validate(&payload)?;
+    let keep_key = payload.private_key.is_none();
+    let private_key = encrypt_secret(payload.private_key.as_deref())?;
+    let keep_password = payload.password.is_none();
+    let password = encrypt_secret(payload.password.as_deref())?;
+    let name = payload.name.trim();
+    let tunnel = sqlx::query_as!(
+        SshTunnelDb,
+        r#"UPDATE ssh_tunnels SET name = ?, host = ?, port = ?, username = ?, private_key = CASE WHEN ? THEN private_key ELSE ? END, password = CASE WHEN ? THEN password ELSE ? END, local_port = ?, updated_at = CURRENT_TIMESTAMP WHERE id = ?
+        RETURNING id AS "id!", name, host, port, username, private_key, password, local_port, created_at AS "created_at: NaiveDateTime", updated_at AS "updated_at: NaiveDateTime""#,
+        name,
+        payload.host,
+        payload.port,
+        payload.username,
+        keep_key,
+        private_key,
+        keep_password,
+        password,
+        payload.local_port,
+        id
+    )
+    .fetch_optional(&pool)
+    .await?
+    .ok_or(TunnelError::NotFound)?;
+    log::info!("Updated SSH tunnel {} '{}'", id, tunnel.name);
+    Ok(Json(tunnel.view(active_port(id).await)))
+}
+
+/// Requests sent through the tunnel are sent directly again.
+async fn delete_tunnel(
+    State(pool): State<DbPool>,
+    Path(id): Path<i64>,
+) -> Result<impl IntoResponse, TunnelError> {
+    let result = sqlx::query!("DELETE FROM ssh_tunnels WHERE id = ?", id)
+        .execute(&pool)
+        .await?;
+    if result.rows_affected() == 0 {
+        return Err(TunnelError::NotFound);
+    }
+    stop(id).await;
+    log::info!("Deleted SSH tunnel {}", id);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Opens the tunnel ahead of the first execution, to check the credentials.
+async fn connect_tunnel(
+    State(pool): State<DbPool>,
+    Path(id): Path<i64>,
+) -> Result<impl IntoResponse, TunnelError> {
+    let local_port = ensure(&pool, id).await?;
+    let tunnel = load(&pool, id).await?;
+    Ok(Json(tunnel.view(Some(local_port))))
+}
+
+async fn disconnect_tunnel(
+    State(pool): State<DbPool>,
+    Path(id): Path<i64>,
+) -> Result<impl IntoResponse, TunnelError> {
+    let tunnel = load(&pool, id).await?;
+    if stop(id).await {
+        log::info!("Closed SSH tunnel '{}'", tunnel.name);
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub fn routes(pool: DbPool) -> Router {
+    Router::new()
+        .route("/ssh-tunnels", get(list_tunnels).post(create_tunnel))
+        .route(
+            "/ssh-tunnels/:id",
+            get(get_tunnel).put(update_tunnel).delete(delete_tunnel),
+        )
+        .route("/ssh-tunnels/:id/connect", post(connect_tunnel))
+        .route("/ssh-tunnels/:id/disconnect", post(disconnect_tunnel))
+        .with_state(pool)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        db::create_test_pool,
+        executor::{self, ExecuteRequestPayload, ExecutorError},
+    };
+    use axum_test::TestServer;
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn test_ssh_tunnels() {
+        let pool = create_test_pool().await;
+        let server = TestServer::new(routes(pool.clone())).unwrap();
+
+        server
+            .post("/ssh-tunnels")
+            .json(&json!({ "name": "Bastion", "host": "-oProxyCommand=sh", "username": "ops" }))
+            .await
+            .assert_status(StatusCode::BAD_REQUEST);
+        server
+            .post("/ssh-tunnels")
+            .json(&json!({ "name": "Bastion", "host": "bastion.test", "username": "ops", "local_port": 80 }))
+            .await
+            .assert_status(StatusCode::BAD_REQUEST);
+
+        // Nothing listens on port 9, so the bastion refuses the connection
+        let response = server
+            .post("/ssh-tunnels")
+            .json(&json!({
+                "name": "Bastion",
+                "host": "127.0.0.1",
+                "port": 9,
+                "username": "ops",
+                "password": "s3cret"
+            }))
+            .await;
+        response.assert_status(StatusCode::CREATED);
+        let tunnel: SshTunnel = response.json();
+        assert!(tunnel.password_set);
+        assert!(!tunnel.private_key_set);
+        assert!(!response.text().contains("s3cret"));
+
+        let updated: SshTunnel = server
+            .put(&format!("/ssh-tunnels/{}", tunnel.id))
+            .json(
+                &json!({ "name": "Bastion", "host": "127.0.0.1", "port": 9, "username": "deploy" }),
+            )
+            .await
+            .json();
+        assert_eq!(updated.username, "deploy");
+        assert!(updated.password_set);
+
+        server
+            .post(&format!("/ssh-tunnels/{}/connect", tunnel.id))
+            .await
+            .assert_status(StatusCode::BAD_GATEWAY);
+        let listed: Vec<SshTunnel> = server.get("/ssh-tunnels").await.json();
+        assert_eq!(listed.len(), 1);
+        assert_eq!(listed[0].active_port, None);
+
+        // Executions through the tunnel fail instead of going direct
+        let (request_id,): (i64,) = sqlx::query_as(
+            "INSERT INTO requests (name, method, url, ssh_tunnel_id) VALUES ('Ledger', 'GET', 'http://ledger.internal/health', ?) RETURNING id",
+        )
+        .bind(tunnel.id)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+        let result =
+            executor::execute(pool.clone(), ExecuteRequestPayload::saved(request_id, None)).await;
+        assert!(matches!(result, Err(ExecutorError::NetworkError(_))));
+
+        server
+            .delete(&format!("/ssh-tunnels/{}", tunnel.id))
+            .await
+            .assert_status(StatusCode::NO_CONTENT);
+        let (ssh_tunnel_id,): (Option<i64>,) =
+            sqlx::query_as("SELECT ssh_tunnel_id FROM requests WHERE id = ?")
+                .bind(request_id)
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+        assert_eq!(ssh_tunnel_id, None);
+        server
+            .get(&format!("/ssh-tunnels/{}", tunnel.id))
+            .await
+            .assert_status(StatusCode::NOT_FOUND);
+    }
+}
@@ -0,0 +1,784 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    routing::get,
+    Json, Router,
+};
+use chrono::{DateTime, Duration, NaiveDateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::time::Duration as StdDuration;
+
+use crate::{db::DbPool, i18n};
+
+/// Upper bounds (exclusive) of the latency bands `hist_0`..`hist_6`;
+/// `hist_7` counts everything slower.
+pub const HISTOGRAM_BOUNDS_MS: [i64; 7] = [50, 100, 250, 500, 1000, 2500, 5000];
+
+const DEFAULT_INTERVAL_SECS: u64 = 300;
+const WATERMARK: &str = "latency_buckets.last_history_id";
+const TIMESTAMP_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Granularity {
+    Hour,
+    Day,
+}
+
+impl Granularity {
+    fn as_str(self) -> &'static str {
+        match self {
+            Granularity::Hour => "hour",
+            Granularity::Day => "day",
+        }
+    }
+
+    /// `strftime` format truncating a timestamp to the start of its bucket.
+    fn bucket_format(self) -> &'static str {
+        match self {
+            Granularity::Hour => "%Y-%m-%d %H:00:00",
+            Granularity::Day => "%Y-%m-%d 00:00:00",
+        }
+    }
+
+    fn default_range(self) -> Duration {
+        match self {
+            Granularity::Hour => Duration::hours(24),
+            Granularity::Day => Duration::days(30),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LatencyBucket {
+    pub bucket_start: DateTime<Utc>,
+    pub count: i64,
+    pub error_count: i64,
+    pub error_rate: f64,
+    pub avg_ms: f64,
+    pub min_ms: i64,
+    pub max_ms: i64,
+    pub histogram: Vec<i64>,
+}
+
+#[derive(sqlx::FromRow)]
+struct LatencyBucketDb {
+    bucket_start: NaiveDateTime,
+    count: i64,
+    error_count: i64,
+    total_ms: i64,
+    min_ms: i64,
+    max_ms: i64,
+    hist_0: i64,
+    hist_1: i64,
+    hist_2: i64,
+    hist_3: i64,
+    hist_4: i64,
+    hist_5: i64,
+    hist_6: i64,
+    hist_7: i64,
+}
+
+impl From<LatencyBucketDb> for LatencyBucket {
+    fn from(b: LatencyBucketDb) -> Self {
+        Self {
+            bucket_start: DateTime::from_naive_utc_and_offset(b.bucket_start, Utc),
+            count: b.count,
+            error_count: b.error_count,
+            error_rate: ratio(b.error_count, b.count),
+            avg_ms: ratio(b.total_ms, b.count),
+            min_ms: b.min_ms,
+            max_ms: b.max_ms,
+            histogram: vec![
+                b.hist_0, b.hist_1, b.hist_2, b.hist_3, b.hist_4, b.hist_5, b.hist_6, b.hist_7,
+            ],
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RequestLatency {
+    pub request_id: i64,
+    pub granularity: Granularity,
+    pub histogram_bounds_ms: Vec<i64>,
+    pub buckets: Vec<LatencyBucket>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LatencySummary {
+    pub request_id: i64,
+    pub name: String,
+    pub count: i64,
+    pub error_count: i64,
+    pub error_rate: f64,
+    pub avg_ms: f64,
+    pub min_ms: i64,
+    pub max_ms: i64,
+}
+
+#[derive(sqlx::FromRow)]
+struct LatencySummaryDb {
+    request_id: i64,
+    name: String,
+    count: i64,
+    error_count: i64,
+    total_ms: i64,
+    min_ms: i64,
+    max_ms: i64,
+}
+
+impl From<LatencySummaryDb> for LatencySummary {
+    fn from(s: LatencySummaryDb) -> Self {
+        Self {
+            request_id: s.request_id,
+            name: s.name,
+            count: s.count,
+            error_count: s.error_count,
+            error_rate: ratio(s.error_count, s.count),
+            avg_ms: ratio(s.total_ms, s.count),
+            min_ms: s.min_ms,
+            max_ms: s.max_ms,
+        }
+    }
+}
+
+/// Runs of one request in a period, and how they went.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct RunStats {
+    pub run_count: i64,
+    /// Runs answered with a status below 400
+    pub success_count: i64,
+    pub success_rate: f64,
+    pub avg_ms: f64,
+    pub p95_ms: i64,
+    /// Runs held to a latency budget, and those of them that exceeded it
+    pub budgeted_count: i64,
+    pub over_budget_count: i64,
+    /// Share of budgeted runs within their budget, `None` without any
+    pub budget_compliance: Option<f64>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct StatsPoint {
+    pub bucket_start: DateTime<Utc>,
+    #[serde(flatten)]
+    pub stats: RunStats,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RequestStats {
+    pub request_id: i64,
+    pub name: String,
+    pub granularity: Granularity,
+    #[serde(flatten)]
+    pub stats: RunStats,
+    pub trend: Vec<StatsPoint>,
+}
+
+/// A request in the overview, least reliable first.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RequestOverview {
+    pub request_id: i64,
+    pub name: String,
+    pub folder_id: Option<i64>,
+    #[serde(flatten)]
+    pub stats: RunStats,
+    pub last_run_at: Option<DateTime<Utc>>,
+}
+
+/// One execution, keyed by what it is grouped under.
+struct RunRow {
+    key: String,
+    duration_ms: i64,
+    success: bool,
+    /// `None` for runs without a budget
+    over_budget: Option<bool>,
+}
+
+struct OverviewRow {
+    request_id: i64,
+    name: String,
+    folder_id: Option<i64>,
+    duration_ms: i64,
+    success: bool,
+    over_budget: Option<bool>,
+    executed_at: NaiveDateTime,
+}
+
+#[derive(Deserialize)]
+pub struct OverviewQuery {
+    folder_id: Option<i64>,
+    from: Option<DateTime<Utc>>,
+    to: Option<DateTime<Utc>>,
+}
+
+#[derive(Deserialize)]
+pub struct LatencyQuery {
+    granularity: Option<Granularity>,
+    from: Option<DateTime<Utc>>,
+    to: Option<DateTime<Utc>>,
+}
+
+impl LatencyQuery {
+    /// Granularity and `[from, to)` range as stored timestamps.
+    fn resolve(&self, default: Granularity) -> (Granularity, String, String) {
+        let granularity = self.granularity.unwrap_or(default);
+        let to = self.to.unwrap_or_else(Utc::now);
+        let from = self.from.unwrap_or(to - granularity.default_range());
+        (
+            granularity,
+            from.format(TIMESTAMP_FORMAT).to_string(),
+            to.format(TIMESTAMP_FORMAT).to_string(),
+        )
+    }
+}
+
+pub enum StatsError {
+    RequestNotFound,
+    DatabaseError(#[allow(dead_code)] sqlx::Error),
+}
+
+impl From<sqlx::Error> for StatsError {
+    fn from(e: sqlx::Error) -> Self {
+        match e {
+            sqlx::Error::RowNotFound => StatsError::RequestNotFound,
+            _ => StatsError::DatabaseError(e),
+        }
+    }
+}
+
+impl IntoResponse for StatsError {
+    fn into_response(self) -> Response {
+        match self {
+            StatsError::RequestNotFound => {
+                (StatusCode::NOT_FOUND, i18n::t("error.request_not_found")).into_response()
+            }
+            StatsError::DatabaseError(_) => {
+                (StatusCode::INTERNAL_SERVER_ERROR, i18n::t("error.database")).into_response()
+            }
+        }
+    }
+}
+
+fn ratio(numerator: i64, denominator: i64) -> f64 {
+    if denominator == 0 {
+        0.0
+    } else {
+        numerator as f64 / denominator as f64
+    }
+}
+
+/// Nearest-rank percentile of durations sorted in ascending order.
+fn percentile(sorted: &[i64], p: f64) -> i64 {
+    if sorted.is_empty() {
+        return 0;
+    }
+    let rank = (p * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+/// Stats of runs whose durations are given in ascending order. `budgets`
+/// tells for each run held to a latency budget whether it exceeded it.
+fn run_stats(
+    durations: &[i64],
+    success_count: i64,
+    budgets: impl Iterator<Item = Option<bool>>,
+) -> RunStats {
+    let run_count = durations.len() as i64;
+    let budgets: Vec<bool> = budgets.flatten().collect();
+    let budgeted_count = budgets.len() as i64;
+    let over_budget_count = budgets.iter().filter(|over| **over).count() as i64;
+    RunStats {
+        run_count,
+        success_count,
+        success_rate: ratio(success_count, run_count),
+        avg_ms: ratio(durations.iter().sum(), run_count),
+        p95_ms: percentile(durations, 0.95),
+        budgeted_count,
+        over_budget_count,
+        budget_compliance: (budgeted_count > 0)
+            .then(|| ratio(budgeted_count - over_budget_count, budgeted_count)),
+    }
+}
+
+/// Stats per key of rows ordered by key, in that order.
+fn group_stats(rows: &[RunRow]) -> Vec<(&str, RunStats)> {
+    let mut groups = Vec::new();
+    for group in rows.chunk_by(|a, b| a.key == b.key) {
+        let mut durations: Vec<i64> = group.iter().map(|r| r.duration_ms).collect();
+        durations.sort_unstable();
+        let successes = group.iter().filter(|r| r.success).count() as i64;
+        let budgets = group.iter().map(|r| r.over_budget);
+        groups.push((
+            group[0].key.as_str(),
+            run_stats(&durations, successes, budgets),
+        ));
+    }
+    groups
+}
+
+/// Folds execution history recorded since the last run into the hourly and
+/// daily latency buckets. Returns the number of history rows processed.
+pub async fn aggregate(pool: &DbPool) -> Result<i64, sqlx::Error> {
+    let mut tx = pool.begin().await?;
+
+    let last_id = sqlx::query_scalar!("SELECT value FROM job_state WHERE name = ?", WATERMARK)
+        .fetch_optional(&mut *tx)
+        .await?
+        .unwrap_or(0);
+    let max_id = sqlx::query_scalar!("SELECT MAX(id) FROM execution_history")
+        .fetch_one(&mut *tx)
+        .await?
+        .unwrap_or(0);
+    if max_id <= last_id {
+        return Ok(0);
+    }
+
+    let [b0, b1, b2, b3, b4, b5, b6] = HISTOGRAM_BOUNDS_MS;
+    for granularity in [Granularity::Hour, Granularity::Day] {
+        let name = granularity.as_str();
+        let format = granularity.bucket_format();
+        sqlx::query!(
+            "INSERT INTO latency_buckets (request_id, granularity, bucket_start, count, error_count, total_ms, min_ms, max_ms, hist_0, hist_1, hist_2, hist_3, hist_4, hist_5, hist_6, hist_7)
+            SELECT request_id, ?, strftime(?, executed_at), COUNT(*),
+                SUM(CASE WHEN error IS NOT NULL OR status IS NULL OR status >= 400 THEN 1 ELSE 0 END),
+                SUM(duration_ms), MIN(duration_ms), MAX(duration_ms),
+                SUM(duration_ms < ?), SUM(duration_ms >= ? AND duration_ms < ?), SUM(duration_ms >= ? AND duration_ms < ?),
+                SUM(duration_ms >= ? AND duration_ms < ?), SUM(duration_ms >= ? AND duration_ms < ?), SUM(duration_ms >= ? AND duration_ms < ?),
+                SUM(duration_ms >= ? AND duration_ms < ?), SUM(duration_ms >= ?)
+            FROM execution_history
+            WHERE id > ? AND id <= ? AND request_id IS NOT NULL
+            GROUP BY request_id, strftime(?, executed_at)
+            ON CONFLICT (request_id, granularity, bucket_start) DO UPDATE SET
+                count = count + excluded.count,
+                error_count = error_count + excluded.error_count,
+                total_ms = total_ms + excluded.total_ms,
+                min_ms = MIN(min_ms, excluded.min_ms),
+                max_ms = MAX(max_ms, excluded.max_ms),
+                hist_0 = hist_0 + excluded.hist_0,
+                hist_1 = hist_1 + excluded.hist_1,
+                hist_2 = hist_2 + excluded.hist_2,
+                hist_3 = hist_3 + excluded.hist_3,
+                hist_4 = hist_4 + excluded.hist_4,
+                hist_5 = hist_5 + excluded.hist_5,
+                hist_6 = hist_6 + excluded.hist_6,
+                hist_7 = hist_7 + excluded.hist_7",
+            name,
+            format,
+            b0,
+            b0,
+            b1,
+            b1,
+            b2,
+            b2,
+            b3,
+            b3,
+            b4,
+            b4,
+            b5,
+            b5,
+            b6,
+            b6,
+            last_id,
+            max_id,
+            format,
+        )
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    sqlx::query!(
+        "INSERT INTO job_state (name, value) VALUES (?, ?) ON CONFLICT (name) DO UPDATE SET value = excluded.value",
+        WATERMARK,
+        max_id
+    )
+    .execute(&mut *tx)
+    .await?;
+    tx.commit().await?;
+
+    Ok(max_id - last_id)
+}
+
+/// Runs [`aggregate`] every `JSLINK_STATS_INTERVAL_SECS` seconds (default 300,
+/// `0` disables the job).
+pub fn spawn_aggregator(pool: DbPool) {
+    let interval_secs = env::var("JSLINK_STATS_INTERVAL_SECS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(DEFAULT_INTERVAL_SECS);
+    if interval_secs == 0 {
+        log::info!("Latency aggregation disabled");
+        return;
+    }
+
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(StdDuration::from_secs(interval_secs));
+        loop {
+            interval.tick().await;
+            match aggregate(&pool).await {
+                Ok(0) => {}
+                Ok(n) => log::debug!("Aggregated {} execution(s) into latency buckets", n),
+                Err(e) => log::warn!("Latency aggregation failed: {}", e),
+            }
+        }
+    });
+}
+
+async fn get_request_latency(
+    State(pool): State<DbPool>,
+    Path(id): Path<i64>,
+    Query(query): Query<LatencyQuery>,
+) -> Result<impl IntoResponse, StatsError> {
+    let (granularity, from, to) = query.resolve(Granularity::Hour);
+    log::debug!(
+        "Getting {} latency for request {} from {} to {}",
+        granularity.as_str(),
+        id,
+        from,
+        to
+    );
+
+    sqlx::query_scalar!("SELECT id FROM requests WHERE id = ?", id)
+        .fetch_one(&pool)
+        .await?;
+
+    let name = granularity.as_str();
+    let buckets = sqlx::query_as!(
+        LatencyBucketDb,
+        r#"SELECT bucket_start as "bucket_start: NaiveDateTime", count, error_count, total_ms, min_ms, max_ms, hist_0, hist_1, hist_2, hist_3, hist_4, hist_5, hist_6, hist_7 FROM latency_buckets WHERE request_id = ? AND granularity = ? AND bucket_start >= ? AND bucket_start < ? ORDER BY bucket_start"#,
+        id,
+        name,
+        from,
+        to
+    )
+    .fetch_all(&pool)
+    .await?;
+
+    Ok(Json(RequestLatency {
+        request_id: id,
+        granularity,
+        histogram_bounds_ms: HISTOGRAM_BOUNDS_MS.to_vec(),
+        buckets: buckets.into_iter().map(LatencyBucket::from).collect(),
+    }))
+}
+
+async fn get_latency_summary(
+    State(pool): State<DbPool>,
+    Query(query): Query<LatencyQuery>,
+) -> Result<impl IntoResponse, StatsError> {
+    let (granularity, from, to) = query.resolve(Granularity::Day);
+    log::debug!(
+        "Getting latency summary from {} {} buckets between {} and {}",
+        granularity.as_str(),
+        granularity.as_str(),
+        from,
+        to
+    );
+
+    let name = granularity.as_str();
+    let summaries = sqlx::query_as!(
+        LatencySummaryDb,
+        r#"SELECT b.request_id as "request_id!", r.name as "name!", SUM(b.count) as "count!: i64", SUM(b.error_count) as "error_count!: i64", SUM(b.total_ms) as "total_ms!: i64", MIN(b.min_ms) as "min_ms!: i64", MAX(b.max_ms) as "max_ms!: i64"
+        FROM latency_buckets b JOIN requests r ON r.id = b.request_id
+        WHERE b.granularity = ? AND b.bucket_start >= ? AND b.bucket_start < ?
+        GROUP BY b.request_id, r.name ORDER BY b.request_id"#,
+        name,
+        from,
+        to
+    )
+    .fetch_all(&pool)
+    .await?;
+
+    Ok(Json(
+        summaries
+            .into_iter()
+            .map(LatencySummary::from)
+            .collect::<Vec<_>>(),
+    ))
+}
+
+async fn get_request_stats(
+    State(pool): State<DbPool>,
+    Path(id): Path<i64>,
+    Query(query): Query<LatencyQuery>,
+) -> Result<impl IntoResponse, StatsError> {
+    let (granularity, from, to) = query.resolve(Granularity::Day);
+    let name = sqlx::query_scalar!("SELECT name FROM requests WHERE id = ?", id)
+        .fetch_one(&pool)
+        .await?;
+
+    // Read from the history itself, the buckets cannot give a percentile. It
+    // is stored by the second, so `to` is inclusive to count runs made just now
+    let format = granularity.bucket_format();
+    let rows = sqlx::query_as!(
+        RunRow,
+        r#"SELECT strftime(?, executed_at) as "key!: String", duration_ms,
+            (error IS NULL AND status IS NOT NULL AND status < 400) as "success!: bool",
+            CASE WHEN latency_budget_ms IS NULL THEN NULL ELSE duration_ms > latency_budget_ms END as "over_budget?: bool"
+        FROM execution_history
+        WHERE request_id = ? AND executed_at >= ? AND executed_at <= ?
+        ORDER BY executed_at"#,
+        format,
+        id,
+        from,
+        to
+    )
+    .fetch_all(&pool)
+    .await?;
+    log::debug!(
+        "Computing stats of request {} over {} run(s)",
+        id,
+        rows.len()
+    );
+
+    let mut durations: Vec<i64> = rows.iter().map(|r| r.duration_ms).collect();
+    durations.sort_unstable();
+    let successes = rows.iter().filter(|r| r.success).count() as i64;
+    let trend = group_stats(&rows)
+        .into_iter()
+        .filter_map(|(bucket, stats)| {
+            let bucket_start = NaiveDateTime::parse_from_str(bucket, TIMESTAMP_FORMAT).ok()?;
+            Some(StatsPoint {
+                bucket_start: bucket_start.and_utc(),
+                stats,
+            })
+        })
+        .collect();
+
+    Ok(Json(RequestStats {
+        request_id: id,
+        name,
+        granularity,
+        stats: run_stats(&durations, successes, rows.iter().map(|r| r.over_budget)),
+        trend,
+    }))
+}
+
+async fn get_overview(
+    State(pool): State<DbPool>,
+    Query(query): Query<OverviewQuery>,
+) -> Result<impl IntoResponse, StatsError> {
+    let (_, from, to) = LatencyQuery {
+        granularity: Some(Granularity::Day),
+        from: query.from,
+        to: query.to,
+    }
+    .resolve(Granularity::Day);
+
+    let rows = sqlx::query_as!(
+        OverviewRow,
+        r#"SELECT h.request_id as "request_id!", r.name, r.folder_id, h.duration_ms,
+            (h.error IS NULL AND h.status IS NOT NULL AND h.status < 400) as "success!: bool",
+            CASE WHEN h.latency_budget_ms IS NULL THEN NULL ELSE h.duration_ms > h.latency_budget_ms END as "over_budget?: bool",
+            h.executed_at as "executed_at: NaiveDateTime"
+        FROM execution_history h JOIN requests r ON r.id = h.request_id
+        WHERE r.archived_at IS NULL AND h.executed_at >= ? AND h.executed_at <= ?
+            AND (? IS NULL OR r.folder_id = ?)
+        ORDER BY h.request_id, h.duration_ms"#,
+        from,
+        to,
+        query.folder_id,
+        query.folder_id
+    )
+    .fetch_all(&pool)
+    .await?;
+
+    let mut overview = Vec::new();
+    for runs in rows.chunk_by(|a, b| a.request_id == b.request_id) {
+        let durations: Vec<i64> = runs.iter().map(|r| r.duration_ms).collect();
+        let successes = runs.iter().filter(|r| r.success).count() as i64;
+        overview.push(RequestOverview {
+            request_id: runs[0].request_id,
+            name: runs[0].name.clone(),
+            folder_id: runs[0].folder_id,
+            stats: run_stats(&durations, successes, runs.iter().map(|r| r.over_budget)),
+            last_run_at: runs.iter().map(|r| r.executed_at.and_utc()).max(),
+        });
+    }
+    overview.sort_by(|a, b| {
+        a.stats
+            .success_rate
+            .total_cmp(&b.stats.success_rate)
+            .then(b.stats.p95_ms.cmp(&a.stats.p95_ms))
+    });
+    Ok(Json(overview))
+}
+
+pub fn routes(pool: DbPool) -> Router {
+    Router::new()
+        .route("/stats/latency", get(get_latency_summary))
+        .route("/stats/requests/:id/latency", get(get_request_latency))
+        .route("/stats/overview", get(get_overview))
+        .route("/requests/:id/stats", get(get_request_stats))
+        .with_state(pool)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db;
+    use crate::history::{self, ExecutionRecord};
+    use axum_test::TestServer;
+
+    async fn create_request(pool: &DbPool) -> i64 {
+        sqlx::query_scalar!(
+            "INSERT INTO requests (name, method, url) VALUES ('Ping', 'GET', 'http://localhost/ping') RETURNING id"
+        )
+        .fetch_one(pool)
+        .await
+        .unwrap()
+    }
+
+    async fn record(pool: &DbPool, request_id: i64, status: Option<u16>, duration_ms: i64) {
+        history::record(
+            pool,
+            ExecutionRecord {
+                request_id: Some(request_id),
+                method: "GET",
+                url: "http://localhost/ping",
+                status,
+                duration_ms,
+                error: status.is_none().then_some("connection refused"),
+                test_results: None,
+                latency_budget_ms: None,
+                response: None,
+            },
+        )
+        .await;
+    }
+
+    #[tokio::test]
+    async fn test_aggregate_is_incremental() {
+        let pool = db::create_test_pool().await;
+        let id = create_request(&pool).await;
+
+        record(&pool, id, Some(200), 40).await;
+        record(&pool, id, Some(500), 300).await;
+        assert_eq!(aggregate(&pool).await.unwrap(), 2);
+        assert_eq!(aggregate(&pool).await.unwrap(), 0);
+
+        record(&pool, id, None, 7000).await;
+        assert_eq!(aggregate(&pool).await.unwrap(), 1);
+
+        let server = TestServer::new(routes(pool)).unwrap();
+        let response = server.get(&format!("/stats/requests/{}/latency", id)).await;
+        response.assert_status_ok();
+        let latency: RequestLatency = response.json();
+        assert_eq!(latency.buckets.len(), 1);
+        let bucket = &latency.buckets[0];
+        assert_eq!(bucket.count, 3);
+        assert_eq!(bucket.error_count, 2);
+        assert_eq!(bucket.min_ms, 40);
+        assert_eq!(bucket.max_ms, 7000);
+        assert_eq!(bucket.histogram, vec![1, 0, 0, 1, 0, 0, 0, 1]);
+    }
+
+    #[tokio::test]
+    async fn test_latency_summary() {
+        let pool = db::create_test_pool().await;
+        let id = create_request(&pool).await;
+        record(&pool, id, Some(200), 100).await;
+        record(&pool, id, Some(200), 300).await;
+        aggregate(&pool).await.unwrap();
+
+        let server = TestServer::new(routes(pool)).unwrap();
+        let summary: Vec<LatencySummary> = server
+            .get("/stats/latency")
+            .add_query_param("granularity", "day")
+            .await
+            .json();
+        assert_eq!(summary.len(), 1);
+        assert_eq!(summary[0].name, "Ping");
+        assert_eq!(summary[0].count, 2);
+        assert_eq!(summary[0].avg_ms, 200.0);
+        assert_eq!(summary[0].error_rate, 0.0);
+
+        server
+            .get("/stats/requests/999/latency")
+            .await
+            .assert_status(StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_request_stats_and_overview() {
+        let pool = db::create_test_pool().await;
+        let steady = create_request(&pool).await;
+        let flaky = create_request(&pool).await;
+        for ms in 1..=20 {
+            record(&pool, steady, Some(200), ms * 10).await;
+        }
+        record(&pool, flaky, Some(200), 50).await;
+        record(&pool, flaky, Some(503), 900).await;
+        record(&pool, flaky, None, 30).await;
+
+        let server = TestServer::new(routes(pool)).unwrap();
+        let stats: RequestStats = server
+            .get(&format!("/requests/{}/stats", steady))
+            .add_query_param("granularity", "hour")
+            .await
+            .json();
+        assert_eq!(stats.stats.run_count, 20);
+        assert_eq!(stats.stats.success_rate, 1.0);
+        assert_eq!(stats.stats.avg_ms, 105.0);
+        assert_eq!(stats.stats.p95_ms, 190);
+        assert_eq!(stats.trend.len(), 1);
+        assert_eq!(stats.trend[0].stats.run_count, 20);
+
+        let overview: Vec<RequestOverview> = server.get("/stats/overview").await.json();
+        let ids: Vec<i64> = overview.iter().map(|o| o.request_id).collect();
+        assert_eq!(ids, vec![flaky, steady]);
+        assert_eq!(overview[0].stats.success_count, 1);
+        assert_eq!(overview[0].stats.p95_ms, 900);
+        assert!(overview[0].last_run_at.is_some());
+
+        server
+            .get("/requests/999/stats")
+            .await
+            .assert_status(StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_budget_compliance() {
+        let pool = db::create_test_pool().await;
+        let id = create_request(&pool).await;
+        record(&pool, id, Some(200), 500).await;
+        for (duration_ms, budget) in [(80, 100), (100, 100), (250, 100), (400, 300)] {
+            history::record(
+                &pool,
+                ExecutionRecord {
+                    request_id: Some(id),
+                    method: "GET",
+                    url: "http://localhost/ping",
+                    status: Some(200),
+                    duration_ms,
+                    error: None,
+                    test_results: None,
+                    latency_budget_ms: Some(budget),
+                    response: None,
+                },
+            )
+            .await;
+        }
+
+        let server = TestServer::new(routes(pool.clone())).unwrap();
+        let stats: RequestStats = server.get(&format!("/requests/{}/stats", id)).await.json();
+        assert_eq!(stats.stats.run_count, 5);
+        assert_eq!(stats.stats.budgeted_count, 4);
+        assert_eq!(stats.stats.over_budget_count, 2);
+        assert_eq!(stats.stats.budget_compliance, Some(0.5));
+
+        let history = TestServer::new(history::routes(pool)).unwrap();
+        let violations: Vec<history::BudgetViolation> = history
+            .get("/history/over-budget")
+            .add_query_param("request_id", id)
+            .await
+            .json();
+        let durations: Vec<(i64, i64)> = violations
+            .iter()
+            .map(|v| (v.duration_ms, v.latency_budget_ms))
+            .collect();
+        assert_eq!(durations, vec![(400, 300), (250, 100)]);
+    }
+}
@@ -0,0 +1,137 @@
+use std::env;
+use std::sync::OnceLock;
+
+use opentelemetry::propagation::{Injector, TextMapPropagator};
+use opentelemetry::trace::TracerProvider as _;
+use opentelemetry_otlp::SpanExporter;
+use opentelemetry_sdk::{
+    propagation::TraceContextPropagator,
+    trace::{SdkTracer, SdkTracerProvider},
+    Resource,
+};
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use tracing::Subscriber;
+use tracing_opentelemetry::{OpenTelemetryLayer, OpenTelemetrySpanExt};
+use tracing_subscriber::registry::LookupSpan;
+
+const SERVICE_NAME: &str = "js-link";
+
+static CONFIG: OnceLock<TelemetryConfig> = OnceLock::new();
+
+/// Where spans are exported, read from the standard OpenTelemetry variables.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TelemetryConfig {
+    /// Spans are exported over OTLP/HTTP when either
+    /// `OTEL_EXPORTER_OTLP_ENDPOINT` or `OTEL_EXPORTER_OTLP_TRACES_ENDPOINT`
+    /// is set.
+    pub export: bool,
+    /// Outgoing requests carry a W3C `traceparent` header
+    /// (`JSLINK_TRACEPARENT`).
+    pub traceparent: bool,
+}
+
+impl TelemetryConfig {
+    pub fn from_env() -> Self {
+        let set = |name: &str| env::var(name).is_ok_and(|v| !v.trim().is_empty());
+        Self {
+            export: set("OTEL_EXPORTER_OTLP_ENDPOINT") || set("OTEL_EXPORTER_OTLP_TRACES_ENDPOINT"),
+            traceparent: env::var("JSLINK_TRACEPARENT")
+                .map(|v| v == "true" || v == "1")
+                .unwrap_or(false),
+        }
+    }
+}
+
+fn config() -> &'static TelemetryConfig {
+    CONFIG.get_or_init(TelemetryConfig::default)
+}
+
+/// The layer exporting spans, when an OTLP endpoint is configured. Spans are
+/// batched and sent from a background thread, so a collector that is down
+/// only costs the spans.
+pub fn layer<S>(config: TelemetryConfig) -> Option<OpenTelemetryLayer<S, SdkTracer>>
+where
+    S: Subscriber + for<'span> LookupSpan<'span>,
+{
+    let _ = CONFIG.set(config.clone());
+    if !config.export {
+        return None;
+    }
+    // The exporter reads the endpoint, headers and timeout itself
+    let exporter = match SpanExporter::builder().with_http().build() {
+        Ok(exporter) => exporter,
+        Err(e) => {
+            eprintln!("OpenTelemetry export disabled: {}", e);
+            return None;
+        }
+    };
+    let mut resource = Resource::builder();
+    if env::var("OTEL_SERVICE_NAME").is_err() {
+        resource = resource.with_service_name(SERVICE_NAME);
+    }
+    let provider = SdkTracerProvider::builder()
+        .with_batch_exporter(exporter)
+        .with_resource(resource.build())
+        .build();
+    Some(tracing_opentelemetry::layer().with_tracer(provider.tracer(SERVICE_NAME)))
+}
+
+struct HeaderInjector<'a>(&'a mut HeaderMap);
+
+impl Injector for HeaderInjector<'_> {
+    fn set(&mut self, key: &str, value: String) {
+        if let (Ok(name), Ok(value)) = (HeaderName::try_from(key), HeaderValue::from_str(&value)) {
+            self.0.insert(name, value);
+        }
+    }
+}
+
+fn inject(span: &tracing::Span, headers: &mut HeaderMap) {
+    // A traceparent set on the request itself wins
+    if headers.contains_key("traceparent") {
+        return;
+    }
+    // Spans that are not exported have no context, nothing is added then
+    TraceContextPropagator::new().inject_context(&span.context(), &mut HeaderInjector(headers));
+}
+
+/// Adds `span` as the parent of an outgoing request when `JSLINK_TRACEPARENT`
+/// is on.
+pub fn inject_traceparent(span: &tracing::Span, headers: &mut HeaderMap) {
+    if config().traceparent {
+        inject(span, headers);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tracing_subscriber::{layer::SubscriberExt, Registry};
+
+    #[test]
+    fn test_traceparent_injection() {
+        // Without an exporter spans carry no context
+        let mut headers = HeaderMap::new();
+        inject(&tracing::info_span!("execute"), &mut headers);
+        assert!(headers.is_empty());
+
+        let tracer = SdkTracerProvider::builder().build().tracer("test");
+        let subscriber =
+            Registry::default().with(tracing_opentelemetry::layer().with_tracer(tracer));
+        tracing::subscriber::with_default(subscriber, || {
+            let span = tracing::info_span!("execute");
+            let mut headers = HeaderMap::new();
+            inject(&span, &mut headers);
+            let traceparent = headers["traceparent"].to_str().unwrap();
+            let parts: Vec<&str> = traceparent.split('-').collect();
+            assert_eq!(parts.len(), 4);
+            assert_eq!(parts[0], "00");
+            assert_eq!(parts[1].len(), 32);
+
+            let mut headers = HeaderMap::new();
+            headers.insert("traceparent", HeaderValue::from_static("00-own-01"));
+            inject(&span, &mut headers);
+            assert_eq!(headers["traceparent"], "00-own-01");
+        });
+    }
+}
@@ -0,0 +1,235 @@
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    routing::get,
+    Json, Router,
+};
+use reqwest::{tls::Version, ClientBuilder};
+use serde::{Deserialize, Serialize};
+
+use crate::{db::DbPool, i18n};
+
+const SETTING: &str = "tls_versions";
+
+/// A TLS protocol version, written as "1.0" to "1.3".
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum TlsVersion {
+    #[serde(rename = "1.0")]
+    Tls1_0,
+    #[serde(rename = "1.1")]
+    Tls1_1,
+    #[serde(rename = "1.2")]
+    Tls1_2,
+    #[serde(rename = "1.3")]
+    Tls1_3,
+}
+
+impl TlsVersion {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            TlsVersion::Tls1_0 => "1.0",
+            TlsVersion::Tls1_1 => "1.1",
+            TlsVersion::Tls1_2 => "1.2",
+            TlsVersion::Tls1_3 => "1.3",
+        }
+    }
+
+    fn to_reqwest(self) -> Version {
+        match self {
+            TlsVersion::Tls1_0 => Version::TLS_1_0,
+            TlsVersion::Tls1_1 => Version::TLS_1_1,
+            TlsVersion::Tls1_2 => Version::TLS_1_2,
+            TlsVersion::Tls1_3 => Version::TLS_1_3,
+        }
+    }
+
+    /// The version a response was received over, when the TLS backend
+    /// reports it.
+    pub fn negotiated(response: &reqwest::Response) -> Option<Self> {
+        let version = response
+            .extensions()
+            .get::<reqwest::tls::TlsInfo>()?
+            .version()?;
+        [
+            TlsVersion::Tls1_0,
+            TlsVersion::Tls1_1,
+            TlsVersion::Tls1_2,
+            TlsVersion::Tls1_3,
+        ]
+        .into_iter()
+        .find(|v| v.to_reqwest() == version)
+    }
+}
+
+/// The TLS versions a connection may use, from the `tls_versions` column of
+/// a request or the global setting, e.g. `{"min": "1.3"}` to check that a
+/// server negotiates TLS 1.3. Unset bounds leave the backend's defaults.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TlsVersions {
+    #[serde(default)]
+    pub min: Option<TlsVersion>,
+    #[serde(default)]
+    pub max: Option<TlsVersion>,
+}
+
+impl TlsVersions {
+    pub fn parse(value: Option<&str>) -> Result<Self, String> {
+        let Some(value) = value.filter(|v| !v.trim().is_empty()) else {
+            return Ok(Self::default());
+        };
+        let versions: TlsVersions = serde_json::from_str(value).map_err(|e| {
+            format!(
+                "Invalid TLS versions: {}, expected versions 1.0, 1.1, 1.2 or 1.3",
+                e
+            )
+        })?;
+        versions.validate()?;
+        Ok(versions)
+    }
+
+    /// The connections use rustls, which speaks TLS 1.2 and 1.3 only. A
+    /// minimum of 1.0 or 1.1 is accepted and has no effect.
+    pub fn validate(&self) -> Result<(), String> {
+        if let (Some(min), Some(max)) = (self.min, self.max) {
+            if min > max {
+                return Err(format!(
+                    "Minimum TLS version {} is above the maximum {}",
+                    min.as_str(),
+                    max.as_str()
+                ));
+            }
+        }
+        match self.max {
+            Some(max) if max < TlsVersion::Tls1_2 => Err(format!(
+                "Maximum TLS version {} is not supported, connections use TLS 1.2 or 1.3",
+                max.as_str()
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Each bound of the request, else the global one. When the mix would
+    /// allow no version, the request's own bounds are used alone.
+    pub fn or(self, global: TlsVersions) -> TlsVersions {
+        let merged = TlsVersions {
+            min: self.min.or(global.min),
+            max: self.max.or(global.max),
+        };
+        if merged.validate().is_ok() {
+            merged
+        } else {
+            self
+        }
+    }
+
+    pub fn apply(self, mut builder: ClientBuilder) -> ClientBuilder {
+        if let Some(min) = self.min {
+            builder = builder.tls_version_min(min.to_reqwest());
+        }
+        if let Some(max) = self.max {
+            builder = builder.tls_version_max(max.to_reqwest());
+        }
+        builder
+    }
+
+    pub async fn global(pool: &DbPool) -> Self {
+        let stored = sqlx::query_scalar!("SELECT value FROM app_settings WHERE name = ?", SETTING)
+            .fetch_optional(pool)
+            .await
+            .unwrap_or_else(|e| {
+                log::warn!("Failed to load TLS versions setting: {}", e);
+                None
+            });
+        stored
+            .and_then(|value| Self::parse(Some(&value)).ok())
+            .unwrap_or_default()
+    }
+}
+
+pub enum TlsSettingError {
+    Invalid(String),
+    DatabaseError(sqlx::Error),
+}
+
+impl From<sqlx::Error> for TlsSettingError {
+    fn from(e: sqlx::Error) -> Self {
+        TlsSettingError::DatabaseError(e)
+    }
+}
+
+impl IntoResponse for TlsSettingError {
+    fn into_response(self) -> Response {
+        match self {
+            TlsSettingError::Invalid(msg) => (StatusCode::BAD_REQUEST, msg).into_response(),
+            TlsSettingError::DatabaseError(e) => {
+                log::error!("Database error: {}", e);
+                (StatusCode::INTERNAL_SERVER_ERROR, i18n::t("error.database")).into_response()
+            }
+        }
+    }
+}
+
+async fn get_setting(State(pool): State<DbPool>) -> Json<TlsVersions> {
+    Json(TlsVersions::global(&pool).await)
+}
+
+async fn set_setting(
+    State(pool): State<DbPool>,
+    Json(payload): Json<TlsVersions>,
+) -> Result<Json<TlsVersions>, TlsSettingError> {
+    payload.validate().map_err(TlsSettingError::Invalid)?;
+    let value = serde_json::to_string(&payload).unwrap_or_else(|_| "{}".to_string());
+    sqlx::query!(
+        "INSERT INTO app_settings (name, value) VALUES (?, ?) ON CONFLICT (name) DO UPDATE SET value = excluded.value, updated_at = CURRENT_TIMESTAMP",
+        SETTING,
+        value
+    )
+    .execute(&pool)
+    .await?;
+    log::info!("TLS versions set to {}", value);
+    Ok(Json(payload))
+}
+
+pub fn routes(pool: DbPool) -> Router {
+    Router::new()
+        .route("/settings/tls-versions", get(get_setting).put(set_setting))
+        .with_state(pool)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_and_merge() {
+        assert!(TlsVersions::parse(None).unwrap().is_default());
+        assert!(TlsVersions::parse(Some(" ")).unwrap().is_default());
+        let strict = TlsVersions::parse(Some(r#"{"min": "1.3"}"#)).unwrap();
+        assert_eq!(strict.min, Some(TlsVersion::Tls1_3));
+        assert_eq!(strict.max, None);
+
+        assert!(TlsVersions::parse(Some(r#"{"min": "1.3", "max": "1.2"}"#)).is_err());
+        assert!(TlsVersions::parse(Some(r#"{"max": "1.1"}"#)).is_err());
+        assert!(TlsVersions::parse(Some(r#"{"min": "1.4"}"#)).is_err());
+        assert!(TlsVersions::parse(Some(r#"{"minimum": "1.2"}"#)).is_err());
+
+        let global = TlsVersions {
+            min: Some(TlsVersion::Tls1_2),
+            max: Some(TlsVersion::Tls1_2),
+        };
+        let pinned = TlsVersions {
+            min: None,
+            max: Some(TlsVersion::Tls1_2),
+        };
+        assert_eq!(pinned.or(global), global);
+        // A request that needs 1.3 is not capped by a global 1.2 maximum
+        assert_eq!(strict.or(global), strict);
+        assert_eq!(TlsVersions::default().or(global), global);
+    }
+}
@@ -0,0 +1,279 @@
+use axum::{
+    extract::DefaultBodyLimit,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    routing::post,
+    Json, Router,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::body_lint::{self, BodyIssue};
+use crate::capture::{self, CaptureSource};
+use crate::{generate, i18n, schema_example};
+
+// Response bodies can be much larger than axum's 2 MB default
+const MAX_BODY_SIZE: usize = 10 * 1024 * 1024;
+
+#[derive(Deserialize)]
+pub struct ExtractPayload {
+    /// `body` for JSONPath, `xpath` or `regex`, as in a capture rule
+    source: CaptureSource,
+    expression: String,
+    body: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ExtractResult {
+    /// Every match, in document order
+    pub matches: Vec<String>,
+    /// What a capture rule with this expression would store
+    pub captured: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct BodyFromSchemaPayload {
+    /// A JSON Schema, or a whole OpenAPI document when `component` is given
+    schema: Value,
+    /// Name of the schema under `components.schemas` (or Swagger's
+    /// `definitions`) to generate a body for
+    component: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct SchemaFromBodyPayload {
+    /// An example body; a string is parsed as JSON
+    body: Value,
+}
+
+#[derive(Deserialize)]
+pub struct ValidateBodyPayload {
+    /// `json`, `xml` or `form`, as on a request; other types always pass
+    body_type: String,
+    body: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ValidateBodyResult {
+    pub valid: bool,
+    pub issues: Vec<BodyIssue>,
+}
+
+pub enum ToolsError {
+    InvalidExpression(String),
+    InvalidSchema(String),
+    ComponentNotFound(String),
+    InvalidBody(String),
+}
+
+impl IntoResponse for ToolsError {
+    fn into_response(self) -> Response {
+        let message = match self {
+            ToolsError::InvalidExpression(msg) => {
+                i18n::tf("error.extraction_failed", &[("message", &msg)])
+            }
+            ToolsError::InvalidSchema(msg) => {
+                i18n::tf("error.invalid_schema", &[("message", &msg)])
+            }
+            ToolsError::ComponentNotFound(name) => {
+                i18n::tf("error.schema_component_not_found", &[("name", &name)])
+            }
+            ToolsError::InvalidBody(msg) => i18n::tf("error.body_not_json", &[("message", &msg)]),
+        };
+        (StatusCode::BAD_REQUEST, message).into_response()
+    }
+}
+
+/// Runs an extraction expression against a body, so capture rules can be
+/// tried before they are saved on a request.
+async fn extract_handler(
+    Json(payload): Json<ExtractPayload>,
+) -> Result<impl IntoResponse, ToolsError> {
+    log::debug!(
+        "Trying {:?} expression on {} bytes",
+        payload.source,
+        payload.body.len()
+    );
+    let matches = capture::find_all(payload.source, &payload.expression, &payload.body)
+        .map_err(ToolsError::InvalidExpression)?;
+    Ok(Json(ExtractResult {
+        captured: matches.first().cloned(),
+        matches,
+    }))
+}
+
+/// Generates an example body matching a JSON Schema or OpenAPI component.
+async fn body_from_schema_handler(
+    Json(payload): Json<BodyFromSchemaPayload>,
+) -> Result<impl IntoResponse, ToolsError> {
+    let schema = match &payload.component {
+        Some(name) => schema_example::component(&payload.schema, name)
+            .ok_or_else(|| ToolsError::ComponentNotFound(name.clone()))?,
+        None => &payload.schema,
+    };
+    let body =
+        schema_example::example(schema, &payload.schema).map_err(ToolsError::InvalidSchema)?;
+    Ok(Json(json!({ "body": body })))
+}
+
+/// Infers a JSON Schema from an example body.
+async fn schema_from_body_handler(
+    Json(payload): Json<SchemaFromBodyPayload>,
+) -> Result<impl IntoResponse, ToolsError> {
+    let body = match payload.body {
+        Value::String(text) => {
+            serde_json::from_str(&text).map_err(|e| ToolsError::InvalidBody(e.to_string()))?
+        }
+        body => body,
+    };
+    Ok(Json(json!({ "schema": generate::infer_schema(&body) })))
+}
+
+/// Lints a body for its declared type, so a malformed payload is caught
+/// before it is sent.
+async fn validate_body_handler(
+    Json(payload): Json<ValidateBodyPayload>,
+) -> Json<ValidateBodyResult> {
+    let issues = body_lint::lint(&payload.body_type, &payload.body);
+    Json(ValidateBodyResult {
+        valid: issues.is_empty(),
+        issues,
+    })
+}
+
+pub fn routes() -> Router {
+    Router::new()
+        .route(
+            "/tools/extract",
+            post(extract_handler).layer(DefaultBodyLimit::max(MAX_BODY_SIZE)),
+        )
+        .route(
+            "/tools/body-from-schema",
+            post(body_from_schema_handler).layer(DefaultBodyLimit::max(MAX_BODY_SIZE)),
+        )
+        .route(
+            "/tools/schema-from-body",
+            post(schema_from_body_handler).layer(DefaultBodyLimit::max(MAX_BODY_SIZE)),
+        )
+        .route(
+            "/tools/validate-body",
+            post(validate_body_handler).layer(DefaultBodyLimit::max(MAX_BODY_SIZE)),
+        )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum_test::TestServer;
+
+    #[tokio::test]
+    async fn test_extract_endpoint() {
+        let server = TestServer::new(routes()).unwrap();
+        let extract = |source: &str, expression: &str, body: &str| {
+            server.post("/tools/extract").json(&json!({
+                "source": source,
+                "expression": expression,
+                "body": body,
+            }))
+        };
+
+        let result: ExtractResult =
+            extract("body", "$.items[1].id", r#"{"items":[{"id":1},{"id":2}]}"#)
+                .await
+                .json();
+        assert_eq!(result.matches, vec!["2"]);
+        assert_eq!(result.captured.as_deref(), Some("2"));
+
+        let result: ExtractResult = extract(
+            "xpath",
+            "//item/@sku",
+            r#"<order><item sku="a"/><item sku="b"/></order>"#,
+        )
+        .await
+        .json();
+        assert_eq!(result.matches, vec!["a", "b"]);
+
+        let result: ExtractResult = extract("regex", r"id=(\d+)", "id=4 id=5").await.json();
+        assert_eq!(result.matches, vec!["4", "5"]);
+        assert_eq!(result.captured.as_deref(), Some("4"));
+
+        let result: ExtractResult = extract("body", "$.missing", "{}").await.json();
+        assert!(result.matches.is_empty());
+        assert_eq!(result.captured, None);
+
+        extract("body", "$.a", "not json")
+            .await
+            .assert_status(StatusCode::BAD_REQUEST);
+        extract("xpath", "order", "<order/>")
+            .await
+            .assert_status(StatusCode::BAD_REQUEST);
+        extract("header", "Location", "")
+            .await
+            .assert_status(StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_schema_round_trip() {
+        let server = TestServer::new(routes()).unwrap();
+        let document = json!({
+            "openapi": "3.0.0",
+            "components": { "schemas": { "User": {
+                "type": "object",
+                "properties": {
+                    "email": { "type": "string", "format": "email" },
+                    "age": { "type": "integer", "minimum": 40 }
+                }
+            }}}
+        });
+
+        let response: Value = server
+            .post("/tools/body-from-schema")
+            .json(&json!({ "schema": document, "component": "User" }))
+            .await
+            .json();
+        let body = &response["body"];
+        assert_eq!(body, &json!({ "email": "jane.doe@example.com", "age": 40 }));
+
+        let response = server
+            .post("/tools/body-from-schema")
+            .json(&json!({ "schema": document, "component": "Order" }))
+            .await;
+        response.assert_status(StatusCode::BAD_REQUEST);
+        response.assert_text("The document has no schema named 'Order'");
+
+        let response: Value = server
+            .post("/tools/schema-from-body")
+            .json(&json!({ "body": body.to_string() }))
+            .await
+            .json();
+        assert_eq!(response["schema"]["properties"]["age"]["type"], "integer");
+        assert_eq!(response["schema"]["properties"]["email"]["format"], "email");
+
+        server
+            .post("/tools/schema-from-body")
+            .json(&json!({ "body": "{not json" }))
+            .await
+            .assert_status(StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_validate_body_endpoint() {
+        let server = TestServer::new(routes()).unwrap();
+        let result: ValidateBodyResult = server
+            .post("/tools/validate-body")
+            .json(&json!({ "body_type": "json", "body": "{\"a\": [1, 2}" }))
+            .await
+            .json();
+        assert!(!result.valid);
+        assert_eq!(result.issues[0].line, Some(1));
+        assert_eq!(result.issues[0].column, Some(12));
+
+        let result: ValidateBodyResult = server
+            .post("/tools/validate-body")
+            .json(&json!({ "body_type": "xml", "body": "<a/>" }))
+            .await
+            .json();
+        assert!(result.valid);
+        assert!(result.issues.is_empty());
+    }
+}
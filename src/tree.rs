@@ -0,0 +1,195 @@
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    routing::get,
+    Json, Router,
+};
+use chrono::{DateTime, NaiveDateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{db::DbPool, i18n};
+
+/// The sidebar: folders in the order they were created, each with its
+/// requests, and the requests outside any folder.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Tree {
+    pub folders: Vec<TreeFolder>,
+    pub requests: Vec<TreeRequest>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TreeFolder {
+    pub id: i64,
+    pub name: String,
+    pub archived_at: Option<DateTime<Utc>>,
+    /// Number of `requests`
+    pub request_count: usize,
+    pub requests: Vec<TreeRequest>,
+}
+
+/// What the sidebar shows of a request; the rest is fetched when it is
+/// opened.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TreeRequest {
+    pub id: i64,
+    pub name: String,
+    pub method: String,
+    pub request_type: String,
+    pub archived_at: Option<DateTime<Utc>>,
+}
+
+/// A folder with one of its requests, a folder without requests or a
+/// request outside any folder.
+struct TreeRow {
+    folder_id: Option<i64>,
+    folder_name: Option<String>,
+    folder_archived_at: Option<NaiveDateTime>,
+    request_id: Option<i64>,
+    request_name: Option<String>,
+    method: Option<String>,
+    request_type: Option<String>,
+    request_archived_at: Option<NaiveDateTime>,
+}
+
+#[derive(Deserialize)]
+pub struct TreeQuery {
+    #[serde(default)]
+    include_archived: bool,
+}
+
+#[derive(Debug)]
+pub enum TreeError {
+    DatabaseError(#[allow(dead_code)] sqlx::Error),
+}
+
+impl From<sqlx::Error> for TreeError {
+    fn from(e: sqlx::Error) -> Self {
+        TreeError::DatabaseError(e)
+    }
+}
+
+impl IntoResponse for TreeError {
+    fn into_response(self) -> Response {
+        match self {
+            TreeError::DatabaseError(_) => {
+                (StatusCode::INTERNAL_SERVER_ERROR, i18n::t("error.database")).into_response()
+            }
+        }
+    }
+}
+
+pub async fn load_tree(pool: &DbPool, include_archived: bool) -> Result<Tree, sqlx::Error> {
+    let rows = sqlx::query_as!(
+        TreeRow,
+        r#"SELECT folder_id as "folder_id?: i64", folder_name as "folder_name?: String", folder_archived_at as "folder_archived_at?: NaiveDateTime",
+            request_id as "request_id?: i64", request_name as "request_name?: String", method as "method?: String",
+            request_type as "request_type?: String", request_archived_at as "request_archived_at?: NaiveDateTime"
+        FROM (
+            SELECT f.id AS folder_id, f.name AS folder_name, f.archived_at AS folder_archived_at, f.created_at AS folder_created_at,
+                r.id AS request_id, r.name AS request_name, r.method, r.request_type, r.archived_at AS request_archived_at
+            FROM folders f
+            LEFT JOIN requests r ON r.folder_id = f.id AND (? OR r.archived_at IS NULL)
+            WHERE (? OR f.archived_at IS NULL)
+            UNION ALL
+            SELECT NULL, NULL, NULL, NULL, r.id, r.name, r.method, r.request_type, r.archived_at
+            FROM requests r
+            WHERE r.folder_id IS NULL AND (? OR r.archived_at IS NULL)
+        )
+        ORDER BY folder_id IS NULL, folder_created_at, folder_id, request_id"#,
+        include_archived,
+        include_archived,
+        include_archived
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let mut tree = Tree::default();
+    for row in rows {
+        let request = match (row.request_id, row.request_name) {
+            (Some(id), Some(name)) => Some(TreeRequest {
+                id,
+                name,
+                method: row.method.unwrap_or_default(),
+                request_type: row.request_type.unwrap_or_default(),
+                archived_at: row.request_archived_at.map(|at| at.and_utc()),
+            }),
+            _ => None,
+        };
+        let Some(folder_id) = row.folder_id else {
+            tree.requests.extend(request);
+            continue;
+        };
+        if tree.folders.last().map(|f| f.id) != Some(folder_id) {
+            tree.folders.push(TreeFolder {
+                id: folder_id,
+                name: row.folder_name.unwrap_or_default(),
+                archived_at: row.folder_archived_at.map(|at| at.and_utc()),
+                request_count: 0,
+                requests: Vec::new(),
+            });
+        }
+        if let (Some(folder), Some(request)) = (tree.folders.last_mut(), request) {
+            folder.requests.push(request);
+            folder.request_count += 1;
+        }
+    }
+    Ok(tree)
+}
+
+async fn get_tree(
+    State(pool): State<DbPool>,
+    Query(query): Query<TreeQuery>,
+) -> Result<impl IntoResponse, TreeError> {
+    let tree = load_tree(&pool, query.include_archived).await?;
+    log::debug!(
+        "Built tree of {} folders and {} unfiled requests",
+        tree.folders.len(),
+        tree.requests.len()
+    );
+    Ok(Json(tree))
+}
+
+pub fn routes(pool: DbPool) -> Router {
+    Router::new().route("/tree", get(get_tree)).with_state(pool)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db;
+    use axum_test::TestServer;
+
+    #[tokio::test]
+    async fn test_tree() {
+        let pool = db::create_test_pool().await;
+        sqlx::query(
+            "INSERT INTO folders (id, name, created_at) VALUES (1, 'Users', '2026-01-02 00:00:00'), (2, 'Orders', '2026-01-01 00:00:00'), (3, 'Empty', '2026-01-03 00:00:00')",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        sqlx::query(
+            "INSERT INTO requests (name, method, url, folder_id, request_type, archived_at) VALUES ('List users', 'GET', 'http://x/users', 1, 'api', NULL), ('Feed', 'GET', 'ws://x/feed', 2, 'ws', NULL), ('Old', 'DELETE', 'http://x/users/1', 1, 'api', '2026-01-05 00:00:00'), ('Ping', 'GET', 'http://x/ping', NULL, 'api', NULL)",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        let server = TestServer::new(routes(pool)).unwrap();
+
+        let tree: Tree = server.get("/tree").await.json();
+        let folders: Vec<(&str, usize)> = tree
+            .folders
+            .iter()
+            .map(|f| (f.name.as_str(), f.request_count))
+            .collect();
+        assert_eq!(folders, [("Orders", 1), ("Users", 1), ("Empty", 0)]);
+        assert_eq!(tree.folders[0].requests[0].request_type, "ws");
+        assert_eq!(tree.requests.len(), 1);
+        assert_eq!(tree.requests[0].name, "Ping");
+
+        let tree: Tree = server.get("/tree?include_archived=true").await.json();
+        assert_eq!(tree.folders[1].request_count, 2);
+        assert!(tree.folders[1].requests[1].archived_at.is_some());
+    }
+}
@@ -0,0 +1,173 @@
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    routing::get,
+    Json, Router,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{db::DbPool, i18n};
+
+const SETTING: &str = "unresolved_variables";
+
+/// What an execution does with `{{name}}` placeholders no variable or
+/// generator matches, from the request's `unresolved_variables` column or
+/// the global setting.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum UnresolvedMode {
+    /// Fails the execution, naming the placeholders
+    #[default]
+    Error,
+    /// Sends the placeholders as they are and reports them in the response
+    Warn,
+    /// Sends the placeholders as they are, for bodies that contain literal
+    /// double braces
+    Keep,
+}
+
+impl UnresolvedMode {
+    pub fn parse(value: &str) -> Result<Self, String> {
+        match value.trim().to_lowercase().as_str() {
+            "error" => Ok(UnresolvedMode::Error),
+            "warn" => Ok(UnresolvedMode::Warn),
+            "keep" => Ok(UnresolvedMode::Keep),
+            _ => Err(format!(
+                "Invalid unresolved variables mode '{}', expected error, warn or keep",
+                value
+            )),
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            UnresolvedMode::Error => "error",
+            UnresolvedMode::Warn => "warn",
+            UnresolvedMode::Keep => "keep",
+        }
+    }
+
+    /// The request's own mode, else the global setting.
+    pub async fn for_request(pool: &DbPool, value: Option<&str>) -> Self {
+        match value.filter(|v| !v.is_empty()).map(Self::parse) {
+            Some(Ok(mode)) => mode,
+            Some(Err(e)) => {
+                log::warn!("{}, using the global setting", e);
+                Self::global(pool).await
+            }
+            None => Self::global(pool).await,
+        }
+    }
+
+    pub async fn global(pool: &DbPool) -> Self {
+        let stored = sqlx::query_scalar!("SELECT value FROM app_settings WHERE name = ?", SETTING)
+            .fetch_optional(pool)
+            .await
+            .unwrap_or_else(|e| {
+                log::warn!("Failed to load unresolved variables setting: {}", e);
+                None
+            });
+        stored
+            .and_then(|value| Self::parse(&value).ok())
+            .unwrap_or_default()
+    }
+}
+
+pub enum SettingError {
+    InvalidMode(String),
+    DatabaseError(#[allow(dead_code)] sqlx::Error),
+}
+
+impl From<sqlx::Error> for SettingError {
+    fn from(e: sqlx::Error) -> Self {
+        SettingError::DatabaseError(e)
+    }
+}
+
+impl IntoResponse for SettingError {
+    fn into_response(self) -> Response {
+        match self {
+            SettingError::InvalidMode(msg) => (StatusCode::BAD_REQUEST, msg).into_response(),
+            SettingError::DatabaseError(_) => {
+                (StatusCode::INTERNAL_SERVER_ERROR, i18n::t("error.database")).into_response()
+            }
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UnresolvedSetting {
+    pub mode: String,
+}
+
+async fn get_setting(State(pool): State<DbPool>) -> Json<UnresolvedSetting> {
+    Json(UnresolvedSetting {
+        mode: UnresolvedMode::global(&pool).await.as_str().to_string(),
+    })
+}
+
+async fn set_setting(
+    State(pool): State<DbPool>,
+    Json(payload): Json<UnresolvedSetting>,
+) -> Result<Json<UnresolvedSetting>, SettingError> {
+    let mode = UnresolvedMode::parse(&payload.mode).map_err(SettingError::InvalidMode)?;
+    let value = mode.as_str();
+    sqlx::query!(
+        "INSERT INTO app_settings (name, value) VALUES (?, ?) ON CONFLICT (name) DO UPDATE SET value = excluded.value, updated_at = CURRENT_TIMESTAMP",
+        SETTING,
+        value
+    )
+    .execute(&pool)
+    .await?;
+    log::info!("Unresolved variables mode set to {}", value);
+    Ok(Json(UnresolvedSetting {
+        mode: value.to_string(),
+    }))
+}
+
+pub fn routes(pool: DbPool) -> Router {
+    Router::new()
+        .route(
+            "/settings/unresolved-variables",
+            get(get_setting).put(set_setting),
+        )
+        .with_state(pool)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db;
+    use axum_test::TestServer;
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn test_setting() {
+        let pool = db::create_test_pool().await;
+        let server = TestServer::new(routes(pool.clone())).unwrap();
+
+        let setting: UnresolvedSetting = server.get("/settings/unresolved-variables").await.json();
+        assert_eq!(setting.mode, "error");
+
+        server
+            .put("/settings/unresolved-variables")
+            .json(&json!({ "mode": "Warn" }))
+            .await
+            .assert_status_ok();
+        assert_eq!(UnresolvedMode::global(&pool).await, UnresolvedMode::Warn);
+        assert_eq!(
+            UnresolvedMode::for_request(&pool, Some("keep")).await,
+            UnresolvedMode::Keep
+        );
+        assert_eq!(
+            UnresolvedMode::for_request(&pool, None).await,
+            UnresolvedMode::Warn
+        );
+
+        server
+            .put("/settings/unresolved-variables")
+            .json(&json!({ "mode": "ignore" }))
+            .await
+            .assert_status(StatusCode::BAD_REQUEST);
+    }
+}
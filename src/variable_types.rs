@@ -0,0 +1,150 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+
+/// The declared type of an environment variable, stored in the `types`
+/// column of environments as a JSON object by variable name, e.g.
+/// `{"port": {"type": "number"}, "region": {"type": "enum", "values": ["eu", "us"]}}`.
+/// Variables without a type take any text.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum VariableType {
+    String,
+    /// An integer or decimal number
+    Number,
+    /// `true` or `false`
+    Boolean,
+    /// Any text, stored encrypted and masked like the environment's secrets
+    Secret,
+    /// One of the listed values
+    Enum {
+        values: Vec<String>,
+    },
+}
+
+pub type VariableTypes = BTreeMap<String, VariableType>;
+
+impl VariableType {
+    /// Checks a value against the type. Values that still hold a
+    /// `{{placeholder}}` are taken to resolve to something valid.
+    pub fn check(&self, name: &str, value: &str) -> Result<(), String> {
+        if value.contains("{{") {
+            return Ok(());
+        }
+        let valid = match self {
+            VariableType::String | VariableType::Secret => true,
+            VariableType::Number => value.trim().parse::<f64>().is_ok_and(f64::is_finite),
+            VariableType::Boolean => value == "true" || value == "false",
+            VariableType::Enum { values } => values.iter().any(|v| v == value),
+        };
+        if valid {
+            return Ok(());
+        }
+        Err(match self {
+            VariableType::Enum { values } => format!(
+                "Variable '{}' must be one of {}, got '{}'",
+                name,
+                values.join(", "),
+                value
+            ),
+            other => format!(
+                "Variable '{}' must be a {}, got '{}'",
+                name,
+                other.label(),
+                value
+            ),
+        })
+    }
+
+    fn label(&self) -> &'static str {
+        match self {
+            VariableType::String => "string",
+            VariableType::Number => "number",
+            VariableType::Boolean => "boolean",
+            VariableType::Secret => "secret",
+            VariableType::Enum { .. } => "enum",
+        }
+    }
+}
+
+/// Reads a `types` column; an enum needs at least one value.
+pub fn parse(types: &str) -> Result<VariableTypes, String> {
+    let types: VariableTypes =
+        serde_json::from_str(types).map_err(|e| format!("Invalid variable types: {}", e))?;
+    validate(&types)?;
+    Ok(types)
+}
+
+pub fn validate(types: &VariableTypes) -> Result<(), String> {
+    for (name, variable_type) in types {
+        if let VariableType::Enum { values } = variable_type {
+            if values.is_empty() {
+                return Err(format!("Enum variable '{}' needs allowed values", name));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Names of the variables declared secret by type.
+pub fn secret_names(types: &VariableTypes) -> impl Iterator<Item = &String> {
+    types
+        .iter()
+        .filter(|(_, t)| **t == VariableType::Secret)
+        .map(|(name, _)| name)
+}
+
+/// Checks each typed variable that has a value; the first invalid one is
+/// reported. `skip` leaves out values that cannot be checked, such as
+/// masked secrets.
+pub fn check_values<'a>(
+    types: &VariableTypes,
+    values: impl IntoIterator<Item = (&'a str, &'a str)>,
+    skip: impl Fn(&str) -> bool,
+) -> Result<(), String> {
+    let values: HashMap<&str, &str> = values.into_iter().collect();
+    for (name, variable_type) in types {
+        match values.get(name.as_str()) {
+            Some(value) if !skip(value) => variable_type.check(name, value)?,
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_values() {
+        let types = parse(
+            r#"{"port": {"type": "number"}, "debug": {"type": "boolean"},
+                "region": {"type": "enum", "values": ["eu", "us"]}, "key": {"type": "secret"}}"#,
+        )
+        .unwrap();
+        assert_eq!(secret_names(&types).collect::<Vec<_>>(), vec!["key"]);
+
+        let valid = [
+            ("port", "8080"),
+            ("debug", "false"),
+            ("region", "eu"),
+            ("key", "anything"),
+            ("untyped", "x"),
+        ];
+        assert!(check_values(&types, valid, |_| false).is_ok());
+        assert_eq!(
+            check_values(&types, [("port", "80a")], |_| false).unwrap_err(),
+            "Variable 'port' must be a number, got '80a'"
+        );
+        assert_eq!(
+            check_values(&types, [("region", "ap")], |_| false).unwrap_err(),
+            "Variable 'region' must be one of eu, us, got 'ap'"
+        );
+        assert!(check_values(&types, [("debug", "yes")], |_| false).is_err());
+        assert!(check_values(&types, [("port", "{{base_port}}")], |_| false).is_ok());
+        assert!(check_values(&types, [("port", "****")], |v| v == "****").is_ok());
+
+        assert!(parse(r#"{"region": {"type": "enum", "values": []}}"#).is_err());
+        assert!(parse(r#"{"port": {"type": "integer"}}"#).is_err());
+    }
+}
@@ -0,0 +1,444 @@
+use axum::{
+    body::Bytes,
+    extract::{Path, State},
+    http::{HeaderMap, Method, StatusCode},
+    response::{IntoResponse, Response},
+    routing::{any, get},
+    Json, Router,
+};
+use chrono::{DateTime, NaiveDateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+use tokio::sync::Notify;
+
+use crate::capture::{self, CaptureRule, CaptureSource};
+use crate::db::DbPool;
+use crate::i18n;
+
+const DEFAULT_TIMEOUT_SECS: u64 = 30;
+const MAX_TIMEOUT_SECS: u64 = 300;
+const MAX_LISTED_DELIVERIES: i64 = 50;
+
+/// Woken whenever a delivery is stored, so waiting executions recheck.
+static DELIVERED: OnceLock<Notify> = OnceLock::new();
+
+fn delivered() -> &'static Notify {
+    DELIVERED.get_or_init(Notify::new)
+}
+
+/// A request received on a webhook bin.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Delivery {
+    pub id: i64,
+    pub bin: String,
+    pub method: String,
+    pub headers: HashMap<String, String>,
+    pub body: String,
+    pub received_at: DateTime<Utc>,
+}
+
+struct DeliveryDb {
+    id: i64,
+    bin: String,
+    method: String,
+    headers: String,
+    body: String,
+    received_at: NaiveDateTime,
+}
+
+impl From<DeliveryDb> for Delivery {
+    fn from(d: DeliveryDb) -> Self {
+        Delivery {
+            id: d.id,
+            bin: d.bin,
+            method: d.method,
+            headers: serde_json::from_str(&d.headers).unwrap_or_default(),
+            body: d.body,
+            received_at: d.received_at.and_utc(),
+        }
+    }
+}
+
+/// The `callback` column of a request: the API replies asynchronously by
+/// calling a webhook bin, and the execution waits for that delivery.
+#[derive(Debug, Deserialize)]
+pub struct CallbackConfig {
+    /// Bin the API is told to call, `/api/hooks/<bin>`
+    pub bin: String,
+    /// JSONPath of the correlation id in the initial response body
+    pub id_path: String,
+    /// JSONPath of the correlation id in a delivery body; without it any
+    /// delivery whose body contains the id matches
+    #[serde(default)]
+    pub match_path: Option<String>,
+    /// How long to wait for the delivery, in seconds
+    #[serde(default)]
+    pub timeout: Option<u64>,
+}
+
+impl CallbackConfig {
+    pub fn parse(callback: Option<&str>) -> Result<Option<Self>, String> {
+        let Some(callback) = callback.filter(|c| !c.trim().is_empty()) else {
+            return Ok(None);
+        };
+        let config: CallbackConfig = serde_json::from_str(callback)
+            .map_err(|e| format!("Invalid callback settings: {}", e))?;
+        validate_bin(&config.bin)?;
+        capture::validate_json_path(&config.id_path)?;
+        if let Some(path) = &config.match_path {
+            capture::validate_json_path(path)?;
+        }
+        if config.timeout.is_some_and(|t| t > MAX_TIMEOUT_SECS) {
+            return Err(format!(
+                "Callback timeout must be at most {} seconds",
+                MAX_TIMEOUT_SECS
+            ));
+        }
+        Ok(Some(config))
+    }
+
+    fn timeout(&self) -> Duration {
+        Duration::from_secs(self.timeout.unwrap_or(DEFAULT_TIMEOUT_SECS))
+    }
+
+    fn body_rule(path: &str) -> CaptureRule {
+        CaptureRule {
+            variable: "id".to_string(),
+            source: CaptureSource::Body,
+            expression: path.to_string(),
+        }
+    }
+
+    fn matches(&self, delivery: &Delivery, correlation_id: &str) -> bool {
+        match &self.match_path {
+            Some(path) => {
+                capture::extract(&Self::body_rule(path), 0, &delivery.headers, &delivery.body)
+                    .is_some_and(|id| id == correlation_id)
+            }
+            None => delivery.body.contains(correlation_id),
+        }
+    }
+}
+
+/// Both legs of an async exchange are reported together; this is the
+/// second one.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CallbackResult {
+    pub bin: String,
+    pub correlation_id: Option<String>,
+    /// The matching delivery, `None` when none arrived in time
+    pub delivery: Option<Delivery>,
+    pub waited_ms: i64,
+    /// Why there is no delivery
+    pub error: Option<String>,
+}
+
+fn validate_bin(bin: &str) -> Result<(), String> {
+    let valid = !bin.is_empty()
+        && bin.len() <= 64
+        && bin
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if valid {
+        Ok(())
+    } else {
+        Err(format!(
+            "Invalid webhook bin '{}', use up to 64 letters, digits, '-' and '_'",
+            bin
+        ))
+    }
+}
+
+pub async fn store(
+    pool: &DbPool,
+    bin: &str,
+    method: &str,
+    headers: &HashMap<String, String>,
+    body: &str,
+) -> Result<i64, sqlx::Error> {
+    let headers = serde_json::to_string(headers).unwrap_or_else(|_| "{}".to_string());
+    let id = sqlx::query_scalar!(
+        "INSERT INTO webhook_deliveries (bin, method, headers, body) VALUES (?, ?, ?, ?) RETURNING id",
+        bin,
+        method,
+        headers,
+        body
+    )
+    .fetch_one(pool)
+    .await?;
+    delivered().notify_waiters();
+    Ok(id)
+}
+
+/// The id of the newest delivery on a bin, so a later wait only considers
+/// deliveries that arrive after it.
+pub async fn latest_id(pool: &DbPool, bin: &str) -> Result<i64, sqlx::Error> {
+    sqlx::query_scalar!(
+        r#"SELECT COALESCE(MAX(id), 0) as "id!: i64" FROM webhook_deliveries WHERE bin = ?"#,
+        bin
+    )
+    .fetch_one(pool)
+    .await
+}
+
+async fn deliveries_after(
+    pool: &DbPool,
+    bin: &str,
+    after_id: i64,
+) -> Result<Vec<Delivery>, sqlx::Error> {
+    let deliveries = sqlx::query_as!(
+        DeliveryDb,
+        r#"SELECT id as "id!", bin, method, headers, body, received_at as "received_at: NaiveDateTime" FROM webhook_deliveries WHERE bin = ? AND id > ? ORDER BY id"#,
+        bin,
+        after_id
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(deliveries.into_iter().map(Delivery::from).collect())
+}
+
+/// Waits for the first delivery after `after_id` that carries the
+/// correlation id.
+pub async fn wait_for(
+    pool: &DbPool,
+    config: &CallbackConfig,
+    after_id: i64,
+    correlation_id: &str,
+    timeout: Duration,
+) -> Result<Option<Delivery>, sqlx::Error> {
+    let deadline = tokio::time::Instant::now() + timeout;
+    let mut after_id = after_id;
+    loop {
+        // Registered before looking, so a delivery stored in between is
+        // not missed
+        let notified = delivered().notified();
+        tokio::pin!(notified);
+        notified.as_mut().enable();
+
+        for delivery in deliveries_after(pool, &config.bin, after_id).await? {
+            after_id = delivery.id;
+            if config.matches(&delivery, correlation_id) {
+                return Ok(Some(delivery));
+            }
+        }
+        if tokio::time::timeout_at(deadline, notified).await.is_err() {
+            return Ok(None);
+        }
+    }
+}
+
+/// Takes the correlation id from the initial response and waits for the
+/// matching delivery. Problems end up in the result rather than failing the
+/// execution, whose first leg already completed.
+pub async fn await_callback(
+    pool: &DbPool,
+    config: &CallbackConfig,
+    after_id: i64,
+    status: u16,
+    headers: &HashMap<String, String>,
+    body: &str,
+) -> CallbackResult {
+    let started = Instant::now();
+    let mut result = CallbackResult {
+        bin: config.bin.clone(),
+        correlation_id: None,
+        delivery: None,
+        waited_ms: 0,
+        error: None,
+    };
+    let rule = CallbackConfig::body_rule(&config.id_path);
+    let Some(correlation_id) = capture::extract(&rule, status, headers, body) else {
+        result.error = Some(format!(
+            "No correlation id at {} in the response",
+            config.id_path
+        ));
+        return result;
+    };
+    log::info!(
+        "Waiting up to {:?} for a delivery on bin '{}' with id {}",
+        config.timeout(),
+        config.bin,
+        correlation_id
+    );
+
+    match wait_for(pool, config, after_id, &correlation_id, config.timeout()).await {
+        Ok(Some(delivery)) => result.delivery = Some(delivery),
+        Ok(None) => {
+            result.error = Some(format!(
+                "No delivery for {} within {} seconds",
+                correlation_id,
+                config.timeout().as_secs()
+            ))
+        }
+        Err(e) => result.error = Some(format!("Failed to read deliveries: {}", e)),
+    }
+    result.waited_ms = started.elapsed().as_millis() as i64;
+    result.correlation_id = Some(correlation_id);
+    result
+}
+
+pub enum WebhookError {
+    InvalidBin(String),
+    DatabaseError(#[allow(dead_code)] sqlx::Error),
+}
+
+impl From<sqlx::Error> for WebhookError {
+    fn from(e: sqlx::Error) -> Self {
+        WebhookError::DatabaseError(e)
+    }
+}
+
+impl IntoResponse for WebhookError {
+    fn into_response(self) -> Response {
+        match self {
+            WebhookError::InvalidBin(msg) => (StatusCode::BAD_REQUEST, msg).into_response(),
+            WebhookError::DatabaseError(_) => {
+                (StatusCode::INTERNAL_SERVER_ERROR, i18n::t("error.database")).into_response()
+            }
+        }
+    }
+}
+
+async fn receive(
+    State(pool): State<DbPool>,
+    Path(bin): Path<String>,
+    method: Method,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<impl IntoResponse, WebhookError> {
+    validate_bin(&bin).map_err(WebhookError::InvalidBin)?;
+    let headers: HashMap<String, String> = headers
+        .iter()
+        .filter_map(|(name, value)| Some((name.to_string(), value.to_str().ok()?.to_string())))
+        .collect();
+    let body = String::from_utf8_lossy(&body);
+    let id = store(&pool, &bin, method.as_str(), &headers, &body).await?;
+    log::info!("Received delivery {} on webhook bin '{}'", id, bin);
+    Ok((StatusCode::ACCEPTED, Json(serde_json::json!({ "id": id }))))
+}
+
+async fn list_deliveries(
+    State(pool): State<DbPool>,
+    Path(bin): Path<String>,
+) -> Result<impl IntoResponse, WebhookError> {
+    validate_bin(&bin).map_err(WebhookError::InvalidBin)?;
+    let deliveries = sqlx::query_as!(
+        DeliveryDb,
+        r#"SELECT id as "id!", bin, method, headers, body, received_at as "received_at: NaiveDateTime" FROM webhook_deliveries WHERE bin = ? ORDER BY id DESC LIMIT ?"#,
+        bin,
+        MAX_LISTED_DELIVERIES
+    )
+    .fetch_all(&pool)
+    .await?;
+    Ok(Json(
+        deliveries
+            .into_iter()
+            .map(Delivery::from)
+            .collect::<Vec<_>>(),
+    ))
+}
+
+pub fn routes(pool: DbPool) -> Router {
+    Router::new()
+        .route("/hooks/:bin", any(receive))
+        .route("/hooks/:bin/deliveries", get(list_deliveries))
+        .with_state(pool)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db;
+    use axum_test::TestServer;
+
+    fn config(match_path: Option<&str>) -> CallbackConfig {
+        CallbackConfig::parse(Some(
+            &serde_json::json!({
+                "bin": "orders",
+                "id_path": "$.job_id",
+                "match_path": match_path,
+            })
+            .to_string(),
+        ))
+        .unwrap()
+        .unwrap()
+    }
+
+    #[test]
+    fn test_parse_config() {
+        assert!(CallbackConfig::parse(None).unwrap().is_none());
+        assert!(CallbackConfig::parse(Some(" ")).unwrap().is_none());
+        assert_eq!(config(None).timeout(), Duration::from_secs(30));
+        assert!(CallbackConfig::parse(Some(r#"{"bin": "a b", "id_path": "$.id"}"#)).is_err());
+        assert!(CallbackConfig::parse(Some(r#"{"bin": "a", "id_path": "id"}"#)).is_err());
+        assert!(
+            CallbackConfig::parse(Some(r#"{"bin": "a", "id_path": "$.id", "timeout": 900}"#))
+                .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_wait_for_correlated_delivery() {
+        let pool = db::create_test_pool().await;
+        let server = TestServer::new(routes(pool.clone())).unwrap();
+        let headers = HashMap::new();
+
+        // Deliveries from before the request are ignored
+        store(&pool, "orders", "POST", &headers, r#"{"job_id": "j1"}"#)
+            .await
+            .unwrap();
+        let after_id = latest_id(&pool, "orders").await.unwrap();
+
+        let waiting = {
+            let pool = pool.clone();
+            tokio::spawn(async move {
+                await_callback(
+                    &pool,
+                    &config(Some("$.job_id")),
+                    after_id,
+                    202,
+                    &HashMap::new(),
+                    r#"{"job_id": "j1"}"#,
+                )
+                .await
+            })
+        };
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        server
+            .post("/hooks/orders")
+            .json(&serde_json::json!({ "job_id": "j2" }))
+            .await
+            .assert_status(StatusCode::ACCEPTED);
+        server
+            .post("/hooks/orders")
+            .json(&serde_json::json!({ "job_id": "j1", "state": "done" }))
+            .await
+            .assert_status(StatusCode::ACCEPTED);
+
+        let result = waiting.await.unwrap();
+        assert_eq!(result.correlation_id.as_deref(), Some("j1"));
+        let delivery = result.delivery.unwrap();
+        assert_eq!(delivery.method, "POST");
+        assert!(delivery.body.contains("done"));
+        assert!(result.error.is_none());
+
+        let listed: Vec<Delivery> = server.get("/hooks/orders/deliveries").await.json();
+        assert_eq!(listed.len(), 3);
+        assert_eq!(listed[0].id, delivery.id);
+    }
+
+    #[tokio::test]
+    async fn test_wait_times_out() {
+        let pool = db::create_test_pool().await;
+        let found = wait_for(&pool, &config(None), 0, "j1", Duration::from_millis(50))
+            .await
+            .unwrap();
+        assert!(found.is_none());
+
+        let result = await_callback(&pool, &config(None), 0, 200, &HashMap::new(), "{}").await;
+        assert!(result.correlation_id.is_none());
+        assert!(result.error.unwrap().contains("$.job_id"));
+    }
+}
@@ -1,27 +1,39 @@
 use axum::{
     extract::{
         ws::{Message, WebSocket, WebSocketUpgrade},
-        State,
+        Query, State,
     },
-    response::IntoResponse,
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
     routing::get,
-    Router,
+    Extension, Router,
 };
 use futures_util::{SinkExt, StreamExt};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet};
+use std::env;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 use tokio::sync::{mpsc, Mutex};
-use tokio_tungstenite::{
-    connect_async_with_config,
-    tungstenite::{
-        client::IntoClientRequest,
-        http::HeaderValue,
-        Message as TungsteniteMessage,
-    },
+use tokio_tungstenite::tungstenite::{
+    client::IntoClientRequest,
+    http::HeaderValue,
+    protocol::{frame::coding::CloseCode, CloseFrame},
+    Message as TungsteniteMessage,
 };
+use tracing::Instrument;
 
-use crate::db::DbPool;
+use crate::{
+    access,
+    db::DbPool,
+    i18n,
+    network::{self, NetworkSettingsDb},
+    proxy, telemetry,
+    ws_history::SessionRecorder,
+};
 
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(tag = "type")]
@@ -39,49 +51,748 @@ pub enum WsClientMessage {
         auth_username: Option<String>,
         #[serde(default)]
         auth_password: Option<String>,
+        /// How received binary messages are passed on; base64 unless `hex`
+        #[serde(default)]
+        binary_encoding: Option<PayloadEncoding>,
+        /// Pings the remote end this often, at least every second
+        #[serde(default)]
+        keepalive_interval_ms: Option<u64>,
+        /// TLS settings for `wss://`
+        #[serde(default)]
+        tls: WsTlsOptions,
+        /// Speaks graphql-transport-ws on this connection, initialised with
+        /// these connection params
+        #[serde(default)]
+        graphql: Option<GraphqlInit>,
     },
+    /// Starts a subscription on a `graphql` connection; its results come
+    /// back as `graphql_next` under the same id
+    #[serde(rename = "subscribe")]
+    Subscribe {
+        subscription_id: String,
+        query: String,
+        #[serde(default)]
+        variables: Option<Value>,
+        #[serde(default)]
+        operation_name: Option<String>,
+    },
+    /// Stops a subscription on a `graphql` connection
+    #[serde(rename = "complete")]
+    Complete { subscription_id: String },
+    /// Pings the remote end; the round trip comes back as `pong`
+    #[serde(rename = "ping")]
+    Ping,
+    /// Closes the connection, by default with code 1000 and no reason
     #[serde(rename = "disconnect")]
-    Disconnect,
+    Disconnect {
+        #[serde(default)]
+        code: Option<u16>,
+        #[serde(default)]
+        reason: Option<String>,
+    },
+    /// A text message, or with `base64` or `hex` a binary one
     #[serde(rename = "send")]
-    Send { message: String },
+    Send {
+        message: String,
+        #[serde(default)]
+        encoding: PayloadEncoding,
+    },
+    /// Sends the message every `interval_ms` from the bridge, `count` times
+    /// or until stopped; replaces a schedule already running
+    #[serde(rename = "start_interval")]
+    StartInterval {
+        message: String,
+        #[serde(default)]
+        encoding: PayloadEncoding,
+        interval_ms: u64,
+        #[serde(default)]
+        count: Option<u64>,
+    },
+    #[serde(rename = "stop_interval")]
+    StopInterval,
+}
+
+/// Subprotocol of the GraphQL over WebSocket protocol
+const GRAPHQL_PROTOCOL: &str = "graphql-transport-ws";
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct GraphqlInit {
+    /// Sent as the `connection_init` payload, often carrying auth
+    #[serde(default)]
+    pub connection_params: Option<Value>,
+}
+
+/// Messages of the graphql-transport-ws protocol, in both directions.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum GraphqlWsMessage {
+    ConnectionInit {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        payload: Option<Value>,
+    },
+    ConnectionAck {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        payload: Option<Value>,
+    },
+    Ping {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        payload: Option<Value>,
+    },
+    Pong {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        payload: Option<Value>,
+    },
+    Subscribe {
+        id: String,
+        payload: GraphqlOperation,
+    },
+    Next {
+        id: String,
+        payload: Value,
+    },
+    Error {
+        id: String,
+        payload: Value,
+    },
+    Complete {
+        id: String,
+    },
+}
+
+impl GraphqlWsMessage {
+    fn to_frame(&self) -> TungsteniteMessage {
+        let json = serde_json::to_string(self).unwrap_or_default();
+        TungsteniteMessage::Text(json.into())
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct GraphqlOperation {
+    query: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    variables: Option<Value>,
+    #[serde(
+        rename = "operationName",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    operation_name: Option<String>,
+}
+
+/// Protocol state of a graphql-transport-ws connection, shared by its read
+/// task and the handlers of browser messages.
+#[derive(Default)]
+struct GraphqlSession {
+    acknowledged: AtomicBool,
+    /// Ids of the subscriptions the server has not ended yet
+    active: std::sync::Mutex<HashSet<String>>,
+}
+
+impl GraphqlSession {
+    fn active(&self) -> std::sync::MutexGuard<'_, HashSet<String>> {
+        self.active.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// The event for a protocol message from the server, if it makes one.
+    fn receive(&self, message: GraphqlWsMessage) -> Option<WsServerMessage> {
+        match message {
+            GraphqlWsMessage::ConnectionAck { payload } => {
+                self.acknowledged.store(true, Ordering::Relaxed);
+                Some(WsServerMessage::GraphqlAck { payload })
+            }
+            GraphqlWsMessage::Next { id, payload } => Some(WsServerMessage::GraphqlNext {
+                subscription_id: id,
+                payload,
+            }),
+            GraphqlWsMessage::Error { id, payload } => {
+                self.active().remove(&id);
+                Some(WsServerMessage::GraphqlError {
+                    subscription_id: id,
+                    errors: payload,
+                })
+            }
+            GraphqlWsMessage::Complete { id } => {
+                self.active().remove(&id);
+                Some(WsServerMessage::GraphqlComplete {
+                    subscription_id: id,
+                })
+            }
+            _ => None,
+        }
+    }
+
+    /// The `subscribe` message for a new subscription.
+    fn subscribe(
+        &self,
+        subscription_id: String,
+        query: String,
+        variables: Option<Value>,
+        operation_name: Option<String>,
+    ) -> Result<TungsteniteMessage, String> {
+        if !self.acknowledged.load(Ordering::Relaxed) {
+            return Err("The GraphQL server has not acknowledged the connection yet".to_string());
+        }
+        if query.trim().is_empty() {
+            return Err("A subscription needs a query".to_string());
+        }
+        if !self.active().insert(subscription_id.clone()) {
+            return Err(format!(
+                "Subscription '{}' is already running",
+                subscription_id
+            ));
+        }
+        Ok(GraphqlWsMessage::Subscribe {
+            id: subscription_id,
+            payload: GraphqlOperation {
+                query,
+                variables,
+                operation_name,
+            },
+        }
+        .to_frame())
+    }
+
+    /// The `complete` message that stops a running subscription.
+    fn complete(&self, subscription_id: String) -> Result<TungsteniteMessage, String> {
+        if !self.active().remove(&subscription_id) {
+            return Err(format!("Subscription '{}' is not running", subscription_id));
+        }
+        Ok(GraphqlWsMessage::Complete {
+            id: subscription_id,
+        }
+        .to_frame())
+    }
+}
+
+/// TLS settings of one connection, for internal services with self-signed
+/// certificates or mutual TLS.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct WsTlsOptions {
+    /// Accepts any server certificate and host name
+    #[serde(default)]
+    pub insecure: bool,
+    /// PEM client certificate chain, sent with `client_key`
+    #[serde(default)]
+    pub client_certificate: Option<String>,
+    /// PEM PKCS#8 private key of the client certificate
+    #[serde(default)]
+    pub client_key: Option<String>,
+}
+
+impl WsTlsOptions {
+    /// The connector for these settings, or `None` for the system defaults.
+    fn connector(&self) -> Result<Option<tokio_tungstenite::Connector>, String> {
+        let identity = match (&self.client_certificate, &self.client_key) {
+            (Some(certificate), Some(key)) => Some(
+                native_tls::Identity::from_pkcs8(certificate.as_bytes(), key.as_bytes())
+                    .map_err(|e| format!("Invalid client certificate: {}", e))?,
+            ),
+            (None, None) => None,
+            _ => {
+                return Err(
+                    "A client certificate needs both the certificate and its key".to_string(),
+                )
+            }
+        };
+        if !self.insecure && identity.is_none() {
+            return Ok(None);
+        }
+
+        let mut builder = native_tls::TlsConnector::builder();
+        builder
+            .danger_accept_invalid_certs(self.insecure)
+            .danger_accept_invalid_hostnames(self.insecure);
+        if let Some(identity) = identity {
+            builder.identity(identity);
+        }
+        let connector = builder
+            .build()
+            .map_err(|e| format!("Failed to set up TLS: {}", e))?;
+        Ok(Some(tokio_tungstenite::Connector::NativeTls(connector)))
+    }
+}
+
+/// A message from the browser for one of its remote connections. Messages
+/// without `connection_id` go to the connection named `default`, so a single
+/// connection needs no id.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WsClientEnvelope {
+    #[serde(default = "default_connection_id")]
+    pub connection_id: String,
+    #[serde(flatten)]
+    pub message: WsClientMessage,
+}
+
+fn default_connection_id() -> String {
+    "default".to_string()
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde(tag = "type")]
 pub enum WsServerMessage {
     #[serde(rename = "connected")]
-    Connected { url: String },
+    /// `session_id` is the WebSocket history session recording it
+    Connected {
+        url: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        session_id: Option<i64>,
+    },
+    /// `code` is the close code sent or received, absent when the connection
+    /// ended without a close frame
     #[serde(rename = "disconnected")]
-    Disconnected { reason: String },
+    Disconnected {
+        reason: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        code: Option<u16>,
+    },
+    /// `data` in the given encoding; `size` is the length of the payload in
+    /// bytes
     #[serde(rename = "message")]
-    Message { data: String, direction: String },
+    Message {
+        data: String,
+        direction: String,
+        #[serde(default)]
+        encoding: PayloadEncoding,
+        #[serde(default)]
+        size: usize,
+    },
     #[serde(rename = "error")]
     Error { message: String },
     #[serde(rename = "info")]
     Info { message: String },
+    /// Round trip of a ping, sent by `ping` or the keepalive
+    #[serde(rename = "pong")]
+    Pong { rtt_ms: f64, keepalive: bool },
+    /// The GraphQL server accepted `connection_init`
+    #[serde(rename = "graphql_ack")]
+    GraphqlAck {
+        #[serde(default)]
+        payload: Option<Value>,
+    },
+    /// One result of a subscription
+    #[serde(rename = "graphql_next")]
+    GraphqlNext {
+        subscription_id: String,
+        payload: Value,
+    },
+    /// The subscription failed; it is over
+    #[serde(rename = "graphql_error")]
+    GraphqlError {
+        subscription_id: String,
+        errors: Value,
+    },
+    /// The server has no more results for the subscription
+    #[serde(rename = "graphql_complete")]
+    GraphqlComplete { subscription_id: String },
+}
+
+/// A message to the browser about one of its remote connections, or about
+/// the bridge itself when `connection_id` is absent.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct WsEvent {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub connection_id: Option<String>,
+    #[serde(flatten)]
+    pub message: WsServerMessage,
+}
+
+/// Sends the events of one remote connection to the browser, recording its
+/// messages when the connection has a session.
+#[derive(Clone)]
+struct EventSender {
+    tx: mpsc::Sender<WsEvent>,
+    connection_id: Option<String>,
+    recorder: Option<SessionRecorder>,
+}
+
+impl EventSender {
+    fn recording(&self, recorder: Option<SessionRecorder>) -> Self {
+        Self {
+            recorder,
+            ..self.clone()
+        }
+    }
+
+    async fn send(&self, message: WsServerMessage) {
+        if let Some(recorder) = &self.recorder {
+            match &message {
+                WsServerMessage::Message {
+                    data,
+                    direction,
+                    encoding,
+                    size,
+                } => {
+                    let direction = if direction == "sent" {
+                        "sent"
+                    } else {
+                        "received"
+                    };
+                    recorder.record(direction, encoding.as_str(), data, *size);
+                }
+                WsServerMessage::GraphqlNext { payload, .. } => {
+                    let data = payload.to_string();
+                    recorder.record(
+                        "received",
+                        PayloadEncoding::Text.as_str(),
+                        &data,
+                        data.len(),
+                    );
+                }
+                _ => {}
+            }
+        }
+        let _ = self
+            .tx
+            .send(WsEvent {
+                connection_id: self.connection_id.clone(),
+                message,
+            })
+            .await;
+    }
 }
 
-// Shared state for WebSocket connection
+/// How a message payload is written in the JSON between browser and bridge.
+/// Text messages are sent as they are; binary ones need an encoding.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PayloadEncoding {
+    #[default]
+    Text,
+    Base64,
+    Hex,
+}
+
+impl PayloadEncoding {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            PayloadEncoding::Text => "text",
+            PayloadEncoding::Base64 => "base64",
+            PayloadEncoding::Hex => "hex",
+        }
+    }
+
+    fn encode(self, bytes: &[u8]) -> String {
+        use base64::Engine;
+        match self {
+            PayloadEncoding::Text => String::from_utf8_lossy(bytes).into_owned(),
+            PayloadEncoding::Base64 => base64::engine::general_purpose::STANDARD.encode(bytes),
+            PayloadEncoding::Hex => bytes.iter().map(|b| format!("{:02x}", b)).collect(),
+        }
+    }
+
+    fn decode(self, data: &str) -> Result<Vec<u8>, String> {
+        use base64::Engine;
+        match self {
+            PayloadEncoding::Text => Ok(data.as_bytes().to_vec()),
+            PayloadEncoding::Base64 => base64::engine::general_purpose::STANDARD
+                .decode(data.trim())
+                .map_err(|e| format!("Invalid base64: {}", e)),
+            PayloadEncoding::Hex => {
+                let digits: Vec<u8> = data.bytes().filter(|b| !b.is_ascii_whitespace()).collect();
+                if !digits.len().is_multiple_of(2) {
+                    return Err("Invalid hex: odd number of digits".to_string());
+                }
+                digits
+                    .chunks(2)
+                    .map(|pair| {
+                        std::str::from_utf8(pair)
+                            .ok()
+                            .and_then(|pair| u8::from_str_radix(pair, 16).ok())
+                            .ok_or_else(|| "Invalid hex: unexpected character".to_string())
+                    })
+                    .collect()
+            }
+        }
+    }
+
+    /// The frame to send for a message in this encoding.
+    fn to_frame(self, data: &str) -> Result<TungsteniteMessage, String> {
+        match self {
+            PayloadEncoding::Text => Ok(TungsteniteMessage::Text(data.to_string().into())),
+            binary => Ok(TungsteniteMessage::Binary(binary.decode(data)?.into())),
+        }
+    }
+}
+
+/// The close frame for a disconnect, rejecting codes an endpoint may not send.
+fn close_frame(code: Option<u16>, reason: Option<String>) -> Result<CloseFrame, String> {
+    let code = CloseCode::from(code.unwrap_or(1000));
+    if !code.is_allowed() {
+        return Err(format!("Close code {} cannot be sent", code));
+    }
+    let reason = reason.unwrap_or_default();
+    if reason.len() > MAX_CLOSE_REASON {
+        return Err(format!(
+            "Close reason is too long, at most {} bytes are allowed",
+            MAX_CLOSE_REASON
+        ));
+    }
+    Ok(CloseFrame {
+        code,
+        reason: reason.into(),
+    })
+}
+
+/// Remote connections one browser may hold open at the same time.
+const MAX_CONNECTIONS: usize = 16;
+/// Longest close reason that fits a control frame, in bytes.
+const MAX_CLOSE_REASON: usize = 123;
+/// Shortest interval for scheduled messages.
+const MIN_SEND_INTERVAL_MS: u64 = 10;
+/// Shortest keepalive interval accepted from the browser.
+const MIN_KEEPALIVE_MS: u64 = 1000;
+/// Pings without a pong are forgotten after this many newer ones.
+const MAX_PENDING_PINGS: usize = 32;
+
+/// Pings sent to the remote end that still wait for their pong. Each ping
+/// carries its sequence number as payload, which the pong echoes back.
+#[derive(Default)]
+struct PingTracker {
+    next: AtomicU64,
+    pending: std::sync::Mutex<HashMap<u64, (Instant, bool)>>,
+}
+
+impl PingTracker {
+    /// Records a ping and returns the frame to send.
+    fn ping(&self, keepalive: bool) -> TungsteniteMessage {
+        let seq = self.next.fetch_add(1, Ordering::Relaxed);
+        let mut pending = self.pending.lock().unwrap_or_else(|e| e.into_inner());
+        if pending.len() >= MAX_PENDING_PINGS {
+            pending.retain(|&id, _| id + MAX_PENDING_PINGS as u64 > seq);
+        }
+        pending.insert(seq, (Instant::now(), keepalive));
+        TungsteniteMessage::Ping(seq.to_be_bytes().to_vec().into())
+    }
+
+    /// The round trip of the ping a pong answers, with whether it was a
+    /// keepalive. Unsolicited pongs answer nothing.
+    fn pong(&self, payload: &[u8]) -> Option<(Duration, bool)> {
+        let seq = u64::from_be_bytes(payload.try_into().ok()?);
+        let (sent, keepalive) = self
+            .pending
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(&seq)?;
+        Some((sent.elapsed(), keepalive))
+    }
+}
+
+/// An open connection to a remote WebSocket. Dropping it closes the
+/// connection and stops its read task.
+struct RemoteConnection {
+    /// Tells a connection apart from a later one under the same id
+    serial: u64,
+    write_tx: mpsc::Sender<TungsteniteMessage>,
+    pings: Arc<PingTracker>,
+    reader: tokio::task::AbortHandle,
+    /// The task sending scheduled messages, if one runs
+    schedule: Option<tokio::task::AbortHandle>,
+    /// Sent when the connection is dropped
+    close_frame: Option<CloseFrame>,
+    /// Set on connections speaking graphql-transport-ws
+    graphql: Option<Arc<GraphqlSession>>,
+    /// Records the messages of the connection
+    recorder: Option<SessionRecorder>,
+}
+
+impl Drop for RemoteConnection {
+    fn drop(&mut self) {
+        // The write task sends the close frame and ends with the channel
+        let _ = self
+            .write_tx
+            .try_send(TungsteniteMessage::Close(self.close_frame.take()));
+        self.reader.abort();
+        if let Some(schedule) = &self.schedule {
+            schedule.abort();
+        }
+    }
+}
+
+// Shared state for the remote connections of one browser socket
+#[derive(Default)]
 struct WsConnectionState {
-    remote_write_tx: Option<mpsc::Sender<String>>,
-    connected_url: Option<String>,
+    remote: HashMap<String, RemoteConnection>,
+    next_serial: u64,
+}
+
+impl WsConnectionState {
+    /// Forgets a connection unless it was already replaced by a newer one.
+    fn remove(&mut self, connection_id: &str, serial: u64) {
+        if self
+            .remote
+            .get(connection_id)
+            .is_some_and(|remote| remote.serial == serial)
+        {
+            self.remote.remove(connection_id);
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct WsAuthQuery {
+    #[serde(default)]
+    token: Option<String>,
+}
+
+/// Access rules for the bridge, read from the environment on each upgrade.
+#[derive(Debug, Clone, Default)]
+struct WsSecurityConfig {
+    /// Extra browser origins allowed besides the server's own (`JSLINK_WS_ALLOWED_ORIGINS`).
+    allowed_origins: Vec<String>,
+    /// Shared secret required as `?token=` when set (`JSLINK_WS_TOKEN`).
+    token: Option<String>,
+    /// Remote hosts a bridge connection may target; empty allows any (`JSLINK_WS_ALLOWED_HOSTS`).
+    allowed_hosts: Vec<String>,
+    /// Names this server may be reached by, as checked for the API; empty
+    /// allows any.
+    server_hosts: Vec<String>,
+}
+
+fn env_list(name: &str) -> Vec<String> {
+    env::var(name)
+        .unwrap_or_default()
+        .split(',')
+        .map(|v| v.trim().to_lowercase())
+        .filter(|v| !v.is_empty())
+        .collect()
+}
+
+impl WsSecurityConfig {
+    /// `access` is the config the API's Host and Origin checks ran with.
+    fn from_env(access: Option<&access::AccessConfig>) -> Self {
+        Self {
+            allowed_origins: env_list("JSLINK_WS_ALLOWED_ORIGINS"),
+            token: env::var("JSLINK_WS_TOKEN").ok().filter(|t| !t.is_empty()),
+            allowed_hosts: env_list("JSLINK_WS_ALLOWED_HOSTS"),
+            server_hosts: access
+                .map(|access| access.allowed_hosts.clone())
+                .unwrap_or_default(),
+        }
+    }
+
+    /// Browsers always send `Origin` on WebSocket upgrades; it must be the UI
+    /// served by this instance or an explicitly allowed origin. Requests
+    /// without it come from non-browser clients and are left to the token check.
+    /// The server's own origin only counts under an allowed `Host`, so a
+    /// DNS-rebound page cannot pass as the UI.
+    fn origin_allowed(&self, origin: Option<&str>, host: Option<&str>) -> bool {
+        let Some(origin) = origin else {
+            return true;
+        };
+        let origin = origin.trim_end_matches('/').to_lowercase();
+        if self
+            .allowed_origins
+            .iter()
+            .any(|o| o == "*" || *o == origin)
+        {
+            return true;
+        }
+
+        access::host_allowed(&self.server_hosts, host) && access::same_origin(&origin, host)
+    }
+
+    /// Checks the origin and token of an upgrade request.
+    fn authorize(
+        &self,
+        headers: &HeaderMap,
+        query: &WsAuthQuery,
+    ) -> Result<(), (StatusCode, String)> {
+        let origin = headers.get(header::ORIGIN).and_then(|v| v.to_str().ok());
+        let host = headers.get(header::HOST).and_then(|v| v.to_str().ok());
+        if !self.origin_allowed(origin, host) {
+            log::warn!("Rejected WebSocket upgrade from origin {:?}", origin);
+            return Err((StatusCode::FORBIDDEN, i18n::t("error.origin_not_allowed")));
+        }
+
+        if !self.token_valid(query.token.as_deref()) {
+            log::warn!("Rejected WebSocket upgrade with missing or invalid token");
+            return Err((StatusCode::UNAUTHORIZED, i18n::t("error.invalid_ws_token")));
+        }
+        Ok(())
+    }
+
+    /// Compares SHA-256 digests of the tokens in constant time, so the time
+    /// taken says nothing about how much of a guess was right.
+    fn token_valid(&self, provided: Option<&str>) -> bool {
+        let Some(expected) = &self.token else {
+            return true;
+        };
+        let Some(provided) = provided else {
+            return false;
+        };
+        let expected = Sha256::digest(expected.as_bytes());
+        let provided = Sha256::digest(provided.as_bytes());
+        expected
+            .iter()
+            .zip(provided.iter())
+            .fold(0u8, |diff, (a, b)| diff | (a ^ b))
+            == 0
+    }
 }
 
-async fn ws_handler(ws: WebSocketUpgrade, State(_pool): State<DbPool>) -> impl IntoResponse {
-    ws.on_upgrade(handle_socket)
+/// Applies the bridge's origin and token rules to another WebSocket endpoint.
+pub fn authorize_upgrade(
+    access: Option<&access::AccessConfig>,
+    headers: &HeaderMap,
+    query: &WsAuthQuery,
+) -> Result<(), (StatusCode, String)> {
+    WsSecurityConfig::from_env(access).authorize(headers, query)
+}
+
+/// Per-connection permissions checked before each `Connect`.
+#[derive(Debug, Clone, Default)]
+struct WsPermissions {
+    allowed_hosts: Vec<String>,
+}
+
+impl WsPermissions {
+    fn check_target(&self, url: &str) -> Result<(), String> {
+        let parsed =
+            reqwest::Url::parse(url).map_err(|e| format!("Invalid WebSocket URL: {}", e))?;
+        if !matches!(parsed.scheme(), "ws" | "wss") {
+            return Err(format!(
+                "Unsupported scheme '{}', expected ws or wss",
+                parsed.scheme()
+            ));
+        }
+        if self.allowed_hosts.is_empty() {
+            return Ok(());
+        }
+        let host = parsed.host_str().unwrap_or_default().to_lowercase();
+        if self.allowed_hosts.contains(&host) {
+            Ok(())
+        } else {
+            Err(format!("Connecting to host '{}' is not permitted", host))
+        }
+    }
+}
+
+async fn ws_handler(
+    ws: WebSocketUpgrade,
+    State(pool): State<DbPool>,
+    access: Option<Extension<access::AccessConfig>>,
+    headers: HeaderMap,
+    Query(query): Query<WsAuthQuery>,
+) -> Response {
+    let config = WsSecurityConfig::from_env(access.as_deref());
+    if let Err(rejection) = config.authorize(&headers, &query) {
+        return rejection.into_response();
+    }
+
+    let permissions = WsPermissions {
+        allowed_hosts: config.allowed_hosts,
+    };
+    ws.on_upgrade(move |socket| handle_socket(socket, pool, permissions))
 }
 
-async fn handle_socket(socket: WebSocket) {
+async fn handle_socket(socket: WebSocket, pool: DbPool, permissions: WsPermissions) {
     let (mut client_sender, mut client_receiver) = socket.split();
 
     // Channel for sending messages to the browser client
-    let (to_client_tx, mut to_client_rx) = mpsc::channel::<WsServerMessage>(100);
+    let (to_client_tx, mut to_client_rx) = mpsc::channel::<WsEvent>(100);
 
     // Shared connection state
-    let connection_state = Arc::new(Mutex::new(WsConnectionState {
-        remote_write_tx: None,
-        connected_url: None,
-    }));
+    let connection_state = Arc::new(Mutex::new(WsConnectionState::default()));
 
     // Task to forward messages to the browser client
     let send_to_client_task = tokio::spawn(async move {
@@ -100,13 +811,31 @@ async fn handle_socket(socket: WebSocket) {
 
     while let Some(Ok(msg)) = client_receiver.next().await {
         if let Message::Text(text) = msg {
-            match serde_json::from_str::<WsClientMessage>(&text) {
-                Ok(client_msg) => {
-                    handle_client_message(client_msg, &conn_state, &tx).await;
+            match serde_json::from_str::<WsClientEnvelope>(&text) {
+                Ok(envelope) => {
+                    let events = EventSender {
+                        tx: tx.clone(),
+                        connection_id: Some(envelope.connection_id.clone()),
+                        recorder: None,
+                    };
+                    handle_client_message(
+                        envelope.connection_id,
+                        envelope.message,
+                        &conn_state,
+                        &events,
+                        &permissions,
+                        &pool,
+                    )
+                    .await;
                 }
                 Err(e) => {
                     log::error!("Failed to parse client message: {}", e);
-                    let _ = tx
+                    let events = EventSender {
+                        tx: tx.clone(),
+                        connection_id: None,
+                        recorder: None,
+                    };
+                    events
                         .send(WsServerMessage::Error {
                             message: format!("Invalid message format: {}", e),
                         })
@@ -119,16 +848,18 @@ async fn handle_socket(socket: WebSocket) {
     // Cleanup
     send_to_client_task.abort();
 
-    // Close remote connection if still open
+    // Close remote connections still open
     let mut state = connection_state.lock().await;
-    state.remote_write_tx = None;
-    state.connected_url = None;
+    state.remote.clear();
 }
 
 async fn handle_client_message(
+    connection_id: String,
     msg: WsClientMessage,
     conn_state: &Arc<Mutex<WsConnectionState>>,
-    to_client_tx: &mpsc::Sender<WsServerMessage>,
+    events: &EventSender,
+    permissions: &WsPermissions,
+    pool: &DbPool,
 ) {
     match msg {
         WsClientMessage::Connect {
@@ -138,14 +869,43 @@ async fn handle_client_message(
             auth_token,
             auth_username,
             auth_password,
+            binary_encoding,
+            keepalive_interval_ms,
+            tls,
+            graphql,
         } => {
-            log::info!("Connecting to WebSocket: {}", url);
+            if let Err(message) = permissions.check_target(&url) {
+                log::warn!("Refused WebSocket connect to {}: {}", url, message);
+                events.send(WsServerMessage::Error { message }).await;
+                return;
+            }
+
+            log::info!("Connecting {} to WebSocket: {}", connection_id, url);
+            let span = tracing::info_span!(
+                "ws.connect",
+                otel.kind = "client",
+                ws.connection_id = %connection_id,
+                url.full = %url,
+                otel.status_code = tracing::field::Empty,
+            );
 
-            // Close existing connection if any
+            // Close an existing connection under this id, and refuse ones
+            // beyond the limit
             {
                 let mut state = conn_state.lock().await;
-                state.remote_write_tx = None;
-                state.connected_url = None;
+                state.remote.remove(&connection_id);
+                if state.remote.len() >= MAX_CONNECTIONS {
+                    drop(state);
+                    events
+                        .send(WsServerMessage::Error {
+                            message: format!(
+                                "Too many open connections, at most {} are allowed",
+                                MAX_CONNECTIONS
+                            ),
+                        })
+                        .await;
+                    return;
+                }
             }
 
             // Build request with headers
@@ -163,6 +923,13 @@ async fn handle_client_message(
                         }
                     }
 
+                    if graphql.is_some() {
+                        req.headers_mut().insert(
+                            tokio_tungstenite::tungstenite::http::header::SEC_WEBSOCKET_PROTOCOL,
+                            HeaderValue::from_static(GRAPHQL_PROTOCOL),
+                        );
+                    }
+
                     // Add auth headers
                     if let Some(auth) = auth_type {
                         match auth.as_str() {
@@ -183,11 +950,8 @@ async fn handle_client_message(
                                     (auth_username, auth_password)
                                 {
                                     use base64::Engine;
-                                    let credentials =
-                                        base64::engine::general_purpose::STANDARD.encode(format!(
-                                            "{}:{}",
-                                            username, password
-                                        ));
+                                    let credentials = base64::engine::general_purpose::STANDARD
+                                        .encode(format!("{}:{}", username, password));
                                     if let Ok(header_value) =
                                         HeaderValue::from_str(&format!("Basic {}", credentials))
                                     {
@@ -201,11 +965,12 @@ async fn handle_client_message(
                             _ => {}
                         }
                     }
+                    telemetry::inject_traceparent(&span, req.headers_mut());
                     req
                 }
                 Err(e) => {
                     log::error!("Failed to create WebSocket request: {}", e);
-                    let _ = to_client_tx
+                    events
                         .send(WsServerMessage::Error {
                             message: format!("Invalid WebSocket URL: {}", e),
                         })
@@ -214,103 +979,229 @@ async fn handle_client_message(
                 }
             };
 
+            // Honour the same proxy settings as HTTP requests
+            let network_settings = network::load_settings(pool).await.unwrap_or_else(|e| {
+                log::debug!("Network settings unavailable ({}), using defaults", e);
+                NetworkSettingsDb::defaults()
+            });
+            let proxy = match network_settings.select_proxy(&url).await {
+                Ok(proxy) => proxy,
+                Err(message) => {
+                    log::error!("Invalid proxy configuration: {}", message);
+                    events.send(WsServerMessage::Error { message }).await;
+                    return;
+                }
+            };
+
             // Connect to the remote WebSocket with headers
-            match connect_async_with_config(request, None, false).await {
+            let connector = match tls.connector() {
+                Ok(connector) => connector,
+                Err(message) => {
+                    events.send(WsServerMessage::Error { message }).await;
+                    return;
+                }
+            };
+            if tls.insecure {
+                log::warn!("Skipping certificate verification for {}", url);
+            }
+
+            match proxy::connect_with_proxy(request, proxy.as_ref(), connector)
+                .instrument(span.clone())
+                .await
+            {
                 Ok((ws_stream, _)) => {
                     let (mut write, mut read) = ws_stream.split();
 
                     // Create channel for sending to remote
-                    let (remote_tx, mut remote_rx) = mpsc::channel::<String>(100);
+                    let (remote_tx, mut remote_rx) = mpsc::channel::<TungsteniteMessage>(100);
+
+                    // The server waits for connection_init before anything else
+                    let graphql = graphql.map(|init| {
+                        let _ = remote_tx.try_send(
+                            GraphqlWsMessage::ConnectionInit {
+                                payload: init.connection_params,
+                            }
+                            .to_frame(),
+                        );
+                        Arc::new(GraphqlSession::default())
+                    });
 
-                    // Store the channel in state
-                    {
-                        let mut state = conn_state.lock().await;
-                        state.remote_write_tx = Some(remote_tx);
-                        state.connected_url = Some(url.clone());
+                    let pings = Arc::new(PingTracker::default());
+                    if let Some(interval) = keepalive_interval_ms.filter(|ms| *ms > 0) {
+                        let interval = Duration::from_millis(interval.max(MIN_KEEPALIVE_MS));
+                        // A weak sender, so the keepalive never holds the
+                        // connection open by itself
+                        let keepalive_tx = remote_tx.downgrade();
+                        let keepalive_pings = Arc::clone(&pings);
+                        tokio::spawn(async move {
+                            loop {
+                                tokio::time::sleep(interval).await;
+                                let Some(tx) = keepalive_tx.upgrade() else {
+                                    break;
+                                };
+                                if tx.send(keepalive_pings.ping(true)).await.is_err() {
+                                    break;
+                                }
+                            }
+                        });
                     }
 
+                    let recorder = SessionRecorder::start(pool, &url).await;
+
                     // Notify client of successful connection
-                    let _ = to_client_tx
-                        .send(WsServerMessage::Connected { url: url.clone() })
+                    events
+                        .send(WsServerMessage::Connected {
+                            url: url.clone(),
+                            session_id: recorder.as_ref().map(SessionRecorder::id),
+                        })
                         .await;
 
                     // Task to write messages to remote WebSocket
                     let write_task = tokio::spawn(async move {
                         while let Some(msg) = remote_rx.recv().await {
-                            if write
-                                .send(TungsteniteMessage::Text(msg.into()))
-                                .await
-                                .is_err()
-                            {
+                            if write.send(msg).await.is_err() {
                                 break;
                             }
                         }
                     });
 
-                    // Task to read messages from remote WebSocket
-                    let tx_for_read = to_client_tx.clone();
+                    // Task to read messages from remote WebSocket. The state
+                    // stays locked until the connection is stored, so the
+                    // task cannot clear it before that.
+                    let mut state = conn_state.lock().await;
+                    state.next_serial += 1;
+                    let serial = state.next_serial;
+                    let events = events.recording(recorder.clone());
                     let conn_state_for_read = Arc::clone(conn_state);
+                    let pings_for_read = Arc::clone(&pings);
+                    let graphql_for_read = graphql.clone();
+                    let write_tx_for_read = remote_tx.downgrade();
+                    let connection_id_for_read = connection_id.clone();
+                    let binary_encoding = match binary_encoding {
+                        Some(PayloadEncoding::Hex) => PayloadEncoding::Hex,
+                        _ => PayloadEncoding::Base64,
+                    };
 
-                    tokio::spawn(async move {
+                    let reader = tokio::spawn(async move {
                         while let Some(msg_result) = read.next().await {
                             match msg_result {
                                 Ok(TungsteniteMessage::Text(text)) => {
-                                    let _ = tx_for_read
+                                    let protocol_message =
+                                        graphql_for_read.as_ref().and_then(|session| {
+                                            serde_json::from_str::<GraphqlWsMessage>(&text)
+                                                .ok()
+                                                .map(|message| (session, message))
+                                        });
+                                    if let Some((session, message)) = protocol_message {
+                                        if let GraphqlWsMessage::Ping { .. } = message {
+                                            if let Some(tx) = write_tx_for_read.upgrade() {
+                                                let pong = GraphqlWsMessage::Pong { payload: None };
+                                                let _ = tx.send(pong.to_frame()).await;
+                                            }
+                                        } else if let Some(event) = session.receive(message) {
+                                            events.send(event).await;
+                                        }
+                                        continue;
+                                    }
+                                    events
                                         .send(WsServerMessage::Message {
+                                            size: text.len(),
                                             data: text.to_string(),
                                             direction: "received".to_string(),
+                                            encoding: PayloadEncoding::Text,
                                         })
                                         .await;
                                 }
                                 Ok(TungsteniteMessage::Binary(data)) => {
-                                    let _ = tx_for_read
+                                    events
                                         .send(WsServerMessage::Message {
-                                            data: format!("[Binary: {} bytes]", data.len()),
+                                            data: binary_encoding.encode(&data),
                                             direction: "received".to_string(),
+                                            encoding: binary_encoding,
+                                            size: data.len(),
                                         })
                                         .await;
                                 }
-                                Ok(TungsteniteMessage::Close(_)) => {
-                                    let _ = tx_for_read
+                                Ok(TungsteniteMessage::Close(frame)) => {
+                                    let reason = frame
+                                        .as_ref()
+                                        .map(|frame| frame.reason.to_string())
+                                        .filter(|reason| !reason.is_empty())
+                                        .unwrap_or_else(|| "Remote closed connection".to_string());
+                                    events
                                         .send(WsServerMessage::Disconnected {
-                                            reason: "Remote closed connection".to_string(),
+                                            reason,
+                                            code: frame.map(|frame| frame.code.into()),
                                         })
                                         .await;
 
                                     // Clear connection state
-                                    let mut state = conn_state_for_read.lock().await;
-                                    state.remote_write_tx = None;
-                                    state.connected_url = None;
+                                    conn_state_for_read
+                                        .lock()
+                                        .await
+                                        .remove(&connection_id_for_read, serial);
                                     break;
                                 }
-                                Ok(TungsteniteMessage::Ping(_))
-                                | Ok(TungsteniteMessage::Pong(_)) => {
-                                    // Handle ping/pong silently
+                                Ok(TungsteniteMessage::Ping(data)) => {
+                                    // tungstenite answers with a pong by itself
+                                    events
+                                        .send(WsServerMessage::Info {
+                                            message: format!(
+                                                "Ping from server ({} bytes)",
+                                                data.len()
+                                            ),
+                                        })
+                                        .await;
+                                }
+                                Ok(TungsteniteMessage::Pong(data)) => {
+                                    if let Some((rtt, keepalive)) = pings_for_read.pong(&data) {
+                                        events
+                                            .send(WsServerMessage::Pong {
+                                                rtt_ms: rtt.as_secs_f64() * 1000.0,
+                                                keepalive,
+                                            })
+                                            .await;
+                                    }
                                 }
                                 Ok(TungsteniteMessage::Frame(_)) => {
                                     // Ignore raw frames
                                 }
                                 Err(e) => {
-                                    let _ = tx_for_read
+                                    events
                                         .send(WsServerMessage::Error {
                                             message: format!("Connection error: {}", e),
                                         })
                                         .await;
 
                                     // Clear connection state
-                                    let mut state = conn_state_for_read.lock().await;
-                                    state.remote_write_tx = None;
-                                    state.connected_url = None;
+                                    conn_state_for_read
+                                        .lock()
+                                        .await
+                                        .remove(&connection_id_for_read, serial);
                                     break;
                                 }
                             }
                         }
                         write_task.abort();
                     });
+                    state.remote.insert(
+                        connection_id,
+                        RemoteConnection {
+                            serial,
+                            write_tx: remote_tx,
+                            pings,
+                            reader: reader.abort_handle(),
+                            schedule: None,
+                            close_frame: None,
+                            graphql,
+                            recorder,
+                        },
+                    );
                 }
                 Err(e) => {
+                    span.record("otel.status_code", "ERROR");
                     log::error!("Failed to connect to WebSocket: {}", e);
-                    let _ = to_client_tx
+                    events
                         .send(WsServerMessage::Error {
                             message: format!("Connection failed: {}", e),
                         })
@@ -318,42 +1209,224 @@ async fn handle_client_message(
                 }
             }
         }
-        WsClientMessage::Disconnect => {
-            log::info!("Disconnecting WebSocket");
+        WsClientMessage::Disconnect { code, reason } => {
+            let frame = match close_frame(code, reason) {
+                Ok(frame) => frame,
+                Err(message) => {
+                    events.send(WsServerMessage::Error { message }).await;
+                    return;
+                }
+            };
+            log::info!(
+                "Disconnecting WebSocket {} with code {}",
+                connection_id,
+                frame.code
+            );
+            let code = frame.code.into();
+            let reason = if frame.reason.is_empty() {
+                "User disconnected".to_string()
+            } else {
+                frame.reason.to_string()
+            };
 
-            let mut state = conn_state.lock().await;
-            state.remote_write_tx = None;
-            state.connected_url = None;
+            {
+                let mut state = conn_state.lock().await;
+                if let Some(mut remote) = state.remote.remove(&connection_id) {
+                    remote.close_frame = Some(frame);
+                }
+            }
 
-            let _ = to_client_tx
+            events
                 .send(WsServerMessage::Disconnected {
-                    reason: "User disconnected".to_string(),
+                    reason,
+                    code: Some(code),
                 })
                 .await;
         }
-        WsClientMessage::Send { message } => {
-            log::debug!("Sending message to remote: {}", message);
+        WsClientMessage::Ping => {
+            let state = conn_state.lock().await;
+            let sent = match state.remote.get(&connection_id) {
+                Some(remote) => remote.write_tx.send(remote.pings.ping(false)).await.is_ok(),
+                None => false,
+            };
+            if !sent {
+                events
+                    .send(WsServerMessage::Error {
+                        message: "Not connected to a WebSocket server".to_string(),
+                    })
+                    .await;
+            }
+        }
+        WsClientMessage::StartInterval {
+            message,
+            encoding,
+            interval_ms,
+            count,
+        } => {
+            let frame = match encoding.to_frame(&message) {
+                Ok(frame) => frame,
+                Err(message) => {
+                    events.send(WsServerMessage::Error { message }).await;
+                    return;
+                }
+            };
+            let interval = Duration::from_millis(interval_ms.max(MIN_SEND_INTERVAL_MS));
+            let mut state = conn_state.lock().await;
+            let Some(remote) = state.remote.get_mut(&connection_id) else {
+                drop(state);
+                events
+                    .send(WsServerMessage::Error {
+                        message: "Not connected to a WebSocket server".to_string(),
+                    })
+                    .await;
+                return;
+            };
+            log::info!(
+                "Sending {:?} message on {} every {:?}",
+                encoding,
+                connection_id,
+                interval
+            );
+
+            // Like the keepalive, the schedule holds only a weak sender
+            let write_tx = remote.write_tx.downgrade();
+            let schedule_events = events.recording(remote.recorder.clone());
+            let schedule = tokio::spawn(async move {
+                schedule_events
+                    .send(WsServerMessage::Info {
+                        message: format!("Sending every {} ms", interval.as_millis()),
+                    })
+                    .await;
+                let mut ticks = tokio::time::interval(interval);
+                ticks.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+                let mut sent = 0;
+                while count.is_none_or(|count| sent < count) {
+                    ticks.tick().await;
+                    let Some(tx) = write_tx.upgrade() else {
+                        return;
+                    };
+                    let size = frame.len();
+                    if tx.send(frame.clone()).await.is_err() {
+                        return;
+                    }
+                    sent += 1;
+                    schedule_events
+                        .send(WsServerMessage::Message {
+                            data: message.clone(),
+                            direction: "sent".to_string(),
+                            encoding,
+                            size,
+                        })
+                        .await;
+                }
+                schedule_events
+                    .send(WsServerMessage::Info {
+                        message: format!("Sent {} scheduled messages", sent),
+                    })
+                    .await;
+            });
+            if let Some(previous) = remote.schedule.replace(schedule.abort_handle()) {
+                previous.abort();
+            }
+        }
+        WsClientMessage::StopInterval => {
+            let stopped = {
+                let mut state = conn_state.lock().await;
+                state
+                    .remote
+                    .get_mut(&connection_id)
+                    .and_then(|remote| remote.schedule.take())
+                    .map(|schedule| {
+                        let running = !schedule.is_finished();
+                        schedule.abort();
+                        running
+                    })
+                    .unwrap_or(false)
+            };
+            let message = if stopped {
+                WsServerMessage::Info {
+                    message: "Stopped sending scheduled messages".to_string(),
+                }
+            } else {
+                WsServerMessage::Error {
+                    message: "No scheduled message is being sent".to_string(),
+                }
+            };
+            events.send(message).await;
+        }
+        WsClientMessage::Subscribe {
+            subscription_id,
+            query,
+            variables,
+            operation_name,
+        } => {
+            let state = conn_state.lock().await;
+            let result = graphql_connection(state.remote.get(&connection_id)).and_then(
+                |(remote, session)| {
+                    log::debug!("Starting GraphQL subscription {}", subscription_id);
+                    let frame =
+                        session.subscribe(subscription_id, query, variables, operation_name)?;
+                    Ok((remote.write_tx.clone(), frame))
+                },
+            );
+            drop(state);
+            if let Err(message) = send_graphql(result).await {
+                events.send(WsServerMessage::Error { message }).await;
+            }
+        }
+        WsClientMessage::Complete { subscription_id } => {
+            let state = conn_state.lock().await;
+            let result = graphql_connection(state.remote.get(&connection_id)).and_then(
+                |(remote, session)| {
+                    let frame = session.complete(subscription_id.clone())?;
+                    Ok((remote.write_tx.clone(), frame))
+                },
+            );
+            drop(state);
+            match send_graphql(result).await {
+                // The server sends nothing more for it, so end it here
+                Ok(()) => {
+                    events
+                        .send(WsServerMessage::GraphqlComplete { subscription_id })
+                        .await
+                }
+                Err(message) => events.send(WsServerMessage::Error { message }).await,
+            }
+        }
+        WsClientMessage::Send { message, encoding } => {
+            log::debug!("Sending {:?} message to remote: {}", encoding, message);
 
+            let frame = match encoding.to_frame(&message) {
+                Ok(frame) => frame,
+                Err(message) => {
+                    events.send(WsServerMessage::Error { message }).await;
+                    return;
+                }
+            };
+            let size = frame.len();
             let state = conn_state.lock().await;
-            if let Some(ref tx) = state.remote_write_tx {
+            if let Some(remote) = state.remote.get(&connection_id) {
                 // Send to remote WebSocket
-                if tx.send(message.clone()).await.is_ok() {
+                if remote.write_tx.send(frame).await.is_ok() {
                     // Notify client that message was sent
-                    let _ = to_client_tx
+                    events
+                        .recording(remote.recorder.clone())
                         .send(WsServerMessage::Message {
                             data: message,
                             direction: "sent".to_string(),
+                            encoding,
+                            size,
                         })
                         .await;
                 } else {
-                    let _ = to_client_tx
+                    events
                         .send(WsServerMessage::Error {
                             message: "Failed to send message".to_string(),
                         })
                         .await;
                 }
             } else {
-                let _ = to_client_tx
+                events
                     .send(WsServerMessage::Error {
                         message: "Not connected to a WebSocket server".to_string(),
                     })
@@ -363,6 +1436,716 @@ async fn handle_client_message(
     }
 }
 
+/// The GraphQL session of a connection, failing for missing connections and
+/// ones not speaking graphql-transport-ws.
+fn graphql_connection(
+    remote: Option<&RemoteConnection>,
+) -> Result<(&RemoteConnection, &GraphqlSession), String> {
+    let remote = remote.ok_or_else(|| "Not connected to a WebSocket server".to_string())?;
+    let session = remote
+        .graphql
+        .as_deref()
+        .ok_or_else(|| "This connection was not opened for GraphQL".to_string())?;
+    Ok((remote, session))
+}
+
+async fn send_graphql(
+    prepared: Result<(mpsc::Sender<TungsteniteMessage>, TungsteniteMessage), String>,
+) -> Result<(), String> {
+    let (write_tx, frame) = prepared?;
+    write_tx
+        .send(frame)
+        .await
+        .map_err(|_| "Failed to send message".to_string())
+}
+
 pub fn routes(pool: DbPool) -> Router {
     Router::new().route("/ws", get(ws_handler)).with_state(pool)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_origin_same_host_allowed() {
+        let config = WsSecurityConfig::default();
+        assert!(config.origin_allowed(Some("http://localhost:3000"), Some("localhost:3000")));
+        assert!(config.origin_allowed(None, Some("localhost:3000")));
+    }
+
+    #[test]
+    fn test_origin_foreign_host_rejected() {
+        let config = WsSecurityConfig::default();
+        assert!(!config.origin_allowed(Some("https://evil.example"), Some("localhost:3000")));
+        assert!(!config.origin_allowed(Some("null"), Some("localhost:3000")));
+    }
+
+    #[test]
+    fn test_origin_rebound_host_rejected() {
+        let config = WsSecurityConfig {
+            server_hosts: access::allowed_hosts(Vec::new(), "127.0.0.1"),
+            ..Default::default()
+        };
+        assert!(!config.origin_allowed(Some("http://evil.test:3000"), Some("evil.test:3000")));
+        assert!(config.origin_allowed(Some("http://localhost:3000"), Some("localhost:3000")));
+    }
+
+    #[test]
+    fn test_origin_allowlist() {
+        let config = WsSecurityConfig {
+            allowed_origins: vec!["https://tools.example".to_string()],
+            ..Default::default()
+        };
+        assert!(config.origin_allowed(Some("https://tools.example/"), Some("localhost:3000")));
+    }
+
+    #[test]
+    fn test_token_check() {
+        let config = WsSecurityConfig {
+            token: Some("secret".to_string()),
+            ..Default::default()
+        };
+        assert!(config.token_valid(Some("secret")));
+        assert!(!config.token_valid(Some("wrong")));
+        assert!(!config.token_valid(None));
+        assert!(WsSecurityConfig::default().token_valid(None));
+    }
+
+    #[test]
+    fn test_payload_encoding() {
+        let bytes = [0x00, 0xff, 0x10, 0x41];
+        assert_eq!(PayloadEncoding::Hex.encode(&bytes), "00ff1041");
+        assert_eq!(PayloadEncoding::Hex.decode("00 FF 10 41").unwrap(), bytes);
+        assert_eq!(PayloadEncoding::Base64.encode(&bytes), "AP8QQQ==");
+        assert_eq!(PayloadEncoding::Base64.decode("AP8QQQ==").unwrap(), bytes);
+        assert!(PayloadEncoding::Hex.decode("abc").is_err());
+        assert!(PayloadEncoding::Hex.decode("zz").is_err());
+        assert!(PayloadEncoding::Base64.decode("***").is_err());
+
+        assert!(matches!(
+            PayloadEncoding::Hex.to_frame("0102").unwrap(),
+            TungsteniteMessage::Binary(data) if data.as_ref() == [1, 2]
+        ));
+        assert!(matches!(
+            PayloadEncoding::Text.to_frame("hi").unwrap(),
+            TungsteniteMessage::Text(_)
+        ));
+
+        let send: WsClientMessage =
+            serde_json::from_str(r#"{"type":"send","message":"AQI=","encoding":"base64"}"#)
+                .unwrap();
+        assert!(matches!(
+            send,
+            WsClientMessage::Send {
+                encoding: PayloadEncoding::Base64,
+                ..
+            }
+        ));
+        let send: WsClientMessage =
+            serde_json::from_str(r#"{"type":"send","message":"hi"}"#).unwrap();
+        assert!(matches!(
+            send,
+            WsClientMessage::Send {
+                encoding: PayloadEncoding::Text,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn test_tls_options() {
+        assert!(WsTlsOptions::default().connector().unwrap().is_none());
+        let insecure = WsTlsOptions {
+            insecure: true,
+            ..Default::default()
+        };
+        assert!(insecure.connector().unwrap().is_some());
+
+        let half = WsTlsOptions {
+            client_certificate: Some("cert".to_string()),
+            ..Default::default()
+        };
+        assert!(half.connector().is_err());
+        let invalid = WsTlsOptions {
+            client_certificate: Some("not a certificate".to_string()),
+            client_key: Some("not a key".to_string()),
+            ..Default::default()
+        };
+        assert!(invalid
+            .connector()
+            .err()
+            .unwrap()
+            .starts_with("Invalid client certificate"));
+
+        let connect: WsClientMessage = serde_json::from_str(
+            r#"{"type":"connect","url":"wss://internal.example","tls":{"insecure":true}}"#,
+        )
+        .unwrap();
+        assert!(matches!(
+            connect,
+            WsClientMessage::Connect {
+                tls: WsTlsOptions { insecure: true, .. },
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn test_ping_tracker() {
+        let tracker = PingTracker::default();
+        let TungsteniteMessage::Ping(first) = tracker.ping(false) else {
+            panic!("expected a ping frame");
+        };
+        let TungsteniteMessage::Ping(second) = tracker.ping(true) else {
+            panic!("expected a ping frame");
+        };
+        assert_eq!(
+            tracker.pong(&second).map(|(_, keepalive)| keepalive),
+            Some(true)
+        );
+        assert!(tracker.pong(&second).is_none());
+        assert_eq!(
+            tracker.pong(&first).map(|(_, keepalive)| keepalive),
+            Some(false)
+        );
+        assert!(tracker.pong(b"unsolicited").is_none());
+
+        for _ in 0..MAX_PENDING_PINGS * 2 {
+            tracker.ping(true);
+        }
+        assert!(tracker.pending.lock().unwrap().len() <= MAX_PENDING_PINGS);
+    }
+
+    /// A remote end echoing text and binary messages; tungstenite answers
+    /// pings by itself.
+    async fn start_echo_server() -> String {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                tokio::spawn(async move {
+                    let mut ws = tokio_tungstenite::accept_async(stream).await.unwrap();
+                    while let Some(Ok(msg)) = ws.next().await {
+                        if msg.is_text() || msg.is_binary() {
+                            ws.send(msg).await.unwrap();
+                        }
+                    }
+                });
+            }
+        });
+        format!("ws://{}", addr)
+    }
+
+    #[tokio::test]
+    async fn test_bridge_ping_and_binary_echo() {
+        let pool = crate::db::create_test_pool().await;
+        let url = start_echo_server().await;
+        let state = Arc::new(Mutex::new(WsConnectionState::default()));
+        let (tx, mut rx) = mpsc::channel(100);
+        let permissions = WsPermissions::default();
+        let handle = |msg: &str| {
+            let envelope: WsClientEnvelope = serde_json::from_str(msg).unwrap();
+            let events = EventSender {
+                tx: tx.clone(),
+                connection_id: Some(envelope.connection_id.clone()),
+                recorder: None,
+            };
+            let (state, permissions, pool) = (&state, &permissions, &pool);
+            async move {
+                handle_client_message(
+                    envelope.connection_id,
+                    envelope.message,
+                    state,
+                    &events,
+                    permissions,
+                    pool,
+                )
+                .await
+            }
+        };
+
+        handle(&format!(
+            r#"{{"type":"connect","url":"{}","binary_encoding":"hex"}}"#,
+            url
+        ))
+        .await;
+        let event = rx.recv().await.unwrap();
+        assert_eq!(event.connection_id.as_deref(), Some("default"));
+        assert!(matches!(
+            event.message,
+            WsServerMessage::Connected {
+                session_id: Some(_),
+                ..
+            }
+        ));
+
+        handle(r#"{"type":"ping"}"#).await;
+        match rx.recv().await.unwrap().message {
+            WsServerMessage::Pong { rtt_ms, keepalive } => {
+                assert!(rtt_ms >= 0.0);
+                assert!(!keepalive);
+            }
+            other => panic!("expected a pong, got {:?}", other),
+        }
+
+        handle(r#"{"type":"send","message":"AP8=","encoding":"base64"}"#).await;
+        assert!(matches!(
+            rx.recv().await.unwrap().message,
+            WsServerMessage::Message { size: 2, .. }
+        ));
+        match rx.recv().await.unwrap().message {
+            WsServerMessage::Message {
+                data,
+                direction,
+                encoding,
+                size,
+            } => {
+                assert_eq!((data.as_str(), direction.as_str()), ("00ff", "received"));
+                assert_eq!((encoding, size), (PayloadEncoding::Hex, 2));
+            }
+            other => panic!("expected the echo, got {:?}", other),
+        }
+
+        // Both directions are kept in the WebSocket history
+        let mut recorded: Vec<(String, String, String)> = Vec::new();
+        for _ in 0..100 {
+            recorded =
+                sqlx::query_as("SELECT direction, encoding, data FROM ws_messages ORDER BY id")
+                    .fetch_all(&pool)
+                    .await
+                    .unwrap();
+            if recorded.len() == 2 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert_eq!(
+            recorded,
+            [
+                ("sent".into(), "base64".into(), "AP8=".into()),
+                ("received".into(), "hex".into(), "00ff".into())
+            ]
+        );
+    }
+
+    /// The browser side of a bridge socket, driving handle_client_message
+    /// directly.
+    struct TestBridge {
+        pool: DbPool,
+        state: Arc<Mutex<WsConnectionState>>,
+        tx: mpsc::Sender<WsEvent>,
+        rx: mpsc::Receiver<WsEvent>,
+    }
+
+    impl TestBridge {
+        async fn new() -> Self {
+            let (tx, rx) = mpsc::channel(100);
+            Self {
+                pool: crate::db::create_test_pool().await,
+                state: Arc::new(Mutex::new(WsConnectionState::default())),
+                tx,
+                rx,
+            }
+        }
+
+        async fn handle(&self, connection_id: &str, msg: &str) {
+            let events = EventSender {
+                tx: self.tx.clone(),
+                connection_id: Some(connection_id.to_string()),
+                recorder: None,
+            };
+            let msg: WsClientMessage = serde_json::from_str(msg).unwrap();
+            handle_client_message(
+                connection_id.to_string(),
+                msg,
+                &self.state,
+                &events,
+                &WsPermissions::default(),
+                &self.pool,
+            )
+            .await
+        }
+
+        async fn next(&mut self) -> WsEvent {
+            tokio::time::timeout(Duration::from_secs(5), self.rx.recv())
+                .await
+                .expect("no event from the bridge")
+                .unwrap()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_bridge_multiple_connections() {
+        let url = start_echo_server().await;
+        let mut bridge = TestBridge::new().await;
+
+        let connect = format!(r#"{{"type":"connect","url":"{}"}}"#, url);
+        bridge.handle("a", &connect).await;
+        bridge.handle("b", &connect).await;
+        let mut connected = Vec::new();
+        for _ in 0..2 {
+            let event = bridge.next().await;
+            assert!(matches!(event.message, WsServerMessage::Connected { .. }));
+            connected.push(event.connection_id.unwrap());
+        }
+        assert_eq!(connected, ["a", "b"]);
+        assert_eq!(bridge.state.lock().await.remote.len(), 2);
+
+        bridge.handle("a", r#"{"type":"disconnect"}"#).await;
+        let event = bridge.next().await;
+        assert_eq!(event.connection_id.as_deref(), Some("a"));
+        assert!(matches!(
+            event.message,
+            WsServerMessage::Disconnected { .. }
+        ));
+
+        // The other connection is unaffected
+        bridge
+            .handle("b", r#"{"type":"send","message":"hello"}"#)
+            .await;
+        for direction in ["sent", "received"] {
+            let event = bridge.next().await;
+            assert_eq!(event.connection_id.as_deref(), Some("b"));
+            match event.message {
+                WsServerMessage::Message {
+                    data, direction: d, ..
+                } => assert_eq!((data.as_str(), d.as_str()), ("hello", direction)),
+                other => panic!("expected a message, got {:?}", other),
+            }
+        }
+
+        bridge
+            .handle("a", r#"{"type":"send","message":"hello"}"#)
+            .await;
+        let event = bridge.next().await;
+        assert_eq!(event.connection_id.as_deref(), Some("a"));
+        assert!(matches!(event.message, WsServerMessage::Error { .. }));
+
+        let json = serde_json::to_value(WsEvent {
+            connection_id: None,
+            message: WsServerMessage::Info {
+                message: "hi".to_string(),
+            },
+        })
+        .unwrap();
+        assert_eq!(json, serde_json::json!({"type": "info", "message": "hi"}));
+    }
+
+    #[tokio::test]
+    async fn test_bridge_interval_send() {
+        let url = start_echo_server().await;
+        let mut bridge = TestBridge::new().await;
+        bridge
+            .handle("ws", &format!(r#"{{"type":"connect","url":"{}"}}"#, url))
+            .await;
+        assert!(matches!(
+            bridge.next().await.message,
+            WsServerMessage::Connected { .. }
+        ));
+
+        bridge
+            .handle(
+                "ws",
+                r#"{"type":"start_interval","message":"beat","interval_ms":10,"count":3}"#,
+            )
+            .await;
+        let (mut sent, mut received) = (0, 0);
+        loop {
+            match bridge.next().await.message {
+                WsServerMessage::Message { direction, .. } if direction == "sent" => sent += 1,
+                WsServerMessage::Message { .. } => received += 1,
+                WsServerMessage::Info { message } if message.starts_with("Sent ") => {
+                    assert_eq!(message, "Sent 3 scheduled messages");
+                    break;
+                }
+                WsServerMessage::Info { .. } => {}
+                other => panic!("unexpected event {:?}", other),
+            }
+        }
+        assert_eq!(sent, 3);
+        while received < 3 {
+            assert!(matches!(
+                bridge.next().await.message,
+                WsServerMessage::Message { .. }
+            ));
+            received += 1;
+        }
+
+        // A schedule without a count runs until stopped
+        bridge
+            .handle(
+                "ws",
+                r#"{"type":"start_interval","message":"beat","interval_ms":1000}"#,
+            )
+            .await;
+        bridge.handle("ws", r#"{"type":"stop_interval"}"#).await;
+        let mut stopped = false;
+        while !stopped {
+            stopped = matches!(
+                bridge.next().await.message,
+                WsServerMessage::Info { message } if message.starts_with("Stopped")
+            );
+        }
+        bridge.handle("ws", r#"{"type":"stop_interval"}"#).await;
+        assert!(matches!(
+            bridge.next().await.message,
+            WsServerMessage::Error { .. }
+        ));
+    }
+
+    /// A remote end that closes with 4001 when sent `close`, and reports the
+    /// close frames it receives.
+    async fn start_closing_server() -> (String, mpsc::UnboundedReceiver<Option<CloseFrame>>) {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (closes_tx, closes_rx) = mpsc::unbounded_channel();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let closes_tx = closes_tx.clone();
+                tokio::spawn(async move {
+                    let mut ws = tokio_tungstenite::accept_async(stream).await.unwrap();
+                    while let Some(Ok(msg)) = ws.next().await {
+                        match msg {
+                            TungsteniteMessage::Text(text) if text.as_str() == "close" => {
+                                let _ = ws
+                                    .close(Some(CloseFrame {
+                                        code: CloseCode::from(4001),
+                                        reason: "bye".into(),
+                                    }))
+                                    .await;
+                            }
+                            TungsteniteMessage::Close(frame) => {
+                                let _ = closes_tx.send(frame);
+                            }
+                            _ => {}
+                        }
+                    }
+                });
+            }
+        });
+        (format!("ws://{}", addr), closes_rx)
+    }
+
+    #[tokio::test]
+    async fn test_bridge_close_codes() {
+        let (url, mut closes) = start_closing_server().await;
+        let mut bridge = TestBridge::new().await;
+        let connect = format!(r#"{{"type":"connect","url":"{}"}}"#, url);
+
+        bridge.handle("a", &connect).await;
+        bridge.handle("b", &connect).await;
+        for _ in 0..2 {
+            assert!(matches!(
+                bridge.next().await.message,
+                WsServerMessage::Connected { .. }
+            ));
+        }
+
+        bridge
+            .handle("a", r#"{"type":"disconnect","code":4000,"reason":"done"}"#)
+            .await;
+        match bridge.next().await.message {
+            WsServerMessage::Disconnected { reason, code } => {
+                assert_eq!((reason.as_str(), code), ("done", Some(4000)));
+            }
+            other => panic!("expected a disconnect, got {:?}", other),
+        }
+        let frame = tokio::time::timeout(Duration::from_secs(5), closes.recv())
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            (u16::from(frame.code), frame.reason.as_str()),
+            (4000, "done")
+        );
+
+        bridge
+            .handle("b", r#"{"type":"send","message":"close"}"#)
+            .await;
+        assert!(matches!(
+            bridge.next().await.message,
+            WsServerMessage::Message { .. }
+        ));
+        let event = bridge.next().await;
+        assert_eq!(event.connection_id.as_deref(), Some("b"));
+        match event.message {
+            WsServerMessage::Disconnected { reason, code } => {
+                assert_eq!((reason.as_str(), code), ("bye", Some(4001)));
+            }
+            other => panic!("expected a disconnect, got {:?}", other),
+        }
+        assert!(bridge.state.lock().await.remote.is_empty());
+
+        bridge
+            .handle("a", r#"{"type":"disconnect","code":1005}"#)
+            .await;
+        assert!(matches!(
+            bridge.next().await.message,
+            WsServerMessage::Error { .. }
+        ));
+        assert!(close_frame(None, Some("x".repeat(124))).is_err());
+        assert_eq!(u16::from(close_frame(None, None).unwrap().code), 1000);
+    }
+
+    /// A graphql-transport-ws server whose subscriptions count to two.
+    // The handshake callback's error type is tungstenite's
+    #[allow(clippy::result_large_err)]
+    async fn start_graphql_server() -> String {
+        use tokio_tungstenite::tungstenite::handshake::server::{Request, Response};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                tokio::spawn(async move {
+                    let accept_protocol = |request: &Request, mut response: Response| {
+                        let protocol = request.headers().get("Sec-WebSocket-Protocol").cloned();
+                        assert_eq!(protocol.as_ref().unwrap(), GRAPHQL_PROTOCOL);
+                        response
+                            .headers_mut()
+                            .insert("Sec-WebSocket-Protocol", protocol.unwrap());
+                        Ok(response)
+                    };
+                    let mut ws = tokio_tungstenite::accept_hdr_async(stream, accept_protocol)
+                        .await
+                        .unwrap();
+                    while let Some(Ok(TungsteniteMessage::Text(text))) = ws.next().await {
+                        let reply =
+                            |value: Value| TungsteniteMessage::Text(value.to_string().into());
+                        let message: Value = serde_json::from_str(&text).unwrap();
+                        match message["type"].as_str().unwrap() {
+                            "connection_init" => {
+                                assert_eq!(message["payload"]["token"], "t");
+                                ws.send(reply(serde_json::json!({"type": "connection_ack"})))
+                                    .await
+                                    .unwrap();
+                                ws.send(reply(serde_json::json!({"type": "ping"})))
+                                    .await
+                                    .unwrap();
+                            }
+                            "subscribe" => {
+                                let id = message["id"].clone();
+                                for n in 1..=2 {
+                                    ws.send(reply(serde_json::json!({
+                                        "id": id, "type": "next", "payload": {"data": {"n": n}}
+                                    })))
+                                    .await
+                                    .unwrap();
+                                }
+                                ws.send(reply(serde_json::json!({"id": id, "type": "complete"})))
+                                    .await
+                                    .unwrap();
+                            }
+                            // Answers the server's own ping
+                            "pong" => {
+                                ws.send(reply(
+                                    serde_json::json!({"type": "raw", "text": "pong seen"}),
+                                ))
+                                .await
+                                .unwrap();
+                            }
+                            _ => {}
+                        }
+                    }
+                });
+            }
+        });
+        format!("ws://{}", addr)
+    }
+
+    #[tokio::test]
+    async fn test_bridge_graphql_subscription() {
+        let url = start_graphql_server().await;
+        let mut bridge = TestBridge::new().await;
+        bridge
+            .handle(
+                "gql",
+                r#"{"type":"subscribe","subscription_id":"s1","query":"subscription { n }"}"#,
+            )
+            .await;
+        assert!(matches!(
+            bridge.next().await.message,
+            WsServerMessage::Error { .. }
+        ));
+
+        bridge
+            .handle(
+                "gql",
+                &format!(
+                    r#"{{"type":"connect","url":"{}","graphql":{{"connection_params":{{"token":"t"}}}}}}"#,
+                    url
+                ),
+            )
+            .await;
+        assert!(matches!(
+            bridge.next().await.message,
+            WsServerMessage::Connected { .. }
+        ));
+        assert!(matches!(
+            bridge.next().await.message,
+            WsServerMessage::GraphqlAck { .. }
+        ));
+        // The reply to the server's ping is not a protocol message
+        match bridge.next().await.message {
+            WsServerMessage::Message { data, .. } => assert!(data.contains("pong seen")),
+            other => panic!("expected the raw message, got {:?}", other),
+        }
+
+        bridge
+            .handle(
+                "gql",
+                r#"{"type":"subscribe","subscription_id":"s1","query":"subscription { n }"}"#,
+            )
+            .await;
+        for n in 1..=2 {
+            match bridge.next().await.message {
+                WsServerMessage::GraphqlNext {
+                    subscription_id,
+                    payload,
+                } => {
+                    assert_eq!(subscription_id, "s1");
+                    assert_eq!(payload, serde_json::json!({"data": {"n": n}}));
+                }
+                other => panic!("expected a result, got {:?}", other),
+            }
+        }
+        assert!(matches!(
+            bridge.next().await.message,
+            WsServerMessage::GraphqlComplete { subscription_id } if subscription_id == "s1"
+        ));
+
+        // Completed by the server, so it can no longer be stopped
+        bridge
+            .handle("gql", r#"{"type":"complete","subscription_id":"s1"}"#)
+            .await;
+        assert!(matches!(
+            bridge.next().await.message,
+            WsServerMessage::Error { .. }
+        ));
+        bridge
+            .handle(
+                "gql",
+                r#"{"type":"subscribe","subscription_id":"s2","query":" "}"#,
+            )
+            .await;
+        assert!(matches!(
+            bridge.next().await.message,
+            WsServerMessage::Error { .. }
+        ));
+    }
+
+    #[test]
+    fn test_permissions_check_target() {
+        let open = WsPermissions::default();
+        assert!(open.check_target("wss://echo.example/ws").is_ok());
+        assert!(open.check_target("http://echo.example").is_err());
+
+        let restricted = WsPermissions {
+            allowed_hosts: vec!["echo.example".to_string()],
+        };
+        assert!(restricted.check_target("ws://echo.example:8080").is_ok());
+        assert!(restricted.check_target("ws://internal.example").is_err());
+    }
+}
@@ -0,0 +1,486 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    routing::get,
+    Json, Router,
+};
+use chrono::{DateTime, NaiveDateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc;
+
+use crate::{db::DbPool, i18n};
+
+/// Only the messages of this many most recent sessions are kept.
+const KEPT_SESSIONS: i64 = 50;
+const DEFAULT_LIMIT: i64 = 1000;
+const MAX_LIMIT: i64 = 10_000;
+
+/// One bridge connection, from connect until it closed.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WsSession {
+    pub id: i64,
+    pub url: String,
+    pub started_at: DateTime<Utc>,
+    pub ended_at: Option<DateTime<Utc>>,
+    pub message_count: i64,
+}
+
+struct WsSessionDb {
+    id: i64,
+    url: String,
+    started_at: NaiveDateTime,
+    ended_at: Option<NaiveDateTime>,
+    message_count: i64,
+}
+
+impl From<WsSessionDb> for WsSession {
+    fn from(s: WsSessionDb) -> Self {
+        Self {
+            id: s.id,
+            url: s.url,
+            started_at: s.started_at.and_utc(),
+            ended_at: s.ended_at.map(|at| at.and_utc()),
+            message_count: s.message_count,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WsMessage {
+    pub id: i64,
+    pub session_id: i64,
+    pub direction: String,
+    /// `text`, `base64` or `hex`, as `data` is written
+    pub encoding: String,
+    pub data: String,
+    /// Payload bytes
+    pub size: i64,
+    pub created_at: DateTime<Utc>,
+}
+
+struct WsMessageDb {
+    id: i64,
+    session_id: i64,
+    direction: String,
+    encoding: String,
+    data: String,
+    size: i64,
+    created_at: NaiveDateTime,
+}
+
+impl From<WsMessageDb> for WsMessage {
+    fn from(m: WsMessageDb) -> Self {
+        Self {
+            id: m.id,
+            session_id: m.session_id,
+            direction: m.direction,
+            encoding: m.encoding,
+            data: m.data,
+            size: m.size,
+            created_at: m.created_at.and_utc(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Direction {
+    Sent,
+    Received,
+}
+
+impl Direction {
+    fn as_str(self) -> &'static str {
+        match self {
+            Direction::Sent => "sent",
+            Direction::Received => "received",
+        }
+    }
+}
+
+/// Filters shared by the message search and the export; all are optional.
+#[derive(Debug, Default, Deserialize)]
+pub struct MessageFilter {
+    session_id: Option<i64>,
+    direction: Option<Direction>,
+    /// Case-insensitive text the message data contains
+    contains: Option<String>,
+    /// Messages at or after this time
+    from: Option<DateTime<Utc>>,
+    /// Messages before this time
+    to: Option<DateTime<Utc>>,
+    limit: Option<i64>,
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    #[default]
+    Json,
+    /// One message per line
+    Ndjson,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ExportQuery {
+    #[serde(default)]
+    format: ExportFormat,
+    #[serde(flatten)]
+    filter: MessageFilter,
+}
+
+#[derive(Serialize)]
+struct SessionExport {
+    session: WsSession,
+    messages: Vec<WsMessage>,
+}
+
+#[derive(Debug)]
+pub enum WsHistoryError {
+    SessionNotFound,
+    DatabaseError(#[allow(dead_code)] sqlx::Error),
+}
+
+impl From<sqlx::Error> for WsHistoryError {
+    fn from(e: sqlx::Error) -> Self {
+        match e {
+            sqlx::Error::RowNotFound => WsHistoryError::SessionNotFound,
+            _ => WsHistoryError::DatabaseError(e),
+        }
+    }
+}
+
+impl IntoResponse for WsHistoryError {
+    fn into_response(self) -> Response {
+        match self {
+            WsHistoryError::SessionNotFound => {
+                (StatusCode::NOT_FOUND, i18n::t("error.ws_session_not_found")).into_response()
+            }
+            WsHistoryError::DatabaseError(_) => {
+                (StatusCode::INTERNAL_SERVER_ERROR, i18n::t("error.database")).into_response()
+            }
+        }
+    }
+}
+
+struct RecordedMessage {
+    direction: &'static str,
+    encoding: &'static str,
+    data: String,
+    size: usize,
+    at: NaiveDateTime,
+}
+
+/// Records the messages of one bridge connection. Messages are written by a
+/// background task so a busy stream is not slowed down by the database; the
+/// session ends when the last clone is dropped.
+#[derive(Clone)]
+pub struct SessionRecorder {
+    id: i64,
+    tx: mpsc::UnboundedSender<RecordedMessage>,
+}
+
+impl SessionRecorder {
+    /// Starts a session for a connection to `url`. Failures are logged rather
+    /// than returned, since they must not fail the connection.
+    pub async fn start(pool: &DbPool, url: &str) -> Option<Self> {
+        let started_at = Utc::now().naive_utc();
+        let result = sqlx::query_scalar!(
+            "INSERT INTO ws_sessions (url, started_at) VALUES (?, ?) RETURNING id",
+            url,
+            started_at
+        )
+        .fetch_one(pool)
+        .await;
+        let id = match result {
+            Ok(id) => id,
+            Err(e) => {
+                log::warn!("Failed to record WebSocket session: {}", e);
+                return None;
+            }
+        };
+        let pruned = sqlx::query!(
+            "DELETE FROM ws_sessions WHERE id <= ? - ?",
+            id,
+            KEPT_SESSIONS
+        )
+        .execute(pool)
+        .await;
+        if let Err(e) = pruned {
+            log::warn!("Failed to clear old WebSocket sessions: {}", e);
+        }
+
+        let (tx, mut rx) = mpsc::unbounded_channel::<RecordedMessage>();
+        let pool = pool.clone();
+        tokio::spawn(async move {
+            while let Some(message) = rx.recv().await {
+                let size = message.size as i64;
+                let result = sqlx::query!(
+                    "INSERT INTO ws_messages (session_id, direction, encoding, data, size, created_at) VALUES (?, ?, ?, ?, ?, ?)",
+                    id,
+                    message.direction,
+                    message.encoding,
+                    message.data,
+                    size,
+                    message.at
+                )
+                .execute(&pool)
+                .await;
+                if let Err(e) = result {
+                    log::warn!("Failed to record WebSocket message: {}", e);
+                }
+            }
+            let ended_at = Utc::now().naive_utc();
+            let result = sqlx::query!(
+                "UPDATE ws_sessions SET ended_at = ? WHERE id = ?",
+                ended_at,
+                id
+            )
+            .execute(&pool)
+            .await;
+            if let Err(e) = result {
+                log::warn!("Failed to end WebSocket session: {}", e);
+            }
+        });
+        Some(Self { id, tx })
+    }
+
+    pub fn id(&self) -> i64 {
+        self.id
+    }
+
+    pub fn record(&self, direction: &'static str, encoding: &'static str, data: &str, size: usize) {
+        let _ = self.tx.send(RecordedMessage {
+            direction,
+            encoding,
+            data: data.to_string(),
+            size,
+            at: Utc::now().naive_utc(),
+        });
+    }
+}
+
+async fn fetch_session(pool: &DbPool, id: i64) -> Result<WsSession, WsHistoryError> {
+    let session = sqlx::query_as!(
+        WsSessionDb,
+        r#"SELECT s.id as "id!", s.url, s.started_at as "started_at: NaiveDateTime", s.ended_at as "ended_at: NaiveDateTime",
+            (SELECT COUNT(*) FROM ws_messages m WHERE m.session_id = s.id) as "message_count!: i64"
+        FROM ws_sessions s WHERE s.id = ?"#,
+        id
+    )
+    .fetch_one(pool)
+    .await?;
+    Ok(session.into())
+}
+
+/// Messages matching the filter in the order they were sent or received.
+/// A negative limit returns all of them.
+async fn fetch_messages(
+    pool: &DbPool,
+    filter: &MessageFilter,
+    limit: i64,
+) -> Result<Vec<WsMessage>, sqlx::Error> {
+    let direction = filter.direction.map(Direction::as_str);
+    let contains = filter.contains.as_deref().filter(|text| !text.is_empty());
+    let from = filter.from.map(|at| at.naive_utc());
+    let to = filter.to.map(|at| at.naive_utc());
+    let messages = sqlx::query_as!(
+        WsMessageDb,
+        r#"SELECT id as "id!", session_id, direction, encoding, data, size, created_at as "created_at: NaiveDateTime"
+        FROM ws_messages
+        WHERE (? IS NULL OR session_id = ?)
+            AND (? IS NULL OR direction = ?)
+            AND (? IS NULL OR instr(lower(data), lower(?)) > 0)
+            AND (? IS NULL OR created_at >= ?)
+            AND (? IS NULL OR created_at < ?)
+        ORDER BY id
+        LIMIT ?"#,
+        filter.session_id,
+        filter.session_id,
+        direction,
+        direction,
+        contains,
+        contains,
+        from,
+        from,
+        to,
+        to,
+        limit
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(messages.into_iter().map(WsMessage::from).collect())
+}
+
+async fn list_sessions(State(pool): State<DbPool>) -> Result<impl IntoResponse, WsHistoryError> {
+    let sessions = sqlx::query_as!(
+        WsSessionDb,
+        r#"SELECT s.id as "id!", s.url, s.started_at as "started_at: NaiveDateTime", s.ended_at as "ended_at: NaiveDateTime",
+            (SELECT COUNT(*) FROM ws_messages m WHERE m.session_id = s.id) as "message_count!: i64"
+        FROM ws_sessions s ORDER BY s.id DESC"#
+    )
+    .fetch_all(&pool)
+    .await?;
+    Ok(Json(
+        sessions
+            .into_iter()
+            .map(WsSession::from)
+            .collect::<Vec<_>>(),
+    ))
+}
+
+async fn search_messages(
+    State(pool): State<DbPool>,
+    Query(filter): Query<MessageFilter>,
+) -> Result<impl IntoResponse, WsHistoryError> {
+    let limit = filter.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+    Ok(Json(fetch_messages(&pool, &filter, limit).await?))
+}
+
+/// A session with its messages as a JSON document or NDJSON stream, with
+/// the same filters as the search.
+async fn export_session(
+    State(pool): State<DbPool>,
+    Path(id): Path<i64>,
+    Query(query): Query<ExportQuery>,
+) -> Result<impl IntoResponse, WsHistoryError> {
+    let session = fetch_session(&pool, id).await?;
+    let filter = MessageFilter {
+        session_id: Some(id),
+        ..query.filter
+    };
+    let messages = fetch_messages(&pool, &filter, -1).await?;
+    log::info!(
+        "Exporting {} messages of WebSocket session {}",
+        messages.len(),
+        id
+    );
+
+    let (content_type, extension, body) = match query.format {
+        ExportFormat::Json => (
+            "application/json",
+            "json",
+            serde_json::to_string_pretty(&SessionExport { session, messages }).unwrap_or_default(),
+        ),
+        ExportFormat::Ndjson => (
+            "application/x-ndjson",
+            "ndjson",
+            messages
+                .iter()
+                .filter_map(|message| serde_json::to_string(message).ok())
+                .map(|line| line + "\n")
+                .collect(),
+        ),
+    };
+    Ok((
+        [
+            (header::CONTENT_TYPE, content_type.to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"ws-session-{}.{}\"", id, extension),
+            ),
+        ],
+        body,
+    ))
+}
+
+pub fn routes(pool: DbPool) -> Router {
+    Router::new()
+        .route("/ws-history/sessions", get(list_sessions))
+        .route("/ws-history/sessions/:id/export", get(export_session))
+        .route("/ws-history/messages", get(search_messages))
+        .with_state(pool)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db;
+    use axum_test::TestServer;
+    use std::time::Duration;
+
+    /// Waits for the recorder task to write a session's messages and end it.
+    async fn wait_for_end(pool: &DbPool, session_id: i64) {
+        for _ in 0..100 {
+            if fetch_session(pool, session_id)
+                .await
+                .unwrap()
+                .ended_at
+                .is_some()
+            {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        panic!("the session did not end");
+    }
+
+    #[tokio::test]
+    async fn test_search_and_export() {
+        let pool = db::create_test_pool().await;
+        let first = SessionRecorder::start(&pool, "wss://stream.example/a")
+            .await
+            .unwrap();
+        first.record("sent", "text", "subscribe ticker", 16);
+        first.record("received", "text", "{\"price\": 10}", 13);
+        first.record("received", "hex", "00ff", 2);
+        drop(first);
+        // Each session has its own writer, so let the first finish to keep
+        // message ids in session order
+        wait_for_end(&pool, 1).await;
+        let second = SessionRecorder::start(&pool, "wss://stream.example/b")
+            .await
+            .unwrap();
+        second.record("received", "text", "PRICE 11", 8);
+        drop(second);
+        wait_for_end(&pool, 2).await;
+
+        let server = TestServer::new(routes(pool)).unwrap();
+        let sessions: Vec<WsSession> = server.get("/ws-history/sessions").await.json();
+        assert_eq!(sessions.len(), 2);
+        assert_eq!(sessions[1].url, "wss://stream.example/a");
+        assert_eq!(sessions[1].message_count, 3);
+
+        let search = |query: &str| server.get(&format!("/ws-history/messages?{}", query));
+        let messages: Vec<WsMessage> = search("contains=price").await.json();
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[1].session_id, 2);
+        let messages: Vec<WsMessage> = search("session_id=1&direction=received").await.json();
+        let data: Vec<&str> = messages.iter().map(|m| m.data.as_str()).collect();
+        assert_eq!(data, ["{\"price\": 10}", "00ff"]);
+        let messages: Vec<WsMessage> = search("limit=1").await.json();
+        assert_eq!(messages[0].data, "subscribe ticker");
+        let messages: Vec<WsMessage> = search("to=2000-01-01T00:00:00Z").await.json();
+        assert!(messages.is_empty());
+        let messages: Vec<WsMessage> = search("from=2000-01-01T00:00:00Z").await.json();
+        assert_eq!(messages.len(), 4);
+        search("direction=sideways")
+            .await
+            .assert_status(StatusCode::BAD_REQUEST);
+
+        let response = server
+            .get("/ws-history/sessions/1/export?format=ndjson&direction=received")
+            .await;
+        assert_eq!(
+            response.header(header::CONTENT_TYPE),
+            "application/x-ndjson"
+        );
+        let lines: Vec<WsMessage> = response
+            .text()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 2);
+
+        let export: serde_json::Value = server.get("/ws-history/sessions/1/export").await.json();
+        assert_eq!(export["session"]["url"], "wss://stream.example/a");
+        assert!(export["session"]["ended_at"].is_string());
+        assert_eq!(export["messages"].as_array().unwrap().len(), 3);
+
+        server
+            .get("/ws-history/sessions/9/export")
+            .await
+            .assert_status(StatusCode::NOT_FOUND);
+    }
+}
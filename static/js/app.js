@@ -6,9 +6,18 @@ let requests = [];
 let environments = [];
 let executionHistory = [];
 let showArchived = false;
-let wsConnection = null;
+let wsBridge = null;
+let wsBridgeReady = null;
+let wsConnections = new Map();
+let wsActiveConnectionId = null;
+let wsNextConnectionId = 1;
+let wsNextSubscriptionId = 1;
 let wsConnected = false;
 let pendingImportFile = null;
+let lastResponseUrl = null;
+let lastHistoryId = null;
+let lastResponseBody = null;
+let i18nMessages = {};
 
 // Initialize app
 document.addEventListener('DOMContentLoaded', () => {
@@ -21,6 +30,8 @@ document.addEventListener('DOMContentLoaded', () => {
     setupTheme();
     setupEnvironmentToggle();
     setupLayoutToggle();
+    setupLanguage();
+    subscribeToChanges();
     // Initialize variable preview with defaults
     setTimeout(() => {
         updateVariablePreview();
@@ -28,6 +39,92 @@ document.addEventListener('DOMContentLoaded', () => {
     }, 500);
 });
 
+// Keeps the sidebar and environments in step with changes made in other tabs
+// or by teammates, reloading once per burst of events.
+function subscribeToChanges() {
+    const token = new URLSearchParams(window.location.search).get('token');
+    const scheme = window.location.protocol === 'https:' ? 'wss' : 'ws';
+    const url = `${scheme}://${window.location.host}/api/events` +
+        (token ? `?token=${encodeURIComponent(token)}` : '');
+    const pending = new Set();
+    let timer = null;
+
+    const reload = () => {
+        timer = null;
+        if (pending.has('folder')) loadFolders();
+        if (pending.has('folder') || pending.has('request')) loadRequests();
+        if (pending.has('environment')) loadEnvironments();
+        pending.clear();
+    };
+
+    const socket = new WebSocket(url);
+    socket.onmessage = (event) => {
+        try {
+            const change = JSON.parse(event.data);
+            if (change.lagged) {
+                ['folder', 'request', 'environment'].forEach(e => pending.add(e));
+            } else {
+                pending.add(change.entity);
+            }
+            if (!timer) timer = setTimeout(reload, 300);
+        } catch (e) {
+            console.error('Failed to parse change event:', e);
+        }
+    };
+    socket.onclose = () => setTimeout(subscribeToChanges, 5000);
+}
+
+// Message catalog of the language picked in the settings; untranslated
+// keys fall back to the text already in the page.
+function t(key, fallback) {
+    return i18nMessages[key] || fallback || key;
+}
+
+function applyTranslations() {
+    document.querySelectorAll('[data-i18n]').forEach(el => {
+        el.textContent = t(el.dataset.i18n, el.textContent);
+    });
+    document.querySelectorAll('[data-i18n-title]').forEach(el => {
+        el.title = t(el.dataset.i18nTitle, el.title);
+    });
+}
+
+async function setupLanguage() {
+    const languageSelect = document.getElementById('language-select');
+    const applyCatalog = (catalog) => {
+        i18nMessages = catalog.messages || {};
+        document.documentElement.lang = catalog.language;
+        if (languageSelect) {
+            languageSelect.innerHTML = catalog.languages
+                .map(l => `<option value="${l.code}">${l.name}</option>`)
+                .join('');
+            languageSelect.value = catalog.language;
+        }
+        applyTranslations();
+    };
+
+    try {
+        const response = await fetch('/api/i18n');
+        if (response.ok) applyCatalog(await response.json());
+    } catch (error) {
+        console.error('Error loading messages:', error);
+    }
+
+    if (!languageSelect) return;
+    languageSelect.addEventListener('change', async () => {
+        const response = await fetch('/api/i18n/language', {
+            method: 'PUT',
+            headers: { 'Content-Type': 'application/json' },
+            body: JSON.stringify({ language: languageSelect.value })
+        });
+        if (response.ok) {
+            applyCatalog(await response.json());
+        } else {
+            showNotification(await response.text(), 'error');
+        }
+    });
+}
+
 // Setup theme
 function setupTheme() {
     const themeToggleBtn = document.getElementById('theme-toggle-btn');
@@ -149,12 +246,18 @@ function setupEventListeners() {
 
     // Method change handler for WebSocket mode
     document.getElementById('request-method').addEventListener('change', handleMethodChange);
+    document.getElementById('request-method-modal')?.addEventListener('change', (e) => chooseCustomMethod(e.target));
 
     // WebSocket buttons
     document.getElementById('ws-connect-btn').addEventListener('click', connectWebSocket);
     document.getElementById('ws-disconnect-btn').addEventListener('click', disconnectWebSocket);
     document.getElementById('ws-send-btn').addEventListener('click', sendWebSocketMessage);
     document.getElementById('ws-clear-btn').addEventListener('click', clearWebSocketMessages);
+    document.getElementById('ws-export-btn').addEventListener('click', exportWebSocketSession);
+    document.getElementById('ws-ping-btn').addEventListener('click', pingWebSocket);
+    document.getElementById('ws-repeat-btn').addEventListener('click', startWebSocketInterval);
+    document.getElementById('ws-subscribe-btn').addEventListener('click', subscribeGraphql);
+    document.getElementById('ws-stop-repeat-btn').addEventListener('click', stopWebSocketInterval);
 
     // Environment selector
     const envSelect = document.getElementById('environment-select');
@@ -174,6 +277,10 @@ function setupEventListeners() {
                 envActions.style.display = 'none';
             }
         }
+        const transferActions = document.getElementById('environment-transfer-actions');
+        if (transferActions) {
+            transferActions.style.display = currentEnvironmentId ? 'flex' : 'none';
+        }
     });
 
     // Add context menu for environment selector
@@ -210,10 +317,15 @@ function setupEventListeners() {
 
     // Header management
     setupHeaderManagement();
+    setupCaptureManagement();
+    setupFormBodyManagement();
 
     // Copy response
     document.getElementById('copy-response').addEventListener('click', copyResponse);
 
+    // Save response body as a file
+    document.getElementById('save-response').addEventListener('click', downloadResponse);
+
     // Format response
     document.getElementById('format-response').addEventListener('click', formatResponse);
 
@@ -263,6 +375,17 @@ function setupEventListeners() {
         addEnvBtn.addEventListener('click', () => openEnvironmentModal());
     }
 
+    // Global variables button
+    const recordingsBtn = document.getElementById('recordings-btn');
+    if (recordingsBtn) {
+        recordingsBtn.addEventListener('click', () => openRecordingsModal());
+    }
+
+    const globalsBtn = document.getElementById('globals-btn');
+    if (globalsBtn) {
+        globalsBtn.addEventListener('click', () => openGlobalsModal());
+    }
+
     // Edit environment button
     const editEnvBtn = document.getElementById('edit-environment-btn');
     if (editEnvBtn) {
@@ -293,6 +416,32 @@ function setupEventListeners() {
         });
     }
 
+    // Export the environment for Postman, or merge a .env file into it
+    const exportEnvBtn = document.getElementById('export-environment-btn');
+    if (exportEnvBtn) {
+        exportEnvBtn.addEventListener('click', () => {
+            if (currentEnvironmentId) {
+                window.location.href = `/api/export/environments/${currentEnvironmentId}/postman`;
+            }
+        });
+    }
+    const dotenvInput = document.getElementById('dotenv-file-input');
+    const importDotenvBtn = document.getElementById('import-dotenv-btn');
+    if (importDotenvBtn && dotenvInput) {
+        importDotenvBtn.addEventListener('click', () => {
+            if (currentEnvironmentId) {
+                dotenvInput.click();
+            }
+        });
+        dotenvInput.addEventListener('change', async () => {
+            const file = dotenvInput.files[0];
+            dotenvInput.value = '';
+            if (file && currentEnvironmentId) {
+                await importDotenv(currentEnvironmentId, file);
+            }
+        });
+    }
+
     // Allow Enter key to submit modals
     document.querySelectorAll('.modal form').forEach(form => {
         form.addEventListener('submit', (e) => {
@@ -679,7 +828,7 @@ function loadRequestIntoEditor(request) {
         typeSelect.dispatchEvent(new Event('change'));
     }
 
-    document.getElementById('request-method').value = request.method || 'GET';
+    setMethodValue(document.getElementById('request-method'), request.method || 'GET');
     document.getElementById('request-url').value = request.url || '';
 
     // Load body type and content
@@ -698,6 +847,7 @@ function loadRequestIntoEditor(request) {
     } else {
         bodyTextarea.value = '';
     }
+    loadFormRows(request.body_type === 'form' ? request.body_content : null);
 
     // Load authentication
     const authTypeSelect = document.getElementById('auth-type-select');
@@ -714,8 +864,33 @@ function loadRequestIntoEditor(request) {
         const passwordInput = document.getElementById('auth-basic-password');
         if (usernameInput && request.auth_username) usernameInput.value = request.auth_username;
         if (passwordInput && request.auth_password) passwordInput.value = request.auth_password;
+    } else if (request.auth_type === 'aws_sigv4') {
+        const awsConfig = request.auth_config ? JSON.parse(request.auth_config) : {};
+        document.getElementById('auth-aws-access-key').value = request.auth_username || '';
+        document.getElementById('auth-aws-secret-key').value = request.auth_password || '';
+        document.getElementById('auth-aws-session-token').value = request.auth_token || '';
+        document.getElementById('auth-aws-region').value = awsConfig.region || '';
+        document.getElementById('auth-aws-service').value = awsConfig.service || '';
+    } else if (request.auth_type === 'jwt') {
+        const jwtConfig = request.auth_config ? JSON.parse(request.auth_config) : {};
+        document.getElementById('auth-jwt-algorithm').value = jwtConfig.algorithm || 'HS256';
+        document.getElementById('auth-jwt-key').value = request.auth_password || '';
+        document.getElementById('auth-jwt-claims').value = jwtConfig.claims ? JSON.stringify(jwtConfig.claims, null, 2) : '';
+        document.getElementById('auth-jwt-expires-in').value = jwtConfig.expires_in || '';
+    } else if (request.auth_type === 'chained') {
+        const chainedConfig = request.auth_config ? JSON.parse(request.auth_config) : {};
+        fillTokenRequestOptions(request.id, chainedConfig.request_id);
+        document.getElementById('auth-chained-path').value = chainedConfig.token_path || '';
+        document.getElementById('auth-chained-ttl').value = chainedConfig.ttl ?? '';
     }
 
+    loadCaptureRows(request.captures);
+
+    const scriptInput = document.getElementById('pre-request-script');
+    if (scriptInput) scriptInput.value = request.pre_request_script || '';
+    const testScriptInput = document.getElementById('test-script');
+    if (testScriptInput) testScriptInput.value = request.test_script || '';
+
     // Load headers
     const tbody = document.getElementById('headers-tbody');
     if (tbody) {
@@ -723,10 +898,12 @@ function loadRequestIntoEditor(request) {
 
         if (request.headers) {
             try {
+                // Rows with an enabled flag, or an object saved before rows existed
                 const headers = JSON.parse(request.headers);
-                Object.entries(headers).forEach(([key, value]) => {
-                    addHeaderRow(key, value);
-                });
+                const rows = Array.isArray(headers)
+                    ? headers
+                    : Object.entries(headers).map(([key, value]) => ({ key, value, enabled: true }));
+                rows.forEach(row => addHeaderRow(row.key, row.value ?? '', row.enabled !== false));
             } catch (e) {
                 console.error('Error parsing headers:', e);
                 showNotification('Warning: Failed to parse headers', 'error');
@@ -859,6 +1036,7 @@ function openFolderModal(folderId = null) {
     const title = document.getElementById('folder-modal-title');
     const nameInput = document.getElementById('folder-name');
     const idInput = document.getElementById('folder-id');
+    const varsInput = document.getElementById('folder-variables');
 
     if (!modal) {
         console.error('Folder modal not found');
@@ -872,22 +1050,35 @@ function openFolderModal(folderId = null) {
             if (title) title.textContent = 'Edit Folder';
             if (nameInput) nameInput.value = folder.name;
             if (idInput) idInput.value = folder.id;
+            if (varsInput) varsInput.value = formatFolderVariables(folder.variables);
         }
     } else {
         // New mode
         if (title) title.textContent = 'New Folder';
         if (nameInput) nameInput.value = '';
         if (idInput) idInput.value = '';
+        if (varsInput) varsInput.value = '';
     }
 
     openModal('folder-modal');
 }
 
+function formatFolderVariables(variables) {
+    try {
+        const vars = JSON.parse(variables || '{}');
+        return Object.keys(vars).length ? JSON.stringify(vars, null, 4) : '';
+    } catch (e) {
+        return variables;
+    }
+}
+
 async function saveFolder() {
     const idInput = document.getElementById('folder-id');
     const nameInput = document.getElementById('folder-name');
+    const varsInput = document.getElementById('folder-variables');
     const folderId = idInput.value ? parseInt(idInput.value) : null;
     const name = nameInput.value.trim();
+    const variables = varsInput.value.trim() || '{}';
 
     if (!name) {
         showNotification('Please enter a folder name', 'error');
@@ -900,6 +1091,13 @@ async function saveFolder() {
         return;
     }
 
+    try {
+        JSON.parse(variables);
+    } catch (e) {
+        showNotification('Invalid JSON format for variables. Please check your syntax.', 'error');
+        return;
+    }
+
     try {
         let response;
         if (folderId) {
@@ -907,14 +1105,14 @@ async function saveFolder() {
             response = await fetch(`/api/folders/${folderId}`, {
                 method: 'PUT',
                 headers: { 'Content-Type': 'application/json' },
-                body: JSON.stringify({ name: name })
+                body: JSON.stringify({ name: name, variables: variables })
             });
         } else {
             // Create new
             response = await fetch('/api/folders', {
                 method: 'POST',
                 headers: { 'Content-Type': 'application/json' },
-                body: JSON.stringify({ name: name })
+                body: JSON.stringify({ name: name, variables: variables })
             });
         }
 
@@ -1000,7 +1198,7 @@ async function openHttpRequestModal(requestId = null, type = 'http') {
                 const request = await response.json();
                 title.textContent = 'Edit Request';
                 nameInput.value = request.name;
-                methodInput.value = request.method;
+                setMethodValue(methodInput, request.method);
                 urlInput.value = request.url;
                 folderInput.value = request.folder_id || '';
                 idInput.value = request.id;
@@ -1016,7 +1214,7 @@ async function openHttpRequestModal(requestId = null, type = 'http') {
             if (request) {
                 title.textContent = 'Edit Request';
                 nameInput.value = request.name;
-                methodInput.value = request.method;
+                setMethodValue(methodInput, request.method);
                 urlInput.value = request.url;
                 folderInput.value = request.folder_id || '';
                 idInput.value = request.id;
@@ -1154,8 +1352,19 @@ function openEnvironmentModal(envId = null) {
     const title = document.getElementById('environment-modal-title');
     const nameInput = document.getElementById('environment-name');
     const varsInput = document.getElementById('environment-variables');
+    const secretsInput = document.getElementById('environment-secrets');
+    const disabledInput = document.getElementById('environment-disabled');
+    const parentSelect = document.getElementById('environment-parent');
     const idInput = document.getElementById('environment-id');
 
+    parentSelect.innerHTML = '<option value="">None</option>';
+    environments.filter(e => e.id !== envId).forEach(e => {
+        const option = document.createElement('option');
+        option.value = e.id;
+        option.textContent = e.name;
+        parentSelect.appendChild(option);
+    });
+
     if (envId) {
         // Edit mode
         const env = environments.find(e => e.id === envId);
@@ -1168,6 +1377,9 @@ function openEnvironmentModal(envId = null) {
             } catch (e) {
                 varsInput.value = env.variables;
             }
+            secretsInput.value = (env.secrets || []).join(', ');
+            disabledInput.value = (env.disabled || []).join(', ');
+            parentSelect.value = env.parent_id || '';
             idInput.value = env.id;
             // Hide sample when editing (textarea has content)
             const sample = document.getElementById('env-variables-sample');
@@ -1178,6 +1390,9 @@ function openEnvironmentModal(envId = null) {
         title.textContent = 'New Environment';
         nameInput.value = '';
         varsInput.value = '';
+        secretsInput.value = '';
+        disabledInput.value = '';
+        parentSelect.value = '';
         idInput.value = '';
     }
 
@@ -1191,10 +1406,16 @@ async function saveEnvironment() {
     const idInput = document.getElementById('environment-id');
     const nameInput = document.getElementById('environment-name');
     const varsInput = document.getElementById('environment-variables');
+    const secretsInput = document.getElementById('environment-secrets');
+    const disabledInput = document.getElementById('environment-disabled');
+    const parentSelect = document.getElementById('environment-parent');
 
     const envId = idInput.value ? parseInt(idInput.value) : null;
     const name = nameInput.value.trim();
     let variables = varsInput.value.trim();
+    const secrets = secretsInput.value.split(',').map(s => s.trim()).filter(s => s);
+    const disabled = disabledInput.value.split(',').map(s => s.trim()).filter(s => s);
+    const parentId = parentSelect.value ? parseInt(parentSelect.value) : null;
 
     if (!name) {
         showNotification('Please enter an environment name', 'error');
@@ -1226,14 +1447,14 @@ async function saveEnvironment() {
             response = await fetch(`/api/environments/${envId}`, {
                 method: 'PUT',
                 headers: { 'Content-Type': 'application/json' },
-                body: JSON.stringify({ name: name, variables: variables })
+                body: JSON.stringify({ name: name, variables: variables, secrets: secrets, disabled: disabled, parent_id: parentId })
             });
         } else {
             // Create new
             response = await fetch('/api/environments', {
                 method: 'POST',
                 headers: { 'Content-Type': 'application/json' },
-                body: JSON.stringify({ name: name, variables: variables })
+                body: JSON.stringify({ name: name, variables: variables, secrets: secrets, disabled: disabled, parent_id: parentId })
             });
         }
 
@@ -1252,6 +1473,141 @@ async function saveEnvironment() {
     }
 }
 
+// Global Variables Modal Functions
+async function openGlobalsModal() {
+    const textarea = document.getElementById('globals-variables');
+    try {
+        const response = await fetch('/api/globals');
+        const globals = await response.json();
+        textarea.value = Object.keys(globals).length ? JSON.stringify(globals, null, 4) : '';
+    } catch (error) {
+        console.error('Error loading global variables:', error);
+        textarea.value = '';
+    }
+    openModal('globals-modal');
+}
+
+async function saveGlobals() {
+    const text = document.getElementById('globals-variables').value.trim();
+    let globals = {};
+    if (text) {
+        try {
+            globals = JSON.parse(text);
+        } catch (e) {
+            showNotification('Invalid JSON format for variables. Please check your syntax.', 'error');
+            return;
+        }
+    }
+    // Values are stored as strings
+    Object.keys(globals).forEach(key => {
+        if (typeof globals[key] !== 'string') globals[key] = JSON.stringify(globals[key]);
+    });
+
+    try {
+        const response = await fetch('/api/globals', {
+            method: 'PUT',
+            headers: { 'Content-Type': 'application/json' },
+            body: JSON.stringify(globals)
+        });
+        if (response.ok) {
+            closeModal('globals-modal');
+            showNotification('Global variables saved');
+        } else {
+            const error = await response.text();
+            showNotification(`Error: ${error}`, 'error');
+        }
+    } catch (error) {
+        console.error('Error saving global variables:', error);
+        showNotification('Failed to save global variables. Please try again.', 'error');
+    }
+}
+
+// Recording Proxy Functions
+async function openRecordingsModal() {
+    await renderRecordings();
+    openModal('recordings-modal');
+}
+
+async function renderRecordings() {
+    const list = document.getElementById('recordings-list');
+    try {
+        const response = await fetch('/api/recordings');
+        const recordings = await response.json();
+        const rows = await Promise.all(recordings.map(async (recording) => {
+            const exchanges = await (await fetch(`/api/recordings/${recording.id}/exchanges`)).json();
+            const proxyUrl = `${window.location.origin}/api/record/${recording.id}`;
+            return `
+                <div style="border: 1px solid var(--border-color); border-radius: 6px; padding: 10px; margin-bottom: 8px;">
+                    <div style="display: flex; justify-content: space-between; font-weight: 600;">
+                        <span>${escapeHtml(recording.name)}${recording.recording ? '' : ' (paused)'}</span>
+                        <span>${exchanges.length} recorded</span>
+                    </div>
+                    <div style="font-size: 12px; color: var(--text-secondary); margin: 4px 0 8px;">
+                        <code>${escapeHtml(proxyUrl)}</code> &rarr; ${escapeHtml(recording.target_url)}
+                    </div>
+                    <div style="display: flex; gap: 8px;">
+                        <button class="btn btn-outline" onclick="toggleRecording(${recording.id}, ${!recording.recording})">${recording.recording ? 'Pause' : 'Resume'}</button>
+                        <button class="btn btn-primary" onclick="saveRecording(${recording.id})" ${exchanges.length ? '' : 'disabled'}>Save as Requests</button>
+                        <button class="btn btn-outline" style="color: var(--danger);" onclick="deleteRecording(${recording.id})">Delete</button>
+                    </div>
+                </div>`;
+        }));
+        list.innerHTML = rows.join('');
+    } catch (error) {
+        console.error('Error loading recordings:', error);
+        list.innerHTML = '';
+    }
+}
+
+async function createRecording() {
+    const name = document.getElementById('recording-name').value.trim();
+    const targetUrl = document.getElementById('recording-target').value.trim();
+    const response = await fetch('/api/recordings', {
+        method: 'POST',
+        headers: { 'Content-Type': 'application/json' },
+        body: JSON.stringify({ name, target_url: targetUrl })
+    });
+    if (response.ok) {
+        document.getElementById('recording-name').value = '';
+        document.getElementById('recording-target').value = '';
+        await renderRecordings();
+    } else {
+        showNotification(await response.text(), 'error');
+    }
+}
+
+async function toggleRecording(id, recording) {
+    await fetch(`/api/recordings/${id}`, {
+        method: 'PUT',
+        headers: { 'Content-Type': 'application/json' },
+        body: JSON.stringify({ recording })
+    });
+    await renderRecordings();
+}
+
+async function saveRecording(id) {
+    const response = await fetch(`/api/recordings/${id}/save`, {
+        method: 'POST',
+        headers: { 'Content-Type': 'application/json' },
+        body: '{}'
+    });
+    if (response.ok) {
+        const saved = await response.json();
+        await loadFolders();
+        showNotification(`Saved ${saved.request_ids.length} request(s)`);
+    } else {
+        showNotification(await response.text(), 'error');
+    }
+}
+
+async function deleteRecording(id) {
+    if (!confirm('Delete this recording and everything it captured?')) {
+        return;
+    }
+    await fetch(`/api/recordings/${id}`, { method: 'DELETE' });
+    await renderRecordings();
+}
+
 // Notification function
 function showNotification(message, type = 'success') {
     // Simple notification - can be enhanced with a toast library
@@ -1394,8 +1750,202 @@ function setupHeaderManagement() {
     });
 }
 
+// Setup capture rule management
+function setupCaptureManagement() {
+    const tbody = document.getElementById('captures-tbody');
+    if (!tbody) return;
+
+    document.getElementById('add-capture-btn')?.addEventListener('click', () => {
+        addCaptureRow({ variable: '', source: 'body', expression: '' });
+    });
+    tbody.addEventListener('click', (e) => {
+        if (e.target.closest('.header-remove')) {
+            e.target.closest('tr')?.remove();
+        }
+    });
+}
+
+// Add capture rule row
+function addCaptureRow(rule) {
+    const tbody = document.getElementById('captures-tbody');
+    const row = document.createElement('tr');
+    row.className = 'capture-row';
+    const sources = ['body', 'header', 'status', 'xpath', 'regex'];
+    const placeholders = {
+        body: '$.data.token',
+        header: 'Location',
+        status: '',
+        xpath: '//order/@id',
+        regex: 'token=(\\w+)'
+    };
+    row.innerHTML = `
+        <td>
+            <input type="text" class="header-input capture-variable" value="${escapeHtml(rule.variable || '')}" placeholder="Variable">
+        </td>
+        <td>
+            <select class="form-select capture-source">
+                ${sources.map(source => `<option value="${source}" ${source === rule.source ? 'selected' : ''}>${source}</option>`).join('')}
+            </select>
+        </td>
+        <td>
+            <input type="text" class="header-input capture-expression" value="${escapeHtml(rule.expression || '')}" placeholder="${escapeHtml(placeholders[rule.source] ?? '')}">
+        </td>
+        <td>
+            <button class="header-remove capture-try" type="button" title="Try on the last response">
+                <i class="fas fa-vial"></i>
+            </button>
+            <button class="header-remove" type="button">
+                <i class="fas fa-times"></i>
+            </button>
+        </td>
+    `;
+    row.querySelector('.capture-source').addEventListener('change', (e) => {
+        row.querySelector('.capture-expression').placeholder = placeholders[e.target.value] ?? '';
+    });
+    row.querySelector('.capture-try').addEventListener('click', (e) => {
+        e.stopPropagation();
+        tryCaptureRule(row);
+    });
+    tbody.appendChild(row);
+}
+
+// Run a capture rule's expression against the last response body
+async function tryCaptureRule(row) {
+    const source = row.querySelector('.capture-source').value;
+    const expression = row.querySelector('.capture-expression').value;
+    if (lastResponseBody === null) {
+        showNotification('Send the request first to try the expression', 'error');
+        return;
+    }
+    try {
+        const response = await fetch('/api/tools/extract', {
+            method: 'POST',
+            headers: { 'Content-Type': 'application/json' },
+            body: JSON.stringify({ source, expression, body: lastResponseBody })
+        });
+        if (!response.ok) {
+            showNotification(await response.text(), 'error');
+            return;
+        }
+        const result = await response.json();
+        if (!result.matches.length) {
+            showNotification('No matches', 'error');
+        } else {
+            showNotification(`${result.matches.length} match(es), captures: ${result.captured}`);
+        }
+    } catch (error) {
+        showNotification('Failed to try the expression', 'error');
+    }
+}
+
+// Fill the capture table from a request's captures JSON
+function loadCaptureRows(captures) {
+    const tbody = document.getElementById('captures-tbody');
+    if (!tbody) return;
+    tbody.innerHTML = '';
+    let rules = [];
+    try {
+        rules = captures ? JSON.parse(captures) : [];
+    } catch (error) {
+        console.error('Invalid capture rules:', error);
+    }
+    rules.forEach(rule => addCaptureRow(rule));
+}
+
+// Get capture rules from the table, an empty string when there are none
+function getCaptures() {
+    const rules = [];
+    document.querySelectorAll('.capture-row').forEach(row => {
+        const variable = row.querySelector('.capture-variable').value.trim();
+        if (variable) {
+            rules.push({
+                variable,
+                source: row.querySelector('.capture-source').value,
+                expression: row.querySelector('.capture-expression').value.trim()
+            });
+        }
+    });
+    return rules.length ? JSON.stringify(rules) : '';
+}
+
+// Setup form body row management
+function setupFormBodyManagement() {
+    const tbody = document.getElementById('form-body-tbody');
+    if (!tbody) return;
+
+    document.getElementById('add-form-row-btn')?.addEventListener('click', () => {
+        addFormRow({ key: '', value: '', enabled: true });
+    });
+    tbody.addEventListener('click', (e) => {
+        if (e.target.closest('.header-remove')) {
+            e.target.closest('tr')?.remove();
+        }
+    });
+}
+
+// Add form body row
+function addFormRow(row) {
+    const tbody = document.getElementById('form-body-tbody');
+    const tr = document.createElement('tr');
+    tr.className = 'form-row';
+    tr.innerHTML = `
+        <td>
+            <input type="checkbox" class="header-checkbox form-row-enabled" ${row.enabled !== false ? 'checked' : ''}>
+        </td>
+        <td>
+            <input type="text" class="header-input form-row-key" value="${escapeHtml(row.key || '')}" placeholder="Key">
+        </td>
+        <td>
+            <input type="text" class="header-input form-row-value" value="${escapeHtml(row.value || '')}" placeholder="Value">
+        </td>
+        <td>
+            <button class="header-remove" type="button">
+                <i class="fas fa-times"></i>
+            </button>
+        </td>
+    `;
+    tbody.appendChild(tr);
+}
+
+// Fill the form body table from a request's rows, or an object saved
+// before rows existed
+function loadFormRows(content) {
+    const tbody = document.getElementById('form-body-tbody');
+    if (!tbody) return;
+    tbody.innerHTML = '';
+    let rows = [];
+    try {
+        const parsed = content ? JSON.parse(content) : [];
+        rows = Array.isArray(parsed)
+            ? parsed
+            : Object.entries(parsed).map(([key, value]) => ({ key, value: String(value), enabled: true }));
+    } catch (error) {
+        console.error('Invalid form body:', error);
+    }
+    rows.forEach(row => addFormRow(row));
+    if (!rows.length) {
+        addFormRow({ key: '', value: '', enabled: true });
+    }
+}
+
+// Get the form body rows as JSON, null when there are none
+function getFormRows() {
+    const rows = [];
+    document.querySelectorAll('.form-row').forEach(row => {
+        const key = row.querySelector('.form-row-key').value.trim();
+        if (key) {
+            rows.push({
+                key,
+                value: row.querySelector('.form-row-value').value,
+                enabled: row.querySelector('.form-row-enabled').checked
+            });
+        }
+    });
+    return rows.length ? JSON.stringify(rows) : null;
+}
+
 // Add header row
-function addHeaderRow(key, value) {
+function addHeaderRow(key, value, enabled = true) {
     const tbody = document.getElementById('headers-tbody');
     const row = document.createElement('tr');
     row.className = 'header-row';
@@ -1404,7 +1954,7 @@ function addHeaderRow(key, value) {
     }
     row.innerHTML = `
         <td>
-            <input type="checkbox" class="header-checkbox" ${key ? 'checked' : ''}>
+            <input type="checkbox" class="header-checkbox" ${key && enabled ? 'checked' : ''}>
         </td>
         <td>
             <input type="text" class="header-input" value="${escapeHtml(key)}" placeholder="Key">
@@ -1437,6 +1987,22 @@ function getHeaders() {
     return headers;
 }
 
+// Get every header row, unchecked ones included, for saving
+function getHeaderRows() {
+    const rows = [];
+    document.querySelectorAll('.header-row').forEach(row => {
+        const key = row.querySelector('input[placeholder="Key"]').value;
+        if (key) {
+            rows.push({
+                key,
+                value: row.querySelector('input[placeholder="Value"]').value,
+                enabled: row.querySelector('.header-checkbox').checked
+            });
+        }
+    });
+    return rows;
+}
+
 // Load environments
 async function loadEnvironments() {
     try {
@@ -1613,6 +2179,9 @@ async function sendRequest() {
         if (env) {
             try {
                 const variables = JSON.parse(env.variables);
+                // Secret values are masked here; the server substitutes them
+                (env.secrets || []).forEach(name => delete variables[name]);
+                (env.disabled || []).forEach(name => delete variables[name]);
                 url = substituteVariables(url, variables);
                 Object.keys(headers).forEach(key => {
                     headers[key] = substituteVariables(headers[key], variables);
@@ -1757,6 +2326,9 @@ function displayResponse(result) {
     displayTimeline(result, responseTime);
 
     // Check if response is HTML and show preview button
+    lastResponseUrl = result.request_url || null;
+    lastHistoryId = result.history_id || null;
+    lastResponseBody = result.body ?? null;
     const contentType = result.headers['content-type'] || result.headers['Content-Type'] || '';
     checkForHtmlResponse(contentType, responseBody);
 }
@@ -1795,6 +2367,51 @@ function displayTimeline(result, totalTime) {
                     <span>Total Time:</span>
                     <span>${totalTime}ms</span>
                 </div>
+                ${result.raw_size !== undefined && result.raw_size !== result.decoded_size ? `
+                <div style="display: flex; justify-content: space-between; margin-top: 8px; color: var(--text-secondary);">
+                    <span>Body Size:</span>
+                    <span>${result.raw_size} B compressed, ${result.decoded_size} B decoded</span>
+                </div>` : ''}
+                ${result.test_results && result.test_results.length ? `
+                <div style="margin-top: 16px; color: var(--text-secondary);">
+                    <div style="font-weight: 600; margin-bottom: 8px;">Tests: ${result.test_results.filter(t => t.passed).length}/${result.test_results.length} passed</div>
+                    ${result.test_results.map(t => `
+                    <div style="color: ${t.passed ? '#22c55e' : '#ef4444'};">${t.passed ? '✓' : '✗'} ${escapeHtml(t.name)}${t.error ? ` — ${escapeHtml(t.error)}` : ''}</div>`).join('')}
+                </div>` : ''}
+                ${result.assertion_results && result.assertion_results.length ? `
+                <div style="margin-top: 16px; color: var(--text-secondary);">
+                    <div style="font-weight: 600; margin-bottom: 8px;">Assertions: ${result.assertion_results.filter(a => a.passed).length}/${result.assertion_results.length} passed</div>
+                    ${result.assertion_results.map(a => `
+                    <div style="color: ${a.passed ? '#22c55e' : '#ef4444'};">${a.passed ? '✓' : '✗'} ${escapeHtml(a.name)}${a.error ? ` — ${escapeHtml(a.error)}` : ''}</div>`).join('')}
+                </div>` : ''}
+                ${result.unresolved && result.unresolved.length ? `
+                <div style="margin-top: 16px; color: #f59e0b;">
+                    Sent with unresolved variables: ${result.unresolved.map(escapeHtml).join(', ')}
+                </div>` : ''}
+                ${result.attempts && result.attempts.length > 1 ? `
+                <div style="margin-top: 16px; color: var(--text-secondary);">
+                    <div style="font-weight: 600; margin-bottom: 8px;">Attempts: ${result.attempts.length}</div>
+                    ${result.attempts.map(a => `
+                    <div>#${a.attempt}: ${a.status !== null ? a.status : escapeHtml(a.error || 'No response')} (${a.duration}ms)</div>`).join('')}
+                </div>` : ''}
+                ${result.callback ? `
+                <div style="margin-top: 16px; color: var(--text-secondary);">
+                    <div style="font-weight: 600; margin-bottom: 8px;">Callback on bin ${escapeHtml(result.callback.bin)}${result.callback.correlation_id ? ` for ${escapeHtml(result.callback.correlation_id)}` : ''} (waited ${result.callback.waited_ms}ms):</div>
+                    ${result.callback.delivery ? `
+                    <div>${escapeHtml(result.callback.delivery.method)} at ${escapeHtml(result.callback.delivery.received_at)}</div>
+                    <pre style="margin: 0;">${escapeHtml(result.callback.delivery.body)}</pre>` : `
+                    <div style="color: #ef4444;">${escapeHtml(result.callback.error || 'No delivery')}</div>`}
+                </div>` : ''}
+                ${result.script_logs && result.script_logs.length ? `
+                <div style="margin-top: 16px; color: var(--text-secondary);">
+                    <div style="font-weight: 600; margin-bottom: 8px;">Script Output:</div>
+                    <pre style="margin: 0;">${escapeHtml(result.script_logs.join('\n'))}</pre>
+                </div>` : ''}
+                ${result.remote_addr ? `
+                <div style="display: flex; justify-content: space-between; color: var(--text-secondary); margin-top: 8px;">
+                    <span>Remote Address:</span>
+                    <span>${escapeHtml(result.remote_addr)}</span>
+                </div>` : ''}
             </div>
         </div>
     `;
@@ -1844,7 +2461,7 @@ async function saveRequest() {
     const method = document.getElementById('request-method').value;
     const url = document.getElementById('request-url').value;
     const body = document.getElementById('request-body').value;
-    const headers = JSON.stringify(getHeaders());
+    const headers = JSON.stringify(getHeaderRows());
     const requestTypeSelect = document.getElementById('request-type');
     const bodyTypeSelect = document.getElementById('body-type-select');
     const authTypeSelect = document.getElementById('auth-type-select');
@@ -1854,13 +2471,16 @@ async function saveRequest() {
 
     // Get body type and content
     const bodyType = bodyTypeSelect ? bodyTypeSelect.value : 'none';
-    const bodyContent = (bodyType !== 'none' && body) ? body : null;
+    const bodyContent = bodyType === 'form'
+        ? getFormRows()
+        : (bodyType !== 'none' && body) ? body : null;
 
     // Get auth data
     const authType = authTypeSelect ? authTypeSelect.value : 'none';
     let authToken = null;
     let authUsername = null;
     let authPassword = null;
+    let authConfig = null;
 
     if (authType === 'bearer') {
         const tokenInput = document.getElementById('auth-bearer-token');
@@ -1870,6 +2490,41 @@ async function saveRequest() {
         const passwordInput = document.getElementById('auth-basic-password');
         authUsername = usernameInput ? usernameInput.value : null;
         authPassword = passwordInput ? passwordInput.value : null;
+    } else if (authType === 'aws_sigv4') {
+        authUsername = document.getElementById('auth-aws-access-key').value;
+        authPassword = document.getElementById('auth-aws-secret-key').value;
+        authToken = document.getElementById('auth-aws-session-token').value || null;
+        authConfig = JSON.stringify({
+            region: document.getElementById('auth-aws-region').value,
+            service: document.getElementById('auth-aws-service').value
+        });
+    } else if (authType === 'jwt') {
+        authPassword = document.getElementById('auth-jwt-key').value;
+        const claims = document.getElementById('auth-jwt-claims').value.trim();
+        const expiresIn = document.getElementById('auth-jwt-expires-in').value;
+        try {
+            authConfig = JSON.stringify({
+                algorithm: document.getElementById('auth-jwt-algorithm').value,
+                claims: claims ? JSON.parse(claims) : {},
+                expires_in: expiresIn ? parseInt(expiresIn, 10) : null
+            });
+        } catch (error) {
+            showNotification(`Invalid JWT claims: ${error.message}`, 'error');
+            return;
+        }
+    } else if (authType === 'chained') {
+        const tokenRequestId = document.getElementById('auth-chained-request').value;
+        const tokenPath = document.getElementById('auth-chained-path').value.trim();
+        const ttl = document.getElementById('auth-chained-ttl').value;
+        if (!tokenRequestId) {
+            showNotification('Choose the request that returns the token', 'error');
+            return;
+        }
+        authConfig = JSON.stringify({
+            request_id: parseInt(tokenRequestId, 10),
+            token_path: tokenPath || undefined,
+            ttl: ttl !== '' ? parseInt(ttl, 10) : null
+        });
     }
 
     try {
@@ -1888,7 +2543,11 @@ async function saveRequest() {
                 auth_type: authType,
                 auth_token: authToken,
                 auth_username: authUsername,
-                auth_password: authPassword
+                auth_password: authPassword,
+                auth_config: authConfig,
+                captures: getCaptures(),
+                pre_request_script: document.getElementById('pre-request-script')?.value ?? null,
+                test_script: document.getElementById('test-script')?.value ?? null
             })
         });
 
@@ -2060,6 +2719,20 @@ function copyResponse() {
     });
 }
 
+// Download the stored body of the last response, keeping binary content intact
+function downloadResponse() {
+    if (!lastHistoryId) {
+        showNotification('No response to save', 'error');
+        return;
+    }
+    const link = document.createElement('a');
+    link.href = `/api/history/${lastHistoryId}/download`;
+    link.download = '';
+    document.body.appendChild(link);
+    link.click();
+    link.remove();
+}
+
 // Format response
 function formatResponse() {
     const content = document.getElementById('response-body-content').textContent;
@@ -2306,6 +2979,7 @@ async function archiveEnvironment(envId) {
                 const envActions = document.getElementById('environment-actions');
                 if (envActions) {
                     envActions.style.display = 'none';
+                    document.getElementById('environment-transfer-actions').style.display = 'none';
                 }
                 // Reset environment selector
                 const envSelect = document.getElementById('environment-select');
@@ -2340,6 +3014,26 @@ async function unarchiveEnvironment(envId) {
     }
 }
 
+async function importDotenv(envId, file) {
+    try {
+        const response = await fetch(`/api/environments/${envId}/dotenv`, {
+            method: 'POST',
+            headers: { 'Content-Type': 'text/plain' },
+            body: await file.text()
+        });
+        if (response.ok) {
+            await loadEnvironments();
+            updateVariablePreview();
+            showNotification(`Imported variables from ${file.name}`);
+        } else {
+            showNotification(await response.text(), 'error');
+        }
+    } catch (error) {
+        console.error('Error importing .env file:', error);
+        showNotification('Failed to import .env file', 'error');
+    }
+}
+
 async function deleteEnvironment(envId) {
     if (!confirm('Are you sure you want to permanently delete this environment? This action cannot be undone.')) {
         return;
@@ -2358,6 +3052,7 @@ async function deleteEnvironment(envId) {
                 const envActions = document.getElementById('environment-actions');
                 if (envActions) {
                     envActions.style.display = 'none';
+                    document.getElementById('environment-transfer-actions').style.display = 'none';
                 }
                 // Reset environment selector
                 const envSelect = document.getElementById('environment-select');
@@ -2528,8 +3223,36 @@ function setupPanelResizer() {
     });
 }
 
+// Select a method, adding an option for extension methods such as PROPFIND
+function setMethodValue(select, method) {
+    if (![...select.options].some(option => option.value === method)) {
+        const option = new Option(method, method);
+        select.insertBefore(option, select.querySelector('option[value="__custom__"]'));
+    }
+    select.value = method;
+    select.dataset.previous = method;
+}
+
+// Ask for a method when "Custom…" is picked; false when the choice was cancelled
+function chooseCustomMethod(select) {
+    if (select.value !== '__custom__') {
+        select.dataset.previous = select.value;
+        return true;
+    }
+    const method = (prompt('HTTP method, e.g. PROPFIND or PURGE') || '').trim();
+    // RFC 7230 token characters, as the server checks
+    if (!/^[A-Za-z0-9!#$%&'*+\-.^_`|~]+$/.test(method)) {
+        if (method) showNotification('Invalid HTTP method', 'error');
+        select.value = select.dataset.previous || 'GET';
+        return false;
+    }
+    setMethodValue(select, method);
+    return true;
+}
+
 // WebSocket Functions
 function handleMethodChange(e) {
+    if (!chooseCustomMethod(e.target)) return;
     const method = e.target.value;
     const sendBtn = document.getElementById('send-request-btn');
     const wsConnectBtn = document.getElementById('ws-connect-btn');
@@ -2579,27 +3302,32 @@ function updateWsButtonVisibility() {
     const wsConnectBtn = document.getElementById('ws-connect-btn');
     const wsDisconnectBtn = document.getElementById('ws-disconnect-btn');
 
+    // Connect stays available to open further connections
+    wsConnectBtn.classList.remove('hidden');
     if (wsConnected) {
-        wsConnectBtn.classList.add('hidden');
         wsDisconnectBtn.classList.remove('hidden');
     } else {
-        wsConnectBtn.classList.remove('hidden');
         wsDisconnectBtn.classList.add('hidden');
     }
 }
 
 function updateWsStatus(status, message) {
-    const indicator = document.querySelector('.ws-status-indicator');
+    const indicator = document.querySelector('#ws-status .ws-status-indicator');
     const statusText = document.getElementById('ws-status-text');
     const sendBtn = document.getElementById('ws-send-btn');
+    const connectedOnly = ['ws-ping-btn', 'ws-repeat-btn', 'ws-stop-repeat-btn']
+        .map(id => document.getElementById(id));
 
     indicator.className = 'ws-status-indicator ' + status;
     statusText.textContent = message;
 
     if (status === 'connected') {
         sendBtn.disabled = false;
+        connectedOnly.forEach(btn => btn.disabled = false);
     } else {
         sendBtn.disabled = true;
+        connectedOnly.forEach(btn => btn.disabled = true);
+        document.getElementById('ws-rtt').textContent = '';
     }
 }
 
@@ -2663,11 +3391,19 @@ function handleAuthTypeChange(e) {
     const noneSection = document.getElementById('auth-none-section');
     const bearerSection = document.getElementById('auth-bearer-section');
     const basicSection = document.getElementById('auth-basic-section');
+    const oauth2Section = document.getElementById('auth-oauth2-section');
+    const awsSection = document.getElementById('auth-aws-section');
+    const jwtSection = document.getElementById('auth-jwt-section');
+    const chainedSection = document.getElementById('auth-chained-section');
 
     // Hide all sections
     if (noneSection) noneSection.classList.add('hidden');
     if (bearerSection) bearerSection.classList.add('hidden');
     if (basicSection) basicSection.classList.add('hidden');
+    if (oauth2Section) oauth2Section.classList.add('hidden');
+    if (awsSection) awsSection.classList.add('hidden');
+    if (jwtSection) jwtSection.classList.add('hidden');
+    if (chainedSection) chainedSection.classList.add('hidden');
 
     // Show selected section
     switch (authType) {
@@ -2680,9 +3416,33 @@ function handleAuthTypeChange(e) {
         case 'basic':
             if (basicSection) basicSection.classList.remove('hidden');
             break;
+        case 'oauth2':
+            if (oauth2Section) oauth2Section.classList.remove('hidden');
+            break;
+        case 'aws_sigv4':
+            if (awsSection) awsSection.classList.remove('hidden');
+            break;
+        case 'jwt':
+            if (jwtSection) jwtSection.classList.remove('hidden');
+            break;
+        case 'chained':
+            if (chainedSection) chainedSection.classList.remove('hidden');
+            fillTokenRequestOptions(currentRequestId, document.getElementById('auth-chained-request').value);
+            break;
     }
 }
 
+// List the other saved requests as candidates for the token request
+function fillTokenRequestOptions(ownId, selectedId) {
+    const select = document.getElementById('auth-chained-request');
+    if (!select) return;
+    select.innerHTML = '<option value="">Select a request…</option>' + requests
+        .filter(r => r.id !== ownId && r.request_type !== 'ws')
+        .map(r => `<option value="${r.id}">${escapeHtml(r.method)} ${escapeHtml(r.name)}</option>`)
+        .join('');
+    select.value = selectedId ? String(selectedId) : '';
+}
+
 // Handle body type change
 function handleBodyTypeChange(e) {
     const bodyType = e.target.value;
@@ -2698,6 +3458,13 @@ function handleBodyTypeChange(e) {
         }
     }
 
+    // Form bodies are edited as rows instead of text
+    bodyTextarea?.classList.toggle('hidden', bodyType === 'form');
+    document.getElementById('form-body-editor')?.classList.toggle('hidden', bodyType !== 'form');
+    if (bodyType === 'form' && !document.querySelector('.form-row')) {
+        addFormRow({ key: '', value: '', enabled: true });
+    }
+
     // Update placeholder based on body type
     if (bodyTextarea) {
         switch (bodyType) {
@@ -2718,8 +3485,7 @@ function handleBodyTypeChange(e) {
                 bodyTextarea.disabled = false;
                 break;
             case 'form':
-                bodyTextarea.placeholder = 'key1=value1&key2=value2';
-                bodyTextarea.disabled = false;
+                bodyTextarea.disabled = true;
                 break;
             case 'multipart':
                 bodyTextarea.placeholder = 'Multipart form data (use key=value format)';
@@ -2754,7 +3520,7 @@ function formatRequestBody() {
 }
 
 // Toggle HTML preview
-function toggleHtmlPreview() {
+async function toggleHtmlPreview() {
     const previewTab = document.getElementById('response-preview-tab');
     const previewPane = document.getElementById('response-preview');
     const previewFrame = document.getElementById('html-preview-frame');
@@ -2768,10 +3534,24 @@ function toggleHtmlPreview() {
     // Switch to preview tab
     switchResponseTab('response-preview');
 
-    // Render HTML in iframe
+    // Render sanitized HTML in the sandboxed iframe; Save still downloads the original body
     if (responseBody && responseBody.textContent) {
-        const htmlContent = responseBody.textContent;
-        previewFrame.srcdoc = htmlContent;
+        try {
+            const response = await fetch('/api/sanitize/html', {
+                method: 'POST',
+                headers: { 'Content-Type': 'application/json' },
+                body: JSON.stringify({ html: responseBody.textContent, base_url: lastResponseUrl })
+            });
+            if (!response.ok) {
+                throw new Error(await response.text());
+            }
+            const result = await response.json();
+            previewFrame.srcdoc = result.html;
+        } catch (error) {
+            console.error('Error sanitizing HTML preview:', error);
+            previewFrame.srcdoc = '';
+            showNotification(`Could not render preview: ${error.message}`, 'error');
+        }
     }
 }
 
@@ -2886,8 +3666,70 @@ function getContentTypeHeader() {
 // Store current WebSocket request details for history
 let currentWsRequestDetails = null;
 
-// FIX: Added cleanup for existing connections and auth/headers support
-function connectWebSocket() {
+// Opens the bridge socket to the backend once; every remote connection of
+// this page is multiplexed over it by connection id.
+function ensureWsBridge() {
+    if (wsBridgeReady) return wsBridgeReady;
+
+    wsBridgeReady = new Promise((resolve, reject) => {
+        // Forward the bridge token (if the UI was opened with ?token=...)
+        const bridgeToken = new URLSearchParams(window.location.search).get('token');
+        const wsScheme = window.location.protocol === 'https:' ? 'wss' : 'ws';
+        const wsUrl = `${wsScheme}://${window.location.host}/api/ws` +
+            (bridgeToken ? `?token=${encodeURIComponent(bridgeToken)}` : '');
+        const bridge = new WebSocket(wsUrl);
+
+        bridge.onopen = () => resolve(bridge);
+
+        bridge.onmessage = (event) => {
+            try {
+                const msg = JSON.parse(event.data);
+                handleWsServerMessage(msg);
+            } catch (e) {
+                console.error('Failed to parse WebSocket message:', e);
+            }
+        };
+
+        bridge.onerror = (error) => {
+            console.error('WebSocket error:', error);
+        };
+
+        bridge.onclose = () => {
+            wsBridge = null;
+            wsBridgeReady = null;
+            reject(new Error('Connection error'));
+            // Every remote connection went down with the bridge
+            wsConnections.forEach((connection, id) => {
+                if (connection.status !== 'disconnected') {
+                    connection.status = 'disconnected';
+                    connection.reason = 'Connection error';
+                    addWsMessage(id, 'error', 'Connection error');
+                }
+            });
+            renderWsConnections();
+            refreshWsView();
+        };
+
+        wsBridge = bridge;
+    });
+    return wsBridgeReady;
+}
+
+function sendToWsBridge(connectionId, message) {
+    if (!wsBridge || wsBridge.readyState !== WebSocket.OPEN) {
+        showNotification('WebSocket is not connected', 'error');
+        return false;
+    }
+    wsBridge.send(JSON.stringify({ connection_id: connectionId, ...message }));
+    return true;
+}
+
+function activeWsConnection() {
+    return wsConnections.get(wsActiveConnectionId) || null;
+}
+
+// Opens another connection to the URL; existing ones stay open
+async function connectWebSocket() {
     const url = document.getElementById('request-url').value.trim();
 
     if (!url) {
@@ -2901,11 +3743,6 @@ function connectWebSocket() {
         return;
     }
 
-    // FIX: Close existing connection if any
-    if (wsConnection && wsConnection.readyState === WebSocket.OPEN) {
-        wsConnection.close();
-    }
-
     // Get headers
     const headers = getHeaders();
 
@@ -2940,93 +3777,254 @@ function connectWebSocket() {
         requestType: 'ws'
     };
 
-    updateWsStatus('connecting', 'Connecting...');
-
-    // Connect to our backend WebSocket proxy
-    const wsUrl = `ws://${window.location.host}/api/ws`;
-    wsConnection = new WebSocket(wsUrl);
-
-    wsConnection.onopen = () => {
-        // Send connect message to proxy with headers and auth
-        const connectMessage = {
-            type: 'connect',
-            url: url,
-            headers: Object.keys(headers).length > 0 ? headers : null,
-            auth_type: authType !== 'none' ? authType : null,
-            auth_token: authToken,
-            auth_username: authUsername,
-            auth_password: authPassword
-        };
-        wsConnection.send(JSON.stringify(connectMessage));
-    };
+    const keepaliveSeconds = parseFloat(document.getElementById('ws-keepalive')?.value) || 0;
 
-    wsConnection.onmessage = (event) => {
-        try {
-            const msg = JSON.parse(event.data);
-            handleWsServerMessage(msg);
-        } catch (e) {
-            console.error('Failed to parse WebSocket message:', e);
+    let tls;
+    let graphql = null;
+    try {
+        tls = await getWsTlsOptions();
+        if (document.getElementById('ws-graphql')?.checked) {
+            const params = document.getElementById('ws-graphql-params').value.trim();
+            graphql = { connection_params: params ? parseJsonField(params, 'Connection params') : null };
         }
-    };
+    } catch (e) {
+        showNotification(e.message, 'error');
+        return;
+    }
 
-    wsConnection.onerror = (error) => {
-        console.error('WebSocket error:', error);
-        updateWsStatus('disconnected', 'Connection error');
-        wsConnected = false;
-        updateWsButtonVisibility();
-        addWsMessage('error', 'Connection error');
-    };
+    const connectionId = `ws-${wsNextConnectionId++}`;
+    wsConnections.set(connectionId, {
+        url: url,
+        status: 'connecting',
+        reason: null,
+        rtt: null,
+        graphql: graphql !== null,
+        subscriptions: new Set(),
+        details: currentWsRequestDetails
+    });
+    wsActiveConnectionId = connectionId;
+    renderWsConnections();
+    refreshWsView();
 
-    wsConnection.onclose = () => {
-        updateWsStatus('disconnected', 'Disconnected');
-        wsConnected = false;
-        updateWsButtonVisibility();
+    try {
+        await ensureWsBridge();
+    } catch (e) {
+        // The bridge's close handler already marked the connection as failed
+        return;
+    }
+
+    // Send connect message to proxy with headers and auth
+    sendToWsBridge(connectionId, {
+        type: 'connect',
+        url: url,
+        headers: Object.keys(headers).length > 0 ? headers : null,
+        auth_type: authType !== 'none' ? authType : null,
+        auth_token: authToken,
+        auth_username: authUsername,
+        auth_password: authPassword,
+        binary_encoding: document.getElementById('ws-encoding')?.value === 'hex' ? 'hex' : 'base64',
+        keepalive_interval_ms: keepaliveSeconds > 0 ? Math.round(keepaliveSeconds * 1000) : null,
+        tls: tls,
+        graphql: graphql
+    });
+}
+
+function parseJsonField(text, name) {
+    try {
+        return JSON.parse(text);
+    } catch (e) {
+        throw new Error(`${name} must be valid JSON`);
+    }
+}
+
+// TLS settings for wss:// connections; the certificate files are sent inline
+async function getWsTlsOptions() {
+    const certFile = document.getElementById('ws-tls-cert')?.files[0];
+    const keyFile = document.getElementById('ws-tls-key')?.files[0];
+    if (!!certFile !== !!keyFile) {
+        throw new Error('Select both the client certificate and its key');
+    }
+    return {
+        insecure: document.getElementById('ws-tls-insecure')?.checked || false,
+        client_certificate: certFile ? await certFile.text() : null,
+        client_key: keyFile ? await keyFile.text() : null
     };
 }
 
 function handleWsServerMessage(msg) {
+    // Events without an id concern the bridge itself
+    const connectionId = msg.connection_id || wsActiveConnectionId;
+    const connection = wsConnections.get(connectionId);
+    if (!connection) return;
+
     switch (msg.type) {
         case 'connected':
-            wsConnected = true;
-            updateWsStatus('connected', `Connected to ${msg.url}`);
-            updateWsButtonVisibility();
-            addWsMessage('info', `Connected to ${msg.url}`);
+            connection.status = 'connected';
+            connection.url = msg.url;
+            connection.sessionId = msg.session_id || null;
+            addWsMessage(connectionId, 'info', `Connected to ${msg.url}`);
             // Add to execution history when successfully connected
-            if (currentWsRequestDetails) {
-                addToExecutionHistory(currentWsRequestDetails);
+            if (connection.details) {
+                addToExecutionHistory(connection.details);
                 // Save the WebSocket request if it has an ID
                 if (currentRequestId) {
                     saveRequest();
                 }
             }
             break;
-        case 'disconnected':
-            wsConnected = false;
-            updateWsStatus('disconnected', msg.reason || 'Disconnected');
-            updateWsButtonVisibility();
-            addWsMessage('info', msg.reason || 'Disconnected');
+        case 'disconnected': {
+            const reason = msg.reason || 'Disconnected';
+            connection.status = 'disconnected';
+            connection.reason = msg.code ? `${reason} (${msg.code})` : reason;
+            addWsMessage(connectionId, 'info', connection.reason);
             break;
+        }
         case 'message':
-            addWsMessage(msg.direction, msg.data);
+            addWsMessage(connectionId, msg.direction, msg.data, msg.encoding, msg.size);
             break;
         case 'error':
-            addWsMessage('error', msg.message);
+            // An error while connecting means the connection failed
+            if (connection.status === 'connecting') {
+                connection.status = 'disconnected';
+                connection.reason = 'Connection failed';
+            }
+            addWsMessage(connectionId, 'error', msg.message);
             break;
         case 'info':
-            addWsMessage('info', msg.message);
+            addWsMessage(connectionId, 'info', msg.message);
+            break;
+        case 'graphql_ack':
+            addWsMessage(connectionId, 'info', 'GraphQL connection acknowledged');
+            break;
+        case 'graphql_next':
+            addWsMessage(connectionId, 'received',
+                `[${msg.subscription_id}] ${JSON.stringify(msg.payload, null, 2)}`);
+            break;
+        case 'graphql_error':
+            connection.subscriptions.delete(msg.subscription_id);
+            addWsMessage(connectionId, 'error',
+                `[${msg.subscription_id}] ${JSON.stringify(msg.errors, null, 2)}`);
+            break;
+        case 'graphql_complete':
+            connection.subscriptions.delete(msg.subscription_id);
+            addWsMessage(connectionId, 'info', `Subscription ${msg.subscription_id} completed`);
+            break;
+        case 'pong':
+            connection.rtt = msg.rtt_ms;
+            // Keepalive pings would flood the log, they only update the status
+            if (!msg.keepalive) {
+                addWsMessage(connectionId, 'info', `Pong after ${msg.rtt_ms.toFixed(1)} ms`);
+            }
             break;
     }
+    renderWsConnections();
+    refreshWsView();
 }
 
-function disconnectWebSocket() {
-    if (wsConnection) {
-        wsConnection.send(JSON.stringify({ type: 'disconnect' }));
-        wsConnection.close();
-        wsConnection = null;
+// Shows the status, buttons and messages of the selected connection
+function refreshWsView() {
+    const connection = activeWsConnection();
+
+    if (!connection) {
+        wsConnected = false;
+        updateWsStatus('disconnected', 'Disconnected');
+    } else if (connection.status === 'connected') {
+        wsConnected = true;
+        updateWsStatus('connected', `Connected to ${connection.url}`);
+        if (connection.rtt !== null) {
+            document.getElementById('ws-rtt').textContent = `RTT ${connection.rtt.toFixed(1)} ms`;
+        }
+    } else if (connection.status === 'connecting') {
+        wsConnected = false;
+        updateWsStatus('connecting', 'Connecting...');
+    } else {
+        wsConnected = false;
+        updateWsStatus('disconnected', connection.reason || 'Disconnected');
     }
-    wsConnected = false;
-    updateWsStatus('disconnected', 'Disconnected');
     updateWsButtonVisibility();
+    renderGraphqlSubscriptions(connection);
+
+    document.querySelectorAll('#ws-messages .ws-message').forEach(messageDiv => {
+        messageDiv.hidden = messageDiv.dataset.connection !== wsActiveConnectionId;
+    });
+}
+
+function renderWsConnections() {
+    const container = document.getElementById('ws-connections');
+    container.innerHTML = '';
+    container.classList.toggle('hidden', wsConnections.size === 0);
+
+    wsConnections.forEach((connection, id) => {
+        let label = connection.url;
+        try {
+            const parsed = new URL(connection.url);
+            label = parsed.host + (parsed.pathname !== '/' ? parsed.pathname : '');
+        } catch (e) {
+            // Keep the URL as typed
+        }
+
+        const tab = document.createElement('div');
+        tab.className = 'ws-connection-tab' + (id === wsActiveConnectionId ? ' active' : '');
+        tab.title = connection.url;
+        tab.innerHTML = `
+            <span class="ws-status-indicator ${connection.status}"></span>
+            <span class="ws-connection-label">${escapeHtml(label)}</span>
+            <button class="ws-connection-close" title="Close connection"><i class="fas fa-times"></i></button>
+        `;
+        tab.addEventListener('click', () => {
+            wsActiveConnectionId = id;
+            renderWsConnections();
+            refreshWsView();
+        });
+        tab.querySelector('.ws-connection-close').addEventListener('click', (e) => {
+            e.stopPropagation();
+            closeWsConnection(id);
+        });
+        container.appendChild(tab);
+    });
+}
+
+// Disconnects a connection and drops it with its messages
+function closeWsConnection(connectionId) {
+    const connection = wsConnections.get(connectionId);
+    if (!connection) return;
+
+    if (connection.status !== 'disconnected' && wsBridge && wsBridge.readyState === WebSocket.OPEN) {
+        wsBridge.send(JSON.stringify({ connection_id: connectionId, type: 'disconnect' }));
+    }
+    wsConnections.delete(connectionId);
+    document.querySelectorAll(`#ws-messages .ws-message[data-connection="${connectionId}"]`)
+        .forEach(messageDiv => messageDiv.remove());
+
+    if (wsActiveConnectionId === connectionId) {
+        const remaining = Array.from(wsConnections.keys());
+        wsActiveConnectionId = remaining.length > 0 ? remaining[remaining.length - 1] : null;
+    }
+    renderWsConnections();
+    refreshWsView();
+}
+
+function pingWebSocket() {
+    if (!wsConnected) {
+        showNotification('WebSocket is not connected', 'error');
+        return;
+    }
+    sendToWsBridge(wsActiveConnectionId, { type: 'ping' });
+}
+
+function disconnectWebSocket() {
+    const connection = activeWsConnection();
+    if (!connection) return;
+
+    const code = parseInt(document.getElementById('ws-close-code')?.value, 10);
+    const reason = document.getElementById('ws-close-reason')?.value.trim();
+
+    // The bridge confirms with a disconnected event carrying the code
+    sendToWsBridge(wsActiveConnectionId, {
+        type: 'disconnect',
+        code: code || null,
+        reason: reason || null
+    });
 }
 
 function sendWebSocketMessage() {
@@ -3038,23 +4036,117 @@ function sendWebSocketMessage() {
         return;
     }
 
-    if (!wsConnection || wsConnection.readyState !== WebSocket.OPEN) {
+    if (!wsConnected) {
         showNotification('WebSocket is not connected', 'error');
         return;
     }
 
-    wsConnection.send(JSON.stringify({
+    const sent = sendToWsBridge(wsActiveConnectionId, {
         type: 'send',
-        message: message
-    }));
+        message: message,
+        encoding: document.getElementById('ws-encoding')?.value || 'text'
+    });
+
+    if (sent) {
+        messageInput.value = '';
+    }
+}
+
+// Subscriptions of GraphQL connections, with a stop button each
+function renderGraphqlSubscriptions(connection) {
+    const section = document.getElementById('ws-graphql-section');
+    const show = connection && connection.graphql && connection.status === 'connected';
+    section.classList.toggle('hidden', !show);
+    if (!show) return;
+
+    const container = document.getElementById('ws-subscriptions');
+    container.innerHTML = '';
+    connection.subscriptions.forEach(subscriptionId => {
+        const chip = document.createElement('div');
+        chip.className = 'ws-connection-tab';
+        chip.innerHTML = `
+            <span class="ws-connection-label">${escapeHtml(subscriptionId)}</span>
+            <button class="ws-connection-close" title="Stop subscription"><i class="fas fa-times"></i></button>
+        `;
+        chip.querySelector('.ws-connection-close').addEventListener('click', () => {
+            sendToWsBridge(wsActiveConnectionId, { type: 'complete', subscription_id: subscriptionId });
+        });
+        container.appendChild(chip);
+    });
+}
+
+function subscribeGraphql() {
+    const connection = activeWsConnection();
+    const query = document.getElementById('ws-graphql-query').value.trim();
+    const variablesText = document.getElementById('ws-graphql-variables').value.trim();
+
+    if (!connection || !connection.graphql || !wsConnected) {
+        showNotification('No GraphQL connection is open', 'error');
+        return;
+    }
+    if (!query) {
+        showNotification('Please enter a subscription query', 'error');
+        return;
+    }
+    let variables = null;
+    try {
+        variables = variablesText ? parseJsonField(variablesText, 'Variables') : null;
+    } catch (e) {
+        showNotification(e.message, 'error');
+        return;
+    }
+
+    const subscriptionId = `sub-${wsNextSubscriptionId++}`;
+    const sent = sendToWsBridge(wsActiveConnectionId, {
+        type: 'subscribe',
+        subscription_id: subscriptionId,
+        query: query,
+        variables: variables
+    });
+    if (sent) {
+        connection.subscriptions.add(subscriptionId);
+        addWsMessage(wsActiveConnectionId, 'sent', `[${subscriptionId}] ${query}`);
+        renderGraphqlSubscriptions(connection);
+    }
+}
+
+// The bridge sends the message itself, so timing does not depend on the browser
+function startWebSocketInterval() {
+    const message = document.getElementById('ws-message-input').value.trim();
+    const intervalMs = parseInt(document.getElementById('ws-interval').value, 10);
+
+    if (!message) {
+        showNotification('Please enter a message to send', 'error');
+        return;
+    }
+    if (!intervalMs || intervalMs < 10) {
+        showNotification('Please enter an interval of at least 10 ms', 'error');
+        return;
+    }
+    if (!wsConnected) {
+        showNotification('WebSocket is not connected', 'error');
+        return;
+    }
+
+    sendToWsBridge(wsActiveConnectionId, {
+        type: 'start_interval',
+        message: message,
+        encoding: document.getElementById('ws-encoding')?.value || 'text',
+        interval_ms: intervalMs
+    });
+}
 
-    messageInput.value = '';
+function stopWebSocketInterval() {
+    if (!wsConnected) return;
+    sendToWsBridge(wsActiveConnectionId, { type: 'stop_interval' });
 }
 
-function addWsMessage(type, content) {
+function addWsMessage(connectionId, type, content, encoding, size) {
     const messagesContainer = document.getElementById('ws-messages');
     const messageDiv = document.createElement('div');
     messageDiv.className = `ws-message ${type}`;
+    messageDiv.dataset.connection = connectionId;
+    messageDiv.hidden = connectionId !== wsActiveConnectionId;
 
     const timestamp = new Date().toLocaleTimeString();
     let directionClass = '';
@@ -3075,6 +4167,7 @@ function addWsMessage(type, content) {
     messageDiv.innerHTML = `
         <div class="ws-message-header">
             <span class="ws-message-direction ${directionClass}">${directionText}</span>
+            ${size !== undefined ? `<span class="ws-message-time">${encoding && encoding !== 'text' ? `binary, ${encoding}, ` : ''}${size} B</span>` : ''}
             <span class="ws-message-time">${timestamp}</span>
         </div>
         <div class="ws-message-content">${escapeHtml(content)}</div>
@@ -3084,9 +4177,29 @@ function addWsMessage(type, content) {
     messagesContainer.scrollTop = messagesContainer.scrollHeight;
 }
 
+// Downloads the recorded messages of the selected connection
+function exportWebSocketSession() {
+    const connection = activeWsConnection();
+    if (!connection || !connection.sessionId) {
+        showNotification('No recorded session to export', 'error');
+        return;
+    }
+    const format = document.getElementById('ws-export-format').value;
+    const link = document.createElement('a');
+    link.href = `/api/ws-history/sessions/${connection.sessionId}/export?format=${format}`;
+    link.download = '';
+    document.body.appendChild(link);
+    link.click();
+    link.remove();
+}
+
+// Clears the messages of the selected connection
 function clearWebSocketMessages() {
-    const messagesContainer = document.getElementById('ws-messages');
-    messagesContainer.innerHTML = '';
+    document.querySelectorAll('#ws-messages .ws-message').forEach(messageDiv => {
+        if (messageDiv.dataset.connection === wsActiveConnectionId) {
+            messageDiv.remove();
+        }
+    });
 }
 
 // Collapsible Sections
@@ -3215,6 +4328,14 @@ function setupImportHandler() {
             openModal('import-modal');
         });
 
+        const backupBtn = document.getElementById('backup-btn');
+        if (backupBtn) {
+            backupBtn.addEventListener('click', (e) => {
+                e.stopPropagation();
+                window.location.href = '/api/export/bundle.zip';
+            });
+        }
+
         importFileInput.addEventListener('change', (e) => {
             if (e.target.files.length > 0) {
                 handleImport(e.target.files[0]);
@@ -3242,7 +4363,10 @@ async function handleImport(file) {
         if (response.ok) {
             const result = await response.json();
             
-            if (result.collections && result.collections.length > 0) {
+            const collections = result.collections || [];
+            const environments = result.environments || [];
+
+            if (collections.length > 0 || environments.length > 0) {
                 // Store file for confirmation
                 pendingImportFile = file;
                 
@@ -3250,7 +4374,7 @@ async function handleImport(file) {
                 const listContainer = document.getElementById('import-preview-list');
                 listContainer.innerHTML = '';
                 
-                result.collections.forEach(col => {
+                collections.forEach(col => {
                     const item = document.createElement('div');
                     item.style.cssText = 'display: flex; justify-content: space-between; align-items: center; padding: 10px; border-bottom: 1px solid var(--border-color);';
                     item.innerHTML = `
@@ -3264,6 +4388,25 @@ async function handleImport(file) {
                     `;
                     listContainer.appendChild(item);
                 });
+
+                environments.forEach(env => {
+                    const item = document.createElement('div');
+                    item.style.cssText = 'display: flex; justify-content: space-between; align-items: center; padding: 10px; border-bottom: 1px solid var(--border-color);';
+                    item.innerHTML = `
+                        <div style="font-weight: 500; color: var(--text-primary);">
+                            <i class="fas fa-globe" style="color: var(--primary-blue); margin-right: 8px;"></i>
+                            ${escapeHtml(env.name)}
+                        </div>
+                        <div style="font-size: 12px; color: var(--text-secondary); background: var(--bg-secondary); padding: 2px 8px; border-radius: 10px;">
+                            ${env.variable_count} variables
+                        </div>
+                    `;
+                    listContainer.appendChild(item);
+                });
+
+                if (result.errors && result.errors.length > 0) {
+                    showNotification(`${result.errors.length} file(s) could not be parsed and will be skipped`, 'error');
+                }
                 
                 // Open confirmation modal
                 closeModal('import-modal'); // Ensure previous modal is closed
@@ -3272,7 +4415,7 @@ async function handleImport(file) {
                 showNotification('No valid collections/requests found in file', 'error');
             }
         } else {
-            const error = await response.text();
+            const error = await importErrorText(response);
             showNotification(`Import analysis failed: ${error}`, 'error');
         }
     } catch (error) {
@@ -3281,6 +4424,17 @@ async function handleImport(file) {
     }
 }
 
+// Failed imports answer with the per-file report, other errors with text
+async function importErrorText(response) {
+    const text = await response.text();
+    try {
+        const result = JSON.parse(text);
+        return (result.errors || []).join('; ') || text;
+    } catch (_) {
+        return text;
+    }
+}
+
 async function confirmImport() {
     if (!pendingImportFile) return;
     
@@ -3289,7 +4443,7 @@ async function confirmImport() {
     
     // Close modal immediately and show loading
     closeModal('import-confirmation-modal');
-    showNotification('Importing collections...', 'info');
+    showNotification(t('ui.importing', 'Importing collections...'), 'info');
     
     try {
         const response = await fetch('/api/import', {
@@ -3299,14 +4453,15 @@ async function confirmImport() {
 
         if (response.ok) {
             const result = await response.json();
-            showNotification(result.message || 'Import successful');
+            showNotification(result.message || t('ui.import_done', 'Import finished'));
             // Refresh UI
             await loadFolders();
             await loadRequests(null, showArchived);
+            await loadEnvironments();
             pendingImportFile = null;
         } else {
-            const error = await response.text();
-            showNotification(`Import failed: ${error}`, 'error');
+            const error = await importErrorText(response);
+            showNotification(`${t('ui.import_failed', 'Import failed')}: ${error}`, 'error');
         }
     } catch (error) {
         console.error('Error during import:', error);